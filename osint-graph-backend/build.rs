@@ -0,0 +1,42 @@
+//! Records when and from which commit the backend was built, for `/api/v1/build-info`
+
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    println!("cargo:rerun-if-env-changed=OSINT_GRAPH_GIT_SHA");
+    for git_file in ["../.git/HEAD", "../.git/refs/heads"] {
+        if std::path::Path::new(git_file).exists() {
+            println!("cargo:rerun-if-changed={git_file}");
+        }
+    }
+
+    // reproducible builds set SOURCE_DATE_EPOCH
+    let timestamp = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.trim().parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|since| since.as_secs())
+                .unwrap_or_default()
+        });
+    println!("cargo:rustc-env=OSINT_GRAPH_BUILD_TIMESTAMP={timestamp}");
+
+    // builds from a source tarball have no git, packagers can pass the commit in instead
+    let git_sha = std::env::var("OSINT_GRAPH_GIT_SHA")
+        .ok()
+        .or_else(|| {
+            Command::new("git")
+                .args(["rev-parse", "HEAD"])
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .and_then(|output| String::from_utf8(output.stdout).ok())
+        })
+        .map(|sha| sha.trim().to_string())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=OSINT_GRAPH_GIT_SHA={git_sha}");
+}
@@ -0,0 +1,118 @@
+//! Project access checks
+//!
+//! A project belongs to the user whose [crate::entity::user::Model::uuid] is in its `user` field.
+//! Projects whose owner isn't a registered user (eg, created before ownership was recorded) are
+//! open to everyone, as is everything when authentication is disabled.
+
+use axum::http::StatusCode;
+use sea_orm::{
+    ColumnTrait, Condition, ConnectionTrait, EntityTrait, PaginatorTrait, QueryFilter, QuerySelect,
+    QueryTrait,
+};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    entity::{attachment, node, project, user},
+    oauth::middleware::AuthUser,
+    project::WebError,
+};
+
+/// Ensure `auth_user` can access `project`
+pub async fn check_project_access(
+    conn: &impl ConnectionTrait,
+    project: &project::Model,
+    auth_user: Option<&AuthUser>,
+) -> Result<(), WebError> {
+    let Some(auth_user) = auth_user else {
+        return Ok(());
+    };
+    if project.user == auth_user.id {
+        return Ok(());
+    }
+    let owned = user::Entity::find()
+        .filter(user::Column::Uuid.eq(project.user))
+        .count(conn)
+        .await?
+        > 0;
+    if owned {
+        warn!(
+            project_id = project.id.to_string(),
+            user = auth_user.subject,
+            "Denied access to another user's project"
+        );
+        return Err(WebError::new(
+            StatusCode::FORBIDDEN,
+            format!("You don't have access to project {}", project.id),
+        ));
+    }
+    Ok(())
+}
+
+/// Find a project and ensure `auth_user` can access it
+pub async fn find_accessible_project(
+    conn: &impl ConnectionTrait,
+    id: Uuid,
+    auth_user: Option<&AuthUser>,
+) -> Result<project::Model, WebError> {
+    let project = project::Entity::find_by_id(id)
+        .one(conn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Project {} not found", id)))?;
+    check_project_access(conn, &project, auth_user).await?;
+    Ok(project)
+}
+
+/// Matches the projects `auth_user` can access, for lists which can't check them one at a time
+pub fn accessible_projects(auth_user: Option<&AuthUser>) -> Condition {
+    let Some(auth_user) = auth_user else {
+        return Condition::all();
+    };
+    Condition::any()
+        .add(project::Column::User.eq(auth_user.id))
+        .add(
+            project::Column::User.not_in_subquery(
+                user::Entity::find()
+                    .select_only()
+                    .column(user::Column::Uuid)
+                    .into_query(),
+            ),
+        )
+}
+
+/// Find the project a node belongs to and ensure `auth_user` can access it
+pub async fn check_node_access(
+    conn: &impl ConnectionTrait,
+    node_id: Uuid,
+    auth_user: Option<&AuthUser>,
+) -> Result<project::Model, WebError> {
+    let project_id: Uuid = node::Entity::find_by_id(node_id)
+        .select_only()
+        .column(node::Column::ProjectId)
+        .into_tuple()
+        .one(conn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Node {} not found", node_id)))?;
+    let project = project::Entity::find_by_id(project_id)
+        .one(conn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Project {} not found", project_id)))?;
+    check_project_access(conn, &project, auth_user).await?;
+    Ok(project)
+}
+
+/// Resolve attachment → node → project and ensure `auth_user` can access it
+pub async fn check_attachment_access(
+    conn: &impl ConnectionTrait,
+    attachment_id: Uuid,
+    auth_user: Option<&AuthUser>,
+) -> Result<project::Model, WebError> {
+    let node_id: Uuid = attachment::Entity::find_by_id(attachment_id)
+        .select_only()
+        .column(attachment::Column::NodeId)
+        .into_tuple()
+        .one(conn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Attachment {} not found", attachment_id)))?;
+    check_node_access(conn, node_id, auth_user).await
+}
@@ -0,0 +1,305 @@
+//! Listing and managing users, for admins
+//!
+//! Resource counts come from one grouped query per kind over the page of users, joining through
+//! [project::Model::user] to find who owns what.
+
+use std::collections::HashMap;
+
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait, IntoActiveModel,
+    JoinType, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, RelationTrait, Set,
+    TransactionTrait, TryIntoModel,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    entity::{api_token, attachment, node, project, user},
+    extract::{Path, Query},
+    oauth::middleware::AuthUser,
+    project::{ErrorResponse, PaginatedResponse, PaginationQuery, WebError},
+    quota::QuotaKind,
+    SharedState,
+};
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AdminUsersQuery {
+    /// Case-insensitive substring of the email, subject or display name
+    #[serde(default)]
+    pub q: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct UserSummary {
+    /// The user's UUID, which owns their projects
+    pub id: Uuid,
+    pub subject: String,
+    pub email: String,
+    pub display_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_login: Option<DateTime<Utc>>,
+    pub is_admin: bool,
+    pub project_count: u64,
+    pub node_count: u64,
+    /// Uncompressed size of every attachment in their projects
+    pub attachment_bytes: u64,
+}
+
+/// Changes to a user, missing fields are left alone
+#[derive(Debug, Default, Deserialize, Serialize, ToSchema)]
+pub struct UserUpdate {
+    pub is_admin: Option<bool>,
+    /// An empty string clears it
+    pub display_name: Option<String>,
+}
+
+/// Per-owner totals from a grouped query
+async fn totals_by_owner<E>(
+    conn: &impl ConnectionTrait,
+    select: sea_orm::Select<E>,
+    total: sea_orm::sea_query::SimpleExpr,
+    owners: &[Uuid],
+) -> Result<HashMap<Uuid, u64>, DbErr>
+where
+    E: EntityTrait,
+{
+    Ok(select
+        .select_only()
+        .column(project::Column::User)
+        .column_as(total, "total")
+        .filter(project::Column::User.is_in(owners.iter().copied()))
+        .group_by(project::Column::User)
+        .into_tuple::<(Uuid, Option<i64>)>()
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|(owner, total)| (owner, total.unwrap_or(0).max(0) as u64))
+        .collect())
+}
+
+/// Summaries for `users`, in the same order
+pub async fn summarise_users(
+    conn: &impl ConnectionTrait,
+    users: Vec<user::Model>,
+) -> Result<Vec<UserSummary>, DbErr> {
+    let owners: Vec<Uuid> = users.iter().map(|user| user.uuid).collect();
+    let projects = totals_by_owner(
+        conn,
+        project::Entity::find(),
+        project::Column::Id.count(),
+        &owners,
+    )
+    .await?;
+    let nodes = totals_by_owner(
+        conn,
+        node::Entity::find().join(JoinType::InnerJoin, node::Relation::Project.def()),
+        node::Column::Id.count(),
+        &owners,
+    )
+    .await?;
+    let attachment_bytes = totals_by_owner(
+        conn,
+        attachment::Entity::find()
+            .join(JoinType::InnerJoin, attachment::Relation::Node.def())
+            .join(JoinType::InnerJoin, node::Relation::Project.def()),
+        attachment::Column::Size.sum(),
+        &owners,
+    )
+    .await?;
+
+    Ok(users
+        .into_iter()
+        .map(|user| UserSummary {
+            project_count: projects.get(&user.uuid).copied().unwrap_or(0),
+            node_count: nodes.get(&user.uuid).copied().unwrap_or(0),
+            attachment_bytes: attachment_bytes.get(&user.uuid).copied().unwrap_or(0),
+            id: user.uuid,
+            subject: user.subject,
+            email: user.email,
+            display_name: user.display_name,
+            created_at: user.created_at,
+            last_login: user.last_login,
+            is_admin: user.is_admin,
+        })
+        .collect())
+}
+
+async fn find_user(conn: &impl ConnectionTrait, id: Uuid) -> Result<user::Model, WebError> {
+    user::Entity::find()
+        .filter(user::Column::Uuid.eq(id))
+        .one(conn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("User {} not found", id)))
+}
+
+/// Refuse changes which would lock the caller out
+fn check_not_self(
+    auth_user: Option<&Extension<AuthUser>>,
+    id: Uuid,
+    action: &str,
+) -> Result<(), WebError> {
+    match auth_user {
+        Some(Extension(auth_user)) if auth_user.id == id => Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            format!("You can't {action} yourself, ask another admin"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Users and what they own, filtered by `q`
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/users",
+    tag = "admin",
+    operation_id = "get_admin_users",
+    params(AdminUsersQuery, PaginationQuery),
+    responses(
+        (status = OK, description = "A page of users, ordered by when they registered", body = PaginatedResponse<UserSummary>),
+        (status = BAD_REQUEST, description = "Invalid query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Caller isn't an admin", body = ErrorResponse)
+    )
+)]
+pub async fn get_admin_users(
+    State(state): State<SharedState>,
+    Query(query): Query<AdminUsersQuery>,
+    Query(pagination): Query<PaginationQuery>,
+) -> Result<Json<PaginatedResponse<UserSummary>>, WebError> {
+    pagination.validate()?;
+    let conn = &state.read().await.conn;
+
+    let mut select = user::Entity::find();
+    if let Some(q) = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        // SQLite's LIKE is case-insensitive for ASCII
+        let pattern = format!("%{q}%");
+        select = select.filter(
+            Condition::any()
+                .add(user::Column::Email.like(&pattern))
+                .add(user::Column::Subject.like(&pattern))
+                .add(user::Column::DisplayName.like(&pattern)),
+        );
+    }
+    let total_count = select.clone().count(conn).await?;
+    let users = select
+        .order_by_asc(user::Column::Id)
+        .offset(pagination.offset())
+        .limit(pagination.page_size)
+        .all(conn)
+        .await?;
+
+    Ok(Json(PaginatedResponse::new(
+        &pagination,
+        total_count,
+        summarise_users(conn, users).await?,
+    )))
+}
+
+/// Grant or revoke admin, or correct a user's display name
+#[utoipa::path(
+    put,
+    path = "/api/v1/admin/users/{id}",
+    tag = "admin",
+    operation_id = "update_admin_user",
+    params(
+        ("id" = Uuid, Path, description = "User ID")
+    ),
+    request_body = UserUpdate,
+    responses(
+        (status = OK, description = "The updated user", body = UserSummary),
+        (status = BAD_REQUEST, description = "Invalid path parameter, or removing your own admin flag", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Caller isn't an admin", body = ErrorResponse),
+        (status = NOT_FOUND, description = "User not found", body = ErrorResponse)
+    )
+)]
+pub async fn update_admin_user(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+    Json(update): Json<UserUpdate>,
+) -> Result<Json<UserSummary>, WebError> {
+    if update.is_admin == Some(false) {
+        check_not_self(auth_user.as_ref(), id, "remove admin from")?;
+    }
+    let conn = &state.read().await.conn;
+    let mut user = find_user(conn, id).await?.into_active_model();
+    if let Some(is_admin) = update.is_admin {
+        user.is_admin = Set(is_admin);
+    }
+    if let Some(display_name) = update.display_name {
+        let display_name = display_name.trim();
+        user.display_name = Set((!display_name.is_empty()).then(|| display_name.to_string()));
+    }
+    let user = match user.is_changed() {
+        true => {
+            user.updated_at = Set(Utc::now());
+            let user = user.update(conn).await?;
+            info!(
+                user = user.subject,
+                is_admin = user.is_admin,
+                "Admin updated user"
+            );
+            user
+        }
+        false => user.try_into_model()?,
+    };
+    let summary = summarise_users(conn, vec![user]).await?.remove(0);
+    Ok(Json(summary))
+}
+
+/// Delete a user's account, their projects and their API tokens
+pub async fn delete_account(conn: &impl TransactionTrait, user: user::Model) -> Result<(), DbErr> {
+    let txn = conn.begin().await?;
+    api_token::Entity::delete_many()
+        .filter(api_token::Column::UserId.eq(user.uuid))
+        .exec(&txn)
+        .await?;
+    // nodes, links and attachments go with their projects
+    let projects = project::Entity::delete_many()
+        .filter(project::Column::User.eq(user.uuid))
+        .exec(&txn)
+        .await?;
+    user::Entity::delete_by_id(user.id).exec(&txn).await?;
+    txn.commit().await?;
+    info!(
+        user = user.subject,
+        projects = projects.rows_affected,
+        "Deleted user account"
+    );
+    Ok(())
+}
+
+/// Delete a user along with their projects and API tokens
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/users/{id}",
+    tag = "admin",
+    operation_id = "delete_admin_user",
+    params(
+        ("id" = Uuid, Path, description = "User ID")
+    ),
+    responses(
+        (status = OK, description = "User deleted"),
+        (status = BAD_REQUEST, description = "Invalid path parameter, or deleting yourself", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Caller isn't an admin", body = ErrorResponse),
+        (status = NOT_FOUND, description = "User not found", body = ErrorResponse)
+    )
+)]
+pub async fn delete_admin_user(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<StatusCode, WebError> {
+    check_not_self(auth_user.as_ref(), id, "delete")?;
+    let reader = state.read().await;
+    let user = find_user(&reader.conn, id).await?;
+    delete_account(&reader.conn, user).await?;
+    reader
+        .quota
+        .invalidate([QuotaKind::Projects, QuotaKind::AttachmentBytes]);
+    Ok(StatusCode::OK)
+}
@@ -0,0 +1,386 @@
+//! Self-contained case archives, for cold storage once an investigation closes
+//!
+//! An archive is a single HTML file that opens in any browser without this tool, scripts or a
+//! network connection: a summary of the project, the graph as Mermaid source, tables of nodes
+//! and links, and the attachments. Notes are rendered by [crate::markdown]. Attachments up to
+//! [MAX_EMBEDDED_ATTACHMENT_BYTES] are embedded as `data:` URIs, bigger ones are listed with
+//! their SHA-256 so the originals can be matched up.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+};
+
+use axum::{
+    extract::State,
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        HeaderValue,
+    },
+    response::IntoResponse,
+    Extension,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use sea_orm::{ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter, QueryOrder};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    access::check_project_access,
+    entity::{attachment, node, nodelink, project},
+    export::html_escape,
+    extract::{Path, Query},
+    markdown::{render_markdown, MarkdownContext},
+    oauth::middleware::AuthUser,
+    project::{build_mermaid, render_project_mermaid, ErrorResponse, WebError},
+    redact::redact,
+    report::ascii_filename,
+    styles::NodeTypeStyles,
+    SharedState,
+};
+
+pub const HTML_CONTENT_TYPE: &str = "text/html; charset=utf-8";
+/// Bigger attachments are listed but not embedded
+pub const MAX_EMBEDDED_ATTACHMENT_BYTES: i64 = 1024 * 1024;
+/// Once this much has been embedded the rest are listed, so archives stay openable
+pub const MAX_EMBEDDED_TOTAL_BYTES: i64 = 64 * 1024 * 1024;
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse;margin-bottom:2em}\
+th,td{border:1px solid #ccc;padding:4px 8px;text-align:left;vertical-align:top}\
+th{background:#f4f4f4}pre{background:#f8f8f8;padding:1em;overflow:auto}\
+img{max-width:320px;max-height:320px}";
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveFormat {
+    /// A single HTML file with attachments embedded
+    #[default]
+    Html,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ArchiveExportQuery {
+    #[serde(default)]
+    pub format: ArchiveFormat,
+    #[serde(default)]
+    pub redact: bool,
+}
+
+/// Everything an archive is built from
+pub struct ArchiveData {
+    pub project: project::Model,
+    pub nodes: Vec<node::Model>,
+    pub nodelinks: Vec<nodelink::Model>,
+    pub attachments: Vec<attachment::ModelNoAttachment>,
+    /// Decoded data of the attachments small enough to embed, by attachment ID
+    pub embedded: HashMap<Uuid, Vec<u8>>,
+    pub mermaid: String,
+}
+
+/// Load a project for [render_archive], `None` if it doesn't exist
+///
+/// A `redacted` archive has been through [redact], so it has no attachments.
+pub async fn load_archive(
+    conn: &impl ConnectionTrait,
+    project_id: Uuid,
+    styles: &NodeTypeStyles,
+    redacted: bool,
+) -> Result<Option<ArchiveData>, DbErr> {
+    let Some(mut project) = project::Entity::find_by_id(project_id).one(conn).await? else {
+        return Ok(None);
+    };
+    let mut nodes = node::Entity::find_live()
+        .filter(node::Column::ProjectId.eq(project_id))
+        .order_by_asc(node::Column::NodeType)
+        .order_by_asc(node::Column::Display)
+        .order_by_asc(node::Column::Id)
+        .all(conn)
+        .await?;
+    let mut nodelinks = nodelink::Entity::find_live()
+        .filter(nodelink::Column::ProjectId.eq(project_id))
+        .order_by_asc(nodelink::Column::Id)
+        .all(conn)
+        .await?;
+    if redacted {
+        redact(&mut project, &mut nodes, &mut nodelinks, &mut Vec::new());
+        // drawn from the same placeholders as the tables
+        let mermaid = build_mermaid(&project, &nodes, &nodelinks, Vec::new(), styles, true);
+        return Ok(Some(ArchiveData {
+            project,
+            nodes,
+            nodelinks,
+            attachments: Vec::new(),
+            embedded: HashMap::new(),
+            mermaid,
+        }));
+    }
+    let mut attachments = attachment::attachment_list(project_id).all(conn).await?;
+    attachments.sort_by(|a, b| a.filename.cmp(&b.filename).then(a.id.cmp(&b.id)));
+
+    let mut budget = MAX_EMBEDDED_TOTAL_BYTES;
+    let embed_ids: Vec<Uuid> = attachments
+        .iter()
+        .filter(|a| a.size <= MAX_EMBEDDED_ATTACHMENT_BYTES)
+        .take_while(|a| {
+            budget -= a.size;
+            budget >= 0
+        })
+        .map(|a| a.id)
+        .collect();
+    let mut embedded = HashMap::new();
+    if !embed_ids.is_empty() {
+        let mut stored = attachment::Entity::find()
+            .filter(attachment::Column::Id.is_in(embed_ids))
+            .all(conn)
+            .await?;
+        attachment::load_shared_data(conn, &mut stored).await?;
+        for stored in stored {
+            match stored.codec.decode(&stored.data) {
+                Ok(data) => {
+                    embedded.insert(stored.id, data);
+                }
+                Err(err) => error!(
+                    error = ?err,
+                    attachment_id = stored.id.to_string(),
+                    "Failed to decode attachment for archive"
+                ),
+            }
+        }
+    }
+
+    let mermaid = render_project_mermaid(conn, &project, None, styles, false).await?;
+    Ok(Some(ArchiveData {
+        project,
+        nodes,
+        nodelinks,
+        attachments,
+        embedded,
+        mermaid,
+    }))
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M UTC").to_string()
+}
+
+fn format_size(bytes: i64) -> String {
+    if bytes < 1024 {
+        format!("{bytes} B")
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+/// Build the HTML for a project, `exported` is when the archive was made
+pub fn render_archive(data: &ArchiveData, exported: DateTime<Utc>) -> String {
+    let project = &data.project;
+    let displays: HashMap<Uuid, &str> = data
+        .nodes
+        .iter()
+        .map(|node| (node.id, node.display.as_str()))
+        .collect();
+    let display = |id: &Uuid| html_escape(displays.get(id).copied().unwrap_or("(missing node)"));
+
+    let mut out = String::new();
+    let name = html_escape(&project.name);
+    let _ = write!(
+        out,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{name}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>{name}</h1>\n"
+    );
+
+    out.push_str("<section id=\"summary\">\n<h2>Summary</h2>\n<table>\n");
+    let mut by_type: BTreeMap<&str, usize> = BTreeMap::new();
+    for node in &data.nodes {
+        *by_type.entry(node.node_type.as_ref()).or_default() += 1;
+    }
+    let by_type = by_type
+        .iter()
+        .map(|(node_type, count)| format!("{count} {node_type}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    // markdown goes through the shared renderer, without images so nothing is fetched
+    let _ = writeln!(
+        out,
+        "<tr><th>Description</th><td>{}</td></tr>",
+        render_markdown(
+            project.description.as_deref().unwrap_or_default(),
+            MarkdownContext::NodeNote
+        )
+    );
+    for (label, value) in [
+        ("Project ID", project.id.to_string()),
+        ("Tags", project.tags.0.join(", ")),
+        ("Created", format_time(project.creationdate)),
+        (
+            "Last updated",
+            project.last_updated.map(format_time).unwrap_or_default(),
+        ),
+        ("Archived", format_time(exported)),
+        ("Nodes", format!("{} ({by_type})", data.nodes.len())),
+        ("Links", data.nodelinks.len().to_string()),
+        ("Attachments", data.attachments.len().to_string()),
+    ] {
+        let _ = writeln!(
+            out,
+            "<tr><th>{label}</th><td>{}</td></tr>",
+            html_escape(&value)
+        );
+    }
+    out.push_str("</table>\n</section>\n");
+
+    // kept as source so the archive doesn't need a script, any Mermaid renderer can draw it
+    out.push_str("<section id=\"graph\">\n<h2>Graph</h2>\n<pre class=\"mermaid\">\n");
+    for line in data.mermaid.lines() {
+        out.push_str(&html_escape(line));
+        out.push('\n');
+    }
+    out.push_str("</pre>\n</section>\n");
+
+    out.push_str(
+        "<section id=\"nodes\">\n<h2>Nodes</h2>\n<table id=\"node-table\">\n\
+         <tr><th>Type</th><th>Display</th><th>Value</th><th>Notes</th><th>Updated</th><th>ID</th></tr>\n",
+    );
+    for node in &data.nodes {
+        let _ = writeln!(
+            out,
+            "<tr id=\"node-{}\"><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            node.id,
+            node.node_type,
+            html_escape(&node.display),
+            html_escape(&node.value),
+            render_markdown(
+                node.notes.as_deref().unwrap_or_default(),
+                MarkdownContext::NodeNote
+            ),
+            format_time(node.updated),
+            node.id,
+        );
+    }
+    out.push_str("</table>\n</section>\n");
+
+    out.push_str(
+        "<section id=\"links\">\n<h2>Links</h2>\n<table id=\"link-table\">\n\
+         <tr><th>From</th><th>To</th><th>Type</th><th>Kind</th><th>Weight</th><th>Valid</th></tr>\n",
+    );
+    for link in &data.nodelinks {
+        let valid = match (link.valid_from, link.valid_to) {
+            (None, None) => String::new(),
+            (from, to) => format!(
+                "{} to {}",
+                from.map(format_time).unwrap_or_default(),
+                to.map(format_time).unwrap_or_default()
+            ),
+        };
+        let _ = writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            display(&link.left),
+            display(&link.right),
+            link.linktype.as_ref(),
+            html_escape(link.kind.as_deref().unwrap_or_default()),
+            link.weight
+                .map(|weight| weight.to_string())
+                .unwrap_or_default(),
+            html_escape(valid.trim()),
+        );
+    }
+    out.push_str("</table>\n</section>\n");
+
+    out.push_str(
+        "<section id=\"attachments\">\n<h2>Attachments</h2>\n<table id=\"attachment-table\">\n\
+         <tr><th>File</th><th>Node</th><th>Type</th><th>Size</th><th>SHA-256</th><th>Content</th></tr>\n",
+    );
+    for attachment in &data.attachments {
+        let filename = html_escape(&attachment.filename);
+        let content = match data.embedded.get(&attachment.id) {
+            Some(bytes) => {
+                let uri = format!(
+                    "data:{};base64,{}",
+                    html_escape(&attachment.content_type),
+                    STANDARD.encode(bytes)
+                );
+                if attachment.content_type.starts_with("image/") {
+                    format!("<img src=\"{uri}\" alt=\"{filename}\">")
+                } else {
+                    format!("<a download=\"{filename}\" href=\"{uri}\">Save</a>")
+                }
+            }
+            None => "Not embedded".to_string(),
+        };
+        let _ = writeln!(
+            out,
+            "<tr><td>{filename}</td><td>{}</td><td>{}</td><td>{}</td><td><code>{}</code></td><td>{content}</td></tr>",
+            display(&attachment.node_id),
+            html_escape(&attachment.content_type),
+            format_size(attachment.size),
+            attachment.sha256.as_deref().unwrap_or_default(),
+        );
+    }
+    out.push_str("</table>\n</section>\n</body>\n</html>\n");
+    out
+}
+
+/// A single file archive of a project for cold storage, readable without this tool
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/export/archive",
+    tag = "exports",
+    operation_id = "export_project_archive",
+    params(
+        ("id" = Uuid, Path, description = "Project ID to archive"),
+        ("format" = Option<ArchiveFormat>, Query, description = "Archive format, only html for now"),
+        ("redact" = bool, Query, description = "Replace values with placeholders and strip attachments and project metadata")
+    ),
+    responses(
+        (status = OK, description = "The archive", content_type = "text/html", body = String),
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user"),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse)
+    )
+)]
+pub async fn export_project_archive(
+    Path(id): Path<Uuid>,
+    Query(query): Query<ArchiveExportQuery>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<impl IntoResponse, WebError> {
+    let reader = state.read().await;
+    let project = project::Entity::find_by_id(id)
+        .one(&reader.conn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Project {} not found", id)))?;
+    check_project_access(&reader.conn, &project, auth_user.as_deref()).await?;
+
+    let data = load_archive(&reader.conn, id, &reader.node_type_styles, query.redact)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Project {} not found", id)))?;
+    debug!(
+        project_id = id.to_string(),
+        nodes = data.nodes.len(),
+        attachments = data.attachments.len(),
+        embedded = data.embedded.len(),
+        "Exporting archive"
+    );
+    Ok((
+        [
+            (CONTENT_TYPE, HeaderValue::from_static(HTML_CONTENT_TYPE)),
+            (
+                CONTENT_DISPOSITION,
+                HeaderValue::from_str(&format!(
+                    "attachment; filename=\"{}.archive.html\"",
+                    ascii_filename(&data.project.name)
+                ))?,
+            ),
+        ],
+        match query.format {
+            ArchiveFormat::Html => render_archive(&data, Utc::now()),
+        },
+    ))
+}
@@ -1,48 +1,71 @@
 use axum::{
     body::Body,
-    extract::{Multipart, Path, State},
+    extract::{Multipart, State},
     http::{
-        header::{ACCEPT_ENCODING, CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_TYPE, COOKIE},
-        HeaderMap, HeaderValue, StatusCode,
+        header::{
+            ACCEPT_RANGES, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_LENGTH,
+            CONTENT_RANGE, CONTENT_TYPE, COOKIE, ETAG, IF_NONE_MATCH, IF_RANGE, RANGE, VARY,
+        },
+        HeaderMap, HeaderName, HeaderValue, StatusCode,
     },
-    response::{IntoResponse, Response},
-    Json,
+    response::Response,
+    Extension, Json,
 };
-use flate2::read::GzDecoder;
-use flate2::write::GzEncoder;
-use flate2::Compression;
 use sea_orm::{
-    ActiveModelTrait, ActiveValue::Set, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter,
-    TryIntoModel,
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, EntityTrait, IntoActiveModel, JoinType,
+    QueryFilter, QueryOrder, QuerySelect, RelationTrait, TryIntoModel,
 };
-use serde::Deserialize;
-use std::io::{Read, Write};
+use serde::{Deserialize, Serialize};
 use tracing::{debug, error};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::{
-    entity::{attachment, node},
-    project::WebError,
+    access::{check_attachment_access, check_node_access, check_project_access},
+    attachment_codec::AttachmentCodec,
+    attachment_dedup::content_hash,
+    entity::{attachment, node, project},
+    extract::{Path, Query},
+    media,
+    oauth::middleware::AuthUser,
+    project::{ErrorResponse, SortOrder, WebError},
+    quota::{warning_headers, QuotaKind},
     SharedState,
 };
 
+/// The multipart form [upload_attachment] accepts
+#[derive(Debug, ToSchema)]
+pub struct AttachmentUpload {
+    /// The file, its filename and content type are taken from the part's headers
+    #[schema(value_type = String, format = Binary)]
+    pub file: Vec<u8>,
+}
+
 /// Upload a file attachment to a node
 #[utoipa::path(
     post,
     path = "/api/v1/node/{id}/attachment",
+    tag = "attachments",
+    operation_id = "upload_attachment",
+    params(
+        ("id" = Uuid, Path, description = "Node ID")
+    ),
+    request_body(content = AttachmentUpload, content_type = "multipart/form-data"),
     responses(
-        (status = OK, description = "Attachment uploaded successfully", body = attachment::Model),
-        (status = BAD_REQUEST, description = "Invalid request"),
+        (status = OK, description = "Attachment uploaded successfully, or the node's existing attachment with the same content", body = attachment::Model),
+        (status = BAD_REQUEST, description = "Invalid request", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Node belongs to another user's project"),
         (status = NOT_FOUND, description = "Node not found")
     )
 )]
 pub async fn upload_attachment(
     State(state): State<SharedState>,
     Path(node_id): Path<Uuid>,
+    auth_user: Option<Extension<AuthUser>>,
     mut multipart: Multipart,
-) -> Result<Json<attachment::Model>, WebError> {
-    let conn = &state.read().await.conn;
+) -> Result<(HeaderMap, Json<attachment::Model>), WebError> {
+    let reader = state.read().await;
+    let conn = &reader.conn;
 
     debug!("Starting file upload for node {}", node_id);
 
@@ -107,28 +130,42 @@ pub async fn upload_attachment(
         })?
         .to_vec();
 
-    // Verify the node exists before creating the attachment
-    let node_exists = node::Entity::find_by_id(node_id)
-        .one(conn)
-        .await
-        .map_err(|e| {
-            error!("Failed to check if node exists: {:?}", e);
-            WebError::internal_server_error(format!("Failed to verify node: {}", e))
-        })?
-        .is_some();
+    // Verify the node exists and is ours before creating the attachment
+    check_node_access(conn, node_id, auth_user.as_deref()).await?;
 
-    if !node_exists {
-        return Err(WebError::not_found(format!("Node {} not found", node_id)));
+    // uploading the same file to the same node again gets the attachment it already has
+    let sha256 = content_hash(&file_data);
+    if let Some(existing) = attachment::Entity::find()
+        .filter(attachment::Column::NodeId.eq(node_id))
+        .filter(attachment::Column::Sha256.eq(&sha256))
+        .one(conn)
+        .await?
+    {
+        debug!(
+            attachment_id = existing.id.to_string(),
+            node_id = node_id.to_string(),
+            "Upload matches an existing attachment"
+        );
+        return Ok((HeaderMap::new(), Json(existing)));
     }
 
-    // Compress data with gzip
-    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
-    encoder.write_all(&file_data).map_err(|e| {
+    reader
+        .quota
+        .check(
+            conn,
+            QuotaKind::AttachmentBytes,
+            file_data.len() as u64,
+            auth_user.as_deref(),
+        )
+        .await?;
+
+    let media = media::probe(&file_data);
+    let content_type = media::correct_content_type(&content_type, &file_data, media.as_ref());
+
+    let codec = reader.attachment_codec;
+    let compressed_data = codec.encode(&file_data).map_err(|e| {
         WebError::internal_server_error(format!("Failed to compress attachment data: {}", e))
     })?;
-    let compressed_data = encoder.finish().map_err(|e| {
-        WebError::internal_server_error(format!("Failed to finish compression: {}", e))
-    })?;
 
     // Create attachment entity
 
@@ -140,6 +177,11 @@ pub async fn upload_attachment(
         size: Set(file_data.len() as i64),
         data: Set(compressed_data),
         created: Set(chrono::Utc::now()),
+        codec: Set(codec),
+        sha256: Set(Some(sha256)),
+        media: Set(media),
+        created_by: Set(AuthUser::created_by(auth_user.as_deref())),
+        shared_blob: Set(false),
     };
 
     // Save to database
@@ -154,32 +196,99 @@ pub async fn upload_attachment(
         "Created attachment"
     );
 
-    Ok(Json(saved))
+    let warning = reader
+        .quota
+        .record(QuotaKind::AttachmentBytes, saved.size as u64);
+    Ok((warning_headers(warning), Json(saved)))
 }
 
 #[derive(Deserialize, Debug, ToSchema)]
 pub struct UpdateAttachmentData {
     node_id: Option<Uuid>,
     data: Option<Vec<u8>>,
+    filename: Option<String>,
+    content_type: Option<String>,
+}
+
+/// Filenames end up in Content-Disposition headers and on people's disks, so keep them boring
+fn validate_filename(filename: &str) -> Result<String, WebError> {
+    let filename = filename.trim();
+    if filename.is_empty() || filename == "." || filename == ".." {
+        return Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid filename {:?}", filename),
+        ));
+    }
+    if let Some(c) = filename
+        .chars()
+        .find(|c| *c == '/' || *c == '\\' || c.is_control())
+    {
+        return Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Filename {:?} contains invalid character {:?}", filename, c),
+        ));
+    }
+    Ok(filename.to_string())
+}
+
+fn validate_content_type(content_type: &str) -> Result<String, WebError> {
+    let invalid = |reason: String| {
+        WebError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid content type {:?}: {}", content_type, reason),
+        )
+    };
+    let mime = content_type
+        .trim()
+        .parse::<mime::Mime>()
+        .map_err(|err| invalid(err.to_string()))?;
+    // the parser is happy with an empty subtype, eg `text/`
+    if mime.subtype().as_str().is_empty() {
+        return Err(invalid("missing subtype".to_string()));
+    }
+    Ok(mime.to_string())
 }
 
-/// Update a file attachment's metadata or data
+/// Update a file attachment's node, filename, content type or data
 #[utoipa::path(
     put,
     path = "/api/v1/attachment/{attachment_id}",
+    tag = "attachments",
+    operation_id = "update_attachment",
+    params(
+        ("attachment_id" = Uuid, Path, description = "Attachment ID")
+    ),
     request_body = UpdateAttachmentData,
     responses(
         (status = OK, description = "Attachment updated successfully", body = attachment::Model),
-        (status = NOT_FOUND, description = "Attachment not found"),
-        (status = BAD_REQUEST, description = "Invalid request")
+        (status = FORBIDDEN, description = "Attachment belongs to another user's project"),
+        (status = NOT_FOUND, description = "Attachment, or the node_id to move it to, not found"),
+        (status = BAD_REQUEST, description = "Invalid request", body = ErrorResponse)
     )
 )]
 pub async fn update_attachment(
     State(state): State<SharedState>,
     Path(attachment_id): Path<Uuid>,
+    auth_user: Option<Extension<AuthUser>>,
     Json(update_data): Json<UpdateAttachmentData>,
 ) -> Result<Json<attachment::Model>, WebError> {
-    let conn = &state.read().await.conn;
+    let reader = state.read().await;
+    let conn = &reader.conn;
+
+    check_attachment_access(conn, attachment_id, auth_user.as_deref()).await?;
+    if let Some(node_id) = update_data.node_id {
+        check_node_access(conn, node_id, auth_user.as_deref()).await?;
+    }
+    let filename = update_data
+        .filename
+        .as_deref()
+        .map(validate_filename)
+        .transpose()?;
+    let content_type = update_data
+        .content_type
+        .as_deref()
+        .map(validate_content_type)
+        .transpose()?;
 
     // Find the attachment
     let attachment = attachment::Entity::find_by_id(attachment_id)
@@ -197,7 +306,29 @@ pub async fn update_attachment(
         updated_attachment.node_id = Set(node_id);
     }
     if let Some(data) = update_data.data {
-        updated_attachment.data = Set(data);
+        let codec = reader.attachment_codec;
+        updated_attachment.data = Set(codec.encode(&data).map_err(|e| {
+            WebError::internal_server_error(format!("Failed to compress attachment data: {}", e))
+        })?);
+        updated_attachment.size = Set(data.len() as i64);
+        updated_attachment.sha256 = Set(Some(content_hash(&data)));
+        updated_attachment.codec = Set(codec);
+        updated_attachment.shared_blob = Set(false);
+        let media = media::probe(&data);
+        let content_type = content_type
+            .as_deref()
+            .unwrap_or(updated_attachment.content_type.as_ref());
+        updated_attachment.content_type = Set(media::correct_content_type(
+            content_type,
+            &data,
+            media.as_ref(),
+        ));
+        updated_attachment.media = Set(media);
+    } else if let Some(content_type) = content_type {
+        updated_attachment.content_type = Set(content_type);
+    }
+    if let Some(filename) = filename {
+        updated_attachment.filename = Set(filename);
     }
 
     if updated_attachment.is_changed() {
@@ -224,20 +355,36 @@ pub async fn update_attachment(
 #[utoipa::path(
     get,
     path = "/api/v1/attachment/{attachment_id}",
+    tag = "attachments",
+    operation_id = "download_attachment",
+    params(
+        ("attachment_id" = Uuid, Path, description = "Attachment ID"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag from an earlier download"),
+        ("Range" = Option<String>, Header, description = "One byte range of the uncompressed file, eg bytes=1000- to resume"),
+        ("If-Range" = Option<String>, Header, description = "Only honour Range if the file still has this ETag")
+    ),
     responses(
         (status = OK, description = "Attachment downloaded successfully", content_type = "application/octet-stream", body = [u8]),
+        (status = PARTIAL_CONTENT, description = "The requested Range", content_type = "application/octet-stream", body = [u8]),
+        (status = NOT_MODIFIED, description = "The If-None-Match ETag is still current"),
+        (status = RANGE_NOT_SATISFIABLE, description = "The Range starts past the end of the file"),
+        (status = FORBIDDEN, description = "Attachment belongs to another user's project"),
         (status = NOT_FOUND, description = "Attachment not found"),
-        (status = BAD_REQUEST, description = "Attachment does not belong to node")
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse)
     )
 )]
 pub async fn download_attachment(
+    headers: HeaderMap,
     State(state): State<SharedState>,
     Path(attachment_id): Path<Uuid>,
+    auth_user: Option<Extension<AuthUser>>,
 ) -> Result<Response, WebError> {
     let conn = &state.read().await.conn;
 
+    check_attachment_access(conn, attachment_id, auth_user.as_deref()).await?;
+
     // Get attachment from database
-    let attachment = attachment::Entity::find_by_id(attachment_id)
+    let mut attachment = attachment::Entity::find_by_id(attachment_id)
         .one(conn)
         .await
         .map_err(|e| {
@@ -245,13 +392,7 @@ pub async fn download_attachment(
             WebError::internal_server_error(format!("Failed to get attachment: {}", e))
         })?
         .ok_or_else(|| WebError::not_found(format!("Attachment {} not found", attachment_id)))?;
-
-    // Decompress data
-    let mut decoder = GzDecoder::new(&attachment.data[..]);
-    let mut decompressed_data = Vec::new();
-    decoder.read_to_end(&mut decompressed_data).map_err(|e| {
-        WebError::internal_server_error(format!("Failed to decompress attachment data: {}", e))
-    })?;
+    attachment::load_shared_data(conn, std::slice::from_mut(&mut attachment)).await?;
 
     debug!(
         attachment_id = attachment_id.to_string(),
@@ -259,28 +400,307 @@ pub async fn download_attachment(
         "Downloading attachment",
     );
 
-    // Return file with appropriate headers
-    Ok((
-        StatusCode::OK,
-        [
-            ("Content-Type", attachment.content_type.as_str()),
-            (
-                "Content-Disposition",
-                &format!("attachment; filename=\"{}\"", attachment.filename),
-            ),
-        ],
-        decompressed_data,
+    let disposition = format!("attachment; filename=\"{}\"", attachment.filename);
+    attachment_response(attachment, &headers, &disposition)
+}
+
+/// Largest single attachment upload
+pub const MAX_UPLOAD_BYTES: usize = 100 * 1024 * 1024;
+
+/// Attachment contents don't change, so clients can hang on to them
+pub const ATTACHMENT_CACHE_CONTROL: &str = "private, max-age=31536000, immutable";
+
+/// What the `Range` header asks for, from [requested_range]
+#[derive(Debug, PartialEq, Eq)]
+enum RangeRequest {
+    /// No usable range, send everything
+    Whole,
+    /// From `start` to `end` inclusive
+    Partial {
+        start: u64,
+        end: u64,
+    },
+    Unsatisfiable,
+}
+
+/// Parse a single `Range: bytes=` range of a `size` byte file
+///
+/// Multiple ranges and anything malformed are ignored, which the RFC allows, so those clients
+/// get the whole file. An `If-Range` which doesn't match `etag` means the file changed since the
+/// client's partial copy, so that gets the whole file too.
+fn requested_range(request_headers: &HeaderMap, size: u64, etag: Option<&str>) -> RangeRequest {
+    let Some(range) = request_headers
+        .get(RANGE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return RangeRequest::Whole;
+    };
+    if let Some(if_range) = request_headers.get(IF_RANGE) {
+        if etag.is_none_or(|etag| if_range.to_str().ok() != Some(etag)) {
+            return RangeRequest::Whole;
+        }
+    }
+    let Some((first, last)) = range
+        .trim()
+        .strip_prefix("bytes=")
+        .filter(|spec| !spec.contains(','))
+        .and_then(|spec| spec.split_once('-'))
+    else {
+        return RangeRequest::Whole;
+    };
+    let (first, last) = (first.trim(), last.trim());
+    if first.is_empty() {
+        // the last `last` bytes
+        return match last.parse::<u64>() {
+            Ok(0) => RangeRequest::Unsatisfiable,
+            Ok(_) if size == 0 => RangeRequest::Unsatisfiable,
+            Ok(suffix) => RangeRequest::Partial {
+                start: size.saturating_sub(suffix),
+                end: size - 1,
+            },
+            Err(_) => RangeRequest::Whole,
+        };
+    }
+    let Ok(start) = first.parse::<u64>() else {
+        return RangeRequest::Whole;
+    };
+    let end = match last {
+        "" => u64::MAX,
+        last => match last.parse::<u64>() {
+            Ok(end) if end >= start => end,
+            _ => return RangeRequest::Whole,
+        },
+    };
+    if start >= size {
+        return RangeRequest::Unsatisfiable;
+    }
+    RangeRequest::Partial {
+        start,
+        end: end.min(size - 1),
+    }
+}
+
+/// Whether `If-None-Match` in the request matches `etag`, using the weak comparison
+pub(crate) fn etag_matches(request_headers: &HeaderMap, etag: &str) -> bool {
+    request_headers
+        .get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Build the response for an attachment's contents
+///
+/// The stored bytes are sent as they are when the client accepts their codec, otherwise they're
+/// decoded as they're streamed out. Each of those gets its own ETag from the content hash, and a
+/// matching `If-None-Match` gets a 304 instead. A `Range` is a range of the decoded file, so
+/// those are always decoded, and get a 206 with just that part.
+fn attachment_response(
+    attachment: attachment::Model,
+    request_headers: &HeaderMap,
+    disposition: &str,
+) -> Result<Response, WebError> {
+    let size = attachment.size.max(0) as u64;
+    let decoded_etag = attachment
+        .sha256
+        .as_deref()
+        .map(|sha256| format!("\"{}\"", sha256));
+    let range = requested_range(request_headers, size, decoded_etag.as_deref());
+    let passthrough = range == RangeRequest::Whole && attachment.codec.is_accepted(request_headers);
+    debug!(
+        attachment_id = attachment.id.to_string(),
+        codec = attachment.codec.content_coding(),
+        passthrough,
+        "Serving attachment"
+    );
+    // rows which haven't been hashed yet go without until the backfill reaches them
+    let etag = match passthrough {
+        true => attachment
+            .sha256
+            .as_deref()
+            .map(|sha256| format!("\"{}.{}\"", sha256, attachment.codec.content_coding())),
+        false => decoded_etag,
+    };
+    let cache_headers = |res: &mut Response| -> Result<(), WebError> {
+        if let Some(etag) = etag.as_deref() {
+            res.headers_mut().extend([
+                (ETAG, HeaderValue::from_str(etag)?),
+                (
+                    CACHE_CONTROL,
+                    HeaderValue::from_static(ATTACHMENT_CACHE_CONTROL),
+                ),
+            ]);
+        }
+        res.headers_mut()
+            .insert(VARY, HeaderValue::from_static("accept-encoding"));
+        res.headers_mut()
+            .insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+        Ok(())
+    };
+
+    if etag
+        .as_deref()
+        .is_some_and(|etag| etag_matches(request_headers, etag))
+    {
+        let mut res = Response::new(Body::empty());
+        *res.status_mut() = StatusCode::NOT_MODIFIED;
+        cache_headers(&mut res)?;
+        return Ok(res);
+    }
+
+    let content_type = HeaderValue::from_str(attachment.content_type.as_str())?;
+    let mut res = match range {
+        RangeRequest::Whole if passthrough => {
+            let length = attachment.data.len();
+            let mut res = Response::new(Body::from(attachment.data));
+            res.headers_mut().extend([
+                (
+                    CONTENT_ENCODING,
+                    HeaderValue::from_static(attachment.codec.content_coding()),
+                ),
+                (CONTENT_LENGTH, HeaderValue::from(length)),
+            ]);
+            res
+        }
+        RangeRequest::Whole => {
+            let mut res = Response::new(attachment.codec.decode_stream(attachment.data));
+            res.headers_mut()
+                .insert(CONTENT_LENGTH, HeaderValue::from(size));
+            res
+        }
+        RangeRequest::Partial { start, end } => {
+            let length = end - start + 1;
+            let mut res = Response::new(attachment.codec.decode_range_stream(
+                attachment.data,
+                start,
+                length,
+            ));
+            *res.status_mut() = StatusCode::PARTIAL_CONTENT;
+            res.headers_mut().extend([
+                (CONTENT_LENGTH, HeaderValue::from(length)),
+                (
+                    CONTENT_RANGE,
+                    HeaderValue::from_str(&format!("bytes {start}-{end}/{size}"))?,
+                ),
+            ]);
+            res
+        }
+        RangeRequest::Unsatisfiable => {
+            let mut res = Response::new(Body::empty());
+            *res.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+            res.headers_mut().insert(
+                CONTENT_RANGE,
+                HeaderValue::from_str(&format!("bytes */{size}"))?,
+            );
+            cache_headers(&mut res)?;
+            return Ok(res);
+        }
+    };
+    res.headers_mut().extend([
+        (CONTENT_TYPE, content_type),
+        (CONTENT_DISPOSITION, HeaderValue::from_str(disposition)?),
+    ]);
+    cache_headers(&mut res)?;
+    Ok(res)
+}
+
+/// Which [AttachmentCodec] the raw attachment bytes are compressed with
+pub const ATTACHMENT_CODEC_HEADER: HeaderName = HeaderName::from_static("x-attachment-codec");
+/// The attachment's uncompressed size in bytes
+pub const ATTACHMENT_SIZE_HEADER: HeaderName = HeaderName::from_static("x-attachment-size");
+/// The attachment's own content type, since the raw response is typed by its codec
+pub const ATTACHMENT_CONTENT_TYPE_HEADER: HeaderName =
+    HeaderName::from_static("x-attachment-content-type");
+/// SHA-256 of the uncompressed data, when it's been hashed
+pub const ATTACHMENT_SHA256_HEADER: HeaderName = HeaderName::from_static("x-attachment-sha256");
+
+/// Download an attachment's stored bytes exactly as persisted, still compressed
+///
+/// For backup and replication tools which copy blobs verbatim rather than decompressing and
+/// recompressing them. The response is typed by the codec, eg `application/zstd`, so it isn't
+/// compressed again on the way out. Admin-only, and not limited to the admin's own projects so a
+/// replica can copy every blob.
+#[utoipa::path(
+    get,
+    path = "/api/v1/attachment/{attachment_id}/raw",
+    tag = "attachments",
+    operation_id = "download_attachment_raw",
+    params(
+        ("attachment_id" = Uuid, Path, description = "Attachment ID")
+    ),
+    responses(
+        (status = OK, description = "Stored attachment bytes, see the X-Attachment-* headers", content_type = "application/octet-stream", body = [u8],
+            headers(
+                ("X-Attachment-Codec" = AttachmentCodec, description = "Codec the bytes are compressed with"),
+                ("X-Attachment-Size" = u64, description = "Uncompressed size in bytes"),
+                ("X-Attachment-Content-Type" = String, description = "Content type of the uncompressed data"),
+                ("X-Attachment-Sha256" = String, description = "SHA-256 of the uncompressed data, if it's been hashed")
+            )
+        ),
+        (status = FORBIDDEN, description = "Caller isn't an admin", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Attachment not found"),
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse)
     )
-        .into_response())
+)]
+pub async fn download_attachment_raw(
+    State(state): State<SharedState>,
+    Path(attachment_id): Path<Uuid>,
+) -> Result<Response, WebError> {
+    let conn = &state.read().await.conn;
+
+    let mut attachment = attachment::Entity::find_by_id(attachment_id)
+        .one(conn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Attachment {} not found", attachment_id)))?;
+    attachment::load_shared_data(conn, std::slice::from_mut(&mut attachment)).await?;
+
+    debug!(
+        attachment_id = attachment_id.to_string(),
+        codec = attachment.codec.content_coding(),
+        "Downloading raw attachment",
+    );
+
+    let length = attachment.data.len();
+    let mut res = Response::new(Body::from(attachment.data));
+    res.headers_mut().extend([
+        (
+            CONTENT_TYPE,
+            HeaderValue::from_static(attachment.codec.media_type()),
+        ),
+        (CONTENT_LENGTH, HeaderValue::from(length)),
+        (
+            ATTACHMENT_CODEC_HEADER,
+            HeaderValue::from_static(attachment.codec.content_coding()),
+        ),
+        (ATTACHMENT_SIZE_HEADER, HeaderValue::from(attachment.size)),
+        (
+            ATTACHMENT_CONTENT_TYPE_HEADER,
+            HeaderValue::from_str(&attachment.content_type)?,
+        ),
+    ]);
+    if let Some(sha256) = attachment.sha256.as_deref() {
+        res.headers_mut()
+            .insert(ATTACHMENT_SHA256_HEADER, HeaderValue::from_str(sha256)?);
+    }
+    Ok(res)
 }
 
-/// View a file attachment (inline display for images, PDFs, text)
+/// View a file attachment (inline display for images, PDFs, text, audio and video)
 /// GET /api/v1//attachment/{attachment_id}/view
 #[utoipa::path(
     get,
     path = "/api/v1/attachment/{attachment_id}/view",
+    tag = "attachments",
+    operation_id = "view_attachment",
+    params(
+        ("attachment_id" = Uuid, Path, description = "Attachment ID")
+    ),
     responses(
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
         (status = OK, description = "Attachment retrieved successfully", content_type = "application/octet-stream", body = [u8]),
+        (status = FORBIDDEN, description = "Attachment belongs to another user's project"),
         (status = NOT_FOUND, description = "Attachment not found")
     )
 )]
@@ -288,80 +708,66 @@ pub async fn view_attachment(
     headers: HeaderMap,
     State(state): State<SharedState>,
     Path(attachment_id): Path<Uuid>,
+    auth_user: Option<Extension<AuthUser>>,
 ) -> Result<Response, WebError> {
+    let conn = &state.read().await.conn;
+
+    check_attachment_access(conn, attachment_id, auth_user.as_deref()).await?;
+
     // Get attachment from database
-    let attachment = attachment::Entity::find_by_id(attachment_id)
-        .one(&state.read().await.conn)
+    let mut attachment = attachment::Entity::find_by_id(attachment_id)
+        .one(conn)
         .await
         .map_err(|e| {
             error!("Failed to get attachment: {:?}", e);
             WebError::internal_server_error(format!("Failed to get attachment: {}", e))
         })?
         .ok_or_else(|| WebError::not_found(format!("Attachment {} not found", attachment_id)))?;
-
-    let mut need_decompress = false;
-
-    if let Some(accept) = headers.get(ACCEPT_ENCODING) {
-        if accept.to_str().unwrap_or("").contains("gzip") {
-            need_decompress = true;
-        }
-    }
+    attachment::load_shared_data(conn, std::slice::from_mut(&mut attachment)).await?;
 
     debug!(
         attachment_id = attachment_id.to_string(),
         node_id = attachment.node_id.to_string(),
-        requires_decompression = need_decompress,
         "Viewing attachment"
     );
 
-    let headers = [
-        (
-            CONTENT_TYPE,
-            HeaderValue::from_str(attachment.content_type.as_str())?,
-        ),
-        (
-            CONTENT_DISPOSITION,
-            HeaderValue::from_str(&format!("inline; filename=\"{}\"", attachment.filename))?,
-        ),
-        (COOKIE, HeaderValue::from_static("")),
-    ];
-    // Decompress data
-    if need_decompress {
-        // TODO: work out if we can stream this instead of loading whole file into memory
-        let mut decoder = GzDecoder::new(attachment.data.as_slice());
-        let mut decompressed_data = Vec::new();
-        decoder.read_to_end(&mut decompressed_data).map_err(|e| {
-            WebError::internal_server_error(format!("Failed to decompress attachment data: {}", e))
-        })?;
-        Ok((StatusCode::OK, headers, decompressed_data).into_response())
-    } else {
-        let mut headers_vec = headers.to_vec();
-        headers_vec.push((CONTENT_ENCODING, HeaderValue::from_static("gzip")));
-        // Return file with inline disposition for viewing in browser
-        let mut res = Response::new(Body::from(attachment.data));
-        *res.status_mut() = StatusCode::OK;
-        res.headers_mut().extend(headers_vec);
-        res.headers_mut()
-            .extend([(CONTENT_ENCODING, HeaderValue::from_static("gzip"))]);
-        Ok(res)
-    }
+    // Return file with inline disposition for viewing in browser
+    let disposition = format!("inline; filename=\"{}\"", attachment.filename);
+    let mut res = attachment_response(attachment, &headers, &disposition)?;
+    res.headers_mut()
+        .insert(COOKIE, HeaderValue::from_static(""));
+    Ok(res)
 }
 
 /// Delete a file attachment
 #[utoipa::path(
     delete,
     path = "/api/v1/attachment/{attachment_id}",
+    tag = "attachments",
+    operation_id = "delete_attachment",
+    params(
+        ("attachment_id" = Uuid, Path, description = "Attachment ID")
+    ),
     responses(
-        (status = OK, description = "Attachment deleted successfully", body = String)
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = OK, description = "Attachment deleted successfully", body = String),
+        (status = FORBIDDEN, description = "Attachment belongs to another user's project"),
+        (status = NOT_FOUND, description = "Attachment not found")
     )
 )]
 pub async fn delete_attachment(
     State(state): State<SharedState>,
     Path(attachment_id): Path<Uuid>,
+    auth_user: Option<Extension<AuthUser>>,
 ) -> Result<String, WebError> {
-    // Just attempt deletion, don't validate if it exists
+    let reader = state.read().await;
+    let conn = &reader.conn;
+
+    check_attachment_access(conn, attachment_id, auth_user.as_deref()).await?;
+
+    reader.quota.invalidate([QuotaKind::AttachmentBytes]);
     match attachment::Entity::delete_by_id(attachment_id)
-        .exec(&state.read().await.conn)
+        .exec(conn)
         .await
         .map_err(|e| {
             error!("Failed to delete attachment: {:?}", e);
@@ -377,21 +783,81 @@ pub async fn delete_attachment(
     }
 }
 
-/// List all attachments for a node, does not include file data
+/// What to order a node's attachments by, ties are broken by ID
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AttachmentSort {
+    #[default]
+    Created,
+    Size,
+    /// The filename
+    Name,
+}
+
+impl AttachmentSort {
+    fn column(&self) -> attachment::Column {
+        match self {
+            AttachmentSort::Created => attachment::Column::Created,
+            AttachmentSort::Size => attachment::Column::Size,
+            AttachmentSort::Name => attachment::Column::Filename,
+        }
+    }
+
+    /// Newest and largest first, names alphabetically
+    fn default_order(&self) -> SortOrder {
+        match self {
+            AttachmentSort::Created | AttachmentSort::Size => SortOrder::Desc,
+            AttachmentSort::Name => SortOrder::Asc,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListAttachmentsQuery {
+    #[serde(default)]
+    pub sort: AttachmentSort,
+    /// Defaults to [AttachmentSort::default_order]
+    #[serde(default)]
+    pub order: Option<SortOrder>,
+}
+
+/// List all attachments for a node, newest first unless asked otherwise, does not include file data
 #[utoipa::path(
     get,
     path = "/api/v1/node/{id}/attachments",
+    tag = "attachments",
+    operation_id = "list_attachments",
+    params(
+        ("id" = Uuid, Path, description = "Node ID"),
+        ("sort" = Option<AttachmentSort>, Query, description = "What to order attachments by, defaults to created"),
+        ("order" = Option<SortOrder>, Query, description = "asc or desc, defaults to desc for created and size and asc for name")
+    ),
     responses(
-        (status = OK, description = "Attachments retrieved successfully", body = Vec<attachment::Model>)
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = OK, description = "Attachments retrieved successfully", body = Vec<attachment::Model>),
+        (status = FORBIDDEN, description = "Node belongs to another user's project"),
+        (status = NOT_FOUND, description = "Node not found")
     )
 )]
 pub async fn list_attachments(
     State(state): State<SharedState>,
     Path(node_id): Path<Uuid>,
+    Query(query): Query<ListAttachmentsQuery>,
+    auth_user: Option<Extension<AuthUser>>,
 ) -> Result<Json<Vec<attachment::Model>>, WebError> {
+    let conn = &state.read().await.conn;
+
+    check_node_access(conn, node_id, auth_user.as_deref()).await?;
+
+    let order: sea_orm::Order = query
+        .order
+        .unwrap_or_else(|| query.sort.default_order())
+        .into();
     let attachments = attachment::Entity::find()
         .filter(attachment::Column::NodeId.eq(node_id))
-        .all(&state.read().await.conn)
+        .order_by(query.sort.column(), order.clone())
+        .order_by(attachment::Column::Id, order)
+        .all(conn)
         .await
         .map_err(|e| {
             error!("Failed to list attachments: {:?}", e);
@@ -413,3 +879,62 @@ pub async fn list_attachments(
 
     Ok(Json(attachments))
 }
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+pub struct NodeAttachmentSummary {
+    pub node_id: Uuid,
+    pub count: u64,
+    /// Uncompressed bytes across the node's attachments
+    pub total_size: u64,
+}
+
+/// Attachment count and size for every node in a project which has any, so the client doesn't
+/// have to list each node's attachments
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/attachment-summary",
+    tag = "attachments",
+    operation_id = "get_project_attachment_summary",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    responses(
+        (status = OK, description = "Per-node attachment counts, ordered by node ID", body = Vec<NodeAttachmentSummary>),
+        (status = BAD_REQUEST, description = "Invalid path parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse)
+    )
+)]
+pub async fn get_project_attachment_summary(
+    State(state): State<SharedState>,
+    Path(id): Path<Uuid>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<Vec<NodeAttachmentSummary>>, WebError> {
+    let conn = &state.read().await.conn;
+    let project = project::Entity::find_by_id(id)
+        .one(conn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Project {} not found", id)))?;
+    check_project_access(conn, &project, auth_user.as_deref()).await?;
+
+    let summary = attachment::Entity::find()
+        .select_only()
+        .column(attachment::Column::NodeId)
+        .column_as(attachment::Column::Id.count(), "count")
+        .column_as(attachment::Column::Size.sum(), "total_size")
+        .join(JoinType::InnerJoin, attachment::Relation::Node.def())
+        .filter(node::Column::ProjectId.eq(id))
+        .group_by(attachment::Column::NodeId)
+        .order_by_asc(attachment::Column::NodeId)
+        .into_tuple::<(Uuid, i64, i64)>()
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|(node_id, count, total_size)| NodeAttachmentSummary {
+            node_id,
+            count: count.max(0) as u64,
+            total_size: total_size.max(0) as u64,
+        })
+        .collect();
+    Ok(Json(summary))
+}
@@ -0,0 +1,678 @@
+//! Attachment content hashes, the duplicate report and deduplication
+//!
+//! Every attachment records the SHA-256 of its uncompressed data, computed on upload. Rows from
+//! before the column existed are hashed by [spawn_attachment_hash_backfill] on startup, and the
+//! duplicates report, hash lookup and deduplication run the same backfill first so they never
+//! miss a legacy row.
+//!
+//! [post_deduplicate_attachments] starts a background job which moves the content of each group
+//! of duplicates into one [attachment_blob] row and empties their `data`, leaving every other
+//! column, and so access through the node, as it was. Jobs are kept in memory like report jobs,
+//! one runs at a time, and they can be cancelled between groups.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use axum::{
+    extract::State,
+    http::{header::LOCATION, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    sea_query::{Alias, Expr, Func, OnConflict, Query, SelectStatement},
+    ColumnTrait, DatabaseConnection, DbErr, EntityTrait, FromQueryResult, JoinType, QueryFilter,
+    QueryOrder, QuerySelect, RelationTrait, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    access::check_project_access,
+    attachment_codec::AttachmentCodec,
+    entity::{attachment, attachment_blob, node, project},
+    extract::Path,
+    oauth::middleware::AuthUser,
+    project::{ErrorResponse, WebError},
+    SharedState,
+};
+
+/// How many attachments the hash backfill loads at once
+pub const HASH_BACKFILL_BATCH_SIZE: u64 = 16;
+/// Error code when a deduplication is started while one is running
+pub const DEDUP_RUNNING: &str = "dedup_running";
+/// How long finished deduplication jobs are kept around for
+const DEDUP_JOB_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Hex SHA-256 of an attachment's uncompressed data
+pub fn content_hash(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Hash every attachment which doesn't have a `sha256` yet, returning how many were updated
+///
+/// Rows which fail to decode are logged and left alone.
+pub async fn backfill_attachment_hashes(conn: &DatabaseConnection) -> Result<u64, DbErr> {
+    let mut hashed = 0;
+    let mut failed: Vec<Uuid> = Vec::new();
+    loop {
+        let batch: Vec<(Uuid, AttachmentCodec, Vec<u8>)> = attachment::Entity::find()
+            .select_only()
+            .columns([
+                attachment::Column::Id,
+                attachment::Column::Codec,
+                attachment::Column::Data,
+            ])
+            .filter(attachment::Column::Sha256.is_null())
+            .filter(attachment::Column::Id.is_not_in(failed.clone()))
+            .limit(HASH_BACKFILL_BATCH_SIZE)
+            .into_tuple()
+            .all(conn)
+            .await?;
+        if batch.is_empty() {
+            break;
+        }
+        for (id, codec, data) in batch {
+            let hash =
+                tokio::task::spawn_blocking(move || codec.decode(&data).map(|d| content_hash(&d)))
+                    .await;
+            let hash = match hash {
+                Ok(Ok(hash)) => hash,
+                Ok(Err(err)) => {
+                    warn!(attachment_id = id.to_string(), error = ?err, "Failed to hash attachment");
+                    failed.push(id);
+                    continue;
+                }
+                Err(err) => {
+                    error!(attachment_id = id.to_string(), error = ?err, "Attachment hash task failed");
+                    failed.push(id);
+                    continue;
+                }
+            };
+            let res = attachment::Entity::update_many()
+                .col_expr(attachment::Column::Sha256, Expr::value(hash))
+                .filter(attachment::Column::Id.eq(id))
+                .filter(attachment::Column::Sha256.is_null())
+                .exec(conn)
+                .await?;
+            hashed += res.rows_affected;
+        }
+    }
+    Ok(hashed)
+}
+
+/// Spawns a task which hashes attachments stored before hashes were recorded
+pub fn spawn_attachment_hash_backfill(conn: DatabaseConnection) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        match backfill_attachment_hashes(&conn).await {
+            Ok(0) => debug!("No attachments to hash"),
+            Ok(hashed) => info!(hashed, "Backfilled attachment hashes"),
+            Err(err) => error!(error = ?err, "Attachment hash backfill failed"),
+        }
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DuplicateAttachment {
+    pub id: Uuid,
+    pub node_id: Uuid,
+    pub project_id: Uuid,
+    pub filename: String,
+    /// Bytes this copy takes up in the database, after compression
+    pub stored_size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DuplicateGroup {
+    pub sha256: String,
+    /// Uncompressed size of the content
+    pub size: u64,
+    pub attachments: Vec<DuplicateAttachment>,
+    /// Stored bytes beyond the smallest single copy, attachments sharing a blob store nothing
+    pub wasted_bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DuplicateReport {
+    pub groups: Vec<DuplicateGroup>,
+    /// Total of `wasted_bytes` across every group
+    pub reclaimable_bytes: u64,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct DuplicateRow {
+    id: Uuid,
+    node_id: Uuid,
+    project_id: Uuid,
+    filename: String,
+    sha256: String,
+    size: i64,
+    stored_size: i64,
+}
+
+/// Hashes more than one attachment has
+fn duplicated_hashes() -> SelectStatement {
+    Query::select()
+        .column(attachment::Column::Sha256)
+        .from(attachment::Entity)
+        .and_where(attachment::Column::Sha256.is_not_null())
+        .group_by_col(attachment::Column::Sha256)
+        .and_having(Expr::expr(Func::count(Expr::col(attachment::Column::Id))).gt(1))
+        .to_owned()
+}
+
+/// Attachments whose content is stored more than once, across every project
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/attachments/duplicates",
+    tag = "admin",
+    operation_id = "get_attachment_duplicates",
+    responses(
+        (status = OK, description = "Duplicate attachment groups", body = DuplicateReport),
+        (status = INTERNAL_SERVER_ERROR, description = "Database error", body = ErrorResponse)
+    )
+)]
+pub async fn get_attachment_duplicates(
+    State(state): State<SharedState>,
+) -> Result<Json<DuplicateReport>, WebError> {
+    let conn = state.read().await.conn.clone();
+    backfill_attachment_hashes(&conn).await?;
+
+    let rows = attachment::Entity::find()
+        .select_only()
+        .columns([
+            attachment::Column::Id,
+            attachment::Column::NodeId,
+            attachment::Column::Filename,
+            attachment::Column::Sha256,
+            attachment::Column::Size,
+        ])
+        .column(node::Column::ProjectId)
+        .column_as(
+            Expr::expr(
+                Func::cust(Alias::new("LENGTH"))
+                    .arg(Expr::col((attachment::Entity, attachment::Column::Data))),
+            ),
+            "stored_size",
+        )
+        .join(JoinType::InnerJoin, attachment::Relation::Node.def())
+        .filter(attachment::Column::Sha256.in_subquery(duplicated_hashes()))
+        .order_by_asc(attachment::Column::Created)
+        .into_model::<DuplicateRow>()
+        .all(&conn)
+        .await?;
+
+    let mut groups: BTreeMap<String, DuplicateGroup> = BTreeMap::new();
+    for row in rows {
+        let group = groups
+            .entry(row.sha256.clone())
+            .or_insert_with(|| DuplicateGroup {
+                sha256: row.sha256,
+                size: row.size.max(0) as u64,
+                attachments: Vec::new(),
+                wasted_bytes: 0,
+            });
+        group.attachments.push(DuplicateAttachment {
+            id: row.id,
+            node_id: row.node_id,
+            project_id: row.project_id,
+            filename: row.filename,
+            stored_size: row.stored_size.max(0) as u64,
+        });
+    }
+
+    let mut groups: Vec<DuplicateGroup> = groups.into_values().collect();
+    for group in groups.iter_mut() {
+        let sizes = group.attachments.iter().map(|a| a.stored_size);
+        group.wasted_bytes = sizes.clone().sum::<u64>() - sizes.min().unwrap_or(0);
+    }
+    // groups which already share a blob
+    groups.retain(|group| group.wasted_bytes > 0);
+    // worst offenders first
+    groups.sort_by_key(|group| std::cmp::Reverse(group.wasted_bytes));
+    let reclaimable_bytes = groups.iter().map(|group| group.wasted_bytes).sum();
+
+    Ok(Json(DuplicateReport {
+        groups,
+        reclaimable_bytes,
+    }))
+}
+
+/// Every attachment with this content, in the projects the caller can see, oldest first
+///
+/// `sha256` is the hex digest of the uncompressed data, in either case. Contents aren't included.
+#[utoipa::path(
+    get,
+    path = "/api/v1/attachment/by-hash/{sha256}",
+    tag = "attachments",
+    operation_id = "get_attachments_by_hash",
+    params(
+        ("sha256" = String, Path, description = "Hex SHA-256 of the attachment's content")
+    ),
+    responses(
+        (status = OK, description = "Matching attachments, empty if there are none", body = Vec<attachment::Model>),
+        (status = BAD_REQUEST, description = "Not a hex SHA-256", body = ErrorResponse)
+    )
+)]
+pub async fn get_attachments_by_hash(
+    State(state): State<SharedState>,
+    Path(sha256): Path<String>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<Vec<attachment::Model>>, WebError> {
+    let sha256 = sha256.to_ascii_lowercase();
+    if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            format!("{sha256} isn't a hex SHA-256"),
+        ));
+    }
+    let conn = state.read().await.conn.clone();
+    backfill_attachment_hashes(&conn).await?;
+
+    let rows: Vec<(attachment::ModelNoAttachment, Uuid)> = attachment::Entity::find()
+        .select_only()
+        .columns([
+            attachment::Column::Id,
+            attachment::Column::NodeId,
+            attachment::Column::Filename,
+            attachment::Column::ContentType,
+            attachment::Column::Size,
+            attachment::Column::Created,
+            attachment::Column::Codec,
+            attachment::Column::Sha256,
+            attachment::Column::Media,
+            attachment::Column::CreatedBy,
+            attachment::Column::SharedBlob,
+        ])
+        .column_as(node::Column::ProjectId, "project_id")
+        .join(JoinType::InnerJoin, attachment::Relation::Node.def())
+        .filter(attachment::Column::Sha256.eq(&sha256))
+        .order_by_asc(attachment::Column::Created)
+        .order_by_asc(attachment::Column::Id)
+        .into_model::<HashMatchRow>()
+        .all(&conn)
+        .await?
+        .into_iter()
+        .map(|row| (row.attachment, row.project_id))
+        .collect();
+
+    // attachments in other users' projects are left out rather than refused
+    let mut accessible: HashMap<Uuid, bool> = HashMap::new();
+    let mut res = Vec::new();
+    for (found, project_id) in rows {
+        let allowed = match accessible.get(&project_id) {
+            Some(allowed) => *allowed,
+            None => {
+                let allowed = match project::Entity::find_by_id(project_id).one(&conn).await? {
+                    Some(project) => check_project_access(&conn, &project, auth_user.as_deref())
+                        .await
+                        .is_ok(),
+                    None => false,
+                };
+                accessible.insert(project_id, allowed);
+                allowed
+            }
+        };
+        if allowed {
+            res.push(attachment::Model::from(found));
+        }
+    }
+    debug!(sha256, matches = res.len(), "Looked up attachments by hash");
+    Ok(Json(res))
+}
+
+#[derive(FromQueryResult)]
+struct HashMatchRow {
+    #[sea_orm(nested)]
+    attachment: attachment::ModelNoAttachment,
+    project_id: Uuid,
+}
+
+/// Where a deduplication job has got to
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DedupJobState {
+    Running,
+    Done,
+    /// Stopped early, the groups done by then stay shared
+    Cancelled,
+    Failed,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, ToSchema)]
+pub struct DedupProgress {
+    /// Groups of duplicates with content still to share when the job started
+    pub groups: u64,
+    pub groups_done: u64,
+    /// Attachments moved to a shared blob
+    pub attachments_shared: u64,
+    /// Stored bytes no longer held twice
+    pub bytes_reclaimed: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct DedupJobStatus {
+    pub job_id: Uuid,
+    pub state: DedupJobState,
+    pub progress: DedupProgress,
+    pub started: DateTime<Utc>,
+    pub finished: Option<DateTime<Utc>>,
+    /// Why the job failed
+    pub error: Option<String>,
+    pub status_url: String,
+}
+
+struct DedupJob {
+    status: DedupJobStatus,
+    cancel: Arc<AtomicBool>,
+}
+
+/// Deduplication jobs, shared with the task running them
+#[derive(Clone, Default)]
+pub struct DedupJobs(Arc<Mutex<HashMap<Uuid, DedupJob>>>);
+
+impl DedupJobs {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<Uuid, DedupJob>> {
+        // a panic while holding the lock can't leave the map half-updated
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Register a new running job unless one is already running, forgetting any which finished
+    /// a while ago
+    fn start(&self) -> Result<(DedupJobStatus, Arc<AtomicBool>), WebError> {
+        let now = Utc::now();
+        let mut jobs = self.lock();
+        jobs.retain(|_, job| match job.status.finished {
+            Some(finished) => (now - finished).to_std().unwrap_or_default() < DEDUP_JOB_TTL,
+            None => true,
+        });
+        if let Some(running) = jobs
+            .values()
+            .find(|job| job.status.state == DedupJobState::Running)
+        {
+            return Err(WebError::new(
+                StatusCode::CONFLICT,
+                "Attachments are already being deduplicated",
+            )
+            .with_code(DEDUP_RUNNING)
+            .with_detail("job_id", running.status.job_id.to_string()));
+        }
+        let job_id = Uuid::new_v4();
+        let status = DedupJobStatus {
+            job_id,
+            state: DedupJobState::Running,
+            progress: DedupProgress::default(),
+            started: now,
+            finished: None,
+            error: None,
+            status_url: format!("/api/v1/admin/attachments/deduplicate/{job_id}"),
+        };
+        let cancel = Arc::new(AtomicBool::new(false));
+        jobs.insert(
+            job_id,
+            DedupJob {
+                status: status.clone(),
+                cancel: cancel.clone(),
+            },
+        );
+        Ok((status, cancel))
+    }
+
+    fn status(&self, job_id: Uuid) -> Result<DedupJobStatus, WebError> {
+        self.lock()
+            .get(&job_id)
+            .map(|job| job.status.clone())
+            .ok_or_else(|| WebError::not_found(format!("Deduplication job {job_id} not found")))
+    }
+
+    fn progress(&self, job_id: Uuid, progress: &DedupProgress) {
+        if let Some(job) = self.lock().get_mut(&job_id) {
+            job.status.progress = progress.clone();
+        }
+    }
+
+    fn finish(&self, job_id: Uuid, state: DedupJobState, error: Option<String>) {
+        if let Some(job) = self.lock().get_mut(&job_id) {
+            job.status.state = state;
+            job.status.finished = Some(Utc::now());
+            job.status.error = error;
+        }
+    }
+}
+
+/// Move the content of every attachment with `sha256` into one shared blob, returning how many
+/// attachments were moved and how many stored bytes that saved
+///
+/// Only attachments whose data really hashes to `sha256` are touched, so a stale hash can't lose
+/// anything.
+async fn share_blob(conn: &DatabaseConnection, sha256: &str) -> Result<(u64, u64), WebError> {
+    let rows = attachment::Entity::find()
+        .filter(attachment::Column::Sha256.eq(sha256))
+        .filter(attachment::Column::SharedBlob.eq(false))
+        .all(conn)
+        .await?;
+    let mut verified = Vec::new();
+    for row in rows {
+        let (codec, data) = (row.codec, row.data.clone());
+        let hash =
+            tokio::task::spawn_blocking(move || codec.decode(&data).map(|d| content_hash(&d)))
+                .await;
+        match hash {
+            Ok(Ok(hash)) if hash == sha256 => verified.push(row),
+            Ok(Ok(hash)) => warn!(
+                attachment_id = row.id.to_string(),
+                recorded = sha256,
+                actual = hash,
+                "Attachment doesn't match its hash, leaving it alone"
+            ),
+            Ok(Err(err)) => {
+                warn!(attachment_id = row.id.to_string(), error = ?err, "Failed to decode attachment")
+            }
+            Err(err) => {
+                error!(attachment_id = row.id.to_string(), error = ?err, "Attachment hash task failed")
+            }
+        }
+    }
+
+    let txn = conn.begin().await?;
+    let blob = attachment_blob::Entity::find_by_id(sha256)
+        .one(&txn)
+        .await?;
+    let (codec, mut saved) = match blob {
+        Some(blob) => (blob.codec, 0),
+        None => {
+            // keep the smallest copy, and only once there's more than one to share it
+            let Some(kept) = verified.iter().min_by_key(|row| row.data.len()) else {
+                return Ok((0, 0));
+            };
+            if verified.len() < 2 {
+                return Ok((0, 0));
+            }
+            attachment_blob::Entity::insert(attachment_blob::ActiveModel {
+                sha256: sea_orm::Set(sha256.to_string()),
+                data: sea_orm::Set(kept.data.clone()),
+                codec: sea_orm::Set(kept.codec),
+                created: sea_orm::Set(Utc::now()),
+            })
+            .on_conflict(
+                OnConflict::column(attachment_blob::Column::Sha256)
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .exec_without_returning(&txn)
+            .await?;
+            (kept.codec, -(kept.data.len() as i64))
+        }
+    };
+    let mut shared = 0;
+    for row in verified {
+        // unless the data was replaced since it was checked
+        let res = attachment::Entity::update_many()
+            .col_expr(attachment::Column::Data, Expr::value(Vec::<u8>::new()))
+            .col_expr(attachment::Column::Codec, Expr::value(codec))
+            .col_expr(attachment::Column::SharedBlob, Expr::value(true))
+            .filter(attachment::Column::Id.eq(row.id))
+            .filter(attachment::Column::Sha256.eq(sha256))
+            .filter(attachment::Column::SharedBlob.eq(false))
+            .exec(&txn)
+            .await?;
+        if res.rows_affected > 0 {
+            shared += 1;
+            saved += row.data.len() as i64;
+        }
+    }
+    txn.commit().await?;
+    Ok((shared, saved.max(0) as u64))
+}
+
+/// Share the content of every group of duplicates, stopping early if `cancel` is set
+pub async fn deduplicate_attachments(
+    conn: &DatabaseConnection,
+    jobs: &DedupJobs,
+    job_id: Uuid,
+    cancel: &AtomicBool,
+) -> Result<DedupJobState, WebError> {
+    backfill_attachment_hashes(conn).await?;
+    let hashes: Vec<String> = attachment::Entity::find()
+        .select_only()
+        .column(attachment::Column::Sha256)
+        .distinct()
+        .filter(attachment::Column::SharedBlob.eq(false))
+        .filter(attachment::Column::Sha256.in_subquery(duplicated_hashes()))
+        .into_tuple()
+        .all(conn)
+        .await?;
+    let mut progress = DedupProgress {
+        groups: hashes.len() as u64,
+        ..Default::default()
+    };
+    jobs.progress(job_id, &progress);
+    for sha256 in hashes {
+        if cancel.load(Ordering::Relaxed) {
+            return Ok(DedupJobState::Cancelled);
+        }
+        let (shared, saved) = share_blob(conn, &sha256).await?;
+        debug!(sha256, shared, saved, "Shared attachment blob");
+        progress.groups_done += 1;
+        progress.attachments_shared += shared;
+        progress.bytes_reclaimed += saved;
+        jobs.progress(job_id, &progress);
+    }
+    Ok(DedupJobState::Done)
+}
+
+/// Start moving every group of duplicate attachments to a shared blob, in the background
+///
+/// Downloads and exports carry on returning the same bytes. Poll the returned `status_url` for
+/// progress.
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/attachments/deduplicate",
+    tag = "admin",
+    operation_id = "post_deduplicate_attachments",
+    responses(
+        (status = ACCEPTED, description = "The job was started", body = DedupJobStatus),
+        (status = CONFLICT, description = "A deduplication is already running", body = ErrorResponse)
+    )
+)]
+pub async fn post_deduplicate_attachments(
+    State(state): State<SharedState>,
+) -> Result<Response, WebError> {
+    let reader = state.read().await;
+    let jobs = reader.dedup_jobs.clone();
+    let (status, cancel) = jobs.start()?;
+    let job_id = status.job_id;
+    let conn = reader.conn.clone();
+    tokio::spawn(async move {
+        match deduplicate_attachments(&conn, &jobs, job_id, &cancel).await {
+            Ok(state) => {
+                info!(job_id = job_id.to_string(), state = ?state, "Finished deduplication job");
+                jobs.finish(job_id, state, None);
+            }
+            Err(err) => {
+                error!(
+                    job_id = job_id.to_string(),
+                    error = err.message(),
+                    "Deduplication job failed"
+                );
+                jobs.finish(
+                    job_id,
+                    DedupJobState::Failed,
+                    Some(err.message().to_string()),
+                );
+            }
+        }
+    });
+    info!(job_id = job_id.to_string(), "Started deduplication job");
+
+    Ok((
+        StatusCode::ACCEPTED,
+        [(LOCATION, HeaderValue::from_str(&status.status_url)?)],
+        Json(status),
+    )
+        .into_response())
+}
+
+/// How a deduplication job is going
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/attachments/deduplicate/{id}",
+    tag = "admin",
+    operation_id = "get_deduplicate_job",
+    params(
+        ("id" = Uuid, Path, description = "Job ID")
+    ),
+    responses(
+        (status = OK, description = "The job's progress", body = DedupJobStatus),
+        (status = NOT_FOUND, description = "No such job, or it finished over an hour ago", body = ErrorResponse)
+    )
+)]
+pub async fn get_deduplicate_job(
+    Path(job_id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Json<DedupJobStatus>, WebError> {
+    Ok(Json(state.read().await.dedup_jobs.status(job_id)?))
+}
+
+/// Stop a deduplication job once it's finished the group it's on
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/attachments/deduplicate/{id}",
+    tag = "admin",
+    operation_id = "cancel_deduplicate_job",
+    params(
+        ("id" = Uuid, Path, description = "Job ID")
+    ),
+    responses(
+        (status = OK, description = "The job's progress, it stops shortly if it's still running", body = DedupJobStatus),
+        (status = NOT_FOUND, description = "No such job, or it finished over an hour ago", body = ErrorResponse)
+    )
+)]
+pub async fn cancel_deduplicate_job(
+    Path(job_id): Path<Uuid>,
+    State(state): State<SharedState>,
+) -> Result<Json<DedupJobStatus>, WebError> {
+    let jobs = state.read().await.dedup_jobs.clone();
+    if let Some(job) = jobs.lock().get(&job_id) {
+        job.cancel.store(true, Ordering::Relaxed);
+    }
+    let status = jobs.status(job_id)?;
+    info!(job_id = job_id.to_string(), "Cancelling deduplication job");
+    Ok(Json(status))
+}
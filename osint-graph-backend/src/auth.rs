@@ -6,9 +6,13 @@ use axum::{
 use serde::Deserialize;
 use tower_sessions::Session;
 use tracing::*;
+use uuid::Uuid;
 
 use crate::{entity::user, SharedState};
-use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, PaginatorTrait, QueryFilter, Set,
+};
 
 // Query params for OAuth callback
 #[derive(Debug, Deserialize)]
@@ -86,11 +90,36 @@ pub(crate) async fn auth_callback(
                 "Database error".to_string(),
             )
         })? {
-        Some(u) => u,
+        Some(u) => {
+            let mut existing = u.into_active_model();
+            existing.last_login = Set(Some(Utc::now()));
+            existing.update(&reader.conn).await.map_err(|e| {
+                error!("Failed to record login: {:?}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Database error".to_string(),
+                )
+            })?
+        }
         None => {
+            // the first user gets to administer the instance
+            let first_user = user::Entity::find()
+                .count(&reader.conn)
+                .await
+                .map_err(|e| {
+                    error!("Failed to count users: {:?}", e);
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Database error".to_string(),
+                    )
+                })?
+                == 0;
             let new_user = user::ActiveModel {
                 subject: Set(subject.clone()),
                 email: Set(email.clone()),
+                uuid: Set(Uuid::new_v4()),
+                is_admin: Set(first_user),
+                last_login: Set(Some(Utc::now())),
                 ..Default::default()
             };
             new_user.insert(&reader.conn).await.map_err(|e| {
@@ -0,0 +1,314 @@
+//! What this server supports, so clients can hide what isn't available
+//!
+//! Every optional feature and limit is listed once in [FEATURES] or [LIMITS], along with the
+//! command line options which control it. Options which don't change what a client can do are
+//! listed in [INTERNAL_OPTIONS] instead, and a test checks every option is in one of the three.
+//!
+//! The payload is served without authentication, so it must never include secrets or URLs.
+
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::State,
+    http::{header, HeaderValue},
+    response::IntoResponse,
+    Json,
+};
+use osint_graph_shared::nodelink::LinkType;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{attachment, project, AppState, SharedState};
+
+/// Capabilities only change when the server restarts, so clients can hang on to them for a bit
+pub const CAPABILITIES_CACHE_CONTROL: &str = "public, max-age=300";
+
+/// Formats a project can be exported as, by the last segment of the export URL
+pub const EXPORT_FORMATS: &[&str] = &[
+    "json",
+    "mermaid",
+    "graphml",
+    "dot",
+    "jsonld",
+    "csv",
+    "timeline.json",
+    "report.pdf",
+];
+
+pub struct Feature {
+    pub name: &'static str,
+    /// Command line options which turn the feature on or off
+    pub options: &'static [&'static str],
+    pub enabled: fn(&AppState) -> bool,
+}
+
+pub struct Limit {
+    pub name: &'static str,
+    /// Command line options which set the limit
+    pub options: &'static [&'static str],
+    /// `None` for no limit
+    pub value: fn(&AppState) -> Option<u64>,
+}
+
+pub const FEATURES: &[Feature] = &[
+    Feature {
+        name: "oidc_auth",
+        options: &["oidc_client_id", "oidc_discovery_url"],
+        enabled: |state| state.oauth_client.is_some(),
+    },
+    Feature {
+        name: "value_policy",
+        options: &["value_policy_file"],
+        enabled: |state| !state.value_policy.policy.rules.is_empty(),
+    },
+    Feature {
+        name: "cross_origin_capture",
+        options: &["cors_allowed_origins"],
+        enabled: |state| !state.cors_allowed_origins.is_empty(),
+    },
+    Feature {
+        name: "quotas",
+        options: &["quota_warning_percent"],
+        enabled: |state| {
+            let limits = &state.quota.limits;
+            limits.max_projects.is_some()
+                || limits.max_nodes_per_project.is_some()
+                || limits.max_nodelinks_per_project.is_some()
+                || limits.max_total_attachment_bytes.is_some()
+        },
+    },
+    Feature {
+        name: "force_layout",
+        options: &[],
+        enabled: |state| state.max_layout_nodes > 0,
+    },
+    Feature {
+        name: "background_reports",
+        options: &[],
+        enabled: |_| true,
+    },
+    Feature {
+        name: "project_snapshots",
+        options: &[],
+        enabled: |_| true,
+    },
+    Feature {
+        name: "node_trash",
+        options: &[],
+        enabled: |_| true,
+    },
+    Feature {
+        name: "truncate_notes",
+        options: &["truncate_notes"],
+        enabled: |state| state.notes_limit.truncate,
+    },
+    Feature {
+        name: "export_push",
+        options: &["s3_endpoint", "s3_region"],
+        enabled: |state| state.s3.available(),
+    },
+    Feature {
+        name: "dev_proxy",
+        options: &["dev_proxy"],
+        #[cfg(debug_assertions)]
+        enabled: |state| state.dev_proxy.is_some(),
+        #[cfg(not(debug_assertions))]
+        enabled: |_| false,
+    },
+];
+
+pub const LIMITS: &[Limit] = &[
+    Limit {
+        name: "max_upload_bytes",
+        options: &[],
+        value: |_| Some(attachment::MAX_UPLOAD_BYTES as u64),
+    },
+    Limit {
+        name: "max_import_bytes",
+        options: &[],
+        value: |_| Some(project::MAX_IMPORT_BYTES as u64),
+    },
+    Limit {
+        name: "max_projects",
+        options: &["max_projects"],
+        value: |state| state.quota.limits.max_projects,
+    },
+    Limit {
+        name: "max_nodes_per_project",
+        options: &["max_nodes_per_project"],
+        value: |state| state.quota.limits.max_nodes_per_project,
+    },
+    Limit {
+        name: "max_nodelinks_per_project",
+        options: &["max_nodelinks_per_project"],
+        value: |state| state.quota.limits.max_nodelinks_per_project,
+    },
+    Limit {
+        name: "max_total_attachment_bytes",
+        options: &["max_total_attachment_bytes"],
+        value: |state| state.quota.limits.max_total_attachment_bytes,
+    },
+    Limit {
+        name: "max_layout_nodes",
+        options: &["max_layout_nodes"],
+        value: |state| Some(state.max_layout_nodes),
+    },
+    Limit {
+        name: "report_sync_max_nodes",
+        options: &["report_sync_max_nodes"],
+        value: |state| Some(state.report_sync_max_nodes),
+    },
+    Limit {
+        name: "max_notes_bytes",
+        options: &["max_notes_bytes"],
+        value: |state| state.notes_limit.max_bytes.map(|max| max as u64),
+    },
+    Limit {
+        name: "trash_retention_days",
+        options: &["trash_retention_days"],
+        value: |state| {
+            state
+                .trash_retention
+                .map(|retention| retention.num_days() as u64)
+        },
+    },
+];
+
+/// Command line options which don't change what clients can do
+pub const INTERNAL_OPTIONS: &[&str] = &[
+    "db_path",
+    "debug",
+    "tls_cert",
+    "tls_key",
+    "frontend_url",
+    "listener_address",
+    "session_cleanup_interval",
+    "cors_max_age_secs",
+    // served as default_link_type rather than a feature or limit
+    "default_link_type",
+    // styles are served by /api/v1/node-type-styles whether or not they're customised
+    "node_type_styles_file",
+    "attachment_codec",
+    "readiness_timeout_ms",
+    "db_max_connections",
+    "no_auto_migrate",
+    // a secret, and only concerns the scraper
+    "metrics_token",
+    "export_openapi",
+];
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMode {
+    /// Users log in with the configured OIDC provider
+    Oidc,
+    /// No login, every request is allowed
+    None,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct Capabilities {
+    pub version: String,
+    /// Every optional feature, and whether it's on
+    pub features: BTreeMap<String, bool>,
+    /// Size and count limits, `null` for unlimited
+    pub limits: BTreeMap<String, Option<u64>>,
+    pub export_formats: Vec<String>,
+    /// Type of the links the server creates by itself
+    pub default_link_type: LinkType,
+    pub auth_mode: AuthMode,
+    /// Whether writes are refused, always false until a read-only mode exists
+    pub read_only: bool,
+}
+
+impl Capabilities {
+    pub fn from_state(state: &AppState) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            features: FEATURES
+                .iter()
+                .map(|feature| (feature.name.to_string(), (feature.enabled)(state)))
+                .collect(),
+            limits: LIMITS
+                .iter()
+                .map(|limit| (limit.name.to_string(), (limit.value)(state)))
+                .collect(),
+            export_formats: EXPORT_FORMATS.iter().map(|f| f.to_string()).collect(),
+            default_link_type: state.default_link_type,
+            auth_mode: match state.oauth_client.is_some() {
+                true => AuthMode::Oidc,
+                false => AuthMode::None,
+            },
+            read_only: false,
+        }
+    }
+}
+
+/// Features and limits of this server, safe to fetch before logging in
+#[utoipa::path(
+    get,
+    path = "/api/v1/capabilities",
+    tag = "status",
+    operation_id = "get_capabilities",
+    responses(
+        (status = OK, description = "Server capabilities", body = Capabilities)
+    )
+)]
+pub async fn get_capabilities(State(state): State<SharedState>) -> impl IntoResponse {
+    let capabilities = Capabilities::from_state(&*state.read().await);
+    (
+        [(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(CAPABILITIES_CACHE_CONTROL),
+        )],
+        Json(capabilities),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use clap::CommandFactory;
+
+    use super::*;
+    use crate::cli::CliOpts;
+
+    #[test]
+    fn test_every_option_is_registered() {
+        let registered: Vec<&str> = FEATURES
+            .iter()
+            .flat_map(|feature| feature.options.iter())
+            .chain(LIMITS.iter().flat_map(|limit| limit.options.iter()))
+            .chain(INTERNAL_OPTIONS.iter())
+            .copied()
+            .collect();
+        let unique: BTreeSet<&str> = registered.iter().copied().collect();
+        assert_eq!(unique.len(), registered.len(), "options registered twice");
+
+        let command = CliOpts::command();
+        let options: BTreeSet<&str> = command
+            .get_arguments()
+            .map(|arg| arg.get_id().as_str())
+            .filter(|id| !matches!(*id, "help" | "version"))
+            .collect();
+        let missing: Vec<&&str> = options.difference(&unique).collect();
+        assert!(
+            missing.is_empty(),
+            "options missing from the capabilities registry: {missing:?}"
+        );
+        let stale: Vec<&&str> = unique.difference(&options).collect();
+        assert!(
+            stale.is_empty(),
+            "registry names unknown options: {stale:?}"
+        );
+    }
+
+    #[test]
+    fn test_registry_names_are_unique() {
+        let names: BTreeSet<&str> = FEATURES.iter().map(|feature| feature.name).collect();
+        assert_eq!(names.len(), FEATURES.len());
+        let names: BTreeSet<&str> = LIMITS.iter().map(|limit| limit.name).collect();
+        assert_eq!(names.len(), LIMITS.len());
+    }
+}
@@ -0,0 +1,233 @@
+//! Quick capture of web pages, for browser extensions and bookmarklets
+//!
+
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use chrono::Utc;
+use osint_graph_shared::node::NodeType;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use url::Url;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    access::check_project_access,
+    entity::{node, nodelink, project},
+    oauth::middleware::AuthUser,
+    project::{clean_url_value, ErrorResponse, WebError},
+    quota::{warning_headers, QuotaKind},
+    SharedState,
+};
+
+/// Query parameters which only track where a click came from
+const TRACKING_PARAMS: &[&str] = &["fbclid", "gclid", "mc_cid", "mc_eid"];
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CaptureRequest {
+    pub url: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Text selected on the page, stored as the node's notes
+    #[serde(default)]
+    pub selection_text: Option<String>,
+    /// Defaults to the Inbox
+    #[serde(default)]
+    pub project_id: Option<Uuid>,
+    /// Also add the URL's domain to the project and link it
+    #[serde(default)]
+    pub expand: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CaptureResponse {
+    /// The captured URL node, followed by the domain node if one was derived
+    pub nodes: Vec<node::Model>,
+    pub nodelinks: Vec<nodelink::Model>,
+    /// Link to the URL node in the web UI
+    pub deep_link: String,
+}
+
+/// Parse and tidy a captured URL, dropping the fragment and tracking parameters
+fn clean_capture_url(input: &str) -> Result<Url, WebError> {
+    let mut url = Url::parse(&clean_url_value(input))
+        .map_err(|err| WebError::new(StatusCode::BAD_REQUEST, format!("Invalid URL: {err}")))?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            "Only http and https URLs can be captured",
+        ));
+    }
+    url.set_fragment(None);
+    let params: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| !key.starts_with("utm_") && !TRACKING_PARAMS.contains(&key.as_ref()))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    if params.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(params);
+    }
+    Ok(url)
+}
+
+/// The page title if there is one, otherwise the host and path
+fn capture_display(url: &Url, title: Option<&str>) -> String {
+    match title.map(str::trim).filter(|title| !title.is_empty()) {
+        Some(title) => title.to_string(),
+        None => format!(
+            "{}{}",
+            url.host_str().unwrap_or_default(),
+            url.path().trim_end_matches('/')
+        ),
+    }
+}
+
+/// Capture a URL as a node, into the Inbox unless a project is given
+#[utoipa::path(
+    post,
+    path = "/api/v1/capture",
+    tag = "nodes",
+    operation_id = "post_capture",
+    request_body = CaptureRequest,
+    responses(
+        (status = OK, description = "URL captured", body = CaptureResponse),
+        (status = BAD_REQUEST, description = "Invalid URL", body = ErrorResponse),
+        (status = FORBIDDEN, description = "No access to the project", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse),
+        (status = UNPROCESSABLE_ENTITY, description = "A node breaks a value policy rule", body = ErrorResponse)
+    )
+)]
+pub async fn post_capture(
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+    Json(request): Json<CaptureRequest>,
+) -> Result<(axum::http::HeaderMap, Json<CaptureResponse>), WebError> {
+    let url = clean_capture_url(&request.url)?;
+    let display = capture_display(&url, request.title.as_deref());
+    let project_id = request.project_id.unwrap_or(Uuid::nil());
+
+    let reader = state.read().await;
+    let txn = reader.begin().await?;
+
+    let project = project::Entity::find_by_id(project_id)
+        .one(&txn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Project {} not found", project_id)))?;
+    check_project_access(&txn, &project, auth_user.as_deref()).await?;
+
+    let domain = match request.expand {
+        true => url
+            .host_str()
+            .map(|host| host.trim_start_matches("www.").to_lowercase()),
+        false => None,
+    };
+    let existing_domain = match &domain {
+        Some(domain) => {
+            node::Entity::find_live()
+                .filter(node::Column::ProjectId.eq(project_id))
+                .filter(node::Column::NodeType.eq(NodeType::Domain))
+                .filter(node::Column::Value.eq(domain))
+                .one(&txn)
+                .await?
+        }
+        None => None,
+    };
+    let new_nodes = 1 + u64::from(domain.is_some() && existing_domain.is_none());
+    let quota_kind = QuotaKind::NodesPerProject(project_id);
+    reader
+        .quota
+        .check(&txn, quota_kind, new_nodes, auth_user.as_deref())
+        .await?;
+
+    let now = Utc::now();
+    let created_by = AuthUser::created_by(auth_user.as_deref());
+    let mut url_node = node::Model {
+        id: Uuid::new_v4(),
+        project_id,
+        node_type: NodeType::Url,
+        display,
+        value: url.to_string(),
+        updated: now,
+        notes: request
+            .selection_text
+            .map(|text| text.trim().to_string())
+            .filter(|text| !text.is_empty()),
+        pos_x: None,
+        pos_y: None,
+        created_by: created_by.clone(),
+        created: Some(now),
+        deleted_at: None,
+    };
+    reader.value_policy.apply(&mut url_node)?;
+    let url_node = url_node
+        .into_active_model()
+        .insert(&txn)
+        .await
+        .inspect_err(|err| error!(error=?err, "Failed to insert captured node"))?;
+
+    let mut nodes = vec![url_node.clone()];
+    let mut nodelinks = Vec::new();
+    if let Some(domain) = domain {
+        let domain_node = match existing_domain {
+            Some(existing) => existing,
+            None => {
+                let mut domain_node = node::Model {
+                    id: Uuid::new_v4(),
+                    project_id,
+                    node_type: NodeType::Domain,
+                    display: domain.clone(),
+                    value: domain,
+                    updated: now,
+                    notes: None,
+                    pos_x: None,
+                    pos_y: None,
+                    created_by: created_by.clone(),
+                    created: Some(now),
+                    deleted_at: None,
+                };
+                reader.value_policy.apply(&mut domain_node)?;
+                domain_node.into_active_model().insert(&txn).await?
+            }
+        };
+        let link = nodelink::Model {
+            id: Uuid::new_v4(),
+            left: url_node.id,
+            right: domain_node.id,
+            project_id,
+            linktype: reader.default_link_type,
+            weight: None,
+            kind: None,
+            valid_from: None,
+            valid_to: None,
+            created_by,
+            created: Some(now),
+        }
+        .into_active_model()
+        .insert(&txn)
+        .await?;
+        nodes.push(domain_node);
+        nodelinks.push(link);
+    }
+
+    txn.commit().await?;
+    info!(
+        node_id = url_node.id.to_string(),
+        project_id = project_id.to_string(),
+        "Captured URL"
+    );
+
+    let warning = reader.quota.record(quota_kind, new_nodes);
+    Ok((
+        warning_headers(warning),
+        Json(CaptureResponse {
+            deep_link: format!(
+                "{}/#project={}&node={}",
+                reader.frontend_url, project_id, url_node.id
+            ),
+            nodes,
+            nodelinks,
+        }),
+    ))
+}
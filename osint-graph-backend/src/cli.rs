@@ -3,10 +3,13 @@
 
 use std::{net::TcpListener, path::PathBuf};
 
-use clap::Parser;
-use osint_graph_shared::Urls;
+use axum::http::HeaderValue;
+use clap::{Parser, Subcommand};
+use osint_graph_shared::{error::OsintError, nodelink::LinkType, Urls};
 use rand::Rng;
 
+use crate::{attachment_codec::AttachmentCodec, quota::QuotaLimits, text_limit::TextLimit};
+
 pub fn db_path_default() -> String {
     shellexpand::tilde("~/.cache/osint-graph.sqlite3").to_string()
 }
@@ -39,7 +42,7 @@ pub struct CliOpts {
     #[clap(
         long,
         env = "OSINT_GRAPH_TLS_CERT",
-        help = "Path to TLS certificate file"
+        help = "Path to TLS certificate file, re-read on SIGHUP"
     )]
     pub tls_cert: PathBuf,
     #[clap(long, env = "OSINT_GRAPH_TLS_KEY", help = "Path to TLS key file")]
@@ -63,11 +66,289 @@ pub struct CliOpts {
     )]
     pub oidc_discovery_url: String,
 
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_SESSION_CLEANUP_INTERVAL",
+        help = "How often to prune expired sessions, in seconds",
+        default_value_t = crate::sessions::DEFAULT_SESSION_CLEANUP_INTERVAL,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub session_cleanup_interval: u64,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_MAX_PROJECTS",
+        help = "Maximum number of projects on this instance"
+    )]
+    pub max_projects: Option<u64>,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_MAX_NODES_PER_PROJECT",
+        help = "Maximum number of nodes in a single project"
+    )]
+    pub max_nodes_per_project: Option<u64>,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_MAX_NODELINKS_PER_PROJECT",
+        help = "Maximum number of links in a single project, 0 for unlimited"
+    )]
+    pub max_nodelinks_per_project: Option<u64>,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_MAX_TOTAL_ATTACHMENT_BYTES",
+        help = "Maximum total size of all attachments, in bytes"
+    )]
+    pub max_total_attachment_bytes: Option<u64>,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_QUOTA_WARNING_PERCENT",
+        help = "Percentage of a limit at which to start warning clients",
+        default_value_t = crate::quota::DEFAULT_QUOTA_WARNING_PERCENT,
+        value_parser = clap::value_parser!(u8).range(1..=100)
+    )]
+    pub quota_warning_percent: u8,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_NODE_TYPE_STYLES_FILE",
+        help = "Path to a JSON file overriding the default node type colours, shapes and icons"
+    )]
+    pub node_type_styles_file: Option<PathBuf>,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_VALUE_POLICY_FILE",
+        help = "Path to a TOML file of rules rejecting, masking or warning about sensitive node values"
+    )]
+    pub value_policy_file: Option<PathBuf>,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_CORS_ALLOWED_ORIGINS",
+        help = "Origins allowed to make credentialed cross-origin requests, eg a browser extension",
+        value_delimiter = ','
+    )]
+    pub cors_allowed_origins: Vec<String>,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_CORS_MAX_AGE_SECS",
+        help = "How long browsers may cache CORS preflight responses, in seconds",
+        default_value_t = crate::middleware::DEFAULT_CORS_MAX_AGE_SECS
+    )]
+    pub cors_max_age_secs: u64,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_ATTACHMENT_CODEC",
+        help = "How to compress stored attachments, zstd is recommended. Existing attachments are re-encoded in the background",
+        value_enum,
+        default_value = "gzip"
+    )]
+    pub attachment_codec: AttachmentCodec,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_DEFAULT_LINK_TYPE",
+        help = "Type of the links the server creates by itself, eg when capture expands a URL. omni or directional",
+        default_value = "omni"
+    )]
+    pub default_link_type: LinkType,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_READINESS_TIMEOUT_MS",
+        help = "How long /readyz waits for the database before reporting unavailable, in milliseconds",
+        default_value_t = crate::status::DEFAULT_READINESS_TIMEOUT_MS,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub readiness_timeout_ms: u64,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_DB_MAX_CONNECTIONS",
+        help = "Most database connections to keep open. SQLite allows one writer at a time, but readers can use the others",
+        default_value_t = crate::storage::DEFAULT_DB_MAX_CONNECTIONS,
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub db_max_connections: u32,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_MAX_LAYOUT_NODES",
+        help = "Largest project, in nodes, which can be given a force-directed layout. Bigger projects can still use grid layout",
+        default_value_t = crate::layout::DEFAULT_MAX_LAYOUT_NODES
+    )]
+    pub max_layout_nodes: u64,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_MAX_NOTES_BYTES",
+        help = "Longest node notes or project description, in bytes, 0 for no limit",
+        default_value_t = crate::text_limit::DEFAULT_MAX_NOTES_BYTES
+    )]
+    pub max_notes_bytes: u64,
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_TRUNCATE_NOTES",
+        help = "Truncate notes and descriptions over --max-notes-bytes, with a warning header, instead of refusing them"
+    )]
+    pub truncate_notes: bool,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_REPORT_SYNC_MAX_NODES",
+        help = "Largest project, in nodes, whose PDF report is built during the request. Bigger projects get a background job",
+        default_value_t = crate::report::DEFAULT_REPORT_SYNC_MAX_NODES
+    )]
+    pub report_sync_max_nodes: u64,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_TRASH_RETENTION_DAYS",
+        help = "Purge nodes which have been in the trash this many days, 0 to keep them until they're purged by hand",
+        default_value_t = 0
+    )]
+    pub trash_retention_days: u64,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_METRICS_TOKEN",
+        help = "Require this bearer token to scrape /metrics, which is otherwise open to anyone who can reach the server"
+    )]
+    pub metrics_token: Option<String>,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_S3_ENDPOINT",
+        help = "S3-compatible service (eg MinIO) to push exports to instead of AWS. Credentials come from the usual AWS_* variables or ~/.aws/credentials"
+    )]
+    pub s3_endpoint: Option<url::Url>,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_S3_REGION",
+        help = "Region of the S3 buckets exports are pushed to",
+        default_value = crate::export_push::DEFAULT_S3_REGION
+    )]
+    pub s3_region: String,
+
+    #[cfg(debug_assertions)]
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_DEV_PROXY",
+        help = "Proxy the frontend from this dev server (eg http://localhost:5173) instead of serving ./dist/. Debug builds only"
+    )]
+    pub dev_proxy: Option<url::Url>,
+
+    #[clap(
+        long,
+        env = "OSINT_GRAPH_NO_AUTO_MIGRATE",
+        help = "Don't migrate the database on startup, refuse to start if it needs it. Use `osint-graph migrate` instead"
+    )]
+    pub no_auto_migrate: bool,
+
     #[clap(long, help = "Export the OpenAPI json file and exit")]
     pub export_openapi: bool,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Back up the database then apply pending migrations, or report on them
+    Migrate(MigrateOpts),
+    /// Replace the database with a backup, moving the current one aside
+    Restore(RestoreOpts),
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct MigrateOpts {
+    #[clap(long, help = "List applied and pending migrations and exit")]
+    pub status: bool,
+    #[clap(
+        long,
+        conflicts_with = "status",
+        help = "Apply pending migrations to a temporary copy and report, leaving the database alone"
+    )]
+    pub dry_run: bool,
+    #[clap(
+        long,
+        help = "Directory for the pre-migration backup, defaults to the database's directory"
+    )]
+    pub backup_dir: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug, Default)]
+pub struct RestoreOpts {
+    #[clap(
+        required_unless_present = "list_backups",
+        help = "Backup to restore, eg one taken by `osint-graph migrate`"
+    )]
+    pub backup: Option<PathBuf>,
+    #[clap(
+        long,
+        help = "Restore over an existing database, which is moved aside rather than deleted"
+    )]
+    pub force: bool,
+    #[clap(
+        long,
+        conflicts_with_all = ["backup", "force"],
+        help = "List backups with their sizes and schema versions and exit"
+    )]
+    pub list_backups: bool,
+    #[clap(
+        long,
+        help = "Directory to list backups from, defaults to the database's directory"
+    )]
+    pub backup_dir: Option<PathBuf>,
 }
 
 impl CliOpts {
+    pub fn db_path(&self) -> PathBuf {
+        self.db_path.clone().unwrap_or(db_path_default().into())
+    }
+
+    pub fn quota_limits(&self) -> QuotaLimits {
+        QuotaLimits {
+            max_projects: self.max_projects,
+            max_nodes_per_project: self.max_nodes_per_project,
+            max_nodelinks_per_project: self.max_nodelinks_per_project.filter(|max| *max > 0),
+            max_total_attachment_bytes: self.max_total_attachment_bytes,
+            warning_percent: self.quota_warning_percent,
+        }
+    }
+
+    pub fn notes_limit(&self) -> TextLimit {
+        TextLimit {
+            max_bytes: Some(self.max_notes_bytes as usize).filter(|max| *max > 0),
+            truncate: self.truncate_notes,
+        }
+    }
+
+    pub fn trash_retention(&self) -> Option<chrono::Duration> {
+        Some(self.trash_retention_days)
+            .filter(|days| *days > 0)
+            .and_then(|days| i64::try_from(days).ok())
+            .and_then(chrono::Duration::try_days)
+    }
+
+    pub fn cors_allowed_origins(&self) -> Result<Vec<HeaderValue>, OsintError> {
+        self.cors_allowed_origins
+            .iter()
+            .map(|origin| {
+                HeaderValue::from_str(origin.trim_end_matches('/')).map_err(|err| {
+                    OsintError::Configuration(format!("Invalid CORS origin {origin:?}: {err}"))
+                })
+            })
+            .collect()
+    }
+
     pub fn redirect_uri(&self) -> String {
         format!(
             "{}{}",
@@ -0,0 +1,164 @@
+//! Who added what to a project
+//!
+//! Nodes, links and attachments record the subject of the user who created them in `created_by`,
+//! this totals them up per user with one grouped query per kind.
+
+use std::collections::BTreeMap;
+
+use axum::{extract::State, Extension, Json};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DbErr, EntityTrait, JoinType, QueryFilter, QuerySelect,
+    RelationTrait, Select,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    access::check_project_access,
+    entity::{attachment, node, nodelink, project},
+    extract::Path,
+    oauth::middleware::AuthUser,
+    project::{ErrorResponse, WebError},
+    SharedState,
+};
+
+#[derive(Debug, Default, Deserialize, Serialize, ToSchema)]
+pub struct Contributor {
+    /// The user's subject, unset for things added while authentication was off
+    pub subject: Option<String>,
+    pub nodes: u64,
+    pub nodelinks: u64,
+    pub attachments: u64,
+    /// Unset if everything they added predates attribution
+    pub first_contribution: Option<DateTime<Utc>>,
+    pub last_contribution: Option<DateTime<Utc>>,
+}
+
+impl Contributor {
+    fn add_times(&mut self, first: Option<DateTime<Utc>>, last: Option<DateTime<Utc>>) {
+        self.first_contribution = match (self.first_contribution, first) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.last_contribution = self.last_contribution.max(last);
+    }
+}
+
+type Contributions = (
+    Option<String>,
+    i64,
+    Option<DateTime<Utc>>,
+    Option<DateTime<Utc>>,
+);
+
+/// Count, first and last creation time per `created_by`
+async fn contributions<E>(
+    conn: &impl ConnectionTrait,
+    select: Select<E>,
+    id: impl ColumnTrait,
+    created_by: impl ColumnTrait,
+    created: impl ColumnTrait,
+) -> Result<Vec<Contributions>, DbErr>
+where
+    E: EntityTrait,
+{
+    select
+        .select_only()
+        .column(created_by)
+        .column_as(id.count(), "total")
+        .column_as(created.min(), "first")
+        .column_as(created.max(), "last")
+        .group_by(created_by)
+        .into_tuple()
+        .all(conn)
+        .await
+}
+
+/// Everyone who added to a project and how much, ordered by subject
+pub async fn project_contributors(
+    conn: &impl ConnectionTrait,
+    project_id: Uuid,
+) -> Result<Vec<Contributor>, DbErr> {
+    let mut contributors: BTreeMap<Option<String>, Contributor> = BTreeMap::new();
+    let nodes = contributions(
+        conn,
+        node::Entity::find_live().filter(node::Column::ProjectId.eq(project_id)),
+        node::Column::Id,
+        node::Column::CreatedBy,
+        node::Column::Created,
+    )
+    .await?;
+    for (subject, total, first, last) in nodes {
+        let contributor = contributors.entry(subject).or_default();
+        contributor.nodes = total.max(0) as u64;
+        contributor.add_times(first, last);
+    }
+    let nodelinks = contributions(
+        conn,
+        nodelink::Entity::find_live().filter(nodelink::Column::ProjectId.eq(project_id)),
+        nodelink::Column::Id,
+        nodelink::Column::CreatedBy,
+        nodelink::Column::Created,
+    )
+    .await?;
+    for (subject, total, first, last) in nodelinks {
+        let contributor = contributors.entry(subject).or_default();
+        contributor.nodelinks = total.max(0) as u64;
+        contributor.add_times(first, last);
+    }
+    let attachments = contributions(
+        conn,
+        attachment::Entity::find()
+            .join(JoinType::InnerJoin, attachment::Relation::Node.def())
+            .filter(node::Column::ProjectId.eq(project_id)),
+        attachment::Column::Id,
+        attachment::Column::CreatedBy,
+        attachment::Column::Created,
+    )
+    .await?;
+    for (subject, total, first, last) in attachments {
+        let contributor = contributors.entry(subject).or_default();
+        contributor.attachments = total.max(0) as u64;
+        contributor.add_times(first, last);
+    }
+
+    Ok(contributors
+        .into_iter()
+        .map(|(subject, contributor)| Contributor {
+            subject,
+            ..contributor
+        })
+        .collect())
+}
+
+/// Who first reported the nodes, links and attachments in a project
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/contributors",
+    tag = "projects",
+    operation_id = "get_project_contributors",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    responses(
+        (status = OK, description = "Per-user counts, ordered by subject with unattributed first", body = Vec<Contributor>),
+        (status = BAD_REQUEST, description = "Invalid path parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse)
+    )
+)]
+pub async fn get_project_contributors(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<Vec<Contributor>>, WebError> {
+    let conn = &state.read().await.conn;
+    let project = project::Entity::find_by_id(id)
+        .one(conn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Project {} not found", id)))?;
+    check_project_access(conn, &project, auth_user.as_deref()).await?;
+    Ok(Json(project_contributors(conn, id).await?))
+}
@@ -0,0 +1,355 @@
+//! Database health, for telling pool exhaustion, lock contention and a runaway WAL apart when
+//! everything slows down under load
+//!
+//! Transactions started through [crate::AppState::begin] record how long they waited for a pooled
+//! connection, and requests failing with `SQLITE_BUSY` or `SQLITE_LOCKED` are counted by the
+//! metrics layer. SQLite doesn't report page cache hits through a pragma, so only the cache's size
+//! is given, to compare against the database's. Everything here is also exported on `/metrics`.
+
+use std::time::Instant;
+
+use axum::{extract::State, Json};
+use sea_orm::{
+    ConnectionTrait, DatabaseConnection, DatabaseTransaction, DbErr, RuntimeErr, Statement,
+    TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    metrics::Metrics,
+    project::{ErrorResponse, WebError},
+    storage_gc::pragma,
+    SharedState,
+};
+
+/// Error code for requests which failed because the database was busy or locked
+pub const DATABASE_BUSY: &str = "database_busy";
+
+/// 95th percentile connection wait, in milliseconds, above which the pool is too small
+pub const ACQUIRE_P95_HINT_MS: f64 = 50.0;
+/// WAL size above which checkpoints are falling behind
+pub const WAL_HINT_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Set on responses for [DATABASE_BUSY] errors, so the metrics layer can count them
+#[derive(Clone, Copy, Debug)]
+pub struct DatabaseBusy;
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Connections currently open
+    pub size: u32,
+    pub idle: u32,
+    pub in_use: u32,
+    /// From --db-max-connections
+    pub max_connections: u32,
+}
+
+impl PoolStats {
+    pub fn of(conn: &DatabaseConnection) -> Self {
+        let pool = conn.get_sqlite_connection_pool();
+        let size = pool.size();
+        let idle = u32::try_from(pool.num_idle()).unwrap_or(u32::MAX).min(size);
+        Self {
+            size,
+            idle,
+            in_use: size - idle,
+            max_connections: pool.options().get_max_connections(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DbHealthReport {
+    pub pool: PoolStats,
+    /// Transactions started since the server did
+    pub acquires: u64,
+    /// 95th percentile wait for a connection to start a transaction, to the nearest histogram
+    /// bucket, in milliseconds
+    pub acquire_p95_ms: Option<f64>,
+    pub acquire_max_ms: Option<f64>,
+    /// Requests which failed with `SQLITE_BUSY` or `SQLITE_LOCKED`
+    pub busy_errors: u64,
+    pub journal_mode: String,
+    /// Unset for in-memory databases, or when there's no WAL file
+    pub wal_bytes: Option<u64>,
+    pub database_bytes: u64,
+    /// Page cache each connection may use
+    pub cache_bytes: u64,
+    /// Suggestions worked out from the numbers above, empty when nothing stands out
+    pub hints: Vec<String>,
+}
+
+/// Start a transaction, recording how long it waited for a pooled connection
+pub async fn begin_timed(
+    conn: &DatabaseConnection,
+    metrics: &Metrics,
+) -> Result<DatabaseTransaction, DbErr> {
+    let start = Instant::now();
+    let txn = conn.begin().await;
+    metrics.record_acquire(start.elapsed());
+    txn
+}
+
+/// Whether `err` is SQLite giving up on a lock, after its busy timeout
+pub fn is_busy(err: &DbErr) -> bool {
+    let runtime = match err {
+        DbErr::Conn(err) | DbErr::Exec(err) | DbErr::Query(err) => err,
+        _ => return false,
+    };
+    match runtime {
+        RuntimeErr::SqlxError(sqlx::Error::Database(err)) => err
+            .code()
+            .and_then(|code| code.parse::<i64>().ok())
+            // extended codes keep the primary code in the low byte
+            .is_some_and(|code| matches!(code & 0xff, 5 | 6)),
+        RuntimeErr::SqlxError(_) => false,
+        RuntimeErr::Internal(message) => {
+            message.contains("database is locked") || message.contains("database is busy")
+        }
+    }
+}
+
+async fn pragma_text(conn: &impl ConnectionTrait, name: &str) -> Result<String, DbErr> {
+    let row = conn
+        .query_one(Statement::from_string(
+            conn.get_database_backend(),
+            format!("PRAGMA {name}"),
+        ))
+        .await?
+        .ok_or_else(|| DbErr::Custom(format!("PRAGMA {name} returned nothing")))?;
+    row.try_get_by_index::<String>(0)
+}
+
+/// Size of the main database's write-ahead log
+pub async fn wal_bytes(conn: &impl ConnectionTrait) -> Result<Option<u64>, DbErr> {
+    let databases = conn
+        .query_all(Statement::from_string(
+            conn.get_database_backend(),
+            "PRAGMA database_list",
+        ))
+        .await?;
+    for database in databases {
+        if database.try_get::<String>("", "name")? != "main" {
+            continue;
+        }
+        let file: String = database.try_get("", "file")?;
+        if file.is_empty() {
+            return Ok(None);
+        }
+        return Ok(std::fs::metadata(format!("{file}-wal"))
+            .ok()
+            .map(|metadata| metadata.len()));
+    }
+    Ok(None)
+}
+
+/// Suggestions for the operator, from thresholds on the report
+pub fn hints(report: &DbHealthReport) -> Vec<String> {
+    let mut hints = Vec::new();
+    if let Some(p95) = report
+        .acquire_p95_ms
+        .filter(|p95| *p95 > ACQUIRE_P95_HINT_MS)
+    {
+        hints.push(format!(
+            "Connection acquire p95 is {p95:.0}ms, over {ACQUIRE_P95_HINT_MS:.0}ms: consider raising --db-max-connections (currently {})",
+            report.pool.max_connections
+        ));
+    }
+    if report.pool.in_use >= report.pool.max_connections {
+        hints.push(format!(
+            "All {} pooled connections are in use, requests are queueing for one",
+            report.pool.max_connections
+        ));
+    }
+    if report.busy_errors > 0 {
+        hints.push(format!(
+            "{} requests failed because the database was locked: long write transactions such as big imports are holding it",
+            report.busy_errors
+        ));
+    }
+    if let Some(wal_bytes) = report.wal_bytes.filter(|bytes| *bytes > WAL_HINT_BYTES) {
+        hints.push(format!(
+            "The WAL is {} MiB: checkpoints are falling behind, usually because of long-running reads",
+            wal_bytes / (1024 * 1024)
+        ));
+    }
+    if report.pool.max_connections > 1 && !report.journal_mode.eq_ignore_ascii_case("wal") {
+        hints.push(format!(
+            "journal_mode is {} so writers block readers, `PRAGMA journal_mode=WAL` on the database lets the extra connections read during writes",
+            report.journal_mode
+        ));
+    }
+    hints
+}
+
+pub async fn db_health(
+    conn: &DatabaseConnection,
+    metrics: &Metrics,
+) -> Result<DbHealthReport, DbErr> {
+    let waits = metrics.acquire_waits();
+    let to_ms = |secs: f64| secs * 1000.0;
+    let page_size = pragma(conn, "page_size").await?;
+    let cache_size = conn
+        .query_one(Statement::from_string(
+            conn.get_database_backend(),
+            "PRAGMA cache_size",
+        ))
+        .await?
+        .map(|row| row.try_get_by_index::<i64>(0))
+        .transpose()?
+        .unwrap_or_default();
+    let mut report = DbHealthReport {
+        pool: PoolStats::of(conn),
+        acquires: waits.count(),
+        acquire_p95_ms: waits.quantile(0.95).map(to_ms),
+        acquire_max_ms: waits.max().map(to_ms),
+        busy_errors: metrics.busy_errors(),
+        journal_mode: pragma_text(conn, "journal_mode").await?,
+        wal_bytes: wal_bytes(conn).await?,
+        database_bytes: pragma(conn, "page_count").await? * page_size,
+        // negative sizes are in KiB rather than pages
+        cache_bytes: match cache_size {
+            size if size < 0 => size.unsigned_abs() * 1024,
+            size => size as u64 * page_size,
+        },
+        hints: Vec::new(),
+    };
+    report.hints = hints(&report);
+    Ok(report)
+}
+
+/// Connection pool usage, lock contention and WAL size, with tuning hints
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/db-health",
+    tag = "admin",
+    operation_id = "get_db_health",
+    responses(
+        (status = OK, description = "Database health and tuning hints", body = DbHealthReport),
+        (status = INTERNAL_SERVER_ERROR, description = "Database error", body = ErrorResponse)
+    )
+)]
+pub async fn get_db_health(
+    State(state): State<SharedState>,
+) -> Result<Json<DbHealthReport>, WebError> {
+    let (conn, metrics) = {
+        let reader = state.read().await;
+        (reader.conn.clone(), reader.metrics.clone())
+    };
+    Ok(Json(db_health(&conn, &metrics).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use super::*;
+    use crate::storage::connect_with;
+
+    #[tokio::test]
+    async fn test_saturated_pool() {
+        let db_path = std::env::temp_dir().join(format!(
+            "osint-graph-db-health-{}.sqlite3",
+            uuid::Uuid::new_v4()
+        ));
+        let conn = connect_with(Some(&db_path), 2).await.unwrap();
+        conn.execute_unprepared("CREATE TABLE evidence (id INTEGER PRIMARY KEY)")
+            .await
+            .unwrap();
+        let metrics = Arc::new(Metrics::default());
+
+        // four slow transactions on two connections, so two of them wait for the others
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let conn = conn.clone();
+                let metrics = metrics.clone();
+                tokio::spawn(async move {
+                    let txn = begin_timed(&conn, &metrics).await.unwrap();
+                    txn.execute_unprepared("SELECT 1").await.unwrap();
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    txn.commit().await.unwrap();
+                })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let busy = PoolStats::of(&conn);
+        assert_eq!(busy.max_connections, 2);
+        assert_eq!(busy.in_use, 2);
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let waits = metrics.acquire_waits();
+        assert_eq!(waits.count(), 4);
+        assert!(waits.max().unwrap() >= 0.1, "{waits:?}");
+        assert!(waits.quantile(0.95).unwrap() > ACQUIRE_P95_HINT_MS / 1000.0);
+        assert!(waits.quantile(0.25).unwrap() < ACQUIRE_P95_HINT_MS / 1000.0);
+
+        let report = db_health(&conn, &metrics).await.unwrap();
+        assert_eq!(report.acquires, 4);
+        assert!(report.database_bytes > 0);
+        assert!(report.cache_bytes > 0);
+        assert!(
+            report
+                .hints
+                .iter()
+                .any(|hint| hint.contains("--db-max-connections (currently 2)")),
+            "{:?}",
+            report.hints
+        );
+
+        conn.close().await.unwrap();
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", db_path.display()));
+        }
+    }
+
+    #[test]
+    fn test_hints() {
+        let mut report = DbHealthReport {
+            pool: PoolStats {
+                size: 4,
+                idle: 0,
+                in_use: 4,
+                max_connections: 4,
+            },
+            acquires: 10,
+            acquire_p95_ms: Some(10.0),
+            acquire_max_ms: Some(20.0),
+            busy_errors: 3,
+            journal_mode: "delete".to_string(),
+            wal_bytes: Some(WAL_HINT_BYTES * 2),
+            database_bytes: 4096,
+            cache_bytes: 2048,
+            hints: Vec::new(),
+        };
+        let found = hints(&report);
+        assert_eq!(found.len(), 4, "{found:?}");
+        assert!(found[0].contains("All 4 pooled connections"));
+        assert!(found[1].starts_with("3 requests failed"));
+        assert!(found[2].contains("128 MiB"));
+        assert!(found[3].contains("journal_mode is delete"));
+
+        report.pool = PoolStats {
+            size: 1,
+            idle: 1,
+            in_use: 0,
+            max_connections: 1,
+        };
+        report.busy_errors = 0;
+        report.wal_bytes = None;
+        assert!(hints(&report).is_empty());
+    }
+
+    #[test]
+    fn test_is_busy() {
+        assert!(is_busy(&DbErr::Exec(RuntimeErr::Internal(
+            "database is locked".to_string()
+        ))));
+        assert!(!is_busy(&DbErr::Exec(RuntimeErr::Internal(
+            "no such table: project".to_string()
+        ))));
+        assert!(!is_busy(&DbErr::Custom("database is locked".to_string())));
+    }
+}
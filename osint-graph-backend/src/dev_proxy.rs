@@ -0,0 +1,161 @@
+//! Development proxy for the frontend dev server
+//!
+//! With `--dev-proxy`, everything which isn't an API route is forwarded to the frontend's dev
+//! server instead of being served from `./dist/`. The browser only ever talks to the backend, so
+//! session cookies, OAuth redirects and CORS behave the same as they do in production, and the dev
+//! server's live-reload websocket is passed straight through.
+//!
+//! Only compiled into debug builds.
+
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{
+        header::{CONNECTION, HOST, UPGRADE},
+        uri::{Authority, Scheme},
+        HeaderName, HeaderValue, StatusCode, Uri,
+    },
+    response::{IntoResponse, Response},
+};
+use hyper_util::{
+    client::legacy::{connect::HttpConnector, Client},
+    rt::{TokioExecutor, TokioIo},
+};
+use osint_graph_shared::error::OsintError;
+use tracing::{debug, error, warn};
+use url::Url;
+
+/// Headers which only mean something for a single connection, dropped unless upgrading
+const HOP_BY_HOP_HEADERS: [&str; 7] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "upgrade",
+];
+
+pub const X_FORWARDED_HOST: HeaderName = HeaderName::from_static("x-forwarded-host");
+pub const X_FORWARDED_PROTO: HeaderName = HeaderName::from_static("x-forwarded-proto");
+
+#[derive(Clone)]
+pub struct DevProxy {
+    authority: Authority,
+    client: Client<HttpConnector, Body>,
+}
+
+impl DevProxy {
+    /// Proxy to the dev server at `upstream`, which must be a plain `http://` URL
+    pub fn new(upstream: &Url) -> Result<Self, OsintError> {
+        if upstream.scheme() != "http" {
+            return Err(OsintError::Configuration(format!(
+                "Dev proxy URL {upstream} must use http://"
+            )));
+        }
+        let host = upstream.host_str().ok_or_else(|| {
+            OsintError::Configuration(format!("Dev proxy URL {upstream} has no host"))
+        })?;
+        let authority = match upstream.port() {
+            Some(port) => format!("{host}:{port}"),
+            None => host.to_string(),
+        };
+        let authority = authority.parse::<Authority>().map_err(|err| {
+            OsintError::Configuration(format!("Invalid dev proxy URL {upstream}: {err}"))
+        })?;
+        Ok(Self {
+            authority,
+            client: Client::builder(TokioExecutor::new()).build_http(),
+        })
+    }
+}
+
+fn is_upgrade(request: &Request) -> bool {
+    request.headers().contains_key(UPGRADE)
+        && request
+            .headers()
+            .get_all(CONNECTION)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+}
+
+/// Forward a request to the dev server, piping the connection through if it's upgraded
+pub async fn proxy(State(proxy): State<Arc<DevProxy>>, mut request: Request) -> Response {
+    // unknown API routes are a backend 404, not something for the frontend to render
+    if request.uri().path().starts_with("/api/") {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let path_and_query = request
+        .uri()
+        .path_and_query()
+        .cloned()
+        .unwrap_or_else(|| "/".parse().expect("/ is a valid path"));
+    let uri = match Uri::builder()
+        .scheme(Scheme::HTTP)
+        .authority(proxy.authority.clone())
+        .path_and_query(path_and_query)
+        .build()
+    {
+        Ok(uri) => uri,
+        Err(err) => {
+            warn!(error = ?err, "Couldn't build dev proxy URI");
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+    };
+    *request.uri_mut() = uri;
+
+    let upgrade = is_upgrade(&request);
+    let headers = request.headers_mut();
+    if !upgrade {
+        for name in HOP_BY_HOP_HEADERS {
+            headers.remove(name);
+        }
+    }
+    if let Some(host) = headers.remove(HOST) {
+        headers.insert(X_FORWARDED_HOST, host);
+    }
+    headers.insert(X_FORWARDED_PROTO, HeaderValue::from_static("https"));
+    if let Ok(host) = HeaderValue::from_str(proxy.authority.as_str()) {
+        headers.insert(HOST, host);
+    }
+
+    let client_upgrade = upgrade.then(|| hyper::upgrade::on(&mut request));
+    debug!(
+        uri = request.uri().to_string(),
+        upgrade, "Proxying to dev server"
+    );
+
+    let mut response = match proxy.client.request(request).await {
+        Ok(response) => response,
+        Err(err) => {
+            error!(error = ?err, "Dev server request failed");
+            return (StatusCode::BAD_GATEWAY, "Dev server unavailable").into_response();
+        }
+    };
+
+    if let Some(client_upgrade) = client_upgrade {
+        if response.status() == StatusCode::SWITCHING_PROTOCOLS {
+            let upstream_upgrade = hyper::upgrade::on(&mut response);
+            tokio::spawn(async move {
+                let (client, upstream) = match tokio::try_join!(client_upgrade, upstream_upgrade) {
+                    Ok(upgraded) => upgraded,
+                    Err(err) => {
+                        warn!(error = ?err, "Dev proxy upgrade failed");
+                        return;
+                    }
+                };
+                let (mut client, mut upstream) = (TokioIo::new(client), TokioIo::new(upstream));
+                if let Err(err) = tokio::io::copy_bidirectional(&mut client, &mut upstream).await {
+                    debug!(error = ?err, "Dev proxy upgraded connection closed");
+                }
+            });
+        }
+    }
+
+    response.map(Body::new)
+}
@@ -1,9 +1,15 @@
+use std::collections::{HashMap, HashSet};
+
 use chrono::Utc;
 use sea_orm::{entity::prelude::*, FromQueryResult, JoinType, QuerySelect, SelectModel, Selector};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-use crate::entity::project;
+use crate::{
+    attachment_codec::AttachmentCodec,
+    entity::{attachment_blob, project},
+    media::MediaInfo,
+};
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
 #[sea_orm(table_name = "attachment")]
@@ -17,6 +23,23 @@ pub struct Model {
     #[sea_orm(column_type = "VarBinary(StringLen::Max)")]
     pub data: Vec<u8>,
     pub created: chrono::DateTime<Utc>,
+    /// How `data` is compressed
+    pub codec: AttachmentCodec,
+    /// Hex SHA-256 of the uncompressed data, unset until older rows have been backfilled
+    pub sha256: Option<String>,
+    /// Container metadata for audio and video files
+    #[sea_orm(column_type = "Text", nullable)]
+    #[serde(default)]
+    pub media: Option<MediaInfo>,
+    /// Subject of the user who uploaded it, unset when authentication is off. Set by the server
+    #[serde(default)]
+    #[schema(read_only)]
+    pub created_by: Option<String>,
+    /// The content is the shared [attachment_blob] with this `sha256` and `data` is empty, see
+    /// [load_shared_data]. Set by the server
+    #[serde(default)]
+    #[schema(read_only)]
+    pub shared_blob: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -47,6 +70,11 @@ pub struct ModelNoAttachment {
     pub content_type: String,
     pub size: i64,
     pub created: chrono::DateTime<Utc>,
+    pub codec: AttachmentCodec,
+    pub sha256: Option<String>,
+    pub media: Option<MediaInfo>,
+    pub created_by: Option<String>,
+    pub shared_blob: bool,
 }
 
 pub fn attachment_list(project_id: Uuid) -> Selector<SelectModel<ModelNoAttachment>> {
@@ -66,6 +94,7 @@ pub fn attachment_list(project_id: Uuid) -> Selector<SelectModel<ModelNoAttachme
                 .into(),
         )
         .filter(project::Column::Id.eq(project_id))
+        .filter(super::node::Column::DeletedAt.is_null())
         .columns([
             Column::Id,
             Column::NodeId,
@@ -73,6 +102,11 @@ pub fn attachment_list(project_id: Uuid) -> Selector<SelectModel<ModelNoAttachme
             Column::ContentType,
             Column::Size,
             Column::Created,
+            Column::Codec,
+            Column::Sha256,
+            Column::Media,
+            Column::CreatedBy,
+            Column::SharedBlob,
         ])
         .into_model::<ModelNoAttachment>()
 }
@@ -87,6 +121,50 @@ impl From<ModelNoAttachment> for Model {
             size: no_attachment.size,
             data: Vec::new(), // Data is not included in ModelNoAttachment
             created: no_attachment.created,
+            codec: no_attachment.codec,
+            sha256: no_attachment.sha256,
+            media: no_attachment.media,
+            created_by: no_attachment.created_by,
+            shared_blob: no_attachment.shared_blob,
         }
     }
 }
+
+/// Fill in `data` and `codec` of the attachments whose content is in a shared blob, so they can be
+/// read and copied like any other
+pub async fn load_shared_data(
+    conn: &impl ConnectionTrait,
+    attachments: &mut [Model],
+) -> Result<(), DbErr> {
+    let hashes: HashSet<String> = attachments
+        .iter()
+        .filter(|attachment| attachment.shared_blob)
+        .filter_map(|attachment| attachment.sha256.clone())
+        .collect();
+    if hashes.is_empty() {
+        return Ok(());
+    }
+    let blobs: HashMap<String, attachment_blob::Model> = attachment_blob::Entity::find()
+        .filter(attachment_blob::Column::Sha256.is_in(hashes))
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|blob| (blob.sha256.clone(), blob))
+        .collect();
+    for attachment in attachments.iter_mut().filter(|a| a.shared_blob) {
+        let blob = attachment
+            .sha256
+            .as_ref()
+            .and_then(|sha256| blobs.get(sha256))
+            .ok_or_else(|| {
+                DbErr::RecordNotFound(format!(
+                    "Shared blob for attachment {} not found",
+                    attachment.id
+                ))
+            })?;
+        attachment.data = blob.data.clone();
+        attachment.codec = blob.codec;
+        attachment.shared_blob = false;
+    }
+    Ok(())
+}
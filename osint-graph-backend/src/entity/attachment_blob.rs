@@ -0,0 +1,27 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+
+use crate::attachment_codec::AttachmentCodec;
+
+/// Content stored once for every attachment with the same hash, written by
+/// [crate::attachment_dedup::deduplicate_attachments]
+///
+/// Attachments point at it with [super::attachment::Model::shared_blob] and their `sha256`, so
+/// there's no foreign key, and blobs nothing points at any more are removed by the storage GC.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "attachment_blob")]
+pub struct Model {
+    /// Hex SHA-256 of the uncompressed data
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub sha256: String,
+    #[sea_orm(column_type = "VarBinary(StringLen::Max)")]
+    pub data: Vec<u8>,
+    /// How `data` is compressed
+    pub codec: AttachmentCodec,
+    pub created: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,53 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::export_push::{ExportPushFormat, ExportPushStatus};
+
+/// An export pushed to external storage by [crate::export_push::push_project_export]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize, ToSchema)]
+#[sea_orm(table_name = "export_record")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub format: ExportPushFormat,
+    /// Kind of destination, currently always `s3`
+    pub destination: String,
+    pub bucket: String,
+    pub object_key: String,
+    pub status: ExportPushStatus,
+    /// As returned by the destination once uploaded
+    pub etag: Option<String>,
+    /// Bytes uploaded
+    pub size: Option<i64>,
+    /// Uploads tried so far
+    pub attempts: i32,
+    /// Why the last attempt failed
+    pub error: Option<String>,
+    /// Subject of the user who asked for the push, unset when authentication is off
+    pub created_by: Option<String>,
+    pub created: DateTime<Utc>,
+    pub finished: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::project::Entity",
+        from = "Column::ProjectId",
+        to = "super::project::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Project,
+}
+
+impl Related<super::project::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Project.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
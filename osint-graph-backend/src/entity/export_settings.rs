@@ -0,0 +1,36 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+
+/// Per-project export options, currently just the snapshot schedule, see [crate::snapshot]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "export_settings")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub project_id: Uuid,
+    /// Seconds between snapshots, unset when scheduled snapshots are off
+    pub snapshot_interval_secs: Option<i64>,
+    /// Snapshots kept before the oldest are pruned
+    pub snapshot_keep: i32,
+    /// When the scheduler last looked at the project, whether or not it took a snapshot
+    pub last_snapshot_run: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::project::Entity",
+        from = "Column::ProjectId",
+        to = "super::project::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Project,
+}
+
+impl Related<super::project::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Project.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
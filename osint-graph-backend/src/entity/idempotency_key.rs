@@ -0,0 +1,30 @@
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+
+/// A POST made with an `Idempotency-Key` header and its response, see [crate::idempotency]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "idempotency_key")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    pub key: String,
+    /// The [crate::oauth::middleware::AuthUser::id] which sent it, nil when authentication is off
+    pub user_id: Uuid,
+    /// Method and path, eg `POST /api/v1/node`
+    pub endpoint: String,
+    /// Hex SHA-256 of the request body
+    pub request_hash: String,
+    /// Unset while the first request is still running
+    pub status: Option<i32>,
+    pub content_type: Option<String>,
+    /// Unset when the response was too large to keep
+    #[sea_orm(column_type = "VarBinary(StringLen::Max)", nullable)]
+    pub body: Option<Vec<u8>>,
+    pub created: DateTime<Utc>,
+    pub expires: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
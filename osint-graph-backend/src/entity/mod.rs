@@ -1,6 +1,14 @@
+pub mod api_token;
 pub mod attachment;
+pub mod attachment_blob;
+pub mod export_cache;
+pub mod export_record;
+pub mod export_settings;
+pub mod idempotency_key;
 pub mod node;
+pub mod node_history;
 pub mod nodelink;
 pub mod pkce_state;
 pub mod project;
+pub mod project_snapshot;
 pub mod user;
@@ -18,6 +18,18 @@ pub struct Model {
     pub notes: Option<String>,
     pub pos_x: Option<i32>,
     pub pos_y: Option<i32>,
+    /// Subject of the user who added it, unset when authentication is off. Set by the server
+    #[serde(default)]
+    #[schema(read_only)]
+    pub created_by: Option<String>,
+    /// When it was added, unset for nodes from before this was recorded. Set by the server
+    #[serde(default)]
+    #[schema(read_only)]
+    pub created: Option<DateTime<Utc>>,
+    /// When it was moved to the trash, unset for live nodes. Set by the server
+    #[serde(default)]
+    #[schema(read_only)]
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 impl Default for Model {
@@ -32,6 +44,9 @@ impl Default for Model {
             notes: None,
             pos_x: None,
             pos_y: None,
+            created_by: None,
+            created: None,
+            deleted_at: None,
         }
     }
 }
@@ -62,4 +77,20 @@ impl Related<super::attachment::Entity> for Entity {
     }
 }
 
+impl Entity {
+    /// Nodes which haven't been moved to the trash
+    pub fn find_live() -> Select<Entity> {
+        Self::find().filter(Column::DeletedAt.is_null())
+    }
+
+    /// IDs of the nodes in the trash, for filtering other tables
+    pub fn trashed_ids() -> sea_orm::sea_query::SelectStatement {
+        sea_orm::sea_query::Query::select()
+            .column(Column::Id)
+            .from(Entity)
+            .and_where(Column::DeletedAt.is_not_null())
+            .to_owned()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
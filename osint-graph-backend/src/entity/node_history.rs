@@ -0,0 +1,117 @@
+use chrono::{DateTime, Utc};
+use osint_graph_shared::node::NodeType;
+use sea_orm::entity::prelude::*;
+use sea_orm::Set;
+use sea_query::table::StringLen;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// What happened to the node, after which it no longer looked like [Model::previous]
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize, ToSchema,
+)]
+#[sea_orm(
+    rs_type = "String",
+    db_type = "String(StringLen::N(8))",
+    rename_all = "lowercase"
+)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeChange {
+    Update,
+    /// Moved to the trash
+    Delete,
+    /// Removed for good
+    Purge,
+    /// Folded into another node by [crate::project::merge_nodes] and removed
+    Merge,
+    /// Taken back out of the trash
+    Restore,
+}
+
+/// A node as it was before [crate::project::update_node] or [crate::project::delete_node]
+/// changed it
+///
+/// There's no relation to the node, so the history of a purged node is kept.
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "node_history")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    pub node_id: Uuid,
+    pub changed_at: DateTime<Utc>,
+    pub change: NodeChange,
+    /// The node serialized as JSON
+    pub previous: String,
+    /// Subject of the user who made the change, unset when authentication is off
+    pub changed_by: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    /// The node as it was before the change
+    pub fn previous_node(&self) -> Result<super::node::Model, serde_json::Error> {
+        serde_json::from_str(&self.previous)
+    }
+}
+
+impl ActiveModel {
+    /// A history entry recording `previous` before `change`
+    pub fn record(
+        previous: &super::node::Model,
+        change: NodeChange,
+        changed_by: Option<String>,
+    ) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            id: Set(Uuid::new_v4()),
+            node_id: Set(previous.id),
+            changed_at: Set(Utc::now()),
+            change: Set(change),
+            previous: Set(serde_json::to_string(previous)?),
+            changed_by: Set(changed_by),
+        })
+    }
+}
+
+/// A [Model] as served, with the previous node as JSON rather than a string of it
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct NodeHistoryEntry {
+    pub id: Uuid,
+    pub node_id: Uuid,
+    pub changed_at: DateTime<Utc>,
+    pub change: NodeChange,
+    /// The node before the change
+    #[schema(value_type = super::node::Model)]
+    pub previous: serde_json::Value,
+    /// Subject of the user who made the change, unset when authentication is off
+    pub changed_by: Option<String>,
+}
+
+impl From<Model> for NodeHistoryEntry {
+    fn from(model: Model) -> Self {
+        Self {
+            previous: serde_json::from_str(&model.previous)
+                .unwrap_or(serde_json::Value::String(model.previous)),
+            id: model.id,
+            node_id: model.node_id,
+            changed_at: model.changed_at,
+            change: model.change,
+            changed_by: model.changed_by,
+        }
+    }
+}
+
+/// A node's type changing, worked out from consecutive [Model]s by
+/// [crate::project::get_node_type_history]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct NodeTypeChange {
+    /// The history entry recorded by the change
+    pub id: Uuid,
+    pub node_id: Uuid,
+    pub from_type: NodeType,
+    pub to_type: NodeType,
+    pub changed: DateTime<Utc>,
+}
@@ -1,5 +1,7 @@
+use chrono::{DateTime, Utc};
 use osint_graph_shared::nodelink::LinkType;
 use sea_orm::entity::prelude::*;
+use sea_orm::Condition;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
@@ -13,6 +15,27 @@ pub struct Model {
     pub project_id: Uuid,
     #[sea_orm(column_type = "String(StringLen::N(15))")]
     pub linktype: LinkType,
+    /// Strength of the relationship, eg for edge thickness
+    #[serde(default)]
+    pub weight: Option<f32>,
+    /// What the relationship means, eg "owns" or "contacted". Also accepted as `kind`, its
+    /// name in older clients and exports
+    #[serde(default, rename = "label", alias = "kind")]
+    pub kind: Option<String>,
+    /// When the relationship started, open-ended if unset
+    #[serde(default)]
+    pub valid_from: Option<DateTime<Utc>>,
+    /// When the relationship ended, ongoing if unset
+    #[serde(default)]
+    pub valid_to: Option<DateTime<Utc>>,
+    /// Subject of the user who added it, unset when authentication is off. Set by the server
+    #[serde(default)]
+    #[schema(read_only)]
+    pub created_by: Option<String>,
+    /// When it was added, unset for links from before this was recorded. Set by the server
+    #[serde(default)]
+    #[schema(read_only)]
+    pub created: Option<DateTime<Utc>>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -33,4 +56,18 @@ impl Related<super::project::Entity> for Entity {
     }
 }
 
+impl Entity {
+    /// Links with neither end in the trash, they come back when the node is restored
+    pub fn find_live() -> Select<Entity> {
+        Self::find().filter(ends_live())
+    }
+}
+
+/// Neither end of the link is in the trash
+pub fn ends_live() -> Condition {
+    Condition::all()
+        .add(Column::Left.not_in_subquery(super::node::Entity::trashed_ids()))
+        .add(Column::Right.not_in_subquery(super::node::Entity::trashed_ids()))
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -15,6 +15,12 @@ pub struct Model {
     pub last_updated: Option<DateTime<Utc>>,
     pub description: Option<String>,
     pub tags: StringVec,
+    /// Pinned projects are listed first
+    #[serde(default)]
+    pub pinned: bool,
+    /// Archived projects are left out of the project list unless asked for
+    #[serde(default)]
+    pub is_archived: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
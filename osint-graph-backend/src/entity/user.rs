@@ -12,8 +12,14 @@ pub struct Model {
     #[sea_orm(unique)]
     pub email: String,
     pub display_name: Option<String>,
+    /// Stable identifier used for project ownership
+    #[sea_orm(unique)]
+    pub uuid: Uuid,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Can use the `/api/v1/admin` endpoints
+    pub is_admin: bool,
+    pub last_login: Option<DateTime<Utc>>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -0,0 +1,696 @@
+//! Export formats for nodes and projects
+//!
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use axum::{
+    extract::State,
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        HeaderValue, StatusCode,
+    },
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use osint_graph_shared::{node::NodeType, nodelink::LinkType};
+use sea_orm::{ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, QueryOrder};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    access::{check_node_access, find_accessible_project},
+    entity::{node, nodelink, project},
+    extract::{Path, Query},
+    oauth::middleware::AuthUser,
+    project::{node_neighbours, parse_node_types, ErrorResponse, ExportQuery, WebError},
+    redact::{redact, REDACTED},
+    SharedState,
+};
+
+pub const VCARD_CONTENT_TYPE: &str = "text/vcard; charset=utf-8";
+
+/// Escape a value for use in a vCard property
+fn vcard_escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+        .replace("\r\n", "\\n")
+        .replace(['\n', '\r'], "\\n")
+}
+
+/// Build a vCard 3.0 for a person, pulling contact details from its neighbours
+pub fn build_vcard(person: &node::Model, neighbours: &[node::Model]) -> String {
+    let display = vcard_escape(&person.display);
+    let mut lines = vec![
+        "BEGIN:VCARD".to_string(),
+        "VERSION:3.0".to_string(),
+        format!("FN:{display}"),
+        format!("N:;{display};;;"),
+    ];
+
+    for neighbour in neighbours {
+        let value = vcard_escape(neighbour.value.trim());
+        if value.is_empty() {
+            continue;
+        }
+        match neighbour.node_type {
+            NodeType::Email => lines.push(format!("EMAIL;TYPE=INTERNET:{value}")),
+            NodeType::Phone => lines.push(format!("TEL:{value}")),
+            NodeType::Url => lines.push(format!("URL:{value}")),
+            _ => {}
+        }
+    }
+
+    if let Some(notes) = person.notes.as_deref().filter(|n| !n.trim().is_empty()) {
+        lines.push(format!("NOTE:{}", vcard_escape(notes)));
+    }
+    lines.push(format!("REV:{}", person.updated.format("%Y%m%dT%H%M%SZ")));
+    lines.push("END:VCARD".to_string());
+
+    let mut res = lines.join("\r\n");
+    res.push_str("\r\n");
+    res
+}
+
+/// Export a Person node as a vCard, including linked emails, phone numbers and URLs
+#[utoipa::path(
+    get,
+    path = "/api/v1/node/{id}/export/vcard",
+    tag = "exports",
+    operation_id = "export_node_vcard",
+    params(
+        ("id" = Uuid, Path, description = "Node ID")
+    ),
+    responses(
+        (status = OK, description = "vCard exported successfully", body = String, content_type = "text/vcard"),
+        (status = BAD_REQUEST, description = "Node is not a person, or invalid path parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Node not found")
+    )
+)]
+pub async fn export_node_vcard(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<impl IntoResponse, WebError> {
+    let conn = &state.read().await.conn;
+    check_node_access(conn, id, auth_user.as_deref()).await?;
+
+    let person = node::Entity::find_by_id(id)
+        .filter(node::Column::DeletedAt.is_null())
+        .one(conn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Node {} not found", id)))?;
+
+    if person.node_type != NodeType::Person {
+        return Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Node {} is a {}, only person nodes can be exported as a vCard",
+                id, person.node_type
+            ),
+        ));
+    }
+
+    let neighbours = node_neighbours(conn, id).await?;
+    debug!(
+        node_id = id.to_string(),
+        neighbours = neighbours.len(),
+        "Exporting vCard"
+    );
+
+    Ok((
+        [
+            (
+                CONTENT_DISPOSITION,
+                HeaderValue::from_str(&format!(
+                    "attachment; filename=\"{}.vcf\"",
+                    person.display.replace('"', "'")
+                ))?,
+            ),
+            (CONTENT_TYPE, HeaderValue::from_static(VCARD_CONTENT_TYPE)),
+        ],
+        build_vcard(&person, &neighbours),
+    ))
+}
+
+/// Escape text for HTML, which both timeline libraries render
+pub fn html_escape(value: &str) -> String {
+    let mut res = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&#39;"),
+            '\n' => res.push_str("<br>"),
+            '\r' => {}
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimelineFlavor {
+    /// [TimelineJS](https://timeline.knightlab.com/docs/json-format.html)
+    #[default]
+    TimelineJs,
+    /// [vis-timeline](https://visjs.github.io/vis-timeline/docs/timeline/) items and groups
+    Vis,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TimelineExportQuery {
+    #[serde(default)]
+    pub flavor: TimelineFlavor,
+    /// Comma-separated list of node types to include, defaults to all of them
+    #[serde(default)]
+    pub node_types: Option<String>,
+    #[serde(default)]
+    pub redact: bool,
+}
+
+/// What was left off the timeline
+#[derive(Debug, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+pub struct TimelineMeta {
+    /// Items with no date to place them at, currently every link
+    pub undated: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+pub struct TimelineJsText {
+    pub headline: String,
+    #[serde(default)]
+    pub text: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+pub struct TimelineJsDate {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl From<DateTime<Utc>> for TimelineJsDate {
+    fn from(value: DateTime<Utc>) -> Self {
+        Self {
+            year: value.year(),
+            month: value.month(),
+            day: value.day(),
+            hour: value.hour(),
+            minute: value.minute(),
+            second: value.second(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+pub struct TimelineJsEvent {
+    pub unique_id: String,
+    pub start_date: TimelineJsDate,
+    pub text: TimelineJsText,
+    pub group: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct TimelineJsExport {
+    pub title: TimelineJsEvent,
+    pub events: Vec<TimelineJsEvent>,
+    pub meta: TimelineMeta,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+pub struct VisTimelineItem {
+    pub id: Uuid,
+    pub content: String,
+    /// RFC 3339
+    pub start: DateTime<Utc>,
+    pub group: String,
+    pub title: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+pub struct VisTimelineGroup {
+    pub id: String,
+    pub content: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct VisTimelineExport {
+    pub items: Vec<VisTimelineItem>,
+    pub groups: Vec<VisTimelineGroup>,
+    pub meta: TimelineMeta,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum TimelineExport {
+    TimelineJs(TimelineJsExport),
+    Vis(VisTimelineExport),
+}
+
+/// Lay out a project's dated nodes as a timeline
+///
+/// Nodes are placed at their last update, the only timestamp they have.
+pub fn build_timeline(
+    flavor: TimelineFlavor,
+    project: &project::Model,
+    nodes: &[node::Model],
+    undated: u64,
+) -> TimelineExport {
+    let meta = TimelineMeta { undated };
+    let node_text = |node: &node::Model| match node.notes.as_deref().map(str::trim) {
+        Some(notes) if !notes.is_empty() => {
+            format!("{}<br>{}", html_escape(&node.value), html_escape(notes))
+        }
+        _ => html_escape(&node.value),
+    };
+    match flavor {
+        TimelineFlavor::TimelineJs => TimelineExport::TimelineJs(TimelineJsExport {
+            title: TimelineJsEvent {
+                unique_id: project.id.to_string(),
+                start_date: project.creationdate.into(),
+                text: TimelineJsText {
+                    headline: html_escape(&project.name),
+                    text: project
+                        .description
+                        .as_deref()
+                        .map(html_escape)
+                        .unwrap_or_default(),
+                },
+                group: "project".to_string(),
+            },
+            events: nodes
+                .iter()
+                .map(|node| TimelineJsEvent {
+                    unique_id: node.id.to_string(),
+                    start_date: node.updated.into(),
+                    text: TimelineJsText {
+                        headline: html_escape(&node.display),
+                        text: node_text(node),
+                    },
+                    group: node.node_type.to_string(),
+                })
+                .collect(),
+            meta,
+        }),
+        TimelineFlavor::Vis => {
+            let groups: BTreeSet<NodeType> = nodes.iter().map(|node| node.node_type).collect();
+            TimelineExport::Vis(VisTimelineExport {
+                items: nodes
+                    .iter()
+                    .map(|node| VisTimelineItem {
+                        id: node.id,
+                        content: html_escape(&node.display),
+                        start: node.updated,
+                        group: node.node_type.to_string(),
+                        title: node_text(node),
+                    })
+                    .collect(),
+                groups: groups
+                    .into_iter()
+                    .map(|node_type| VisTimelineGroup {
+                        id: node_type.to_string(),
+                        content: html_escape(node_type.as_ref()),
+                    })
+                    .collect(),
+                meta,
+            })
+        }
+    }
+}
+
+/// Export a project's dated material for TimelineJS or vis-timeline
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/export/timeline.json",
+    tag = "exports",
+    operation_id = "export_project_timeline",
+    params(
+        ("id" = Uuid, Path, description = "Project ID to export"),
+        ("flavor" = Option<TimelineFlavor>, Query, description = "Which library's schema to produce, defaults to timelinejs"),
+        ("node_types" = Option<String>, Query, description = "Comma-separated node types to include, defaults to all"),
+        ("redact" = bool, Query, description = "Replace values with placeholders and strip attachments and project metadata")
+    ),
+    responses(
+        (status = OK, description = "Timeline exported successfully", body = TimelineExport),
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found")
+    )
+)]
+pub async fn export_project_timeline(
+    Path(id): Path<Uuid>,
+    Query(query): Query<TimelineExportQuery>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<impl IntoResponse, WebError> {
+    let node_types = parse_node_types("node_types", query.node_types.as_deref())?;
+    let conn = &state.read().await.conn;
+
+    let mut project = find_accessible_project(conn, id, auth_user.as_deref()).await?;
+
+    let mut nodes_query = node::Entity::find_live()
+        .filter(node::Column::ProjectId.eq(id))
+        .order_by_asc(node::Column::Updated);
+    if let Some(node_types) = node_types {
+        nodes_query = nodes_query.filter(node::Column::NodeType.is_in(node_types));
+    }
+    let mut nodes = nodes_query.all(conn).await?;
+    // links don't record when they were made, so they're all counted as undated
+    let node_ids: HashSet<Uuid> = nodes.iter().map(|node| node.id).collect();
+    let undated = nodelink::Entity::find_live()
+        .filter(nodelink::Column::ProjectId.eq(id))
+        .all(conn)
+        .await?
+        .iter()
+        .filter(|link| node_ids.contains(&link.left) && node_ids.contains(&link.right))
+        .count() as u64;
+    if query.redact {
+        redact(&mut project, &mut nodes, &mut [], &mut Vec::new());
+    }
+    debug!(
+        project_id = id.to_string(),
+        events = nodes.len(),
+        undated,
+        "Exporting timeline"
+    );
+
+    Ok((
+        [(
+            CONTENT_DISPOSITION,
+            HeaderValue::from_str(&format!(
+                "attachment; filename=\"{}.timeline.json\"",
+                project.name.replace('"', "'")
+            ))?,
+        )],
+        Json(build_timeline(query.flavor, &project, &nodes, undated)),
+    ))
+}
+
+pub const JSONLD_CONTENT_TYPE: &str = "application/ld+json";
+
+/// The schema.org type a node is published as
+pub fn schema_org_type(node_type: NodeType) -> &'static str {
+    match node_type {
+        NodeType::Person => "Person",
+        NodeType::Organisation => "Organization",
+        NodeType::Location => "Place",
+        NodeType::Image => "ImageObject",
+        NodeType::Document => "DigitalDocument",
+        NodeType::Url => "WebPage",
+        NodeType::Domain => "WebSite",
+        NodeType::Email | NodeType::Phone => "ContactPoint",
+        NodeType::Ip | NodeType::Currency => "Thing",
+    }
+}
+
+/// Nodes are identified by their UUID so the graph can be merged with other exports
+fn jsonld_id(id: Uuid) -> String {
+    format!("urn:uuid:{id}")
+}
+
+/// Build a schema.org JSON-LD document, one `@graph` entry per node
+///
+/// Links become `knows` between two people and `relatedTo` otherwise, on both ends unless the
+/// link is directional.
+pub fn build_jsonld(
+    project: &project::Model,
+    nodes: &[node::Model],
+    links: &[nodelink::Model],
+) -> serde_json::Value {
+    let node_types: HashMap<Uuid, NodeType> =
+        nodes.iter().map(|node| (node.id, node.node_type)).collect();
+    let mut related: HashMap<Uuid, Vec<(&'static str, Uuid)>> = HashMap::new();
+    for link in links {
+        let (Some(left), Some(right)) = (node_types.get(&link.left), node_types.get(&link.right))
+        else {
+            continue;
+        };
+        let property = match (left, right) {
+            (NodeType::Person, NodeType::Person) => "knows",
+            _ => "relatedTo",
+        };
+        related
+            .entry(link.left)
+            .or_default()
+            .push((property, link.right));
+        if link.linktype == LinkType::Omni {
+            related
+                .entry(link.right)
+                .or_default()
+                .push((property, link.left));
+        }
+    }
+
+    let graph: Vec<serde_json::Value> = nodes
+        .iter()
+        .map(|node| {
+            let mut entry = serde_json::Map::new();
+            entry.insert("@id".into(), jsonld_id(node.id).into());
+            entry.insert("@type".into(), schema_org_type(node.node_type).into());
+            entry.insert("name".into(), node.display.clone().into());
+            let value = node.value.trim();
+            if !value.is_empty() {
+                entry.insert("identifier".into(), value.into());
+                let property = match node.node_type {
+                    NodeType::Url => Some("url"),
+                    NodeType::Email => Some("email"),
+                    NodeType::Phone => Some("telephone"),
+                    NodeType::Image if value.starts_with("http") => Some("contentUrl"),
+                    _ => None,
+                };
+                if let Some(property) = property {
+                    entry.insert(property.into(), value.into());
+                }
+            }
+            if let Some(notes) = node.notes.as_deref().filter(|n| !n.trim().is_empty()) {
+                entry.insert("description".into(), notes.into());
+            }
+            entry.insert("dateModified".into(), node.updated.to_rfc3339().into());
+            for (property, target) in related.remove(&node.id).unwrap_or_default() {
+                let target = serde_json::json!({ "@id": jsonld_id(target) });
+                match entry
+                    .entry(property)
+                    .or_insert_with(|| serde_json::Value::Array(Vec::new()))
+                {
+                    serde_json::Value::Array(targets) if !targets.contains(&target) => {
+                        targets.push(target)
+                    }
+                    _ => {}
+                }
+            }
+            serde_json::Value::Object(entry)
+        })
+        .collect();
+
+    serde_json::json!({
+        "@context": "https://schema.org",
+        "@id": jsonld_id(project.id),
+        "name": project.name,
+        "@graph": graph,
+    })
+}
+
+/// [build_jsonld] for a whole project, pretty printed
+pub(crate) async fn render_project_jsonld(
+    conn: &impl ConnectionTrait,
+    project: &project::Model,
+    redacted: bool,
+) -> Result<String, WebError> {
+    let mut nodes = node::Entity::find_live()
+        .filter(node::Column::ProjectId.eq(project.id))
+        .order_by_asc(node::Column::Display)
+        .order_by_asc(node::Column::Id)
+        .all(conn)
+        .await?;
+    let mut links = nodelink::Entity::find_live()
+        .filter(nodelink::Column::ProjectId.eq(project.id))
+        .order_by_asc(nodelink::Column::Id)
+        .all(conn)
+        .await?;
+    debug!(
+        project_id = project.id.to_string(),
+        nodes = nodes.len(),
+        links = links.len(),
+        redacted,
+        "Exporting JSON-LD"
+    );
+    let mut project = project.clone();
+    if redacted {
+        redact(&mut project, &mut nodes, &mut links, &mut Vec::new());
+    }
+
+    serde_json::to_string_pretty(&build_jsonld(&project, &nodes, &links))
+        .map_err(|err| WebError::internal_server_error(format!("Failed to build JSON-LD: {err}")))
+}
+
+/// Export a project as schema.org JSON-LD, for publishing findings on the web
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/export/jsonld",
+    tag = "exports",
+    operation_id = "export_project_jsonld",
+    params(
+        ("id" = Uuid, Path, description = "Project ID to export"),
+        ("redact" = bool, Query, description = "Replace values with placeholders and strip attachments and project metadata")
+    ),
+    responses(
+        (status = OK, description = "JSON-LD exported successfully", body = Object, content_type = "application/ld+json"),
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found")
+    )
+)]
+pub async fn export_project_jsonld(
+    Path(id): Path<Uuid>,
+    Query(query): Query<ExportQuery>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<impl IntoResponse, WebError> {
+    let conn = &state.read().await.conn;
+
+    let project = find_accessible_project(conn, id, auth_user.as_deref()).await?;
+    let body = render_project_jsonld(conn, &project, query.redact).await?;
+    Ok((
+        [
+            (CONTENT_TYPE, HeaderValue::from_static(JSONLD_CONTENT_TYPE)),
+            (
+                CONTENT_DISPOSITION,
+                HeaderValue::from_str(&format!(
+                    "attachment; filename=\"{}.jsonld\"",
+                    if query.redact {
+                        REDACTED.to_string()
+                    } else {
+                        project.name.replace('"', "'")
+                    }
+                ))?,
+            ),
+        ],
+        body,
+    ))
+}
+
+pub const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+pub const CSV_COLUMNS: [&str; 8] = [
+    "id",
+    "node_type",
+    "display",
+    "value",
+    "notes",
+    "pos_x",
+    "pos_y",
+    "updated",
+];
+
+/// Quote a CSV field if it needs it, per RFC 4180
+fn csv_field(value: &str) -> std::borrow::Cow<'_, str> {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\"")).into()
+    } else {
+        value.into()
+    }
+}
+
+/// One CRLF-terminated row per node, after a header row of [CSV_COLUMNS]
+pub fn build_nodes_csv(nodes: &[node::Model]) -> String {
+    let mut csv = CSV_COLUMNS.join(",");
+    csv.push_str("\r\n");
+    for node in nodes {
+        let row = [
+            node.id.to_string(),
+            node.node_type.to_string(),
+            csv_field(&node.display).into_owned(),
+            csv_field(&node.value).into_owned(),
+            csv_field(node.notes.as_deref().unwrap_or_default()).into_owned(),
+            node.pos_x.map(|x| x.to_string()).unwrap_or_default(),
+            node.pos_y.map(|y| y.to_string()).unwrap_or_default(),
+            node.updated.to_rfc3339(),
+        ];
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+/// A project name made safe for a `Content-Disposition` filename
+fn disposition_filename(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .filter(|c| !c.is_control())
+        .map(|c| match c {
+            '"' | '/' | '\\' => '_',
+            c => c,
+        })
+        .collect();
+    match name.trim() {
+        "" => "project".to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// Export a project's nodes as CSV, for spreadsheets
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/export/csv",
+    tag = "exports",
+    operation_id = "export_project_csv",
+    params(
+        ("id" = Uuid, Path, description = "Project ID to export"),
+        ("redact" = bool, Query, description = "Replace values with placeholders and strip attachments and project metadata")
+    ),
+    responses(
+        (status = OK, description = "One row per node, ordered by creation", body = String, content_type = "text/csv"),
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse)
+    )
+)]
+pub async fn export_project_csv(
+    Path(id): Path<Uuid>,
+    Query(query): Query<ExportQuery>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<impl IntoResponse, WebError> {
+    let conn = &state.read().await.conn;
+
+    let mut project = find_accessible_project(conn, id, auth_user.as_deref()).await?;
+    let mut nodes = node::Entity::find_live()
+        .filter(node::Column::ProjectId.eq(id))
+        .order_by_asc(node::Column::Created)
+        .order_by_asc(node::Column::Id)
+        .all(conn)
+        .await?;
+    if query.redact {
+        redact(&mut project, &mut nodes, &mut [], &mut Vec::new());
+    }
+    debug!(
+        project_id = id.to_string(),
+        nodes = nodes.len(),
+        "Exporting CSV"
+    );
+
+    Ok((
+        [
+            (CONTENT_TYPE, HeaderValue::from_static(CSV_CONTENT_TYPE)),
+            (
+                CONTENT_DISPOSITION,
+                HeaderValue::from_str(&format!(
+                    "attachment; filename=\"{}.csv\"",
+                    disposition_filename(&project.name)
+                ))?,
+            ),
+        ],
+        build_nodes_csv(&nodes),
+    ))
+}
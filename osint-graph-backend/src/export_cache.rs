@@ -0,0 +1,259 @@
+//! Cache of rendered project exports
+//!
+//! Entries are keyed on the project, export format, a hash of the export's filter options and a
+//! fingerprint of the project's content. Any change to the project changes the fingerprint, so
+//! stale entries are never served, they just stop being hit and eventually get evicted.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use axum::http::HeaderName;
+use chrono::Utc;
+use osint_graph_shared::nodelink::LinkType;
+use sea_orm::{
+    sea_query::OnConflict, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, JoinType, QueryFilter,
+    QueryOrder, QuerySelect, RelationTrait, Set,
+};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::entity::{attachment, export_cache, node, nodelink, project};
+
+/// Response header saying whether an export came from the cache, `hit` or `miss`
+pub const CACHE_HEADER: HeaderName = HeaderName::from_static("x-cache");
+
+pub const DEFAULT_MAX_ENTRIES_PER_PROJECT: u64 = 16;
+/// Exports larger than this aren't worth keeping in the database
+pub const DEFAULT_MAX_ENTRY_BYTES: usize = 1024 * 1024;
+
+fn hash_to_string(value: &impl Hash) -> String {
+    // DefaultHasher isn't guaranteed stable across Rust releases, which only costs us cache misses
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Hash of the options an export was filtered with
+pub fn filter_hash(filter: &impl Hash) -> String {
+    hash_to_string(filter)
+}
+
+type LinkFingerprint = (
+    Uuid,
+    Uuid,
+    Uuid,
+    LinkType,
+    Option<f32>,
+    Option<String>,
+    Option<chrono::DateTime<Utc>>,
+    Option<chrono::DateTime<Utc>>,
+);
+
+/// A cheap fingerprint of everything in a project that can affect its exports
+///
+/// Only the narrow identifying columns are read, never attachment data. `extra` is mixed in for
+/// anything else the rendering depends on, such as the configured node styles.
+pub async fn project_fingerprint(
+    conn: &impl ConnectionTrait,
+    project: &project::Model,
+    extra: &impl Hash,
+) -> Result<String, DbErr> {
+    let nodes: Vec<(Uuid, chrono::DateTime<Utc>)> = node::Entity::find_live()
+        .select_only()
+        .columns([node::Column::Id, node::Column::Updated])
+        .filter(node::Column::ProjectId.eq(project.id))
+        .order_by_asc(node::Column::Id)
+        .into_tuple()
+        .all(conn)
+        .await?;
+    let links: Vec<LinkFingerprint> = nodelink::Entity::find_live()
+        .select_only()
+        .columns([
+            nodelink::Column::Id,
+            nodelink::Column::Left,
+            nodelink::Column::Right,
+            nodelink::Column::Linktype,
+            nodelink::Column::Weight,
+            nodelink::Column::Kind,
+            nodelink::Column::ValidFrom,
+            nodelink::Column::ValidTo,
+        ])
+        .filter(nodelink::Column::ProjectId.eq(project.id))
+        .order_by_asc(nodelink::Column::Id)
+        .into_tuple()
+        .all(conn)
+        .await?;
+    // f32 isn't Hash, so the weight goes in as its bits
+    let links: Vec<_> = links
+        .into_iter()
+        .map(
+            |(id, left, right, linktype, weight, kind, valid_from, valid_to)| {
+                (
+                    id,
+                    left,
+                    right,
+                    linktype,
+                    weight.map(f32::to_bits),
+                    kind,
+                    valid_from,
+                    valid_to,
+                )
+            },
+        )
+        .collect();
+    let attachments: Vec<(Uuid, Uuid, String)> = attachment::Entity::find()
+        .select_only()
+        .columns([
+            attachment::Column::Id,
+            attachment::Column::NodeId,
+            attachment::Column::Filename,
+        ])
+        .join(JoinType::InnerJoin, attachment::Relation::Node.def())
+        .filter(node::Column::ProjectId.eq(project.id))
+        .filter(node::Column::DeletedAt.is_null())
+        .order_by_asc(attachment::Column::Id)
+        .into_tuple()
+        .all(conn)
+        .await?;
+
+    Ok(hash_to_string(&(
+        env!("CARGO_PKG_VERSION"),
+        &project.name,
+        &project.description,
+        project.last_updated,
+        nodes,
+        links,
+        attachments,
+        extra,
+    )))
+}
+
+#[derive(Clone, Debug)]
+pub struct ExportCacheKey {
+    pub project_id: Uuid,
+    pub format: &'static str,
+    pub filter_hash: String,
+    pub fingerprint: String,
+}
+
+impl ExportCacheKey {
+    fn filter(&self) -> sea_orm::Condition {
+        sea_orm::Condition::all()
+            .add(export_cache::Column::ProjectId.eq(self.project_id))
+            .add(export_cache::Column::Format.eq(self.format))
+            .add(export_cache::Column::FilterHash.eq(&self.filter_hash))
+            .add(export_cache::Column::Fingerprint.eq(&self.fingerprint))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ExportCache {
+    /// Least recently used entries beyond this are evicted
+    pub max_entries_per_project: u64,
+    pub max_entry_bytes: usize,
+}
+
+impl Default for ExportCache {
+    fn default() -> Self {
+        Self {
+            max_entries_per_project: DEFAULT_MAX_ENTRIES_PER_PROJECT,
+            max_entry_bytes: DEFAULT_MAX_ENTRY_BYTES,
+        }
+    }
+}
+
+impl ExportCache {
+    /// Fetch a cached export, marking it as recently used
+    pub async fn get(
+        &self,
+        conn: &impl ConnectionTrait,
+        key: &ExportCacheKey,
+    ) -> Result<Option<String>, DbErr> {
+        let Some(entry) = export_cache::Entity::find()
+            .filter(key.filter())
+            .one(conn)
+            .await?
+        else {
+            return Ok(None);
+        };
+        export_cache::Entity::update_many()
+            .col_expr(
+                export_cache::Column::LastUsed,
+                sea_orm::sea_query::Expr::value(Utc::now()),
+            )
+            .filter(export_cache::Column::Id.eq(entry.id))
+            .exec(conn)
+            .await?;
+        Ok(Some(entry.content))
+    }
+
+    /// Store a rendered export, evicting the project's least recently used entries if needed
+    pub async fn put(
+        &self,
+        conn: &impl ConnectionTrait,
+        key: &ExportCacheKey,
+        content: &str,
+    ) -> Result<(), DbErr> {
+        if content.len() > self.max_entry_bytes {
+            debug!(
+                project_id = key.project_id.to_string(),
+                format = key.format,
+                size = content.len(),
+                "Export too large to cache"
+            );
+            return Ok(());
+        }
+
+        let now = Utc::now();
+        export_cache::Entity::insert(export_cache::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            project_id: Set(key.project_id),
+            format: Set(key.format.to_string()),
+            filter_hash: Set(key.filter_hash.clone()),
+            fingerprint: Set(key.fingerprint.clone()),
+            content: Set(content.to_string()),
+            size: Set(content.len() as i64),
+            created: Set(now),
+            last_used: Set(now),
+        })
+        .on_conflict(
+            OnConflict::columns([
+                export_cache::Column::ProjectId,
+                export_cache::Column::Format,
+                export_cache::Column::FilterHash,
+                export_cache::Column::Fingerprint,
+            ])
+            .update_columns([
+                export_cache::Column::Content,
+                export_cache::Column::Size,
+                export_cache::Column::LastUsed,
+            ])
+            .to_owned(),
+        )
+        .exec_without_returning(conn)
+        .await?;
+
+        let evict: Vec<Uuid> = export_cache::Entity::find()
+            .select_only()
+            .column(export_cache::Column::Id)
+            .filter(export_cache::Column::ProjectId.eq(key.project_id))
+            .order_by_desc(export_cache::Column::LastUsed)
+            // SQLite won't take an OFFSET without a LIMIT
+            .limit(i64::MAX as u64)
+            .offset(self.max_entries_per_project)
+            .into_tuple()
+            .all(conn)
+            .await?;
+        if !evict.is_empty() {
+            debug!(
+                project_id = key.project_id.to_string(),
+                evicted = evict.len(),
+                "Evicting export cache entries"
+            );
+            export_cache::Entity::delete_many()
+                .filter(export_cache::Column::Id.is_in(evict))
+                .exec(conn)
+                .await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,512 @@
+//! Pushing exports straight to external storage, for evidence which has to land in a WORM bucket
+//!
+//! A push renders the export once, then uploads it in a background task, retrying failed uploads
+//! a few times. Each push is an [export_record] row which the task keeps up to date, so the row is
+//! both the job status and the receipt.
+//!
+//! S3 is the only destination, and needs the `s3-export` cargo feature.
+
+use std::{path::PathBuf, time::Duration};
+
+use axum::{
+    extract::State,
+    http::{header::LOCATION, HeaderValue, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, ConnectionTrait, DatabaseConnection,
+    DeriveActiveEnum, EntityTrait, EnumIter, IntoActiveModel, QueryFilter, QueryOrder,
+};
+use sea_query::table::StringLen;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    access::check_project_access,
+    entity::{export_record, project},
+    export::{render_project_jsonld, JSONLD_CONTENT_TYPE},
+    extract::Path,
+    oauth::middleware::AuthUser,
+    project::{
+        load_project_graph, render_project_dot, render_project_graphml, render_project_mermaid,
+        ErrorResponse, WebError, DOT_CONTENT_TYPE, GRAPHML_CONTENT_TYPE, MERMAID_CONTENT_TYPE,
+    },
+    styles::NodeTypeStyles,
+    SharedState,
+};
+
+/// Error code when the server can't push exports
+pub const EXPORT_PUSH_UNAVAILABLE: &str = "export_push_unavailable";
+
+pub const DEFAULT_S3_REGION: &str = "us-east-1";
+
+/// Uploads tried before a push is marked failed
+pub const EXPORT_PUSH_ATTEMPTS: i32 = 3;
+/// Wait before the first retry, doubled for each one after
+pub const EXPORT_PUSH_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize, ToSchema,
+)]
+#[sea_orm(
+    rs_type = "String",
+    db_type = "String(StringLen::N(8))",
+    rename_all = "lowercase"
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportPushFormat {
+    Graphml,
+    Dot,
+    Mermaid,
+    Jsonld,
+}
+
+impl ExportPushFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Graphml => "graphml",
+            Self::Dot => "dot",
+            Self::Mermaid => "mmd",
+            Self::Jsonld => "jsonld",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Graphml => GRAPHML_CONTENT_TYPE,
+            Self::Dot => DOT_CONTENT_TYPE,
+            Self::Mermaid => MERMAID_CONTENT_TYPE,
+            Self::Jsonld => JSONLD_CONTENT_TYPE,
+        }
+    }
+
+    /// The same bytes as the matching `/api/v1/project/{id}/export/...` endpoint, unfiltered
+    pub async fn render(
+        &self,
+        conn: &impl ConnectionTrait,
+        project: &project::Model,
+        styles: &NodeTypeStyles,
+    ) -> Result<Vec<u8>, WebError> {
+        let body = match self {
+            Self::Graphml => {
+                let (nodes, nodelinks) = load_project_graph(conn, project.id).await?;
+                render_project_graphml(project, &nodes, &nodelinks)
+            }
+            Self::Dot => {
+                let (nodes, nodelinks) = load_project_graph(conn, project.id).await?;
+                render_project_dot(project, &nodes, &nodelinks, styles)
+            }
+            Self::Mermaid => render_project_mermaid(conn, project, None, styles, false).await?,
+            Self::Jsonld => render_project_jsonld(conn, project, false).await?,
+        };
+        Ok(body.into_bytes())
+    }
+}
+
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize, ToSchema,
+)]
+#[sea_orm(
+    rs_type = "String",
+    db_type = "String(StringLen::N(8))",
+    rename_all = "lowercase"
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportPushStatus {
+    /// Being rendered or uploaded
+    Pending,
+    Uploaded,
+    /// Gave up, see the record's `error`
+    Failed,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ExportDestination {
+    S3 {
+        bucket: String,
+        /// Prepended to the object key, a trailing `/` is added if it's missing
+        #[serde(default)]
+        prefix: Option<String>,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ExportPushRequest {
+    pub format: ExportPushFormat,
+    pub destination: ExportDestination,
+}
+
+/// Access keys for S3, from the standard `AWS_*` environment variables or credentials file
+#[derive(Clone)]
+pub struct S3Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl std::fmt::Debug for S3Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("S3Credentials")
+            .field("access_key_id", &self.access_key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl S3Credentials {
+    /// `AWS_ACCESS_KEY_ID` and friends, falling back to the `AWS_PROFILE` (or `default`) profile
+    /// in `AWS_SHARED_CREDENTIALS_FILE` or `~/.aws/credentials`
+    pub fn from_environment() -> Option<Self> {
+        let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        if let (Some(access_key_id), Some(secret_access_key)) =
+            (env("AWS_ACCESS_KEY_ID"), env("AWS_SECRET_ACCESS_KEY"))
+        {
+            return Some(Self {
+                access_key_id,
+                secret_access_key,
+                session_token: env("AWS_SESSION_TOKEN"),
+            });
+        }
+        let path = env("AWS_SHARED_CREDENTIALS_FILE")
+            .map(PathBuf::from)
+            .or_else(|| env("HOME").map(|home| PathBuf::from(home).join(".aws/credentials")))?;
+        let contents = std::fs::read_to_string(path).ok()?;
+        Self::from_profile(
+            &contents,
+            &env("AWS_PROFILE").unwrap_or_else(|| "default".to_string()),
+        )
+    }
+
+    /// Read one profile out of an AWS credentials file
+    pub fn from_profile(contents: &str, profile: &str) -> Option<Self> {
+        let mut in_profile = false;
+        let (mut access_key_id, mut secret_access_key, mut session_token) = (None, None, None);
+        for line in contents.lines().map(str::trim) {
+            if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                in_profile = section.trim() == profile;
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=').filter(|_| in_profile) else {
+                continue;
+            };
+            let value = Some(value.trim().to_string());
+            match key.trim() {
+                "aws_access_key_id" => access_key_id = value,
+                "aws_secret_access_key" => secret_access_key = value,
+                "aws_session_token" => session_token = value,
+                _ => {}
+            }
+        }
+        Some(Self {
+            access_key_id: access_key_id?,
+            secret_access_key: secret_access_key?,
+            session_token,
+        })
+    }
+}
+
+/// Where S3 is and how to sign in to it
+#[derive(Clone, Debug, Default)]
+pub struct S3Config {
+    /// S3-compatible service to use instead of AWS, addressed path-style
+    pub endpoint: Option<url::Url>,
+    pub region: String,
+    pub credentials: Option<S3Credentials>,
+}
+
+impl S3Config {
+    /// Whether pushes can be started, needs the `s3-export` feature and some credentials
+    pub fn available(&self) -> bool {
+        cfg!(feature = "s3-export") && self.credentials.is_some()
+    }
+}
+
+/// S3 bucket naming rules, roughly, so typos fail before the job starts
+fn valid_bucket_name(bucket: &str) -> bool {
+    (3..=63).contains(&bucket.len())
+        && bucket
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'.' || b == b'-')
+        && bucket.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && bucket.ends_with(|c: char| c.is_ascii_alphanumeric())
+}
+
+fn object_key(prefix: Option<&str>, record: &export_record::Model) -> String {
+    let prefix = prefix.unwrap_or_default().trim_matches('/');
+    format!(
+        "{prefix}{}{}/{}-{}.{}",
+        if prefix.is_empty() { "" } else { "/" },
+        record.project_id,
+        record.created.format("%Y%m%dT%H%M%SZ"),
+        record.id,
+        record.format.extension()
+    )
+}
+
+#[cfg(feature = "s3-export")]
+async fn upload(
+    config: &S3Config,
+    record: &export_record::Model,
+    body: Vec<u8>,
+) -> Result<String, String> {
+    use openidconnect::reqwest;
+
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .map_err(|err| format!("Failed to build HTTP client: {err}"))?;
+    crate::s3::put_object(
+        &client,
+        config,
+        &record.bucket,
+        &record.object_key,
+        body,
+        record.format.content_type(),
+    )
+    .await
+}
+
+#[cfg(not(feature = "s3-export"))]
+async fn upload(
+    _config: &S3Config,
+    _record: &export_record::Model,
+    _body: Vec<u8>,
+) -> Result<String, String> {
+    Err("Built without the s3-export feature".to_string())
+}
+
+/// Render and upload, updating the record as it goes
+async fn run_push(
+    conn: DatabaseConnection,
+    config: S3Config,
+    styles: NodeTypeStyles,
+    project: project::Model,
+    record: export_record::Model,
+) -> Result<(), sea_orm::DbErr> {
+    let record_id = record.id;
+    let body = match record.format.render(&conn, &project, &styles).await {
+        Ok(body) => body,
+        Err(err) => {
+            let mut failed = record.into_active_model();
+            failed.status = Set(ExportPushStatus::Failed);
+            failed.error = Set(Some(format!("Failed to render export: {}", err.message())));
+            failed.finished = Set(Some(Utc::now()));
+            failed.update(&conn).await?;
+            return Ok(());
+        }
+    };
+    let size = body.len() as i64;
+
+    let mut delay = EXPORT_PUSH_RETRY_DELAY;
+    let mut record = record;
+    for attempt in 1..=EXPORT_PUSH_ATTEMPTS {
+        let result = upload(&config, &record, body.clone()).await;
+        let mut update = record.into_active_model();
+        update.attempts = Set(attempt);
+        match result {
+            Ok(etag) => {
+                update.status = Set(ExportPushStatus::Uploaded);
+                update.etag = Set(Some(etag));
+                update.size = Set(Some(size));
+                update.error = Set(None);
+                update.finished = Set(Some(Utc::now()));
+                update.update(&conn).await?;
+                info!(
+                    record_id = record_id.to_string(),
+                    bytes = size,
+                    attempt,
+                    "Pushed export"
+                );
+                return Ok(());
+            }
+            Err(err) => {
+                warn!(
+                    record_id = record_id.to_string(),
+                    attempt,
+                    error = err,
+                    "Export push failed"
+                );
+                update.error = Set(Some(err));
+                if attempt == EXPORT_PUSH_ATTEMPTS {
+                    update.status = Set(ExportPushStatus::Failed);
+                    update.finished = Set(Some(Utc::now()));
+                }
+                record = update.update(&conn).await?;
+            }
+        }
+        if attempt < EXPORT_PUSH_ATTEMPTS {
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+    Ok(())
+}
+
+/// Render an export and upload it to S3 in the background
+///
+/// Poll `/api/v1/project/{id}/export-records` to see how it went.
+#[utoipa::path(
+    post,
+    path = "/api/v1/project/{id}/export/push",
+    tag = "exports",
+    operation_id = "push_project_export",
+    params(
+        ("id" = Uuid, Path, description = "Project ID to export")
+    ),
+    request_body = ExportPushRequest,
+    responses(
+        (status = ACCEPTED, description = "Push started, the record is updated as it goes", body = export_record::Model),
+        (status = BAD_REQUEST, description = "Invalid bucket name or path parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse),
+        (status = SERVICE_UNAVAILABLE, description = "Built without S3 support, or no S3 credentials are configured", body = ErrorResponse)
+    )
+)]
+pub async fn push_project_export(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+    Json(request): Json<ExportPushRequest>,
+) -> Result<impl IntoResponse, WebError> {
+    let reader = state.read().await;
+    let conn = &reader.conn;
+    let project = project::Entity::find_by_id(id)
+        .one(conn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Project {} not found", id)))?;
+    check_project_access(conn, &project, auth_user.as_deref()).await?;
+
+    if !reader.s3.available() {
+        return Err(WebError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Pushing exports to S3 isn't available on this server",
+        )
+        .with_code(EXPORT_PUSH_UNAVAILABLE));
+    }
+    let ExportDestination::S3 { bucket, prefix } = request.destination;
+    if !valid_bucket_name(&bucket) {
+        return Err(
+            WebError::new(StatusCode::BAD_REQUEST, "Invalid S3 bucket name")
+                .with_detail("bucket", bucket),
+        );
+    }
+
+    let mut record = export_record::Model {
+        id: Uuid::new_v4(),
+        project_id: id,
+        format: request.format,
+        destination: "s3".to_string(),
+        bucket,
+        object_key: String::new(),
+        status: ExportPushStatus::Pending,
+        etag: None,
+        size: None,
+        attempts: 0,
+        error: None,
+        created_by: AuthUser::created_by(auth_user.as_deref()),
+        created: Utc::now(),
+        finished: None,
+    };
+    record.object_key = object_key(prefix.as_deref(), &record);
+    let record = export_record::ActiveModel::from(record.clone())
+        .insert(conn)
+        .await?;
+
+    let (task_conn, config, styles) = (
+        conn.clone(),
+        reader.s3.clone(),
+        reader.node_type_styles.clone(),
+    );
+    let task_record = record.clone();
+    let record_id = record.id;
+    tokio::spawn(async move {
+        if let Err(err) = run_push(task_conn, config, styles, project, task_record).await {
+            error!(error = ?err, record_id = record_id.to_string(), "Failed to update export record");
+        }
+    });
+    info!(
+        record_id = record_id.to_string(),
+        project_id = id.to_string(),
+        key = record.object_key,
+        "Started export push"
+    );
+
+    Ok((
+        StatusCode::ACCEPTED,
+        [(
+            LOCATION,
+            HeaderValue::from_str(&format!("/api/v1/project/{id}/export-records"))?,
+        )],
+        Json(record),
+    ))
+}
+
+/// Exports pushed from a project, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/export-records",
+    tag = "exports",
+    operation_id = "get_export_records",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    responses(
+        (status = OK, description = "Pushes, with where they ended up or why they failed", body = Vec<export_record::Model>),
+        (status = BAD_REQUEST, description = "Invalid path parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse)
+    )
+)]
+pub async fn get_export_records(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<Vec<export_record::Model>>, WebError> {
+    let conn = &state.read().await.conn;
+    let project = project::Entity::find_by_id(id)
+        .one(conn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Project {} not found", id)))?;
+    check_project_access(conn, &project, auth_user.as_deref()).await?;
+    Ok(Json(
+        export_record::Entity::find()
+            .filter(export_record::Column::ProjectId.eq(id))
+            .order_by_desc(export_record::Column::Created)
+            .order_by_desc(export_record::Column::Id)
+            .all(conn)
+            .await?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_credentials_from_profile() {
+        let contents = "[default]\naws_access_key_id = AKDEFAULT\naws_secret_access_key = s1\n\n\
+                        [evidence]\naws_access_key_id=AKEVIDENCE\naws_secret_access_key=s2\n\
+                        aws_session_token = t2\n";
+        let credentials = S3Credentials::from_profile(contents, "evidence").unwrap();
+        assert_eq!(credentials.access_key_id, "AKEVIDENCE");
+        assert_eq!(credentials.secret_access_key, "s2");
+        assert_eq!(credentials.session_token.as_deref(), Some("t2"));
+        let credentials = S3Credentials::from_profile(contents, "default").unwrap();
+        assert_eq!(credentials.access_key_id, "AKDEFAULT");
+        assert!(credentials.session_token.is_none());
+        assert!(S3Credentials::from_profile(contents, "missing").is_none());
+    }
+
+    #[test]
+    fn test_valid_bucket_name() {
+        assert!(valid_bucket_name("osint-evidence.2026"));
+        assert!(!valid_bucket_name("ab"));
+        assert!(!valid_bucket_name("Evidence"));
+        assert!(!valid_bucket_name("-evidence"));
+        assert!(!valid_bucket_name("evidence/cases"));
+    }
+}
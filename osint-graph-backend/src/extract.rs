@@ -0,0 +1,129 @@
+//! Request extractors which reject with our standard JSON error body
+//!
+//! These wrap the axum extractors of the same name, so handlers can use them as drop-in replacements.
+
+use axum::{
+    extract::{path::ErrorKind, rejection::PathRejection, FromRequestParts, RawPathParams},
+    http::{request::Parts, StatusCode},
+};
+use serde::de::DeserializeOwned;
+use tracing::debug;
+
+use crate::project::WebError;
+
+pub const INVALID_PATH_PARAMETER: &str = "invalid_path_parameter";
+pub const INVALID_QUERY_PARAMETER: &str = "invalid_query_parameter";
+
+/// Path parameter extractor, see [axum::extract::Path]
+#[derive(Debug)]
+pub struct Path<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = WebError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::extract::Path::<T>::from_request_parts(parts, state).await {
+            Ok(axum::extract::Path(value)) => Ok(Self(value)),
+            Err(rejection) => {
+                let raw_params = RawPathParams::from_request_parts(parts, state)
+                    .await
+                    .map(|params| {
+                        params
+                            .iter()
+                            .map(|(key, _)| key.to_string())
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                Err(path_rejection(rejection, &raw_params))
+            }
+        }
+    }
+}
+
+fn path_rejection(rejection: PathRejection, param_names: &[String]) -> WebError {
+    let err = match rejection {
+        PathRejection::FailedToDeserializePathParams(err) => err,
+        other => return WebError::new(other.status(), other.body_text()),
+    };
+
+    let (parameter, value) = match err.kind() {
+        ErrorKind::ParseErrorAtKey { key, value, .. }
+        | ErrorKind::DeserializeError { key, value, .. } => {
+            (Some(key.clone()), Some(value.clone()))
+        }
+        ErrorKind::ParseErrorAtIndex { index, value, .. } => {
+            (param_names.get(*index).cloned(), Some(value.clone()))
+        }
+        ErrorKind::ParseError { value, .. } => (param_names.first().cloned(), Some(value.clone())),
+        ErrorKind::InvalidUtf8InPathParam { key } => (Some(key.clone()), None),
+        ErrorKind::UnsupportedType { .. } | ErrorKind::WrongNumberOfParameters { .. } => {
+            return WebError::internal_server_error(err.body_text());
+        }
+        _ => (None, None),
+    };
+    debug!(?parameter, ?value, "Rejected invalid path parameter");
+
+    let message = match &parameter {
+        Some(parameter) => format!("Invalid path parameter `{parameter}`: {}", err.body_text()),
+        None => err.body_text(),
+    };
+    WebError::new(StatusCode::BAD_REQUEST, message)
+        .with_code(INVALID_PATH_PARAMETER)
+        .with_detail("parameter", parameter)
+        .with_detail("value", value)
+}
+
+/// Query string extractor, see [axum::extract::Query]
+#[derive(Debug)]
+pub struct Query<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for Query<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = WebError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let query = parts.uri.query().unwrap_or_default();
+        let deserializer =
+            serde_urlencoded::Deserializer::new(form_urlencoded::parse(query.as_bytes()));
+        serde_path_to_error::deserialize(deserializer)
+            .map(Self)
+            .map_err(|err| query_rejection(err, query))
+    }
+}
+
+fn query_rejection(
+    err: serde_path_to_error::Error<serde_urlencoded::de::Error>,
+    query: &str,
+) -> WebError {
+    let inner = err.inner().to_string();
+    let parameter = match err.path().to_string() {
+        path if path != "." => Some(path),
+        // missing fields don't have a path, but serde names them in the message
+        _ => inner
+            .strip_prefix("missing field `")
+            .and_then(|rest| rest.split('`').next())
+            .map(|name| name.to_string()),
+    };
+    let value = parameter.as_ref().and_then(|parameter| {
+        form_urlencoded::parse(query.as_bytes())
+            .find(|(key, _)| key == parameter)
+            .map(|(_, value)| value.to_string())
+    });
+    debug!(?parameter, ?value, "Rejected invalid query parameter");
+
+    let message = match &parameter {
+        Some(parameter) => format!("Invalid query parameter `{parameter}`: {inner}"),
+        None => format!("Invalid query string: {inner}"),
+    };
+    WebError::new(StatusCode::BAD_REQUEST, message)
+        .with_code(INVALID_QUERY_PARAMETER)
+        .with_detail("parameter", parameter)
+        .with_detail("value", value)
+}
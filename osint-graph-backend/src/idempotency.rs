@@ -0,0 +1,279 @@
+//! Replaying responses to retried POSTs
+//!
+//! Clients on flaky networks retry requests which may have already worked. A POST with an
+//! `Idempotency-Key` header is recorded against the caller, and a retry with the same key and body
+//! gets the first response back instead of running again. Keys are scoped per user and forgotten
+//! after [IDEMPOTENCY_KEY_TTL].
+
+use std::time::Duration;
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header::CONTENT_TYPE, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use chrono::Utc;
+use sea_orm::{
+    sea_query::{Expr, OnConflict},
+    ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Set,
+};
+use tokio::task::JoinHandle;
+use tracing::{debug, error};
+use uuid::Uuid;
+
+use crate::{
+    attachment_dedup::content_hash,
+    entity::idempotency_key,
+    oauth::middleware::AuthUser,
+    project::{WebError, MAX_IMPORT_BYTES},
+    SharedState,
+};
+
+pub const IDEMPOTENCY_KEY_HEADER: HeaderName = HeaderName::from_static("idempotency-key");
+/// Set on responses which were replayed rather than run
+pub const IDEMPOTENT_REPLAYED_HEADER: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+/// How long a key and its response are kept
+pub const IDEMPOTENCY_KEY_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long a request holds its key, so a retry can run again if the server died part way through
+pub const IDEMPOTENCY_PENDING_TTL: Duration = Duration::from_secs(5 * 60);
+pub const IDEMPOTENCY_KEY_MAX_CHARS: usize = 255;
+/// Larger responses aren't kept, retries get a 409 telling them to fetch the current state
+pub const IDEMPOTENCY_MAX_RESPONSE_BYTES: usize = 64 * 1024;
+
+pub const INVALID_IDEMPOTENCY_KEY: &str = "invalid_idempotency_key";
+pub const IDEMPOTENCY_KEY_REUSED: &str = "idempotency_key_reused";
+pub const IDEMPOTENCY_KEY_IN_PROGRESS: &str = "idempotency_key_in_progress";
+pub const IDEMPOTENT_RESPONSE_NOT_STORED: &str = "idempotent_response_not_stored";
+
+fn expires_after(ttl: Duration) -> chrono::DateTime<Utc> {
+    Utc::now() + chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::zero())
+}
+
+/// Middleware which replays the stored response for a repeated `Idempotency-Key`
+///
+/// Runs inside [crate::oauth::middleware::require_auth], so keys belong to the logged in user.
+pub async fn idempotency(
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() != Method::POST || !request.headers().contains_key(IDEMPOTENCY_KEY_HEADER) {
+        return next.run(request).await;
+    }
+    // cloned so the lock isn't held while the handler runs
+    let conn = state.read().await.conn.clone();
+    let user_id = auth_user.map_or(Uuid::nil(), |Extension(auth_user)| auth_user.id);
+    run_once(&conn, user_id, request, next)
+        .await
+        .unwrap_or_else(IntoResponse::into_response)
+}
+
+async fn run_once(
+    conn: &DatabaseConnection,
+    user_id: Uuid,
+    request: Request,
+    next: Next,
+) -> Result<Response, WebError> {
+    let key = request
+        .headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|key| !key.is_empty() && key.len() <= IDEMPOTENCY_KEY_MAX_CHARS)
+        .ok_or_else(|| {
+            WebError::new(
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Idempotency-Key must be 1 to {} visible ASCII characters",
+                    IDEMPOTENCY_KEY_MAX_CHARS
+                ),
+            )
+            .with_code(INVALID_IDEMPOTENCY_KEY)
+        })?
+        .to_string();
+    let endpoint = format!("{} {}", request.method(), request.uri().path());
+
+    let (parts, body) = request.into_parts();
+    let body = to_bytes(body, MAX_IMPORT_BYTES).await.map_err(|err| {
+        WebError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Failed to read request body: {}", err),
+        )
+    })?;
+    let request_hash = content_hash(&body);
+
+    let filter = idempotency_key::Column::UserId
+        .eq(user_id)
+        .and(idempotency_key::Column::Key.eq(&key));
+    // expired keys, and those whose request never finished, are free again
+    idempotency_key::Entity::delete_many()
+        .filter(filter.clone())
+        .filter(idempotency_key::Column::Expires.lt(Utc::now()))
+        .exec(conn)
+        .await?;
+    let claimed = idempotency_key::Entity::insert(idempotency_key::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        key: Set(key.clone()),
+        user_id: Set(user_id),
+        endpoint: Set(endpoint.clone()),
+        request_hash: Set(request_hash.clone()),
+        status: Set(None),
+        content_type: Set(None),
+        body: Set(None),
+        created: Set(Utc::now()),
+        expires: Set(expires_after(IDEMPOTENCY_PENDING_TTL)),
+    })
+    .on_conflict(
+        OnConflict::columns([
+            idempotency_key::Column::UserId,
+            idempotency_key::Column::Key,
+        ])
+        .do_nothing()
+        .to_owned(),
+    )
+    .exec_without_returning(conn)
+    .await?;
+    if claimed == 0 {
+        let stored = idempotency_key::Entity::find()
+            .filter(filter)
+            .one(conn)
+            .await?;
+        return replay(stored, &endpoint, &request_hash);
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(err) => {
+            forget(conn, user_id, &key).await;
+            return Err(WebError::internal_server_error(format!(
+                "Failed to read response body: {}",
+                err
+            )));
+        }
+    };
+
+    // failures may work next time, so they aren't replayed
+    if parts.status.is_server_error() {
+        forget(conn, user_id, &key).await;
+    } else {
+        let stored = idempotency_key::Entity::update_many()
+            .col_expr(
+                idempotency_key::Column::Status,
+                Expr::value(parts.status.as_u16() as i32),
+            )
+            .col_expr(
+                idempotency_key::Column::ContentType,
+                Expr::value(
+                    parts
+                        .headers
+                        .get(CONTENT_TYPE)
+                        .and_then(|value| value.to_str().ok())
+                        .map(str::to_string),
+                ),
+            )
+            .col_expr(
+                idempotency_key::Column::Body,
+                Expr::value((body.len() <= IDEMPOTENCY_MAX_RESPONSE_BYTES).then(|| body.to_vec())),
+            )
+            .col_expr(
+                idempotency_key::Column::Expires,
+                Expr::value(expires_after(IDEMPOTENCY_KEY_TTL)),
+            )
+            .filter(idempotency_key::Column::UserId.eq(user_id))
+            .filter(idempotency_key::Column::Key.eq(&key))
+            .exec(conn)
+            .await;
+        if let Err(err) = stored {
+            error!(error = ?err, endpoint, "Failed to store idempotent response");
+        }
+    }
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+/// Release a key so the request can be retried
+async fn forget(conn: &impl ConnectionTrait, user_id: Uuid, key: &str) {
+    if let Err(err) = idempotency_key::Entity::delete_many()
+        .filter(idempotency_key::Column::UserId.eq(user_id))
+        .filter(idempotency_key::Column::Key.eq(key))
+        .exec(conn)
+        .await
+    {
+        error!(error = ?err, "Failed to release idempotency key");
+    }
+}
+
+fn replay(
+    stored: Option<idempotency_key::Model>,
+    endpoint: &str,
+    request_hash: &str,
+) -> Result<Response, WebError> {
+    let in_progress = || {
+        WebError::new(
+            StatusCode::CONFLICT,
+            "A request with this Idempotency-Key is already in progress",
+        )
+        .with_code(IDEMPOTENCY_KEY_IN_PROGRESS)
+    };
+    // only missing if the first request failed in the last few milliseconds
+    let stored = stored.ok_or_else(in_progress)?;
+    if stored.endpoint != endpoint || stored.request_hash != request_hash {
+        return Err(WebError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "This Idempotency-Key was already used for a different request",
+        )
+        .with_code(IDEMPOTENCY_KEY_REUSED));
+    }
+    let status = stored.status.ok_or_else(in_progress)?;
+    let Some(body) = stored.body else {
+        return Err(WebError::new(
+            StatusCode::CONFLICT,
+            "The request with this Idempotency-Key already succeeded but its response was too large to keep, fetch the current state instead",
+        )
+        .with_code(IDEMPOTENT_RESPONSE_NOT_STORED));
+    };
+
+    debug!(endpoint, "Replaying idempotent response");
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = StatusCode::from_u16(status as u16)
+        .map_err(|_| WebError::internal_server_error("Stored response has an invalid status"))?;
+    if let Some(content_type) = stored
+        .content_type
+        .and_then(|content_type| HeaderValue::from_str(&content_type).ok())
+    {
+        response.headers_mut().insert(CONTENT_TYPE, content_type);
+    }
+    response
+        .headers_mut()
+        .insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+    Ok(response)
+}
+
+/// Remove expired keys and their responses
+pub async fn delete_expired_keys(conn: &impl ConnectionTrait) -> Result<u64, DbErr> {
+    Ok(idempotency_key::Entity::delete_many()
+        .filter(idempotency_key::Column::Expires.lt(Utc::now()))
+        .exec(conn)
+        .await?
+        .rows_affected)
+}
+
+/// Spawns a task which prunes expired keys every `interval`
+pub fn spawn_idempotency_cleanup(conn: DatabaseConnection, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match delete_expired_keys(&conn).await {
+                Ok(deleted) => debug!(deleted, "Cleaned up expired idempotency keys"),
+                Err(err) => error!(error = ?err, "Idempotency key cleanup failed"),
+            }
+        }
+    })
+}
@@ -1,6 +1,19 @@
 //* Functionality to identify contents / nodes
 //*
 
+use std::net::IpAddr;
+
+use axum::{http::StatusCode, Json};
+use osint_graph_shared::node::NodeType;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::project::{clean_url_value, ErrorResponse, WebError};
+
+/// Most URLs [identify_urls] takes at once
+pub const MAX_IDENTIFY_URLS: usize = 1000;
+
 #[derive(Debug, Eq, PartialEq)]
 pub enum SocialNode {
     Facebook(String),
@@ -13,6 +26,65 @@ pub enum SocialNode {
     Mastodon(String),
 }
 
+impl SocialNode {
+    pub fn platform(&self) -> &'static str {
+        match self {
+            Self::Facebook(_) => "facebook",
+            Self::Twitter(_) => "twitter",
+            Self::Instagram(_) => "instagram",
+            Self::Youtube(_) => "youtube",
+            Self::Tiktok(_) => "tiktok",
+            Self::Reddit(_) => "reddit",
+            Self::Mastodon(_) => "mastodon",
+        }
+    }
+
+    /// The account a profile URL points at, if it's one we know the shape of
+    pub fn username(&self) -> Option<String> {
+        let url = match self {
+            Self::Facebook(url)
+            | Self::Twitter(url)
+            | Self::Instagram(url)
+            | Self::Youtube(url)
+            | Self::Tiktok(url)
+            | Self::Reddit(url)
+            | Self::Mastodon(url) => url::Url::parse(url).ok()?,
+        };
+        let segments: Vec<&str> = url
+            .path_segments()?
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        let username = match (self, segments.as_slice()) {
+            (Self::Reddit(_), ["u" | "user", name, ..]) => *name,
+            (Self::Youtube(_), ["c" | "user", name, ..]) => *name,
+            (Self::Youtube(_) | Self::Tiktok(_) | Self::Mastodon(_), [name, ..])
+                if name.starts_with('@') =>
+            {
+                name.trim_start_matches('@')
+            }
+            (Self::Facebook(_), ["profile.php", ..]) => {
+                return url
+                    .query_pairs()
+                    .find(|(key, _)| key == "id")
+                    .map(|(_, id)| id.to_string());
+            }
+            (Self::Facebook(_) | Self::Twitter(_) | Self::Instagram(_), [name, ..])
+                if !RESERVED_PATHS.contains(name) =>
+            {
+                name.trim_start_matches('@')
+            }
+            _ => return None,
+        };
+        (!username.is_empty()).then(|| username.to_string())
+    }
+}
+
+/// First path segments on social sites which aren't accounts
+const RESERVED_PATHS: &[&str] = &[
+    "explore", "hashtag", "home", "i", "p", "reel", "reels", "search", "share", "stories",
+    "groups", "events", "watch", "intent",
+];
+
 #[derive(Debug, Eq, PartialEq)]
 pub enum UrlNode {
     Unknown,
@@ -29,8 +101,6 @@ pub fn identify_url(input: &str) -> Result<UrlNode, String> {
 
     //insta
     if host == "instagram.com" || host.ends_with(".instagram.com") {
-        println!("Instagram: {:?}", url);
-        // todo: parse out username
         Ok(UrlNode::Social(SocialNode::Instagram(input.to_string())))
     } else if host == "twitter.com"
         || host == "x.com"
@@ -47,11 +117,116 @@ pub fn identify_url(input: &str) -> Result<UrlNode, String> {
     } else if host == "youtube.com" || host.ends_with(".youtube.com") {
         Ok(UrlNode::Social(SocialNode::Youtube(input.to_string())))
     } else {
-        println!("Url: {:?}", url);
+        debug!(host, "Unrecognised URL host");
         Ok(UrlNode::Unknown)
     }
 }
 
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct IdentifyRequest {
+    pub urls: Vec<String>,
+}
+
+/// What [identify_url] made of one URL
+#[derive(Debug, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+pub struct IdentifiedUrl {
+    pub url: String,
+    /// Social platform the URL belongs to, unset for other sites and URLs which didn't parse
+    pub platform: Option<String>,
+    /// The account on `platform`, when the URL is a profile
+    pub username: Option<String>,
+    /// Why the URL couldn't be identified
+    pub error: Option<String>,
+}
+
+impl IdentifiedUrl {
+    pub fn new(url: &str) -> Self {
+        let (platform, username, error) = match identify_url(url.trim()) {
+            Ok(UrlNode::Social(social)) => {
+                (Some(social.platform().to_string()), social.username(), None)
+            }
+            Ok(UrlNode::Unknown) => (None, None, None),
+            Err(err) => (None, None, Some(err)),
+        };
+        Self {
+            url: url.to_string(),
+            platform,
+            username,
+            error,
+        }
+    }
+}
+
+/// Work out which platforms a list of URLs belong to, before creating nodes for them
+///
+/// Results are in the same order as the request. A URL which can't be parsed gets an `error`
+/// rather than failing the whole batch.
+#[utoipa::path(
+    post,
+    path = "/api/v1/identify",
+    tag = "nodes",
+    operation_id = "identify_urls",
+    request_body = IdentifyRequest,
+    responses(
+        (status = OK, description = "One result per URL", body = Vec<IdentifiedUrl>),
+        (status = BAD_REQUEST, description = "More than MAX_IDENTIFY_URLS URLs", body = ErrorResponse)
+    )
+)]
+pub async fn identify_urls(
+    Json(request): Json<IdentifyRequest>,
+) -> Result<Json<Vec<IdentifiedUrl>>, WebError> {
+    if request.urls.len() > MAX_IDENTIFY_URLS {
+        return Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            format!("At most {MAX_IDENTIFY_URLS} URLs can be identified at once"),
+        )
+        .with_detail("urls", request.urls.len()));
+    }
+    Ok(Json(
+        request
+            .urls
+            .iter()
+            .map(|url| IdentifiedUrl::new(url))
+            .collect(),
+    ))
+}
+
+/// Normalise a node's value so the same thing entered slightly differently compares equal
+///
+/// Used to spot duplicate nodes, together with the node type.
+pub fn canonical_key(node_type: NodeType, value: &str) -> String {
+    let value = value.trim();
+    match node_type {
+        NodeType::Domain => value.trim_end_matches('.').to_lowercase(),
+        NodeType::Email => value.to_lowercase(),
+        NodeType::Ip => match value.parse::<IpAddr>() {
+            Ok(ip) => ip.to_string(),
+            Err(_) => value.to_lowercase(),
+        },
+        NodeType::Phone => value
+            .chars()
+            .enumerate()
+            .filter(|(idx, c)| c.is_ascii_digit() || (*idx == 0 && *c == '+'))
+            .map(|(_, c)| c)
+            .collect(),
+        NodeType::Url => {
+            let value = clean_url_value(value);
+            match url::Url::parse(&value) {
+                Ok(mut url) => {
+                    url.set_fragment(None);
+                    url.to_string().trim_end_matches('/').to_string()
+                }
+                Err(_) => value.trim_end_matches('/').to_string(),
+            }
+        }
+        _ => value
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -109,4 +284,72 @@ mod tests {
             UrlNode::Unknown //(other_url.to_string())
         );
     }
+
+    #[test]
+    fn test_social_username() {
+        use super::*;
+
+        let username = |url: &str| match identify_url(url).unwrap() {
+            UrlNode::Social(social) => social.username(),
+            UrlNode::Unknown => panic!("{url} should be a social URL"),
+        };
+        assert_eq!(
+            username("https://www.instagram.com/yaleman13/").as_deref(),
+            Some("yaleman13")
+        );
+        assert_eq!(username("https://www.instagram.com/p/Cabc123/"), None);
+        assert_eq!(
+            username("https://old.reddit.com/u/yaleman").as_deref(),
+            Some("yaleman")
+        );
+        assert_eq!(username("https://reddit.com/r/rust"), None);
+        assert_eq!(
+            username("https://x.com/@someone/status/1").as_deref(),
+            Some("someone")
+        );
+        assert_eq!(
+            username("https://www.tiktok.com/@dancer/video/1").as_deref(),
+            Some("dancer")
+        );
+        assert_eq!(
+            username("https://www.youtube.com/@channel").as_deref(),
+            Some("channel")
+        );
+        assert_eq!(username("https://www.youtube.com/watch?v=abc"), None);
+        assert_eq!(
+            username("https://www.facebook.com/profile.php?id=100064082793320").as_deref(),
+            Some("100064082793320")
+        );
+    }
+
+    #[test]
+    fn test_canonical_key() {
+        use super::*;
+
+        assert_eq!(
+            canonical_key(NodeType::Domain, " Example.COM. "),
+            canonical_key(NodeType::Domain, "example.com")
+        );
+        assert_eq!(
+            canonical_key(NodeType::Email, "Jane@Example.com"),
+            "jane@example.com"
+        );
+        assert_eq!(canonical_key(NodeType::Ip, "2001:DB8:0::1"), "2001:db8::1");
+        assert_eq!(
+            canonical_key(NodeType::Phone, "+61 (400) 000-000"),
+            "+61400000000"
+        );
+        assert_eq!(
+            canonical_key(NodeType::Url, "https://EXAMPLE.com/page/#top"),
+            canonical_key(NodeType::Url, "https://example.com/page")
+        );
+        assert_eq!(
+            canonical_key(NodeType::Person, "Jane   Doe"),
+            canonical_key(NodeType::Person, "jane doe")
+        );
+        assert_ne!(
+            canonical_key(NodeType::Person, "Jane Doe"),
+            canonical_key(NodeType::Person, "John Doe")
+        );
+    }
 }
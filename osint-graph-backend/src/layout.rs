@@ -0,0 +1,281 @@
+//! Automatic node layouts
+//!
+//! Grid layout is cheap at any size. Force-directed (Fruchterman-Reingold) layout compares every
+//! pair of nodes on every iteration, so it's refused for projects with more than
+//! `--max-layout-nodes` nodes and runs fewer iterations as projects grow.
+
+use std::collections::HashMap;
+
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    access::check_project_access,
+    entity::{node, nodelink, project},
+    extract::{Path, Query},
+    oauth::middleware::AuthUser,
+    project::{ErrorResponse, WebError},
+    SharedState,
+};
+
+/// Default for `--max-layout-nodes`
+pub const DEFAULT_MAX_LAYOUT_NODES: u64 = 2000;
+/// Error code when a project is too big for force layout
+pub const LAYOUT_TOO_LARGE: &str = "layout_too_large";
+/// Node pair comparisons a force layout may spend across all its iterations
+pub const FORCE_LAYOUT_PAIR_BUDGET: u64 = 200_000_000;
+pub const MIN_FORCE_ITERATIONS: u64 = 10;
+pub const MAX_FORCE_ITERATIONS: u64 = 300;
+/// Ideal distance between linked nodes, and the grid cell size
+const NODE_SPACING: f64 = 150.0;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutAlgorithm {
+    /// Fruchterman-Reingold, linked nodes pull together and everything else pushes apart
+    #[default]
+    Force,
+    /// Rows of nodes ordered by type, then display name
+    Grid,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LayoutQuery {
+    #[serde(default)]
+    pub algorithm: LayoutAlgorithm,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct NodePosition {
+    pub id: Uuid,
+    pub pos_x: i32,
+    pub pos_y: i32,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct LayoutResponse {
+    pub algorithm: LayoutAlgorithm,
+    /// Iterations the force layout ran, zero for grid layout
+    pub iterations: u64,
+    pub positions: Vec<NodePosition>,
+}
+
+/// How many iterations a force layout of `node_count` nodes gets, fewer for bigger projects
+pub fn force_iterations(node_count: u64) -> u64 {
+    let pairs = node_count.saturating_mul(node_count).max(1);
+    (FORCE_LAYOUT_PAIR_BUDGET / pairs).clamp(MIN_FORCE_ITERATIONS, MAX_FORCE_ITERATIONS)
+}
+
+/// Lay `count` nodes out in a square-ish grid
+pub fn grid_layout(count: usize) -> Vec<(f64, f64)> {
+    let columns = (count as f64).sqrt().ceil().max(1.0) as usize;
+    (0..count)
+        .map(|index| {
+            (
+                (index % columns) as f64 * NODE_SPACING,
+                (index / columns) as f64 * NODE_SPACING,
+            )
+        })
+        .collect()
+}
+
+/// Fruchterman-Reingold layout of `count` nodes, `edges` being pairs of node indexes
+///
+/// Nodes start on a sunflower spiral so the result is the same every time for the same graph.
+pub fn force_layout(count: usize, edges: &[(usize, usize)], iterations: u64) -> Vec<(f64, f64)> {
+    let k = NODE_SPACING;
+    let mut positions: Vec<(f64, f64)> = (0..count)
+        .map(|index| {
+            let radius = k * (index as f64).sqrt();
+            let angle = index as f64 * 2.399_963;
+            (radius * angle.cos(), radius * angle.sin())
+        })
+        .collect();
+
+    let initial_temperature = k * (count as f64).sqrt() / 10.0 + k;
+    for iteration in 0..iterations {
+        let temperature = initial_temperature * (1.0 - iteration as f64 / iterations as f64);
+        let mut displacement = vec![(0.0, 0.0); count];
+
+        for i in 0..count {
+            for j in (i + 1)..count {
+                let (dx, dy) = (
+                    positions[i].0 - positions[j].0,
+                    positions[i].1 - positions[j].1,
+                );
+                let distance = (dx * dx + dy * dy).sqrt().max(0.01);
+                let force = k * k / distance / distance;
+                displacement[i].0 += dx * force;
+                displacement[i].1 += dy * force;
+                displacement[j].0 -= dx * force;
+                displacement[j].1 -= dy * force;
+            }
+        }
+        for &(a, b) in edges {
+            let (dx, dy) = (
+                positions[a].0 - positions[b].0,
+                positions[a].1 - positions[b].1,
+            );
+            let distance = (dx * dx + dy * dy).sqrt().max(0.01);
+            let force = distance / k;
+            displacement[a].0 -= dx * force;
+            displacement[a].1 -= dy * force;
+            displacement[b].0 += dx * force;
+            displacement[b].1 += dy * force;
+        }
+
+        for (position, (dx, dy)) in positions.iter_mut().zip(displacement) {
+            let length = (dx * dx + dy * dy).sqrt();
+            if length > 0.0 {
+                let step = length.min(temperature) / length;
+                position.0 += dx * step;
+                position.1 += dy * step;
+            }
+        }
+    }
+
+    // keep coordinates positive, like a hand-drawn graph
+    let min_x = positions.iter().map(|p| p.0).fold(f64::INFINITY, f64::min);
+    let min_y = positions.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+    positions
+        .into_iter()
+        .map(|(x, y)| (x - min_x, y - min_y))
+        .collect()
+}
+
+/// Work out new positions for every node in a project and save them
+#[utoipa::path(
+    post,
+    path = "/api/v1/project/{id}/layout",
+    tag = "projects",
+    operation_id = "layout_project",
+    params(
+        ("id" = Uuid, Path, description = "Project ID"),
+        ("algorithm" = Option<LayoutAlgorithm>, Query, description = "force (default) or grid")
+    ),
+    responses(
+        (status = OK, description = "Nodes moved", body = LayoutResponse),
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user"),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse),
+        (status = PAYLOAD_TOO_LARGE, description = "Too many nodes for force layout, use grid instead", body = ErrorResponse)
+    )
+)]
+pub async fn layout_project(
+    Path(id): Path<Uuid>,
+    Query(query): Query<LayoutQuery>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<LayoutResponse>, WebError> {
+    let reader = state.read().await;
+    let txn = reader.begin().await?;
+
+    let project = project::Entity::find_by_id(id)
+        .one(&txn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Project {} not found", id)))?;
+    check_project_access(&txn, &project, auth_user.as_deref()).await?;
+
+    let node_count = node::Entity::find_live()
+        .filter(node::Column::ProjectId.eq(id))
+        .count(&txn)
+        .await?;
+    if query.algorithm == LayoutAlgorithm::Force && node_count > reader.max_layout_nodes {
+        return Err(WebError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!(
+                "Project has {} nodes but force layout is limited to {}, use ?algorithm=grid instead",
+                node_count, reader.max_layout_nodes
+            ),
+        )
+        .with_code(LAYOUT_TOO_LARGE)
+        .with_detail("node_count", node_count)
+        .with_detail("max_layout_nodes", reader.max_layout_nodes));
+    }
+
+    let node_ids: Vec<Uuid> = node::Entity::find_live()
+        .select_only()
+        .column(node::Column::Id)
+        .filter(node::Column::ProjectId.eq(id))
+        .order_by_asc(node::Column::NodeType)
+        .order_by_asc(node::Column::Display)
+        .order_by_asc(node::Column::Id)
+        .into_tuple()
+        .all(&txn)
+        .await?;
+    let iterations = match query.algorithm {
+        LayoutAlgorithm::Force => force_iterations(node_ids.len() as u64),
+        LayoutAlgorithm::Grid => 0,
+    };
+    let edges: Vec<(usize, usize)> = match query.algorithm {
+        LayoutAlgorithm::Force => {
+            let index: HashMap<Uuid, usize> = node_ids
+                .iter()
+                .enumerate()
+                .map(|(index, id)| (*id, index))
+                .collect();
+            nodelink::Entity::find()
+                .select_only()
+                .columns([nodelink::Column::Left, nodelink::Column::Right])
+                .filter(nodelink::Column::ProjectId.eq(id))
+                .into_tuple::<(Uuid, Uuid)>()
+                .all(&txn)
+                .await?
+                .into_iter()
+                .filter_map(|(left, right)| Some((*index.get(&left)?, *index.get(&right)?)))
+                .filter(|(left, right)| left != right)
+                .collect()
+        }
+        LayoutAlgorithm::Grid => Vec::new(),
+    };
+
+    // force layout can take a while, keep it off the async workers
+    let count = node_ids.len();
+    let algorithm = query.algorithm;
+    let coordinates = tokio::task::spawn_blocking(move || match algorithm {
+        LayoutAlgorithm::Force => force_layout(count, &edges, iterations),
+        LayoutAlgorithm::Grid => grid_layout(count),
+    })
+    .await
+    .map_err(|err| {
+        error!(error = ?err, "Layout task failed");
+        WebError::internal_server_error("Layout failed")
+    })?;
+
+    let mut positions = Vec::with_capacity(count);
+    for (node_id, (x, y)) in node_ids.into_iter().zip(coordinates) {
+        let position = NodePosition {
+            id: node_id,
+            pos_x: x.round() as i32,
+            pos_y: y.round() as i32,
+        };
+        // positions aren't content, so `updated` is left alone
+        node::Entity::update_many()
+            .col_expr(node::Column::PosX, Expr::value(position.pos_x))
+            .col_expr(node::Column::PosY, Expr::value(position.pos_y))
+            .filter(node::Column::Id.eq(node_id))
+            .exec(&txn)
+            .await?;
+        positions.push(position);
+    }
+    txn.commit().await?;
+    info!(
+        project_id = id.to_string(),
+        algorithm = ?algorithm,
+        nodes = count,
+        iterations,
+        "Laid out project"
+    );
+
+    Ok(Json(LayoutResponse {
+        algorithm,
+        iterations,
+        positions,
+    }))
+}
@@ -1,18 +1,60 @@
+pub mod access;
+pub mod admin_users;
+pub mod archive;
 pub mod attachment;
+pub mod attachment_codec;
+pub mod attachment_dedup;
 pub mod auth;
+pub mod capabilities;
+pub mod capture;
 pub mod cli;
+pub mod contributors;
+pub mod db_health;
+#[cfg(debug_assertions)]
+pub mod dev_proxy;
 pub mod entity;
+pub mod export;
+pub mod export_cache;
+pub mod export_push;
+pub mod extract;
+pub mod idempotency;
 pub mod identifier;
+pub mod layout;
 pub mod logging;
+pub mod markdown;
+pub mod media;
+pub mod merge;
+pub mod metrics;
 pub mod middleware;
+pub mod migrate;
 pub mod migration;
 pub mod oauth;
 pub mod openapi;
+pub mod pdf;
 pub mod project;
+pub mod quota;
+pub mod redact;
+pub mod reindex;
+pub mod report;
+pub mod restore;
+pub mod review;
+#[cfg(feature = "s3-export")]
+pub mod s3;
+pub mod score;
+pub mod sessions;
+pub mod snapshot;
+pub mod split;
+pub mod status;
 pub mod storage;
+pub mod storage_gc;
+pub mod styles;
 #[cfg(test)]
 mod tests;
+pub mod text_limit;
 pub mod tls;
+pub mod tokens;
+pub mod trash;
+pub mod value_policy;
 
 use attachment::{
     delete_attachment, download_attachment, list_attachments, upload_attachment, view_attachment,
@@ -21,34 +63,52 @@ use axum::{
     body::Body,
     error_handling::HandleErrorLayer,
     extract::DefaultBodyLimit,
-    http::{header, Response, StatusCode},
-    middleware::from_fn_with_state,
-    routing::{delete, get, post},
+    http::{header, HeaderValue, Response, StatusCode},
+    middleware::{from_fn, from_fn_with_state},
+    routing::{delete, get, patch, post, put},
     Router,
 };
-use osint_graph_shared::{error::OsintError, Urls};
+use osint_graph_shared::{error::OsintError, nodelink::LinkType, Urls};
 use project::{
-    delete_node, delete_nodelink, delete_project, export_project_mermaid, get_node,
-    get_nodelinks_by_project, get_nodes_by_project, get_project, get_projects, post_node,
-    post_nodelink, post_project, search_global, update_project,
+    archive_project, delete_node, delete_nodelink, delete_project, export_project_mermaid,
+    get_node, get_nodelinks_by_node, get_nodelinks_by_project, get_nodes, get_nodes_by_project,
+    get_project, get_project_stats, get_projects, import_project, pin_project, post_node,
+    post_nodelink, post_nodes, post_project, post_project_full, search_global, unarchive_project,
+    unpin_project, update_nodelink, update_project,
 };
 use sea_orm::DatabaseConnection;
 use sqlx::{Pool, Sqlite};
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::sync::RwLock;
 use tower::{BoxError, ServiceBuilder};
 use tower_http::{
-    compression::CompressionLayer, services::ServeDir, set_header::SetResponseHeaderLayer,
+    compression::{
+        predicate::{DefaultPredicate, NotForContentType, Predicate},
+        CompressionLayer,
+    },
+    services::ServeDir,
+    set_header::SetResponseHeaderLayer,
 };
 use tower_sessions::{cookie::time, Expiry, SessionManagerLayer};
 use tracing::error;
 
 use crate::{
     attachment::update_attachment,
-    cli::{db_path_default, CliOpts},
+    attachment_codec::AttachmentCodec,
+    cli::CliOpts,
+    export_cache::ExportCache,
     logging::logging_layer,
-    oauth::{middleware::require_auth, OAuthClient},
+    oauth::{
+        middleware::{require_admin, require_auth},
+        OAuthClient,
+    },
     project::{export_project, update_node, WebError},
+    quota::Quota,
+    styles::NodeTypeStyles,
+    value_policy::ValuePolicyEngine,
 };
 
 pub type SharedState = Arc<RwLock<AppState>>;
@@ -57,11 +117,76 @@ pub struct AppState {
     pub conn: DatabaseConnection,
 
     pub oauth_client: Option<Arc<OAuthClient>>,
+
+    /// How often expired sessions are pruned from the session store
+    pub session_cleanup_interval: Duration,
+
+    pub quota: Quota,
+
+    pub node_type_styles: NodeTypeStyles,
+
+    /// Rules for sensitive data in node values
+    pub value_policy: ValuePolicyEngine,
+
+    pub export_cache: ExportCache,
+
+    /// Base URL of the web UI, used to build deep links
+    pub frontend_url: String,
+
+    pub cors_allowed_origins: Vec<HeaderValue>,
+    /// How long browsers may cache preflight responses
+    pub cors_max_age: Duration,
+
+    /// How new attachments are compressed
+    pub attachment_codec: AttachmentCodec,
+
+    /// Type of the links the server creates itself, eg capture and split
+    pub default_link_type: LinkType,
+
+    /// How long the readiness check waits for the database
+    pub readiness_timeout: Duration,
+    /// When the server started, for uptime in the health check
+    pub started: Instant,
+
+    /// Largest project force layout will run on
+    pub max_layout_nodes: u64,
+
+    /// Limit on node notes and project descriptions
+    pub notes_limit: text_limit::TextLimit,
+
+    /// Largest project whose PDF report is built during the request, bigger ones get a job
+    pub report_sync_max_nodes: u64,
+    pub report_jobs: report::ReportJobs,
+    pub dedup_jobs: attachment_dedup::DedupJobs,
+
+    /// How long nodes stay in the trash before they're purged, unset to keep them
+    pub trash_retention: Option<chrono::Duration>,
+
+    /// Where exports are pushed to
+    pub s3: export_push::S3Config,
+
+    pub metrics: Arc<metrics::Metrics>,
+    /// Bearer token required to scrape `/metrics`
+    pub metrics_token: Option<String>,
+
+    /// Frontend dev server to proxy to instead of serving `./dist/`
+    #[cfg(debug_assertions)]
+    pub dev_proxy: Option<dev_proxy::DevProxy>,
 }
 
 impl AppState {
+    /// Start a transaction, recording how long it waited for a connection in the metrics
+    pub async fn begin(&self) -> Result<sea_orm::DatabaseTransaction, sea_orm::DbErr> {
+        db_health::begin_timed(&self.conn, &self.metrics).await
+    }
+
     pub async fn new(cli: &CliOpts) -> Result<Self, OsintError> {
-        let conn = storage::new(&cli.db_path.clone().unwrap_or(db_path_default().into())).await?;
+        let mut conn =
+            storage::new(&cli.db_path(), !cli.no_auto_migrate, cli.db_max_connections).await?;
+        let metrics = Arc::new(metrics::Metrics::default());
+        let query_metrics = metrics.clone();
+        // set before the connection is cloned, clones keep the callback they were made with
+        conn.set_metric_callback(move |info| query_metrics.record_query(info.elapsed));
         Ok(Self {
             oauth_client: Some(Arc::new(
                 OAuthClient::new(
@@ -73,17 +198,81 @@ impl AppState {
                 .await?,
             )),
             conn,
+            session_cleanup_interval: Duration::from_secs(cli.session_cleanup_interval),
+            quota: Quota::new(cli.quota_limits()),
+            node_type_styles: NodeTypeStyles::load(cli.node_type_styles_file.as_deref())?,
+            value_policy: ValuePolicyEngine::load(cli.value_policy_file.as_deref())?,
+            export_cache: ExportCache::default(),
+            frontend_url: cli.frontend_url.trim_end_matches('/').to_string(),
+            cors_allowed_origins: cli.cors_allowed_origins()?,
+            cors_max_age: Duration::from_secs(cli.cors_max_age_secs),
+            attachment_codec: cli.attachment_codec,
+            default_link_type: cli.default_link_type,
+            readiness_timeout: Duration::from_millis(cli.readiness_timeout_ms),
+            started: Instant::now(),
+            max_layout_nodes: cli.max_layout_nodes,
+            notes_limit: cli.notes_limit(),
+            report_sync_max_nodes: cli.report_sync_max_nodes,
+            report_jobs: report::ReportJobs::default(),
+            dedup_jobs: attachment_dedup::DedupJobs::default(),
+            trash_retention: cli.trash_retention(),
+            s3: export_push::S3Config {
+                endpoint: cli.s3_endpoint.clone(),
+                region: cli.s3_region.clone(),
+                credentials: export_push::S3Credentials::from_environment(),
+            },
+            metrics,
+            metrics_token: cli.metrics_token.clone(),
+            #[cfg(debug_assertions)]
+            dev_proxy: cli
+                .dev_proxy
+                .as_ref()
+                .map(dev_proxy::DevProxy::new)
+                .transpose()?,
         })
     }
 
     #[cfg(test)]
     pub async fn test() -> Self {
-        let db = storage::start_db(None)
+        let mut db = storage::start_db(None)
             .await
             .expect("Failed to start test DB");
+        let metrics = Arc::new(metrics::Metrics::default());
+        let query_metrics = metrics.clone();
+        db.set_metric_callback(move |info| {
+            tests::query_budget::record_statement(info);
+            query_metrics.record_query(info.elapsed);
+        });
         Self {
             conn: db,
             oauth_client: None,
+            session_cleanup_interval: Duration::from_secs(
+                sessions::DEFAULT_SESSION_CLEANUP_INTERVAL,
+            ),
+            quota: Quota::default(),
+            node_type_styles: NodeTypeStyles::default(),
+            value_policy: ValuePolicyEngine::default(),
+            export_cache: ExportCache::default(),
+            frontend_url: "https://localhost:9000".to_string(),
+            cors_allowed_origins: Vec::new(),
+            cors_max_age: Duration::from_secs(middleware::DEFAULT_CORS_MAX_AGE_SECS),
+            attachment_codec: AttachmentCodec::default(),
+            default_link_type: LinkType::default(),
+            readiness_timeout: Duration::from_millis(status::DEFAULT_READINESS_TIMEOUT_MS),
+            started: Instant::now(),
+            max_layout_nodes: layout::DEFAULT_MAX_LAYOUT_NODES,
+            notes_limit: text_limit::TextLimit {
+                max_bytes: Some(text_limit::DEFAULT_MAX_NOTES_BYTES as usize),
+                truncate: false,
+            },
+            report_sync_max_nodes: report::DEFAULT_REPORT_SYNC_MAX_NODES,
+            report_jobs: report::ReportJobs::default(),
+            dedup_jobs: attachment_dedup::DedupJobs::default(),
+            trash_retention: None,
+            s3: export_push::S3Config::default(),
+            metrics,
+            metrics_token: None,
+            dev_proxy: None,
         }
     }
 }
@@ -102,24 +291,119 @@ pub async fn build_app(
         .await
         .expect("Failed to migrate session store");
 
+    let (conn, cleanup_interval) = {
+        let reader = shared_state.read().await;
+        (reader.conn.clone(), reader.session_cleanup_interval)
+    };
+    sessions::spawn_session_cleanup(session_store.clone(), cleanup_interval);
+    idempotency::spawn_idempotency_cleanup(conn.clone(), cleanup_interval);
+    snapshot::spawn_snapshot_scheduler(conn);
+    if shared_state.read().await.trash_retention.is_some() {
+        trash::spawn_trash_purge(shared_state.clone(), cleanup_interval);
+    }
+
     let session_layer = SessionManagerLayer::new(session_store)
         .with_secure(true) // HTTPS only - secure cookies
         .with_expiry(Expiry::OnInactivity(time::Duration::hours(1)));
 
-    let static_service = ServeDir::new("./dist/").append_index_html_on_directories(true);
+    let (cors_allowed_origins, cors_max_age, metrics) = {
+        let reader = shared_state.read().await;
+        (
+            reader.cors_allowed_origins.clone(),
+            reader.cors_max_age,
+            reader.metrics.clone(),
+        )
+    };
+
+    // Only admins get past require_admin, which needs require_auth to have found the user
+    let admin_routes = Router::new()
+        // creating nodes in bulk is for everyone, browsing every user's nodes isn't
+        .route("/api/v1/nodes", get(get_nodes))
+        .route(
+            "/api/v1/attachment/{attachment_id}/raw",
+            get(attachment::download_attachment_raw),
+        )
+        .route(
+            "/api/v1/admin/attachments/duplicates",
+            get(attachment_dedup::get_attachment_duplicates),
+        )
+        .route(
+            "/api/v1/admin/attachments/deduplicate",
+            post(attachment_dedup::post_deduplicate_attachments),
+        )
+        .route(
+            "/api/v1/admin/attachments/deduplicate/{id}",
+            get(attachment_dedup::get_deduplicate_job)
+                .delete(attachment_dedup::cancel_deduplicate_job),
+        )
+        .route(
+            "/api/v1/admin/value-policy",
+            get(value_policy::get_value_policy),
+        )
+        .route(
+            "/api/v1/admin/storage/orphans",
+            get(storage_gc::get_storage_orphans),
+        )
+        .route(
+            "/api/v1/admin/storage/gc",
+            post(storage_gc::post_storage_gc),
+        )
+        .route(
+            "/api/v1/admin/prune-orphans",
+            post(storage_gc::post_prune_orphans),
+        )
+        .route("/api/v1/admin/reindex", post(reindex::post_reindex))
+        .route("/api/v1/admin/db-health", get(db_health::get_db_health))
+        .route("/api/v1/admin/users", get(admin_users::get_admin_users))
+        .route(
+            "/api/v1/admin/users/{id}",
+            put(admin_users::update_admin_user).delete(admin_users::delete_admin_user),
+        )
+        .route_layer(from_fn(require_admin));
 
     // Build our application by composing routes
     let protected_routes = Router::new()
         .route("/api/v1/node", post(post_node))
+        .route("/api/v1/nodes", post(post_nodes))
+        .merge(admin_routes)
         .route(
             "/api/v1/node/{id}",
             get(get_node).delete(delete_node).put(update_node),
         )
         .route(
             "/api/v1/node/{id}/attachment",
-            post(upload_attachment).layer(DefaultBodyLimit::max(100 * 1024 * 1024)), // 100MB limit
+            post(upload_attachment).layer(DefaultBodyLimit::max(attachment::MAX_UPLOAD_BYTES)),
         )
         .route("/api/v1/node/{id}/attachments", get(list_attachments))
+        .route("/api/v1/node/{id}/nodelinks", get(get_nodelinks_by_node))
+        .route(
+            "/api/v1/node/{id}/type-history",
+            get(project::get_node_type_history),
+        )
+        .route("/api/v1/node/{id}/history", get(project::get_node_history))
+        .route(
+            "/api/v1/node/{id}/merge/{target_id}",
+            post(project::merge_nodes),
+        )
+        .route(
+            "/api/v1/node/{id}/export/vcard",
+            get(export::export_node_vcard),
+        )
+        .route("/api/v1/node/{id}/split", post(split::split_node))
+        .route("/api/v1/node/{id}/copy", post(project::copy_node))
+        .route("/api/v1/node/{id}/restore", post(project::restore_node))
+        .route(
+            "/api/v1/project/{keep_id}/merge/{absorb_id}",
+            post(merge::merge_projects),
+        )
+        .route(
+            "/api/v1/node-type-styles",
+            get(styles::get_node_type_styles),
+        )
+        .route(
+            "/api/v1/attachment/by-hash/{sha256}",
+            get(attachment_dedup::get_attachments_by_hash),
+        )
         .route(
             "/api/v1/attachment/{attachment_id}",
             get(download_attachment)
@@ -131,53 +415,166 @@ pub async fn build_app(
             get(view_attachment),
         )
         .route("/api/v1/nodelink", post(post_nodelink))
-        .route("/api/v1/nodelink/{id}", delete(delete_nodelink))
+        .route(
+            "/api/v1/nodelink/{id}",
+            delete(delete_nodelink).put(update_nodelink),
+        )
         .route(
             "/api/v1/project/{id}/nodelinks",
             get(get_nodelinks_by_project),
         )
+        .route("/api/v1/project/{id}/trash", get(trash::get_project_trash))
+        .route(
+            "/api/v1/project/{id}/trash/restore",
+            post(trash::restore_trash),
+        )
         .route("/api/v1/project", post(post_project))
+        .route("/api/v1/project/full", post(post_project_full))
+        .route(
+            "/api/v1/project/import",
+            post(import_project).layer(DefaultBodyLimit::max(project::MAX_IMPORT_BYTES)),
+        )
         .route(
             "/api/v1/project/{id}",
             get(get_project).put(update_project).delete(delete_project),
         )
         .route("/api/v1/project/{id}/nodes", get(get_nodes_by_project))
+        .route(
+            "/api/v1/project/{id}/snapshot-settings",
+            get(snapshot::get_snapshot_settings).put(snapshot::update_snapshot_settings),
+        )
+        .route(
+            "/api/v1/project/{id}/snapshots",
+            get(snapshot::get_project_snapshots),
+        )
+        .route("/api/v1/snapshot/{id}", get(snapshot::get_snapshot))
+        .route(
+            "/api/v1/snapshot/{id}/diff/{other_id}",
+            get(snapshot::diff_snapshots),
+        )
+        .route(
+            "/api/v1/project/{id}/attachment-summary",
+            get(attachment::get_project_attachment_summary),
+        )
+        .route(
+            "/api/v1/project/{id}/contributors",
+            get(contributors::get_project_contributors),
+        )
+        .route("/api/v1/project/{id}/layout", post(layout::layout_project))
+        .route("/api/v1/project/{id}/archive", patch(archive_project))
+        .route("/api/v1/project/{id}/pin", post(pin_project))
+        .route("/api/v1/project/{id}/review", get(review::review_project))
+        .route("/api/v1/project/{id}/score", get(score::score_project))
+        .route("/api/v1/project/{id}/stats", get(get_project_stats))
+        .route("/api/v1/project/{id}/unarchive", patch(unarchive_project))
+        .route("/api/v1/project/{id}/unpin", post(unpin_project))
         .route("/api/v1/projects", get(get_projects))
         .route(
             "/api/v1/project/{id}/export/mermaid",
             get(export_project_mermaid),
         )
+        .route(
+            "/api/v1/project/{id}/export/graphml",
+            get(project::export_project_graphml),
+        )
+        .route(
+            "/api/v1/project/{id}/export/dot",
+            get(project::export_project_dot),
+        )
+        .route(
+            "/api/v1/project/{id}/export/jsonld",
+            get(export::export_project_jsonld),
+        )
+        .route(
+            "/api/v1/project/{id}/export/csv",
+            get(export::export_project_csv),
+        )
+        .route(
+            "/api/v1/project/{id}/export/archive",
+            get(archive::export_project_archive),
+        )
+        .route(
+            "/api/v1/project/{id}/export/report.pdf",
+            get(report::export_project_report),
+        )
+        .route("/api/v1/report-jobs/{id}", get(report::get_report_job))
+        .route(
+            "/api/v1/project/{id}/export/push",
+            post(export_push::push_project_export),
+        )
+        .route(
+            "/api/v1/project/{id}/export-records",
+            get(export_push::get_export_records),
+        )
+        .route(
+            "/api/v1/project/{id}/export/timeline.json",
+            get(export::export_project_timeline),
+        )
         .route("/api/v1/project/{id}/export", get(export_project))
         .route("/api/v1/search", get(search_global))
-        .nest_service("/static", static_service.clone())
+        .route("/api/v1/identify", post(identifier::identify_urls))
+        .route(
+            "/api/v1/render-markdown",
+            post(markdown::render_markdown_preview),
+        )
+        .route("/api/v1/status", get(status::get_status))
+        .route("/api/v1/capture", post(capture::post_capture))
+        .route(
+            "/api/v1/tokens",
+            get(tokens::get_tokens).post(tokens::post_token),
+        )
+        .route("/api/v1/tokens/{id}", delete(tokens::delete_token))
         .merge(openapi::api_route())
-        .fallback_service(static_service);
+        .layer(from_fn_with_state(
+            shared_state.clone(),
+            idempotency::idempotency,
+        ));
+    let protected_routes = with_frontend(protected_routes, shared_state).await;
+
+    // Probes don't log in
+    let public_routes = Router::new()
+        .route("/readyz", get(status::get_readyz))
+        .route("/api/v1/health", get(status::health_check))
+        // the SPA checks these before it knows whether to log in
+        .route("/api/v1/capabilities", get(capabilities::get_capabilities))
+        .route("/api/v1/build-info", get(status::get_build_info))
+        // scrapers can't log in either, --metrics-token protects it instead
+        .route("/metrics", get(metrics::get_metrics));
 
     let res = if enable_oauth {
         // Auth routes should NOT have the require_auth middleware
-        Router::new()
+        public_routes
             .route(Urls::Login.as_ref(), get(auth::auth_login))
             .route(Urls::Callback.as_ref(), get(auth::auth_callback))
             .route(Urls::Logout.as_ref(), get(auth::auth_logout))
             .merge(protected_routes.layer(from_fn_with_state(shared_state.clone(), require_auth)))
     } else {
-        protected_routes
+        public_routes.merge(protected_routes)
     };
 
     res
         // Add middleware to all routes
         .layer(
             ServiceBuilder::new()
+                // outermost so timeouts and overload responses are counted too
+                .layer(metrics::MetricsLayer::new(metrics))
                 .layer(session_layer)
                 .layer(
                     CompressionLayer::new()
                         .gzip(true)
                         .deflate(true)
-                        .quality(tower_http::CompressionLevel::Best),
+                        .quality(tower_http::CompressionLevel::Best)
+                        // raw attachments are already compressed
+                        .compress_when(
+                            DefaultPredicate::new()
+                                .and(NotForContentType::const_new("application/gzip"))
+                                .and(NotForContentType::const_new("application/zstd")),
+                        ),
                 )
                 // Handle errors from middleware
-                .layer(middleware::corslayer())
-                .layer(SetResponseHeaderLayer::overriding(
+                .layer(middleware::corslayer(&cors_allowed_origins, cors_max_age))
+                // handlers which know better, eg attachments, set their own
+                .layer(SetResponseHeaderLayer::if_not_present(
                     header::CACHE_CONTROL,
                     |response: &Response<Body>| {
                         if response.status() == StatusCode::OK {
@@ -196,6 +593,26 @@ pub async fn build_app(
         .with_state(shared_state.clone())
 }
 
+/// Serve the frontend from `./dist/`, or from the dev server when `--dev-proxy` is set
+#[cfg_attr(not(debug_assertions), allow(unused_variables))]
+async fn with_frontend(
+    routes: Router<SharedState>,
+    shared_state: &SharedState,
+) -> Router<SharedState> {
+    #[cfg(debug_assertions)]
+    if let Some(proxy) = shared_state.read().await.dev_proxy.clone() {
+        return routes.fallback_service(
+            Router::new()
+                .fallback(dev_proxy::proxy)
+                .with_state(Arc::new(proxy)),
+        );
+    }
+    let static_service = ServeDir::new("./dist/").append_index_html_on_directories(true);
+    routes
+        .nest_service("/static", static_service.clone())
+        .fallback_service(static_service)
+}
+
 async fn handle_error(error: BoxError) -> WebError {
     if error.is::<tower::timeout::error::Elapsed>() {
         return WebError::new(StatusCode::REQUEST_TIMEOUT, "request timed out");
@@ -3,13 +3,17 @@ use std::{process::ExitCode, sync::Arc};
 use axum::Router;
 use axum_server::tls_rustls::RustlsConfig;
 use clap::Parser;
-use osint_graph_backend::{build_app, cli::CliOpts, AppState};
+use osint_graph_backend::{
+    build_app,
+    cli::{CliOpts, Command},
+    AppState,
+};
 
 use tokio::{
     signal::unix::{signal, SignalKind},
     sync::RwLock,
 };
-use tracing::{error, info, warn};
+use tracing::{error, info};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 fn export_openapi() {
@@ -42,6 +46,44 @@ async fn main() -> ExitCode {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    match &cli.command {
+        Some(Command::Migrate(opts)) => {
+            return match osint_graph_backend::migrate::run(&cli.db_path(), opts).await {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(err) => {
+                    error!("{err}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Some(Command::Restore(opts)) => {
+            return match osint_graph_backend::restore::run(&cli.db_path(), opts).await {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(err) => {
+                    error!("{err}");
+                    ExitCode::from(err.exit_code())
+                }
+            };
+        }
+        None => {}
+    }
+
+    // held for as long as the server runs, `osint-graph restore` refuses to run while it is
+    let _db_lock = match osint_graph_backend::storage::lock_database_for_server(&cli.db_path()) {
+        Ok(lock) => lock,
+        Err(_) => {
+            let pid =
+                std::fs::read_to_string(osint_graph_backend::storage::lock_path(&cli.db_path()))
+                    .unwrap_or_default();
+            error!(
+                pid = pid.trim(),
+                "{} is in use by a restore or another server, not starting",
+                cli.db_path().display()
+            );
+            return ExitCode::FAILURE;
+        }
+    };
+
     let appstate = match AppState::new(&cli).await {
         Ok(state) => state,
         Err(err) => {
@@ -50,11 +92,24 @@ async fn main() -> ExitCode {
         }
     };
     let db_pool = appstate.conn.get_sqlite_connection_pool().clone();
+    osint_graph_backend::attachment_codec::spawn_attachment_reencode(
+        appstate.conn.clone(),
+        appstate.attachment_codec,
+    );
+    osint_graph_backend::attachment_dedup::spawn_attachment_hash_backfill(appstate.conn.clone());
 
     let shared_state = Arc::new(RwLock::new(appstate));
 
     let app = build_app(&shared_state, db_pool, true).await;
 
+    let tls_config = match RustlsConfig::from_pem_file(&cli.tls_cert, &cli.tls_key)
+        .await
+        .inspect_err(|err| error!(error=?err, "Failed to configure TLS server"))
+    {
+        Ok(val) => val,
+        Err(_) => return ExitCode::FAILURE,
+    };
+
     // Run our app with hyper
     let mut hangup_waiter = match signal(SignalKind::hangup()) {
         Ok(signal) => signal,
@@ -63,18 +118,16 @@ async fn main() -> ExitCode {
             return ExitCode::FAILURE;
         }
     };
+    let server = run_server(&cli, app, tls_config.clone());
+    tokio::pin!(server);
     loop {
         tokio::select! {
-            res = run_server(&cli, app.clone()) => {
-                if let Some(res) = res {
-                    return res;
-                }
+            res = &mut server => {
+                return res;
             }
             _ = hangup_waiter.recv() => {
-                warn!("Received SIGHUP, shutting down.");
-                break
-                // TODO: Implement configuration reload logic here
-
+                info!("Received SIGHUP, reloading the TLS certificate.");
+                reload_tls(&cli, &tls_config).await;
             }
             _ = tokio::signal::ctrl_c() => {
                 info!("Received Ctrl-C, shutting down.");
@@ -85,21 +138,40 @@ async fn main() -> ExitCode {
     ExitCode::SUCCESS
 }
 
-async fn run_server(cli: &CliOpts, app: Router) -> Option<ExitCode> {
-    let tls_server_config = match RustlsConfig::from_pem_file(&cli.tls_cert, &cli.tls_key)
+/// Swap in the certificate and key from disk, new connections get them and existing ones carry
+/// on with what they negotiated. If they don't load the old ones stay in use.
+async fn reload_tls(cli: &CliOpts, tls_config: &RustlsConfig) {
+    match tls_config
+        .reload_from_pem_file(&cli.tls_cert, &cli.tls_key)
         .await
-        .inspect_err(|err| error!(error=?err, "Failed to configure TLS server"))
     {
-        Ok(val) => val,
-        Err(_) => return Some(ExitCode::FAILURE),
-    };
+        Ok(()) => info!(
+            cert = %cli.tls_cert.display(),
+            key = %cli.tls_key.display(),
+            "Reloaded the TLS certificate"
+        ),
+        Err(err) => error!(
+            error = ?err,
+            cert = %cli.tls_cert.display(),
+            key = %cli.tls_key.display(),
+            "Failed to reload the TLS certificate, still using the old one"
+        ),
+    }
+}
+
+async fn run_server(cli: &CliOpts, app: Router, tls_config: RustlsConfig) -> ExitCode {
     info!("Starting server on {}", cli.frontend_url);
-    axum_server::bind_rustls(
+    match axum_server::bind_rustls(
         cli.listener_address.parse().expect("Invalid address"),
-        tls_server_config,
+        tls_config,
     )
     .serve(app.into_make_service())
     .await
-    .unwrap();
-    None
+    {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            error!(error = ?err, "Server failed");
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,498 @@
+//! Markdown rendering for node notes, comments and case notes
+//!
+//! This is the one place the XSS policy lives, everything which turns user text into HTML goes
+//! through [render_markdown]. Markdown is parsed by pulldown-cmark with raw HTML turned into text,
+//! and what comes out is cleaned by ammonia with the [Allowlist] of the [MarkdownContext], so
+//! only its tags, attributes and URL schemes survive, eg images are only allowed in case notes.
+//!
+//! The supported subset is CommonMark without extensions: headings, paragraphs, emphasis, inline
+//! and fenced code, block quotes, lists, rules, links and images.
+
+use std::collections::{HashMap, HashSet};
+
+use ammonia::UrlRelative;
+use axum::{
+    http::{
+        header::{CACHE_CONTROL, ETAG},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    Json,
+};
+use pulldown_cmark::{Event, LinkType, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    attachment::etag_matches,
+    attachment_dedup::content_hash,
+    project::{ErrorResponse, WebError},
+};
+
+/// Largest text [render_markdown_preview] accepts
+pub const MAX_MARKDOWN_BYTES: usize = 64 * 1024;
+/// Error code for text over [MAX_MARKDOWN_BYTES]
+pub const MARKDOWN_TOO_LARGE: &str = "markdown_too_large";
+/// The same text always renders the same way, until the renderer changes
+pub const MARKDOWN_CACHE_CONTROL: &str = "private, max-age=31536000, immutable";
+/// Part of the content hash, bump it when the output changes so cached renders are dropped
+const RENDERER_VERSION: &str = "2";
+
+/// Block quotes nested deeper than this are shown unquoted
+const MAX_QUOTE_DEPTH: usize = 8;
+const LINK_REL: &str = "nofollow noopener noreferrer";
+const LINK_SCHEMES: &[&str] = &["http", "https", "mailto"];
+const IMAGE_SCHEMES: &[&str] = &["http", "https"];
+/// Tags every context may produce
+const BASE_TAGS: &[&str] = &[
+    "p",
+    "strong",
+    "em",
+    "code",
+    "pre",
+    "blockquote",
+    "ul",
+    "ol",
+    "li",
+    "hr",
+    "br",
+    "a",
+];
+const HEADING_TAGS: &[&str] = &["h1", "h2", "h3", "h4", "h5", "h6"];
+
+/// Where the markdown is shown, which decides its [Allowlist]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, ToSchema, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkdownContext {
+    NodeNote,
+    Comment,
+    CaseNote,
+}
+
+/// What a context may produce on top of text, emphasis, code, quotes, lists and links
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Allowlist {
+    /// Otherwise headings are shown as bold paragraphs
+    pub headings: bool,
+    /// Otherwise images are shown as links to them
+    pub images: bool,
+}
+
+impl MarkdownContext {
+    pub const ALL: [MarkdownContext; 3] = [
+        MarkdownContext::NodeNote,
+        MarkdownContext::Comment,
+        MarkdownContext::CaseNote,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MarkdownContext::NodeNote => "node_note",
+            MarkdownContext::Comment => "comment",
+            MarkdownContext::CaseNote => "case_note",
+        }
+    }
+
+    pub fn allowlist(&self) -> Allowlist {
+        match self {
+            MarkdownContext::NodeNote => Allowlist {
+                headings: true,
+                images: false,
+            },
+            MarkdownContext::Comment => Allowlist {
+                headings: false,
+                images: false,
+            },
+            MarkdownContext::CaseNote => Allowlist {
+                headings: true,
+                images: true,
+            },
+        }
+    }
+}
+
+impl Allowlist {
+    /// The ammonia config enforcing this on rendered HTML
+    pub fn sanitizer(&self) -> ammonia::Builder<'static> {
+        let mut tags: HashSet<&str> = BASE_TAGS.iter().copied().collect();
+        let mut tag_attributes = HashMap::from([
+            ("a", HashSet::from(["href"])),
+            ("ol", HashSet::from(["start"])),
+        ]);
+        if self.headings {
+            tags.extend(HEADING_TAGS);
+        }
+        if self.images {
+            tags.insert("img");
+            tag_attributes.insert("img", HashSet::from(["src", "alt"]));
+        }
+        let mut builder = ammonia::Builder::empty();
+        builder
+            .tags(tags)
+            .tag_attributes(tag_attributes)
+            .url_schemes(LINK_SCHEMES.iter().copied().collect())
+            .url_relative(UrlRelative::Deny)
+            .link_rel(Some(LINK_REL));
+        builder
+    }
+}
+
+/// Render markdown as HTML which is safe to put straight into a page
+pub fn render_markdown(text: &str, context: MarkdownContext) -> String {
+    let allow = context.allowlist();
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, events(text, allow).into_iter());
+    allow.sanitizer().clean(&html).to_string()
+}
+
+fn allowed_url(url: &str, schemes: &[&str]) -> bool {
+    url::Url::parse(url.trim()).is_ok_and(|url| schemes.contains(&url.scheme()))
+}
+
+/// What a link or image in the markdown became
+#[derive(Clone, Copy, PartialEq)]
+enum Opened {
+    Link,
+    Image,
+    /// Only its text is shown
+    Dropped,
+}
+
+/// The parsed markdown, changed for what [Allowlist::sanitizer] would otherwise strip whole
+///
+/// Raw HTML is shown as the text it was written as, line breaks are kept, headings become bold paragraphs and images
+/// links to them where they aren't allowed, and links with a bad URL or inside another link and
+/// quotes past [MAX_QUOTE_DEPTH] are unwrapped.
+fn events(text: &str, allow: Allowlist) -> Vec<Event<'_>> {
+    let mut res = Vec::new();
+    let mut opened: Vec<Opened> = Vec::new();
+    let mut quote_depth = 0;
+    for event in Parser::new(text) {
+        let in_link = opened.contains(&Opened::Link);
+        match event {
+            Event::Html(html) | Event::InlineHtml(html) => res.push(Event::Text(html)),
+            Event::SoftBreak => res.push(Event::HardBreak),
+            Event::Start(Tag::HtmlBlock) => res.push(Event::Start(Tag::Paragraph)),
+            Event::End(TagEnd::HtmlBlock) => res.push(Event::End(TagEnd::Paragraph)),
+            Event::Start(Tag::Heading { .. }) if !allow.headings => {
+                res.extend([Event::Start(Tag::Paragraph), Event::Start(Tag::Strong)])
+            }
+            Event::End(TagEnd::Heading(_)) if !allow.headings => {
+                res.extend([Event::End(TagEnd::Strong), Event::End(TagEnd::Paragraph)])
+            }
+            Event::Start(Tag::BlockQuote(kind)) => {
+                quote_depth += 1;
+                if quote_depth <= MAX_QUOTE_DEPTH {
+                    res.push(Event::Start(Tag::BlockQuote(kind)));
+                }
+            }
+            Event::End(TagEnd::BlockQuote(kind)) => {
+                if quote_depth <= MAX_QUOTE_DEPTH {
+                    res.push(Event::End(TagEnd::BlockQuote(kind)));
+                }
+                quote_depth -= 1;
+            }
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => match !in_link && allowed_url(&dest_url, LINK_SCHEMES) {
+                true => {
+                    opened.push(Opened::Link);
+                    res.push(Event::Start(Tag::Link {
+                        link_type,
+                        dest_url,
+                        title,
+                        id,
+                    }));
+                }
+                false => opened.push(Opened::Dropped),
+            },
+            Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => match allowed_url(&dest_url, IMAGE_SCHEMES) {
+                true if allow.images => {
+                    opened.push(Opened::Image);
+                    res.push(Event::Start(Tag::Image {
+                        link_type,
+                        dest_url,
+                        title,
+                        id,
+                    }));
+                }
+                true if !in_link => {
+                    opened.push(Opened::Link);
+                    res.push(Event::Start(Tag::Link {
+                        link_type: LinkType::Inline,
+                        dest_url,
+                        title,
+                        id,
+                    }));
+                }
+                _ => opened.push(Opened::Dropped),
+            },
+            Event::End(TagEnd::Link | TagEnd::Image) => match opened.pop() {
+                Some(Opened::Link) => res.push(Event::End(TagEnd::Link)),
+                Some(Opened::Image) => res.push(Event::End(TagEnd::Image)),
+                _ => {}
+            },
+            event => res.push(event),
+        }
+    }
+    res
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct RenderMarkdownRequest {
+    pub text: String,
+    pub context: MarkdownContext,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct RenderedMarkdown {
+    pub html: String,
+    /// Hash of the text, context and renderer, also sent as the ETag
+    pub hash: String,
+}
+
+/// The key a render is cached under, see [RENDERER_VERSION]
+pub fn markdown_hash(text: &str, context: MarkdownContext) -> String {
+    content_hash(format!("{RENDERER_VERSION}\n{}\n{text}", context.as_str()).as_bytes())
+}
+
+/// Render markdown to sanitised HTML, so the frontend doesn't need a renderer of its own
+///
+/// Responses can be cached for good under their hash, and `If-None-Match` with it gets a 304.
+#[utoipa::path(
+    post,
+    path = "/api/v1/render-markdown",
+    tag = "nodes",
+    operation_id = "render_markdown_preview",
+    request_body = RenderMarkdownRequest,
+    responses(
+        (status = OK, description = "The rendered HTML", body = RenderedMarkdown),
+        (status = NOT_MODIFIED, description = "The If-None-Match hash is still current"),
+        (status = PAYLOAD_TOO_LARGE, description = "The text is over 64 KiB", body = ErrorResponse)
+    )
+)]
+pub async fn render_markdown_preview(
+    headers: HeaderMap,
+    Json(request): Json<RenderMarkdownRequest>,
+) -> Result<Response, WebError> {
+    if request.text.len() > MAX_MARKDOWN_BYTES {
+        return Err(WebError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Markdown is limited to {MAX_MARKDOWN_BYTES} bytes"),
+        )
+        .with_code(MARKDOWN_TOO_LARGE)
+        .with_detail("max_bytes", MAX_MARKDOWN_BYTES.to_string()));
+    }
+    let hash = markdown_hash(&request.text, request.context);
+    let etag = format!("\"{hash}\"");
+    let cache_headers = [
+        (ETAG, HeaderValue::from_str(&etag)?),
+        (
+            CACHE_CONTROL,
+            HeaderValue::from_static(MARKDOWN_CACHE_CONTROL),
+        ),
+    ];
+    if etag_matches(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+    let html = render_markdown(&request.text, request.context);
+    Ok((cache_headers, Json(RenderedMarkdown { html, hash })).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALLOWED_TAGS: &[&str] = &[
+        "p",
+        "h1",
+        "h2",
+        "h3",
+        "h4",
+        "h5",
+        "h6",
+        "strong",
+        "em",
+        "code",
+        "pre",
+        "blockquote",
+        "ul",
+        "ol",
+        "li",
+        "hr",
+        "br",
+        "a",
+        "img",
+    ];
+    const ALLOWED_ATTRIBUTES: &[&str] = &["href", "rel", "src", "alt", "start"];
+
+    /// Adversarial input, every context has to render all of it harmlessly
+    const XSS_CORPUS: &[&str] = &[
+        "<script>alert(1)</script>",
+        "<img src=x onerror=alert(1)>",
+        "<svg/onload=alert(1)>",
+        "<iframe src=\"javascript:alert(1)\"></iframe>",
+        "[click](javascript:alert(1))",
+        "[click](JaVaScRiPt:alert(1))",
+        "[click]( javascript:alert(1))",
+        "[click](java\tscript:alert(1))",
+        "[click](data:text/html;base64,PHNjcmlwdD5hbGVydCgxKTwvc2NyaXB0Pg==)",
+        "[click](vbscript:msgbox(1))",
+        "![x](javascript:alert(1))",
+        "![x\" onerror=\"alert(1)](https://example.com/a.png)",
+        "[x](https://example.com/\"onmouseover=\"alert(1))",
+        "[x](https://example.com/'><script>alert(1)</script>)",
+        "[<img src=x onerror=alert(1)>](https://example.com)",
+        "`<script>alert(1)</script>`",
+        "```\n</code></pre><script>alert(1)</script>\n```",
+        "> <script>alert(1)</script>",
+        "- <b onmouseover=alert(1)>x</b>",
+        "# <script>alert(1)</script>",
+        "**<script>**alert(1)**</script>**",
+        "&lt;script&gt;alert(1)&lt;/script&gt;",
+        "<a href=\"javascript:alert(1)\">x</a>",
+        "<style>body{display:none}</style>",
+        "<!-- <script>alert(1)</script> -->",
+        "[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[[(https://example.com)",
+        "**************************************************x",
+    ];
+
+    /// Every tag and attribute in `html`, which only works on output from [render_markdown]
+    fn tags(html: &str) -> Vec<(String, Vec<(String, String)>)> {
+        let mut res = Vec::new();
+        for tag in html.split('<').skip(1) {
+            let tag = tag.split('>').next().expect("split always has one item");
+            let tag = tag.trim_start_matches('/');
+            let (name, mut attributes) = tag.split_once(' ').unwrap_or((tag, ""));
+            let mut parsed = Vec::new();
+            while let Some((attribute, rest)) = attributes.trim_start().split_once("=\"") {
+                let (value, rest) = rest.split_once('"').expect("attribute values are quoted");
+                parsed.push((attribute.to_string(), value.to_string()));
+                attributes = rest;
+            }
+            assert!(
+                attributes.trim().is_empty(),
+                "stray attribute text in <{tag}>"
+            );
+            res.push((name.to_string(), parsed));
+        }
+        res
+    }
+
+    #[test]
+    fn test_xss_corpus() {
+        for context in MarkdownContext::ALL {
+            for input in XSS_CORPUS {
+                let html = render_markdown(input, context);
+                for (name, attributes) in tags(&html) {
+                    assert!(
+                        ALLOWED_TAGS.contains(&name.as_str()),
+                        "{context:?} {input:?} made <{name}>: {html}"
+                    );
+                    for (attribute, value) in attributes {
+                        assert!(
+                            ALLOWED_ATTRIBUTES.contains(&attribute.as_str()),
+                            "{context:?} {input:?} made {attribute}=: {html}"
+                        );
+                        if matches!(attribute.as_str(), "href" | "src") {
+                            assert!(
+                                ["http://", "https://", "mailto:"]
+                                    .iter()
+                                    .any(|scheme| value.starts_with(scheme)),
+                                "{context:?} {input:?} linked to {value}: {html}"
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let context = MarkdownContext::NodeNote;
+        assert_eq!(
+            render_markdown("Seen on *their* **about** page", context),
+            "<p>Seen on <em>their</em> <strong>about</strong> page</p>\n"
+        );
+        assert_eq!(
+            render_markdown("snake_case_name and `a < b`", context),
+            "<p>snake_case_name and <code>a &lt; b</code></p>\n"
+        );
+        assert_eq!(
+            render_markdown("## Sources\n\n- [Profile](https://example.com/a?b=1&c=2)\n- two\n  lines", context),
+            "<h2>Sources</h2>\n<ul>\n<li><a href=\"https://example.com/a?b=1&amp;c=2\" rel=\"nofollow noopener noreferrer\">Profile</a></li>\n<li>two<br>\nlines</li>\n</ul>\n"
+        );
+        assert_eq!(
+            render_markdown("3. third\n4. fourth\n\n---\n> quoted\n>> twice", context),
+            "<ol start=\"3\">\n<li>third</li>\n<li>fourth</li>\n</ol>\n<hr>\n<blockquote>\n<p>quoted</p>\n<blockquote>\n<p>twice</p>\n</blockquote>\n</blockquote>\n"
+        );
+        assert_eq!(
+            render_markdown("```\nfn main() {}\n<tag>\n```", context),
+            "<pre><code>fn main() {}\n&lt;tag&gt;\n</code></pre>\n"
+        );
+        assert_eq!(
+            render_markdown("[bad](javascript:alert(1)) \\*not em\\*", context),
+            "<p>bad *not em*</p>\n"
+        );
+        assert_eq!(
+            render_markdown("<b onclick=\"x()\">bold</b>\n\n<div>\nblock\n</div>", context),
+            "<p>&lt;b onclick=\"x()\"&gt;bold&lt;/b&gt;</p>\n<p>&lt;div&gt;\nblock\n&lt;/div&gt;</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_context_allowlists() {
+        let image = "![Logo](https://example.com/logo.png)";
+        let heading = "# Findings";
+        let rendered = |context| {
+            (
+                render_markdown(image, context),
+                render_markdown(heading, context),
+            )
+        };
+
+        let (image_html, heading_html) = rendered(MarkdownContext::CaseNote);
+        assert_eq!(
+            image_html,
+            "<p><img src=\"https://example.com/logo.png\" alt=\"Logo\"></p>\n"
+        );
+        assert_eq!(heading_html, "<h1>Findings</h1>\n");
+
+        let (image_html, heading_html) = rendered(MarkdownContext::NodeNote);
+        assert_eq!(
+            image_html,
+            "<p><a href=\"https://example.com/logo.png\" rel=\"nofollow noopener noreferrer\">Logo</a></p>\n"
+        );
+        assert_eq!(heading_html, "<h1>Findings</h1>\n");
+
+        let (image_html, heading_html) = rendered(MarkdownContext::Comment);
+        assert!(!image_html.contains("<img"));
+        assert_eq!(heading_html, "<p><strong>Findings</strong></p>\n");
+    }
+
+    #[test]
+    fn test_pathological_input_is_quick() {
+        for input in [
+            "[".repeat(MAX_MARKDOWN_BYTES),
+            "*a".repeat(MAX_MARKDOWN_BYTES / 2),
+            "_ ".repeat(MAX_MARKDOWN_BYTES / 2),
+            "`".repeat(MAX_MARKDOWN_BYTES),
+            "> ".repeat(MAX_MARKDOWN_BYTES / 2),
+            "**".repeat(MAX_MARKDOWN_BYTES / 2),
+            "[a](".repeat(MAX_MARKDOWN_BYTES / 4),
+        ] {
+            let start = std::time::Instant::now();
+            render_markdown(&input, MarkdownContext::CaseNote);
+            assert!(start.elapsed() < std::time::Duration::from_secs(2));
+        }
+    }
+}
@@ -0,0 +1,341 @@
+//! Audio and video container metadata
+//!
+//! Uploads are sniffed for common container signatures so `<audio>`/`<video>` players get the
+//! right MIME type even when the browser guessed wrong, and WAV and MP4 headers are parsed for
+//! duration, dimensions and codecs. Everything here works on the uploaded bytes alone, anything
+//! which can't be parsed just doesn't get metadata.
+
+use sea_orm::FromJsonQueryResult;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Boxes nested deeper than this in an MP4 are ignored
+const MAX_BOX_DEPTH: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Container {
+    Wav,
+    Mp4,
+    Ogg,
+    Mp3,
+    Flac,
+    Webm,
+    Matroska,
+}
+
+impl Container {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Container::Wav => "wav",
+            Container::Mp4 => "mp4",
+            Container::Ogg => "ogg",
+            Container::Mp3 => "mp3",
+            Container::Flac => "flac",
+            Container::Webm => "webm",
+            Container::Matroska => "matroska",
+        }
+    }
+
+    /// MIME types which are reasonable for this container, the first is used when correcting
+    pub fn media_types(&self) -> &'static [&'static str] {
+        match self {
+            Container::Wav => &["audio/wav", "audio/x-wav", "audio/wave", "audio/vnd.wave"],
+            Container::Mp4 => &[
+                "video/mp4",
+                "audio/mp4",
+                "audio/x-m4a",
+                "audio/m4a",
+                "video/quicktime",
+            ],
+            Container::Ogg => &["audio/ogg", "video/ogg", "application/ogg", "audio/opus"],
+            Container::Mp3 => &["audio/mpeg", "audio/mp3", "audio/x-mpeg"],
+            Container::Flac => &["audio/flac", "audio/x-flac"],
+            Container::Webm => &["video/webm", "audio/webm"],
+            Container::Matroska => &["video/x-matroska", "audio/x-matroska", "video/webm"],
+        }
+    }
+}
+
+/// What could be read from an audio or video file's container
+#[derive(
+    Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, FromJsonQueryResult, ToSchema,
+)]
+pub struct MediaInfo {
+    /// Container format, eg `wav` or `mp4`
+    pub container: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// Codec identifiers, eg `pcm` or MP4 sample entry types like `avc1`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub codecs: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channels: Option<u16>,
+}
+
+/// Work out the container from the first few bytes of a file
+pub fn sniff(data: &[u8]) -> Option<Container> {
+    if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WAVE" {
+        Some(Container::Wav)
+    } else if data.len() >= 8 && &data[4..8] == b"ftyp" {
+        Some(Container::Mp4)
+    } else if data.starts_with(b"OggS") {
+        Some(Container::Ogg)
+    } else if data.starts_with(b"fLaC") {
+        Some(Container::Flac)
+    } else if data.starts_with(b"ID3")
+        || (data.len() >= 2 && data[0] == 0xFF && matches!(data[1], 0xFB | 0xFA | 0xF3 | 0xF2))
+    {
+        Some(Container::Mp3)
+    } else if data.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        // the EBML header's DocType is near the start
+        let header = &data[..data.len().min(64)];
+        match header.windows(4).any(|window| window == b"webm") {
+            true => Some(Container::Webm),
+            false => Some(Container::Matroska),
+        }
+    } else {
+        None
+    }
+}
+
+/// Sniff and parse an audio or video file, `None` if it isn't one we recognise
+pub fn probe(data: &[u8]) -> Option<MediaInfo> {
+    let container = sniff(data)?;
+    let mut info = match container {
+        Container::Wav => parse_wav(data).unwrap_or_default(),
+        Container::Mp4 => parse_mp4(data),
+        _ => MediaInfo::default(),
+    };
+    info.container = container.as_str().to_string();
+    Some(info)
+}
+
+/// The declared content type, unless it's obviously wrong for the file's container
+pub fn correct_content_type(declared: &str, data: &[u8], media: Option<&MediaInfo>) -> String {
+    let Some(container) = sniff(data) else {
+        return declared.to_string();
+    };
+    let essence = declared
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    if container.media_types().contains(&essence.as_str()) {
+        return declared.to_string();
+    }
+    match container {
+        // no picture means it's an audio file in an MP4 wrapper, like an m4a
+        Container::Mp4 if media.is_some_and(|media| media.width.is_none()) => "audio/mp4",
+        container => container.media_types()[0],
+    }
+    .to_string()
+}
+
+fn read_u16_le(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32_le(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32_be(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64_be(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+/// Walk the RIFF chunks for the `fmt ` header and the size of the `data` chunk
+fn parse_wav(data: &[u8]) -> Option<MediaInfo> {
+    let mut info = MediaInfo::default();
+    let mut byte_rate = None;
+    let mut data_size = None;
+    let mut offset = 12;
+    while offset + 8 <= data.len() {
+        let id = &data[offset..offset + 4];
+        let size = read_u32_le(data, offset + 4)? as usize;
+        let body = offset + 8;
+        match id {
+            b"fmt " => {
+                info.codecs = vec![match read_u16_le(data, body)? {
+                    1 => "pcm".to_string(),
+                    3 => "pcm_float".to_string(),
+                    6 => "alaw".to_string(),
+                    7 => "mulaw".to_string(),
+                    0x55 => "mp3".to_string(),
+                    0xFFFE => "extensible".to_string(),
+                    other => format!("0x{other:04x}"),
+                }];
+                info.channels = Some(read_u16_le(data, body + 2)?);
+                info.sample_rate = Some(read_u32_le(data, body + 4)?);
+                byte_rate = Some(read_u32_le(data, body + 8)?);
+            }
+            // the data chunk's size is trusted even if the upload was truncated
+            b"data" => data_size = Some(size as u64),
+            _ => {}
+        }
+        // chunks are padded to an even length
+        offset = body.checked_add(size)?.checked_add(size % 2)?;
+    }
+    if let (Some(byte_rate), Some(data_size)) = (byte_rate, data_size) {
+        if byte_rate > 0 {
+            info.duration_ms = Some(data_size * 1000 / byte_rate as u64);
+        }
+    }
+    Some(info)
+}
+
+/// Read the movie header's duration and each track's dimensions and codec
+fn parse_mp4(data: &[u8]) -> MediaInfo {
+    let mut info = MediaInfo::default();
+    walk_mp4_boxes(data, 0, &mut info);
+    info
+}
+
+fn walk_mp4_boxes(data: &[u8], depth: usize, info: &mut MediaInfo) -> Option<()> {
+    if depth > MAX_BOX_DEPTH {
+        return None;
+    }
+    let mut offset = 0;
+    while offset + 8 <= data.len() {
+        let (header, size) = match read_u32_be(data, offset)? {
+            0 => (8, data.len() - offset),
+            1 => (16, usize::try_from(read_u64_be(data, offset + 8)?).ok()?),
+            size => (8, size as usize),
+        };
+        if size < header || offset.checked_add(size)? > data.len() {
+            return None;
+        }
+        let kind = &data[offset + 4..offset + 8];
+        let body = &data[offset + header..offset + size];
+        match kind {
+            b"moov" | b"trak" | b"mdia" | b"minf" | b"stbl" => {
+                walk_mp4_boxes(body, depth + 1, info);
+            }
+            b"mvhd" => {
+                let (timescale, duration) = match body.first()? {
+                    1 => (read_u32_be(body, 20)?, read_u64_be(body, 24)?),
+                    _ => (read_u32_be(body, 12)?, read_u32_be(body, 16)? as u64),
+                };
+                if timescale > 0 && duration != u32::MAX as u64 && duration != u64::MAX {
+                    info.duration_ms = Some(duration.saturating_mul(1000) / timescale as u64);
+                }
+            }
+            b"tkhd" => {
+                let dimensions = match body.first()? {
+                    1 => 88,
+                    _ => 76,
+                };
+                // 16.16 fixed point, audio tracks are zero by zero
+                let width = read_u32_be(body, dimensions)? >> 16;
+                let height = read_u32_be(body, dimensions + 4)? >> 16;
+                if width > 0 && height > 0 && info.width.is_none() {
+                    info.width = Some(width);
+                    info.height = Some(height);
+                }
+            }
+            b"stsd" => {
+                // the first sample entry's type is the codec
+                let codec = body.get(12..16)?;
+                if codec.iter().all(u8::is_ascii_graphic) {
+                    let codec = String::from_utf8_lossy(codec).to_string();
+                    if !info.codecs.contains(&codec) {
+                        info.codecs.push(codec);
+                    }
+                }
+            }
+            _ => {}
+        }
+        offset += size;
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(sniff(b"RIFF\0\0\0\0WAVEfmt "), Some(Container::Wav));
+        assert_eq!(sniff(b"\0\0\0\x18ftypisom"), Some(Container::Mp4));
+        assert_eq!(sniff(b"OggS\0\x02"), Some(Container::Ogg));
+        assert_eq!(sniff(b"fLaC\0\0\0\x22"), Some(Container::Flac));
+        assert_eq!(sniff(b"ID3\x04\0"), Some(Container::Mp3));
+        assert_eq!(
+            sniff(b"\x1a\x45\xdf\xa3\x9f\x42\x82\x84webm"),
+            Some(Container::Webm)
+        );
+        assert_eq!(sniff(b"RIFF\0\0\0\0AVI "), None);
+        assert_eq!(sniff(b"hello world"), None);
+        assert_eq!(sniff(b""), None);
+    }
+
+    #[test]
+    fn test_correct_content_type() {
+        let wav = b"RIFF\0\0\0\0WAVE";
+        assert_eq!(
+            correct_content_type("application/octet-stream", wav, None),
+            "audio/wav"
+        );
+        assert_eq!(
+            correct_content_type("audio/x-wav", wav, None),
+            "audio/x-wav"
+        );
+        assert_eq!(
+            correct_content_type("Audio/WAV; codecs=1", wav, None),
+            "Audio/WAV; codecs=1"
+        );
+        assert_eq!(
+            correct_content_type("text/plain", b"hello", None),
+            "text/plain"
+        );
+
+        let mp4 = b"\0\0\0\x08ftyp";
+        let audio = MediaInfo::default();
+        let video = MediaInfo {
+            width: Some(320),
+            height: Some(240),
+            ..Default::default()
+        };
+        assert_eq!(
+            correct_content_type("text/plain", mp4, Some(&audio)),
+            "audio/mp4"
+        );
+        assert_eq!(
+            correct_content_type("text/plain", mp4, Some(&video)),
+            "video/mp4"
+        );
+    }
+
+    #[test]
+    fn test_probe_garbage() {
+        // recognisable signatures with broken headers still get a container, nothing else
+        let info = probe(b"RIFF\0\0\0\0WAVEfmt \xff\xff\xff\xff").expect("sniffed as wav");
+        assert_eq!(info.container, "wav");
+        assert_eq!(info.duration_ms, None);
+
+        let info = probe(b"\xff\xff\xff\xffftypisom").expect("sniffed as mp4");
+        assert_eq!(info.container, "mp4");
+        assert_eq!(info.duration_ms, None);
+
+        assert_eq!(probe(b"not media"), None);
+    }
+}
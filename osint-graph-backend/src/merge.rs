@@ -0,0 +1,302 @@
+//! Merging one project into another
+//!
+//! Only live nodes are moved, deduped or counted. Nodes in the absorbed project's trash go to the
+//! kept project's trash as they are, so they can still be restored.
+
+use std::collections::{HashMap, HashSet};
+
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use chrono::Utc;
+use osint_graph_shared::{node::NodeType, nodelink::LinkType};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ActiveValue::Set, ColumnTrait, EntityTrait, IntoActiveModel,
+    PaginatorTrait, QueryFilter, QueryOrder,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    access::check_project_access,
+    entity::{attachment, node, nodelink, project},
+    extract::{Path, Query},
+    identifier::canonical_key,
+    oauth::middleware::AuthUser,
+    project::{ErrorResponse, WebError},
+    quota::{warning_headers, QuotaKind},
+    SharedState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct MergeQuery {
+    /// Collapse nodes with the same type and [canonical_key] into one
+    #[serde(default)]
+    pub dedupe: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ProjectMergeResponse {
+    pub project: project::Model,
+    /// Nodes moved across from the absorbed project
+    pub moved_nodes: u64,
+    /// Absorbed nodes folded into an existing node, their links and attachments moved to it
+    pub deduped_nodes: u64,
+    pub moved_nodelinks: u64,
+    /// Links dropped because deduping made them loops or repeats of another link
+    pub deduped_nodelinks: u64,
+    /// Attachments on nodes which came across, including those moved onto a deduped node
+    pub moved_attachments: u64,
+    /// Nodes in the absorbed project's trash, now in the kept project's trash
+    pub trashed_nodes: u64,
+    /// False when the absorbed project is the Inbox, which is emptied but kept
+    pub absorbed_project_deleted: bool,
+}
+
+/// Tack `extra` notes onto `notes`, skipping blanks and repeats
+pub(crate) fn merge_notes(notes: Option<String>, extra: Option<String>) -> Option<String> {
+    match (notes, extra) {
+        (Some(notes), Some(extra)) if !extra.trim().is_empty() && !notes.contains(extra.trim()) => {
+            if notes.trim().is_empty() {
+                Some(extra)
+            } else {
+                Some(format!("{}\n\n{}", notes, extra))
+            }
+        }
+        (None, extra) => extra,
+        (notes, _) => notes,
+    }
+}
+
+/// Move everything in one project into another, then delete the emptied project
+#[utoipa::path(
+    post,
+    path = "/api/v1/project/{keep_id}/merge/{absorb_id}",
+    tag = "projects",
+    operation_id = "merge_projects",
+    params(
+        ("keep_id" = Uuid, Path, description = "Project to merge into"),
+        ("absorb_id" = Uuid, Path, description = "Project to empty and delete"),
+        ("dedupe" = bool, Query, description = "Collapse nodes with the same type and normalised value")
+    ),
+    responses(
+        (status = OK, description = "Projects merged", body = ProjectMergeResponse),
+        (status = BAD_REQUEST, description = "Invalid merge request", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Either project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found")
+    )
+)]
+pub async fn merge_projects(
+    Path((keep_id, absorb_id)): Path<(Uuid, Uuid)>,
+    Query(query): Query<MergeQuery>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<(axum::http::HeaderMap, Json<ProjectMergeResponse>), WebError> {
+    if keep_id == absorb_id {
+        return Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            "Can't merge a project into itself",
+        ));
+    }
+
+    let reader = state.read().await;
+    let txn = reader.begin().await?;
+
+    let keep = project::Entity::find_by_id(keep_id)
+        .one(&txn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Project {} not found", keep_id)))?;
+    let absorb = project::Entity::find_by_id(absorb_id)
+        .one(&txn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Project {} not found", absorb_id)))?;
+    // both, as absorbing a project moves everything out of it and deletes it
+    for project in [&keep, &absorb] {
+        check_project_access(&txn, project, auth_user.as_deref()).await?;
+    }
+
+    let absorbed_nodes = node::Entity::find_live()
+        .filter(node::Column::ProjectId.eq(absorb_id))
+        .order_by_asc(node::Column::Updated)
+        .all(&txn)
+        .await?;
+    let moved_attachments = attachment::Entity::find()
+        .filter(attachment::Column::NodeId.is_in(absorbed_nodes.iter().map(|node| node.id)))
+        .count(&txn)
+        .await?;
+
+    // which absorbed nodes fold into which surviving node
+    let mut replacements: HashMap<Uuid, Uuid> = HashMap::new();
+    if query.dedupe {
+        let mut survivors: HashMap<(NodeType, String), node::Model> = HashMap::new();
+        for existing in node::Entity::find_live()
+            .filter(node::Column::ProjectId.eq(keep_id))
+            .order_by_asc(node::Column::Updated)
+            .all(&txn)
+            .await?
+        {
+            let key = (
+                existing.node_type,
+                canonical_key(existing.node_type, &existing.value),
+            );
+            survivors.entry(key).or_insert(existing);
+        }
+        let mut changed_notes: HashSet<Uuid> = HashSet::new();
+        for absorbed in absorbed_nodes.iter() {
+            let key = (
+                absorbed.node_type,
+                canonical_key(absorbed.node_type, &absorbed.value),
+            );
+            match survivors.get_mut(&key) {
+                Some(survivor) => {
+                    replacements.insert(absorbed.id, survivor.id);
+                    let notes = merge_notes(survivor.notes.clone(), absorbed.notes.clone());
+                    if notes != survivor.notes {
+                        survivor.notes = notes;
+                        changed_notes.insert(survivor.id);
+                    }
+                }
+                None => {
+                    survivors.insert(key, absorbed.clone());
+                }
+            }
+        }
+        for survivor in survivors
+            .into_values()
+            .filter(|survivor| changed_notes.contains(&survivor.id))
+        {
+            let mut active = survivor.clone().into_active_model();
+            active.notes = Set(survivor.notes);
+            active.updated = Set(Utc::now());
+            active.update(&txn).await?;
+        }
+    }
+
+    let moved_nodes = (absorbed_nodes.len() - replacements.len()) as u64;
+    let quota_kind = QuotaKind::NodesPerProject(keep_id);
+    reader
+        .quota
+        .check(&txn, quota_kind, moved_nodes, auth_user.as_deref())
+        .await?;
+
+    for (duplicate, survivor) in replacements.iter() {
+        attachment::Entity::update_many()
+            .col_expr(attachment::Column::NodeId, Expr::value(*survivor))
+            .filter(attachment::Column::NodeId.eq(*duplicate))
+            .exec(&txn)
+            .await?;
+        for column in [nodelink::Column::Left, nodelink::Column::Right] {
+            nodelink::Entity::update_many()
+                .col_expr(column, Expr::value(*survivor))
+                .filter(column.eq(*duplicate))
+                .exec(&txn)
+                .await?;
+        }
+    }
+    if !replacements.is_empty() {
+        node::Entity::delete_many()
+            .filter(node::Column::Id.is_in(replacements.keys().copied()))
+            .exec(&txn)
+            .await?;
+    }
+
+    let now = Utc::now();
+    node::Entity::update_many()
+        .col_expr(node::Column::ProjectId, Expr::value(keep_id))
+        .col_expr(node::Column::Updated, Expr::value(now))
+        .filter(node::Column::ProjectId.eq(absorb_id))
+        .filter(node::Column::DeletedAt.is_null())
+        .exec(&txn)
+        .await?;
+    // otherwise deleting the absorbed project would take them with it
+    let trashed_nodes = node::Entity::update_many()
+        .col_expr(node::Column::ProjectId, Expr::value(keep_id))
+        .filter(node::Column::ProjectId.eq(absorb_id))
+        .exec(&txn)
+        .await?
+        .rows_affected;
+
+    // deduping can turn links into loops or repeats of links the kept project already has
+    let mut deduped_nodelinks = 0;
+    if !replacements.is_empty() {
+        let mut seen: HashSet<(Uuid, Uuid, LinkType)> = nodelink::Entity::find()
+            .filter(nodelink::Column::ProjectId.eq(keep_id))
+            .all(&txn)
+            .await?
+            .into_iter()
+            .map(|link| (link.left, link.right, link.linktype))
+            .collect();
+        let mut dropped = Vec::new();
+        for link in nodelink::Entity::find()
+            .filter(nodelink::Column::ProjectId.eq(absorb_id))
+            .order_by_asc(nodelink::Column::Id)
+            .all(&txn)
+            .await?
+        {
+            let reversed = link.linktype == LinkType::Omni
+                && seen.contains(&(link.right, link.left, link.linktype));
+            if link.left == link.right
+                || reversed
+                || !seen.insert((link.left, link.right, link.linktype))
+            {
+                dropped.push(link.id);
+            }
+        }
+        if !dropped.is_empty() {
+            deduped_nodelinks = nodelink::Entity::delete_many()
+                .filter(nodelink::Column::Id.is_in(dropped))
+                .exec(&txn)
+                .await?
+                .rows_affected;
+        }
+    }
+    let moved_nodelinks = nodelink::Entity::update_many()
+        .col_expr(nodelink::Column::ProjectId, Expr::value(keep_id))
+        .filter(nodelink::Column::ProjectId.eq(absorb_id))
+        .exec(&txn)
+        .await?
+        .rows_affected;
+
+    // the Inbox always exists, so it's emptied rather than deleted
+    let absorbed_project_deleted = absorb_id != Uuid::nil();
+    if absorbed_project_deleted {
+        project::Entity::delete_by_id(absorb_id).exec(&txn).await?;
+    }
+
+    let mut keep = keep.into_active_model();
+    keep.last_updated = Set(Some(now));
+    let keep = keep.update(&txn).await?;
+
+    txn.commit().await?;
+    reader.quota.invalidate([
+        QuotaKind::Projects,
+        QuotaKind::NodesPerProject(absorb_id),
+        QuotaKind::NodelinksPerProject(absorb_id),
+        QuotaKind::NodelinksPerProject(keep_id),
+    ]);
+    info!(
+        keep_id = keep_id.to_string(),
+        absorb_id = absorb_id.to_string(),
+        moved_nodes,
+        deduped_nodes = replacements.len(),
+        moved_nodelinks,
+        deduped_nodelinks,
+        trashed_nodes,
+        "Merged projects"
+    );
+
+    let warning = reader.quota.record(quota_kind, moved_nodes);
+    Ok((
+        warning_headers(warning),
+        Json(ProjectMergeResponse {
+            project: keep,
+            moved_nodes,
+            deduped_nodes: replacements.len() as u64,
+            moved_nodelinks,
+            deduped_nodelinks,
+            moved_attachments,
+            trashed_nodes,
+            absorbed_project_deleted,
+        }),
+    ))
+}
@@ -0,0 +1,455 @@
+//! Prometheus metrics, scraped from `/metrics`
+//!
+//! Counters and histograms are kept in memory by [Metrics] and rendered in the Prometheus text
+//! format on each scrape, along with gauges counted from the database at the time. Requests are
+//! labelled with the route they matched rather than the raw path, so IDs don't each get their own
+//! series.
+//!
+//! The endpoint is outside authentication. Set `--metrics-token` to require
+//! `Authorization: Bearer <token>` from the scraper.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{MatchedPath, State},
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE},
+        HeaderMap, HeaderValue, Request, Response, StatusCode,
+    },
+    response::IntoResponse,
+};
+use sea_orm::{EntityTrait, PaginatorTrait};
+use tower::{Layer, Service};
+
+use crate::{
+    attachment_dedup::content_hash,
+    db_health::{self, DatabaseBusy, PoolStats},
+    entity::{attachment, node, project},
+    project::WebError,
+    sessions::session_count,
+    SharedState,
+};
+
+pub const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Upper bounds of the duration histogram buckets in seconds, the Prometheus client defaults
+pub const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Route label for requests which didn't match a route, eg frontend files
+const UNMATCHED_PATH: &str = "unmatched";
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Histogram {
+    /// Observations in each of [DURATION_BUCKETS], not cumulative
+    buckets: [u64; DURATION_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+    /// Longest observation, in seconds
+    max: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        if let Some(bucket) = DURATION_BUCKETS.iter().position(|bound| secs <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.sum += secs;
+        self.count += 1;
+        self.max = self.max.max(secs);
+    }
+
+    pub(crate) fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Longest observation in seconds, unset when there haven't been any
+    pub(crate) fn max(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    /// The bucket bound which the `quantile` of observations fall within, in seconds, capped at
+    /// the longest observation
+    pub(crate) fn quantile(&self, quantile: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = (quantile * self.count as f64).ceil().max(1.0) as u64;
+        let mut cumulative = 0;
+        for (bound, count) in DURATION_BUCKETS.iter().zip(self.buckets) {
+            cumulative += count;
+            if cumulative >= rank {
+                return Some(bound.min(self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    /// `labels` is either empty or ends with a comma, so `le` can follow it
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (bound, count) in DURATION_BUCKETS.iter().zip(self.buckets) {
+            cumulative += count;
+            let _ = writeln!(out, "{name}_bucket{{{labels}le=\"{bound}\"}} {cumulative}");
+        }
+        let _ = writeln!(out, "{name}_bucket{{{labels}le=\"+Inf\"}} {}", self.count);
+        let labels = labels.trim_end_matches(',');
+        let _ = writeln!(out, "{name}_sum{{{labels}}} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{{{labels}}} {}", self.count);
+    }
+}
+
+/// Escape a label value for the text format
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// By method, route and status
+    requests: Mutex<BTreeMap<(String, String, u16), u64>>,
+    /// By method and route
+    request_durations: Mutex<BTreeMap<(String, String), Histogram>>,
+    db_query_durations: Mutex<Histogram>,
+    /// Time spent waiting for a pooled connection to start a transaction
+    db_acquire_waits: Mutex<Histogram>,
+    /// Requests which failed because SQLite was busy or locked
+    db_busy_errors: AtomicU64,
+}
+
+/// Totals counted from the database when scraped
+#[derive(Debug, Default)]
+pub struct Gauges {
+    pub active_sessions: u64,
+    pub projects: u64,
+    pub nodes: u64,
+    pub attachments: u64,
+    pub db_pool: PoolStats,
+    /// Unset for in-memory databases, or when there's no WAL file
+    pub db_wal_bytes: Option<u64>,
+}
+
+impl Metrics {
+    pub fn record_request(&self, method: &str, path: &str, status: u16, elapsed: Duration) {
+        *self
+            .requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry((method.to_string(), path.to_string(), status))
+            .or_default() += 1;
+        self.request_durations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry((method.to_string(), path.to_string()))
+            .or_default()
+            .observe(elapsed);
+    }
+
+    pub fn record_query(&self, elapsed: Duration) {
+        self.db_query_durations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .observe(elapsed);
+    }
+
+    pub fn record_acquire(&self, elapsed: Duration) {
+        self.db_acquire_waits
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .observe(elapsed);
+    }
+
+    pub fn record_busy_error(&self) {
+        self.db_busy_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn acquire_waits(&self) -> Histogram {
+        self.db_acquire_waits
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn busy_errors(&self) -> u64 {
+        self.db_busy_errors.load(Ordering::Relaxed)
+    }
+
+    /// Everything in the Prometheus text format
+    pub fn render(&self, gauges: &Gauges) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP osint_graph_requests_total HTTP requests by route and status\n");
+        out.push_str("# TYPE osint_graph_requests_total counter\n");
+        for ((method, path, status), count) in self
+            .requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+        {
+            let _ = writeln!(
+                out,
+                "osint_graph_requests_total{{method=\"{}\",path=\"{}\",status=\"{status}\"}} {count}",
+                label(method),
+                label(path),
+            );
+        }
+
+        out.push_str(
+            "# HELP osint_graph_request_duration_seconds Time to respond to HTTP requests\n",
+        );
+        out.push_str("# TYPE osint_graph_request_duration_seconds histogram\n");
+        for ((method, path), histogram) in self
+            .request_durations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+        {
+            histogram.render(
+                &mut out,
+                "osint_graph_request_duration_seconds",
+                &format!("method=\"{}\",path=\"{}\",", label(method), label(path)),
+            );
+        }
+
+        out.push_str(
+            "# HELP osint_graph_db_query_duration_seconds Time taken by database statements\n",
+        );
+        out.push_str("# TYPE osint_graph_db_query_duration_seconds histogram\n");
+        self.db_query_durations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .render(&mut out, "osint_graph_db_query_duration_seconds", "");
+
+        out.push_str(
+            "# HELP osint_graph_db_acquire_wait_seconds Time transactions waited for a pooled connection\n",
+        );
+        out.push_str("# TYPE osint_graph_db_acquire_wait_seconds histogram\n");
+        self.acquire_waits()
+            .render(&mut out, "osint_graph_db_acquire_wait_seconds", "");
+
+        out.push_str(
+            "# HELP osint_graph_db_busy_errors_total Requests which failed with SQLITE_BUSY or SQLITE_LOCKED\n",
+        );
+        out.push_str("# TYPE osint_graph_db_busy_errors_total counter\n");
+        let _ = writeln!(
+            out,
+            "osint_graph_db_busy_errors_total {}",
+            self.busy_errors()
+        );
+
+        out.push_str(
+            "# HELP osint_graph_db_pool_connections Pooled database connections by state\n",
+        );
+        out.push_str("# TYPE osint_graph_db_pool_connections gauge\n");
+        for (state, value) in [
+            ("idle", gauges.db_pool.idle),
+            ("in_use", gauges.db_pool.in_use),
+        ] {
+            let _ = writeln!(
+                out,
+                "osint_graph_db_pool_connections{{state=\"{state}\"}} {value}"
+            );
+        }
+
+        for (name, help, value) in [
+            (
+                "osint_graph_active_sessions_total",
+                "Sessions in the session store",
+                gauges.active_sessions,
+            ),
+            ("osint_graph_projects_total", "Projects", gauges.projects),
+            (
+                "osint_graph_nodes_total",
+                "Nodes in every project",
+                gauges.nodes,
+            ),
+            (
+                "osint_graph_attachments_total",
+                "Attachments in every project",
+                gauges.attachments,
+            ),
+            (
+                "osint_graph_db_pool_max_connections",
+                "Most connections the pool will open, from --db-max-connections",
+                gauges.db_pool.max_connections.into(),
+            ),
+            (
+                "osint_graph_db_wal_bytes",
+                "Size of the SQLite write-ahead log",
+                gauges.db_wal_bytes.unwrap_or_default(),
+            ),
+        ] {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} gauge");
+            let _ = writeln!(out, "{name} {value}");
+        }
+        out
+    }
+}
+
+/// Records [Metrics::record_request] for every response
+///
+/// Needs to be applied with [axum::Router::layer] so requests have their [MatchedPath].
+#[derive(Clone)]
+pub struct MetricsLayer {
+    metrics: Arc<Metrics>,
+}
+
+impl MetricsLayer {
+    pub fn new(metrics: Arc<Metrics>) -> Self {
+        Self { metrics }
+    }
+}
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = MetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MetricsService {
+            inner,
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MetricsService<S> {
+    inner: S,
+    metrics: Arc<Metrics>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for MetricsService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let method = request.method().to_string();
+        let path = request
+            .extensions()
+            .get::<MatchedPath>()
+            .map_or(UNMATCHED_PATH, |path| path.as_str())
+            .to_string();
+        let metrics = self.metrics.clone();
+        let start = Instant::now();
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let response = response.await?;
+            if response.extensions().get::<DatabaseBusy>().is_some() {
+                metrics.record_busy_error();
+            }
+            metrics.record_request(&method, &path, response.status().as_u16(), start.elapsed());
+            Ok(response)
+        })
+    }
+}
+
+async fn count_gauges(state: &SharedState) -> Result<Gauges, WebError> {
+    let reader = state.read().await;
+    let conn = &reader.conn;
+    let active_sessions = session_count(conn.get_sqlite_connection_pool())
+        .await
+        .map_err(|err| {
+            WebError::internal_server_error(format!("Failed to count sessions: {err}"))
+        })?;
+    Ok(Gauges {
+        active_sessions: active_sessions.max(0) as u64,
+        projects: project::Entity::find().count(conn).await?,
+        nodes: node::Entity::find().count(conn).await?,
+        attachments: attachment::Entity::find().count(conn).await?,
+        db_pool: PoolStats::of(conn),
+        db_wal_bytes: db_health::wal_bytes(conn).await?,
+    })
+}
+
+/// Prometheus metrics in the text exposition format
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "status",
+    operation_id = "get_metrics",
+    responses(
+        (status = OK, description = "Metrics in the Prometheus text format", content_type = "text/plain", body = String),
+        (status = UNAUTHORIZED, description = "--metrics-token is set and the bearer token doesn't match it", body = crate::project::ErrorResponse)
+    )
+)]
+pub async fn get_metrics(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, WebError> {
+    let (metrics, metrics_token) = {
+        let reader = state.read().await;
+        (reader.metrics.clone(), reader.metrics_token.clone())
+    };
+    if let Some(metrics_token) = metrics_token {
+        let bearer = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim);
+        // hashed so the comparison doesn't leak how much of the token matched
+        if bearer.map(|bearer| content_hash(bearer.as_bytes()))
+            != Some(content_hash(metrics_token.as_bytes()))
+        {
+            return Err(WebError::new(
+                StatusCode::UNAUTHORIZED,
+                "A valid metrics bearer token is required",
+            ));
+        }
+    }
+    let gauges = count_gauges(&state).await?;
+    Ok((
+        [(CONTENT_TYPE, HeaderValue::from_static(METRICS_CONTENT_TYPE))],
+        metrics.render(&gauges),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_render() {
+        let mut histogram = Histogram::default();
+        histogram.observe(Duration::from_millis(3));
+        histogram.observe(Duration::from_millis(200));
+        histogram.observe(Duration::from_secs(60));
+        let mut out = String::new();
+        histogram.render(&mut out, "test_seconds", "path=\"/x\",");
+        assert!(out.contains("test_seconds_bucket{path=\"/x\",le=\"0.005\"} 1\n"));
+        assert!(out.contains("test_seconds_bucket{path=\"/x\",le=\"0.25\"} 2\n"));
+        assert!(out.contains("test_seconds_bucket{path=\"/x\",le=\"10\"} 2\n"));
+        assert!(out.contains("test_seconds_bucket{path=\"/x\",le=\"+Inf\"} 3\n"));
+        assert!(out.contains("test_seconds_count{path=\"/x\"} 3\n"));
+
+        assert_eq!(histogram.quantile(0.3), Some(0.005));
+        assert_eq!(histogram.quantile(0.5), Some(0.25));
+        // past the last bucket is as long as the longest
+        assert_eq!(histogram.quantile(0.95), Some(60.0));
+        assert_eq!(Histogram::default().quantile(0.95), None);
+    }
+}
@@ -1,13 +1,67 @@
 //! Axum middleware things
 //!
 
-use axum::http::Method;
+use std::time::Duration;
+
+use axum::http::{
+    header::{
+        ACCEPT_RANGES, AUTHORIZATION, CONTENT_DISPOSITION, CONTENT_RANGE, CONTENT_TYPE, ETAG,
+        LOCATION,
+    },
+    HeaderName, HeaderValue, Method,
+};
 use tower_http::cors::{Any, CorsLayer};
 
-pub fn corslayer() -> CorsLayer {
-    CorsLayer::new()
+use crate::{
+    attachment::{
+        ATTACHMENT_CODEC_HEADER, ATTACHMENT_CONTENT_TYPE_HEADER, ATTACHMENT_SHA256_HEADER,
+        ATTACHMENT_SIZE_HEADER,
+    },
+    export_cache::CACHE_HEADER,
+    idempotency::{IDEMPOTENCY_KEY_HEADER, IDEMPOTENT_REPLAYED_HEADER},
+    quota::QUOTA_WARNING_HEADER,
+    text_limit::TRUNCATED_HEADER,
+};
+
+/// Default for `--cors-max-age-secs`
+pub const DEFAULT_CORS_MAX_AGE_SECS: u64 = 600;
+
+/// Response headers cross-origin scripts are allowed to read
+pub const CORS_EXPOSED_HEADERS: [HeaderName; 15] = [
+    ETAG,
+    CONTENT_DISPOSITION,
+    CONTENT_RANGE,
+    ACCEPT_RANGES,
+    LOCATION,
+    HeaderName::from_static("x-request-id"),
+    HeaderName::from_static("x-content-sha256"),
+    ATTACHMENT_CODEC_HEADER,
+    ATTACHMENT_SIZE_HEADER,
+    ATTACHMENT_CONTENT_TYPE_HEADER,
+    ATTACHMENT_SHA256_HEADER,
+    CACHE_HEADER,
+    QUOTA_WARNING_HEADER,
+    TRUNCATED_HEADER,
+    IDEMPOTENT_REPLAYED_HEADER,
+];
+
+/// If `allowed_origins` is empty any origin can make requests, but without credentials. Otherwise
+/// only the listed origins can, and they can send the session cookie, eg from a browser extension.
+///
+/// Browsers cache preflight responses for `max_age`.
+pub fn corslayer(allowed_origins: &[HeaderValue], max_age: Duration) -> CorsLayer {
+    let layer = CorsLayer::new()
         // allow `GET` and `POST` when accessing the resource
         .allow_methods([Method::GET, Method::POST])
+        .allow_headers([AUTHORIZATION, CONTENT_TYPE, IDEMPOTENCY_KEY_HEADER])
+        .expose_headers(CORS_EXPOSED_HEADERS)
+        .max_age(max_age);
+    if allowed_origins.is_empty() {
         // allow requests from any origin
-        .allow_origin(Any)
+        layer.allow_origin(Any)
+    } else {
+        layer
+            .allow_origin(allowed_origins.to_vec())
+            .allow_credentials(true)
+    }
 }
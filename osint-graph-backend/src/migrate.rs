@@ -0,0 +1,254 @@
+//! The `osint-graph migrate` command, for operators who'd rather upgrade the database by hand
+//!
+//! `--status` lists migrations, `--dry-run` applies them to a throwaway copy and compares row
+//! counts, and otherwise the database is backed up with `VACUUM INTO` before migrating, which
+//! won't go ahead if the backup fails.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbErr, Statement};
+use sea_orm_migration::MigratorTrait;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    cli::MigrateOpts,
+    migration::Migrator,
+    storage::{backup_database, connect},
+};
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct MigrationStatus {
+    pub applied: Vec<String>,
+    pub pending: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DryRunReport {
+    pub pending: Vec<String>,
+    /// Why migrating the copy failed
+    pub error: Option<String>,
+    /// Rows per table before and after, tables missing on one side were created or dropped
+    pub rows_before: BTreeMap<String, i64>,
+    pub rows_after: BTreeMap<String, i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MigrateReport {
+    /// Unset when there was nothing to do
+    pub backup: Option<PathBuf>,
+    pub applied: Vec<String>,
+}
+
+fn names(migrations: Vec<sea_orm_migration::Migration>) -> Vec<String> {
+    migrations
+        .iter()
+        .map(|migration| migration.name().to_string())
+        .collect()
+}
+
+/// Open an existing database without migrating it
+async fn open_existing(db_path: &Path) -> Result<DatabaseConnection, String> {
+    if !db_path.is_file() {
+        return Err(format!("No database at {}", db_path.display()));
+    }
+    connect(Some(&db_path.to_path_buf()))
+        .await
+        .map_err(|err| err.to_string())
+}
+
+pub async fn migration_status(conn: &DatabaseConnection) -> Result<MigrationStatus, DbErr> {
+    Ok(MigrationStatus {
+        applied: names(Migrator::get_applied_migrations(conn).await?),
+        pending: names(Migrator::get_pending_migrations(conn).await?),
+    })
+}
+
+/// Rows in every table, skipping SQLite's own
+pub async fn table_row_counts(conn: &impl ConnectionTrait) -> Result<BTreeMap<String, i64>, DbErr> {
+    let backend = conn.get_database_backend();
+    let tables = conn
+        .query_all(Statement::from_string(
+            backend,
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+        ))
+        .await?;
+    let mut counts = BTreeMap::new();
+    for table in tables {
+        let name: String = table.try_get("", "name")?;
+        let count = conn
+            .query_one(Statement::from_string(
+                backend,
+                format!(
+                    "SELECT COUNT(*) AS total FROM \"{}\"",
+                    name.replace('"', "\"\"")
+                ),
+            ))
+            .await?
+            .map(|row| row.try_get::<i64>("", "total"))
+            .transpose()?
+            .unwrap_or_default();
+        counts.insert(name, count);
+    }
+    Ok(counts)
+}
+
+/// Migrate a temporary copy of the database, leaving the original untouched
+pub async fn dry_run(db_path: &Path) -> Result<DryRunReport, String> {
+    let original = open_existing(db_path).await?;
+    let copy_path =
+        std::env::temp_dir().join(format!("osint-graph-dry-run-{}.sqlite3", Uuid::new_v4()));
+    backup_database(&original, &copy_path)
+        .await
+        .map_err(|err| format!("Failed to copy the database: {err}"))?;
+    original.close().await.map_err(|err| err.to_string())?;
+
+    let result = async {
+        let copy = connect(Some(&copy_path))
+            .await
+            .map_err(|err| err.to_string())?;
+        let pending = names(
+            Migrator::get_pending_migrations(&copy)
+                .await
+                .map_err(|err| err.to_string())?,
+        );
+        let rows_before = table_row_counts(&copy)
+            .await
+            .map_err(|err| err.to_string())?;
+        let error = Migrator::up(&copy, None)
+            .await
+            .err()
+            .map(|err| err.to_string());
+        let rows_after = table_row_counts(&copy)
+            .await
+            .map_err(|err| err.to_string())?;
+        copy.close().await.map_err(|err| err.to_string())?;
+        Ok(DryRunReport {
+            pending,
+            error,
+            rows_before,
+            rows_after,
+        })
+    }
+    .await;
+    let _ = std::fs::remove_file(&copy_path);
+    result
+}
+
+/// Where the pre-migration backup of `db_path` goes, timestamped so backups never collide
+pub fn backup_path(db_path: &Path, backup_dir: Option<&Path>) -> PathBuf {
+    let dir = backup_dir
+        .or_else(|| db_path.parent())
+        .unwrap_or_else(|| Path::new("."));
+    let stem = db_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "osint-graph".to_string());
+    dir.join(format!(
+        "{stem}.{}.pre-migrate.sqlite3",
+        Utc::now().format("%Y%m%dT%H%M%SZ")
+    ))
+}
+
+/// Back up the database then apply pending migrations, refusing to migrate without a backup
+pub async fn migrate_with_backup(
+    db_path: &Path,
+    backup_dir: Option<&Path>,
+) -> Result<MigrateReport, String> {
+    let conn = open_existing(db_path).await?;
+    let pending = names(
+        Migrator::get_pending_migrations(&conn)
+            .await
+            .map_err(|err| err.to_string())?,
+    );
+    if pending.is_empty() {
+        return Ok(MigrateReport {
+            backup: None,
+            applied: pending,
+        });
+    }
+
+    let backup = backup_path(db_path, backup_dir);
+    backup_database(&conn, &backup).await.map_err(|err| {
+        format!(
+            "Not migrating, failed to back up to {}: {err}",
+            backup.display()
+        )
+    })?;
+    Migrator::up(&conn, None).await.map_err(|err| {
+        format!(
+            "Migration failed, the database can be restored from {}: {err}",
+            backup.display()
+        )
+    })?;
+    Ok(MigrateReport {
+        backup: Some(backup),
+        applied: pending,
+    })
+}
+
+/// Run `osint-graph migrate`, printing what happened
+pub async fn run(db_path: &Path, opts: &MigrateOpts) -> Result<(), String> {
+    let display = db_path.display();
+    if opts.status {
+        let conn = open_existing(db_path).await?;
+        let status = migration_status(&conn)
+            .await
+            .map_err(|err| err.to_string())?;
+        println!("Database: {display}");
+        for name in &status.applied {
+            println!("  applied  {name}");
+        }
+        for name in &status.pending {
+            println!("  pending  {name}");
+        }
+        println!(
+            "{} applied, {} pending",
+            status.applied.len(),
+            status.pending.len()
+        );
+    } else if opts.dry_run {
+        let report = dry_run(db_path).await?;
+        println!("Dry run against a copy of {display}");
+        for name in &report.pending {
+            println!("  would apply  {name}");
+        }
+        let tables: std::collections::BTreeSet<&String> = report
+            .rows_before
+            .keys()
+            .chain(report.rows_after.keys())
+            .collect();
+        for table in tables {
+            let count = |counts: &BTreeMap<String, i64>| {
+                counts
+                    .get(table)
+                    .map_or("-".to_string(), |count| count.to_string())
+            };
+            println!(
+                "  {table}: {} -> {}",
+                count(&report.rows_before),
+                count(&report.rows_after)
+            );
+        }
+        if let Some(error) = report.error {
+            return Err(format!("Migrations failed on the copy: {error}"));
+        }
+        println!("Migrations succeeded on the copy");
+    } else {
+        let report = migrate_with_backup(db_path, opts.backup_dir.as_deref()).await?;
+        match report.backup {
+            None => println!("{display} is up to date"),
+            Some(backup) => {
+                println!("Backed up {display} to {}", backup.display());
+                for name in &report.applied {
+                    println!("  applied  {name}");
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ExportCache::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ExportCache::Id)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ExportCache::ProjectId).string().not_null())
+                    .col(ColumnDef::new(ExportCache::Format).string().not_null())
+                    .col(ColumnDef::new(ExportCache::FilterHash).string().not_null())
+                    .col(ColumnDef::new(ExportCache::Fingerprint).string().not_null())
+                    .col(ColumnDef::new(ExportCache::Content).text().not_null())
+                    .col(ColumnDef::new(ExportCache::Size).big_integer().not_null())
+                    .col(ColumnDef::new(ExportCache::Created).string().not_null())
+                    .col(ColumnDef::new(ExportCache::LastUsed).string().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_export_cache_project")
+                            .from(ExportCache::Table, ExportCache::ProjectId)
+                            .to(Project::Table, Project::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_export_cache_key")
+                    .table(ExportCache::Table)
+                    .col(ExportCache::ProjectId)
+                    .col(ExportCache::Format)
+                    .col(ExportCache::FilterHash)
+                    .col(ExportCache::Fingerprint)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ExportCache::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum ExportCache {
+    Table,
+    Id,
+    ProjectId,
+    Format,
+    FilterHash,
+    Fingerprint,
+    Content,
+    Size,
+    Created,
+    LastUsed,
+}
+
+#[derive(DeriveIden)]
+enum Project {
+    Table,
+    Id,
+}
@@ -0,0 +1,78 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::ConnectionTrait;
+use uuid::Uuid;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite can't add a NOT NULL column without a default, so existing users get filled in below
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(ColumnDef::new(Users::Uuid).string())
+                    .to_owned(),
+            )
+            .await?;
+
+        let conn = manager.get_connection();
+        let backend = manager.get_database_backend();
+        let select = Query::select()
+            .column(Users::Id)
+            .from(Users::Table)
+            .to_owned();
+        for row in conn.query_all(backend.build(&select)).await? {
+            let id: i64 = row.try_get("", &Users::Id.to_string())?;
+            let update = Query::update()
+                .table(Users::Table)
+                .value(Users::Uuid, Uuid::new_v4())
+                .and_where(Expr::col(Users::Id).eq(id))
+                .to_owned();
+            conn.execute(backend.build(&update)).await?;
+        }
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_users_uuid")
+                    .table(Users::Table)
+                    .col(Users::Uuid)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_users_uuid")
+                    .table(Users::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_column(Users::Uuid)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Id,
+    Uuid,
+}
@@ -0,0 +1,45 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // everything stored so far was gzipped
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Attachment::Table)
+                    .add_column(
+                        ColumnDef::new(Attachment::Codec)
+                            .string_len(8)
+                            .not_null()
+                            .default("gzip"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Attachment::Table)
+                    .drop_column(Attachment::Codec)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Attachment {
+    Table,
+    Codec,
+}
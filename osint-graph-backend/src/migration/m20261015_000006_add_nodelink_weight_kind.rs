@@ -0,0 +1,57 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite only takes one column per ALTER TABLE
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NodeLink::Table)
+                    .add_column(ColumnDef::new(NodeLink::Weight).float())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NodeLink::Table)
+                    .add_column(ColumnDef::new(NodeLink::Kind).string())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NodeLink::Table)
+                    .drop_column(NodeLink::Kind)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NodeLink::Table)
+                    .drop_column(NodeLink::Weight)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum NodeLink {
+    Table,
+    Weight,
+    Kind,
+}
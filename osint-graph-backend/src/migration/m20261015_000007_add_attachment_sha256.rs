@@ -0,0 +1,59 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // existing rows are hashed in the background after startup
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Attachment::Table)
+                    .add_column(ColumnDef::new(Attachment::Sha256).string_len(64).null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_attachment_sha256")
+                    .table(Attachment::Table)
+                    .col(Attachment::Sha256)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_attachment_sha256")
+                    .table(Attachment::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Attachment::Table)
+                    .drop_column(Attachment::Sha256)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Attachment {
+    Table,
+    Sha256,
+}
@@ -0,0 +1,38 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // left over from when the node update list was stored with the project, nothing reads it
+        manager
+            .exec_stmt(
+                TableAlterStatement::new()
+                    .table(Project::Table)
+                    .drop_column(Project::Nodes)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let add_nodes = TableAlterStatement::new()
+            .table(Project::Table)
+            .add_column(ColumnDef::new(Project::Nodes).string().null())
+            .to_owned();
+
+        manager.exec_stmt(add_nodes).await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Project {
+    Table,
+    Nodes,
+}
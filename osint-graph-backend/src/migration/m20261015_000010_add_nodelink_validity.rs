@@ -0,0 +1,57 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // when the relationship held, either end can be open
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NodeLink::Table)
+                    .add_column(ColumnDef::new(NodeLink::ValidFrom).string())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NodeLink::Table)
+                    .add_column(ColumnDef::new(NodeLink::ValidTo).string())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NodeLink::Table)
+                    .drop_column(NodeLink::ValidTo)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NodeLink::Table)
+                    .drop_column(NodeLink::ValidFrom)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum NodeLink {
+    Table,
+    ValidFrom,
+    ValidTo,
+}
@@ -0,0 +1,77 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::ConnectionTrait;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(
+                        ColumnDef::new(Users::IsAdmin)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(ColumnDef::new(Users::LastLogin).timestamp())
+                    .to_owned(),
+            )
+            .await?;
+
+        // the first user to log in becomes the admin, so existing installs need one too
+        let first_user = Query::select()
+            .expr(Expr::col(Users::Id).min())
+            .from(Users::Table)
+            .to_owned();
+        let update = Query::update()
+            .table(Users::Table)
+            .value(Users::IsAdmin, true)
+            .and_where(Expr::col(Users::Id).in_subquery(first_user))
+            .to_owned();
+        let conn = manager.get_connection();
+        conn.execute(manager.get_database_backend().build(&update))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_column(Users::LastLogin)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_column(Users::IsAdmin)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Id,
+    IsAdmin,
+    LastLogin,
+}
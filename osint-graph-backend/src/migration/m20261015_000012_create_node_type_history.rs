@@ -0,0 +1,75 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(NodeTypeHistory::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(NodeTypeHistory::Id)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(NodeTypeHistory::NodeId).string().not_null())
+                    .col(
+                        ColumnDef::new(NodeTypeHistory::FromType)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(NodeTypeHistory::ToType).string().not_null())
+                    .col(ColumnDef::new(NodeTypeHistory::Changed).string().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_node_type_history_node")
+                            .from(NodeTypeHistory::Table, NodeTypeHistory::NodeId)
+                            .to(Node::Table, Node::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_node_type_history_node_id")
+                    .table(NodeTypeHistory::Table)
+                    .col(NodeTypeHistory::NodeId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(NodeTypeHistory::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum NodeTypeHistory {
+    Table,
+    Id,
+    NodeId,
+    FromType,
+    ToType,
+    Changed,
+}
+
+#[derive(DeriveIden)]
+enum Node {
+    Table,
+    Id,
+}
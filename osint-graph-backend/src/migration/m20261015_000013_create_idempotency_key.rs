@@ -0,0 +1,82 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(IdempotencyKey::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(IdempotencyKey::Id)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(IdempotencyKey::Key).string().not_null())
+                    .col(ColumnDef::new(IdempotencyKey::UserId).string().not_null())
+                    .col(ColumnDef::new(IdempotencyKey::Endpoint).string().not_null())
+                    .col(
+                        ColumnDef::new(IdempotencyKey::RequestHash)
+                            .string_len(64)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(IdempotencyKey::Status).integer().null())
+                    .col(ColumnDef::new(IdempotencyKey::ContentType).string().null())
+                    .col(ColumnDef::new(IdempotencyKey::Body).binary().null())
+                    .col(ColumnDef::new(IdempotencyKey::Created).string().not_null())
+                    .col(ColumnDef::new(IdempotencyKey::Expires).string().not_null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_idempotency_key_user_key")
+                    .table(IdempotencyKey::Table)
+                    .col(IdempotencyKey::UserId)
+                    .col(IdempotencyKey::Key)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_idempotency_key_expires")
+                    .table(IdempotencyKey::Table)
+                    .col(IdempotencyKey::Expires)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(IdempotencyKey::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum IdempotencyKey {
+    Table,
+    Id,
+    Key,
+    UserId,
+    Endpoint,
+    RequestHash,
+    Status,
+    ContentType,
+    Body,
+    Created,
+    Expires,
+}
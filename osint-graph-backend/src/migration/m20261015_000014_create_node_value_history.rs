@@ -0,0 +1,83 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(NodeValueHistory::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(NodeValueHistory::Id)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(NodeValueHistory::NodeId).string().not_null())
+                    .col(
+                        ColumnDef::new(NodeValueHistory::OldValue)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(NodeValueHistory::NewValue)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(NodeValueHistory::Changed)
+                            .string()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_node_value_history_node")
+                            .from(NodeValueHistory::Table, NodeValueHistory::NodeId)
+                            .to(Node::Table, Node::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_node_value_history_node_id")
+                    .table(NodeValueHistory::Table)
+                    .col(NodeValueHistory::NodeId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(NodeValueHistory::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum NodeValueHistory {
+    Table,
+    Id,
+    NodeId,
+    OldValue,
+    NewValue,
+    Changed,
+}
+
+#[derive(DeriveIden)]
+enum Node {
+    Table,
+    Id,
+}
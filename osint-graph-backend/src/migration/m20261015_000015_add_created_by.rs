@@ -0,0 +1,85 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// (table, column, type) for each column added, SQLite can only add one per statement
+fn columns() -> [(DynIden, DynIden, ColumnType); 5] {
+    let subject = || ColumnType::String(StringLen::None);
+    [
+        (
+            Node::Table.into_iden(),
+            Node::CreatedBy.into_iden(),
+            subject(),
+        ),
+        (
+            Node::Table.into_iden(),
+            Node::Created.into_iden(),
+            ColumnType::Timestamp,
+        ),
+        (
+            NodeLink::Table.into_iden(),
+            NodeLink::CreatedBy.into_iden(),
+            subject(),
+        ),
+        (
+            NodeLink::Table.into_iden(),
+            NodeLink::Created.into_iden(),
+            ColumnType::Timestamp,
+        ),
+        (
+            Attachment::Table.into_iden(),
+            Attachment::CreatedBy.into_iden(),
+            subject(),
+        ),
+    ]
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // who added each item and when, unknown for anything added before now
+        for (table, column, column_type) in columns() {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table)
+                        .add_column(ColumnDef::new_with_type(column, column_type))
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for (table, column, _) in columns().into_iter().rev() {
+            manager
+                .alter_table(Table::alter().table(table).drop_column(column).to_owned())
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Node {
+    Table,
+    CreatedBy,
+    Created,
+}
+
+#[derive(DeriveIden)]
+enum NodeLink {
+    Table,
+    CreatedBy,
+    Created,
+}
+
+#[derive(DeriveIden)]
+enum Attachment {
+    Table,
+    CreatedBy,
+}
@@ -0,0 +1,98 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ExportRecord::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ExportRecord::Id)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ExportRecord::ProjectId).string().not_null())
+                    .col(ColumnDef::new(ExportRecord::Format).string().not_null())
+                    .col(
+                        ColumnDef::new(ExportRecord::Destination)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ExportRecord::Bucket).string().not_null())
+                    .col(ColumnDef::new(ExportRecord::ObjectKey).string().not_null())
+                    .col(ColumnDef::new(ExportRecord::Status).string().not_null())
+                    .col(ColumnDef::new(ExportRecord::Etag).string().null())
+                    .col(ColumnDef::new(ExportRecord::Size).big_integer().null())
+                    .col(
+                        ColumnDef::new(ExportRecord::Attempts)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(ColumnDef::new(ExportRecord::Error).string().null())
+                    .col(ColumnDef::new(ExportRecord::CreatedBy).string().null())
+                    .col(ColumnDef::new(ExportRecord::Created).string().not_null())
+                    .col(ColumnDef::new(ExportRecord::Finished).string().null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_export_record_project")
+                            .from(ExportRecord::Table, ExportRecord::ProjectId)
+                            .to(Project::Table, Project::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_export_record_project_id")
+                    .table(ExportRecord::Table)
+                    .col(ExportRecord::ProjectId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ExportRecord::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum ExportRecord {
+    Table,
+    Id,
+    ProjectId,
+    Format,
+    Destination,
+    Bucket,
+    ObjectKey,
+    Status,
+    Etag,
+    Size,
+    Attempts,
+    Error,
+    CreatedBy,
+    Created,
+    Finished,
+}
+
+#[derive(DeriveIden)]
+enum Project {
+    Table,
+    Id,
+}
@@ -0,0 +1,136 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ExportSettings::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ExportSettings::ProjectId)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(ExportSettings::SnapshotIntervalSecs)
+                            .big_integer()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(ExportSettings::SnapshotKeep)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ExportSettings::LastSnapshotRun)
+                            .string()
+                            .null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_export_settings_project")
+                            .from(ExportSettings::Table, ExportSettings::ProjectId)
+                            .to(Project::Table, Project::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ProjectSnapshot::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ProjectSnapshot::Id)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(ProjectSnapshot::ProjectId)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ProjectSnapshot::Created).string().not_null())
+                    .col(
+                        ColumnDef::new(ProjectSnapshot::Fingerprint)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ProjectSnapshot::Size)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ProjectSnapshot::Content).text().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_project_snapshot_project")
+                            .from(ProjectSnapshot::Table, ProjectSnapshot::ProjectId)
+                            .to(Project::Table, Project::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_project_snapshot_project_created")
+                    .table(ProjectSnapshot::Table)
+                    .col(ProjectSnapshot::ProjectId)
+                    .col(ProjectSnapshot::Created)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ProjectSnapshot::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(ExportSettings::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum ExportSettings {
+    Table,
+    ProjectId,
+    SnapshotIntervalSecs,
+    SnapshotKeep,
+    LastSnapshotRun,
+}
+
+#[derive(DeriveIden)]
+enum ProjectSnapshot {
+    Table,
+    Id,
+    ProjectId,
+    Created,
+    Fingerprint,
+    Size,
+    Content,
+}
+
+#[derive(DeriveIden)]
+enum Project {
+    Table,
+    Id,
+}
@@ -0,0 +1,61 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // no foreign key to the node, so the history outlives a purge
+        manager
+            .create_table(
+                Table::create()
+                    .table(NodeHistory::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(NodeHistory::Id)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(NodeHistory::NodeId).string().not_null())
+                    .col(ColumnDef::new(NodeHistory::ChangedAt).string().not_null())
+                    .col(ColumnDef::new(NodeHistory::Change).string().not_null())
+                    .col(ColumnDef::new(NodeHistory::Previous).text().not_null())
+                    .col(ColumnDef::new(NodeHistory::ChangedBy).string().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_node_history_node_changed_at")
+                    .table(NodeHistory::Table)
+                    .col(NodeHistory::NodeId)
+                    .col(NodeHistory::ChangedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(NodeHistory::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum NodeHistory {
+    Table,
+    Id,
+    NodeId,
+    ChangedAt,
+    Change,
+    Previous,
+    ChangedBy,
+}
@@ -0,0 +1,244 @@
+use chrono::{DateTime, Utc};
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, QueryResult};
+use uuid::Uuid;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        copy_legacy_history(manager).await?;
+
+        // type changes and old values are worked out from node_history now, nothing reads these
+        for table in [
+            NodeTypeHistory::Table.into_iden(),
+            NodeValueHistory::Table.into_iden(),
+        ] {
+            manager
+                .drop_table(Table::drop().table(table).if_exists().to_owned())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        super::m20261015_000012_create_node_type_history::Migration
+            .up(manager)
+            .await?;
+        super::m20261015_000014_create_node_value_history::Migration
+            .up(manager)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// A row from either of the old tables
+struct LegacyChange {
+    id: Uuid,
+    changed: DateTime<Utc>,
+    /// The node field it changed, and what it was before
+    field: &'static str,
+    old: String,
+}
+
+/// Turn each old type or value change into an `update` entry in node_history
+///
+/// The old tables only kept the one field, so the node before each change is rebuilt by undoing
+/// the changes newest first, starting from the node as it is now. Changes from after the node's
+/// first node_history entry are already covered by it, so they're undone but not copied.
+async fn copy_legacy_history(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+    let conn = manager.get_connection();
+    let backend = manager.get_database_backend();
+
+    let mut changes: Vec<(Uuid, LegacyChange)> = Vec::new();
+    for (table, field, old_column) in [
+        (
+            NodeTypeHistory::Table.into_iden(),
+            "node_type",
+            NodeTypeHistory::FromType.into_iden(),
+        ),
+        (
+            NodeValueHistory::Table.into_iden(),
+            "value",
+            NodeValueHistory::OldValue.into_iden(),
+        ),
+    ] {
+        if !manager.has_table(table.to_string()).await? {
+            continue;
+        }
+        // both tables have the same id, node_id and changed columns
+        let select = Query::select()
+            .column(NodeTypeHistory::Id)
+            .column(NodeTypeHistory::NodeId)
+            .column(NodeTypeHistory::Changed)
+            .expr_as(Expr::col(old_column), Alias::new("old"))
+            .from(table)
+            .to_owned();
+        for row in conn.query_all(backend.build(&select)).await? {
+            changes.push((
+                row.try_get("", "node_id")?,
+                LegacyChange {
+                    id: row.try_get("", "id")?,
+                    changed: row.try_get("", "changed")?,
+                    field,
+                    old: row.try_get("", "old")?,
+                },
+            ));
+        }
+    }
+    // newest first within each node
+    changes.sort_by(|(a_node, a), (b_node, b)| {
+        a_node
+            .cmp(b_node)
+            .then(b.changed.cmp(&a.changed))
+            .then(b.id.cmp(&a.id))
+    });
+
+    let mut changes = changes.into_iter().peekable();
+    while let Some((node_id, _)) = changes.peek() {
+        let node_id = *node_id;
+        let node_changes: Vec<LegacyChange> = std::iter::from_fn(|| {
+            changes
+                .next_if(|(next, _)| *next == node_id)
+                .map(|(_, change)| change)
+        })
+        .collect();
+
+        let select = Query::select()
+            .columns([
+                Node::Id,
+                Node::ProjectId,
+                Node::Type,
+                Node::Display,
+                Node::Value,
+                Node::Updated,
+                Node::Notes,
+                Node::PosX,
+                Node::PosY,
+                Node::CreatedBy,
+                Node::Created,
+                Node::DeletedAt,
+            ])
+            .from(Node::Table)
+            .and_where(Expr::col(Node::Id).eq(node_id))
+            .to_owned();
+        // the old tables cascaded on delete, so this is only for databases which had foreign keys off
+        let Some(row) = conn.query_one(backend.build(&select)).await? else {
+            continue;
+        };
+        let mut node = node_json(&row)?;
+
+        let select = Query::select()
+            .expr_as(
+                Func::min(Expr::col(NodeHistory::ChangedAt)),
+                Alias::new("first"),
+            )
+            .from(NodeHistory::Table)
+            .and_where(Expr::col(NodeHistory::NodeId).eq(node_id))
+            .to_owned();
+        let first_recorded: Option<DateTime<Utc>> =
+            match conn.query_one(backend.build(&select)).await? {
+                Some(row) => row.try_get("", "first")?,
+                None => None,
+            };
+
+        for change in node_changes {
+            node[change.field] = change.old.into();
+            if first_recorded.is_some_and(|first| change.changed >= first) {
+                continue;
+            }
+            let insert = Query::insert()
+                .into_table(NodeHistory::Table)
+                .columns([
+                    NodeHistory::Id,
+                    NodeHistory::NodeId,
+                    NodeHistory::ChangedAt,
+                    NodeHistory::Change,
+                    NodeHistory::Previous,
+                    NodeHistory::ChangedBy,
+                ])
+                .values_panic([
+                    change.id.into(),
+                    node_id.into(),
+                    change.changed.into(),
+                    "update".into(),
+                    node.to_string().into(),
+                    Option::<String>::None.into(),
+                ])
+                .to_owned();
+            conn.execute(backend.build(&insert)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A node row as the JSON node_history keeps
+fn node_json(row: &QueryResult) -> Result<serde_json::Value, DbErr> {
+    let timestamp = |column: &str| -> Result<Option<String>, DbErr> {
+        Ok(row
+            .try_get::<Option<DateTime<Utc>>>("", column)?
+            .map(|at| at.to_rfc3339()))
+    };
+    Ok(serde_json::json!({
+        "id": row.try_get::<Uuid>("", "id")?,
+        "project_id": row.try_get::<Uuid>("", "project_id")?,
+        "node_type": row.try_get::<String>("", "type")?,
+        "display": row.try_get::<String>("", "display")?,
+        "value": row.try_get::<String>("", "value")?,
+        "updated": timestamp("updated")?,
+        "notes": row.try_get::<Option<String>>("", "notes")?,
+        "pos_x": row.try_get::<Option<i32>>("", "pos_x")?,
+        "pos_y": row.try_get::<Option<i32>>("", "pos_y")?,
+        "created_by": row.try_get::<Option<String>>("", "created_by")?,
+        "created": timestamp("created")?,
+        "deleted_at": timestamp("deleted_at")?,
+    }))
+}
+
+#[derive(DeriveIden)]
+enum NodeTypeHistory {
+    Table,
+    Id,
+    NodeId,
+    FromType,
+    Changed,
+}
+
+#[derive(DeriveIden)]
+enum NodeValueHistory {
+    Table,
+    OldValue,
+}
+
+#[derive(DeriveIden)]
+enum NodeHistory {
+    Table,
+    Id,
+    NodeId,
+    ChangedAt,
+    Change,
+    Previous,
+    ChangedBy,
+}
+
+#[derive(DeriveIden)]
+enum Node {
+    Table,
+    Id,
+    ProjectId,
+    Type,
+    Display,
+    Value,
+    Updated,
+    Notes,
+    PosX,
+    PosY,
+    CreatedBy,
+    Created,
+    DeletedAt,
+}
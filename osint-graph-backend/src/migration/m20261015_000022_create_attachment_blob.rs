@@ -0,0 +1,74 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // content shared by duplicate attachments, keyed by hash rather than a foreign key
+        manager
+            .create_table(
+                Table::create()
+                    .table(AttachmentBlob::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AttachmentBlob::Sha256)
+                            .string_len(64)
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(AttachmentBlob::Data).binary().not_null())
+                    .col(ColumnDef::new(AttachmentBlob::Codec).string().not_null())
+                    .col(ColumnDef::new(AttachmentBlob::Created).string().not_null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Attachment::Table)
+                    .add_column(
+                        ColumnDef::new(Attachment::SharedBlob)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Attachment::Table)
+                    .drop_column(Attachment::SharedBlob)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(AttachmentBlob::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum AttachmentBlob {
+    Table,
+    Sha256,
+    Data,
+    Codec,
+    Created,
+}
+
+#[derive(DeriveIden)]
+enum Attachment {
+    Table,
+    SharedBlob,
+}
@@ -4,6 +4,28 @@ mod m20240101_000001_create_tables;
 mod m20250105_000001_insert_default_inbox_project;
 mod m20251106_000001_drop_attachments_column_nodes;
 mod m20251106_000002_create_sessions;
+mod m20261015_000001_add_project_pinned;
+mod m20261015_000002_create_export_cache;
+mod m20261015_000003_add_user_uuid;
+mod m20261015_000004_create_api_tokens;
+mod m20261015_000005_add_attachment_codec;
+mod m20261015_000006_add_nodelink_weight_kind;
+mod m20261015_000007_add_attachment_sha256;
+mod m20261015_000008_drop_project_nodes;
+mod m20261015_000009_add_attachment_media;
+mod m20261015_000010_add_nodelink_validity;
+mod m20261015_000011_add_user_admin;
+mod m20261015_000012_create_node_type_history;
+mod m20261015_000013_create_idempotency_key;
+mod m20261015_000014_create_node_value_history;
+mod m20261015_000015_add_created_by;
+mod m20261015_000016_create_export_record;
+mod m20261015_000017_add_project_is_archived;
+mod m20261015_000018_create_project_snapshots;
+mod m20261015_000019_add_node_deleted_at;
+mod m20261015_000020_create_node_history;
+mod m20261015_000021_drop_node_change_history;
+mod m20261015_000022_create_attachment_blob;
 
 pub struct Migrator;
 
@@ -15,6 +37,28 @@ impl MigratorTrait for Migrator {
             Box::new(m20250105_000001_insert_default_inbox_project::Migration),
             Box::new(m20251106_000001_drop_attachments_column_nodes::Migration),
             Box::new(m20251106_000002_create_sessions::Migration),
+            Box::new(m20261015_000001_add_project_pinned::Migration),
+            Box::new(m20261015_000002_create_export_cache::Migration),
+            Box::new(m20261015_000003_add_user_uuid::Migration),
+            Box::new(m20261015_000004_create_api_tokens::Migration),
+            Box::new(m20261015_000005_add_attachment_codec::Migration),
+            Box::new(m20261015_000006_add_nodelink_weight_kind::Migration),
+            Box::new(m20261015_000007_add_attachment_sha256::Migration),
+            Box::new(m20261015_000008_drop_project_nodes::Migration),
+            Box::new(m20261015_000009_add_attachment_media::Migration),
+            Box::new(m20261015_000010_add_nodelink_validity::Migration),
+            Box::new(m20261015_000011_add_user_admin::Migration),
+            Box::new(m20261015_000012_create_node_type_history::Migration),
+            Box::new(m20261015_000013_create_idempotency_key::Migration),
+            Box::new(m20261015_000014_create_node_value_history::Migration),
+            Box::new(m20261015_000015_add_created_by::Migration),
+            Box::new(m20261015_000016_create_export_record::Migration),
+            Box::new(m20261015_000017_add_project_is_archived::Migration),
+            Box::new(m20261015_000018_create_project_snapshots::Migration),
+            Box::new(m20261015_000019_add_node_deleted_at::Migration),
+            Box::new(m20261015_000020_create_node_history::Migration),
+            Box::new(m20261015_000021_drop_node_change_history::Migration),
+            Box::new(m20261015_000022_create_attachment_blob::Migration),
         ]
     }
 }
@@ -1,43 +1,82 @@
 use axum::{
     extract::{Request, State},
+    http::{header::AUTHORIZATION, StatusCode},
     middleware::Next,
     response::{IntoResponse, Redirect, Response},
 };
 use osint_graph_shared::Urls;
 use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
 use tower_sessions::Session;
+use uuid::Uuid;
 
 use crate::entity::user::{self, Column};
-use crate::SharedState;
+use crate::project::WebError;
+use crate::{tokens, SharedState};
 
 /// Authenticated user information extracted from session
 #[derive(Clone, Debug)]
 pub struct AuthUser {
+    /// The user's [user::Model::uuid], which owns their projects
+    pub id: Uuid,
     pub subject: String,
     pub email: String,
     #[allow(dead_code)] // TODO: decide if this is used
     pub display_name: Option<String>,
+    pub is_admin: bool,
+}
+
+impl AuthUser {
+    /// What to record as `created_by` for things `auth_user` adds, unset when authentication is off
+    pub fn created_by(auth_user: Option<&AuthUser>) -> Option<String> {
+        auth_user.map(|auth_user| auth_user.subject.clone())
+    }
 }
 
 impl From<user::Model> for AuthUser {
     fn from(user: user::Model) -> Self {
         AuthUser {
+            id: user.uuid,
             subject: user.subject,
             email: user.email,
             display_name: user.display_name,
+            is_admin: user.is_admin,
         }
     }
 }
 
 /// Middleware that requires authentication
-/// Checks session for user_subject, loads user from DB, and adds to request extensions
-/// Redirects to /admin/login if not authenticated
+/// Checks for a bearer API token or the session's user_subject, loads user from DB, and adds to request extensions
+/// Redirects to /admin/login if not authenticated, a bad API token gets a 401 instead
 pub async fn require_auth(
     State(state): State<SharedState>,
     session: Session,
     mut request: Request,
     next: Next,
 ) -> Response {
+    let bearer = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+    if let Some(token) = bearer {
+        let user = match tokens::authenticate(&state.read().await.conn, &token).await {
+            Ok(Some(user)) => user,
+            Ok(None) => {
+                return WebError::new(StatusCode::UNAUTHORIZED, "Invalid API token")
+                    .into_response();
+            }
+            Err(e) => {
+                tracing::error!("Failed to check API token: {:?}", e);
+                return WebError::internal_server_error("Failed to check API token")
+                    .into_response();
+            }
+        };
+        let auth_user: AuthUser = user.into();
+        request.extensions_mut().insert(auth_user);
+        return next.run(request).await;
+    }
+
     // Get user subject from session
     let user_subject: Option<String> = match session.get("user_subject").await {
         Ok(subject) => subject,
@@ -84,3 +123,19 @@ pub async fn require_auth(
 
     next.run(request).await
 }
+
+/// Middleware for `/api/v1/admin` routes, which runs inside [require_auth]
+/// Users without the admin flag get a 403, everyone's allowed when authentication is disabled
+pub async fn require_admin(request: Request, next: Next) -> Response {
+    if let Some(auth_user) = request.extensions().get::<AuthUser>() {
+        if !auth_user.is_admin {
+            tracing::warn!(
+                user = auth_user.subject,
+                path = request.uri().path(),
+                "Denied a non-admin user access to an admin endpoint"
+            );
+            return WebError::new(StatusCode::FORBIDDEN, "Only admins can do that").into_response();
+        }
+    }
+    next.run(request).await
+}
@@ -8,29 +8,123 @@ use utoipa_swagger_ui::SwaggerUi;
     paths(
         crate::project::get_projects,
         crate::project::get_project,
+        crate::project::get_project_stats,
         crate::project::post_project,
+        crate::project::post_project_full,
+        crate::project::import_project,
         crate::project::update_project,
         crate::project::delete_project,
-        crate::project::export_project,
-        crate::project::export_project_mermaid,
+        crate::project::pin_project,
+        crate::project::unpin_project,
+        crate::project::archive_project,
+        crate::project::unarchive_project,
+        crate::merge::merge_projects,
+        crate::layout::layout_project,
+        crate::review::review_project,
+        crate::score::score_project,
+        crate::contributors::get_project_contributors,
         crate::project::get_nodes_by_project,
         crate::project::get_node,
+        crate::project::get_nodes,
         crate::project::post_node,
+        crate::project::post_nodes,
         crate::project::update_node,
+        crate::project::get_node_type_history,
+        crate::project::get_node_history,
+        crate::project::merge_nodes,
         crate::project::delete_node,
+        crate::trash::get_project_trash,
+        crate::trash::restore_trash,
+        crate::project::restore_node,
+        crate::split::split_node,
+        crate::project::copy_node,
+        crate::capture::post_capture,
+        crate::styles::get_node_type_styles,
+        crate::identifier::identify_urls,
+        crate::markdown::render_markdown_preview,
         crate::project::get_nodelinks_by_project,
+        crate::project::get_nodelinks_by_node,
         crate::project::post_nodelink,
+        crate::project::update_nodelink,
         crate::project::delete_nodelink,
         crate::attachment::list_attachments,
+        crate::attachment::get_project_attachment_summary,
         crate::attachment::upload_attachment,
         crate::attachment::view_attachment,
         crate::attachment::download_attachment,
+        crate::attachment::download_attachment_raw,
         crate::attachment::update_attachment,
-        crate::attachment::delete_attachment
+        crate::attachment::delete_attachment,
+        crate::attachment_dedup::get_attachments_by_hash,
+        crate::project::search_global,
+        crate::project::export_project,
+        crate::project::export_project_mermaid,
+        crate::project::export_project_graphml,
+        crate::project::export_project_dot,
+        crate::export::export_node_vcard,
+        crate::export::export_project_timeline,
+        crate::export::export_project_jsonld,
+        crate::export::export_project_csv,
+        crate::report::export_project_report,
+        crate::archive::export_project_archive,
+        crate::report::get_report_job,
+        crate::export_push::push_project_export,
+        crate::export_push::get_export_records,
+        crate::snapshot::get_snapshot_settings,
+        crate::snapshot::update_snapshot_settings,
+        crate::snapshot::get_project_snapshots,
+        crate::snapshot::get_snapshot,
+        crate::snapshot::diff_snapshots,
+        crate::tokens::post_token,
+        crate::tokens::get_tokens,
+        crate::tokens::delete_token,
+        crate::attachment_dedup::get_attachment_duplicates,
+        crate::attachment_dedup::post_deduplicate_attachments,
+        crate::attachment_dedup::get_deduplicate_job,
+        crate::attachment_dedup::cancel_deduplicate_job,
+        crate::value_policy::get_value_policy,
+        crate::storage_gc::get_storage_orphans,
+        crate::storage_gc::post_storage_gc,
+        crate::storage_gc::post_prune_orphans,
+        crate::reindex::post_reindex,
+        crate::db_health::get_db_health,
+        crate::admin_users::get_admin_users,
+        crate::admin_users::update_admin_user,
+        crate::admin_users::delete_admin_user,
+        crate::status::get_status,
+        crate::capabilities::get_capabilities,
+        crate::status::get_build_info,
+        crate::metrics::get_metrics,
+        crate::status::get_readyz,
+        crate::status::health_check
+    ),
+    tags(
+        (name = "projects", description = "Projects, and merging them together"),
+        (name = "nodes", description = "Nodes within a project"),
+        (name = "links", description = "Links between nodes"),
+        (name = "attachments", description = "Files attached to nodes"),
+        (name = "search", description = "Searching across every project"),
+        (name = "exports", description = "Exporting projects and nodes to other formats"),
+        (name = "auth", description = "API tokens"),
+        (name = "admin", description = "Instance-wide maintenance and user management"),
+        (name = "status", description = "Health and readiness")
     )
 )]
 pub struct ApiDoc;
 
+/// Every operation carries exactly one of these tags
+pub const API_TAGS: &[&str] = &[
+    "projects",
+    "nodes",
+    "links",
+    "attachments",
+    "search",
+    "exports",
+    "auth",
+    "admin",
+    "status",
+];
+
 pub(crate) fn api_route<T: Clone + Sync + Send + 'static>() -> Router<T> {
     let doc = ApiDoc::openapi();
     Router::new().merge(SwaggerUi::new("/api/v1/swagger-ui").url("/api/v1/openapi.json", doc))
@@ -0,0 +1,751 @@
+//! A minimal PDF writer, enough for text, tables, simple vector drawings and JPEG images
+//!
+//! Text uses the standard Helvetica fonts with WinAnsi encoding, so nothing needs embedding.
+//! Characters outside that encoding are written as `?` and control characters are dropped,
+//! which makes [pdf_encode] the sanitising step for any user text in a document.
+//!
+//! Content flows down A4 pages from the top, with [PdfWriter::paragraph] and
+//! [PdfWriter::table] starting new pages as they fill up. Page numbers are added by
+//! [PdfWriter::finish] once the page count is known.
+
+use std::io::Write;
+
+use flate2::{write::ZlibEncoder, Compression};
+
+/// A4, in points
+pub const PAGE_WIDTH: f64 = 595.0;
+pub const PAGE_HEIGHT: f64 = 842.0;
+pub const MARGIN: f64 = 50.0;
+/// Width available between the margins
+pub const CONTENT_WIDTH: f64 = PAGE_WIDTH - 2.0 * MARGIN;
+/// Room kept clear at the bottom of each page for the footer
+const FOOTER_SPACE: f64 = 30.0;
+const LINE_SPACING: f64 = 1.3;
+const CELL_PADDING: f64 = 3.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource_name(&self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+        }
+    }
+}
+
+/// Helvetica advance widths in 1/1000 em for ASCII 32-126, from the standard AFM files
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611, 975, 722, 722, 722, 722, 667,
+    611, 778, 722, 278, 556, 722, 611, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 333, 278, 333, 584, 556, 333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556,
+    278, 889, 611, 611, 611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+/// Used for everything outside ASCII, close enough for line wrapping
+const DEFAULT_WIDTH: u16 = 556;
+
+/// Map a character to its WinAnsi byte, `None` for control characters
+fn win_ansi(c: char) -> Option<u8> {
+    match c {
+        '\t' | '\n' | '\r' => Some(b' '),
+        ' '..='~' => Some(c as u8),
+        '\u{A0}'..='\u{FF}' => Some(c as u32 as u8),
+        '€' => Some(0x80),
+        '‚' => Some(0x82),
+        '„' => Some(0x84),
+        '…' => Some(0x85),
+        '‘' => Some(0x91),
+        '’' => Some(0x92),
+        '“' => Some(0x93),
+        '”' => Some(0x94),
+        '•' => Some(0x95),
+        '–' => Some(0x96),
+        '—' => Some(0x97),
+        '™' => Some(0x99),
+        c if c.is_control() => None,
+        _ => Some(b'?'),
+    }
+}
+
+/// Encode text as a PDF string literal, escaped and limited to what Helvetica can show
+pub fn pdf_encode(text: &str) -> Vec<u8> {
+    let mut res = Vec::with_capacity(text.len() + 2);
+    res.push(b'(');
+    for byte in text.chars().filter_map(win_ansi) {
+        if matches!(byte, b'(' | b')' | b'\\') {
+            res.push(b'\\');
+        }
+        res.push(byte);
+    }
+    res.push(b')');
+    res
+}
+
+fn char_width(font: Font, c: char) -> u16 {
+    let widths = match font {
+        Font::Regular => &HELVETICA_WIDTHS,
+        Font::Bold => &HELVETICA_BOLD_WIDTHS,
+    };
+    match c {
+        ' '..='~' => widths[c as usize - 32],
+        _ => DEFAULT_WIDTH,
+    }
+}
+
+/// Width of `text` in points
+pub fn text_width(font: Font, size: f64, text: &str) -> f64 {
+    text.chars()
+        .filter(|c| !c.is_control())
+        .map(|c| char_width(font, c) as f64)
+        .sum::<f64>()
+        * size
+        / 1000.0
+}
+
+/// Break `text` into lines no wider than `width`, splitting words which don't fit on their own
+pub fn wrap_text(font: Font, size: f64, text: &str, width: f64) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = match line.is_empty() {
+                true => word.to_string(),
+                false => format!("{line} {word}"),
+            };
+            if text_width(font, size, &candidate) <= width {
+                line = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            for c in word.chars() {
+                line.push(c);
+                if text_width(font, size, &line) > width && line.chars().count() > 1 {
+                    line.pop();
+                    lines.push(std::mem::take(&mut line));
+                    line.push(c);
+                }
+            }
+        }
+        lines.push(line);
+    }
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+    lines
+}
+
+/// Cut `text` down to `max_chars`, with an ellipsis if anything was removed
+pub fn truncate(text: &str, max_chars: usize) -> String {
+    match text.chars().count() > max_chars {
+        true => format!(
+            "{}…",
+            text.chars()
+                .take(max_chars.saturating_sub(1))
+                .collect::<String>()
+        ),
+        false => text.to_string(),
+    }
+}
+
+/// An RGB colour with components from 0 to 1
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rgb(pub f64, pub f64, pub f64);
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb(0.0, 0.0, 0.0);
+    pub const GREY: Rgb = Rgb(0.6, 0.6, 0.6);
+    pub const LIGHT_GREY: Rgb = Rgb(0.92, 0.92, 0.92);
+
+    /// Parse a CSS hex colour like `#3b82f6`, ignoring any alpha
+    pub fn from_hex(value: &str) -> Option<Self> {
+        let hex = value.strip_prefix('#')?;
+        if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |index: usize| {
+            u8::from_str_radix(&hex[index..index + 2], 16)
+                .ok()
+                .map(|value| value as f64 / 255.0)
+        };
+        Some(Rgb(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+/// A JPEG image added with [PdfWriter::add_jpeg]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageRef {
+    index: usize,
+    pub width: u32,
+    pub height: u32,
+}
+
+struct PdfImage {
+    width: u32,
+    height: u32,
+    color_space: &'static str,
+    data: Vec<u8>,
+}
+
+/// Find the dimensions and colour space of a JPEG from its frame header
+pub fn jpeg_info(data: &[u8]) -> Option<(u32, u32, &'static str)> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut pos = 2;
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xFF {
+            return None;
+        }
+        let marker = data[pos + 1];
+        match marker {
+            // padding
+            0xFF => {
+                pos += 1;
+                continue;
+            }
+            // markers without a length
+            0x01 | 0xD0..=0xD7 => {
+                pos += 2;
+                continue;
+            }
+            // end of image or start of scan before a frame header
+            0xD9 | 0xDA => return None,
+            _ => {}
+        }
+        let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let is_frame = matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+        if is_frame {
+            let header = data.get(pos + 4..pos + 10)?;
+            let height = u16::from_be_bytes([header[1], header[2]]) as u32;
+            let width = u16::from_be_bytes([header[3], header[4]]) as u32;
+            let color_space = match header[5] {
+                1 => "DeviceGray",
+                3 => "DeviceRGB",
+                // CMYK JPEGs are often stored inverted, which PDF can't tell
+                _ => return None,
+            };
+            if width == 0 || height == 0 {
+                return None;
+            }
+            return Some((width, height, color_space));
+        }
+        pos += 2 + length;
+    }
+    None
+}
+
+pub struct PdfWriter {
+    pages: Vec<Vec<u8>>,
+    images: Vec<PdfImage>,
+    /// Where the next line of flowing content goes
+    y: f64,
+    /// Shown at the bottom of every page next to the page number
+    pub footer: String,
+}
+
+impl Default for PdfWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PdfWriter {
+    pub fn new() -> Self {
+        let mut writer = Self {
+            pages: Vec::new(),
+            images: Vec::new(),
+            y: 0.0,
+            footer: String::new(),
+        };
+        writer.new_page();
+        writer
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Start a new page, flowing content from the top
+    pub fn new_page(&mut self) {
+        self.pages.push(Vec::new());
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    /// The vertical position flowing content continues from
+    pub fn cursor(&self) -> f64 {
+        self.y
+    }
+
+    /// Room left on this page for flowing content
+    pub fn remaining(&self) -> f64 {
+        self.y - MARGIN - FOOTER_SPACE
+    }
+
+    /// Start a new page unless there's `height` left on this one
+    pub fn ensure_space(&mut self, height: f64) {
+        if self.remaining() < height {
+            self.new_page();
+        }
+    }
+
+    pub fn space(&mut self, height: f64) {
+        self.y -= height;
+    }
+
+    fn ops(&mut self) -> &mut Vec<u8> {
+        self.pages.last_mut().expect("there's always a page")
+    }
+
+    /// Draw one line of text with its baseline at `y`
+    pub fn text_at(&mut self, x: f64, y: f64, font: Font, size: f64, text: &str) {
+        let encoded = pdf_encode(text);
+        let ops = self.ops();
+        let _ = write!(
+            ops,
+            "BT /{} {size:.1} Tf {x:.2} {y:.2} Td ",
+            font.resource_name()
+        );
+        ops.extend_from_slice(&encoded);
+        ops.extend_from_slice(b" Tj ET\n");
+    }
+
+    pub fn line(&mut self, from: (f64, f64), to: (f64, f64), width: f64, color: Rgb) {
+        let _ = writeln!(
+            self.ops(),
+            "{:.3} {:.3} {:.3} RG {width:.2} w {:.2} {:.2} m {:.2} {:.2} l S",
+            color.0,
+            color.1,
+            color.2,
+            from.0,
+            from.1,
+            to.0,
+            to.1
+        );
+    }
+
+    pub fn rect(&mut self, x: f64, y: f64, width: f64, height: f64, fill: Rgb) {
+        let _ = writeln!(
+            self.ops(),
+            "{:.3} {:.3} {:.3} rg {x:.2} {y:.2} {width:.2} {height:.2} re f",
+            fill.0,
+            fill.1,
+            fill.2
+        );
+    }
+
+    /// A filled ellipse centred on `(x, y)`
+    pub fn ellipse(&mut self, x: f64, y: f64, rx: f64, ry: f64, fill: Rgb) {
+        // control point distance for approximating a quarter circle with a bezier curve
+        const KAPPA: f64 = 0.552_284_8;
+        let (kx, ky) = (rx * KAPPA, ry * KAPPA);
+        let _ = writeln!(
+            self.ops(),
+            "{:.3} {:.3} {:.3} rg {:.2} {y:.2} m \
+             {:.2} {:.2} {:.2} {:.2} {x:.2} {:.2} c \
+             {:.2} {:.2} {:.2} {:.2} {:.2} {y:.2} c \
+             {:.2} {:.2} {:.2} {:.2} {x:.2} {:.2} c \
+             {:.2} {:.2} {:.2} {:.2} {:.2} {y:.2} c f",
+            fill.0,
+            fill.1,
+            fill.2,
+            x + rx,
+            x + rx,
+            y + ky,
+            x + kx,
+            y + ry,
+            y + ry,
+            x - kx,
+            y + ry,
+            x - rx,
+            y + ky,
+            x - rx,
+            x - rx,
+            y - ky,
+            x - kx,
+            y - ry,
+            y - ry,
+            x + kx,
+            y - ry,
+            x + rx,
+            y - ky,
+            x + rx,
+        );
+    }
+
+    /// A filled diamond centred on `(x, y)`
+    pub fn diamond(&mut self, x: f64, y: f64, rx: f64, ry: f64, fill: Rgb) {
+        let _ = writeln!(
+            self.ops(),
+            "{:.3} {:.3} {:.3} rg {:.2} {y:.2} m {x:.2} {:.2} l {:.2} {y:.2} l {x:.2} {:.2} l h f",
+            fill.0,
+            fill.1,
+            fill.2,
+            x + rx,
+            y + ry,
+            x - rx,
+            y - ry
+        );
+    }
+
+    /// Add a JPEG to the document, `None` if it can't be embedded as is
+    pub fn add_jpeg(&mut self, data: Vec<u8>) -> Option<ImageRef> {
+        let (width, height, color_space) = jpeg_info(&data)?;
+        self.images.push(PdfImage {
+            width,
+            height,
+            color_space,
+            data,
+        });
+        Some(ImageRef {
+            index: self.images.len() - 1,
+            width,
+            height,
+        })
+    }
+
+    /// Draw an image with its bottom left corner at `(x, y)`
+    pub fn image(&mut self, image: ImageRef, x: f64, y: f64, width: f64, height: f64) {
+        let _ = writeln!(
+            self.ops(),
+            "q {width:.2} 0 0 {height:.2} {x:.2} {y:.2} cm /Im{} Do Q",
+            image.index
+        );
+    }
+
+    /// Wrapped text flowing down the page, continuing on new pages as needed
+    pub fn paragraph(&mut self, font: Font, size: f64, text: &str) {
+        self.paragraph_indented(font, size, text, 0.0);
+    }
+
+    pub fn paragraph_indented(&mut self, font: Font, size: f64, text: &str, indent: f64) {
+        let line_height = size * LINE_SPACING;
+        for line in wrap_text(font, size, text, CONTENT_WIDTH - indent) {
+            self.ensure_space(line_height);
+            self.y -= line_height;
+            let y = self.y;
+            self.text_at(MARGIN + indent, y, font, size, &line);
+        }
+    }
+
+    pub fn heading(&mut self, size: f64, text: &str) {
+        // keep headings with at least a few lines of what follows
+        self.ensure_space(size * LINE_SPACING + 40.0);
+        self.space(size * 0.5);
+        self.paragraph(Font::Bold, size, text);
+        self.space(size * 0.3);
+    }
+
+    /// A table with a shaded header row, repeated on each page the table spans
+    ///
+    /// `widths` should add up to [CONTENT_WIDTH]. Cells wrap, and rows aren't split across pages.
+    pub fn table(&mut self, widths: &[f64], header: &[&str], rows: &[Vec<String>], size: f64) {
+        let line_height = size * LINE_SPACING;
+        let header: Vec<String> = header.iter().map(|cell| cell.to_string()).collect();
+        self.table_row(widths, &header, Font::Bold, size, true);
+        for row in rows {
+            let height = self.row_height(widths, row, Font::Regular, size);
+            if self.remaining() < height {
+                self.new_page();
+                self.table_row(widths, &header, Font::Bold, size, true);
+            }
+            self.table_row(widths, row, Font::Regular, size, false);
+        }
+        self.space(line_height * 0.5);
+    }
+
+    fn wrap_cells(widths: &[f64], row: &[String], font: Font, size: f64) -> Vec<Vec<String>> {
+        widths
+            .iter()
+            .zip(row)
+            .map(|(width, cell)| wrap_text(font, size, cell, width - 2.0 * CELL_PADDING))
+            .collect()
+    }
+
+    fn row_height(&self, widths: &[f64], row: &[String], font: Font, size: f64) -> f64 {
+        let lines = Self::wrap_cells(widths, row, font, size)
+            .iter()
+            .map(Vec::len)
+            .max()
+            .unwrap_or(1);
+        lines as f64 * size * LINE_SPACING + 2.0 * CELL_PADDING
+    }
+
+    fn table_row(&mut self, widths: &[f64], row: &[String], font: Font, size: f64, shaded: bool) {
+        let height = self.row_height(widths, row, font, size);
+        self.ensure_space(height);
+        let top = self.y;
+        if shaded {
+            self.rect(MARGIN, top - height, CONTENT_WIDTH, height, Rgb::LIGHT_GREY);
+        }
+        let mut x = MARGIN;
+        for (width, lines) in widths.iter().zip(Self::wrap_cells(widths, row, font, size)) {
+            for (index, line) in lines.iter().enumerate() {
+                let y = top - CELL_PADDING - (index + 1) as f64 * size * LINE_SPACING + size * 0.3;
+                self.text_at(x + CELL_PADDING, y, font, size, line);
+            }
+            x += width;
+        }
+        self.y -= height;
+        let y = self.y;
+        self.line((MARGIN, y), (MARGIN + CONTENT_WIDTH, y), 0.3, Rgb::GREY);
+    }
+
+    /// Number the pages and serialise the document
+    pub fn finish(mut self) -> Vec<u8> {
+        let page_count = self.pages.len();
+        for index in 0..page_count {
+            let footer = match self.footer.is_empty() {
+                true => format!("Page {} of {}", index + 1, page_count),
+                false => format!("{} - page {} of {}", self.footer, index + 1, page_count),
+            };
+            let encoded = pdf_encode(&footer);
+            let ops = &mut self.pages[index];
+            let _ = write!(
+                ops,
+                "0 0 0 rg BT /F1 8.0 Tf {MARGIN:.2} {:.2} Td ",
+                MARGIN / 2.0
+            );
+            ops.extend_from_slice(&encoded);
+            ops.extend_from_slice(b" Tj ET\n");
+        }
+
+        // objects are numbered catalog, pages, the two fonts, resources, images, then a page
+        // and its content stream for each page
+        let first_image = 6;
+        let first_page = first_image + self.images.len();
+        let page_ids: Vec<usize> = (0..page_count)
+            .map(|index| first_page + index * 2)
+            .collect();
+
+        let mut objects: Vec<Vec<u8>> = Vec::new();
+        objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+        objects.push(
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                page_ids
+                    .iter()
+                    .map(|id| format!("{id} 0 R"))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                page_count
+            )
+            .into_bytes(),
+        );
+        for base_font in ["Helvetica", "Helvetica-Bold"] {
+            objects.push(
+                format!(
+                    "<< /Type /Font /Subtype /Type1 /BaseFont /{base_font} /Encoding /WinAnsiEncoding >>"
+                )
+                .into_bytes(),
+            );
+        }
+        objects.push(
+            format!(
+                "<< /Font << /F1 3 0 R /F2 4 0 R >> /XObject << {}>> >>",
+                (0..self.images.len())
+                    .map(|index| format!("/Im{index} {} 0 R ", first_image + index))
+                    .collect::<String>()
+            )
+            .into_bytes(),
+        );
+        for image in &self.images {
+            let mut object = format!(
+                "<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /{} \
+                 /BitsPerComponent 8 /Filter /DCTDecode /Length {} >>\nstream\n",
+                image.width,
+                image.height,
+                image.color_space,
+                image.data.len()
+            )
+            .into_bytes();
+            object.extend_from_slice(&image.data);
+            object.extend_from_slice(b"\nendstream");
+            objects.push(object);
+        }
+        for (index, ops) in self.pages.iter().enumerate() {
+            objects.push(
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] \
+                     /Resources 5 0 R /Contents {} 0 R >>",
+                    page_ids[index] + 1
+                )
+                .into_bytes(),
+            );
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            let compressed = encoder
+                .write_all(ops)
+                .and_then(|_| encoder.finish())
+                .expect("writing to a Vec can't fail");
+            let mut object = format!(
+                "<< /Filter /FlateDecode /Length {} >>\nstream\n",
+                compressed.len()
+            )
+            .into_bytes();
+            object.extend_from_slice(&compressed);
+            object.extend_from_slice(b"\nendstream");
+            objects.push(object);
+        }
+
+        let mut res = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(res.len());
+            res.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+            res.extend_from_slice(object);
+            res.extend_from_slice(b"\nendobj\n");
+        }
+        let xref = res.len();
+        res.extend_from_slice(
+            format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+        );
+        for offset in offsets {
+            res.extend_from_slice(format!("{offset:010} 00000 n \n").as_bytes());
+        }
+        res.extend_from_slice(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+                objects.len() + 1
+            )
+            .as_bytes(),
+        );
+        res
+    }
+}
+
+/// Pull the text shown by each `Tj` out of a document from [PdfWriter], for tests
+#[cfg(test)]
+pub(crate) fn extract_text(pdf: &[u8]) -> String {
+    use std::io::Read;
+
+    let mut res = String::new();
+    let mut rest = pdf;
+    while let Some(start) = find(rest, b"stream\n") {
+        let body = &rest[start + 7..];
+        let end = find(body, b"\nendstream").unwrap_or(body.len());
+        let mut ops = Vec::new();
+        if flate2::read::ZlibDecoder::new(&body[..end])
+            .read_to_end(&mut ops)
+            .is_ok()
+        {
+            let mut chars = ops.iter().map(|byte| *byte as char);
+            while let Some(c) = chars.next() {
+                if c != '(' {
+                    continue;
+                }
+                while let Some(c) = chars.next() {
+                    match c {
+                        ')' => break,
+                        '\\' => res.extend(chars.next()),
+                        c => res.push(c),
+                    }
+                }
+                res.push('\n');
+            }
+        }
+        rest = &body[(end + 10).min(body.len())..];
+    }
+    res
+}
+
+/// How many pages a document from [PdfWriter] has
+#[cfg(test)]
+pub(crate) fn page_count(pdf: &[u8]) -> usize {
+    let pdf = String::from_utf8_lossy(pdf);
+    pdf.matches("/Type /Page ").count()
+}
+
+/// Just enough of a JPEG for [jpeg_info], a 40x30 RGB frame header
+#[cfg(test)]
+pub(crate) const TINY_JPEG: &[u8] = &[
+    0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, 0xFF, 0xC0, 0x00, 0x11, 0x08, 0x00, 0x1E, 0x00,
+    0x28, 0x03, 0x01, 0x22, 0x00, 0x02, 0x11, 0x01, 0x03, 0x11, 0x01, 0xFF, 0xD9,
+];
+
+#[cfg(test)]
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pdf_encode() {
+        assert_eq!(pdf_encode("a (b) \\c"), b"(a \\(b\\) \\\\c)");
+        assert_eq!(pdf_encode("tab\there\u{7}"), b"(tab here)");
+        assert_eq!(pdf_encode("café – 日本"), b"(caf\xE9 \x96 ??)");
+    }
+
+    #[test]
+    fn test_wrap_text() {
+        let lines = wrap_text(Font::Regular, 10.0, "the quick brown fox jumps over", 60.0);
+        assert!(lines.len() > 1);
+        assert!(lines
+            .iter()
+            .all(|line| text_width(Font::Regular, 10.0, line) <= 60.0));
+        assert_eq!(lines.join(" "), "the quick brown fox jumps over");
+
+        let long = "x".repeat(100);
+        let lines = wrap_text(Font::Regular, 10.0, &long, 50.0);
+        assert_eq!(lines.concat(), long);
+        assert_eq!(wrap_text(Font::Bold, 10.0, "", 50.0), vec![String::new()]);
+        assert_eq!(wrap_text(Font::Bold, 10.0, "a\nb", 50.0), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_jpeg_info() {
+        assert_eq!(jpeg_info(TINY_JPEG), Some((40, 30, "DeviceRGB")));
+        assert_eq!(jpeg_info(b"\x89PNG\r\n\x1a\n"), None);
+        assert_eq!(jpeg_info(&TINY_JPEG[..12]), None);
+    }
+
+    #[test]
+    fn test_rgb_from_hex() {
+        assert_eq!(Rgb::from_hex("#ff0000"), Some(Rgb(1.0, 0.0, 0.0)));
+        assert_eq!(Rgb::from_hex("#c7c400ff").map(|c| c.2), Some(0.0));
+        assert_eq!(Rgb::from_hex("red"), None);
+        assert_eq!(Rgb::from_hex("#ff00"), None);
+    }
+
+    #[test]
+    fn test_pdf_writer() {
+        let mut pdf = PdfWriter::new();
+        pdf.footer = "Case (1)".to_string();
+        pdf.heading(18.0, "Report");
+        for index in 0..200 {
+            pdf.paragraph(Font::Regular, 10.0, &format!("line {index}"));
+        }
+        let image = pdf.add_jpeg(TINY_JPEG.to_vec()).expect("valid jpeg");
+        pdf.image(image, MARGIN, MARGIN, 40.0, 30.0);
+        assert!(pdf.add_jpeg(b"not a jpeg".to_vec()).is_none());
+        let pages = pdf.page_count();
+        assert!(pages > 1);
+
+        let bytes = pdf.finish();
+        assert!(bytes.starts_with(b"%PDF-1.4"));
+        assert!(bytes.ends_with(b"%%EOF\n"));
+        assert_eq!(page_count(&bytes), pages);
+        let text = extract_text(&bytes);
+        assert!(text.contains("Report\n"));
+        assert!(text.contains("line 199\n"));
+        assert!(text.contains(&format!("Case (1) - page {pages} of {pages}")));
+        assert!(find(&bytes, b"/Width 40 /Height 30").is_some());
+    }
+}
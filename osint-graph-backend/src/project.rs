@@ -1,28 +1,58 @@
-use axum::extract::{Path, Query, State};
+use axum::extract::State;
 use axum::http::header::{InvalidHeaderValue, CONTENT_DISPOSITION, CONTENT_TYPE};
-use axum::http::{HeaderValue, StatusCode};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
 use axum::response::IntoResponse;
-use axum::Json;
+use axum::{Extension, Json};
 use osint_graph_shared::node::NodeType;
+use osint_graph_shared::nodelink::LinkType;
+use osint_graph_shared::{MAX_TAGS, MAX_TAG_CHARS};
+use sea_orm::sea_query::{Alias, Expr, Func};
 use sea_orm::ActiveValue::Set;
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, DbErr, EntityTrait, IntoActiveModel, ModelTrait, QueryFilter,
-    TransactionTrait, TryIntoModel,
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait, IntoActiveModel,
+    Iterable, JoinType, ModelTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect,
+    QueryTrait, RelationTrait, TryIntoModel,
 };
 use serde::{Deserialize, Serialize};
 use sqlx::types::chrono::Utc;
-use tracing::{debug, error, info};
-use utoipa::ToSchema;
+use std::collections::{HashMap, HashSet};
+use tracing::{debug, error, info, warn};
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
+use crate::access::{
+    accessible_projects, check_node_access, check_project_access, find_accessible_project,
+};
+use crate::db_health::{is_busy, DatabaseBusy, DATABASE_BUSY};
+use crate::entity::node_history::{self, NodeChange, NodeHistoryEntry, NodeTypeChange};
 use crate::entity::{attachment, node, nodelink, project};
-use crate::SharedState;
+use crate::export_cache::{filter_hash, project_fingerprint, ExportCacheKey, CACHE_HEADER};
+use crate::extract::{Path, Query, INVALID_QUERY_PARAMETER};
+use crate::oauth::middleware::AuthUser;
+use crate::quota::{warning_headers, QuotaKind};
+use crate::redact::{redact, REDACTED};
+use crate::review;
+use crate::styles::{NodeShape, NodeTypeStyles};
+use crate::text_limit::truncated_headers;
+use crate::value_policy::VALUE_POLICY_VIOLATION;
+use crate::{AppState, SharedState};
 
 pub const MERMAID_CONTENT_TYPE: &str = "text/vnd.mermaid; charset=utf-8";
+pub const GRAPHML_CONTENT_TYPE: &str = "application/graphml+xml";
+pub const DOT_CONTENT_TYPE: &str = "text/vnd.graphviz; charset=utf-8";
+
+/// Error code when a client-supplied node ID is already taken
+pub const NODE_ID_CONFLICT: &str = "node_id_conflict";
+/// Error code when a new project's ID is already taken
+pub const PROJECT_ID_CONFLICT: &str = "project_id_conflict";
+
+/// Largest project import body, exports include attachment data as JSON arrays, several times
+/// their size
+pub const MAX_IMPORT_BYTES: usize = 512 * 1024 * 1024;
 
 /// Clean URL values by removing invisible Unicode characters
 /// Removes zero-width spaces, directional isolates, and other invisible formatting characters
-fn clean_url_value(value: &str) -> String {
+pub(crate) fn clean_url_value(value: &str) -> String {
     value
         .trim()
         .chars()
@@ -39,25 +69,59 @@ fn clean_url_value(value: &str) -> String {
         .collect()
 }
 
-/// POST handler for project things
-///
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProjectPostQuery {
+    /// Update the project if the ID already exists instead of refusing, for older tools
+    #[serde(default)]
+    pub upsert: bool,
+}
+
+/// Create a project, use `PUT /api/v1/project/{id}` to change one
 #[utoipa::path(
     post,
     path = "/api/v1/project",
+    tag = "projects",
+    operation_id = "post_project",
+    params(ProjectPostQuery),
     request_body = project::Model,
     responses(
-        (status = OK, description = "Created a project", body = project::Model)
+        (status = OK, description = "Created a project, or updated it with upsert", body = project::Model),
+        (status = BAD_REQUEST, description = "Empty, overlong or too many tags, or a description over --max-notes-bytes", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Upserting another user's project", body = ErrorResponse),
+        (status = CONFLICT, description = "Project ID already in use", body = ErrorResponse)
     )
 )]
 pub async fn post_project(
     State(state): State<SharedState>,
-    Json(project): Json<project::Model>,
-) -> Result<Json<project::Model>, WebError> {
+    Query(query): Query<ProjectPostQuery>,
+    auth_user: Option<Extension<AuthUser>>,
+    Json(mut project): Json<project::Model>,
+) -> Result<(HeaderMap, Json<project::Model>), WebError> {
+    normalize_project_tags(&mut project)?;
+    let reader = state.read().await;
+    let truncated = reader
+        .notes_limit
+        .apply("description", &mut project.description)?;
+    let mut warning = None;
     let project = match project::Entity::find_by_id(project.id)
-        .one(&state.read().await.conn)
+        .one(&reader.conn)
         .await?
     {
+        // a reused ID would otherwise overwrite someone else's project
+        Some(_) if !query.upsert => {
+            return Err(WebError::new(
+                StatusCode::CONFLICT,
+                format!(
+                    "Project {} already exists, update it with PUT /api/v1/project/{}",
+                    project.id, project.id
+                ),
+            )
+            .with_code(PROJECT_ID_CONFLICT)
+            .with_detail("id", project.id.to_string()));
+        }
         Some(val) => {
+            check_project_access(&reader.conn, &val, auth_user.as_deref()).await?;
             let mut target_project = val.into_active_model();
             target_project.description = Set(project.description);
             target_project.name = Set(project.name);
@@ -65,27 +129,383 @@ pub async fn post_project(
             target_project.last_updated = Set(Some(Utc::now()));
 
             target_project
-                .update(&state.read().await.conn)
+                .update(&reader.conn)
                 .await
                 .inspect_err(|err| error!("Failed to update project: {:?}", err))?
                 .try_into_model()?
         }
         None => {
-            let project = project.into_active_model();
+            reader
+                .quota
+                .check(&reader.conn, QuotaKind::Projects, 1, auth_user.as_deref())
+                .await?;
+            let mut project = project.into_active_model();
+            // new projects belong to whoever created them
+            if let Some(auth_user) = auth_user {
+                project.user = Set(auth_user.id);
+            }
             debug!("Creating project: {:?}", project);
-            project
-                .insert(&state.read().await.conn)
+            let project = project
+                .insert(&reader.conn)
                 .await
-                .inspect_err(|err| error!("Failed to save project: {:?}", err))?
+                .inspect_err(|err| error!("Failed to save project: {:?}", err))?;
+            warning = reader.quota.record(QuotaKind::Projects, 1);
+            project
         }
     };
 
-    Ok(Json(project))
+    let mut headers = warning_headers(warning);
+    if truncated {
+        headers.extend(truncated_headers(&["description"]));
+    }
+    Ok((headers, Json(project)))
+}
+
+/// A whole project graph, for creating in one go
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ProjectGraph {
+    pub project: project::Model,
+    #[serde(default)]
+    pub nodes: Vec<node::Model>,
+    #[serde(default)]
+    pub nodelinks: Vec<nodelink::Model>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ProjectGraphCreated {
+    pub project_id: Uuid,
+    pub node_ids: Vec<Uuid>,
+    pub nodelink_ids: Vec<Uuid>,
+}
+
+/// How many rows go into each INSERT, to stay under SQLite's bound parameter limit
+const BULK_INSERT_CHUNK_SIZE: usize = 500;
+
+/// An error about one item in a bulk request
+fn bulk_item_error(
+    status: StatusCode,
+    field: &str,
+    index: usize,
+    id: Uuid,
+    message: String,
+) -> WebError {
+    WebError::new(status, format!("{field}[{index}]: {message}"))
+        .with_detail("field", field)
+        .with_detail("index", index)
+        .with_detail("id", id.to_string())
+}
+
+/// Create a project along with all of its nodes and links
+#[utoipa::path(
+    post,
+    path = "/api/v1/project/full",
+    tag = "projects",
+    operation_id = "post_project_full",
+    request_body = ProjectGraph,
+    responses(
+        (status = OK, description = "Created the project, nodes and links", body = ProjectGraphCreated),
+        (status = BAD_REQUEST, description = "A node or link is invalid, the body says which", body = ErrorResponse),
+        (status = FORBIDDEN, description = "More links than --max-nodelinks-per-project allows", body = ErrorResponse),
+        (status = CONFLICT, description = "The project, or a node or link ID, already exists", body = ErrorResponse),
+        (status = UNPROCESSABLE_ENTITY, description = "A node breaks a value policy rule", body = ErrorResponse)
+    )
+)]
+pub async fn post_project_full(
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+    Json(graph): Json<ProjectGraph>,
+) -> Result<(HeaderMap, Json<ProjectGraphCreated>), WebError> {
+    let reader = state.read().await;
+    let (headers, created) =
+        create_project_graph(&reader, auth_user.as_deref(), graph, Vec::new()).await?;
+    Ok((headers, Json(created)))
+}
+
+/// Validate and store a whole project graph in one transaction, for [post_project_full] and
+/// [import_project]
+///
+/// Attachments are stored as given, so `data` must already be encoded with their `codec`. The
+/// project belongs to `auth_user`, who is also recorded as having created everything in it.
+async fn create_project_graph(
+    reader: &AppState,
+    auth_user: Option<&AuthUser>,
+    graph: ProjectGraph,
+    mut attachments: Vec<attachment::Model>,
+) -> Result<(HeaderMap, ProjectGraphCreated), WebError> {
+    let ProjectGraph {
+        mut project,
+        mut nodes,
+        mut nodelinks,
+    } = graph;
+    normalize_project_tags(&mut project)?;
+    let created_by = AuthUser::created_by(auth_user);
+    let now = Utc::now();
+
+    // check everything we can before touching the database
+    let mut node_ids = HashSet::with_capacity(nodes.len());
+    for (index, node) in nodes.iter_mut().enumerate() {
+        if node.project_id != project.id {
+            return Err(bulk_item_error(
+                StatusCode::BAD_REQUEST,
+                "nodes",
+                index,
+                node.id,
+                format!("project_id must be {}", project.id),
+            ));
+        }
+        if !node_ids.insert(node.id) {
+            return Err(bulk_item_error(
+                StatusCode::BAD_REQUEST,
+                "nodes",
+                index,
+                node.id,
+                "ID is used more than once".to_string(),
+            ));
+        }
+        if node.node_type == NodeType::Url {
+            node.value = clean_url_value(&node.value);
+        }
+        node.created_by = created_by.clone();
+        node.created = Some(now);
+        node.deleted_at = None;
+        let id = node.id;
+        reader.value_policy.apply(node).map_err(|violation| {
+            bulk_item_error(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "nodes",
+                index,
+                id,
+                violation.message(),
+            )
+            .with_code(VALUE_POLICY_VIOLATION)
+            .with_detail("rule", violation.rule)
+        })?;
+    }
+    let mut nodelink_ids = HashSet::with_capacity(nodelinks.len());
+    for (index, nodelink) in nodelinks.iter_mut().enumerate() {
+        let id = nodelink.id;
+        let invalid = |message: String| {
+            bulk_item_error(StatusCode::BAD_REQUEST, "nodelinks", index, id, message)
+        };
+        if nodelink.project_id != project.id {
+            return Err(invalid(format!("project_id must be {}", project.id)));
+        }
+        if !nodelink_ids.insert(nodelink.id) {
+            return Err(invalid("ID is used more than once".to_string()));
+        }
+        if let Some(missing) = [nodelink.left, nodelink.right]
+            .into_iter()
+            .find(|id| !node_ids.contains(id))
+        {
+            return Err(invalid(format!("node {missing} isn't in this request")));
+        }
+        validate_nodelink(nodelink).map_err(|err| invalid(err.message))?;
+        nodelink.created_by = created_by.clone();
+        nodelink.created = Some(now);
+    }
+    let mut attachment_ids = HashSet::with_capacity(attachments.len());
+    for (index, attachment) in attachments.iter_mut().enumerate() {
+        let invalid = |message: String| {
+            bulk_item_error(
+                StatusCode::BAD_REQUEST,
+                "attachments",
+                index,
+                attachment.id,
+                message,
+            )
+        };
+        if !attachment_ids.insert(attachment.id) {
+            return Err(invalid("ID is used more than once".to_string()));
+        }
+        if !node_ids.contains(&attachment.node_id) {
+            return Err(invalid(format!(
+                "node {} isn't in this request",
+                attachment.node_id
+            )));
+        }
+        attachment.created_by = created_by.clone();
+    }
+    let attachment_bytes: u64 = attachments.iter().map(|a| a.size.max(0) as u64).sum();
+
+    let txn = reader.begin().await?;
+
+    if project::Entity::find_by_id(project.id)
+        .one(&txn)
+        .await?
+        .is_some()
+    {
+        return Err(WebError::new(
+            StatusCode::CONFLICT,
+            format!("Project {} already exists", project.id),
+        ));
+    }
+    if let Some(existing) = node::Entity::find()
+        .filter(node::Column::Id.is_in(node_ids.iter().copied()))
+        .one(&txn)
+        .await?
+    {
+        let index = nodes
+            .iter()
+            .position(|n| n.id == existing.id)
+            .unwrap_or_default();
+        return Err(bulk_item_error(
+            StatusCode::CONFLICT,
+            "nodes",
+            index,
+            existing.id,
+            "ID is already used in another project".to_string(),
+        )
+        .with_code(NODE_ID_CONFLICT));
+    }
+    if let Some(existing) = nodelink::Entity::find()
+        .filter(nodelink::Column::Id.is_in(nodelink_ids.iter().copied()))
+        .one(&txn)
+        .await?
+    {
+        let index = nodelinks
+            .iter()
+            .position(|l| l.id == existing.id)
+            .unwrap_or_default();
+        return Err(bulk_item_error(
+            StatusCode::CONFLICT,
+            "nodelinks",
+            index,
+            existing.id,
+            "Nodelink already exists".to_string(),
+        ));
+    }
+    if let Some(existing) = attachment::Entity::find()
+        .select_only()
+        .column(attachment::Column::Id)
+        .filter(attachment::Column::Id.is_in(attachment_ids.iter().copied()))
+        .into_tuple::<Uuid>()
+        .one(&txn)
+        .await?
+    {
+        let index = attachments
+            .iter()
+            .position(|a| a.id == existing)
+            .unwrap_or_default();
+        return Err(bulk_item_error(
+            StatusCode::CONFLICT,
+            "attachments",
+            index,
+            existing,
+            "Attachment already exists".to_string(),
+        ));
+    }
+
+    reader
+        .quota
+        .check(&txn, QuotaKind::Projects, 1, auth_user)
+        .await?;
+    let nodes_quota = QuotaKind::NodesPerProject(project.id);
+    reader
+        .quota
+        .check(&txn, nodes_quota, nodes.len() as u64, auth_user)
+        .await?;
+    let nodelinks_quota = QuotaKind::NodelinksPerProject(project.id);
+    reader
+        .quota
+        .check(&txn, nodelinks_quota, nodelinks.len() as u64, auth_user)
+        .await?;
+    if !attachments.is_empty() {
+        reader
+            .quota
+            .check(
+                &txn,
+                QuotaKind::AttachmentBytes,
+                attachment_bytes,
+                auth_user,
+            )
+            .await?;
+    }
+
+    let mut new_project = project.into_active_model();
+    // new projects belong to whoever created them
+    if let Some(auth_user) = auth_user {
+        new_project.user = Set(auth_user.id);
+    }
+    let project = new_project
+        .insert(&txn)
+        .await
+        .inspect_err(|err| error!("Failed to save project: {:?}", err))?;
+
+    let created = ProjectGraphCreated {
+        project_id: project.id,
+        node_ids: nodes.iter().map(|node| node.id).collect(),
+        nodelink_ids: nodelinks.iter().map(|link| link.id).collect(),
+    };
+    for chunk in nodes.chunks(BULK_INSERT_CHUNK_SIZE) {
+        node::Entity::insert_many(chunk.iter().cloned().map(node::ActiveModel::from))
+            .exec(&txn)
+            .await
+            .inspect_err(|err| error!(error=?err, "Failed to insert nodes"))?;
+    }
+    for chunk in nodelinks.chunks(BULK_INSERT_CHUNK_SIZE) {
+        nodelink::Entity::insert_many(chunk.iter().cloned().map(nodelink::ActiveModel::from))
+            .exec(&txn)
+            .await
+            .inspect_err(|err| error!(error=?err, "Failed to insert nodelinks"))?;
+    }
+    // one at a time, attachments can be large enough to hit SQLite's statement size limit
+    let attachment_count = attachments.len();
+    for attachment in attachments {
+        // imported data is always the attachment's own
+        attachment::ActiveModel::from(attachment::Model {
+            shared_blob: false,
+            ..attachment
+        })
+        .insert(&txn)
+        .await
+        .inspect_err(|err| error!(error=?err, "Failed to insert attachment"))?;
+    }
+    txn.commit().await?;
+    info!(
+        project_id = project.id.to_string(),
+        nodes = created.node_ids.len(),
+        nodelinks = created.nodelink_ids.len(),
+        attachments = attachment_count,
+        "Created project graph"
+    );
+
+    let projects_warning = reader.quota.record(QuotaKind::Projects, 1);
+    let nodes_warning = reader
+        .quota
+        .record(nodes_quota, created.node_ids.len() as u64);
+    let nodelinks_warning = reader
+        .quota
+        .record(nodelinks_quota, created.nodelink_ids.len() as u64);
+    let attachments_warning = match attachment_count {
+        0 => None,
+        _ => reader
+            .quota
+            .record(QuotaKind::AttachmentBytes, attachment_bytes),
+    };
+    let warning = projects_warning
+        .or(nodes_warning)
+        .or(nodelinks_warning)
+        .or(attachments_warning);
+    Ok((warning_headers(warning), created))
 }
 
 pub struct WebError {
     status: StatusCode,
     message: String,
+    code: Option<&'static str>,
+    details: serde_json::Map<String, serde_json::Value>,
+}
+
+/// The JSON body returned for errors
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ErrorResponse {
+    pub error: String,
+    /// Machine-readable error code, eg `invalid_path_parameter`
+    pub code: Option<String>,
+    /// The request parameter which caused the error
+    pub parameter: Option<String>,
+    /// The offending value of the parameter
+    pub value: Option<String>,
 }
 
 impl WebError {
@@ -93,21 +513,33 @@ impl WebError {
         WebError {
             status,
             message: message.to_string(),
+            code: None,
+            details: serde_json::Map::new(),
         }
     }
 
     pub fn not_found(message: impl ToString) -> Self {
-        WebError {
-            status: StatusCode::NOT_FOUND,
-            message: message.to_string(),
-        }
+        Self::new(StatusCode::NOT_FOUND, message)
     }
 
     pub fn internal_server_error(message: impl ToString) -> Self {
-        WebError {
-            status: StatusCode::INTERNAL_SERVER_ERROR,
-            message: message.to_string(),
-        }
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, message)
+    }
+
+    /// Set the machine-readable error code
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Add an extra field to the error body
+    pub fn with_detail(mut self, key: &str, value: impl Into<serde_json::Value>) -> Self {
+        self.details.insert(key.to_string(), value.into());
+        self
     }
 }
 
@@ -119,33 +551,40 @@ impl From<InvalidHeaderValue> for WebError {
 
 impl IntoResponse for WebError {
     fn into_response(self) -> axum::response::Response {
-        let body = serde_json::json!({
-            "error": self.message,
-        });
-        let mut response = axum::response::Response::new(body.to_string().into());
+        let mut body = self.details;
+        body.insert("error".to_string(), self.message.into());
+        if let Some(code) = self.code {
+            body.insert("code".to_string(), code.into());
+        }
+        let mut response =
+            axum::response::Response::new(serde_json::Value::Object(body).to_string().into());
         *response.status_mut() = self.status;
         response
             .headers_mut()
             .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        if self.code == Some(DATABASE_BUSY) {
+            response.extensions_mut().insert(DatabaseBusy);
+        }
         response
     }
 }
 
 impl From<DbErr> for WebError {
     fn from(err: DbErr) -> Self {
-        WebError {
-            status: StatusCode::INTERNAL_SERVER_ERROR,
-            message: format!("Database error: {:?}", err),
+        if is_busy(&err) {
+            return WebError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "The database is busy, try again shortly",
+            )
+            .with_code(DATABASE_BUSY);
         }
+        WebError::internal_server_error(format!("Database error: {:?}", err))
     }
 }
 
 impl From<serde_json::Error> for WebError {
     fn from(err: serde_json::Error) -> Self {
-        WebError {
-            status: StatusCode::INTERNAL_SERVER_ERROR,
-            message: format!("Serialization error: {:?}", err),
-        }
+        WebError::internal_server_error(format!("Serialization error: {:?}", err))
     }
 }
 
@@ -154,54 +593,209 @@ impl From<serde_json::Error> for WebError {
 #[utoipa::path(
     get,
     path = "/api/v1/project/{id}",
+    tag = "projects",
+    operation_id = "get_project",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
     responses(
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
         (status = OK, description = "One result ok", body = project::Model)
     )
 )]
 pub async fn get_project(
     Path(id): Path<Uuid>,
     State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
 ) -> Result<Json<project::Model>, WebError> {
-    let res = project::Entity::find_by_id(id)
-        .one(&state.read().await.conn)
+    let conn = &state.read().await.conn;
+    Ok(Json(
+        find_accessible_project(conn, id, auth_user.as_deref()).await?,
+    ))
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ProjectStats {
+    pub project_id: Uuid,
+    pub nodes: u64,
+    /// Every node type, including those without any nodes
+    pub nodes_by_type: HashMap<NodeType, u64>,
+    pub nodelinks: u64,
+    pub nodelinks_by_type: HashMap<LinkType, u64>,
+    pub attachments: u64,
+    /// Uncompressed size of every attachment
+    pub attachment_bytes: u64,
+    /// Unset if no node records when it was created
+    pub last_node_created: Option<chrono::DateTime<Utc>>,
+    pub last_attachment_created: Option<chrono::DateTime<Utc>>,
+}
+
+/// Counts of a project's nodes, links and attachments
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/stats",
+    tag = "projects",
+    operation_id = "get_project_stats",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    responses(
+        (status = OK, description = "Counts by type and attachment totals", body = ProjectStats),
+        (status = BAD_REQUEST, description = "Invalid path parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse)
+    )
+)]
+pub async fn get_project_stats(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<ProjectStats>, WebError> {
+    // one transaction so the counts agree with each other
+    let txn = state.read().await.begin().await?;
+    find_accessible_project(&txn, id, auth_user.as_deref()).await?;
+
+    let mut nodes_by_type: HashMap<NodeType, u64> =
+        NodeType::iter().map(|node_type| (node_type, 0)).collect();
+    let node_counts: Vec<(NodeType, i64)> = node::Entity::find_live()
+        .select_only()
+        .column(node::Column::NodeType)
+        .column_as(node::Column::Id.count(), "total")
+        .filter(node::Column::ProjectId.eq(id))
+        .group_by(node::Column::NodeType)
+        .into_tuple()
+        .all(&txn)
+        .await?;
+    for (node_type, total) in node_counts {
+        nodes_by_type.insert(node_type, total.max(0) as u64);
+    }
+    let last_node_created: Option<Option<chrono::DateTime<Utc>>> = node::Entity::find_live()
+        .select_only()
+        .column_as(node::Column::Created.max(), "last")
+        .filter(node::Column::ProjectId.eq(id))
+        .into_tuple()
+        .one(&txn)
         .await?;
 
-    match res {
-        Some(project) => Ok(Json(project)),
-        None => Err(WebError::not_found(format!("Project {} not found", id))),
+    let mut nodelinks_by_type: HashMap<LinkType, u64> =
+        LinkType::iter().map(|linktype| (linktype, 0)).collect();
+    let nodelink_counts: Vec<(LinkType, i64)> = nodelink::Entity::find_live()
+        .select_only()
+        .column(nodelink::Column::Linktype)
+        .column_as(nodelink::Column::Id.count(), "total")
+        .filter(nodelink::Column::ProjectId.eq(id))
+        .group_by(nodelink::Column::Linktype)
+        .into_tuple()
+        .all(&txn)
+        .await?;
+    for (linktype, total) in nodelink_counts {
+        nodelinks_by_type.insert(linktype, total.max(0) as u64);
     }
+
+    let (attachments, attachment_bytes, last_attachment_created): (
+        i64,
+        Option<i64>,
+        Option<chrono::DateTime<Utc>>,
+    ) = attachment::Entity::find()
+        .select_only()
+        .column_as(attachment::Column::Id.count(), "total")
+        .column_as(attachment::Column::Size.sum(), "bytes")
+        .column_as(attachment::Column::Created.max(), "last")
+        .join(JoinType::InnerJoin, attachment::Relation::Node.def())
+        .filter(node::Column::ProjectId.eq(id))
+        .into_tuple()
+        .one(&txn)
+        .await?
+        .unwrap_or_default();
+    txn.commit().await?;
+
+    Ok(Json(ProjectStats {
+        project_id: id,
+        nodes: nodes_by_type.values().sum(),
+        nodes_by_type,
+        nodelinks: nodelinks_by_type.values().sum(),
+        nodelinks_by_type,
+        attachments: attachments.max(0) as u64,
+        attachment_bytes: attachment_bytes.unwrap_or(0).max(0) as u64,
+        last_node_created: last_node_created.flatten(),
+        last_attachment_created,
+    }))
 }
 
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProjectsQuery {
+    /// List archived projects too
+    #[serde(default)]
+    pub include_archived: bool,
+}
+
+/// Lists the caller's projects that aren't archived, pinned projects first and then newest first
 #[utoipa::path(
     get,
     path = "/api/v1/projects",
+    tag = "projects",
+    operation_id = "get_projects",
+    params(ProjectsQuery, PaginationQuery),
     responses(
-        (status = OK, description = "One result ok", body = Vec<project::Model>)
+        (status = BAD_REQUEST, description = "Invalid query parameter", body = ErrorResponse),
+        (status = OK, description = "One page of projects", body = PaginatedResponse<project::Model>)
     )
 )]
 pub async fn get_projects(
     State(state): State<SharedState>,
-) -> Result<Json<Vec<project::Model>>, WebError> {
-    let val = project::Entity::find()
-        .all(&state.read().await.conn)
+    Query(query): Query<ProjectsQuery>,
+    Query(pagination): Query<PaginationQuery>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<PaginatedResponse<project::Model>>, WebError> {
+    pagination.validate()?;
+    let conn = &state.read().await.conn;
+    let mut select = project::Entity::find().filter(accessible_projects(auth_user.as_deref()));
+    if !query.include_archived {
+        select = select.filter(project::Column::IsArchived.eq(false));
+    }
+    let paginator = select
+        .order_by_desc(project::Column::Pinned)
+        .order_by_desc(project::Column::Creationdate)
+        .order_by_asc(project::Column::Id)
+        .paginate(conn, pagination.page_size);
+    let total_count = paginator.num_items().await?;
+    let items = paginator
+        .fetch_page(pagination.page - 1)
         .await
         .inspect_err(|err| error!(error=?err, "Failed to query project list"))?;
-    Ok(Json(val))
+    Ok(Json(PaginatedResponse::new(
+        &pagination,
+        total_count,
+        items,
+    )))
 }
 
 #[utoipa::path(
     get,
     path = "/api/v1/node/{id}",
+    tag = "nodes",
+    operation_id = "get_node",
+    params(
+        ("id" = Uuid, Path, description = "Node ID")
+    ),
     responses(
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
         (status = OK, description = "One result ok", body = node::Model)
     )
 )]
 pub async fn get_node(
     Path(id): Path<Uuid>,
     State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
 ) -> Result<Json<node::Model>, WebError> {
+    let conn = &state.read().await.conn;
+    check_node_access(conn, id, auth_user.as_deref()).await?;
     match node::Entity::find_by_id(id)
-        .one(&state.read().await.conn)
+        .filter(node::Column::DeletedAt.is_null())
+        .one(conn)
         .await?
     {
         Some(val) => Ok(Json(val)),
@@ -212,178 +806,1553 @@ pub async fn get_node(
 #[utoipa::path(
     get,
     path = "/api/v1/project/{project_id}/nodes",
+    tag = "nodes",
+    operation_id = "get_nodes_by_project",
+    params(
+        ("project_id" = Uuid, Path, description = "Project ID"),
+        ("incomplete_only" = Option<bool>, Query, description = "Only return nodes failing a review check"),
+        ("checks" = Option<String>, Query, description = "Comma-separated review checks for incomplete_only, defaults to all"),
+        ("node_type" = Option<String>, Query, description = "Comma-separated node types to return, eg email,domain, defaults to all"),
+        ("created_by" = Option<String>, Query, description = "Only nodes first reported by this user's subject, or `me` for the caller"),
+        ("sort" = Option<NodeSort>, Query, description = "What to order nodes by, defaults to id"),
+        ("order" = Option<SortOrder>, Query, description = "asc (the default) or desc"),
+        PaginationQuery
+    ),
     responses(
-        (status = OK, description = "One result ok", body = Vec<node::Model>)
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = OK, description = "One page of nodes", body = PaginatedResponse<node::Model>)
     )
 )]
 pub async fn get_nodes_by_project(
     Path(project_id): Path<Uuid>,
+    Query(query): Query<NodesByProjectQuery>,
+    Query(pagination): Query<PaginationQuery>,
     State(state): State<SharedState>,
-) -> Result<Json<Vec<node::Model>>, WebError> {
-    let nodes = node::Entity::find()
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<PaginatedResponse<node::Model>>, WebError> {
+    pagination.validate()?;
+    let node_types = parse_node_types("node_type", query.node_type.as_deref())?;
+    // a project which doesn't exist has no nodes, rather than being an error
+    let conn = &state.read().await.conn;
+    if let Some(project) = project::Entity::find_by_id(project_id).one(conn).await? {
+        check_project_access(conn, &project, auth_user.as_deref()).await?;
+    }
+    // without authentication nobody is recorded, so `me` finds nodes with no creator
+    let created_by = query
+        .created_by
+        .as_deref()
+        .map(|created_by| match created_by {
+            "me" => AuthUser::created_by(auth_user.as_deref()),
+            subject => Some(subject.to_string()),
+        });
+    if query.incomplete_only {
+        let checks = review::parse_checks(query.checks.as_deref())?;
+        let mut nodes: Vec<node::Model> = review::review_nodes(conn, project_id, &checks)
+            .await?
+            .into_iter()
+            .filter(|(node, failing)| {
+                !failing.is_empty()
+                    && node_types
+                        .as_ref()
+                        .is_none_or(|node_types| node_types.contains(&node.node_type))
+                    && created_by
+                        .as_ref()
+                        .is_none_or(|created_by| node.created_by == *created_by)
+            })
+            .map(|(node, _)| node)
+            .collect();
+        nodes.sort_by(|a, b| match query.order {
+            SortOrder::Asc => query.sort.compare(a, b),
+            SortOrder::Desc => query.sort.compare(b, a),
+        });
+        let total_count = nodes.len() as u64;
+        let items = nodes
+            .into_iter()
+            .skip(pagination.offset() as usize)
+            .take(pagination.page_size as usize)
+            .collect();
+        return Ok(Json(PaginatedResponse::new(
+            &pagination,
+            total_count,
+            items,
+        )));
+    }
+    let mut select = node::Entity::find_live()
         .filter(node::Column::ProjectId.eq(project_id))
-        .all(&state.read().await.conn)
+        .order_by(query.sort.column(), query.order.into());
+    if let Some(node_types) = node_types {
+        select = select.filter(node::Column::NodeType.is_in(node_types));
+    }
+    select = match created_by {
+        Some(Some(subject)) => select.filter(node::Column::CreatedBy.eq(subject)),
+        Some(None) => select.filter(node::Column::CreatedBy.is_null()),
+        None => select,
+    };
+    if query.sort != NodeSort::Id {
+        select = select.order_by(node::Column::Id, query.order.into());
+    }
+    let paginator = select.paginate(conn, pagination.page_size);
+    let total_count = paginator.num_items().await?;
+    let items = paginator
+        .fetch_page(pagination.page - 1)
         .await
         .inspect_err(|err| error!("Failed to get nodes for project {}: {:?}", project_id, err))?;
-    Ok(Json(nodes))
+    Ok(Json(PaginatedResponse::new(
+        &pagination,
+        total_count,
+        items,
+    )))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NodesByProjectQuery {
+    /// Only return nodes failing one of the review `checks`
+    #[serde(default)]
+    pub incomplete_only: bool,
+    #[serde(default)]
+    pub checks: Option<String>,
+    /// Comma-separated node types to return, defaults to all of them
+    #[serde(default)]
+    pub node_type: Option<String>,
+    /// A user's subject, or `me` for the caller
+    #[serde(default)]
+    pub created_by: Option<String>,
+    #[serde(default)]
+    pub sort: NodeSort,
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+/// What to order a list of nodes by, ties are broken by ID
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeSort {
+    #[default]
+    Id,
+    Updated,
+    Display,
+    NodeType,
+}
+
+impl NodeSort {
+    fn column(&self) -> node::Column {
+        match self {
+            NodeSort::Id => node::Column::Id,
+            NodeSort::Updated => node::Column::Updated,
+            NodeSort::Display => node::Column::Display,
+            NodeSort::NodeType => node::Column::NodeType,
+        }
+    }
+
+    /// The same ordering as [NodeSort::column], for nodes already in memory
+    fn compare(&self, a: &node::Model, b: &node::Model) -> std::cmp::Ordering {
+        match self {
+            NodeSort::Id => std::cmp::Ordering::Equal,
+            NodeSort::Updated => a.updated.cmp(&b.updated),
+            NodeSort::Display => a.display.cmp(&b.display),
+            NodeSort::NodeType => a.node_type.as_ref().cmp(b.node_type.as_ref()),
+        }
+        .then_with(|| a.id.cmp(&b.id))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl From<SortOrder> for sea_orm::Order {
+    fn from(order: SortOrder) -> Self {
+        match order {
+            SortOrder::Asc => sea_orm::Order::Asc,
+            SortOrder::Desc => sea_orm::Order::Desc,
+        }
+    }
+}
+
+/// Page size for paginated lists when the client doesn't say
+pub const DEFAULT_PAGE_SIZE: u64 = 50;
+/// The biggest page a paginated list will return
+pub const MAX_PAGE_SIZE: u64 = 1000;
+
+fn default_page() -> u64 {
+    1
+}
+
+fn default_page_size() -> u64 {
+    DEFAULT_PAGE_SIZE
+}
+
+/// `?page=&page_size=` for page-numbered lists
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PaginationQuery {
+    /// Page number, starting from 1
+    #[serde(default = "default_page")]
+    #[param(default = 1, minimum = 1)]
+    pub page: u64,
+    /// Items per page, defaults to 50 and at most 1000
+    #[serde(default = "default_page_size")]
+    #[param(default = 50, minimum = 1, maximum = 1000)]
+    pub page_size: u64,
+}
+
+impl PaginationQuery {
+    pub fn validate(&self) -> Result<(), WebError> {
+        let invalid = |parameter: &'static str, value: u64, message: &str| {
+            WebError::new(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid query parameter `{parameter}`: {message}"),
+            )
+            .with_code(INVALID_QUERY_PARAMETER)
+            .with_detail("parameter", Some(parameter))
+            .with_detail("value", Some(value.to_string()))
+        };
+        if self.page == 0 {
+            return Err(invalid("page", self.page, "pages start from 1"));
+        }
+        if self.page_size == 0 || self.page_size > MAX_PAGE_SIZE {
+            return Err(invalid(
+                "page_size",
+                self.page_size,
+                &format!("must be between 1 and {MAX_PAGE_SIZE}"),
+            ));
+        }
+        Ok(())
+    }
+
+    /// How many items come before this page
+    pub fn offset(&self) -> u64 {
+        (self.page - 1).saturating_mul(self.page_size)
+    }
+}
+
+/// One page of a list, past the last page is an empty page rather than an error
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct PaginatedResponse<T> {
+    pub total_count: u64,
+    pub page: u64,
+    pub page_size: u64,
+    pub items: Vec<T>,
+}
+
+impl<T> PaginatedResponse<T> {
+    pub fn new(pagination: &PaginationQuery, total_count: u64, items: Vec<T>) -> Self {
+        Self {
+            total_count,
+            page: pagination.page,
+            page_size: pagination.page_size,
+            items,
+        }
+    }
+}
+
+/// How many nodes [get_nodes] returns when the client doesn't say
+pub const DEFAULT_NODES_PAGE_SIZE: u64 = 100;
+/// The most nodes [get_nodes] will return at once
+pub const MAX_NODES_PAGE_SIZE: u64 = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct NodesQuery {
+    pub node_type: Option<NodeType>,
+    pub project_id: Option<Uuid>,
+    pub q: Option<String>,
+    /// The `next_cursor` from the previous page
+    pub cursor: Option<Uuid>,
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NodesPage {
+    pub nodes: Vec<node::Model>,
+    /// Pass this as `cursor` to get the next page, unset on the last page
+    pub next_cursor: Option<Uuid>,
+}
+
+/// Browse nodes across every project, ordered by ID, for admins
+#[utoipa::path(
+    get,
+    path = "/api/v1/nodes",
+    tag = "nodes",
+    operation_id = "get_nodes",
+    params(
+        ("node_type" = Option<NodeType>, Query, description = "Only return nodes of this type"),
+        ("project_id" = Option<Uuid>, Query, description = "Only return nodes in this project"),
+        ("q" = Option<String>, Query, description = "Substring to match against display, value and notes"),
+        ("cursor" = Option<Uuid>, Query, description = "The next_cursor from the previous page"),
+        ("limit" = Option<u64>, Query, description = "Page size, defaults to 100 and is capped at 1000")
+    ),
+    responses(
+        (status = BAD_REQUEST, description = "Invalid query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Caller isn't an admin", body = ErrorResponse),
+        (status = OK, description = "One page of nodes", body = NodesPage)
+    )
+)]
+pub async fn get_nodes(
+    State(state): State<SharedState>,
+    Query(query): Query<NodesQuery>,
+) -> Result<Json<NodesPage>, WebError> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_NODES_PAGE_SIZE)
+        .clamp(1, MAX_NODES_PAGE_SIZE);
+
+    let mut select = node::Entity::find_live().order_by_asc(node::Column::Id);
+    if let Some(node_type) = query.node_type {
+        select = select.filter(node::Column::NodeType.eq(node_type));
+    }
+    if let Some(project_id) = query.project_id {
+        select = select.filter(node::Column::ProjectId.eq(project_id));
+    }
+    if let Some(q) = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        let search_term = format!("%{}%", q.to_lowercase());
+        select = select.filter(
+            node::Column::Display
+                .like(&search_term)
+                .or(node::Column::Value.like(&search_term))
+                .or(node::Column::Notes.like(&search_term)),
+        );
+    }
+    if let Some(cursor) = query.cursor {
+        select = select.filter(node::Column::Id.gt(cursor));
+    }
+
+    // fetch one extra to find out if there's another page
+    let mut nodes = select
+        .limit(limit + 1)
+        .all(&state.read().await.conn)
+        .await?;
+    let next_cursor = if nodes.len() as u64 > limit {
+        nodes.truncate(limit as usize);
+        nodes.last().map(|node| node.id)
+    } else {
+        None
+    };
+    Ok(Json(NodesPage { nodes, next_cursor }))
+}
+
+/// Find all the nodes linked to a node, in either direction
+pub(crate) async fn node_neighbours(
+    conn: &impl ConnectionTrait,
+    node_id: Uuid,
+) -> Result<Vec<node::Model>, DbErr> {
+    let neighbour_ids: Vec<Uuid> = nodelink::Entity::find_live()
+        .filter(
+            nodelink::Column::Left
+                .eq(node_id)
+                .or(nodelink::Column::Right.eq(node_id)),
+        )
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|link| {
+            if link.left == node_id {
+                link.right
+            } else {
+                link.left
+            }
+        })
+        .filter(|id| *id != node_id)
+        .collect();
+
+    if neighbour_ids.is_empty() {
+        return Ok(vec![]);
+    }
+    node::Entity::find_live()
+        .filter(node::Column::Id.is_in(neighbour_ids))
+        .all(conn)
+        .await
 }
 
 #[utoipa::path(
     post,
     path = "/api/v1/node",
+    tag = "nodes",
+    operation_id = "post_node",
     request_body = node::Model,
     responses(
-        (status = OK, description = "One result ok", body = node::Model)
+        (status = OK, description = "One result ok", body = node::Model),
+        (status = BAD_REQUEST, description = "The value doesn't fit the node type, or notes over --max-notes-bytes", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = CONFLICT, description = "Node ID already in use", body = ErrorResponse),
+        (status = UNPROCESSABLE_ENTITY, description = "A node breaks a value policy rule", body = ErrorResponse)
     )
 )]
 pub async fn post_node(
     State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
     Json(mut node): Json<node::Model>,
-) -> Result<Json<node::Model>, WebError> {
-    let txn = state
-        .read()
-        .await
-        .conn
+) -> Result<(HeaderMap, Json<node::Model>), WebError> {
+    let reader = state.read().await;
+    let txn = reader
         .begin()
         .await
         .inspect_err(|err| error!(error=?err, "failed to get transaction!"))?;
 
-    if project::Entity::find_by_id(node.project_id)
+    let Some(project) = project::Entity::find_by_id(node.project_id)
         .one(&txn)
         .await?
-        .is_none()
-    {
+    else {
         return Err(WebError::not_found(format!(
             "Project {} not found for new node",
             node.project_id
         )));
+    };
+    check_project_access(&txn, &project, auth_user.as_deref()).await?;
+
+    // IDs come from the client and are unique across every project, catch reuse before the
+    // insert fails with a database error
+    if let Some(existing) = node::Entity::find_by_id(node.id).one(&txn).await? {
+        let message = if existing.project_id == node.project_id {
+            format!("Node {} already exists, update it instead", node.id)
+        } else {
+            format!("Node ID {} is already used in another project", node.id)
+        };
+        return Err(WebError::new(StatusCode::CONFLICT, message)
+            .with_code(NODE_ID_CONFLICT)
+            .with_detail("id", node.id.to_string()));
     }
 
+    let quota_kind = QuotaKind::NodesPerProject(node.project_id);
+    reader
+        .quota
+        .check(&txn, quota_kind, 1, auth_user.as_deref())
+        .await?;
+
     // Clean URL values before saving
     if node.node_type == NodeType::Url {
         node.value = clean_url_value(&node.value);
     }
+    reader.value_policy.apply(&mut node)?;
+    validate_node_value(&node)?;
+    let truncated = reader.notes_limit.apply("notes", &mut node.notes)?;
+    node.created_by = AuthUser::created_by(auth_user.as_deref());
+    node.created = Some(Utc::now());
+    node.deleted_at = None;
+
+    let node = node::ActiveModel::from(node);
+    let res = node
+        .insert(&txn)
+        .await
+        .inspect_err(|err| error!(error=?err, "Failed to insert node"))?;
+    debug!("Saved node: {:?}", res);
+    let model = res
+        .try_into_model()
+        .inspect_err(|err| error!("Failed to convert inserted node to model: {:?}", err))?;
+    txn.commit().await.inspect_err(
+        |err| error!(error=?err, node=?model, "Failed to commit transaction for new node"),
+    )?;
+    let warning = reader.quota.record(quota_kind, 1);
+    let mut headers = warning_headers(warning);
+    if truncated {
+        headers.extend(truncated_headers(&["notes"]));
+    }
+    Ok((headers, Json(model)))
+}
+
+/// Create many nodes in one transaction, eg results from an external tool
+///
+/// Nodes may belong to different projects. If any node is rejected nothing is stored, and the
+/// error names the offending `index` and `id`.
+#[utoipa::path(
+    post,
+    path = "/api/v1/nodes",
+    tag = "nodes",
+    operation_id = "post_nodes",
+    request_body = Vec<node::Model>,
+    responses(
+        (status = OK, description = "The stored nodes, in request order", body = Vec<node::Model>),
+        (status = BAD_REQUEST, description = "A node ID is used more than once", body = ErrorResponse),
+        (status = FORBIDDEN, description = "A node's project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "A node's project doesn't exist", body = ErrorResponse),
+        (status = CONFLICT, description = "A node ID is already in use", body = ErrorResponse),
+        (status = UNPROCESSABLE_ENTITY, description = "A node breaks a value policy rule", body = ErrorResponse)
+    )
+)]
+pub async fn post_nodes(
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+    Json(mut nodes): Json<Vec<node::Model>>,
+) -> Result<(HeaderMap, Json<Vec<node::Model>>), WebError> {
+    let reader = state.read().await;
+
+    // the server's clock is the canonical one for new nodes
+    let now = Utc::now();
+    let created_by = AuthUser::created_by(auth_user.as_deref());
+    let mut node_ids = HashSet::with_capacity(nodes.len());
+    let mut per_project: HashMap<Uuid, u64> = HashMap::new();
+    for (index, node) in nodes.iter_mut().enumerate() {
+        if !node_ids.insert(node.id) {
+            return Err(bulk_item_error(
+                StatusCode::BAD_REQUEST,
+                "nodes",
+                index,
+                node.id,
+                "ID is used more than once".to_string(),
+            ));
+        }
+        if node.node_type == NodeType::Url {
+            node.value = clean_url_value(&node.value);
+        }
+        node.updated = now;
+        node.created_by = created_by.clone();
+        node.created = Some(now);
+        node.deleted_at = None;
+        let id = node.id;
+        reader.value_policy.apply(node).map_err(|violation| {
+            bulk_item_error(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "nodes",
+                index,
+                id,
+                violation.message(),
+            )
+            .with_code(VALUE_POLICY_VIOLATION)
+            .with_detail("rule", violation.rule)
+        })?;
+        *per_project.entry(node.project_id).or_default() += 1;
+    }
+
+    let txn = reader.begin().await?;
+
+    let projects = project::Entity::find()
+        .filter(project::Column::Id.is_in(per_project.keys().copied()))
+        .all(&txn)
+        .await?;
+    let found: HashSet<Uuid> = projects.iter().map(|project| project.id).collect();
+    if let Some((index, node)) = nodes
+        .iter()
+        .enumerate()
+        .find(|(_, node)| !found.contains(&node.project_id))
+    {
+        return Err(bulk_item_error(
+            StatusCode::NOT_FOUND,
+            "nodes",
+            index,
+            node.id,
+            format!("Project {} not found", node.project_id),
+        ));
+    }
+    for project in projects.iter() {
+        check_project_access(&txn, project, auth_user.as_deref()).await?;
+    }
+    if let Some(existing) = node::Entity::find()
+        .filter(node::Column::Id.is_in(node_ids.iter().copied()))
+        .one(&txn)
+        .await?
+    {
+        let index = nodes
+            .iter()
+            .position(|n| n.id == existing.id)
+            .unwrap_or_default();
+        return Err(bulk_item_error(
+            StatusCode::CONFLICT,
+            "nodes",
+            index,
+            existing.id,
+            "ID is already in use".to_string(),
+        )
+        .with_code(NODE_ID_CONFLICT));
+    }
+    for (project_id, count) in &per_project {
+        reader
+            .quota
+            .check(
+                &txn,
+                QuotaKind::NodesPerProject(*project_id),
+                *count,
+                auth_user.as_deref(),
+            )
+            .await?;
+    }
+
+    let mut res = Vec::with_capacity(nodes.len());
+    for (index, node) in nodes.into_iter().enumerate() {
+        let id = node.id;
+        let model = node::ActiveModel::from(node)
+            .insert(&txn)
+            .await
+            .map_err(|err| {
+                error!(error=?err, index, "Failed to insert node");
+                bulk_item_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "nodes",
+                    index,
+                    id,
+                    "Failed to save node".to_string(),
+                )
+            })?;
+        res.push(model);
+    }
+    txn.commit().await?;
+    info!(nodes = res.len(), "Created nodes in bulk");
+
+    let mut warning = None;
+    for (project_id, count) in per_project {
+        warning = warning.or(reader
+            .quota
+            .record(QuotaKind::NodesPerProject(project_id), count));
+    }
+    Ok((warning_headers(warning), Json(res)))
+}
+
+/// A link's validity range for labels, eg `2019-01-01 to 2022-06-30`
+fn validity_label(nodelink: &nodelink::Model) -> Option<String> {
+    let date = |time: chrono::DateTime<Utc>| time.format("%Y-%m-%d").to_string();
+    match (nodelink.valid_from, nodelink.valid_to) {
+        (Some(from), Some(to)) => Some(format!("{} to {}", date(from), date(to))),
+        (Some(from), None) => Some(format!("from {}", date(from))),
+        (None, Some(to)) => Some(format!("until {}", date(to))),
+        (None, None) => None,
+    }
+}
+
+/// Error code for a node value which doesn't fit its type
+pub const INVALID_NODE_VALUE: &str = "invalid_node_value";
+
+/// Reject email, IP, domain and URL nodes whose value isn't one, see [NodeType::validate_value]
+///
+/// Empty values are allowed so a node can be filled in later, the review checks catch those.
+pub fn validate_node_value(node: &node::Model) -> Result<(), WebError> {
+    if node.value.trim().is_empty() {
+        return Ok(());
+    }
+    node.node_type.validate_value(&node.value).map_err(|err| {
+        WebError::new(StatusCode::BAD_REQUEST, err)
+            .with_code(INVALID_NODE_VALUE)
+            .with_detail("node_type", node.node_type.as_ref())
+            .with_detail("value", node.value.as_str())
+    })
+}
+
+/// Error code for project tags which are empty, too long or too many
+pub const INVALID_PROJECT_TAGS: &str = "invalid_project_tags";
+
+/// Trim and deduplicate a project's tags, see [osint_graph_shared::StringVec::normalized_tags]
+fn normalize_project_tags(project: &mut project::Model) -> Result<(), WebError> {
+    project.tags = project.tags.normalized_tags().map_err(|err| {
+        WebError::new(StatusCode::BAD_REQUEST, err)
+            .with_code(INVALID_PROJECT_TAGS)
+            .with_detail("max_tags", MAX_TAGS.to_string())
+            .with_detail("max_tag_chars", MAX_TAG_CHARS.to_string())
+    })?;
+    Ok(())
+}
+
+/// Check a nodelink's weight and validity range, and tidy its kind
+fn validate_nodelink(nodelink: &mut nodelink::Model) -> Result<(), WebError> {
+    if let (Some(valid_from), Some(valid_to)) = (nodelink.valid_from, nodelink.valid_to) {
+        if valid_from > valid_to {
+            return Err(WebError::new(
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Link valid_from ({}) must not be after valid_to ({})",
+                    valid_from.to_rfc3339(),
+                    valid_to.to_rfc3339()
+                ),
+            ));
+        }
+    }
+    if let Some(weight) = nodelink.weight {
+        if !weight.is_finite() || weight < 0.0 {
+            return Err(WebError::new(
+                StatusCode::BAD_REQUEST,
+                format!("Link weight must be a non-negative number, got {weight}"),
+            ));
+        }
+    }
+    nodelink.kind = nodelink
+        .kind
+        .take()
+        .map(|kind| kind.trim().to_string())
+        .filter(|kind| !kind.is_empty());
+    Ok(())
+}
+
+/// Error code for a link whose end is missing or in another project
+pub const NODELINK_PROJECT_MISMATCH: &str = "nodelink_project_mismatch";
+
+/// Ensure both ends of a link are nodes in `project_id`, so per-project graphs stay whole
+async fn check_nodelink_ends(
+    conn: &impl ConnectionTrait,
+    project_id: Uuid,
+    left: Uuid,
+    right: Uuid,
+) -> Result<(), WebError> {
+    let ends: HashMap<Uuid, Uuid> = node::Entity::find()
+        .select_only()
+        .column(node::Column::Id)
+        .column(node::Column::ProjectId)
+        .filter(node::Column::Id.is_in([left, right]))
+        .into_tuple::<(Uuid, Uuid)>()
+        .all(conn)
+        .await?
+        .into_iter()
+        .collect();
+    for (end, node_id) in [("left", left), ("right", right)] {
+        let message = match ends.get(&node_id) {
+            Some(node_project_id) if *node_project_id == project_id => continue,
+            Some(node_project_id) => format!(
+                "The {end} node {node_id} is in project {node_project_id}, not the link's project {project_id}"
+            ),
+            None => format!("The {end} node {node_id} wasn't found"),
+        };
+        return Err(WebError::new(StatusCode::BAD_REQUEST, message)
+            .with_code(NODELINK_PROJECT_MISMATCH)
+            .with_detail("end", end)
+            .with_detail("node_id", node_id.to_string()));
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/nodelink",
+    tag = "links",
+    operation_id = "post_nodelink",
+    request_body = nodelink::Model,
+    responses(
+        (status = OK, description = "One result ok", body = nodelink::Model),
+        (status = BAD_REQUEST, description = "Invalid weight, or an end isn't a node in the link's project", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user, or has reached --max-nodelinks-per-project", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse),
+        (status = CONFLICT, description = "Nodelink already exists", body = ErrorResponse)
+    )
+)]
+pub async fn post_nodelink(
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+    Json(mut nodelink): Json<nodelink::Model>,
+) -> Result<(HeaderMap, Json<nodelink::Model>), WebError> {
+    validate_nodelink(&mut nodelink)?;
+    nodelink.created_by = AuthUser::created_by(auth_user.as_deref());
+    nodelink.created = Some(Utc::now());
+    let reader = state.read().await;
+    let txn = reader.begin().await?;
+
+    // Validate that the project exists before saving the nodelink
+    let Some(project) = project::Entity::find_by_id(nodelink.project_id)
+        .one(&txn)
+        .await?
+    else {
+        return Err(WebError::not_found(format!(
+            "Project {} not found for new nodelink",
+            nodelink.project_id
+        )));
+    };
+    check_project_access(&txn, &project, auth_user.as_deref()).await?;
+    check_nodelink_ends(&txn, nodelink.project_id, nodelink.left, nodelink.right).await?;
+
+    if nodelink::Entity::find_by_id(nodelink.id)
+        .one(&txn)
+        .await?
+        .is_some()
+    {
+        return Err(WebError::new(
+            StatusCode::CONFLICT,
+            "Nodelink already exists",
+        ));
+    }
+
+    let quota_kind = QuotaKind::NodelinksPerProject(nodelink.project_id);
+    reader
+        .quota
+        .check(&txn, quota_kind, 1, auth_user.as_deref())
+        .await?;
+
+    let nodelink = nodelink.into_active_model();
+    let res = nodelink.insert(&txn).await?;
+    debug!("Saved nodelink: {:?}", res);
+    let model = res.try_into_model()?;
+    txn.commit().await?;
+    let warning = reader.quota.record(quota_kind, 1);
+    Ok((warning_headers(warning), Json(model)))
+}
+
+/// Change a link's type, ends, weight, kind or validity range, keeping its ID
+#[utoipa::path(
+    put,
+    path = "/api/v1/nodelink/{id}",
+    tag = "links",
+    operation_id = "update_nodelink",
+    params(
+        ("id" = Uuid, Path, description = "Nodelink ID")
+    ),
+    request_body = nodelink::Model,
+    responses(
+        (status = BAD_REQUEST, description = "Invalid path parameter or weight, or an end isn't a node in the link's project", body = ErrorResponse),
+        (status = OK, description = "One result ok", body = nodelink::Model),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Nodelink not found")
+    )
+)]
+pub async fn update_nodelink(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+    Json(mut nodelink): Json<nodelink::Model>,
+) -> Result<Json<nodelink::Model>, WebError> {
+    validate_nodelink(&mut nodelink)?;
+    let txn = state.read().await.begin().await?;
+
+    let Some(db_nodelink) = nodelink::Entity::find_by_id(id).one(&txn).await? else {
+        debug!("Nodelink {} not found for update", id);
+        return Err(WebError::not_found(format!("Nodelink {} not found", id)));
+    };
+    find_accessible_project(&txn, db_nodelink.project_id, auth_user.as_deref()).await?;
+    // the link stays in its project, so both ends have to be in there too
+    check_nodelink_ends(&txn, db_nodelink.project_id, nodelink.left, nodelink.right).await?;
+
+    debug!("Updating nodelink {}: {:?}", id, nodelink);
+    let mut db_nodelink = db_nodelink.into_active_model();
+    db_nodelink.left = Set(nodelink.left);
+    db_nodelink.right = Set(nodelink.right);
+    db_nodelink.linktype = Set(nodelink.linktype);
+    db_nodelink.weight = Set(nodelink.weight);
+    db_nodelink.kind = Set(nodelink.kind);
+    db_nodelink.valid_from = Set(nodelink.valid_from);
+    db_nodelink.valid_to = Set(nodelink.valid_to);
+
+    let res = db_nodelink.update(&txn).await?;
+    txn.commit().await?;
+
+    Ok(Json(res.try_into_model()?))
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NodelinksQuery {
+    /// Only links valid at this instant (RFC 3339), links without a range are always valid
+    pub active_at: Option<chrono::DateTime<Utc>>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{project_id}/nodelinks",
+    tag = "links",
+    operation_id = "get_nodelinks_by_project",
+    params(
+        ("project_id" = Uuid, Path, description = "Project ID"),
+        NodelinksQuery
+    ),
+    responses(
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = OK, description = "One result ok", body = Vec<nodelink::Model>)
+    )
+)]
+pub async fn get_nodelinks_by_project(
+    Path(project_id): Path<Uuid>,
+    Query(query): Query<NodelinksQuery>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<Vec<nodelink::Model>>, WebError> {
+    let conn = &state.read().await.conn;
+    if let Some(project) = project::Entity::find_by_id(project_id).one(conn).await? {
+        check_project_access(conn, &project, auth_user.as_deref()).await?;
+    }
+    let mut select =
+        nodelink::Entity::find_live().filter(nodelink::Column::ProjectId.eq(project_id));
+    if let Some(active_at) = query.active_at {
+        select = select
+            .filter(
+                Condition::any()
+                    .add(nodelink::Column::ValidFrom.is_null())
+                    .add(nodelink::Column::ValidFrom.lte(active_at)),
+            )
+            .filter(
+                Condition::any()
+                    .add(nodelink::Column::ValidTo.is_null())
+                    .add(nodelink::Column::ValidTo.gte(active_at)),
+            );
+    }
+    let nodelinks = select.all(conn).await?;
+
+    Ok(Json(nodelinks))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/node/{id}/nodelinks",
+    tag = "links",
+    operation_id = "get_nodelinks_by_node",
+    params(
+        ("id" = Uuid, Path, description = "Node ID")
+    ),
+    responses(
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Node not found", body = ErrorResponse),
+        (status = OK, description = "Links with the node on either end", body = Vec<nodelink::Model>)
+    )
+)]
+pub async fn get_nodelinks_by_node(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<Vec<nodelink::Model>>, WebError> {
+    let conn = &state.read().await.conn;
+    check_node_access(conn, id, auth_user.as_deref()).await?;
+    if node::Entity::find_live()
+        .filter(node::Column::Id.eq(id))
+        .one(conn)
+        .await?
+        .is_none()
+    {
+        return Err(WebError::not_found(format!("Node {} not found", id)));
+    }
+
+    let nodelinks = nodelink::Entity::find_live()
+        .filter(
+            Condition::any()
+                .add(nodelink::Column::Left.eq(id))
+                .add(nodelink::Column::Right.eq(id)),
+        )
+        .all(conn)
+        .await?;
+
+    Ok(Json(nodelinks))
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteNodeQuery {
+    /// Remove the node and its attachments for good, rather than moving it to the trash
+    #[serde(default)]
+    pub purge: bool,
+}
+
+/// Move a node to the trash, or with `?purge=true` remove it and its attachments for good
+///
+/// Links to a node in the trash are hidden rather than deleted, so they come back with it.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/node/{id}",
+    tag = "nodes",
+    operation_id = "delete_node",
+    params(
+        ("id" = Uuid, Path, description = "Node ID"),
+        DeleteNodeQuery
+    ),
+    responses(
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = OK, description = "Node moved to the trash, or purged", body = String),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Node not found, or already in the trash without purge")
+    )
+)]
+pub async fn delete_node(
+    Path(id): Path<Uuid>,
+    Query(query): Query<DeleteNodeQuery>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<String>, WebError> {
+    let txn = state.read().await.begin().await?;
+    let mut select = node::Entity::find_by_id(id);
+    if !query.purge {
+        select = select.filter(node::Column::DeletedAt.is_null());
+    }
+    let Some(db_node) = select.one(&txn).await? else {
+        debug!(node_id = id.to_string(), "Node not found for deletion");
+        return Err(WebError::not_found(format!("Node {} not found", id)));
+    };
+    find_accessible_project(&txn, db_node.project_id, auth_user.as_deref()).await?;
+    node_history::ActiveModel::record(
+        &db_node,
+        match query.purge {
+            true => NodeChange::Purge,
+            false => NodeChange::Delete,
+        },
+        AuthUser::created_by(auth_user.as_deref()),
+    )?
+    .insert(&txn)
+    .await?;
+
+    if !query.purge {
+        let mut db_node = db_node.into_active_model();
+        db_node.deleted_at = Set(Some(Utc::now()));
+        db_node.update(&txn).await?;
+        txn.commit().await?;
+        debug!(node_id = id.to_string(), "Moved node to the trash");
+        return Ok(Json(format!("Node {id} moved to the trash")));
+    }
+
+    let (attachments, attachment_bytes, rows_affected) = purge_node(&txn, id).await?;
+    match rows_affected {
+        0 => {
+            debug!(node_id = id.to_string(), "Node not found for deletion");
+            Err(WebError::not_found(format!("Node {} not found", id)))
+        }
+        _ => {
+            txn.commit().await?;
+            state.read().await.quota.invalidate([
+                QuotaKind::NodesPerProject(db_node.project_id),
+                QuotaKind::NodelinksPerProject(db_node.project_id),
+                QuotaKind::AttachmentBytes,
+            ]);
+            if attachments > 0 {
+                info!(
+                    node_id = id.to_string(),
+                    attachments,
+                    attachment_bytes = attachment_bytes.unwrap_or_default(),
+                    "Deleted node and its attachments"
+                );
+            } else {
+                debug!(node_id = id.to_string(), "Deleted node");
+            }
+            Ok(Json(format!("Node {id} deleted successfully")))
+        }
+    }
+}
+
+/// Remove a node and its attachments for good, returning how many attachments went, their
+/// stored bytes, and how many nodes were deleted
+pub(crate) async fn purge_node(
+    conn: &impl ConnectionTrait,
+    id: Uuid,
+) -> Result<(i64, Option<i64>, u64), DbErr> {
+    // ON DELETE CASCADE would do this, but not in a database which has had foreign keys off
+    let (attachments, attachment_bytes): (i64, Option<i64>) = attachment::Entity::find()
+        .select_only()
+        .column_as(attachment::Column::Id.count(), "count")
+        .column_as(
+            Expr::expr(Func::sum(
+                Func::cust(Alias::new("LENGTH"))
+                    .arg(Expr::col((attachment::Entity, attachment::Column::Data))),
+            )),
+            "bytes",
+        )
+        .filter(attachment::Column::NodeId.eq(id))
+        .into_tuple()
+        .one(conn)
+        .await?
+        .unwrap_or_default();
+    attachment::Entity::delete_many()
+        .filter(attachment::Column::NodeId.eq(id))
+        .exec(conn)
+        .await?;
+
+    let res = node::Entity::delete_by_id(id).exec(conn).await?;
+    Ok((attachments, attachment_bytes, res.rows_affected))
+}
+
+/// Take a node out of the trash, its links come back with it
+///
+/// Restoring a node which isn't in the trash does nothing.
+#[utoipa::path(
+    post,
+    path = "/api/v1/node/{id}/restore",
+    tag = "nodes",
+    operation_id = "restore_node",
+    params(
+        ("id" = Uuid, Path, description = "Node ID")
+    ),
+    responses(
+        (status = BAD_REQUEST, description = "Invalid path parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "No access to the node's project", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Node not found", body = ErrorResponse),
+        (status = OK, description = "The restored node", body = node::Model)
+    )
+)]
+pub async fn restore_node(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<node::Model>, WebError> {
+    let txn = state.read().await.begin().await?;
+    let node = node::Entity::find_by_id(id)
+        .one(&txn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Node {} not found", id)))?;
+    check_node_access(&txn, id, auth_user.as_deref()).await?;
+    if node.deleted_at.is_none() {
+        return Ok(Json(node));
+    }
+    node_history::ActiveModel::record(
+        &node,
+        NodeChange::Restore,
+        AuthUser::created_by(auth_user.as_deref()),
+    )?
+    .insert(&txn)
+    .await?;
+    let mut node = node.into_active_model();
+    node.deleted_at = Set(None);
+    let node = node.update(&txn).await?;
+    txn.commit().await?;
+    info!(node_id = id.to_string(), "Restored node from the trash");
+    Ok(Json(node))
+}
+
+/// Ensure `auth_user` can access the project a node is in, or going by its history was in
+/// before it was purged
+async fn check_node_history_access(
+    conn: &impl ConnectionTrait,
+    id: Uuid,
+    auth_user: Option<&AuthUser>,
+) -> Result<(), WebError> {
+    let project_id: Option<Uuid> = match node::Entity::find_by_id(id)
+        .select_only()
+        .column(node::Column::ProjectId)
+        .into_tuple()
+        .one(conn)
+        .await?
+    {
+        Some(project_id) => Some(project_id),
+        None => node_history::Entity::find()
+            .filter(node_history::Column::NodeId.eq(id))
+            .order_by_desc(node_history::Column::ChangedAt)
+            .one(conn)
+            .await?
+            .map(|entry| entry.previous_node())
+            .transpose()?
+            .map(|node| node.project_id),
+    };
+    // a deleted project took the access rules with it
+    if let Some(project) = match project_id {
+        Some(project_id) => project::Entity::find_by_id(project_id).one(conn).await?,
+        None => None,
+    } {
+        check_project_access(conn, &project, auth_user).await?;
+    }
+    Ok(())
+}
+
+/// Every change to a node's type, oldest first
+///
+/// Worked out from the node's history, so it's still there after the node is purged.
+#[utoipa::path(
+    get,
+    path = "/api/v1/node/{id}/type-history",
+    tag = "nodes",
+    operation_id = "get_node_type_history",
+    params(
+        ("id" = Uuid, Path, description = "Node ID")
+    ),
+    responses(
+        (status = BAD_REQUEST, description = "Invalid path parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Node not found", body = ErrorResponse),
+        (status = OK, description = "Type changes, oldest first", body = Vec<NodeTypeChange>)
+    )
+)]
+pub async fn get_node_type_history(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<Vec<NodeTypeChange>>, WebError> {
+    let conn = &state.read().await.conn;
+    check_node_history_access(conn, id, auth_user.as_deref()).await?;
+    let current = node::Entity::find_by_id(id).one(conn).await?;
+    let history = node_history::Entity::find()
+        .filter(node_history::Column::NodeId.eq(id))
+        .order_by_asc(node_history::Column::ChangedAt)
+        .order_by_asc(node_history::Column::Id)
+        .all(conn)
+        .await?;
+    if current.is_none() && history.is_empty() {
+        return Err(WebError::not_found(format!("Node {} not found", id)));
+    }
 
-    let node = node::ActiveModel::from(node);
-    let res = node
-        .insert(&txn)
-        .await
-        .inspect_err(|err| error!(error=?err, "Failed to insert node"))?;
-    debug!("Saved node: {:?}", res);
-    let model = res
-        .try_into_model()
-        .inspect_err(|err| error!("Failed to convert inserted node to model: {:?}", err))?;
-    txn.commit().await.inspect_err(
-        |err| error!(error=?err, node=?model, "Failed to commit transaction for new node"),
-    )?;
-    Ok(Json(model))
+    // each entry is the node before a change, so the type after it is in the next entry,
+    // or the node itself for the latest one
+    let mut types = history
+        .iter()
+        .map(|entry| entry.previous_node().map(|node| node.node_type))
+        .collect::<Result<Vec<_>, _>>()?;
+    types.extend(current.map(|node| node.node_type));
+    let changes = history
+        .iter()
+        .zip(types.windows(2))
+        .filter(|(_, pair)| pair[0] != pair[1])
+        .map(|(entry, pair)| NodeTypeChange {
+            id: entry.id,
+            node_id: id,
+            from_type: pair[0],
+            to_type: pair[1],
+            changed: entry.changed_at,
+        })
+        .collect();
+    Ok(Json(changes))
 }
 
+/// Earlier versions of a node from before each update or deletion, newest first
+///
+/// The history of a purged node is kept, so this only 404s for a node which never existed.
 #[utoipa::path(
-    post,
-    path = "/api/v1/nodelink",
-    request_body = nodelink::Model,
+    get,
+    path = "/api/v1/node/{id}/history",
+    tag = "nodes",
+    operation_id = "get_node_history",
+    params(
+        ("id" = Uuid, Path, description = "Node ID"),
+        PaginationQuery
+    ),
     responses(
-        (status = OK, description = "One result ok", body = nodelink::Model)
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Node not found", body = ErrorResponse),
+        (status = OK, description = "One page of history, newest first", body = PaginatedResponse<NodeHistoryEntry>)
     )
 )]
-pub async fn post_nodelink(
+pub async fn get_node_history(
+    Path(id): Path<Uuid>,
     State(state): State<SharedState>,
-    Json(nodelink): Json<nodelink::Model>,
-) -> Result<Json<nodelink::Model>, WebError> {
-    let txn = state.read().await.conn.begin().await?;
-
-    // Validate that the project exists before saving the nodelink
-    match nodelink::Entity::find_by_id(nodelink.id).one(&txn).await? {
-        Some(_) => {
-            // throw an error because it already exists
-            Err(WebError {
-                status: StatusCode::CONFLICT,
-                message: "Nodelink already exists".into(),
-            })
-        }
-        None => {
-            // Project doesn't exist
-            let nodelink = nodelink.into_active_model();
-            let res = nodelink.insert(&txn).await?;
-            debug!("Saved nodelink: {:?}", res);
-            let model = res.try_into_model()?;
-            txn.commit().await?;
-            Ok(Json(model))
-        }
+    Query(pagination): Query<PaginationQuery>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<PaginatedResponse<NodeHistoryEntry>>, WebError> {
+    pagination.validate()?;
+    let conn = &state.read().await.conn;
+    check_node_history_access(conn, id, auth_user.as_deref()).await?;
+    let paginator = node_history::Entity::find()
+        .filter(node_history::Column::NodeId.eq(id))
+        .order_by_desc(node_history::Column::ChangedAt)
+        .order_by_desc(node_history::Column::Id)
+        .paginate(conn, pagination.page_size);
+    let total_count = paginator.num_items().await?;
+    if total_count == 0 && node::Entity::find_by_id(id).one(conn).await?.is_none() {
+        return Err(WebError::not_found(format!("Node {} not found", id)));
     }
+    let items = paginator
+        .fetch_page(pagination.page - 1)
+        .await?
+        .into_iter()
+        .map(NodeHistoryEntry::from)
+        .collect();
+    Ok(Json(PaginatedResponse::new(
+        &pagination,
+        total_count,
+        items,
+    )))
 }
 
+/// Fold one node into another in the same project, moving its links, attachments and notes
+///
+/// Both nodes get a history entry, the merged node a `merge` and the target an `update`.
 #[utoipa::path(
-    get,
-    path = "/api/v1/project/{project_id}/nodelinks",
+    post,
+    path = "/api/v1/node/{id}/merge/{target_id}",
+    tag = "nodes",
+    operation_id = "merge_nodes",
+    params(
+        ("id" = Uuid, Path, description = "Node to merge and delete"),
+        ("target_id" = Uuid, Path, description = "Node to merge into")
+    ),
     responses(
-        (status = OK, description = "One result ok", body = Vec<nodelink::Model>)
+        (status = BAD_REQUEST, description = "Invalid path parameter, or nodes which can't be merged", body = ErrorResponse),
+        (status = FORBIDDEN, description = "The nodes belong to another user's project", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Node not found", body = ErrorResponse),
+        (status = OK, description = "The updated target node", body = node::Model)
     )
 )]
-pub async fn get_nodelinks_by_project(
-    Path(project_id): Path<Uuid>,
+pub async fn merge_nodes(
+    Path((id, target_id)): Path<(Uuid, Uuid)>,
     State(state): State<SharedState>,
-) -> Result<Json<Vec<nodelink::Model>>, WebError> {
-    let nodelinks = nodelink::Entity::find()
-        .filter(nodelink::Column::ProjectId.eq(project_id))
-        .all(&state.read().await.conn)
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<node::Model>, WebError> {
+    if id == Uuid::nil() || target_id == Uuid::nil() {
+        debug!("Attempted to merge node with nil UUID");
+        return Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            "Cannot merge node with nil UUID",
+        ));
+    }
+    if id == target_id {
+        return Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            "Can't merge a node into itself",
+        ));
+    }
+
+    let txn = state.read().await.begin().await?;
+    let source = node::Entity::find_live()
+        .filter(node::Column::Id.eq(id))
+        .one(&txn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Node {} not found", id)))?;
+    let target = node::Entity::find_live()
+        .filter(node::Column::Id.eq(target_id))
+        .one(&txn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Node {} not found", target_id)))?;
+    for node_id in [id, target_id] {
+        check_node_access(&txn, node_id, auth_user.as_deref()).await?;
+    }
+    if source.project_id != target.project_id {
+        return Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            "Can't merge nodes from different projects",
+        ));
+    }
+    let changed_by = AuthUser::created_by(auth_user.as_deref());
+    node_history::ActiveModel::record(&source, NodeChange::Merge, changed_by.clone())?
+        .insert(&txn)
+        .await?;
+    node_history::ActiveModel::record(&target, NodeChange::Update, changed_by)?
+        .insert(&txn)
         .await?;
 
-    Ok(Json(nodelinks))
+    // links between the two would become loops, and others may repeat a link the target has
+    let links = nodelink::Entity::find()
+        .filter(
+            Condition::any()
+                .add(nodelink::Column::Left.is_in([id, target_id]))
+                .add(nodelink::Column::Right.is_in([id, target_id])),
+        )
+        .order_by_asc(nodelink::Column::Id)
+        .all(&txn)
+        .await?;
+    let repoint = |node_id: Uuid| if node_id == id { target_id } else { node_id };
+    let mut seen: HashSet<(Uuid, Uuid, LinkType)> = links
+        .iter()
+        .filter(|link| link.left != id && link.right != id)
+        .map(|link| (link.left, link.right, link.linktype))
+        .collect();
+    let mut dropped = Vec::new();
+    for link in links
+        .iter()
+        .filter(|link| link.left == id || link.right == id)
+    {
+        let (left, right) = (repoint(link.left), repoint(link.right));
+        let reversed =
+            link.linktype == LinkType::Omni && seen.contains(&(right, left, link.linktype));
+        if left == right || reversed || !seen.insert((left, right, link.linktype)) {
+            dropped.push(link.id);
+        }
+    }
+    if !dropped.is_empty() {
+        nodelink::Entity::delete_many()
+            .filter(nodelink::Column::Id.is_in(dropped))
+            .exec(&txn)
+            .await?;
+    }
+    let mut moved_nodelinks = 0;
+    for column in [nodelink::Column::Left, nodelink::Column::Right] {
+        moved_nodelinks += nodelink::Entity::update_many()
+            .col_expr(column, Expr::value(target_id))
+            .filter(column.eq(id))
+            .exec(&txn)
+            .await?
+            .rows_affected;
+    }
+    let moved_attachments = attachment::Entity::update_many()
+        .col_expr(attachment::Column::NodeId, Expr::value(target_id))
+        .filter(attachment::Column::NodeId.eq(id))
+        .exec(&txn)
+        .await?
+        .rows_affected;
+
+    let notes = crate::merge::merge_notes(target.notes.clone(), source.notes.clone());
+    let mut target = target.into_active_model();
+    target.notes = Set(notes);
+    target.updated = Set(Utc::now());
+    let target = target.update(&txn).await?;
+    node::Entity::delete_by_id(id).exec(&txn).await?;
+    txn.commit().await?;
+    state.read().await.quota.invalidate([
+        QuotaKind::NodesPerProject(source.project_id),
+        QuotaKind::NodelinksPerProject(source.project_id),
+    ]);
+
+    info!(
+        node_id = id.to_string(),
+        target_id = target_id.to_string(),
+        moved_nodelinks,
+        moved_attachments,
+        "Merged nodes"
+    );
+    Ok(Json(target))
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CopyNodeQuery {
+    /// Project to copy the node into, defaults to the node's own project
+    #[serde(default)]
+    pub project_id: Option<Uuid>,
+    /// Copy the node's attachments too
+    #[serde(default)]
+    pub include_attachments: bool,
 }
 
+/// Copy a node, and optionally its attachments, into this or another project
+///
+/// The copy gets a new ID and is recorded as created by the caller. Links aren't copied.
 #[utoipa::path(
-    delete,
-    path = "/api/v1/node/{id}",
+    post,
+    path = "/api/v1/node/{id}/copy",
+    tag = "nodes",
+    operation_id = "copy_node",
+    params(
+        ("id" = Uuid, Path, description = "Node to copy"),
+        CopyNodeQuery
+    ),
     responses(
-        (status = OK, description = "Node deleted successfully", body = String),
-        (status = NOT_FOUND, description = "Node not found")
+        (status = OK, description = "The new node", body = node::Model),
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "The node or target project belongs to another user, or a quota is exceeded", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Node or target project not found", body = ErrorResponse),
+        (status = UNPROCESSABLE_ENTITY, description = "The node breaks a value policy rule", body = ErrorResponse)
     )
 )]
-pub async fn delete_node(
+pub async fn copy_node(
     Path(id): Path<Uuid>,
+    Query(query): Query<CopyNodeQuery>,
     State(state): State<SharedState>,
-) -> Result<Json<String>, WebError> {
-    let res = node::Entity::delete_by_id(id)
-        .exec(&state.read().await.conn)
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<(HeaderMap, Json<node::Model>), WebError> {
+    let reader = state.read().await;
+    let txn = reader.begin().await?;
+
+    let source = node::Entity::find_live()
+        .filter(node::Column::Id.eq(id))
+        .one(&txn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Node {} not found", id)))?;
+    check_node_access(&txn, id, auth_user.as_deref()).await?;
+    let project_id = query.project_id.unwrap_or(source.project_id);
+    let target_project = project::Entity::find_by_id(project_id)
+        .one(&txn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Project {} not found", project_id)))?;
+    check_project_access(&txn, &target_project, auth_user.as_deref()).await?;
+
+    let attachments = if query.include_attachments {
+        let mut attachments = source.find_related(attachment::Entity).all(&txn).await?;
+        attachment::load_shared_data(&txn, &mut attachments).await?;
+        attachments
+    } else {
+        Vec::new()
+    };
+    let attachment_bytes: u64 = attachments
+        .iter()
+        .map(|attachment| attachment.size.max(0) as u64)
+        .sum();
+    let quota_kind = QuotaKind::NodesPerProject(project_id);
+    reader
+        .quota
+        .check(&txn, quota_kind, 1, auth_user.as_deref())
         .await?;
-    match res.rows_affected {
-        0 => {
-            debug!(node_id = id.to_string(), "Node not found for deletion");
-            Err(WebError::not_found(format!("Node {} not found", id)))
-        }
-        _ => {
-            debug!(node_id = id.to_string(), "Deleted node");
-            Ok(Json(format!("Node {id} deleted successfully")))
+    if !attachments.is_empty() {
+        reader
+            .quota
+            .check(
+                &txn,
+                QuotaKind::AttachmentBytes,
+                attachment_bytes,
+                auth_user.as_deref(),
+            )
+            .await?;
+    }
+
+    let created_by = AuthUser::created_by(auth_user.as_deref());
+    let now = Utc::now();
+    let mut copy = node::Model {
+        id: Uuid::new_v4(),
+        project_id,
+        updated: now,
+        created_by: created_by.clone(),
+        created: Some(now),
+        ..source
+    };
+    reader.value_policy.apply(&mut copy)?;
+    let copy = node::ActiveModel::from(copy)
+        .insert(&txn)
+        .await
+        .inspect_err(|err| error!(error=?err, "Failed to insert copied node"))?;
+
+    // data is copied as stored, so it keeps its codec and doesn't need compressing again
+    let attachment_count = attachments.len();
+    for attachment in attachments {
+        attachment::Model {
+            id: Uuid::new_v4(),
+            node_id: copy.id,
+            created: now,
+            created_by: created_by.clone(),
+            ..attachment
         }
+        .into_active_model()
+        .insert(&txn)
+        .await?;
     }
+    txn.commit().await?;
+    info!(
+        node_id = id.to_string(),
+        copy_id = copy.id.to_string(),
+        project_id = project_id.to_string(),
+        attachments = attachment_count,
+        "Copied node"
+    );
+
+    let warning = reader.quota.record(quota_kind, 1);
+    let attachment_warning = match attachment_count {
+        0 => None,
+        _ => reader
+            .quota
+            .record(QuotaKind::AttachmentBytes, attachment_bytes),
+    };
+    Ok((warning_headers(warning.or(attachment_warning)), Json(copy)))
 }
 
 #[utoipa::path(
     put,
     path = "/api/v1/node/{id}",
+    tag = "nodes",
+    operation_id = "update_node",
+    params(
+        ("id" = Uuid, Path, description = "Node ID")
+    ),
     responses(
-        (status = OK, description = "One result ok", body = node::Model)
+        (status = BAD_REQUEST, description = "Invalid path or query parameter, the value doesn't fit the node type, or notes over --max-notes-bytes", body = ErrorResponse),
+        (status = OK, description = "One result ok", body = node::Model),
+        (status = UNPROCESSABLE_ENTITY, description = "A node breaks a value policy rule", body = ErrorResponse)
     )
 )]
 pub async fn update_node(
     Path(id): Path<Uuid>,
     State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
     Json(mut node): Json<node::Model>,
-) -> Result<Json<node::Model>, WebError> {
-    let txn = state.read().await.conn.begin().await?;
+) -> Result<(HeaderMap, Json<node::Model>), WebError> {
+    let reader = state.read().await;
+    let txn = reader.begin().await?;
 
     // Clean URL values before updating
     if node.node_type == NodeType::Url {
         node.value = clean_url_value(&node.value);
     }
+    reader.value_policy.apply(&mut node)?;
+    validate_node_value(&node)?;
+    let truncated = reader.notes_limit.apply("notes", &mut node.notes)?;
 
     // Verify node exists first
-    match node::Entity::find_by_id(id).one(&txn).await? {
+    match node::Entity::find_by_id(id)
+        .filter(node::Column::DeletedAt.is_null())
+        .one(&txn)
+        .await?
+    {
         Some(db_node) => {
+            find_accessible_project(&txn, db_node.project_id, auth_user.as_deref()).await?;
             // Update the node ID to match the path parameter
             debug!("Updating node {}: {:?}", id, node);
+            node_history::ActiveModel::record(
+                &db_node,
+                NodeChange::Update,
+                AuthUser::created_by(auth_user.as_deref()),
+            )?
+            .insert(&txn)
+            .await?;
+            if db_node.node_type != node.node_type {
+                info!(
+                    node_id = id.to_string(),
+                    from = db_node.node_type.as_ref(),
+                    to = node.node_type.as_ref(),
+                    "Node type changed"
+                );
+            }
             let mut db_node = db_node.into_active_model();
             db_node.node_type = Set(node.node_type);
             db_node.display = Set(node.display);
@@ -396,7 +2365,11 @@ pub async fn update_node(
             let res = db_node.update(&txn).await?;
             txn.commit().await?;
 
-            Ok(Json(res.try_into_model()?))
+            let headers = match truncated {
+                true => truncated_headers(&["notes"]),
+                false => HeaderMap::new(),
+            };
+            Ok((headers, Json(res.try_into_model()?)))
         }
         None => {
             debug!("Node {} not found for update", id);
@@ -408,49 +2381,69 @@ pub async fn update_node(
 #[utoipa::path(
     delete,
     path = "/api/v1/nodelink/{id}",
+    tag = "links",
+    operation_id = "delete_nodelink",
+    params(
+        ("id" = Uuid, Path, description = "Nodelink ID")
+    ),
     responses(
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
         (status = OK, description = "Nodelink deleted successfully", body = ()),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
         (status = NOT_FOUND, description = "Nodelink not found")
     )
 )]
 pub async fn delete_nodelink(
     Path(id): Path<Uuid>,
     State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
 ) -> Result<Json<()>, WebError> {
-    let result = nodelink::Entity::delete_by_id(id)
-        .exec(&state.read().await.conn)
-        .await?;
-
-    match result.rows_affected {
-        0 => {
-            debug!(
-                nodelink_id = id.to_string(),
-                "Nodelink not found for deletion"
-            );
-            Err(WebError::not_found(format!("Nodelink {} not found", id)))
-        }
-        _ => {
-            debug!(nodelink_id = id.to_string(), "Deleted nodelink");
-            Ok(Json(()))
-        }
-    }
+    let reader = state.read().await;
+    let Some(nodelink) = nodelink::Entity::find_by_id(id).one(&reader.conn).await? else {
+        debug!(
+            nodelink_id = id.to_string(),
+            "Nodelink not found for deletion"
+        );
+        return Err(WebError::not_found(format!("Nodelink {} not found", id)));
+    };
+    let project_id = nodelink.project_id;
+    find_accessible_project(&reader.conn, project_id, auth_user.as_deref()).await?;
+    nodelink.delete(&reader.conn).await?;
+    reader
+        .quota
+        .invalidate([QuotaKind::NodelinksPerProject(project_id)]);
+    debug!(nodelink_id = id.to_string(), "Deleted nodelink");
+    Ok(Json(()))
 }
 
 /// PUT handler to update an existing project
 #[utoipa::path(
     put,
     path = "/api/v1/project/{id}",
+    tag = "projects",
+    operation_id = "update_project",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
     request_body = project::Model,
     responses(
+        (status = BAD_REQUEST, description = "Invalid path or query parameter, or a description over --max-notes-bytes", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
         (status = OK, description = "One result ok", body = project::Model)
     )
 )]
 pub async fn update_project(
     Path(id): Path<Uuid>,
     State(state): State<SharedState>,
-    Json(project): Json<project::Model>,
-) -> Result<Json<project::Model>, WebError> {
-    let txn = state.read().await.conn.begin().await?;
+    auth_user: Option<Extension<AuthUser>>,
+    Json(mut project): Json<project::Model>,
+) -> Result<(HeaderMap, Json<project::Model>), WebError> {
+    normalize_project_tags(&mut project)?;
+    let reader = state.read().await;
+    let truncated = reader
+        .notes_limit
+        .apply("description", &mut project.description)?;
+    let txn = reader.begin().await?;
     // Verify project exists first
     match project::Entity::find_by_id(id)
         .one(&txn)
@@ -458,6 +2451,7 @@ pub async fn update_project(
         .inspect_err(|err| error!("Failed to find project {}: {:?}", id, err))?
     {
         Some(db_project) => {
+            check_project_access(&txn, &db_project, auth_user.as_deref()).await?;
             // Update the project ID to match the path parameter
             debug!("Updating project {}: {:?}", id, project);
             let mut db_project = db_project.into_active_model();
@@ -468,7 +2462,11 @@ pub async fn update_project(
             debug!("db_project.is_changed(): {}", db_project.is_changed());
             let res = db_project.update(&txn).await?;
             txn.commit().await?;
-            Ok(Json(res.try_into_model()?))
+            let headers = match truncated {
+                true => truncated_headers(&["description"]),
+                false => HeaderMap::new(),
+            };
+            Ok((headers, Json(res.try_into_model()?)))
         }
         None => {
             debug!("Project {} not found for update", id);
@@ -477,31 +2475,198 @@ pub async fn update_project(
     }
 }
 
+async fn set_project_pinned(
+    state: &SharedState,
+    id: Uuid,
+    pinned: bool,
+    auth_user: Option<&AuthUser>,
+) -> Result<Json<project::Model>, WebError> {
+    let conn = &state.read().await.conn;
+    match project::Entity::find_by_id(id).one(conn).await? {
+        Some(db_project) => {
+            check_project_access(conn, &db_project, auth_user).await?;
+            let mut db_project = db_project.into_active_model();
+            db_project.pinned = Set(pinned);
+            let res = db_project
+                .update(conn)
+                .await
+                .inspect_err(|err| error!("Failed to set pinned on project {}: {:?}", id, err))?;
+            debug!(project_id = id.to_string(), pinned, "Updated project pin");
+            Ok(Json(res))
+        }
+        None => Err(WebError::not_found(format!("Project {} not found", id))),
+    }
+}
+
+/// Pin a project so it's listed first
+#[utoipa::path(
+    post,
+    path = "/api/v1/project/{id}/pin",
+    tag = "projects",
+    operation_id = "pin_project",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    responses(
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = OK, description = "Project pinned", body = project::Model),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found")
+    )
+)]
+pub async fn pin_project(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<project::Model>, WebError> {
+    set_project_pinned(&state, id, true, auth_user.as_deref()).await
+}
+
+/// Unpin a project
+#[utoipa::path(
+    post,
+    path = "/api/v1/project/{id}/unpin",
+    tag = "projects",
+    operation_id = "unpin_project",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    responses(
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = OK, description = "Project unpinned", body = project::Model),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found")
+    )
+)]
+pub async fn unpin_project(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<project::Model>, WebError> {
+    set_project_pinned(&state, id, false, auth_user.as_deref()).await
+}
+
+async fn set_project_archived(
+    state: &SharedState,
+    id: Uuid,
+    is_archived: bool,
+    auth_user: Option<&AuthUser>,
+) -> Result<Json<project::Model>, WebError> {
+    if is_archived && id == Uuid::nil() {
+        debug!("Attempted to archive project with nil UUID");
+        return Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            "Cannot archive project with nil UUID",
+        ));
+    }
+    let conn = &state.read().await.conn;
+    match project::Entity::find_by_id(id).one(conn).await? {
+        Some(db_project) => {
+            check_project_access(conn, &db_project, auth_user).await?;
+            let mut db_project = db_project.into_active_model();
+            db_project.is_archived = Set(is_archived);
+            let res = db_project.update(conn).await.inspect_err(|err| {
+                error!("Failed to set is_archived on project {}: {:?}", id, err)
+            })?;
+            info!(
+                project_id = id.to_string(),
+                is_archived, "Updated project archiving"
+            );
+            Ok(Json(res))
+        }
+        None => Err(WebError::not_found(format!("Project {} not found", id))),
+    }
+}
+
+/// Archive a project, hiding it from the project list without deleting anything
+#[utoipa::path(
+    patch,
+    path = "/api/v1/project/{id}/archive",
+    tag = "projects",
+    operation_id = "archive_project",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    responses(
+        (status = BAD_REQUEST, description = "Invalid path parameter, or the Inbox project", body = ErrorResponse),
+        (status = OK, description = "Project archived", body = project::Model),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found")
+    )
+)]
+pub async fn archive_project(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<project::Model>, WebError> {
+    set_project_archived(&state, id, true, auth_user.as_deref()).await
+}
+
+/// Bring an archived project back into the project list
+#[utoipa::path(
+    patch,
+    path = "/api/v1/project/{id}/unarchive",
+    tag = "projects",
+    operation_id = "unarchive_project",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    responses(
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = OK, description = "Project unarchived", body = project::Model),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found")
+    )
+)]
+pub async fn unarchive_project(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<project::Model>, WebError> {
+    set_project_archived(&state, id, false, auth_user.as_deref()).await
+}
+
 /// DELETE handler to delete a project and cascade to nodes/nodelinks
 #[utoipa::path(
     delete,
     path = "/api/v1/project/{id}",
+    tag = "projects",
+    operation_id = "delete_project",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
     responses(
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
         (status = OK, description = "Project deleted successfully"),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
         (status = NOT_FOUND, description = "Project not found")
     )
 )]
 pub async fn delete_project(
     Path(id): Path<Uuid>,
     State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
 ) -> Result<String, WebError> {
     if id == Uuid::nil() {
         debug!("Attempted to delete project with nil UUID");
-        return Err(WebError {
-            status: StatusCode::BAD_REQUEST,
-            message: "Cannot delete project with nil UUID".to_string(),
-        });
+        return Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            "Cannot delete project with nil UUID",
+        ));
     }
 
-    let res = project::Entity::delete_by_id(id)
-        .exec(&state.read().await.conn)
-        .await?;
+    let reader = state.read().await;
+    if let Some(project) = project::Entity::find_by_id(id).one(&reader.conn).await? {
+        check_project_access(&reader.conn, &project, auth_user.as_deref()).await?;
+    }
+    let res = project::Entity::delete_by_id(id).exec(&reader.conn).await?;
     if res.rows_affected > 0 {
+        reader.quota.invalidate([
+            QuotaKind::Projects,
+            QuotaKind::NodesPerProject(id),
+            QuotaKind::NodelinksPerProject(id),
+            QuotaKind::AttachmentBytes,
+        ]);
         info!(
             rows_affected = res.rows_affected,
             id = id.to_string(),
@@ -514,7 +2679,7 @@ pub async fn delete_project(
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct ProjectExport {
     pub project: project::Model,
     pub nodes: Vec<node::Model>,
@@ -522,22 +2687,32 @@ pub struct ProjectExport {
     pub exported_at: chrono::DateTime<Utc>,
     pub version: String,
     pub attachments: Vec<attachment::Model>,
+    /// Set when values, attachments and project metadata have been stripped, see [crate::redact]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub redacted: bool,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ExportQuery {
     #[serde(default)]
     pub include_attachments: bool,
+    #[serde(default)]
+    pub redact: bool,
 }
 
 #[utoipa::path(
     get,
     path = "/api/v1/project/{id}/export",
+    tag = "exports",
+    operation_id = "export_project",
     params(
         ("id" = Uuid, Path, description = "Project ID to export"),
-        ("include_attachments" = bool, Query, description = "Whether to include attachments in the export")
+        ("include_attachments" = bool, Query, description = "Whether to include attachments in the export"),
+        ("redact" = bool, Query, description = "Replace values with placeholders and strip attachments and project metadata")
     ),
     responses(
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
         (status = OK, description = "One result ok", body = ProjectExport)
     )
 )]
@@ -545,20 +2720,44 @@ pub async fn export_project(
     Path(id): Path<Uuid>,
     Query(query): Query<ExportQuery>,
     State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
 ) -> Result<Json<ProjectExport>, WebError> {
-    let txn = state.read().await.conn.begin().await?;
+    let txn = state.read().await.begin().await?;
 
     // Fetch the project
-    let project = match project::Entity::find_by_id(id).one(&txn).await? {
+    let mut project = match project::Entity::find_by_id(id).one(&txn).await? {
         Some(project) => project,
         None => return Err(WebError::not_found(format!("Project {} not found", id))),
     };
+    check_project_access(&txn, &project, auth_user.as_deref()).await?;
 
     // Fetch nodes
-    let nodes = project.find_related(node::Entity).all(&txn).await?;
+    let mut nodes = project
+        .find_related(node::Entity)
+        .filter(node::Column::DeletedAt.is_null())
+        .all(&txn)
+        .await?;
 
     // Fetch nodelinks
-    let nodelinks = project.find_related(nodelink::Entity).all(&txn).await?;
+    let mut nodelinks = project
+        .find_related(nodelink::Entity)
+        .filter(nodelink::ends_live())
+        .all(&txn)
+        .await?;
+
+    if query.redact {
+        let mut attachments = Vec::new();
+        redact(&mut project, &mut nodes, &mut nodelinks, &mut attachments);
+        return Ok(Json(ProjectExport {
+            project,
+            nodes,
+            nodelinks,
+            exported_at: Utc::now(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            attachments,
+            redacted: true,
+        }));
+    }
 
     // Optionally fetch attachments
     // Get all node IDs for this project
@@ -566,185 +2765,772 @@ pub async fn export_project(
 
     // Construct export object
     if query.include_attachments {
+        let mut attachments = attachment::Entity::find()
+            .filter(attachment::Column::NodeId.is_in(node_ids))
+            .all(&txn)
+            .await?;
+        attachment::load_shared_data(&txn, &mut attachments).await?;
         Ok(Json(ProjectExport {
             project,
             nodes,
             nodelinks,
             exported_at: Utc::now(),
             version: env!("CARGO_PKG_VERSION").to_string(),
-            attachments: attachment::Entity::find()
-                .filter(attachment::Column::NodeId.is_in(node_ids))
-                .all(&txn)
-                .await?,
+            attachments,
+            redacted: false,
         }))
     } else {
-        let attachments: Vec<attachment::Model> = attachment::attachment_list(id)
-            .all(&txn)
-            .await?
-            .into_iter()
-            .map(attachment::Model::from)
-            .collect();
+        Ok(Json(
+            project_export_without_data(&txn, project, nodes, nodelinks).await?,
+        ))
+    }
+}
+
+/// A [ProjectExport] listing attachments without their data
+pub(crate) async fn project_export_without_data(
+    conn: &impl ConnectionTrait,
+    project: project::Model,
+    nodes: Vec<node::Model>,
+    nodelinks: Vec<nodelink::Model>,
+) -> Result<ProjectExport, DbErr> {
+    let attachments: Vec<attachment::Model> = attachment::attachment_list(project.id)
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(attachment::Model::from)
+        .collect();
+
+    Ok(ProjectExport {
+        project,
+        nodes,
+        nodelinks,
+        exported_at: Utc::now(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        attachments,
+        redacted: false,
+    })
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportQuery {
+    /// Give the project and everything in it new IDs, so an export can be imported repeatedly
+    #[serde(default, alias = "regenerate_ids")]
+    pub remap_ids: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ProjectImported {
+    pub project_id: Uuid,
+    pub nodes: usize,
+    pub nodelinks: usize,
+    pub attachments: usize,
+    /// Attachments listed without their data, eg from an export without `include_attachments`
+    pub skipped_attachments: usize,
+    /// The project as stored, with any new IDs, listing attachments without their data
+    pub export: ProjectExport,
+}
+
+/// Give every item in an export a new ID, rewriting the references between them
+fn remap_export_ids(export: &mut ProjectExport) {
+    let project_id = Uuid::new_v4();
+    export.project.id = project_id;
+
+    let mut node_ids = HashMap::with_capacity(export.nodes.len());
+    for node in export.nodes.iter_mut() {
+        let new_id = Uuid::new_v4();
+        node_ids.insert(node.id, new_id);
+        node.id = new_id;
+        node.project_id = project_id;
+    }
+    // references to nodes which aren't in the export are left alone, and rejected later
+    let remap = |id: Uuid| node_ids.get(&id).copied().unwrap_or(id);
+    for nodelink in export.nodelinks.iter_mut() {
+        nodelink.id = Uuid::new_v4();
+        nodelink.project_id = project_id;
+        nodelink.left = remap(nodelink.left);
+        nodelink.right = remap(nodelink.right);
+    }
+    for attachment in export.attachments.iter_mut() {
+        attachment.id = Uuid::new_v4();
+        attachment.node_id = remap(attachment.node_id);
+    }
+}
 
-        Ok(Json(ProjectExport {
+/// Load a project from the output of [export_project]
+#[utoipa::path(
+    post,
+    path = "/api/v1/project/import",
+    tag = "projects",
+    operation_id = "import_project",
+    params(ImportQuery),
+    request_body = ProjectExport,
+    responses(
+        (status = OK, description = "Imported the project", body = ProjectImported),
+        (status = BAD_REQUEST, description = "An item in the export is invalid, the body says which", body = ErrorResponse),
+        (status = FORBIDDEN, description = "More links than --max-nodelinks-per-project allows", body = ErrorResponse),
+        (status = CONFLICT, description = "An ID is already in use, try remap_ids=true", body = ErrorResponse),
+        (status = UNPROCESSABLE_ENTITY, description = "A node breaks a value policy rule", body = ErrorResponse)
+    )
+)]
+pub async fn import_project(
+    State(state): State<SharedState>,
+    Query(query): Query<ImportQuery>,
+    auth_user: Option<Extension<AuthUser>>,
+    Json(mut export): Json<ProjectExport>,
+) -> Result<(HeaderMap, Json<ProjectImported>), WebError> {
+    if export.version != env!("CARGO_PKG_VERSION") {
+        warn!(
+            export_version = export.version,
+            server_version = env!("CARGO_PKG_VERSION"),
+            "Importing an export from a different version"
+        );
+    }
+    if query.remap_ids {
+        remap_export_ids(&mut export);
+    }
+    let ProjectExport {
+        project,
+        nodes,
+        nodelinks,
+        attachments,
+        ..
+    } = export;
+    // exports without include_attachments list attachments but leave out their data
+    let (attachments, skipped): (Vec<_>, Vec<_>) = attachments
+        .into_iter()
+        .partition(|attachment| attachment.size == 0 || !attachment.data.is_empty());
+    let attachment_count = attachments.len();
+
+    let reader = state.read().await;
+    let (headers, created) = create_project_graph(
+        &reader,
+        auth_user.as_deref(),
+        ProjectGraph {
             project,
             nodes,
             nodelinks,
-            exported_at: Utc::now(),
-            version: env!("CARGO_PKG_VERSION").to_string(),
-            attachments,
-        }))
-    }
+        },
+        attachments,
+    )
+    .await?;
+    let project = project::Entity::find_by_id(created.project_id)
+        .one(&reader.conn)
+        .await?
+        .ok_or_else(|| WebError::internal_server_error("Imported project disappeared"))?;
+    let nodes = project.find_related(node::Entity).all(&reader.conn).await?;
+    let nodelinks = project
+        .find_related(nodelink::Entity)
+        .all(&reader.conn)
+        .await?;
+    let imported = ProjectImported {
+        project_id: created.project_id,
+        nodes: created.node_ids.len(),
+        nodelinks: created.nodelink_ids.len(),
+        attachments: attachment_count,
+        skipped_attachments: skipped.len(),
+        export: project_export_without_data(&reader.conn, project, nodes, nodelinks).await?,
+    };
+    Ok((headers, Json(imported)))
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub enum SearchResultType {
     Node(NodeType),
+    /// `id` is the project's first node
     Project,
+    /// A project without any nodes to link to, `id` is the project's
+    EmptyProject,
     Attachment,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct SearchResult {
     pub id: Uuid,
     pub project_id: Uuid,
     pub title: String,
 
     pub result_type: SearchResultType,
+    /// Up to [SEARCH_SNIPPET_CHARS] characters around the first match
+    #[serde(default)]
+    pub snippet: Option<String>,
+    /// Set when the match is a value the node used to have, to when that value was replaced
+    #[serde(default)]
+    pub historical_at: Option<chrono::DateTime<Utc>>,
 }
 
+/// Shorter queries would match most of the database
+pub const SEARCH_MIN_QUERY_CHARS: usize = 2;
+pub const SEARCH_MAX_QUERY_CHARS: usize = 200;
+pub const SEARCH_SNIPPET_CHARS: usize = 120;
+pub const SEARCH_DEFAULT_LIMIT: u64 = 50;
+pub const SEARCH_MAX_LIMIT: u64 = 500;
+
 #[derive(Debug, Deserialize)]
 pub struct SearchQuery {
     pub q: String,
+    /// Also match values nodes used to have
+    #[serde(default)]
+    pub include_history: bool,
+    /// Only search this project
+    #[serde(default)]
+    pub project_id: Option<Uuid>,
+    /// Most results to return, defaults to [SEARCH_DEFAULT_LIMIT]
+    #[serde(default)]
+    pub limit: Option<u64>,
+}
+
+impl SearchQuery {
+    /// The trimmed search term, if it's a sensible length
+    pub fn term(&self) -> Result<&str, WebError> {
+        let term = self.q.trim();
+        let length = term.chars().count();
+        let problem = match length {
+            length if length < SEARCH_MIN_QUERY_CHARS => {
+                format!("must be at least {SEARCH_MIN_QUERY_CHARS} characters")
+            }
+            length if length > SEARCH_MAX_QUERY_CHARS => {
+                format!("must be at most {SEARCH_MAX_QUERY_CHARS} characters")
+            }
+            _ => return Ok(term),
+        };
+        Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid query parameter `q`: {problem}"),
+        )
+        .with_code(INVALID_QUERY_PARAMETER)
+        .with_detail("parameter", Some("q"))
+        .with_detail("value", Some(self.q.clone())))
+    }
+
+    /// How many results to return, between 1 and [SEARCH_MAX_LIMIT]
+    pub fn limit(&self) -> Result<usize, WebError> {
+        match self.limit.unwrap_or(SEARCH_DEFAULT_LIMIT) {
+            limit @ 1..=SEARCH_MAX_LIMIT => Ok(limit as usize),
+            limit => Err(WebError::new(
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Invalid query parameter `limit`: must be between 1 and {SEARCH_MAX_LIMIT}"
+                ),
+            )
+            .with_code(INVALID_QUERY_PARAMETER)
+            .with_detail("parameter", Some("limit"))
+            .with_detail("value", Some(limit.to_string()))),
+        }
+    }
+}
+
+/// How well a search result matched, best first
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum SearchRank {
+    /// The whole node display or project name
+    ExactTitle,
+    /// The whole of some other field, like a value, filename or tag
+    ExactField,
+    Substring,
+}
+
+impl SearchRank {
+    fn of<'a>(
+        titles: impl IntoIterator<Item = &'a str>,
+        fields: impl IntoIterator<Item = &'a str>,
+        term: &str,
+    ) -> Self {
+        let exact = |field: &str| field.trim().to_lowercase() == term;
+        if titles.into_iter().any(exact) {
+            SearchRank::ExactTitle
+        } else if fields.into_iter().any(exact) {
+            SearchRank::ExactField
+        } else {
+            SearchRank::Substring
+        }
+    }
+}
+
+/// Up to [SEARCH_SNIPPET_CHARS] characters of `text` around the first case-insensitive match of
+/// `term`, or `None` if it isn't there
+pub fn search_snippet(text: &str, term: &str) -> Option<String> {
+    let text: Vec<char> = text.chars().collect();
+    let term: Vec<char> = term.chars().flat_map(char::to_lowercase).collect();
+    if term.is_empty() || term.len() > text.len() {
+        return None;
+    }
+    let start = (0..=text.len() - term.len()).find(|&start| {
+        text[start..start + term.len()]
+            .iter()
+            .flat_map(|c| c.to_lowercase())
+            .eq(term.iter().copied())
+    })?;
+    // centre the match, shifting the window back if it runs off the end
+    let context = SEARCH_SNIPPET_CHARS.saturating_sub(term.len()) / 2;
+    let end = (start.saturating_sub(context) + SEARCH_SNIPPET_CHARS).min(text.len());
+    let begin = end.saturating_sub(SEARCH_SNIPPET_CHARS);
+    Some(text[begin..end].iter().collect())
+}
+
+/// Snippet from the first of `fields` which contains `term`
+fn first_snippet<'a>(fields: impl IntoIterator<Item = &'a str>, term: &str) -> Option<String> {
+    fields
+        .into_iter()
+        .find_map(|field| search_snippet(field, term))
 }
 
-/// Search across all nodes in all projects
+/// Search across all nodes in the caller's projects, or just one
+///
+/// Exact matches on a node's display or a project's name come first, then exact matches on any
+/// other field, then substring matches.
+#[utoipa::path(
+    get,
+    path = "/api/v1/search",
+    tag = "search",
+    operation_id = "search_global",
+    params(
+        ("q" = String, Query, description = "Case-insensitive substring to look for, 2 to 200 characters after trimming"),
+        ("include_history" = Option<bool>, Query, description = "Also match values nodes used to have, as results with `historical_at` set"),
+        ("project_id" = Option<Uuid>, Query, description = "Only search this project"),
+        ("limit" = Option<u64>, Query, description = "Most results to return, 1 to 500, defaults to 50")
+    ),
+    responses(
+        (status = OK, description = "Matching nodes, attachments and projects, best matches first", body = Vec<SearchResult>),
+        (status = BAD_REQUEST, description = "Invalid query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse)
+    )
+)]
 pub async fn search_global(
     State(state): State<SharedState>,
     Query(query): Query<SearchQuery>,
+    auth_user: Option<Extension<AuthUser>>,
 ) -> Result<Json<Vec<SearchResult>>, WebError> {
-    if query.q.trim().is_empty() {
-        return Ok(Json(vec![]));
+    let term = query.term()?;
+    let limit = query.limit()?;
+    let lower_term = term.to_lowercase();
+    let search_term = format!("%{lower_term}%");
+    let txn = state.read().await.begin().await?;
+
+    if let Some(project_id) = query.project_id {
+        let Some(project) = project::Entity::find_by_id(project_id).one(&txn).await? else {
+            return Err(WebError::not_found(format!(
+                "Project with id {project_id} not found"
+            )));
+        };
+        check_project_access(&txn, &project, auth_user.as_deref()).await?;
     }
+    let in_project = |column: node::Column| query.project_id.map(|id| column.eq(id));
 
-    let search_term = format!("%{}%", query.q.trim().to_lowercase());
-    let txn = state.read().await.conn.begin().await?;
-
-    let mut results: Vec<SearchResult> = Vec::new();
+    let mut results: Vec<(SearchRank, SearchResult)> = Vec::new();
 
     // Search in node display, value, and notes fields
-    let nodes = node::Entity::find()
+    let nodes = node::Entity::find_live()
         .filter(
             node::Column::Display
                 .like(&search_term)
                 .or(node::Column::Value.like(&search_term))
                 .or(node::Column::Notes.like(&search_term)),
         )
+        .apply_if(in_project(node::Column::ProjectId), QueryFilter::filter)
         .all(&txn)
         .await?;
 
     // Add node results
-    results.extend(nodes.into_iter().map(|node| SearchResult {
-        id: node.id,
-        project_id: node.project_id,
-        title: node.display,
-        result_type: SearchResultType::Node(node.node_type),
+    results.extend(nodes.into_iter().map(|node| {
+        let rank = SearchRank::of([node.display.as_str()], [node.value.as_str()], &lower_term);
+        (
+            rank,
+            SearchResult {
+                snippet: first_snippet(
+                    [
+                        node.display.as_str(),
+                        node.value.as_str(),
+                        node.notes.as_deref().unwrap_or_default(),
+                    ],
+                    term,
+                ),
+                id: node.id,
+                project_id: node.project_id,
+                title: node.display,
+                result_type: SearchResultType::Node(node.node_type),
+                historical_at: None,
+            },
+        )
     }));
 
-    // Search in attachment filenames
+    // Old values of nodes which don't match any more, only the latest change per node
+    if query.include_history {
+        let current: HashSet<Uuid> = results.iter().map(|(_, result)| result.id).collect();
+        let mut seen = HashSet::new();
+        let history = node_history::Entity::find()
+            .filter(node_history::Column::Change.eq(NodeChange::Update))
+            .filter(
+                Expr::expr(
+                    Func::cust(Alias::new("json_extract"))
+                        .arg(Expr::col(node_history::Column::Previous))
+                        .arg("$.value"),
+                )
+                .like(&search_term),
+            )
+            .order_by_desc(node_history::Column::ChangedAt)
+            .all(&txn)
+            .await?;
+        let mut changes = Vec::new();
+        for entry in history {
+            if !current.contains(&entry.node_id) && seen.insert(entry.node_id) {
+                changes.push((entry.previous_node()?.value, entry));
+            }
+        }
+        let nodes: HashMap<Uuid, node::Model> = node::Entity::find_live()
+            .filter(node::Column::Id.is_in(changes.iter().map(|(_, entry)| entry.node_id)))
+            .apply_if(in_project(node::Column::ProjectId), QueryFilter::filter)
+            .all(&txn)
+            .await?
+            .into_iter()
+            .map(|node| (node.id, node))
+            .collect();
+        results.extend(changes.into_iter().filter_map(|(old_value, entry)| {
+            let node = nodes.get(&entry.node_id)?;
+            let rank = SearchRank::of([], [old_value.as_str()], &lower_term);
+            Some((
+                rank,
+                SearchResult {
+                    id: node.id,
+                    project_id: node.project_id,
+                    title: format!("{} (previously: {})", node.display, old_value),
+                    result_type: SearchResultType::Node(node.node_type),
+                    snippet: search_snippet(&old_value, term),
+                    historical_at: Some(entry.changed_at),
+                },
+            ))
+        }));
+    }
+
+    // Search in attachment filenames, joined to their node to find project_id
     let attachments = attachment::Entity::find()
         .filter(attachment::Column::Filename.like(&search_term))
+        .find_also_related(node::Entity)
+        .filter(node::Column::DeletedAt.is_null())
+        .apply_if(in_project(node::Column::ProjectId), QueryFilter::filter)
         .all(&txn)
         .await?;
 
-    // For each attachment, get the associated node to find project_id
-    for attachment_model in attachments {
-        if let Some(node_model) = node::Entity::find_by_id(attachment_model.node_id)
-            .one(&txn)
-            .await?
-        {
-            results.push(SearchResult {
-                id: node_model.id,
-                project_id: node_model.project_id,
-                title: format!(
-                    "{} (attachment: {})",
-                    node_model.display, attachment_model.filename
-                ),
-                result_type: SearchResultType::Node(node_model.node_type),
-            });
-        }
-    }
-
-    // Search in project names, descriptions, and tags
-    let projects = project::Entity::find()
+    results.extend(
+        attachments
+            .into_iter()
+            .filter_map(|(attachment_model, node_model)| {
+                node_model.map(|node_model| {
+                    let rank =
+                        SearchRank::of([], [attachment_model.filename.as_str()], &lower_term);
+                    (
+                        rank,
+                        SearchResult {
+                            id: node_model.id,
+                            project_id: node_model.project_id,
+                            title: format!(
+                                "{} (attachment: {})",
+                                node_model.display, attachment_model.filename
+                            ),
+                            result_type: SearchResultType::Node(node_model.node_type),
+                            snippet: search_snippet(&attachment_model.filename, term),
+                            historical_at: None,
+                        },
+                    )
+                })
+            }),
+    );
+
+    // Search in project names and descriptions, and for a whole tag. Tags are stored as a JSON
+    // array so the LIKE only narrows things down, they're parsed before matching
+    let projects: Vec<project::Model> = project::Entity::find()
         .filter(
             project::Column::Name
                 .like(&search_term)
                 .or(project::Column::Description.like(&search_term))
                 .or(project::Column::Tags.like(&search_term)),
         )
+        .apply_if(query.project_id, |select, id| {
+            select.filter(project::Column::Id.eq(id))
+        })
         .all(&txn)
-        .await?;
-
-    // For projects, we need to return a representative node or create a special entry
-    // Since we need a node_id, we'll find the first node in each matching project
-    for project_model in projects {
-        if let Some(first_node) = node::Entity::find()
-            .filter(node::Column::ProjectId.eq(project_model.id))
-            .one(&txn)
-            .await?
-        {
-            results.push(SearchResult {
-                id: first_node.id,
+        .await?
+        .into_iter()
+        .filter(|project_model| {
+            project_model.tags.has_tag(term)
+                || first_snippet(
+                    [
+                        project_model.name.as_str(),
+                        project_model.description.as_deref().unwrap_or_default(),
+                    ],
+                    term,
+                )
+                .is_some()
+        })
+        .collect();
+
+    // Project results link to the lowest node ID in each project, so the client can jump
+    // straight to something, projects without nodes link to themselves
+    let first_nodes: HashMap<Uuid, Uuid> = node::Entity::find_live()
+        .select_only()
+        .column(node::Column::ProjectId)
+        .column_as(node::Column::Id.min(), "first_node")
+        .filter(node::Column::ProjectId.is_in(projects.iter().map(|p| p.id)))
+        .group_by(node::Column::ProjectId)
+        .into_tuple::<(Uuid, Uuid)>()
+        .all(&txn)
+        .await?
+        .into_iter()
+        .collect();
+    results.extend(projects.into_iter().map(|project_model| {
+        let (id, result_type) = match first_nodes.get(&project_model.id) {
+            Some(first_node) => (*first_node, SearchResultType::Project),
+            None => (project_model.id, SearchResultType::EmptyProject),
+        };
+        let rank = SearchRank::of(
+            [project_model.name.as_str()],
+            project_model.tags.0.iter().map(String::as_str),
+            &lower_term,
+        );
+        (
+            rank,
+            SearchResult {
+                id,
                 project_id: project_model.id,
                 title: format!("Project: {}", project_model.name),
-                result_type: SearchResultType::Project,
-            });
-        }
+                result_type,
+                snippet: first_snippet(
+                    [
+                        project_model.name.as_str(),
+                        project_model.description.as_deref().unwrap_or_default(),
+                    ]
+                    .into_iter()
+                    .chain(project_model.tags.0.iter().map(String::as_str)),
+                    term,
+                ),
+                historical_at: None,
+            },
+        )
+    }));
+
+    if auth_user.is_some() {
+        let accessible: HashSet<Uuid> = project::Entity::find()
+            .select_only()
+            .column(project::Column::Id)
+            .filter(accessible_projects(auth_user.as_deref()))
+            .into_tuple()
+            .all(&txn)
+            .await?
+            .into_iter()
+            .collect();
+        results.retain(|(_, result)| accessible.contains(&result.project_id));
     }
 
+    // stable, so results keep the nodes, history, attachments, projects order within a rank
+    results.sort_by_key(|(rank, _)| *rank);
+    let results: Vec<SearchResult> = results
+        .into_iter()
+        .map(|(_, result)| result)
+        .take(limit)
+        .collect();
+
     Ok(Json(results))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct MermaidExportQuery {
+    /// Comma-separated list of node types to include, defaults to all of them
+    #[serde(default)]
+    pub node_types: Option<String>,
+    #[serde(default)]
+    pub redact: bool,
+}
+
+impl MermaidExportQuery {
+    fn parse_node_types(&self) -> Result<Option<Vec<NodeType>>, WebError> {
+        parse_node_types("node_types", self.node_types.as_deref())
+    }
+}
+
+/// Parse a comma-separated node type filter from the query parameter `parameter`, sorted so that
+/// equivalent filters hash the same
+pub(crate) fn parse_node_types(
+    parameter: &'static str,
+    node_types: Option<&str>,
+) -> Result<Option<Vec<NodeType>>, WebError> {
+    let Some(node_types) = node_types else {
+        return Ok(None);
+    };
+    let mut res = node_types
+        .split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(|t| {
+            NodeType::try_from(t).map_err(|err| {
+                WebError::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid query parameter `{parameter}`: {err}"),
+                )
+                .with_code(INVALID_QUERY_PARAMETER)
+                .with_detail("parameter", Some(parameter))
+                .with_detail("value", Some(node_types))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    res.sort();
+    res.dedup();
+    Ok(Some(res))
+}
+
 /// Export a project as a Mermaid class diagram
 #[utoipa::path(
     get,
     path = "/api/v1/project/{id}/export/mermaid",
+    tag = "exports",
+    operation_id = "export_project_mermaid",
+    params(
+        ("id" = Uuid, Path, description = "Project ID to export"),
+        ("node_types" = Option<String>, Query, description = "Comma-separated node types to include, defaults to all"),
+        ("redact" = bool, Query, description = "Replace values with placeholders and strip attachments and project metadata")
+    ),
     responses(
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
         (status = OK, description = "Mermaid diagram exported successfully", body = String, content_type = "text/vnd.mermaid")
     )
 )]
 pub async fn export_project_mermaid(
     Path(id): Path<Uuid>,
+    Query(query): Query<MermaidExportQuery>,
     State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
 ) -> Result<impl IntoResponse, WebError> {
-    let txn = state.read().await.conn.begin().await?;
+    let node_types = query.parse_node_types()?;
+    let reader = state.read().await;
+    let txn = reader.begin().await?;
 
-    // Fetch the project
-    let project_model = match project::Entity::find_by_id(id).one(&txn).await? {
-        Some(project) => project,
-        None => return Err(WebError::not_found(format!("Project {} not found", id))),
+    let project_model = find_accessible_project(&txn, id, auth_user.as_deref()).await?;
+
+    let cache_key = ExportCacheKey {
+        project_id: id,
+        format: "mermaid",
+        filter_hash: filter_hash(&node_types),
+        fingerprint: project_fingerprint(&txn, &project_model, &reader.node_type_styles).await?,
+    };
+    let (diagram, cache_status) = if query.redact {
+        // placeholders are different on every export, so there's nothing to reuse
+        let diagram = render_project_mermaid(
+            &txn,
+            &project_model,
+            node_types.as_deref(),
+            &reader.node_type_styles,
+            true,
+        )
+        .await?;
+        (diagram, "bypass")
+    } else {
+        match reader.export_cache.get(&txn, &cache_key).await? {
+            Some(diagram) => (diagram, "hit"),
+            None => {
+                let diagram = render_project_mermaid(
+                    &txn,
+                    &project_model,
+                    node_types.as_deref(),
+                    &reader.node_type_styles,
+                    false,
+                )
+                .await?;
+                reader.export_cache.put(&txn, &cache_key, &diagram).await?;
+                (diagram, "miss")
+            }
+        }
     };
+    txn.commit().await?;
+    debug!(
+        project_id = id.to_string(),
+        cache = cache_status,
+        "Exported Mermaid diagram"
+    );
+
+    Ok((
+        [
+            (
+                CONTENT_DISPOSITION,
+                HeaderValue::from_str(&format!(
+                    "inline; filename=\"{}.mermaid\"",
+                    if query.redact {
+                        REDACTED
+                    } else {
+                        &project_model.name
+                    }
+                ))?,
+            ),
+            (CONTENT_TYPE, HeaderValue::from_static(MERMAID_CONTENT_TYPE)),
+            (CACHE_HEADER, HeaderValue::from_static(cache_status)),
+        ],
+        diagram,
+    ))
+}
 
+pub(crate) async fn render_project_mermaid(
+    txn: &impl ConnectionTrait,
+    project_model: &project::Model,
+    node_types: Option<&[NodeType]>,
+    styles: &NodeTypeStyles,
+    redacted: bool,
+) -> Result<String, DbErr> {
     // Fetch nodes
-    let nodes = project_model.find_related(node::Entity).all(&txn).await?;
+    let mut nodes_query = project_model
+        .find_related(node::Entity)
+        .filter(node::Column::DeletedAt.is_null());
+    if let Some(node_types) = node_types {
+        nodes_query = nodes_query.filter(node::Column::NodeType.is_in(node_types.iter().copied()));
+    }
+    let mut nodes = nodes_query.all(txn).await?;
 
     // Fetch nodelinks
-    let nodelinks = project_model
+    let mut nodelinks = project_model
         .find_related(nodelink::Entity)
-        .all(&txn)
+        .filter(nodelink::ends_live())
+        .all(txn)
         .await?;
 
     // Get all attachments for nodes in this project
     let node_ids: Vec<Uuid> = nodes.iter().map(|n| n.id).collect();
-    let attachments = if !node_ids.is_empty() {
+    let mut attachments = if !node_ids.is_empty() && !redacted {
         attachment::Entity::find()
             .filter(attachment::Column::NodeId.is_in(node_ids))
-            .all(&txn)
+            .all(txn)
             .await?
     } else {
         vec![]
     };
 
+    let mut project_model = project_model.clone();
+    if redacted {
+        redact(
+            &mut project_model,
+            &mut nodes,
+            &mut nodelinks,
+            &mut attachments,
+        );
+    }
+
+    Ok(build_mermaid(
+        &project_model,
+        &nodes,
+        &nodelinks,
+        attachments,
+        styles,
+        redacted,
+    ))
+}
+
+/// The Mermaid class diagram of already loaded project data, `redacted` adds the watermark
+pub(crate) fn build_mermaid(
+    project_model: &project::Model,
+    nodes: &[node::Model],
+    nodelinks: &[nodelink::Model],
+    attachments: Vec<attachment::Model>,
+    styles: &NodeTypeStyles,
+    redacted: bool,
+) -> String {
     // Group attachments by node_id
     let mut attachments_by_node: std::collections::HashMap<Uuid, Vec<attachment::Model>> =
         std::collections::HashMap::new();
@@ -758,6 +3544,9 @@ pub async fn export_project_mermaid(
     // Build the Mermaid diagram
     let mut diagram = String::new();
     diagram.push_str("classDiagram\n");
+    if redacted {
+        diagram.push_str(&format!("    %% {}\n", REDACTED));
+    }
 
     // Add a title comment
     diagram.push_str(&format!("    %% Project: {}\n", project_model.name));
@@ -857,34 +3646,378 @@ pub async fn export_project_mermaid(
         diagram.push_str("    }\n\n");
     }
 
+    // Colour each class by its node type
+    for node_model in nodes {
+        if let Some(class_name) = node_class_names.get(&node_model.id) {
+            diagram.push_str(&format!(
+                "    style {} fill:{}\n",
+                class_name,
+                styles.get(node_model.node_type).color
+            ));
+        }
+    }
+    diagram.push('\n');
+
     // Add relationships
-    for nodelink_model in &nodelinks {
+    for nodelink_model in nodelinks {
         if let (Some(left_class), Some(right_class)) = (
             node_class_names.get(&nodelink_model.left),
             node_class_names.get(&nodelink_model.right),
         ) {
-            match nodelink_model.linktype {
-                osint_graph_shared::nodelink::LinkType::Directional => {
-                    diagram.push_str(&format!("    {} --> {}\n", left_class, right_class));
-                }
-                osint_graph_shared::nodelink::LinkType::Omni => {
-                    diagram.push_str(&format!("    {} -- {}\n", left_class, right_class));
-                }
+            let arrow = match nodelink_model.linktype {
+                osint_graph_shared::nodelink::LinkType::Directional => "-->",
+                osint_graph_shared::nodelink::LinkType::Omni => "--",
+            };
+            diagram.push_str(&format!("    {} {} {}", left_class, arrow, right_class));
+            let label = match (&nodelink_model.kind, nodelink_model.weight) {
+                (Some(kind), Some(weight)) => Some(format!("{} ({})", kind, weight)),
+                (Some(kind), None) => Some(kind.clone()),
+                (None, Some(weight)) => Some(weight.to_string()),
+                (None, None) => None,
+            };
+            let label = match (label, validity_label(nodelink_model)) {
+                (Some(label), Some(validity)) => Some(format!("{label} {validity}")),
+                (label, validity) => label.or(validity),
+            };
+            if let Some(label) = label {
+                diagram.push_str(&format!(" : {}", sanitize_mermaid(&label)));
             }
+            diagram.push('\n');
         }
     }
 
+    diagram
+}
+
+/// A project's nodes and links in ID order, as the GraphML and DOT exports lay them out
+pub(crate) async fn load_project_graph(
+    conn: &impl ConnectionTrait,
+    project_id: Uuid,
+) -> Result<(Vec<node::Model>, Vec<nodelink::Model>), DbErr> {
+    let nodes = node::Entity::find_live()
+        .filter(node::Column::ProjectId.eq(project_id))
+        .order_by_asc(node::Column::Id)
+        .all(conn)
+        .await?;
+    let nodelinks = nodelink::Entity::find_live()
+        .filter(nodelink::Column::ProjectId.eq(project_id))
+        .order_by_asc(nodelink::Column::Id)
+        .all(conn)
+        .await?;
+    Ok((nodes, nodelinks))
+}
+
+/// Export a project as a GraphML document, for Gephi, yEd, Cytoscape and friends
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/export/graphml",
+    tag = "exports",
+    operation_id = "export_project_graphml",
+    params(
+        ("id" = Uuid, Path, description = "Project ID to export"),
+        ("redact" = bool, Query, description = "Replace values with placeholders and strip attachments and project metadata")
+    ),
+    responses(
+        (status = OK, description = "GraphML exported successfully", body = String, content_type = "application/graphml+xml"),
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse)
+    )
+)]
+pub async fn export_project_graphml(
+    Path(id): Path<Uuid>,
+    Query(query): Query<ExportQuery>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<impl IntoResponse, WebError> {
+    let conn = &state.read().await.conn;
+
+    let mut project_model = find_accessible_project(conn, id, auth_user.as_deref()).await?;
+    let (mut nodes, mut nodelinks) = load_project_graph(conn, id).await?;
+    if query.redact {
+        redact(
+            &mut project_model,
+            &mut nodes,
+            &mut nodelinks,
+            &mut Vec::new(),
+        );
+    }
+    debug!(
+        project_id = id.to_string(),
+        nodes = nodes.len(),
+        links = nodelinks.len(),
+        "Exporting GraphML"
+    );
+
     Ok((
         [
+            (CONTENT_TYPE, HeaderValue::from_static(GRAPHML_CONTENT_TYPE)),
             (
                 CONTENT_DISPOSITION,
                 HeaderValue::from_str(&format!(
-                    "inline; filename=\"{}.mermaid\"",
-                    project_model.name
+                    "attachment; filename=\"{}.graphml\"",
+                    project_model.name.replace('"', "'")
                 ))?,
             ),
-            (CONTENT_TYPE, HeaderValue::from_static(MERMAID_CONTENT_TYPE)),
         ],
-        diagram,
+        render_project_graphml(&project_model, &nodes, &nodelinks),
+    ))
+}
+
+/// Export a project as a Graphviz DOT digraph
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/export/dot",
+    tag = "exports",
+    operation_id = "export_project_dot",
+    params(
+        ("id" = Uuid, Path, description = "Project ID to export"),
+        ("redact" = bool, Query, description = "Replace values with placeholders and strip attachments and project metadata")
+    ),
+    responses(
+        (status = OK, description = "DOT exported successfully", body = String, content_type = "text/vnd.graphviz"),
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse)
+    )
+)]
+pub async fn export_project_dot(
+    Path(id): Path<Uuid>,
+    Query(query): Query<ExportQuery>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<impl IntoResponse, WebError> {
+    let reader = state.read().await;
+    let conn = &reader.conn;
+
+    let mut project_model = find_accessible_project(conn, id, auth_user.as_deref()).await?;
+    let (mut nodes, mut nodelinks) = load_project_graph(conn, id).await?;
+    if query.redact {
+        redact(
+            &mut project_model,
+            &mut nodes,
+            &mut nodelinks,
+            &mut Vec::new(),
+        );
+    }
+    debug!(
+        project_id = id.to_string(),
+        nodes = nodes.len(),
+        links = nodelinks.len(),
+        "Exporting DOT"
+    );
+
+    Ok((
+        [
+            (CONTENT_TYPE, HeaderValue::from_static(DOT_CONTENT_TYPE)),
+            (
+                CONTENT_DISPOSITION,
+                HeaderValue::from_str(&format!(
+                    "attachment; filename=\"{}.dot\"",
+                    project_model.name.replace('"', "'")
+                ))?,
+            ),
+        ],
+        render_project_dot(&project_model, &nodes, &nodelinks, &reader.node_type_styles),
     ))
 }
+
+/// A DOT quoted string, with newlines kept as line breaks in labels
+fn dot_quote(s: &str) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => {}
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+pub(crate) fn render_project_dot(
+    project_model: &project::Model,
+    nodes: &[node::Model],
+    nodelinks: &[nodelink::Model],
+    styles: &NodeTypeStyles,
+) -> String {
+    let mut doc = String::new();
+    doc.push_str(&format!(
+        "// Project: {}\n",
+        project_model.name.replace(['\n', '\r'], " ")
+    ));
+    doc.push_str(&format!(
+        "digraph {} {{\n",
+        dot_quote(&project_model.id.to_string())
+    ));
+    doc.push_str(&format!("    label={};\n", dot_quote(&project_model.name)));
+    doc.push_str("    node [style=filled];\n\n");
+
+    for node_model in nodes {
+        let style = styles.get(node_model.node_type);
+        let (shape, extra) = match style.shape {
+            NodeShape::Rectangle => ("box", ""),
+            NodeShape::RoundedRectangle => ("box", ", style=\"rounded,filled\""),
+            NodeShape::Ellipse => ("ellipse", ""),
+            NodeShape::Diamond => ("diamond", ""),
+            NodeShape::Hexagon => ("hexagon", ""),
+        };
+        doc.push_str(&format!(
+            "    {} [label={}, shape={}, fillcolor={}{}];\n",
+            dot_quote(&node_model.id.to_string()),
+            dot_quote(&node_model.display),
+            shape,
+            dot_quote(&style.color),
+            extra
+        ));
+    }
+    doc.push('\n');
+
+    for link in nodelinks {
+        let mut attrs = Vec::new();
+        if link.linktype == osint_graph_shared::nodelink::LinkType::Omni {
+            attrs.push("dir=none".to_string());
+        }
+        let label = match (&link.kind, link.weight) {
+            (Some(kind), Some(weight)) => Some(format!("{} ({})", kind, weight)),
+            (Some(kind), None) => Some(kind.clone()),
+            (None, Some(weight)) => Some(weight.to_string()),
+            (None, None) => None,
+        };
+        let label = match (label, validity_label(link)) {
+            (Some(label), Some(validity)) => Some(format!("{label} {validity}")),
+            (label, validity) => label.or(validity),
+        };
+        if let Some(label) = label {
+            attrs.push(format!("label={}", dot_quote(&label)));
+        }
+        if let Some(weight) = link.weight {
+            attrs.push(format!("weight={}", weight));
+        }
+        doc.push_str(&format!(
+            "    {} -> {}",
+            dot_quote(&link.left.to_string()),
+            dot_quote(&link.right.to_string())
+        ));
+        if !attrs.is_empty() {
+            doc.push_str(&format!(" [{}]", attrs.join(", ")));
+        }
+        doc.push_str(";\n");
+    }
+
+    doc.push_str("}\n");
+    doc
+}
+
+/// Escape text for XML content and attribute values, dropping characters XML 1.0 can't hold
+fn xml_escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            '\t' | '\n' | '\r' => res.push(c),
+            c if c.is_control() => {}
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+pub(crate) fn render_project_graphml(
+    project_model: &project::Model,
+    nodes: &[node::Model],
+    nodelinks: &[nodelink::Model],
+) -> String {
+    // (id, element, name, type)
+    const KEYS: &[(&str, &str, &str, &str)] = &[
+        ("node_type", "node", "node_type", "string"),
+        ("display", "node", "display", "string"),
+        ("value", "node", "value", "string"),
+        ("notes", "node", "notes", "string"),
+        ("pos_x", "node", "pos_x", "int"),
+        ("pos_y", "node", "pos_y", "int"),
+        ("kind", "edge", "kind", "string"),
+        ("weight", "edge", "weight", "double"),
+        ("valid_from", "edge", "valid_from", "string"),
+        ("valid_to", "edge", "valid_to", "string"),
+    ];
+    fn data(doc: &mut String, key: &str, value: &str) {
+        doc.push_str(&format!(
+            "      <data key=\"{key}\">{}</data>\n",
+            xml_escape(value)
+        ));
+    }
+
+    let mut doc = String::new();
+    doc.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    doc.push_str(
+        "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\" \
+         xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+         xsi:schemaLocation=\"http://graphml.graphdrawing.org/xmlns \
+         http://graphml.graphdrawing.org/xmlns/1.0/graphml.xsd\">\n",
+    );
+    for (key, element, name, value_type) in KEYS {
+        doc.push_str(&format!(
+            "  <key id=\"{key}\" for=\"{element}\" attr.name=\"{name}\" attr.type=\"{value_type}\"/>\n"
+        ));
+    }
+    doc.push_str(&format!(
+        "  <graph id=\"{}\" edgedefault=\"undirected\">\n",
+        project_model.id
+    ));
+    doc.push_str(&format!(
+        "    <desc>{}</desc>\n",
+        xml_escape(&project_model.name)
+    ));
+
+    for node_model in nodes {
+        doc.push_str(&format!("    <node id=\"{}\">\n", node_model.id));
+        data(&mut doc, "node_type", node_model.node_type.as_ref());
+        data(&mut doc, "display", &node_model.display);
+        data(&mut doc, "value", &node_model.value);
+        if let Some(notes) = &node_model.notes {
+            data(&mut doc, "notes", notes);
+        }
+        if let Some(pos_x) = node_model.pos_x {
+            data(&mut doc, "pos_x", &pos_x.to_string());
+        }
+        if let Some(pos_y) = node_model.pos_y {
+            data(&mut doc, "pos_y", &pos_y.to_string());
+        }
+        doc.push_str("    </node>\n");
+    }
+
+    for link in nodelinks {
+        doc.push_str(&format!(
+            "    <edge id=\"{}\" source=\"{}\" target=\"{}\" directed=\"{}\">\n",
+            link.id,
+            link.left,
+            link.right,
+            link.linktype == osint_graph_shared::nodelink::LinkType::Directional
+        ));
+        if let Some(kind) = &link.kind {
+            data(&mut doc, "kind", kind);
+        }
+        if let Some(weight) = link.weight {
+            data(&mut doc, "weight", &weight.to_string());
+        }
+        if let Some(valid_from) = link.valid_from {
+            data(&mut doc, "valid_from", &valid_from.to_rfc3339());
+        }
+        if let Some(valid_to) = link.valid_to {
+            data(&mut doc, "valid_to", &valid_to.to_rfc3339());
+        }
+        doc.push_str("    </edge>\n");
+    }
+
+    doc.push_str("  </graph>\n</graphml>\n");
+    doc
+}
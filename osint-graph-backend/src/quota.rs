@@ -0,0 +1,296 @@
+//! Instance-wide resource limits
+//!
+//! Limits are optional and configured by the operator. Current usage is cached
+//! for a short period so that we don't count rows on every create request, and
+//! the cached figures are bumped locally whenever something is created. Anything
+//! which removes rows invalidates them instead, so freed space can be used straight
+//! away. Nodes in the trash still count until they're purged.
+//!
+//! Admins aren't held to the hard limits, so cleanup such as merging projects can
+//! go ahead at the cap. Their usage is still counted and warned about.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait, QueryFilter, QuerySelect,
+};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    entity::{attachment, node, nodelink, project},
+    oauth::middleware::AuthUser,
+    project::WebError,
+};
+
+/// Response header added when usage crosses the soft warning threshold
+pub const QUOTA_WARNING_HEADER: HeaderName = HeaderName::from_static("x-osintgraph-quota-warning");
+
+/// Default soft-warning threshold, as a percentage of the hard limit
+pub const DEFAULT_QUOTA_WARNING_PERCENT: u8 = 80;
+
+/// How long usage figures are trusted before they're re-counted
+const USAGE_CACHE_TTL: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum QuotaKind {
+    Projects,
+    NodesPerProject(Uuid),
+    NodelinksPerProject(Uuid),
+    AttachmentBytes,
+}
+
+impl QuotaKind {
+    /// The name of the option which configures this limit
+    pub fn limit_name(&self) -> &'static str {
+        match self {
+            QuotaKind::Projects => "max_projects",
+            QuotaKind::NodesPerProject(_) => "max_nodes_per_project",
+            QuotaKind::NodelinksPerProject(_) => "max_nodelinks_per_project",
+            QuotaKind::AttachmentBytes => "max_total_attachment_bytes",
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            QuotaKind::Projects => "projects".to_string(),
+            QuotaKind::NodesPerProject(project_id) => format!("nodes in project {project_id}"),
+            QuotaKind::NodelinksPerProject(project_id) => {
+                format!("links in project {project_id}")
+            }
+            QuotaKind::AttachmentBytes => "attachment bytes".to_string(),
+        }
+    }
+
+    fn exceeded_status(&self) -> StatusCode {
+        match self {
+            QuotaKind::AttachmentBytes => StatusCode::INSUFFICIENT_STORAGE,
+            QuotaKind::NodelinksPerProject(_) => StatusCode::FORBIDDEN,
+            QuotaKind::Projects | QuotaKind::NodesPerProject(_) => StatusCode::CONFLICT,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct QuotaLimits {
+    pub max_projects: Option<u64>,
+    pub max_nodes_per_project: Option<u64>,
+    pub max_nodelinks_per_project: Option<u64>,
+    pub max_total_attachment_bytes: Option<u64>,
+    pub warning_percent: u8,
+}
+
+impl Default for QuotaLimits {
+    fn default() -> Self {
+        Self {
+            max_projects: None,
+            max_nodes_per_project: None,
+            max_nodelinks_per_project: None,
+            max_total_attachment_bytes: None,
+            warning_percent: DEFAULT_QUOTA_WARNING_PERCENT,
+        }
+    }
+}
+
+impl QuotaLimits {
+    pub fn limit_for(&self, kind: QuotaKind) -> Option<u64> {
+        match kind {
+            QuotaKind::Projects => self.max_projects,
+            QuotaKind::NodesPerProject(_) => self.max_nodes_per_project,
+            QuotaKind::NodelinksPerProject(_) => self.max_nodelinks_per_project,
+            QuotaKind::AttachmentBytes => self.max_total_attachment_bytes,
+        }
+    }
+}
+
+struct CachedUsage {
+    value: u64,
+    fetched: Instant,
+}
+
+#[derive(Default)]
+pub struct Quota {
+    pub limits: QuotaLimits,
+    usage: Mutex<HashMap<QuotaKind, CachedUsage>>,
+    warnings: Mutex<HashMap<QuotaKind, String>>,
+}
+
+impl Quota {
+    pub fn new(limits: QuotaLimits) -> Self {
+        Self {
+            limits,
+            ..Default::default()
+        }
+    }
+
+    async fn count_usage(conn: &impl ConnectionTrait, kind: QuotaKind) -> Result<u64, WebError> {
+        let res = match kind {
+            QuotaKind::Projects => project::Entity::find().count(conn).await?,
+            QuotaKind::NodesPerProject(project_id) => {
+                node::Entity::find()
+                    .filter(node::Column::ProjectId.eq(project_id))
+                    .count(conn)
+                    .await?
+            }
+            QuotaKind::NodelinksPerProject(project_id) => {
+                nodelink::Entity::find()
+                    .filter(nodelink::Column::ProjectId.eq(project_id))
+                    .count(conn)
+                    .await?
+            }
+            QuotaKind::AttachmentBytes => attachment::Entity::find()
+                .select_only()
+                .column_as(attachment::Column::Size.sum(), "total")
+                .into_tuple::<Option<i64>>()
+                .one(conn)
+                .await?
+                .flatten()
+                .unwrap_or(0)
+                .max(0) as u64,
+        };
+        Ok(res)
+    }
+
+    /// Current usage for a limit, from the cache if it's fresh enough
+    pub async fn usage(
+        &self,
+        conn: &impl ConnectionTrait,
+        kind: QuotaKind,
+    ) -> Result<u64, WebError> {
+        if let Some(cached) = self.lock_usage().get(&kind) {
+            if cached.fetched.elapsed() < USAGE_CACHE_TTL {
+                return Ok(cached.value);
+            }
+        }
+        let value = Self::count_usage(conn, kind).await?;
+        self.lock_usage().insert(
+            kind,
+            CachedUsage {
+                value,
+                fetched: Instant::now(),
+            },
+        );
+        self.update_warning(kind, value);
+        Ok(value)
+    }
+
+    /// Ensure there's room for `additional` more units of `kind`, unless `auth_user` is an admin
+    pub async fn check(
+        &self,
+        conn: &impl ConnectionTrait,
+        kind: QuotaKind,
+        additional: u64,
+        auth_user: Option<&AuthUser>,
+    ) -> Result<(), WebError> {
+        let Some(limit) = self.limits.limit_for(kind) else {
+            return Ok(());
+        };
+        let usage = self.usage(conn, kind).await?;
+        if usage.saturating_add(additional) > limit {
+            if let Some(admin) = auth_user.filter(|auth_user| auth_user.is_admin) {
+                info!(
+                    limit = kind.limit_name(),
+                    usage,
+                    additional,
+                    max = limit,
+                    user = admin.subject,
+                    "Admin exempt from exceeded quota"
+                );
+                return Ok(());
+            }
+            warn!(
+                limit = kind.limit_name(),
+                usage,
+                additional,
+                max = limit,
+                "Quota exceeded"
+            );
+            return Err(WebError::new(
+                kind.exceeded_status(),
+                format!(
+                    "Limit {} reached: {} of {} {} in use, cannot add {} more",
+                    kind.limit_name(),
+                    usage,
+                    limit,
+                    kind.describe(),
+                    additional
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Record that `added` units of `kind` were created, returning the soft-limit warning if there is one
+    pub fn record(&self, kind: QuotaKind, added: u64) -> Option<String> {
+        self.limits.limit_for(kind)?;
+        let value = {
+            let mut usage = self.lock_usage();
+            let cached = usage.get_mut(&kind)?;
+            cached.value = cached.value.saturating_add(added);
+            cached.value
+        };
+        self.update_warning(kind, value)
+    }
+
+    /// Forget the cached usage for `kinds` after rows were removed, so it's counted again
+    pub fn invalidate(&self, kinds: impl IntoIterator<Item = QuotaKind>) {
+        let mut usage = self.lock_usage();
+        for kind in kinds {
+            usage.remove(&kind);
+        }
+    }
+
+    /// All soft-limit warnings that are currently active
+    pub fn warnings(&self) -> Vec<String> {
+        let mut res: Vec<String> = self.lock_warnings().values().cloned().collect();
+        res.sort();
+        res
+    }
+
+    fn update_warning(&self, kind: QuotaKind, usage: u64) -> Option<String> {
+        let limit = self.limits.limit_for(kind)?;
+        let mut warnings = self.lock_warnings();
+        if usage.saturating_mul(100) >= limit.saturating_mul(self.limits.warning_percent as u64) {
+            let msg = format!(
+                "{}: {} of {} {} in use",
+                kind.limit_name(),
+                usage,
+                limit,
+                kind.describe()
+            );
+            warnings.insert(kind, msg.clone());
+            Some(msg)
+        } else {
+            warnings.remove(&kind);
+            None
+        }
+    }
+
+    fn lock_usage(&self) -> std::sync::MutexGuard<'_, HashMap<QuotaKind, CachedUsage>> {
+        self.usage.lock().unwrap_or_else(|err| {
+            error!("Quota usage cache lock was poisoned, recovering");
+            err.into_inner()
+        })
+    }
+
+    fn lock_warnings(&self) -> std::sync::MutexGuard<'_, HashMap<QuotaKind, String>> {
+        self.warnings.lock().unwrap_or_else(|err| {
+            error!("Quota warnings lock was poisoned, recovering");
+            err.into_inner()
+        })
+    }
+}
+
+/// Builds the response headers for a soft-limit warning, if there is one
+pub fn warning_headers(warning: Option<String>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(warning) = warning.and_then(|w| HeaderValue::from_str(&w).ok()) {
+        headers.insert(QUOTA_WARNING_HEADER, warning);
+    }
+    headers
+}
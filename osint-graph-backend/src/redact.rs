@@ -0,0 +1,89 @@
+//! Redacted exports, for sharing the shape of an investigation without its contents
+//!
+//! Every exporter runs its data through [redact] when asked for `?redact=true`, so node types, link
+//! types and positions survive but nothing that identifies a selector does. Pushes to S3
+//! ([crate::export_push]) are always of the full export.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::Utc;
+use osint_graph_shared::StringVec;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use uuid::Uuid;
+
+use crate::entity::{attachment, node, nodelink, project};
+
+/// Watermark used in place of the project name and in export headers
+pub const REDACTED: &str = "REDACTED";
+
+/// Replace node values, notes, link labels and IDs with placeholders, and strip attachments, when
+/// links were valid, who created what, and project metadata
+///
+/// Placeholders look like `person-1`. They're numbered in a shuffled order using a seed that's
+/// thrown away afterwards, so the numbering can't be mapped back to creation order.
+pub fn redact(
+    project: &mut project::Model,
+    nodes: &mut [node::Model],
+    nodelinks: &mut [nodelink::Model],
+    attachments: &mut Vec<attachment::Model>,
+) {
+    let mut rng = StdRng::seed_from_u64(rand::random());
+    let now = Utc::now();
+
+    *project = project::Model {
+        id: Uuid::new_v4(),
+        name: REDACTED.to_string(),
+        user: Uuid::nil(),
+        creationdate: now,
+        last_updated: None,
+        description: None,
+        tags: StringVec(vec![]),
+        pinned: false,
+        is_archived: false,
+    };
+    attachments.clear();
+
+    let mut by_type: BTreeMap<_, Vec<&mut node::Model>> = BTreeMap::new();
+    for node in nodes.iter_mut() {
+        by_type.entry(node.node_type).or_default().push(node);
+    }
+    let mut new_ids = HashMap::new();
+    for (node_type, mut nodes_of_type) in by_type {
+        nodes_of_type.shuffle(&mut rng);
+        for (index, node) in nodes_of_type.into_iter().enumerate() {
+            let placeholder = format!("{}-{}", node_type, index + 1);
+            let new_id = Uuid::new_v4();
+            new_ids.insert(node.id, new_id);
+            node.id = new_id;
+            node.project_id = project.id;
+            node.display = placeholder.clone();
+            node.value = placeholder;
+            node.notes = None;
+            node.updated = now;
+            node.created_by = None;
+            node.created = None;
+        }
+    }
+    // output order would otherwise follow the database
+    nodes.sort_by_key(|node| {
+        let (node_type, number) = node.value.rsplit_once('-').unwrap_or_default();
+        (
+            node_type.to_string(),
+            number.parse::<usize>().unwrap_or_default(),
+        )
+    });
+
+    for link in nodelinks.iter_mut() {
+        link.id = Uuid::new_v4();
+        link.project_id = project.id;
+        link.left = *new_ids.entry(link.left).or_insert_with(Uuid::new_v4);
+        link.right = *new_ids.entry(link.right).or_insert_with(Uuid::new_v4);
+        // free text, but whether a link has a label is part of the shape
+        link.kind = link.kind.as_ref().map(|_| REDACTED.to_string());
+        link.valid_from = None;
+        link.valid_to = None;
+        link.created_by = None;
+        link.created = None;
+    }
+    nodelinks.sort_by_key(|link| link.id);
+}
@@ -0,0 +1,47 @@
+//! Rebuilding derived data which can drift when a migration or bug skips rows
+//!
+//! Attachment content hashes are the only derived data stored so far. Search runs `LIKE` queries
+//! over the live columns and canonical keys are computed when they're needed, so neither has an
+//! index to rebuild. Each step works in batches so no single write holds the lock for long.
+
+use axum::{extract::State, Json};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::{
+    attachment_dedup::backfill_attachment_hashes,
+    project::{ErrorResponse, WebError},
+    SharedState,
+};
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ReindexReport {
+    /// Attachments which were missing their `sha256`
+    pub attachment_hashes: u64,
+}
+
+/// Recompute derived data which is missing, reporting how many rows were fixed
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/reindex",
+    tag = "admin",
+    operation_id = "post_reindex",
+    responses(
+        (status = OK, description = "Rows fixed by each step", body = ReindexReport),
+        (status = INTERNAL_SERVER_ERROR, description = "Database error", body = ErrorResponse)
+    )
+)]
+pub async fn post_reindex(
+    State(state): State<SharedState>,
+) -> Result<Json<ReindexReport>, WebError> {
+    let conn = state.read().await.conn.clone();
+    let report = ReindexReport {
+        attachment_hashes: backfill_attachment_hashes(&conn).await?,
+    };
+    info!(
+        attachment_hashes = report.attachment_hashes,
+        "Reindex finished"
+    );
+    Ok(Json(report))
+}
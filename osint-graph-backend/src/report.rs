@@ -0,0 +1,796 @@
+//! PDF case reports
+//!
+//! A report has a cover page from the project metadata, a drawing of the graph, a table of
+//! nodes per type with notes and sources as footnotes, a chronology, and an evidence appendix
+//! listing every attachment with its hash, plus a thumbnail for JPEGs.
+//!
+//! Projects up to `--report-sync-max-nodes` nodes get their report straight away. Bigger ones
+//! are built by a background job, whose PDF is fetched from `/api/v1/report-jobs/{id}` once done.
+//! Jobs are kept in memory, so they don't survive a restart.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    extract::State,
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE, LOCATION},
+        HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use osint_graph_shared::node::NodeType;
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait, PaginatorTrait,
+    QueryFilter, QueryOrder,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    access::check_project_access,
+    entity::{attachment, node, nodelink, project},
+    extract::{Path, Query},
+    layout::{force_iterations, force_layout, grid_layout},
+    oauth::middleware::AuthUser,
+    pdf::{truncate, Font, PdfWriter, Rgb, CONTENT_WIDTH, MARGIN, PAGE_HEIGHT},
+    project::{ErrorResponse, ExportQuery, WebError},
+    redact::redact,
+    styles::{NodeShape, NodeTypeStyles},
+    SharedState,
+};
+
+pub const PDF_CONTENT_TYPE: &str = "application/pdf";
+/// Default for `--report-sync-max-nodes`
+pub const DEFAULT_REPORT_SYNC_MAX_NODES: u64 = 250;
+/// Error code when a report job failed
+pub const REPORT_FAILED: &str = "report_failed";
+/// How long finished jobs are kept around for
+const REPORT_JOB_TTL: Duration = Duration::from_secs(60 * 60);
+/// Bigger JPEGs are listed in the appendix without a thumbnail
+const MAX_THUMBNAIL_BYTES: i64 = 4 * 1024 * 1024;
+const MAX_THUMBNAILS: usize = 100;
+const THUMBNAIL_SIZE: f64 = 80.0;
+/// Graphs with more nodes than this are drawn without labels
+const MAX_LABELLED_NODES: usize = 150;
+
+/// Everything a report is built from
+pub struct ReportData {
+    pub project: project::Model,
+    pub nodes: Vec<node::Model>,
+    pub nodelinks: Vec<nodelink::Model>,
+    pub attachments: Vec<attachment::Model>,
+    /// Decoded JPEG data for the appendix, by attachment ID
+    pub thumbnails: HashMap<Uuid, Vec<u8>>,
+}
+
+/// Load a project for [render_report], `None` if it doesn't exist
+///
+/// A `redacted` report has been through [redact], so it has no attachments.
+pub async fn load_report(
+    conn: &impl ConnectionTrait,
+    project_id: Uuid,
+    redacted: bool,
+) -> Result<Option<ReportData>, DbErr> {
+    let Some(mut project) = project::Entity::find_by_id(project_id).one(conn).await? else {
+        return Ok(None);
+    };
+    let mut nodes = node::Entity::find_live()
+        .filter(node::Column::ProjectId.eq(project_id))
+        .order_by_asc(node::Column::NodeType)
+        .order_by_asc(node::Column::Display)
+        .order_by_asc(node::Column::Id)
+        .all(conn)
+        .await?;
+    let mut nodelinks = nodelink::Entity::find_live()
+        .filter(nodelink::Column::ProjectId.eq(project_id))
+        .all(conn)
+        .await?;
+    if redacted {
+        let mut attachments = Vec::new();
+        redact(&mut project, &mut nodes, &mut nodelinks, &mut attachments);
+        return Ok(Some(ReportData {
+            project,
+            nodes,
+            nodelinks,
+            attachments,
+            thumbnails: HashMap::new(),
+        }));
+    }
+    let attachments: Vec<attachment::Model> = attachment::attachment_list(project_id)
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(attachment::Model::from)
+        .collect();
+
+    let thumbnail_ids: Vec<Uuid> = attachments
+        .iter()
+        .filter(|a| a.content_type == "image/jpeg" && a.size <= MAX_THUMBNAIL_BYTES)
+        .take(MAX_THUMBNAILS)
+        .map(|a| a.id)
+        .collect();
+    let mut thumbnails = HashMap::new();
+    if !thumbnail_ids.is_empty() {
+        let mut stored = attachment::Entity::find()
+            .filter(attachment::Column::Id.is_in(thumbnail_ids))
+            .all(conn)
+            .await?;
+        attachment::load_shared_data(conn, &mut stored).await?;
+        for stored in stored {
+            match stored.codec.decode(&stored.data) {
+                Ok(data) => {
+                    thumbnails.insert(stored.id, data);
+                }
+                Err(err) => error!(
+                    error = ?err,
+                    attachment_id = stored.id.to_string(),
+                    "Failed to decode attachment for report thumbnail"
+                ),
+            }
+        }
+    }
+
+    Ok(Some(ReportData {
+        project,
+        nodes,
+        nodelinks,
+        attachments,
+        thumbnails,
+    }))
+}
+
+/// A node type as a heading, eg `Person`
+fn type_label(node_type: NodeType) -> String {
+    let name = node_type.as_ref();
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M UTC").to_string()
+}
+
+/// Build the PDF for a project
+///
+/// Force layout is used to draw graphs whose nodes haven't all been placed, up to
+/// `max_layout_nodes`, then a grid.
+pub fn render_report(data: ReportData, styles: &NodeTypeStyles, max_layout_nodes: u64) -> Vec<u8> {
+    let mut pdf = PdfWriter::new();
+    pdf.footer = truncate(&data.project.name, 60);
+
+    cover_page(&mut pdf, &data);
+    pdf.new_page();
+    graph_page(&mut pdf, &data, styles, max_layout_nodes);
+    pdf.new_page();
+    node_tables(&mut pdf, &data);
+    pdf.new_page();
+    chronology(&mut pdf, &data);
+    pdf.new_page();
+    evidence_appendix(&mut pdf, data);
+
+    pdf.finish()
+}
+
+fn cover_page(pdf: &mut PdfWriter, data: &ReportData) {
+    let project = &data.project;
+    pdf.space(PAGE_HEIGHT / 5.0);
+    pdf.paragraph(Font::Bold, 26.0, &project.name);
+    pdf.space(10.0);
+    if let Some(description) = project.description.as_deref().map(str::trim) {
+        if !description.is_empty() {
+            pdf.paragraph(Font::Regular, 12.0, description);
+            pdf.space(10.0);
+        }
+    }
+    if !project.tags.0.is_empty() {
+        pdf.paragraph(
+            Font::Regular,
+            10.0,
+            &format!("Tags: {}", project.tags.0.join(", ")),
+        );
+    }
+    pdf.space(20.0);
+    for line in [
+        format!("Created: {}", format_time(project.creationdate)),
+        format!(
+            "Last updated: {}",
+            project
+                .last_updated
+                .map(format_time)
+                .unwrap_or_else(|| "never".to_string())
+        ),
+        format!(
+            "{} nodes, {} links, {} attachments",
+            data.nodes.len(),
+            data.nodelinks.len(),
+            data.attachments.len()
+        ),
+        format!("Project ID: {}", project.id),
+        format!(
+            "Generated {} by osint-graph {}",
+            format_time(Utc::now()),
+            env!("CARGO_PKG_VERSION")
+        ),
+    ] {
+        pdf.paragraph(Font::Regular, 10.0, &line);
+    }
+}
+
+fn graph_page(
+    pdf: &mut PdfWriter,
+    data: &ReportData,
+    styles: &NodeTypeStyles,
+    max_layout_nodes: u64,
+) {
+    pdf.heading(18.0, "Graph");
+    if data.nodes.is_empty() {
+        pdf.paragraph(Font::Regular, 10.0, "This project has no nodes.");
+        return;
+    }
+
+    let index: HashMap<Uuid, usize> = data
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(index, node)| (node.id, index))
+        .collect();
+    let edges: Vec<(usize, usize)> = data
+        .nodelinks
+        .iter()
+        .filter_map(|link| Some((*index.get(&link.left)?, *index.get(&link.right)?)))
+        .filter(|(left, right)| left != right)
+        .collect();
+    // use where the nodes were put on the canvas, unless some never were
+    let positions: Vec<(f64, f64)> = match data
+        .nodes
+        .iter()
+        .map(|node| Some((node.pos_x? as f64, node.pos_y? as f64)))
+        .collect::<Option<Vec<_>>>()
+    {
+        Some(positions) => positions,
+        None if data.nodes.len() as u64 <= max_layout_nodes => force_layout(
+            data.nodes.len(),
+            &edges,
+            force_iterations(data.nodes.len() as u64),
+        ),
+        None => grid_layout(data.nodes.len()),
+    };
+
+    // fit the drawing in the rest of the page, y going up in PDF
+    let labelled = data.nodes.len() <= MAX_LABELLED_NODES;
+    let padding = if labelled { 30.0 } else { 10.0 };
+    let (left, right) = (MARGIN + padding, MARGIN + CONTENT_WIDTH - padding);
+    let (bottom, top) = (MARGIN + 40.0 + padding, pdf.cursor() - padding);
+    let min_x = positions.iter().map(|p| p.0).fold(f64::INFINITY, f64::min);
+    let max_x = positions
+        .iter()
+        .map(|p| p.0)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let min_y = positions.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+    let max_y = positions
+        .iter()
+        .map(|p| p.1)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let scale =
+        ((right - left) / (max_x - min_x).max(1.0)).min((top - bottom) / (max_y - min_y).max(1.0));
+    let place = |(x, y): (f64, f64)| (left + (x - min_x) * scale, top - (y - min_y) * scale);
+    let points: Vec<(f64, f64)> = positions.into_iter().map(place).collect();
+
+    for (from, to) in &edges {
+        pdf.line(points[*from], points[*to], 0.5, Rgb::GREY);
+    }
+    let radius = if labelled { 5.0 } else { 2.5 };
+    for (node, (x, y)) in data.nodes.iter().zip(&points) {
+        let style = styles.get(node.node_type);
+        let color = Rgb::from_hex(&style.color).unwrap_or(Rgb::GREY);
+        match style.shape {
+            NodeShape::Ellipse => pdf.ellipse(*x, *y, radius, radius, color),
+            NodeShape::Diamond => pdf.diamond(*x, *y, radius * 1.3, radius * 1.3, color),
+            _ => pdf.rect(x - radius, y - radius, radius * 2.0, radius * 2.0, color),
+        }
+        if labelled {
+            pdf.text_at(
+                x - radius,
+                y - radius - 7.0,
+                Font::Regular,
+                6.0,
+                &truncate(&node.display, 30),
+            );
+        }
+    }
+
+    // a legend of the types in the drawing
+    let types: Vec<NodeType> = data
+        .nodes
+        .iter()
+        .map(|node| node.node_type)
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    const LEGEND_COLUMNS: usize = 7;
+    for (index, node_type) in types.into_iter().enumerate() {
+        let x = MARGIN + (index % LEGEND_COLUMNS) as f64 * CONTENT_WIDTH / LEGEND_COLUMNS as f64;
+        let y = MARGIN + 30.0 - (index / LEGEND_COLUMNS) as f64 * 10.0;
+        let color = Rgb::from_hex(&styles.get(node_type).color).unwrap_or(Rgb::GREY);
+        pdf.rect(x, y, 6.0, 6.0, color);
+        pdf.text_at(x + 9.0, y + 0.5, Font::Regular, 7.0, &type_label(node_type));
+    }
+}
+
+fn node_tables(pdf: &mut PdfWriter, data: &ReportData) {
+    pdf.heading(18.0, "Nodes");
+    if data.nodes.is_empty() {
+        pdf.paragraph(Font::Regular, 10.0, "This project has no nodes.");
+        return;
+    }
+
+    // linked URLs and documents are where a node's information came from
+    let by_id: HashMap<Uuid, &node::Model> =
+        data.nodes.iter().map(|node| (node.id, node)).collect();
+    let mut sources: HashMap<Uuid, Vec<&str>> = HashMap::new();
+    for link in &data.nodelinks {
+        for (from, to) in [(link.left, link.right), (link.right, link.left)] {
+            if let Some(source) = by_id
+                .get(&to)
+                .filter(|node| matches!(node.node_type, NodeType::Url | NodeType::Document))
+            {
+                sources.entry(from).or_default().push(source.value.as_str());
+            }
+        }
+    }
+
+    let mut by_type: BTreeMap<NodeType, Vec<&node::Model>> = BTreeMap::new();
+    for node in &data.nodes {
+        by_type.entry(node.node_type).or_default().push(node);
+    }
+    let mut footnote = 0;
+    for (node_type, nodes) in by_type {
+        pdf.heading(
+            13.0,
+            &format!("{} ({})", type_label(node_type), nodes.len()),
+        );
+        let mut rows = Vec::with_capacity(nodes.len());
+        let mut footnotes = Vec::new();
+        for node in nodes {
+            let notes = node
+                .notes
+                .as_deref()
+                .map(str::trim)
+                .filter(|notes| !notes.is_empty());
+            let node_sources = sources.get(&node.id).cloned().unwrap_or_default();
+            let mut display = node.display.clone();
+            if notes.is_some() || !node_sources.is_empty() {
+                footnote += 1;
+                display.push_str(&format!(" [{footnote}]"));
+                let mut text = format!("[{footnote}]");
+                if let Some(notes) = notes {
+                    text.push_str(&format!(" {notes}"));
+                }
+                if !node_sources.is_empty() {
+                    text.push_str(&format!(" Sources: {}", node_sources.join(", ")));
+                }
+                footnotes.push(text);
+            }
+            rows.push(vec![display, node.value.clone(), format_time(node.updated)]);
+        }
+        pdf.table(
+            &[150.0, CONTENT_WIDTH - 250.0, 100.0],
+            &["Name", "Value", "Updated"],
+            &rows,
+            9.0,
+        );
+        for text in footnotes {
+            pdf.paragraph(Font::Regular, 8.0, &text);
+        }
+    }
+}
+
+fn chronology(pdf: &mut PdfWriter, data: &ReportData) {
+    pdf.heading(18.0, "Chronology");
+    pdf.paragraph(
+        Font::Regular,
+        9.0,
+        "Nodes are placed at their last update, the only time recorded for them. Links aren't dated.",
+    );
+    pdf.space(6.0);
+    pdf.paragraph(
+        Font::Regular,
+        10.0,
+        &format!(
+            "{}  Project \"{}\" created",
+            format_time(data.project.creationdate),
+            data.project.name
+        ),
+    );
+    let mut nodes: Vec<&node::Model> = data.nodes.iter().collect();
+    nodes.sort_by_key(|node| (node.updated, node.id));
+    for node in nodes {
+        pdf.paragraph(
+            Font::Regular,
+            10.0,
+            &format!(
+                "{}  {} \"{}\": {}",
+                format_time(node.updated),
+                node.node_type,
+                node.display,
+                truncate(&node.value, 200)
+            ),
+        );
+    }
+}
+
+fn evidence_appendix(pdf: &mut PdfWriter, data: ReportData) {
+    pdf.heading(18.0, "Evidence appendix");
+    if data.attachments.is_empty() {
+        pdf.paragraph(Font::Regular, 10.0, "This project has no attachments.");
+        return;
+    }
+    let ReportData {
+        nodes,
+        attachments,
+        mut thumbnails,
+        ..
+    } = data;
+    let by_id: HashMap<Uuid, &node::Model> = nodes.iter().map(|node| (node.id, node)).collect();
+
+    for attachment in attachments {
+        let image = thumbnails
+            .remove(&attachment.id)
+            .and_then(|jpeg| pdf.add_jpeg(jpeg));
+        let mut lines = vec![
+            format!(
+                "Node: {}",
+                by_id
+                    .get(&attachment.node_id)
+                    .map(|node| format!("{} ({})", node.display, node.node_type))
+                    .unwrap_or_else(|| attachment.node_id.to_string())
+            ),
+            format!(
+                "Type: {}, {} bytes",
+                attachment.content_type, attachment.size
+            ),
+            format!(
+                "SHA-256: {}",
+                attachment.sha256.as_deref().unwrap_or("not recorded yet")
+            ),
+            format!(
+                "Custody: uploaded {}, stored {}-compressed",
+                format_time(attachment.created),
+                attachment.codec.content_coding()
+            ),
+            format!("Attachment ID: {}", attachment.id),
+        ];
+        if let Some(media) = &attachment.media {
+            let mut details = vec![media.container.clone()];
+            if let Some(duration_ms) = media.duration_ms {
+                details.push(format!("{:.1}s", duration_ms as f64 / 1000.0));
+            }
+            if let (Some(width), Some(height)) = (media.width, media.height) {
+                details.push(format!("{width}x{height}"));
+            }
+            lines.push(format!("Media: {}", details.join(", ")));
+        }
+
+        let line_height = 8.0 * 1.3;
+        let text_height = 12.0 + line_height * lines.len() as f64;
+        let height = match image {
+            Some(_) => text_height.max(THUMBNAIL_SIZE),
+            None => text_height,
+        } + 10.0;
+        pdf.ensure_space(height);
+        let top = pdf.cursor();
+        let text_x = match image {
+            Some(image) => {
+                // fit the thumbnail in a square, keeping its aspect ratio
+                let scale = THUMBNAIL_SIZE / image.width.max(image.height) as f64;
+                let (width, height) = (image.width as f64 * scale, image.height as f64 * scale);
+                pdf.image(image, MARGIN, top - height, width, height);
+                MARGIN + THUMBNAIL_SIZE + 10.0
+            }
+            None => MARGIN,
+        };
+        pdf.text_at(
+            text_x,
+            top - 10.0,
+            Font::Bold,
+            10.0,
+            &truncate(&attachment.filename, 80),
+        );
+        for (index, line) in lines.iter().enumerate() {
+            pdf.text_at(
+                text_x,
+                top - 12.0 - line_height * (index + 1) as f64,
+                Font::Regular,
+                8.0,
+                &truncate(line, 110),
+            );
+        }
+        pdf.space(height);
+    }
+}
+
+/// Where a background report has got to
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportJobState {
+    Running,
+    Done,
+    Failed,
+}
+
+struct ReportJob {
+    project_id: Uuid,
+    started: DateTime<Utc>,
+    finished: Option<DateTime<Utc>>,
+    result: Option<Result<Arc<Vec<u8>>, String>>,
+}
+
+/// Background report jobs, shared with the tasks building them
+#[derive(Clone, Default)]
+pub struct ReportJobs(Arc<Mutex<HashMap<Uuid, ReportJob>>>);
+
+impl ReportJobs {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<Uuid, ReportJob>> {
+        // a panic while holding the lock can't leave the map half-updated
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Register a new running job, forgetting any which finished a while ago
+    fn start(&self, project_id: Uuid) -> Uuid {
+        let now = Utc::now();
+        let mut jobs = self.lock();
+        jobs.retain(|_, job| match job.finished {
+            Some(finished) => (now - finished).to_std().unwrap_or_default() < REPORT_JOB_TTL,
+            None => true,
+        });
+        let job_id = Uuid::new_v4();
+        jobs.insert(
+            job_id,
+            ReportJob {
+                project_id,
+                started: now,
+                finished: None,
+                result: None,
+            },
+        );
+        job_id
+    }
+
+    fn finish(&self, job_id: Uuid, result: Result<Vec<u8>, String>) {
+        if let Some(job) = self.lock().get_mut(&job_id) {
+            job.finished = Some(Utc::now());
+            job.result = Some(result.map(Arc::new));
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ReportJobStatus {
+    pub job_id: Uuid,
+    pub project_id: Uuid,
+    pub state: ReportJobState,
+    pub started: DateTime<Utc>,
+    pub finished: Option<DateTime<Utc>>,
+    /// Where to fetch the PDF from once the job is done
+    pub status_url: String,
+}
+
+/// A project name as a plain ASCII filename so every client can read the header
+pub(crate) fn ascii_filename(project_name: &str) -> String {
+    project_name
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if !matches!(c, '"' | '\\') => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+fn pdf_response(project_name: &str, pdf: Vec<u8>) -> Result<Response, WebError> {
+    let filename = ascii_filename(project_name);
+    Ok((
+        [
+            (CONTENT_TYPE, HeaderValue::from_static(PDF_CONTENT_TYPE)),
+            (
+                CONTENT_DISPOSITION,
+                HeaderValue::from_str(&format!("attachment; filename=\"{filename}.report.pdf\""))?,
+            ),
+        ],
+        pdf,
+    )
+        .into_response())
+}
+
+async fn build_report(
+    conn: &DatabaseConnection,
+    project_id: Uuid,
+    styles: NodeTypeStyles,
+    max_layout_nodes: u64,
+    redacted: bool,
+) -> Result<Option<(String, Vec<u8>)>, WebError> {
+    let Some(data) = load_report(conn, project_id, redacted).await? else {
+        return Ok(None);
+    };
+    let name = data.project.name.clone();
+    // layout and rendering are CPU bound, keep them off the async workers
+    let pdf = tokio::task::spawn_blocking(move || render_report(data, &styles, max_layout_nodes))
+        .await
+        .map_err(|err| {
+            error!(error = ?err, "Report task failed");
+            WebError::internal_server_error("Failed to render report")
+        })?;
+    Ok(Some((name, pdf)))
+}
+
+/// A PDF report of the whole project, or a background job for big projects
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/export/report.pdf",
+    tag = "exports",
+    operation_id = "export_project_report",
+    params(
+        ("id" = Uuid, Path, description = "Project ID to report on"),
+        ("redact" = bool, Query, description = "Replace values with placeholders and strip attachments and project metadata")
+    ),
+    responses(
+        (status = OK, description = "The report", content_type = "application/pdf", body = Vec<u8>),
+        (status = ACCEPTED, description = "The project is big, the report is being built by the returned job", body = ReportJobStatus),
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user"),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse)
+    )
+)]
+pub async fn export_project_report(
+    Path(id): Path<Uuid>,
+    Query(query): Query<ExportQuery>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Response, WebError> {
+    let reader = state.read().await;
+    let project = project::Entity::find_by_id(id)
+        .one(&reader.conn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Project {} not found", id)))?;
+    check_project_access(&reader.conn, &project, auth_user.as_deref()).await?;
+
+    let node_count = node::Entity::find_live()
+        .filter(node::Column::ProjectId.eq(id))
+        .count(&reader.conn)
+        .await?;
+    let styles = reader.node_type_styles.clone();
+    let max_layout_nodes = reader.max_layout_nodes;
+
+    if node_count <= reader.report_sync_max_nodes {
+        let (name, pdf) = build_report(&reader.conn, id, styles, max_layout_nodes, query.redact)
+            .await?
+            .ok_or_else(|| WebError::not_found(format!("Project {} not found", id)))?;
+        return pdf_response(&name, pdf);
+    }
+
+    let jobs = reader.report_jobs.clone();
+    let job_id = jobs.start(id);
+    let conn = reader.conn.clone();
+    tokio::spawn(async move {
+        let result = match build_report(&conn, id, styles, max_layout_nodes, query.redact).await {
+            Ok(Some((_, pdf))) => Ok(pdf),
+            Ok(None) => Err(format!("Project {id} was deleted")),
+            Err(err) => Err(err.message().to_string()),
+        };
+        match &result {
+            Ok(pdf) => info!(
+                job_id = job_id.to_string(),
+                project_id = id.to_string(),
+                bytes = pdf.len(),
+                "Finished report job"
+            ),
+            Err(err) => error!(
+                job_id = job_id.to_string(),
+                error = err,
+                "Report job failed"
+            ),
+        }
+        jobs.finish(job_id, result);
+    });
+    info!(
+        job_id = job_id.to_string(),
+        project_id = id.to_string(),
+        node_count,
+        "Started report job"
+    );
+
+    let status_url = format!("/api/v1/report-jobs/{job_id}");
+    Ok((
+        StatusCode::ACCEPTED,
+        [(LOCATION, HeaderValue::from_str(&status_url)?)],
+        Json(ReportJobStatus {
+            job_id,
+            project_id: id,
+            state: ReportJobState::Running,
+            started: Utc::now(),
+            finished: None,
+            status_url,
+        }),
+    )
+        .into_response())
+}
+
+/// The PDF from a report job once it's done, otherwise how it's going
+#[utoipa::path(
+    get,
+    path = "/api/v1/report-jobs/{id}",
+    tag = "exports",
+    operation_id = "get_report_job",
+    params(
+        ("id" = Uuid, Path, description = "Job ID from the report export")
+    ),
+    responses(
+        (status = OK, description = "The finished report", content_type = "application/pdf", body = Vec<u8>),
+        (status = ACCEPTED, description = "Still running", body = ReportJobStatus),
+        (status = FORBIDDEN, description = "Project belongs to another user"),
+        (status = NOT_FOUND, description = "No such job, or it finished over an hour ago", body = ErrorResponse),
+        (status = INTERNAL_SERVER_ERROR, description = "The report couldn't be built", body = ErrorResponse)
+    )
+)]
+pub async fn get_report_job(
+    Path(job_id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Response, WebError> {
+    let reader = state.read().await;
+    let (project_id, started, finished, result) = {
+        let jobs = reader.report_jobs.lock();
+        let job = jobs
+            .get(&job_id)
+            .ok_or_else(|| WebError::not_found(format!("Report job {job_id} not found")))?;
+        (
+            job.project_id,
+            job.started,
+            job.finished,
+            job.result.clone(),
+        )
+    };
+    let project = project::Entity::find_by_id(project_id)
+        .one(&reader.conn)
+        .await?;
+    if let Some(project) = &project {
+        check_project_access(&reader.conn, project, auth_user.as_deref()).await?;
+    }
+
+    match result {
+        None => Ok((
+            StatusCode::ACCEPTED,
+            Json(ReportJobStatus {
+                job_id,
+                project_id,
+                state: ReportJobState::Running,
+                started,
+                finished,
+                status_url: format!("/api/v1/report-jobs/{job_id}"),
+            }),
+        )
+            .into_response()),
+        Some(Ok(pdf)) => pdf_response(
+            project
+                .as_ref()
+                .map(|project| project.name.as_str())
+                .unwrap_or("report"),
+            pdf.as_ref().clone(),
+        ),
+        Some(Err(message)) => Err(WebError::internal_server_error(message)
+            .with_code(REPORT_FAILED)
+            .with_detail("job_id", job_id.to_string())),
+    }
+}
@@ -0,0 +1,327 @@
+//! The `osint-graph restore` command, putting a backup back in place of the database
+//!
+//! The backup has to open and pass `integrity_check` first. The current database, along with
+//! its WAL files, is moved aside rather than deleted, and only with `--force`. Restoring never
+//! happens while a server holds the lock from [lock_database]. Backups from before this version
+//! are migrated once they're in place.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use sea_orm::{ConnectionTrait, Database, DatabaseConnection, Statement};
+use sea_orm_migration::MigratorTrait;
+use serde::Serialize;
+
+use crate::{
+    cli::RestoreOpts,
+    migration::Migrator,
+    storage::{connect, lock_database, lock_path},
+};
+
+#[derive(Debug)]
+pub enum RestoreError {
+    /// Something went wrong along the way
+    Failed(String),
+    /// The backup is missing, corrupt, or from a newer version
+    InvalidBackup(String),
+    /// There's already a database and `--force` wasn't given
+    DatabaseExists(PathBuf),
+    /// A server holds the lock on the database
+    ServerRunning(String),
+}
+
+impl RestoreError {
+    /// Distinct for each kind of failure so scripts can tell them apart, 2 is a usage error
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            RestoreError::Failed(_) => 1,
+            RestoreError::InvalidBackup(_) => 3,
+            RestoreError::DatabaseExists(_) => 4,
+            RestoreError::ServerRunning(_) => 5,
+        }
+    }
+}
+
+impl fmt::Display for RestoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RestoreError::Failed(msg)
+            | RestoreError::InvalidBackup(msg)
+            | RestoreError::ServerRunning(msg) => f.write_str(msg),
+            RestoreError::DatabaseExists(path) => write!(
+                f,
+                "{} already exists, use --force to move it aside and restore over it",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for RestoreError {
+    fn from(err: io::Error) -> Self {
+        RestoreError::Failed(err.to_string())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BackupInfo {
+    pub path: PathBuf,
+    pub size: u64,
+    pub applied: Vec<String>,
+    /// Migrations this version has which the backup doesn't, applied when it's restored
+    pub pending: Vec<String>,
+}
+
+impl BackupInfo {
+    /// The last migration the backup has, which is its schema version
+    pub fn schema_version(&self) -> &str {
+        self.applied.last().map_or("none", String::as_str)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestoreReport {
+    /// Where the database which was replaced went, unset if there wasn't one
+    pub moved_aside: Option<PathBuf>,
+    /// Migrations applied because the backup predates this version
+    pub applied: Vec<String>,
+}
+
+/// Open a database without any chance of changing it
+async fn open_read_only(path: &Path) -> Result<DatabaseConnection, String> {
+    Database::connect(format!("sqlite://{}?mode=ro", path.display()))
+        .await
+        .map_err(|err| err.to_string())
+}
+
+/// Check a backup opens and passes `integrity_check`, and find which migrations it has
+pub async fn inspect_backup(path: &Path) -> Result<BackupInfo, RestoreError> {
+    let invalid = |reason: String| {
+        RestoreError::InvalidBackup(format!("{} can't be restored: {reason}", path.display()))
+    };
+    if !path.is_file() {
+        return Err(RestoreError::InvalidBackup(format!(
+            "No backup at {}",
+            path.display()
+        )));
+    }
+    let size = fs::metadata(path)?.len();
+    let conn = open_read_only(path).await.map_err(invalid)?;
+    let result = async {
+        let backend = conn.get_database_backend();
+        let problems: Vec<String> = conn
+            .query_all(Statement::from_string(backend, "PRAGMA integrity_check"))
+            .await
+            .map_err(|err| invalid(err.to_string()))?
+            .iter()
+            .filter_map(|row| row.try_get_by_index::<String>(0).ok())
+            .filter(|line| line != "ok")
+            .collect();
+        if !problems.is_empty() {
+            return Err(invalid(format!(
+                "integrity_check failed: {}",
+                problems.join("; ")
+            )));
+        }
+        let applied: Vec<String> = conn
+            .query_all(Statement::from_string(
+                backend,
+                "SELECT version FROM seaql_migrations ORDER BY version",
+            ))
+            .await
+            .map_err(|_| invalid("it isn't an osint-graph database".to_string()))?
+            .iter()
+            .map(|row| row.try_get_by_index::<String>(0))
+            .collect::<Result<_, _>>()
+            .map_err(|err| invalid(err.to_string()))?;
+        let known: Vec<String> = Migrator::migrations()
+            .iter()
+            .map(|migration| migration.name().to_string())
+            .collect();
+        let unknown: Vec<&str> = applied
+            .iter()
+            .filter(|name| !known.contains(name))
+            .map(String::as_str)
+            .collect();
+        if !unknown.is_empty() {
+            return Err(invalid(format!(
+                "it's from a newer version, with migrations this one doesn't know: {}",
+                unknown.join(", ")
+            )));
+        }
+        let pending = known
+            .into_iter()
+            .filter(|name| !applied.contains(name))
+            .collect();
+        Ok(BackupInfo {
+            path: path.to_path_buf(),
+            size,
+            applied,
+            pending,
+        })
+    }
+    .await;
+    let _ = conn.close().await;
+    result
+}
+
+/// Every `.sqlite3` file in `dir` except the database itself, by name so timestamped backups
+/// come out oldest first
+pub async fn list_backups(
+    dir: &Path,
+    db_path: &Path,
+) -> Result<Vec<(PathBuf, Result<BackupInfo, RestoreError>)>, RestoreError> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|err| RestoreError::Failed(format!("Failed to read {}: {err}", dir.display())))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.is_file()
+                && path.extension().is_some_and(|ext| ext == "sqlite3")
+                && path.file_name() != db_path.file_name()
+        })
+        .collect();
+    paths.sort();
+    let mut backups = Vec::with_capacity(paths.len());
+    for path in paths {
+        let info = inspect_backup(&path).await;
+        backups.push((path, info));
+    }
+    Ok(backups)
+}
+
+/// `path` with `suffix` added to the end of its file name
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// Where the database goes when a backup replaces it, next to it and timestamped
+pub fn aside_path(db_path: &Path) -> PathBuf {
+    let stem = db_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "osint-graph".to_string());
+    db_path.with_file_name(format!(
+        "{stem}.{}.pre-restore.sqlite3",
+        Utc::now().format("%Y%m%dT%H%M%SZ")
+    ))
+}
+
+/// Replace the database at `db_path` with `backup`, see the module docs for the checks
+pub async fn restore_backup(
+    db_path: &Path,
+    backup: &Path,
+    force: bool,
+) -> Result<RestoreReport, RestoreError> {
+    let info = inspect_backup(backup).await?;
+    // held until the restore's done, so a server can't start on a half-restored database
+    let _lock = lock_database(db_path).map_err(|err| match err.kind() {
+        io::ErrorKind::WouldBlock => {
+            let pid = fs::read_to_string(lock_path(db_path)).unwrap_or_default();
+            RestoreError::ServerRunning(format!(
+                "A server is using {} (pid {}), stop it before restoring",
+                db_path.display(),
+                pid.trim()
+            ))
+        }
+        _ => RestoreError::Failed(format!(
+            "Failed to lock {}: {err}",
+            lock_path(db_path).display()
+        )),
+    })?;
+
+    let moved_aside = if db_path.exists() {
+        if !force {
+            return Err(RestoreError::DatabaseExists(db_path.to_path_buf()));
+        }
+        let aside = aside_path(db_path);
+        if aside.exists() {
+            return Err(RestoreError::Failed(format!(
+                "{} already exists, not moving the database over it",
+                aside.display()
+            )));
+        }
+        for suffix in ["", "-wal", "-shm"] {
+            let from = with_suffix(db_path, suffix);
+            if from.exists() {
+                fs::rename(&from, with_suffix(&aside, suffix)).map_err(|err| {
+                    RestoreError::Failed(format!("Failed to move {} aside: {err}", from.display()))
+                })?;
+            }
+        }
+        Some(aside)
+    } else {
+        None
+    };
+
+    // copied alongside first, so the database only appears once it's complete
+    let partial = with_suffix(db_path, ".restoring");
+    fs::copy(backup, &partial)
+        .and_then(|_| fs::rename(&partial, db_path))
+        .map_err(|err| {
+            let _ = fs::remove_file(&partial);
+            RestoreError::Failed(format!(
+                "Failed to copy {} to {}: {err}",
+                backup.display(),
+                db_path.display()
+            ))
+        })?;
+
+    if !info.pending.is_empty() {
+        let conn = connect(Some(&db_path.to_path_buf())).await?;
+        Migrator::up(&conn, None).await.map_err(|err| {
+            RestoreError::Failed(format!(
+                "Restored {}, but migrating it failed: {err}",
+                db_path.display()
+            ))
+        })?;
+        let _ = conn.close().await;
+    }
+    Ok(RestoreReport {
+        moved_aside,
+        applied: info.pending,
+    })
+}
+
+/// Run `osint-graph restore`, printing what happened
+pub async fn run(db_path: &Path, opts: &RestoreOpts) -> Result<(), RestoreError> {
+    if opts.list_backups {
+        let dir = opts
+            .backup_dir
+            .as_deref()
+            .or_else(|| db_path.parent())
+            .unwrap_or_else(|| Path::new("."));
+        for (path, info) in list_backups(dir, db_path).await? {
+            match info {
+                Ok(info) => println!(
+                    "{}\t{}\t{}\t{} pending",
+                    path.display(),
+                    info.size,
+                    info.schema_version(),
+                    info.pending.len()
+                ),
+                Err(err) => println!("{}\t-\tinvalid\t{err}", path.display()),
+            }
+        }
+        return Ok(());
+    }
+
+    let Some(backup) = opts.backup.as_deref() else {
+        return Err(RestoreError::Failed(
+            "Give a backup to restore, or --list-backups".to_string(),
+        ));
+    };
+    let report = restore_backup(db_path, backup, opts.force).await?;
+    if let Some(aside) = report.moved_aside {
+        println!("Moved {} to {}", db_path.display(), aside.display());
+    }
+    println!("Restored {} to {}", backup.display(), db_path.display());
+    for name in &report.applied {
+        println!("  applied  {name}");
+    }
+    Ok(())
+}
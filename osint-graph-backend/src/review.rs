@@ -0,0 +1,333 @@
+//! Evidence completeness review
+//!
+//! Before a case ships every node should be justified. Each [ReviewCheck] is a pure function of
+//! a node and the data hanging off it, so [review_nodes] loads the nodes and their attachment
+//! counts once and evaluates the checklist in memory.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+};
+
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use osint_graph_shared::node::NodeType;
+use sea_orm::{ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    access::find_accessible_project,
+    entity::{attachment, node},
+    extract::{Path, Query, INVALID_QUERY_PARAMETER},
+    oauth::middleware::AuthUser,
+    project::{ErrorResponse, WebError},
+    SharedState,
+};
+
+#[derive(
+    Clone, Copy, Debug, Deserialize, Serialize, ToSchema, PartialEq, Eq, Hash, PartialOrd, Ord,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewCheck {
+    /// At least one file is attached to the node
+    HasAttachment,
+    /// The node has notes explaining it
+    HasNotes,
+    /// The value looks right for the node type, eg an IP address parses
+    ValueValidates,
+}
+
+impl ReviewCheck {
+    pub const ALL: [ReviewCheck; 3] = [
+        ReviewCheck::HasAttachment,
+        ReviewCheck::HasNotes,
+        ReviewCheck::ValueValidates,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReviewCheck::HasAttachment => "has_attachment",
+            ReviewCheck::HasNotes => "has_notes",
+            ReviewCheck::ValueValidates => "value_validates",
+        }
+    }
+
+    /// Whether `node`, which has `attachments` files attached, passes this check
+    pub fn passes(&self, node: &node::Model, attachments: u64) -> bool {
+        match self {
+            ReviewCheck::HasAttachment => has_attachment(attachments),
+            ReviewCheck::HasNotes => has_notes(node.notes.as_deref()),
+            ReviewCheck::ValueValidates => value_validates(node.node_type, &node.value),
+        }
+    }
+}
+
+impl FromStr for ReviewCheck {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ReviewCheck::ALL
+            .into_iter()
+            .find(|check| check.as_str() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown check {s:?}, expected one of {}",
+                    ReviewCheck::ALL.map(|check| check.as_str()).join(", ")
+                )
+            })
+    }
+}
+
+pub fn has_attachment(attachments: u64) -> bool {
+    attachments > 0
+}
+
+pub fn has_notes(notes: Option<&str>) -> bool {
+    notes.is_some_and(|notes| !notes.trim().is_empty())
+}
+
+/// A loose sanity check of a node's value for its type, free-form types only need a value
+///
+/// Phone numbers are checked here too, see [NodeType::validate_value] for the rest.
+pub fn value_validates(node_type: NodeType, value: &str) -> bool {
+    let value = value.trim();
+    if value.is_empty() {
+        return false;
+    }
+    match node_type {
+        NodeType::Phone => {
+            value.chars().filter(char::is_ascii_digit).count() >= 5
+                && value
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || " +-().".contains(c))
+        }
+        _ => node_type.validate_value(value).is_ok(),
+    }
+}
+
+/// Parse a comma-separated `checks` query parameter, defaulting to every check
+pub fn parse_checks(checks: Option<&str>) -> Result<Vec<ReviewCheck>, WebError> {
+    let Some(value) = checks else {
+        return Ok(ReviewCheck::ALL.to_vec());
+    };
+    let mut res = value
+        .split(',')
+        .map(str::trim)
+        .filter(|check| !check.is_empty())
+        .map(|check| {
+            check.parse::<ReviewCheck>().map_err(|err| {
+                WebError::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid query parameter `checks`: {err}"),
+                )
+                .with_code(INVALID_QUERY_PARAMETER)
+                .with_detail("parameter", Some("checks"))
+                .with_detail("value", Some(value))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    res.sort();
+    res.dedup();
+    Ok(res)
+}
+
+/// Every node in a project with the checks it fails, in display order
+pub async fn review_nodes(
+    conn: &impl ConnectionTrait,
+    project_id: Uuid,
+    checks: &[ReviewCheck],
+) -> Result<Vec<(node::Model, Vec<ReviewCheck>)>, WebError> {
+    let nodes = node::Entity::find_live()
+        .filter(node::Column::ProjectId.eq(project_id))
+        .order_by_asc(node::Column::Display)
+        .order_by_asc(node::Column::Id)
+        .all(conn)
+        .await?;
+    let attachment_counts: HashMap<Uuid, i64> = match checks.contains(&ReviewCheck::HasAttachment) {
+        true => attachment::Entity::find()
+            .select_only()
+            .column(attachment::Column::NodeId)
+            .column_as(attachment::Column::Id.count(), "count")
+            .filter(attachment::Column::NodeId.is_in(nodes.iter().map(|node| node.id)))
+            .group_by(attachment::Column::NodeId)
+            .into_tuple::<(Uuid, i64)>()
+            .all(conn)
+            .await?
+            .into_iter()
+            .collect(),
+        false => HashMap::new(),
+    };
+    Ok(nodes
+        .into_iter()
+        .map(|node| {
+            let attachments = attachment_counts.get(&node.id).copied().unwrap_or(0) as u64;
+            let failing = checks
+                .iter()
+                .copied()
+                .filter(|check| !check.passes(&node, attachments))
+                .collect();
+            (node, failing)
+        })
+        .collect())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReviewQuery {
+    /// Comma-separated checks to apply, defaults to all of them
+    #[serde(default)]
+    pub checks: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ReviewNode {
+    pub id: Uuid,
+    pub node_type: NodeType,
+    pub display: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ProjectReview {
+    pub checks: Vec<ReviewCheck>,
+    pub node_count: u64,
+    /// Nodes passing every check
+    pub complete_nodes: u64,
+    /// Percentage of node checks which pass, 100 for an empty project or checklist
+    pub completeness: f64,
+    /// Nodes failing each check, checks nobody fails are left out
+    pub failing: BTreeMap<ReviewCheck, Vec<ReviewNode>>,
+}
+
+/// Percentage of `passed` out of `total`, an empty checklist counts as complete
+pub fn completeness(passed: u64, total: u64) -> f64 {
+    match total {
+        0 => 100.0,
+        total => passed as f64 * 100.0 / total as f64,
+    }
+}
+
+/// Check every node in a project against the review checklist
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/review",
+    tag = "projects",
+    operation_id = "review_project",
+    params(
+        ("id" = Uuid, Path, description = "Project ID"),
+        ("checks" = Option<String>, Query, description = "Comma-separated checks to apply (has_attachment, has_notes, value_validates), defaults to all")
+    ),
+    responses(
+        (status = OK, description = "Nodes grouped by failing check", body = ProjectReview),
+        (status = BAD_REQUEST, description = "Invalid path or query parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse)
+    )
+)]
+pub async fn review_project(
+    Path(id): Path<Uuid>,
+    Query(query): Query<ReviewQuery>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<ProjectReview>, WebError> {
+    let checks = parse_checks(query.checks.as_deref())?;
+    let conn = &state.read().await.conn;
+    find_accessible_project(conn, id, auth_user.as_deref()).await?;
+
+    let reviewed = review_nodes(conn, id, &checks).await?;
+    let node_count = reviewed.len() as u64;
+    let mut complete_nodes = 0;
+    let mut failed_checks = 0;
+    let mut failing: BTreeMap<ReviewCheck, Vec<ReviewNode>> = BTreeMap::new();
+    for (node, failed) in reviewed {
+        if failed.is_empty() {
+            complete_nodes += 1;
+        }
+        failed_checks += failed.len() as u64;
+        for check in failed {
+            failing.entry(check).or_default().push(ReviewNode {
+                id: node.id,
+                node_type: node.node_type,
+                display: node.display.clone(),
+            });
+        }
+    }
+    let total_checks = node_count * checks.len() as u64;
+
+    Ok(Json(ProjectReview {
+        checks,
+        node_count,
+        complete_nodes,
+        completeness: completeness(total_checks - failed_checks, total_checks),
+        failing,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_attachment() {
+        assert!(!has_attachment(0));
+        assert!(has_attachment(1));
+        assert!(has_attachment(12));
+    }
+
+    #[test]
+    fn test_has_notes() {
+        assert!(!has_notes(None));
+        assert!(!has_notes(Some("")));
+        assert!(!has_notes(Some("  \n\t")));
+        assert!(has_notes(Some("Seen on their about page")));
+    }
+
+    #[test]
+    fn test_value_validates() {
+        for (node_type, value, expected) in [
+            (NodeType::Ip, "10.0.0.1", true),
+            (NodeType::Ip, "2001:db8::1", true),
+            (NodeType::Ip, "10.0.0.300", false),
+            (NodeType::Url, "https://example.com/about", true),
+            (NodeType::Url, "ftp://example.com", false),
+            (NodeType::Url, "example.com", false),
+            (NodeType::Email, "user@example.com", true),
+            (NodeType::Email, "user@localhost", false),
+            (NodeType::Email, "@example.com", false),
+            (NodeType::Email, "a@b@example.com", false),
+            (NodeType::Domain, "example.com.", true),
+            (NodeType::Domain, "sub.exa-mple.com", true),
+            (NodeType::Domain, "example", false),
+            (NodeType::Domain, "-bad.example.com", false),
+            (NodeType::Domain, "two words.com", false),
+            (NodeType::Phone, "+61 (2) 9999-1234", true),
+            (NodeType::Phone, "1234", false),
+            (NodeType::Phone, "call 555 1234", false),
+            (NodeType::Person, "Jane Citizen", true),
+            (NodeType::Person, "   ", false),
+        ] {
+            assert_eq!(
+                value_validates(node_type, value),
+                expected,
+                "{node_type} {value:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_checks() {
+        assert_eq!(parse_checks(None).ok(), Some(ReviewCheck::ALL.to_vec()));
+        assert_eq!(
+            parse_checks(Some("has_notes, has_attachment,has_notes")).ok(),
+            Some(vec![ReviewCheck::HasAttachment, ReviewCheck::HasNotes])
+        );
+        assert_eq!(parse_checks(Some("")).ok(), Some(vec![]));
+        assert!(parse_checks(Some("has_source")).is_err());
+    }
+
+    #[test]
+    fn test_completeness() {
+        assert_eq!(completeness(0, 0), 100.0);
+        assert_eq!(completeness(3, 4), 75.0);
+        assert_eq!(completeness(0, 4), 0.0);
+    }
+}
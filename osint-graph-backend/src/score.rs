@@ -0,0 +1,190 @@
+//! Project completeness score
+//!
+//! A rough "how fleshed out is this investigation" number for prioritising, computed from the
+//! same per-node checks as [crate::review]. There's no verified flag on nodes, so values which
+//! pass [crate::review::value_validates] stand in for verified ones.
+
+use axum::{extract::State, Extension, Json};
+use sea_orm::{ColumnTrait, PaginatorTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    access::find_accessible_project,
+    entity::nodelink,
+    extract::Path,
+    oauth::middleware::AuthUser,
+    project::{ErrorResponse, WebError},
+    review::{review_nodes, ReviewCheck},
+    SharedState,
+};
+
+/// Nodes needed before a project counts as fully sized
+pub const SCORE_TARGET_NODES: u64 = 25;
+/// Links per node needed before a project counts as fully connected
+pub const SCORE_TARGET_LINKS_PER_NODE: f64 = 1.5;
+
+pub const SCORE_FORMULA: &str = "score = round(25 * min(nodes / 25, 1) \
+    + 20 * min(links / nodes / 1.5, 1) \
+    + 20 * nodes_with_notes / nodes \
+    + 15 * nodes_with_attachments / nodes \
+    + 20 * nodes_with_valid_values / nodes), 0 for an empty project";
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ScoreComponent {
+    pub name: String,
+    /// Most points this component can add
+    pub weight: u32,
+    /// How complete this component is, from 0 to 1
+    pub value: f64,
+    /// `weight * value`
+    pub points: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ProjectScore {
+    pub project_id: Uuid,
+    /// From 0 to 100
+    pub score: u8,
+    pub components: Vec<ScoreComponent>,
+    /// How the score is worked out
+    pub formula: String,
+}
+
+/// Counts the score is computed from
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ScoreInputs {
+    pub nodes: u64,
+    pub links: u64,
+    pub with_notes: u64,
+    pub with_attachments: u64,
+    pub with_valid_values: u64,
+}
+
+fn fraction(count: u64, total: u64) -> f64 {
+    match total {
+        0 => 0.0,
+        total => (count as f64 / total as f64).min(1.0),
+    }
+}
+
+/// The score and its breakdown, see [SCORE_FORMULA]
+pub fn compute_score(inputs: ScoreInputs) -> (u8, Vec<ScoreComponent>) {
+    let link_density = match inputs.nodes {
+        0 => 0.0,
+        nodes => (inputs.links as f64 / nodes as f64 / SCORE_TARGET_LINKS_PER_NODE).min(1.0),
+    };
+    let components: Vec<ScoreComponent> = [
+        ("node_count", 25, fraction(inputs.nodes, SCORE_TARGET_NODES)),
+        ("link_density", 20, link_density),
+        ("notes", 20, fraction(inputs.with_notes, inputs.nodes)),
+        (
+            "attachments",
+            15,
+            fraction(inputs.with_attachments, inputs.nodes),
+        ),
+        (
+            "verified",
+            20,
+            fraction(inputs.with_valid_values, inputs.nodes),
+        ),
+    ]
+    .into_iter()
+    .map(|(name, weight, value)| ScoreComponent {
+        name: name.to_string(),
+        weight,
+        value,
+        points: weight as f64 * value,
+    })
+    .collect();
+    let score = components
+        .iter()
+        .map(|component| component.points)
+        .sum::<f64>()
+        .round()
+        .clamp(0.0, 100.0) as u8;
+    (score, components)
+}
+
+/// Score how complete a project is, with the breakdown
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/score",
+    tag = "projects",
+    operation_id = "score_project",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    responses(
+        (status = OK, description = "Completeness score and its components", body = ProjectScore),
+        (status = BAD_REQUEST, description = "Invalid path parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse)
+    )
+)]
+pub async fn score_project(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<ProjectScore>, WebError> {
+    let conn = &state.read().await.conn;
+    find_accessible_project(conn, id, auth_user.as_deref()).await?;
+
+    let mut inputs = ScoreInputs {
+        links: nodelink::Entity::find_live()
+            .filter(nodelink::Column::ProjectId.eq(id))
+            .count(conn)
+            .await?,
+        ..Default::default()
+    };
+    for (_, failing) in review_nodes(conn, id, &ReviewCheck::ALL).await? {
+        inputs.nodes += 1;
+        inputs.with_notes += !failing.contains(&ReviewCheck::HasNotes) as u64;
+        inputs.with_attachments += !failing.contains(&ReviewCheck::HasAttachment) as u64;
+        inputs.with_valid_values += !failing.contains(&ReviewCheck::ValueValidates) as u64;
+    }
+    let (score, components) = compute_score(inputs);
+
+    Ok(Json(ProjectScore {
+        project_id: id,
+        score,
+        components,
+        formula: SCORE_FORMULA.to_string(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_score() {
+        assert_eq!(compute_score(ScoreInputs::default()).0, 0);
+
+        let full = ScoreInputs {
+            nodes: 40,
+            links: 80,
+            with_notes: 40,
+            with_attachments: 40,
+            with_valid_values: 40,
+        };
+        let (score, components) = compute_score(full);
+        assert_eq!(score, 100);
+        assert_eq!(
+            components.iter().map(|c| c.weight).sum::<u32>(),
+            100,
+            "weights should add up to 100"
+        );
+
+        let half = ScoreInputs {
+            nodes: 10,
+            links: 5,
+            with_notes: 5,
+            with_attachments: 0,
+            with_valid_values: 10,
+        };
+        // 25 * 0.4 + 20 * (0.5 / 1.5) + 20 * 0.5 + 0 + 20
+        assert_eq!(compute_score(half).0, 47);
+    }
+}
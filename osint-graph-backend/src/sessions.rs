@@ -0,0 +1,45 @@
+//! Session store housekeeping
+//!
+
+use std::time::Duration;
+
+use sqlx::{Pool, Sqlite};
+use tokio::task::JoinHandle;
+use tower_sessions::ExpiredDeletion;
+use tower_sessions_sqlx_store::SqliteStore;
+use tracing::{debug, error};
+
+/// The table `tower_sessions_sqlx_store::SqliteStore` uses by default
+pub const SESSION_TABLE: &str = "tower_sessions";
+
+/// Default interval between expired session cleanups, in seconds
+pub const DEFAULT_SESSION_CLEANUP_INTERVAL: u64 = 3600;
+
+/// Remove any expired sessions from the store
+pub async fn delete_expired_sessions(store: &SqliteStore) -> Result<(), String> {
+    store
+        .delete_expired()
+        .await
+        .map_err(|err| format!("Failed to delete expired sessions: {err:?}"))
+}
+
+/// Spawns a task which prunes expired sessions every `interval`
+pub fn spawn_session_cleanup(store: SqliteStore, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match delete_expired_sessions(&store).await {
+                Ok(()) => debug!("Cleaned up expired sessions"),
+                Err(err) => error!(error = err, "Session cleanup failed"),
+            }
+        }
+    })
+}
+
+/// Count the sessions currently held in the store
+pub async fn session_count(pool: &Pool<Sqlite>) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>(&format!("SELECT COUNT(*) FROM {SESSION_TABLE}"))
+        .fetch_one(pool)
+        .await
+}
@@ -0,0 +1,560 @@
+//! Scheduled project snapshots, so long-running cases keep a history without anyone exporting
+//!
+//! A project's [export_settings] row says how often to snapshot it and how many snapshots to keep.
+//! [run_due_snapshots] takes a JSON export of every project which is due, skipping projects whose
+//! [project_fingerprint] hasn't changed since the last snapshot, and prunes the oldest beyond the
+//! limit. [spawn_snapshot_scheduler] runs it every [SNAPSHOT_CHECK_INTERVAL].
+
+use std::{collections::BTreeMap, time::Duration};
+
+use axum::{
+    extract::State,
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    sea_query::OnConflict, ActiveValue::Set, ColumnTrait, ConnectionTrait, DatabaseConnection,
+    DbErr, EntityTrait, ModelTrait, QueryFilter, QueryOrder, QuerySelect,
+};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    access::find_accessible_project,
+    entity::{export_settings, node, nodelink, project, project_snapshot},
+    export_cache::project_fingerprint,
+    extract::Path,
+    oauth::middleware::AuthUser,
+    project::{project_export_without_data, ErrorResponse, ProjectExport, WebError},
+    SharedState,
+};
+
+/// Error code when snapshot settings are out of range
+pub const INVALID_SNAPSHOT_SETTINGS: &str = "invalid_snapshot_settings";
+/// Error code when two snapshots can't be compared
+pub const SNAPSHOT_PROJECT_MISMATCH: &str = "snapshot_project_mismatch";
+
+/// How often the scheduler looks for projects which are due
+pub const SNAPSHOT_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Shorter intervals than this would snapshot on every check
+pub const MIN_SNAPSHOT_INTERVAL_SECS: u64 = 60;
+pub const DEFAULT_SNAPSHOT_KEEP: u32 = 10;
+pub const MAX_SNAPSHOT_KEEP: u32 = 100;
+
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+pub struct SnapshotSettings {
+    /// Seconds between snapshots, `null` to turn scheduled snapshots off. A week is 604800
+    #[serde(default)]
+    pub interval_secs: Option<u64>,
+    /// Snapshots kept before the oldest are pruned
+    #[serde(default = "default_snapshot_keep")]
+    pub keep: u32,
+    /// When the scheduler last looked at the project
+    #[serde(default)]
+    pub last_run: Option<DateTime<Utc>>,
+}
+
+fn default_snapshot_keep() -> u32 {
+    DEFAULT_SNAPSHOT_KEEP
+}
+
+impl Default for SnapshotSettings {
+    fn default() -> Self {
+        Self {
+            interval_secs: None,
+            keep: DEFAULT_SNAPSHOT_KEEP,
+            last_run: None,
+        }
+    }
+}
+
+impl From<export_settings::Model> for SnapshotSettings {
+    fn from(settings: export_settings::Model) -> Self {
+        Self {
+            interval_secs: settings
+                .snapshot_interval_secs
+                .map(|secs| secs.max(0) as u64),
+            keep: settings.snapshot_keep.max(0) as u32,
+            last_run: settings.last_snapshot_run,
+        }
+    }
+}
+
+/// A snapshot without its content
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct SnapshotInfo {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub created: DateTime<Utc>,
+    /// Bytes of JSON
+    pub size: u64,
+    /// Fingerprint of the project's content, equal fingerprints mean nothing changed
+    pub fingerprint: String,
+}
+
+/// What a scheduler run did
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SnapshotRun {
+    pub created: u64,
+    /// Due projects which hadn't changed since their last snapshot
+    pub unchanged: u64,
+    pub pruned: u64,
+}
+
+/// Snapshot every project which is due at `now`
+pub async fn run_due_snapshots(
+    conn: &DatabaseConnection,
+    now: DateTime<Utc>,
+) -> Result<SnapshotRun, DbErr> {
+    let mut run = SnapshotRun::default();
+    let scheduled = export_settings::Entity::find()
+        .filter(export_settings::Column::SnapshotIntervalSecs.is_not_null())
+        .all(conn)
+        .await?;
+    for settings in scheduled {
+        let due = match (settings.snapshot_interval_secs, settings.last_snapshot_run) {
+            (Some(_), None) => true,
+            (Some(interval), Some(last_run)) => {
+                last_run + chrono::Duration::seconds(interval) <= now
+            }
+            (None, _) => false,
+        };
+        if !due {
+            continue;
+        }
+        let Some(project) = project::Entity::find_by_id(settings.project_id)
+            .one(conn)
+            .await?
+        else {
+            continue;
+        };
+
+        let fingerprint = project_fingerprint(conn, &project, &()).await?;
+        let latest: Option<String> = project_snapshot::Entity::find()
+            .select_only()
+            .column(project_snapshot::Column::Fingerprint)
+            .filter(project_snapshot::Column::ProjectId.eq(project.id))
+            .order_by_desc(project_snapshot::Column::Created)
+            .into_tuple()
+            .one(conn)
+            .await?;
+        if latest.as_ref() == Some(&fingerprint) {
+            debug!(
+                project_id = project.id.to_string(),
+                "Project unchanged, no snapshot"
+            );
+            run.unchanged += 1;
+        } else {
+            take_snapshot(conn, project.clone(), fingerprint, now).await?;
+            run.created += 1;
+        }
+        run.pruned +=
+            prune_snapshots(conn, project.id, settings.snapshot_keep.max(1) as u64).await?;
+
+        export_settings::Entity::update_many()
+            .col_expr(
+                export_settings::Column::LastSnapshotRun,
+                sea_orm::sea_query::Expr::value(now),
+            )
+            .filter(export_settings::Column::ProjectId.eq(project.id))
+            .exec(conn)
+            .await?;
+    }
+    Ok(run)
+}
+
+async fn take_snapshot(
+    conn: &impl ConnectionTrait,
+    project: project::Model,
+    fingerprint: String,
+    now: DateTime<Utc>,
+) -> Result<project_snapshot::Model, DbErr> {
+    let project_id = project.id;
+    let nodes = project
+        .find_related(node::Entity)
+        .filter(node::Column::DeletedAt.is_null())
+        .all(conn)
+        .await?;
+    let nodelinks = project
+        .find_related(nodelink::Entity)
+        .filter(nodelink::ends_live())
+        .all(conn)
+        .await?;
+    let mut export = project_export_without_data(conn, project, nodes, nodelinks).await?;
+    export.exported_at = now;
+    let content = serde_json::to_string(&export)
+        .map_err(|err| DbErr::Custom(format!("Failed to serialise snapshot: {err}")))?;
+
+    let snapshot = project_snapshot::Model {
+        id: Uuid::new_v4(),
+        project_id,
+        created: now,
+        fingerprint,
+        size: content.len() as i64,
+        content,
+    };
+    project_snapshot::Entity::insert(project_snapshot::ActiveModel::from(snapshot.clone()))
+        .exec_without_returning(conn)
+        .await?;
+    info!(
+        project_id = project_id.to_string(),
+        snapshot_id = snapshot.id.to_string(),
+        bytes = snapshot.size,
+        "Took project snapshot"
+    );
+    Ok(snapshot)
+}
+
+/// Delete all but the newest `keep` snapshots of a project
+async fn prune_snapshots(
+    conn: &impl ConnectionTrait,
+    project_id: Uuid,
+    keep: u64,
+) -> Result<u64, DbErr> {
+    let prune: Vec<Uuid> = project_snapshot::Entity::find()
+        .select_only()
+        .column(project_snapshot::Column::Id)
+        .filter(project_snapshot::Column::ProjectId.eq(project_id))
+        .order_by_desc(project_snapshot::Column::Created)
+        // SQLite won't take an OFFSET without a LIMIT
+        .limit(i64::MAX as u64)
+        .offset(keep)
+        .into_tuple()
+        .all(conn)
+        .await?;
+    if prune.is_empty() {
+        return Ok(0);
+    }
+    Ok(project_snapshot::Entity::delete_many()
+        .filter(project_snapshot::Column::Id.is_in(prune))
+        .exec(conn)
+        .await?
+        .rows_affected)
+}
+
+/// Spawns a task which takes due snapshots every [SNAPSHOT_CHECK_INTERVAL]
+pub fn spawn_snapshot_scheduler(conn: DatabaseConnection) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        // no hurry at startup, and waiting keeps the first check out of the way of migrations
+        let mut ticker = tokio::time::interval_at(
+            tokio::time::Instant::now() + SNAPSHOT_CHECK_INTERVAL,
+            SNAPSHOT_CHECK_INTERVAL,
+        );
+        loop {
+            ticker.tick().await;
+            match run_due_snapshots(&conn, Utc::now()).await {
+                Ok(run) => debug!(
+                    created = run.created,
+                    unchanged = run.unchanged,
+                    pruned = run.pruned,
+                    "Checked project snapshots"
+                ),
+                Err(err) => error!(error = ?err, "Project snapshots failed"),
+            }
+        }
+    })
+}
+
+async fn find_accessible_snapshot(
+    conn: &impl ConnectionTrait,
+    id: Uuid,
+    auth_user: Option<&AuthUser>,
+) -> Result<project_snapshot::Model, WebError> {
+    let snapshot = project_snapshot::Entity::find_by_id(id)
+        .one(conn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Snapshot {} not found", id)))?;
+    find_accessible_project(conn, snapshot.project_id, auth_user).await?;
+    Ok(snapshot)
+}
+
+/// A project's snapshot schedule
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/snapshot-settings",
+    tag = "exports",
+    operation_id = "get_snapshot_settings",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    responses(
+        (status = OK, description = "Snapshot settings, off unless they've been set", body = SnapshotSettings),
+        (status = BAD_REQUEST, description = "Invalid path parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse)
+    )
+)]
+pub async fn get_snapshot_settings(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<SnapshotSettings>, WebError> {
+    let conn = &state.read().await.conn;
+    find_accessible_project(conn, id, auth_user.as_deref()).await?;
+    let settings = export_settings::Entity::find_by_id(id)
+        .one(conn)
+        .await?
+        .map(SnapshotSettings::from)
+        .unwrap_or_default();
+    Ok(Json(settings))
+}
+
+/// Turn scheduled snapshots on or off, or change how many are kept
+///
+/// `last_run` is ignored, so a new schedule's first snapshot is taken on the next check.
+#[utoipa::path(
+    put,
+    path = "/api/v1/project/{id}/snapshot-settings",
+    tag = "exports",
+    operation_id = "update_snapshot_settings",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    request_body = SnapshotSettings,
+    responses(
+        (status = OK, description = "Updated snapshot settings", body = SnapshotSettings),
+        (status = BAD_REQUEST, description = "Interval or retention out of range", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse)
+    )
+)]
+pub async fn update_snapshot_settings(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+    Json(settings): Json<SnapshotSettings>,
+) -> Result<Json<SnapshotSettings>, WebError> {
+    let conn = &state.read().await.conn;
+    find_accessible_project(conn, id, auth_user.as_deref()).await?;
+
+    if settings
+        .interval_secs
+        .is_some_and(|secs| !(MIN_SNAPSHOT_INTERVAL_SECS..=i64::MAX as u64).contains(&secs))
+    {
+        return Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            format!("interval_secs must be at least {MIN_SNAPSHOT_INTERVAL_SECS}"),
+        )
+        .with_code(INVALID_SNAPSHOT_SETTINGS)
+        .with_detail("interval_secs", settings.interval_secs));
+    }
+    if !(1..=MAX_SNAPSHOT_KEEP).contains(&settings.keep) {
+        return Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            format!("keep must be between 1 and {MAX_SNAPSHOT_KEEP}"),
+        )
+        .with_code(INVALID_SNAPSHOT_SETTINGS)
+        .with_detail("keep", settings.keep));
+    }
+
+    export_settings::Entity::insert(export_settings::ActiveModel {
+        project_id: Set(id),
+        snapshot_interval_secs: Set(settings.interval_secs.map(|secs| secs as i64)),
+        snapshot_keep: Set(settings.keep as i32),
+        last_snapshot_run: Set(None),
+    })
+    .on_conflict(
+        OnConflict::column(export_settings::Column::ProjectId)
+            .update_columns([
+                export_settings::Column::SnapshotIntervalSecs,
+                export_settings::Column::SnapshotKeep,
+            ])
+            .to_owned(),
+    )
+    .exec_without_returning(conn)
+    .await?;
+    let settings = export_settings::Entity::find_by_id(id)
+        .one(conn)
+        .await?
+        .map(SnapshotSettings::from)
+        .unwrap_or_default();
+    Ok(Json(settings))
+}
+
+/// A project's snapshots, newest first, without their content
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/snapshots",
+    tag = "exports",
+    operation_id = "get_project_snapshots",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    responses(
+        (status = OK, description = "Snapshots, newest first", body = Vec<SnapshotInfo>),
+        (status = BAD_REQUEST, description = "Invalid path parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse)
+    )
+)]
+pub async fn get_project_snapshots(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<Vec<SnapshotInfo>>, WebError> {
+    let conn = &state.read().await.conn;
+    find_accessible_project(conn, id, auth_user.as_deref()).await?;
+    let snapshots: Vec<(Uuid, DateTime<Utc>, i64, String)> = project_snapshot::Entity::find()
+        .select_only()
+        .columns([
+            project_snapshot::Column::Id,
+            project_snapshot::Column::Created,
+            project_snapshot::Column::Size,
+            project_snapshot::Column::Fingerprint,
+        ])
+        .filter(project_snapshot::Column::ProjectId.eq(id))
+        .order_by_desc(project_snapshot::Column::Created)
+        .into_tuple()
+        .all(conn)
+        .await?;
+    Ok(Json(
+        snapshots
+            .into_iter()
+            .map(|(snapshot_id, created, size, fingerprint)| SnapshotInfo {
+                id: snapshot_id,
+                project_id: id,
+                created,
+                size: size.max(0) as u64,
+                fingerprint,
+            })
+            .collect(),
+    ))
+}
+
+/// Download a snapshot, in the same format as `/api/v1/project/{id}/export`
+#[utoipa::path(
+    get,
+    path = "/api/v1/snapshot/{id}",
+    tag = "exports",
+    operation_id = "get_snapshot",
+    params(
+        ("id" = Uuid, Path, description = "Snapshot ID")
+    ),
+    responses(
+        (status = OK, description = "The project as it was when the snapshot was taken", body = ProjectExport),
+        (status = BAD_REQUEST, description = "Invalid path parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Snapshot not found", body = ErrorResponse)
+    )
+)]
+pub async fn get_snapshot(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<impl IntoResponse, WebError> {
+    let conn = &state.read().await.conn;
+    let snapshot = find_accessible_snapshot(conn, id, auth_user.as_deref()).await?;
+    Ok(([(CONTENT_TYPE, "application/json")], snapshot.content))
+}
+
+/// IDs which were added, removed or changed between two snapshots
+#[derive(Clone, Debug, Default, Deserialize, Serialize, ToSchema)]
+pub struct IdDiff {
+    pub added: Vec<Uuid>,
+    pub removed: Vec<Uuid>,
+    pub changed: Vec<Uuid>,
+}
+
+impl IdDiff {
+    fn between<T: Serialize>(
+        from: &[T],
+        to: &[T],
+        id: impl Fn(&T) -> Uuid,
+    ) -> Result<Self, WebError> {
+        let values = |items: &[T]| -> Result<BTreeMap<Uuid, serde_json::Value>, WebError> {
+            items
+                .iter()
+                .map(|item| Ok((id(item), serde_json::to_value(item)?)))
+                .collect()
+        };
+        let (from, to) = (values(from)?, values(to)?);
+        let mut diff = Self::default();
+        for (item_id, value) in to.iter() {
+            match from.get(item_id) {
+                None => diff.added.push(*item_id),
+                Some(old) if old != value => diff.changed.push(*item_id),
+                Some(_) => {}
+            }
+        }
+        diff.removed = from
+            .keys()
+            .filter(|id| !to.contains_key(id))
+            .copied()
+            .collect();
+        Ok(diff)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct SnapshotDiff {
+    pub from: SnapshotInfo,
+    pub to: SnapshotInfo,
+    /// Whether the project's own fields, like its name or tags, changed
+    pub project_changed: bool,
+    pub nodes: IdDiff,
+    pub nodelinks: IdDiff,
+    pub attachments: IdDiff,
+}
+
+fn parse_snapshot(snapshot: &project_snapshot::Model) -> Result<ProjectExport, WebError> {
+    serde_json::from_str(&snapshot.content).map_err(|err| {
+        error!(snapshot_id = snapshot.id.to_string(), error = ?err, "Unreadable snapshot");
+        WebError::internal_server_error(format!("Snapshot {} is unreadable", snapshot.id))
+    })
+}
+
+/// What changed in a project between two of its snapshots
+#[utoipa::path(
+    get,
+    path = "/api/v1/snapshot/{id}/diff/{other_id}",
+    tag = "exports",
+    operation_id = "diff_snapshots",
+    params(
+        ("id" = Uuid, Path, description = "Snapshot to compare from, usually the older one"),
+        ("other_id" = Uuid, Path, description = "Snapshot to compare to")
+    ),
+    responses(
+        (status = OK, description = "Added, removed and changed IDs", body = SnapshotDiff),
+        (status = BAD_REQUEST, description = "The snapshots are of different projects", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Project belongs to another user", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Snapshot not found", body = ErrorResponse)
+    )
+)]
+pub async fn diff_snapshots(
+    Path((id, other_id)): Path<(Uuid, Uuid)>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<SnapshotDiff>, WebError> {
+    let conn = &state.read().await.conn;
+    let from = find_accessible_snapshot(conn, id, auth_user.as_deref()).await?;
+    let to = find_accessible_snapshot(conn, other_id, auth_user.as_deref()).await?;
+    if from.project_id != to.project_id {
+        return Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            "Only snapshots of the same project can be compared",
+        )
+        .with_code(SNAPSHOT_PROJECT_MISMATCH));
+    }
+    let (old, new) = (parse_snapshot(&from)?, parse_snapshot(&to)?);
+
+    let info = |snapshot: project_snapshot::Model| SnapshotInfo {
+        id: snapshot.id,
+        project_id: snapshot.project_id,
+        created: snapshot.created,
+        size: snapshot.size.max(0) as u64,
+        fingerprint: snapshot.fingerprint,
+    };
+    Ok(Json(SnapshotDiff {
+        project_changed: serde_json::to_value(&old.project)? != serde_json::to_value(&new.project)?,
+        nodes: IdDiff::between(&old.nodes, &new.nodes, |node| node.id)?,
+        nodelinks: IdDiff::between(&old.nodelinks, &new.nodelinks, |link| link.id)?,
+        attachments: IdDiff::between(&old.attachments, &new.attachments, |a| a.id)?,
+        from: info(from),
+        to: info(to),
+    }))
+}
@@ -0,0 +1,350 @@
+//! Splitting one node into several
+//!
+
+use std::collections::HashMap;
+
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use chrono::Utc;
+use osint_graph_shared::node::NodeType;
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, QueryFilter,
+    QuerySelect,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    access::check_node_access,
+    entity::{
+        attachment, node,
+        node_history::{self, NodeChange},
+        nodelink,
+    },
+    extract::Path,
+    oauth::middleware::AuthUser,
+    project::{ErrorResponse, WebError},
+    quota::{warning_headers, QuotaKind},
+    SharedState,
+};
+
+/// A new node to create from the original
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct NodeSplitSpec {
+    pub node_type: NodeType,
+    pub display: String,
+    pub value: String,
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+/// Where an attachment or link of the original node should end up
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SplitTarget {
+    /// Leave it on the original node
+    Keep,
+    /// Move it to the new node at this index in the request's `nodes` list
+    Node(usize),
+}
+
+/// Error code for `delete_original` while attachments or links would be left on the original
+pub const SPLIT_UNASSIGNED_CHILDREN: &str = "split_unassigned_children";
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct NodeSplitRequest {
+    pub nodes: Vec<NodeSplitSpec>,
+    /// Attachment ID to target, attachments which aren't listed stay on the original
+    #[serde(default)]
+    pub attachments: HashMap<Uuid, SplitTarget>,
+    /// Nodelink ID to target, links which aren't listed stay on the original
+    #[serde(default)]
+    pub links: HashMap<Uuid, SplitTarget>,
+    /// Link each new node back to the original node
+    #[serde(default)]
+    pub link_to_original: bool,
+    /// Move the original to the trash, only allowed once every attachment and link is assigned
+    /// to a new node
+    #[serde(default)]
+    pub delete_original: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct MovedItem {
+    pub id: Uuid,
+    pub node_id: Uuid,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct NodeSplitResponse {
+    pub nodes: Vec<node::Model>,
+    pub moved_attachments: Vec<MovedItem>,
+    pub moved_links: Vec<MovedItem>,
+    pub created_links: Vec<nodelink::Model>,
+    pub original_deleted: bool,
+}
+
+fn validate_target(target: SplitTarget, node_count: usize, item: &str) -> Result<(), WebError> {
+    match target {
+        SplitTarget::Node(index) if index >= node_count => Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "{} is assigned to new node {} but only {} nodes were specified",
+                item, index, node_count
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Split a node into several new nodes, redistributing its attachments and links
+#[utoipa::path(
+    post,
+    path = "/api/v1/node/{id}/split",
+    tag = "nodes",
+    operation_id = "split_node",
+    params(
+        ("id" = Uuid, Path, description = "Node ID")
+    ),
+    request_body = NodeSplitRequest,
+    responses(
+        (status = OK, description = "Node split successfully", body = NodeSplitResponse),
+        (status = BAD_REQUEST, description = "Invalid split request, or delete_original with attachments or links left unassigned", body = ErrorResponse),
+        (status = FORBIDDEN, description = "Node belongs to another user's project", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Node not found"),
+        (status = UNPROCESSABLE_ENTITY, description = "A node breaks a value policy rule", body = ErrorResponse)
+    )
+)]
+pub async fn split_node(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+    Json(request): Json<NodeSplitRequest>,
+) -> Result<(axum::http::HeaderMap, Json<NodeSplitResponse>), WebError> {
+    if request.nodes.is_empty() {
+        return Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            "At least one new node must be specified",
+        ));
+    }
+    for (attachment_id, target) in request.attachments.iter() {
+        validate_target(
+            *target,
+            request.nodes.len(),
+            &format!("Attachment {attachment_id}"),
+        )?;
+    }
+    for (link_id, target) in request.links.iter() {
+        validate_target(*target, request.nodes.len(), &format!("Nodelink {link_id}"))?;
+    }
+
+    let reader = state.read().await;
+    let txn = reader.begin().await?;
+
+    let original = node::Entity::find_by_id(id)
+        .filter(node::Column::DeletedAt.is_null())
+        .one(&txn)
+        .await?
+        .ok_or_else(|| WebError::not_found(format!("Node {} not found", id)))?;
+    check_node_access(&txn, id, auth_user.as_deref()).await?;
+
+    // make sure everything we're moving actually belongs to the original
+    let attachment_ids: Vec<Uuid> = attachment::Entity::find()
+        .select_only()
+        .column(attachment::Column::Id)
+        .filter(attachment::Column::NodeId.eq(id))
+        .into_tuple()
+        .all(&txn)
+        .await?;
+    if let Some(missing) = request
+        .attachments
+        .keys()
+        .find(|a| !attachment_ids.contains(a))
+    {
+        return Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Attachment {} does not belong to node {}", missing, id),
+        ));
+    }
+    let links: HashMap<Uuid, nodelink::Model> = nodelink::Entity::find()
+        .filter(
+            nodelink::Column::Left
+                .eq(id)
+                .or(nodelink::Column::Right.eq(id)),
+        )
+        .all(&txn)
+        .await?
+        .into_iter()
+        .map(|link| (link.id, link))
+        .collect();
+    if let Some(missing) = request.links.keys().find(|l| !links.contains_key(l)) {
+        return Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            format!("Nodelink {} is not linked to node {}", missing, id),
+        ));
+    }
+    // unassigned children stay put, so there has to be somewhere for them to stay
+    if request.delete_original {
+        let assigned = |targets: &HashMap<Uuid, SplitTarget>, id: &Uuid| {
+            matches!(targets.get(id), Some(SplitTarget::Node(_)))
+        };
+        let mut unassigned_attachments: Vec<String> = attachment_ids
+            .iter()
+            .filter(|a| !assigned(&request.attachments, a))
+            .map(Uuid::to_string)
+            .collect();
+        let mut unassigned_links: Vec<String> = links
+            .keys()
+            .filter(|l| !assigned(&request.links, l))
+            .map(Uuid::to_string)
+            .collect();
+        if !unassigned_attachments.is_empty() || !unassigned_links.is_empty() {
+            unassigned_attachments.sort();
+            unassigned_links.sort();
+            return Err(WebError::new(
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Can't delete node {} with {} attachments and {} links left on it, assign them to a new node",
+                    id,
+                    unassigned_attachments.len(),
+                    unassigned_links.len()
+                ),
+            )
+            .with_code(SPLIT_UNASSIGNED_CHILDREN)
+            .with_detail("attachments", unassigned_attachments)
+            .with_detail("links", unassigned_links));
+        }
+    }
+
+    let quota_kind = QuotaKind::NodesPerProject(original.project_id);
+    reader
+        .quota
+        .check(
+            &txn,
+            quota_kind,
+            request.nodes.len() as u64,
+            auth_user.as_deref(),
+        )
+        .await?;
+
+    let created_by = AuthUser::created_by(auth_user.as_deref());
+    let mut new_nodes = Vec::with_capacity(request.nodes.len());
+    for spec in request.nodes {
+        let mut new_node = node::Model {
+            id: Uuid::new_v4(),
+            project_id: original.project_id,
+            node_type: spec.node_type,
+            display: spec.display,
+            value: spec.value,
+            updated: Utc::now(),
+            notes: spec.notes,
+            pos_x: original.pos_x,
+            pos_y: original.pos_y,
+            created_by: created_by.clone(),
+            created: Some(Utc::now()),
+            deleted_at: None,
+        };
+        reader.value_policy.apply(&mut new_node)?;
+        let new_node = new_node
+            .into_active_model()
+            .insert(&txn)
+            .await
+            .inspect_err(|err| error!(error=?err, "Failed to insert split node"))?;
+        new_nodes.push(new_node);
+    }
+
+    let mut moved_attachments = Vec::new();
+    for (attachment_id, target) in request.attachments {
+        let SplitTarget::Node(index) = target else {
+            continue;
+        };
+        let node_id = new_nodes[index].id;
+        attachment::Entity::update_many()
+            .col_expr(attachment::Column::NodeId, Expr::value(node_id))
+            .filter(attachment::Column::Id.eq(attachment_id))
+            .exec(&txn)
+            .await?;
+        moved_attachments.push(MovedItem {
+            id: attachment_id,
+            node_id,
+        });
+    }
+
+    let mut moved_links = Vec::new();
+    for (link_id, target) in request.links {
+        let SplitTarget::Node(index) = target else {
+            continue;
+        };
+        let node_id = new_nodes[index].id;
+        let link = links[&link_id].clone();
+        let mut active = link.clone().into_active_model();
+        if link.left == id {
+            active.left = sea_orm::Set(node_id);
+        }
+        if link.right == id {
+            active.right = sea_orm::Set(node_id);
+        }
+        active.update(&txn).await?;
+        moved_links.push(MovedItem {
+            id: link_id,
+            node_id,
+        });
+    }
+
+    let mut created_links = Vec::new();
+    if request.link_to_original && !request.delete_original {
+        for new_node in new_nodes.iter() {
+            let link = nodelink::Model {
+                id: Uuid::new_v4(),
+                left: id,
+                right: new_node.id,
+                project_id: original.project_id,
+                linktype: reader.default_link_type,
+                weight: None,
+                kind: None,
+                valid_from: None,
+                valid_to: None,
+                created_by: created_by.clone(),
+                created: Some(Utc::now()),
+            }
+            .into_active_model()
+            .insert(&txn)
+            .await?;
+            created_links.push(link);
+        }
+    }
+
+    // to the trash, as delete_node does, with nothing left on it to hide
+    if request.delete_original {
+        node_history::ActiveModel::record(&original, NodeChange::Delete, created_by.clone())?
+            .insert(&txn)
+            .await?;
+        let mut original = original.clone().into_active_model();
+        original.deleted_at = sea_orm::Set(Some(Utc::now()));
+        original.update(&txn).await?;
+    }
+
+    txn.commit().await?;
+    info!(
+        node_id = id.to_string(),
+        new_nodes = new_nodes.len(),
+        moved_attachments = moved_attachments.len(),
+        moved_links = moved_links.len(),
+        deleted_original = request.delete_original,
+        "Split node"
+    );
+    debug!("Split node {} into {:?}", id, new_nodes);
+
+    let warning = reader.quota.record(quota_kind, new_nodes.len() as u64);
+    Ok((
+        warning_headers(warning),
+        Json(NodeSplitResponse {
+            nodes: new_nodes,
+            moved_attachments,
+            moved_links,
+            created_links,
+            original_deleted: request.delete_original,
+        }),
+    ))
+}
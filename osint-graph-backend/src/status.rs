@@ -0,0 +1,209 @@
+//! Instance status reporting
+//!
+
+use axum::{
+    extract::State,
+    http::{header, HeaderValue, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+use sea_orm::{ConnectionTrait, DatabaseConnection};
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+use utoipa::ToSchema;
+
+use crate::{capabilities::Capabilities, project::WebError, sessions::session_count, SharedState};
+
+/// Default time the readiness check waits for the database, in milliseconds
+pub const DEFAULT_READINESS_TIMEOUT_MS: u64 = 2000;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StatusResponse {
+    pub version: String,
+    /// Number of sessions currently held in the session store
+    pub sessions: i64,
+    /// Resource limits which have crossed the soft warning threshold
+    pub quota_warnings: Vec<String>,
+    pub capabilities: Capabilities,
+}
+
+/// Get runtime status information about the instance
+#[utoipa::path(
+    get,
+    path = "/api/v1/status",
+    tag = "status",
+    operation_id = "get_status",
+    responses(
+        (status = OK, description = "Instance status", body = StatusResponse)
+    )
+)]
+pub async fn get_status(
+    State(state): State<SharedState>,
+) -> Result<Json<StatusResponse>, WebError> {
+    let reader = state.read().await;
+    let sessions = session_count(reader.conn.get_sqlite_connection_pool())
+        .await
+        .map_err(|err| {
+            error!(error=?err, "Failed to count sessions");
+            WebError::internal_server_error(format!("Failed to count sessions: {err}"))
+        })?;
+
+    Ok(Json(StatusResponse {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        sessions,
+        quota_warnings: reader.quota.warnings(),
+        capabilities: Capabilities::from_state(&reader),
+    }))
+}
+
+/// Readiness probe, checks the database answers within `--readiness-timeout-ms`
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "status",
+    operation_id = "get_readyz",
+    responses(
+        (status = OK, description = "Ready to serve requests", body = String),
+        (status = SERVICE_UNAVAILABLE, description = "The database didn't answer in time", body = crate::project::ErrorResponse)
+    )
+)]
+pub async fn get_readyz(State(state): State<SharedState>) -> Result<&'static str, WebError> {
+    let (conn, timeout) = {
+        let reader = state.read().await;
+        (reader.conn.clone(), reader.readiness_timeout)
+    };
+    probe_database(&conn, timeout)
+        .await
+        .map(|_| "ok")
+        .map_err(|err| WebError::new(StatusCode::SERVICE_UNAVAILABLE, err))
+}
+
+/// Run `SELECT 1`, giving up after `timeout`
+async fn probe_database(conn: &DatabaseConnection, timeout: Duration) -> Result<(), String> {
+    // a deadlocked or saturated database shouldn't hang the probe too
+    match tokio::time::timeout(timeout, conn.execute_unprepared("SELECT 1")).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(err)) => {
+            error!(error=?err, "Database check failed");
+            Err(format!("Database check failed: {err}"))
+        }
+        Err(_) => {
+            warn!(
+                timeout_ms = timeout.as_millis() as u64,
+                "Database check timed out"
+            );
+            Err(format!(
+                "Database check timed out after {}ms",
+                timeout.as_millis()
+            ))
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthState {
+    Ok,
+    /// Running, but the database didn't answer
+    Degraded,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct HealthStatus {
+    pub status: HealthState,
+    pub db_ok: bool,
+    pub version: String,
+    /// Seconds since the server started
+    pub uptime_seconds: u64,
+}
+
+/// Health check for load balancers, with the same database probe as `/readyz`
+#[utoipa::path(
+    get,
+    path = "/api/v1/health",
+    tag = "status",
+    operation_id = "health_check",
+    responses(
+        (status = OK, description = "Healthy", body = HealthStatus),
+        (status = SERVICE_UNAVAILABLE, description = "The database didn't answer within --readiness-timeout-ms", body = HealthStatus)
+    )
+)]
+pub async fn health_check(State(state): State<SharedState>) -> impl IntoResponse {
+    let (conn, timeout, started) = {
+        let reader = state.read().await;
+        (
+            reader.conn.clone(),
+            reader.readiness_timeout,
+            reader.started,
+        )
+    };
+    let db_ok = probe_database(&conn, timeout).await.is_ok();
+    let health = HealthStatus {
+        status: if db_ok {
+            HealthState::Ok
+        } else {
+            HealthState::Degraded
+        },
+        db_ok,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        uptime_seconds: started.elapsed().as_secs(),
+    };
+    let status = if db_ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        [(header::CACHE_CONTROL, HeaderValue::from_static("no-store"))],
+        Json(health),
+    )
+}
+
+/// Unix time the backend was built, from build.rs
+const BUILD_TIMESTAMP: &str = env!("OSINT_GRAPH_BUILD_TIMESTAMP");
+/// Commit the backend was built from, empty if build.rs couldn't find it
+const GIT_SHA: &str = env!("OSINT_GRAPH_GIT_SHA");
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BuildInfo {
+    pub backend_version: String,
+    pub build_timestamp: DateTime<Utc>,
+    /// Unset when built outside a git checkout
+    pub git_sha: Option<String>,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        Self {
+            backend_version: env!("CARGO_PKG_VERSION").to_string(),
+            build_timestamp: BUILD_TIMESTAMP
+                .parse()
+                .ok()
+                .and_then(|secs| DateTime::from_timestamp(secs, 0))
+                .unwrap_or_default(),
+            git_sha: (!GIT_SHA.is_empty()).then(|| GIT_SHA.to_string()),
+        }
+    }
+}
+
+/// Which build is running, so a loaded frontend can tell it's been replaced and reload
+#[utoipa::path(
+    get,
+    path = "/api/v1/build-info",
+    tag = "status",
+    operation_id = "get_build_info",
+    responses(
+        (status = OK, description = "Version, build time and commit of the server", body = BuildInfo)
+    )
+)]
+pub async fn get_build_info() -> impl IntoResponse {
+    (
+        // the point is noticing a deploy, so never answer from a cache
+        [(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"))],
+        Json(BuildInfo::current()),
+    )
+}
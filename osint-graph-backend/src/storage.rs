@@ -1,17 +1,63 @@
-use std::path::PathBuf;
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
 
-use sea_orm::{Database, DatabaseConnection, DbErr};
+use sea_orm::{ConnectOptions, ConnectionTrait, Database, DatabaseConnection, DbErr};
 use sea_orm_migration::MigratorTrait;
-use tracing::debug;
+use tracing::{debug, warn};
 
 use crate::migration::Migrator;
 
+/// SeaORM's default for SQLite, one connection shared by everything
+pub const DEFAULT_DB_MAX_CONNECTIONS: u32 = 1;
+
 // Start the database
-pub async fn new(db_path: &PathBuf) -> Result<DatabaseConnection, std::io::Error> {
-    start_db(Some(db_path)).await
+pub async fn new(
+    db_path: &PathBuf,
+    auto_migrate: bool,
+    max_connections: u32,
+) -> Result<DatabaseConnection, std::io::Error> {
+    let conn = connect_with(Some(db_path), max_connections).await?;
+    if auto_migrate {
+        return migrate(conn).await;
+    }
+    let pending = Migrator::get_pending_migrations(&conn)
+        .await
+        .map_err(|err| std::io::Error::other(format!("Failed to check migrations: {err:?}")))?;
+    if !pending.is_empty() {
+        return Err(std::io::Error::other(format!(
+            "The database has {} pending migrations and --no-auto-migrate is set, run `osint-graph migrate` first",
+            pending.len()
+        )));
+    }
+    Ok(conn)
 }
 
+/// Open the database and run any pending migrations
 pub async fn start_db(db_path: Option<&PathBuf>) -> Result<DatabaseConnection, std::io::Error> {
+    migrate(connect(db_path).await?).await
+}
+
+async fn migrate(conn: DatabaseConnection) -> Result<DatabaseConnection, std::io::Error> {
+    Migrator::up(&conn, None)
+        .await
+        .map_err(|err| std::io::Error::other(format!("Migration failed: {err:?}")))?;
+    Ok(conn)
+}
+
+/// Open the database as it is, creating the file if it's missing
+pub async fn connect(db_path: Option<&PathBuf>) -> Result<DatabaseConnection, std::io::Error> {
+    connect_with(db_path, DEFAULT_DB_MAX_CONNECTIONS).await
+}
+
+/// [connect] with up to `max_connections` pooled connections, in-memory databases only ever get
+/// one because each connection would have its own
+pub async fn connect_with(
+    db_path: Option<&PathBuf>,
+    max_connections: u32,
+) -> Result<DatabaseConnection, std::io::Error> {
     let db_url = match db_path {
         Some(path) => {
             let path = path.to_string_lossy().to_string();
@@ -26,13 +72,16 @@ pub async fn start_db(db_path: Option<&PathBuf>) -> Result<DatabaseConnection, s
         None => "sqlite::memory:".to_string(),
     };
     debug!("Opening Database: {db_url}");
+    let mut options = ConnectOptions::new(&db_url);
+    if db_path.is_some() {
+        options.max_connections(max_connections);
+    }
 
-    let conn = Database::connect(&db_url)
+    let conn = Database::connect(options)
         .await
         .map_err(|err| std::io::Error::other(format!("connection failed: {err:?}")))?;
 
     // Enable foreign key constraints
-    use sea_orm::ConnectionTrait;
     let _ = conn
         .execute(sea_orm::Statement::from_string(
             sea_orm::DatabaseBackend::Sqlite,
@@ -41,12 +90,60 @@ pub async fn start_db(db_path: Option<&PathBuf>) -> Result<DatabaseConnection, s
         .await
         .map_err(|err| std::io::Error::other(format!("Failed to enable foreign keys: {err:?}")))?;
 
-    // Run migrations
-    Migrator::up(&conn, None)
+    Ok(conn)
+}
+
+/// Write a consistent copy of the whole database to `dest` with `VACUUM INTO`
+///
+/// Safe while the server is running, and fails rather than overwrite an existing file.
+pub async fn backup_database(conn: &impl ConnectionTrait, dest: &Path) -> Result<(), DbErr> {
+    let dest = dest.to_string_lossy().replace('\'', "''");
+    conn.execute_unprepared(&format!("VACUUM INTO '{dest}'"))
         .await
-        .map_err(|err| std::io::Error::other(format!("Migration failed: {err:?}")))?;
+        .map(|_| ())
+}
 
-    Ok(conn)
+/// The file a server holds a lock on while it's using `db_path`
+pub fn lock_path(db_path: &Path) -> PathBuf {
+    let mut path = db_path.as_os_str().to_owned();
+    path.push(".lock");
+    PathBuf::from(path)
+}
+
+/// Lock `db_path` against `osint-graph restore` until the returned file is dropped
+///
+/// The lock goes away with the process, so a crashed server never leaves it stale. Fails with
+/// [io::ErrorKind::WouldBlock] when another process holds it, the lock file has its PID.
+pub fn lock_database(db_path: &Path) -> io::Result<File> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_path(db_path))?;
+    file.try_lock().map_err(|err| match err {
+        TryLockError::WouldBlock => io::Error::from(io::ErrorKind::WouldBlock),
+        TryLockError::Error(err) => err,
+    })?;
+    file.set_len(0)?;
+    write!(file, "{}", std::process::id())?;
+    Ok(file)
+}
+
+/// [lock_database] for a starting server, which mustn't run on a database `osint-graph restore`
+/// or another server is using
+///
+/// Fails only when another process holds the lock. If the lock can't be taken for any other
+/// reason, eg the directory is read-only, the server runs without it and this returns `None`.
+pub fn lock_database_for_server(db_path: &Path) -> io::Result<Option<File>> {
+    match lock_database(db_path) {
+        Ok(file) => Ok(Some(file)),
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock => Err(err),
+        Err(err) => {
+            warn!(error = ?err, "Failed to lock the database, restores won't know the server is running");
+            Ok(None)
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -0,0 +1,358 @@
+//! Finding and removing rows which outlived what they belong to
+//!
+//! Attachments, nodes and links are meant to go when their node or project is deleted, via
+//! `ON DELETE CASCADE`. Anything written while foreign keys were off, eg by hand or by an old
+//! build, can be left behind and keep the database file large. [find_orphans] cross-checks each
+//! table against its parent, and [post_storage_gc] deletes what it finds and vacuums the file so
+//! the space goes back to the filesystem. [post_prune_orphans] just deletes them, for when a
+//! vacuum would lock the database for too long.
+//!
+//! Deduplicated attachments share an `attachment_blob` by hash instead of a foreign key, so a blob
+//! no shared attachment has the hash of any more is an orphan too.
+
+use axum::{extract::State, Json};
+use sea_orm::{
+    sea_query::{Alias, Expr, Func, Query},
+    ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter,
+    QuerySelect, Statement, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    entity::{attachment, attachment_blob, node, nodelink, project},
+    extract::Query as QueryParams,
+    project::{ErrorResponse, WebError},
+    quota::QuotaKind,
+    SharedState,
+};
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct StorageOrphans {
+    /// Attachments whose node is gone
+    pub attachments: Vec<Uuid>,
+    /// Stored, compressed bytes held by the orphaned attachments and blobs
+    pub attachment_bytes: u64,
+    /// Hashes of shared blobs no attachment uses
+    #[serde(default)]
+    pub blobs: Vec<String>,
+    /// Nodes whose project is gone
+    pub nodes: Vec<Uuid>,
+    /// Links whose project or either end is gone
+    pub nodelinks: Vec<Uuid>,
+}
+
+impl StorageOrphans {
+    pub fn is_empty(&self) -> bool {
+        self.attachments.is_empty()
+            && self.blobs.is_empty()
+            && self.nodes.is_empty()
+            && self.nodelinks.is_empty()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DatabaseSize {
+    /// Size of the database, in bytes
+    pub total_bytes: u64,
+    /// Bytes in free pages, which only a vacuum hands back to the filesystem
+    pub free_bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OrphanReport {
+    pub orphans: StorageOrphans,
+    pub database: DatabaseSize,
+}
+
+/// Rows whose parent no longer exists
+pub async fn find_orphans(conn: &impl ConnectionTrait) -> Result<StorageOrphans, DbErr> {
+    let node_ids = Query::select()
+        .column(node::Column::Id)
+        .from(node::Entity)
+        .to_owned();
+    let project_ids = Query::select()
+        .column(project::Column::Id)
+        .from(project::Entity)
+        .to_owned();
+
+    let attachments: Vec<(Uuid, i64)> = attachment::Entity::find()
+        .select_only()
+        .column(attachment::Column::Id)
+        .column_as(
+            Expr::expr(
+                Func::cust(Alias::new("LENGTH"))
+                    .arg(Expr::col((attachment::Entity, attachment::Column::Data))),
+            ),
+            "stored_size",
+        )
+        .filter(attachment::Column::NodeId.not_in_subquery(node_ids.clone()))
+        .into_tuple()
+        .all(conn)
+        .await?;
+    let shared_hashes = Query::select()
+        .column(attachment::Column::Sha256)
+        .from(attachment::Entity)
+        .and_where(attachment::Column::SharedBlob.eq(true))
+        .and_where(attachment::Column::Sha256.is_not_null())
+        .to_owned();
+    let blobs: Vec<(String, i64)> = attachment_blob::Entity::find()
+        .select_only()
+        .column(attachment_blob::Column::Sha256)
+        .column_as(
+            Expr::expr(Func::cust(Alias::new("LENGTH")).arg(Expr::col((
+                attachment_blob::Entity,
+                attachment_blob::Column::Data,
+            )))),
+            "stored_size",
+        )
+        .filter(attachment_blob::Column::Sha256.not_in_subquery(shared_hashes))
+        .into_tuple()
+        .all(conn)
+        .await?;
+    let nodes: Vec<Uuid> = node::Entity::find()
+        .select_only()
+        .column(node::Column::Id)
+        .filter(node::Column::ProjectId.not_in_subquery(project_ids.clone()))
+        .into_tuple()
+        .all(conn)
+        .await?;
+    let nodelinks: Vec<Uuid> = nodelink::Entity::find()
+        .select_only()
+        .column(nodelink::Column::Id)
+        .filter(
+            Condition::any()
+                .add(nodelink::Column::ProjectId.not_in_subquery(project_ids))
+                .add(nodelink::Column::Left.not_in_subquery(node_ids.clone()))
+                .add(nodelink::Column::Right.not_in_subquery(node_ids)),
+        )
+        .into_tuple()
+        .all(conn)
+        .await?;
+
+    Ok(StorageOrphans {
+        attachment_bytes: attachments
+            .iter()
+            .map(|(_, size)| size)
+            .chain(blobs.iter().map(|(_, size)| size))
+            .map(|size| (*size).max(0) as u64)
+            .sum(),
+        attachments: attachments.into_iter().map(|(id, _)| id).collect(),
+        blobs: blobs.into_iter().map(|(sha256, _)| sha256).collect(),
+        nodes,
+        nodelinks,
+    })
+}
+
+pub(crate) async fn pragma(conn: &impl ConnectionTrait, name: &str) -> Result<u64, DbErr> {
+    let row = conn
+        .query_one(Statement::from_string(
+            conn.get_database_backend(),
+            format!("PRAGMA {name}"),
+        ))
+        .await?
+        .ok_or_else(|| DbErr::Custom(format!("PRAGMA {name} returned nothing")))?;
+    Ok(row.try_get_by_index::<i64>(0)?.max(0) as u64)
+}
+
+pub async fn database_size(conn: &impl ConnectionTrait) -> Result<DatabaseSize, DbErr> {
+    let page_size = pragma(conn, "page_size").await?;
+    Ok(DatabaseSize {
+        total_bytes: pragma(conn, "page_count").await? * page_size,
+        free_bytes: pragma(conn, "freelist_count").await? * page_size,
+    })
+}
+
+/// Rows left behind by deletes which didn't cascade, and how much space is waiting to be reclaimed
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/storage/orphans",
+    tag = "admin",
+    operation_id = "get_storage_orphans",
+    responses(
+        (status = OK, description = "Orphaned rows and database size", body = OrphanReport),
+        (status = INTERNAL_SERVER_ERROR, description = "Database error", body = ErrorResponse)
+    )
+)]
+pub async fn get_storage_orphans(
+    State(state): State<SharedState>,
+) -> Result<Json<OrphanReport>, WebError> {
+    let conn = &state.read().await.conn;
+    Ok(Json(OrphanReport {
+        orphans: find_orphans(conn).await?,
+        database: database_size(conn).await?,
+    }))
+}
+
+/// How to hand free pages back to the filesystem after deleting orphans
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VacuumMode {
+    /// Rebuild the whole file, which needs as much free disk as the database takes up
+    #[default]
+    Full,
+    /// Release free pages without a rebuild, only works when the database uses
+    /// `auto_vacuum = INCREMENTAL`
+    Incremental,
+    /// Leave the free pages for SQLite to reuse
+    None,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StorageGcQuery {
+    /// How to reclaim space afterwards, defaults to a full vacuum
+    #[serde(default)]
+    pub vacuum: VacuumMode,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StorageGcReport {
+    /// What was deleted
+    pub removed: StorageOrphans,
+    pub vacuum: VacuumMode,
+    /// How much smaller the database got
+    pub bytes_freed: u64,
+    pub database: DatabaseSize,
+}
+
+/// Delete orphaned rows, including attachments stranded by deleting orphaned nodes and the blobs
+/// only they used
+async fn remove_orphans(conn: &DatabaseConnection) -> Result<StorageOrphans, DbErr> {
+    let txn = conn.begin().await?;
+    let mut removed = StorageOrphans::default();
+    // nodes first, their attachments and links are orphans once they're gone
+    loop {
+        let orphans = find_orphans(&txn).await?;
+        if orphans.is_empty() {
+            break;
+        }
+        nodelink::Entity::delete_many()
+            .filter(nodelink::Column::Id.is_in(orphans.nodelinks.clone()))
+            .exec(&txn)
+            .await?;
+        attachment::Entity::delete_many()
+            .filter(attachment::Column::Id.is_in(orphans.attachments.clone()))
+            .exec(&txn)
+            .await?;
+        node::Entity::delete_many()
+            .filter(node::Column::Id.is_in(orphans.nodes.clone()))
+            .exec(&txn)
+            .await?;
+        attachment_blob::Entity::delete_many()
+            .filter(attachment_blob::Column::Sha256.is_in(orphans.blobs.clone()))
+            .exec(&txn)
+            .await?;
+        removed.attachments.extend(orphans.attachments);
+        removed.blobs.extend(orphans.blobs);
+        removed.attachment_bytes += orphans.attachment_bytes;
+        removed.nodes.extend(orphans.nodes);
+        removed.nodelinks.extend(orphans.nodelinks);
+    }
+    txn.commit().await?;
+    Ok(removed)
+}
+
+/// Delete orphaned rows and vacuum the database
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/storage/gc",
+    tag = "admin",
+    operation_id = "post_storage_gc",
+    params(StorageGcQuery),
+    responses(
+        (status = OK, description = "What was removed and how much space came back", body = StorageGcReport),
+        (status = BAD_REQUEST, description = "Invalid query parameter", body = ErrorResponse),
+        (status = INTERNAL_SERVER_ERROR, description = "Database error", body = ErrorResponse)
+    )
+)]
+pub async fn post_storage_gc(
+    State(state): State<SharedState>,
+    QueryParams(query): QueryParams<StorageGcQuery>,
+) -> Result<Json<StorageGcReport>, WebError> {
+    let reader = state.read().await;
+    let conn = &reader.conn;
+    let before = database_size(conn).await?;
+    let removed = remove_orphans(conn).await?;
+    reader.quota.invalidate([QuotaKind::AttachmentBytes]);
+
+    // VACUUM can't run inside a transaction
+    let statement = match query.vacuum {
+        VacuumMode::Full => Some("VACUUM"),
+        VacuumMode::Incremental => Some("PRAGMA incremental_vacuum"),
+        VacuumMode::None => None,
+    };
+    if let Some(statement) = statement {
+        conn.execute_unprepared(statement).await?;
+    }
+    let after = database_size(conn).await?;
+    if query.vacuum == VacuumMode::Incremental && after.free_bytes > 0 {
+        warn!(
+            free_bytes = after.free_bytes,
+            "Incremental vacuum left free pages, the database may not use auto_vacuum = INCREMENTAL"
+        );
+    }
+    let bytes_freed = before.total_bytes.saturating_sub(after.total_bytes);
+    info!(
+        attachments = removed.attachments.len(),
+        blobs = removed.blobs.len(),
+        nodes = removed.nodes.len(),
+        nodelinks = removed.nodelinks.len(),
+        bytes_freed,
+        "Storage garbage collection finished"
+    );
+
+    Ok(Json(StorageGcReport {
+        removed,
+        vacuum: query.vacuum,
+        bytes_freed,
+        database: after,
+    }))
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct PruneOrphansReport {
+    pub attachments: u64,
+    /// Compressed bytes of the deleted attachments and blobs
+    pub attachment_bytes: u64,
+    pub blobs: u64,
+    pub nodes: u64,
+    pub nodelinks: u64,
+}
+
+/// Delete orphaned attachments, nodes and links without vacuuming
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/prune-orphans",
+    tag = "admin",
+    operation_id = "post_prune_orphans",
+    responses(
+        (status = OK, description = "How many rows of each kind were deleted", body = PruneOrphansReport),
+        (status = INTERNAL_SERVER_ERROR, description = "Database error", body = ErrorResponse)
+    )
+)]
+pub async fn post_prune_orphans(
+    State(state): State<SharedState>,
+) -> Result<Json<PruneOrphansReport>, WebError> {
+    let reader = state.read().await;
+    let removed = remove_orphans(&reader.conn).await?;
+    reader.quota.invalidate([QuotaKind::AttachmentBytes]);
+    let report = PruneOrphansReport {
+        attachments: removed.attachments.len() as u64,
+        attachment_bytes: removed.attachment_bytes,
+        blobs: removed.blobs.len() as u64,
+        nodes: removed.nodes.len() as u64,
+        nodelinks: removed.nodelinks.len() as u64,
+    };
+    info!(
+        attachments = report.attachments,
+        attachment_bytes = report.attachment_bytes,
+        blobs = report.blobs,
+        nodes = report.nodes,
+        nodelinks = report.nodelinks,
+        "Pruned orphans"
+    );
+    Ok(Json(report))
+}
@@ -0,0 +1,149 @@
+//! Per-node-type styling, shared by the frontend and the exporters
+//!
+//! The built-in defaults can be overridden with a JSON file passed via `--node-type-styles-file`,
+//! which maps a node type to any of the style fields, eg `{"person": {"color": "#000000"}}`.
+
+use std::{collections::BTreeMap, path::Path};
+
+use axum::{extract::State, Json};
+use osint_graph_shared::{error::OsintError, node::NodeType};
+use sea_orm::Iterable;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use utoipa::ToSchema;
+
+use crate::SharedState;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, ToSchema, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeShape {
+    Rectangle,
+    RoundedRectangle,
+    Ellipse,
+    Diamond,
+    Hexagon,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema, PartialEq, Eq, Hash)]
+pub struct NodeTypeStyle {
+    /// CSS colour, eg `#3b82f6`
+    pub color: String,
+    pub shape: NodeShape,
+    pub icon: String,
+}
+
+impl NodeTypeStyle {
+    fn new(color: &str, shape: NodeShape, icon: &str) -> Self {
+        Self {
+            color: color.to_string(),
+            shape,
+            icon: icon.to_string(),
+        }
+    }
+
+    /// The built-in style for a node type
+    pub fn default_for(node_type: NodeType) -> Self {
+        match node_type {
+            NodeType::Person => Self::new("#3b82f6", NodeShape::Ellipse, "👤"),
+            NodeType::Domain => Self::new("#f59e0b", NodeShape::RoundedRectangle, "🌐"),
+            NodeType::Ip => Self::new("#ef4444", NodeShape::RoundedRectangle, "🖧"),
+            NodeType::Phone => Self::new("#8b5cf6", NodeShape::RoundedRectangle, "📞"),
+            NodeType::Email => Self::new("#ec4899", NodeShape::RoundedRectangle, "✉️"),
+            NodeType::Url => Self::new("#06b6d4", NodeShape::RoundedRectangle, "🔗"),
+            NodeType::Image => Self::new("#10b981", NodeShape::Rectangle, "🖼️"),
+            NodeType::Location => Self::new("#84cc16", NodeShape::Diamond, "📍"),
+            NodeType::Organisation => Self::new("#f97316", NodeShape::Hexagon, "🏢"),
+            NodeType::Document => Self::new("#6b7280", NodeShape::Rectangle, "📄"),
+            NodeType::Currency => Self::new("#c7c400ff", NodeShape::Diamond, "💰"),
+        }
+    }
+}
+
+/// A partial style from the overrides file, unset fields keep their defaults
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NodeTypeStyleOverride {
+    color: Option<String>,
+    shape: Option<NodeShape>,
+    icon: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema, PartialEq, Eq, Hash)]
+pub struct NodeTypeStyles(pub BTreeMap<NodeType, NodeTypeStyle>);
+
+impl Default for NodeTypeStyles {
+    fn default() -> Self {
+        Self(
+            NodeType::iter()
+                .map(|node_type| (node_type, NodeTypeStyle::default_for(node_type)))
+                .collect(),
+        )
+    }
+}
+
+impl NodeTypeStyles {
+    /// The defaults, with any overrides from `path` applied
+    pub fn load(path: Option<&Path>) -> Result<Self, OsintError> {
+        let mut res = Self::default();
+        let Some(path) = path else {
+            return Ok(res);
+        };
+        let contents = std::fs::read_to_string(path).map_err(|err| {
+            OsintError::Configuration(format!(
+                "Failed to read node type styles file {}: {}",
+                path.display(),
+                err
+            ))
+        })?;
+        res.apply_overrides(&contents).map_err(|err| {
+            OsintError::Configuration(format!(
+                "Failed to parse node type styles file {}: {}",
+                path.display(),
+                err
+            ))
+        })?;
+        info!("Loaded node type styles from {}", path.display());
+        Ok(res)
+    }
+
+    fn apply_overrides(&mut self, contents: &str) -> Result<(), serde_json::Error> {
+        let overrides: BTreeMap<NodeType, NodeTypeStyleOverride> = serde_json::from_str(contents)?;
+        for (node_type, style_override) in overrides {
+            let style = self
+                .0
+                .entry(node_type)
+                .or_insert_with(|| NodeTypeStyle::default_for(node_type));
+            if let Some(color) = style_override.color {
+                style.color = color;
+            }
+            if let Some(shape) = style_override.shape {
+                style.shape = shape;
+            }
+            if let Some(icon) = style_override.icon {
+                style.icon = icon;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, node_type: NodeType) -> NodeTypeStyle {
+        self.0
+            .get(&node_type)
+            .cloned()
+            .unwrap_or_else(|| NodeTypeStyle::default_for(node_type))
+    }
+}
+
+/// Get the display style for each node type
+#[utoipa::path(
+    get,
+    path = "/api/v1/node-type-styles",
+    tag = "nodes",
+    operation_id = "get_node_type_styles",
+    responses(
+        (status = OK, description = "Styles keyed by node type", body = NodeTypeStyles)
+    )
+)]
+pub async fn get_node_type_styles(State(state): State<SharedState>) -> Json<NodeTypeStyles> {
+    Json(state.read().await.node_type_styles.clone())
+}
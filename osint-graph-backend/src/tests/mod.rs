@@ -1,7 +1,7 @@
 use crate::entity::{node, project};
-use crate::project::{ProjectExport, MERMAID_CONTENT_TYPE};
+use crate::project::{PaginatedResponse, ProjectExport, MERMAID_CONTENT_TYPE};
 use crate::{build_app, AppState};
-use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_TYPE};
 use axum_test::*;
 use osint_graph_shared::node::NodeType;
 use osint_graph_shared::StringVec;
@@ -12,9 +12,17 @@ use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 use uuid::Uuid;
 
+pub(crate) mod query_budget;
+
+use query_budget::with_query_budget;
+
 static INIT: Once = Once::new();
 
 async fn setup_test_server() -> TestServer {
+    setup_test_server_with_state(AppState::test().await).await
+}
+
+async fn setup_test_server_with_state(appstate: AppState) -> TestServer {
     INIT.call_once(|| {
         tracing_subscriber::registry()
             .with(tracing_subscriber::EnvFilter::new(
@@ -23,11 +31,55 @@ async fn setup_test_server() -> TestServer {
             .with(tracing_subscriber::fmt::layer())
             .init();
     });
-    let appstate = AppState::test().await;
     let dbpool: sqlx::Pool<sqlx::Sqlite> = appstate.conn.get_sqlite_connection_pool().clone();
     let shared_state = Arc::new(RwLock::new(appstate));
     let app = build_app(&shared_state, dbpool, false).await;
 
+    test_server(app)
+}
+
+/// One test server per user sharing the same state, each acting as if that user had logged in
+async fn setup_test_servers_as_users(
+    appstate: AppState,
+    users: &[crate::oauth::middleware::AuthUser],
+) -> Vec<TestServer> {
+    let dbpool: sqlx::Pool<sqlx::Sqlite> = appstate.conn.get_sqlite_connection_pool().clone();
+    let shared_state = Arc::new(RwLock::new(appstate));
+    let mut res = Vec::new();
+    for user in users {
+        let app = build_app(&shared_state, dbpool.clone(), false)
+            .await
+            .layer(axum::Extension(user.clone()));
+        res.push(test_server(app));
+    }
+    res
+}
+
+/// Register a user for each `(subject, is_admin)`, with an `@example.com` address
+async fn new_test_users(
+    conn: &sea_orm::DatabaseConnection,
+    users: &[(&str, bool)],
+) -> Vec<crate::oauth::middleware::AuthUser> {
+    use sea_orm::{ActiveModelTrait, Set};
+
+    let mut res = Vec::new();
+    for (subject, is_admin) in users {
+        let user = crate::entity::user::ActiveModel {
+            subject: Set(subject.to_string()),
+            email: Set(format!("{subject}@example.com")),
+            uuid: Set(Uuid::new_v4()),
+            is_admin: Set(*is_admin),
+            ..Default::default()
+        }
+        .insert(conn)
+        .await
+        .expect("Failed to create user");
+        res.push(user.into());
+    }
+    res
+}
+
+fn test_server(app: axum::Router) -> TestServer {
     let config = TestServerConfig {
         // Preserve cookies across requests
         // for the session cookie to work.
@@ -68,6 +120,8 @@ async fn test_api_project_node_save_load() {
         last_updated: None,
         description: None,
         tags: StringVec::default(),
+        pinned: false,
+        is_archived: false,
     };
 
     // create the project
@@ -113,7 +167,10 @@ async fn test_api_project_node_save_load() {
     // looking for something that shouldn't exist
     let res = server.get("/api/v1/projects").expect_success().await;
     assert_eq!(res.status_code(), 200);
-    assert!(!res.json::<Vec<project::Model>>().is_empty());
+    assert!(!res
+        .json::<PaginatedResponse<project::Model>>()
+        .items
+        .is_empty());
 
     // looking for something that shouldn't exist
     let res = server
@@ -142,6 +199,8 @@ async fn test_api_get_nodes_by_project() {
         last_updated: None,
         description: None,
         tags: StringVec::empty(),
+        pinned: false,
+        is_archived: false,
     };
 
     // Create second project
@@ -153,6 +212,8 @@ async fn test_api_get_nodes_by_project() {
         last_updated: None,
         description: None,
         tags: StringVec::empty(),
+        pinned: false,
+        is_archived: false,
     };
 
     // Create both projects
@@ -177,8 +238,9 @@ async fn test_api_get_nodes_by_project() {
         .await;
     res.assert_status_ok();
     debug!("Fetched nodes for project 1");
-    let nodes: Vec<node::Model> = res.json();
-    assert!(nodes.is_empty());
+    let nodes: PaginatedResponse<node::Model> = res.json();
+    assert!(nodes.items.is_empty());
+    assert_eq!(nodes.total_count, 0);
 
     // Create nodes for first project
     let node1 = node::Model {
@@ -191,6 +253,9 @@ async fn test_api_get_nodes_by_project() {
         notes: Some("First person".to_string()),
         pos_x: Some(100),
         pos_y: Some(200),
+        created_by: None,
+        created: None,
+        deleted_at: None,
     };
 
     let node2 = node::Model {
@@ -203,6 +268,9 @@ async fn test_api_get_nodes_by_project() {
         notes: Some("Domain node".to_string()),
         pos_x: Some(300),
         pos_y: Some(400),
+        created_by: None,
+        created: None,
+        deleted_at: None,
     };
 
     // Create node for second project
@@ -216,6 +284,9 @@ async fn test_api_get_nodes_by_project() {
         notes: None,
         pos_x: Some(500),
         pos_y: Some(600),
+        created_by: None,
+        created: None,
+        deleted_at: None,
     };
 
     // Add all nodes
@@ -235,12 +306,13 @@ async fn test_api_get_nodes_by_project() {
         .await
         .assert_status_ok();
 
-    // Test getting nodes for first project
-    let res = server
-        .get(&format!("/api/v1/project/{}/nodes", project_id))
-        .await;
+    // Test getting nodes for first project: the project, counting them and fetching the page
+    let res = with_query_budget(3, || {
+        server.get(&format!("/api/v1/project/{}/nodes", project_id))
+    })
+    .await;
     res.assert_status_ok();
-    let nodes: Vec<node::Model> = res.json();
+    let nodes = res.json::<PaginatedResponse<node::Model>>().items;
     assert_eq!(nodes.len(), 2);
 
     // Verify we got the right nodes
@@ -254,7 +326,7 @@ async fn test_api_get_nodes_by_project() {
         .get(&format!("/api/v1/project/{}/nodes", other_project_id))
         .await;
     res.assert_status_ok();
-    let nodes: Vec<node::Model> = res.json();
+    let nodes = res.json::<PaginatedResponse<node::Model>>().items;
     assert_eq!(nodes.len(), 1);
     assert_eq!(nodes[0].id, other_node_id);
 
@@ -263,10 +335,319 @@ async fn test_api_get_nodes_by_project() {
         .get(&format!("/api/v1/project/{}/nodes", Uuid::new_v4()))
         .await;
     res.assert_status_ok();
-    let nodes: Vec<node::Model> = res.json();
+    let nodes = res.json::<PaginatedResponse<node::Model>>().items;
     assert!(nodes.is_empty());
 }
 
+#[tokio::test]
+async fn test_api_node_value_validation() {
+    use crate::project::{ErrorResponse, INVALID_NODE_VALUE};
+
+    let server = setup_test_server().await;
+    for (node_type, value, valid) in [
+        (NodeType::Email, "user@example.com", true),
+        (NodeType::Email, "用户@例子.广告", true),
+        (NodeType::Email, "not an email", false),
+        (NodeType::Email, "user@@example.com", false),
+        (NodeType::Email, "user@localhost", false),
+        (NodeType::Ip, "192.0.2.1", true),
+        (NodeType::Ip, "2001:db8::1", true),
+        (NodeType::Ip, "::ffff:192.0.2.1", true),
+        (NodeType::Ip, "192.0.2.300", false),
+        (NodeType::Ip, "2001:db8:::1", false),
+        (NodeType::Domain, "example.com", true),
+        (NodeType::Domain, "bücher.de", true),
+        (NodeType::Domain, "例え.テスト", true),
+        (NodeType::Domain, "xn--bcher-kva.de", true),
+        (NodeType::Domain, "-bad-.example.com", false),
+        (NodeType::Domain, "no spaces.com", false),
+        (NodeType::Domain, "localhost", false),
+        (NodeType::Url, "https://example.com/path?q=1", true),
+        (NodeType::Url, "https://bücher.de/", true),
+        (NodeType::Url, "http://[2001:db8::1]:8080/", true),
+        (NodeType::Url, "not a url", false),
+        // free-form types and empty values aren't checked
+        (NodeType::Person, "not an email", true),
+        (NodeType::Email, "", true),
+    ] {
+        let node = node::Model {
+            project_id: Uuid::nil(),
+            node_type,
+            display: value.to_string(),
+            value: value.to_string(),
+            ..Default::default()
+        };
+        if valid {
+            server
+                .post("/api/v1/node")
+                .json(&node)
+                .await
+                .assert_status_ok();
+            continue;
+        }
+        let res = server
+            .post("/api/v1/node")
+            .json(&node)
+            .expect_failure()
+            .await;
+        assert_eq!(res.status_code(), 400, "{node_type:?} {value}");
+        let body: ErrorResponse = res.json();
+        assert_eq!(body.code.as_deref(), Some(INVALID_NODE_VALUE));
+        assert!(
+            body.error
+                .ends_with(&format!("for node type {}", node_type.as_ref())),
+            "{}",
+            body.error
+        );
+    }
+
+    // updates are checked too
+    let mut node: node::Model = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: Uuid::nil(),
+            node_type: NodeType::Email,
+            value: "user@example.com".to_string(),
+            ..Default::default()
+        })
+        .await
+        .json();
+    node.value = "user at example dot com".to_string();
+    let res = server
+        .put(&format!("/api/v1/node/{}", node.id))
+        .json(&node)
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 400);
+    let body: ErrorResponse = res.json();
+    assert_eq!(body.error, "Invalid email address for node type email");
+    node.node_type = NodeType::Person;
+    server
+        .put(&format!("/api/v1/node/{}", node.id))
+        .json(&node)
+        .await
+        .assert_status_ok();
+}
+
+#[tokio::test]
+async fn test_api_node_type_history() {
+    use crate::entity::node_history::NodeTypeChange;
+
+    let server = setup_test_server().await;
+    let mut node: node::Model = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: Uuid::nil(),
+            node_type: NodeType::Document,
+            display: "scan.png".to_string(),
+            ..Default::default()
+        })
+        .await
+        .json();
+    let url = format!("/api/v1/node/{}/type-history", node.id);
+    let history: Vec<NodeTypeChange> = server.get(&url).await.json();
+    assert!(history.is_empty());
+
+    // other edits aren't type changes
+    for (node_type, display) in [
+        (NodeType::Image, "scan.png"),
+        (NodeType::Image, "scan of the letter"),
+        (NodeType::Url, "https://example.com/scan.png"),
+    ] {
+        node.node_type = node_type;
+        node.display = display.to_string();
+        server
+            .put(&format!("/api/v1/node/{}", node.id))
+            .json(&node)
+            .await
+            .assert_status_ok();
+    }
+
+    let history: Vec<NodeTypeChange> = server.get(&url).await.json();
+    let changes: Vec<(NodeType, NodeType)> = history
+        .iter()
+        .map(|change| (change.from_type, change.to_type))
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            (NodeType::Document, NodeType::Image),
+            (NodeType::Image, NodeType::Url),
+        ]
+    );
+    assert!(history.iter().all(|change| change.node_id == node.id));
+    assert!(history[0].changed <= history[1].changed);
+
+    // still there once the node is purged
+    server
+        .delete(&format!("/api/v1/node/{}?purge=true", node.id))
+        .await
+        .assert_status_ok();
+    let purged: Vec<NodeTypeChange> = server.get(&url).await.json();
+    assert_eq!(purged, history);
+
+    server
+        .get(&format!("/api/v1/node/{}/type-history", Uuid::new_v4()))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+}
+
+#[tokio::test]
+async fn test_api_get_nodes_by_project_sorted() {
+    let server = setup_test_server().await;
+    let project = new_test_project("sorting");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    let types = [NodeType::Person, NodeType::Domain, NodeType::Email];
+    let nodes: Vec<node::Model> = (0..50)
+        .map(|index| node::Model {
+            project_id: project.id,
+            node_type: types[index % types.len()],
+            display: format!("node {index:02}"),
+            ..Default::default()
+        })
+        .collect();
+    server
+        .post("/api/v1/nodes")
+        .json(&nodes)
+        .await
+        .assert_status_ok();
+
+    let page = |query: String| {
+        let server = &server;
+        let url = format!("/api/v1/project/{}/nodes?{query}", project.id);
+        async move {
+            let res = server.get(&url).await;
+            res.assert_status_ok();
+            res.json::<PaginatedResponse<node::Model>>()
+        }
+    };
+
+    // pages of 20 descending by display name: 20, 20, 10 then nothing
+    let mut seen = Vec::new();
+    for (number, expected) in [(1, 20), (2, 20), (3, 10), (4, 0)] {
+        let res = page(format!(
+            "sort=display&order=desc&page={number}&page_size=20"
+        ))
+        .await;
+        assert_eq!(res.total_count, 50);
+        assert_eq!(res.page, number);
+        assert_eq!(res.items.len(), expected, "page {number}");
+        seen.extend(res.items.into_iter().map(|node| node.display));
+    }
+    let mut expected: Vec<String> = nodes.iter().map(|node| node.display.clone()).collect();
+    expected.sort();
+    expected.reverse();
+    assert_eq!(seen, expected);
+
+    let by_type = page("sort=node_type&page_size=50".to_string()).await;
+    let type_names: Vec<&str> = by_type
+        .items
+        .iter()
+        .map(|node| node.node_type.as_ref())
+        .collect();
+    let mut sorted_names = type_names.clone();
+    sorted_names.sort();
+    assert_eq!(type_names, sorted_names);
+    // ties come out in ID order
+    let first_type = &by_type.items[..17];
+    assert!(first_type.windows(2).all(|pair| pair[0].id < pair[1].id));
+
+    let by_updated = page("sort=updated&order=desc&page_size=50".to_string()).await;
+    assert!(by_updated
+        .items
+        .windows(2)
+        .all(|pair| pair[0].updated >= pair[1].updated));
+
+    // the default is still ID order
+    let default = page("page_size=50".to_string()).await;
+    assert!(default.items.windows(2).all(|pair| pair[0].id < pair[1].id));
+
+    let res = server
+        .get(&format!("/api/v1/project/{}/nodes?sort=colour", project.id))
+        .expect_failure()
+        .await;
+    res.assert_status_bad_request();
+}
+
+#[tokio::test]
+async fn test_api_get_nodes_by_project_node_type() {
+    let server = setup_test_server().await;
+    let project = new_test_project("node type filter");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    let nodes: Vec<node::Model> = [
+        (NodeType::Email, "user@example.com"),
+        (NodeType::Email, "not an email"),
+        (NodeType::Domain, "example.com"),
+        (NodeType::Person, "Jane Citizen"),
+    ]
+    .into_iter()
+    .map(|(node_type, value)| node::Model {
+        project_id: project.id,
+        node_type,
+        display: value.to_string(),
+        value: value.to_string(),
+        ..Default::default()
+    })
+    .collect();
+    server
+        .post("/api/v1/nodes")
+        .json(&nodes)
+        .await
+        .assert_status_ok();
+
+    let types = |query: &str| {
+        let server = &server;
+        let url = format!("/api/v1/project/{}/nodes?{query}", project.id);
+        async move {
+            let res = server.get(&url).await;
+            res.assert_status_ok();
+            let page: PaginatedResponse<node::Model> = res.json();
+            assert_eq!(page.total_count, page.items.len() as u64);
+            let mut types: Vec<NodeType> = page.items.iter().map(|node| node.node_type).collect();
+            types.sort();
+            types
+        }
+    };
+    assert_eq!(
+        types("node_type=email").await,
+        [NodeType::Email, NodeType::Email]
+    );
+    assert_eq!(
+        types("node_type=email,domain").await,
+        [NodeType::Domain, NodeType::Email, NodeType::Email]
+    );
+    assert_eq!(types("node_type=ip").await, []);
+    assert_eq!(types("").await.len(), nodes.len());
+    // combined with the review filter, only the broken email is left
+    assert_eq!(
+        types("node_type=email,domain&incomplete_only=true&checks=value_validates").await,
+        [NodeType::Email]
+    );
+
+    let res = server
+        .get(&format!(
+            "/api/v1/project/{}/nodes?node_type=email,cat",
+            project.id
+        ))
+        .expect_failure()
+        .await;
+    res.assert_status_bad_request();
+    let body: serde_json::Value = res.json();
+    assert_eq!(body["parameter"], "node_type");
+    let message = body["error"].as_str().expect("error message");
+    assert!(message.contains("cat"), "{message}");
+    assert!(message.contains("person, domain"), "{message}");
+}
+
 #[tokio::test]
 async fn test_api_projects_crud() {
     let server = setup_test_server().await;
@@ -274,7 +655,7 @@ async fn test_api_projects_crud() {
     // Test getting all projects (should include default project)
     let res = server.get("/api/v1/projects").await;
     res.assert_status_ok();
-    let initial_projects: Vec<project::Model> = res.json();
+    let initial_projects = res.json::<PaginatedResponse<project::Model>>().items;
     let initial_count = initial_projects.len();
 
     // Create a new project
@@ -288,6 +669,8 @@ async fn test_api_projects_crud() {
         last_updated: None,
         description: None,
         tags: StringVec::default(),
+        pinned: false,
+        is_archived: false,
     };
 
     // Test project creation
@@ -297,7 +680,7 @@ async fn test_api_projects_crud() {
     // Test getting all projects (should have one more)
     let res = server.get("/api/v1/projects").await;
     res.assert_status_ok();
-    let projects: Vec<project::Model> = res.json();
+    let projects = res.json::<PaginatedResponse<project::Model>>().items;
     assert_eq!(projects.len(), initial_count + 1);
 
     // Test getting specific project
@@ -315,10 +698,13 @@ async fn test_api_projects_crud() {
         .await;
     assert_eq!(res.status_code(), 404);
 
-    let res = server
-        .get(&format!("/api/v1/project/{}/export", retrieved_project.id))
-        .expect_success()
-        .await;
+    // project, nodes, links, attachments
+    let res = with_query_budget(4, || {
+        server
+            .get(&format!("/api/v1/project/{}/export", retrieved_project.id))
+            .expect_success()
+    })
+    .await;
 
     let exported: ProjectExport = res.json();
     assert_eq!(exported.project.id, retrieved_project.id);
@@ -338,6 +724,8 @@ async fn test_api_nodes_crud() {
         last_updated: None,
         description: None,
         tags: StringVec::default(),
+        pinned: false,
+        is_archived: false,
     };
     server
         .post("/api/v1/project")
@@ -357,6 +745,9 @@ async fn test_api_nodes_crud() {
         notes: Some("Test email node".to_string()),
         pos_x: Some(150),
         pos_y: Some(250),
+        created_by: None,
+        created: None,
+        deleted_at: None,
     };
 
     let res = server.post("/api/v1/node").json(&node).await;
@@ -386,6 +777,9 @@ async fn test_api_nodes_crud() {
         notes: Some("Updated test email node".to_string()),
         pos_x: Some(300),
         pos_y: Some(400),
+        created_by: None,
+        created: None,
+        deleted_at: None,
     };
 
     let res = server
@@ -432,6 +826,9 @@ async fn test_api_node_foreign_key_constraint() {
         notes: None,
         pos_x: None,
         pos_y: None,
+        created_by: None,
+        created: None,
+        deleted_at: None,
     };
 
     // This should fail due to project validation (project doesn't exist)
@@ -443,6 +840,76 @@ async fn test_api_node_foreign_key_constraint() {
     assert_eq!(res.status_code(), 404); // Project not found
 }
 
+#[tokio::test]
+async fn test_api_node_id_collision() {
+    use crate::project::{ErrorResponse, NODE_ID_CONFLICT};
+
+    let server = setup_test_server().await;
+    let mut project_ids = Vec::new();
+    for name in ["first", "second"] {
+        let project: project::Model = server
+            .post("/api/v1/project")
+            .json(&new_test_project(name))
+            .await
+            .json();
+        project_ids.push(project.id);
+    }
+
+    let node = node::Model {
+        id: Uuid::new_v4(),
+        project_id: project_ids[0],
+        node_type: NodeType::Person,
+        display: "Original".to_string(),
+        value: "original".to_string(),
+        ..Default::default()
+    };
+    server
+        .post("/api/v1/node")
+        .json(&node)
+        .await
+        .assert_status_ok();
+
+    // reusing the ID in another project is a clean conflict, not a database error
+    let res = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project_ids[1],
+            display: "Copy".to_string(),
+            ..node.clone()
+        })
+        .expect_failure()
+        .await;
+    res.assert_status(axum::http::StatusCode::CONFLICT);
+    let error: ErrorResponse = res.json();
+    assert_eq!(error.code.as_deref(), Some(NODE_ID_CONFLICT));
+    assert!(error.error.contains("another project"));
+
+    let res = server
+        .post("/api/v1/node")
+        .json(&node)
+        .expect_failure()
+        .await;
+    res.assert_status(axum::http::StatusCode::CONFLICT);
+    assert!(res.json::<ErrorResponse>().error.contains("already exists"));
+
+    // the original is untouched and the second project is still usable
+    let saved: node::Model = server
+        .get(&format!("/api/v1/node/{}", node.id))
+        .await
+        .json();
+    assert_eq!(saved.project_id, project_ids[0]);
+    assert_eq!(saved.display, "Original");
+    server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            id: Uuid::new_v4(),
+            project_id: project_ids[1],
+            ..node
+        })
+        .await
+        .assert_status_ok();
+}
+
 #[tokio::test]
 async fn test_api_update_project() {
     let server = setup_test_server().await;
@@ -459,6 +926,8 @@ async fn test_api_update_project() {
 
         description: None,
         tags: StringVec::default(),
+        pinned: false,
+        is_archived: false,
     };
 
     server
@@ -476,6 +945,8 @@ async fn test_api_update_project() {
         last_updated: None,
         description: Some("A test description".to_string()),
         tags: StringVec(vec!["tag1".to_string(), "tag2".to_string()]),
+        pinned: false,
+        is_archived: false,
     };
 
     let res = server
@@ -528,6 +999,8 @@ async fn test_api_delete_project() {
         last_updated: None,
         description: Some("Will be deleted".to_string()),
         tags: StringVec(vec!["test".to_string()]),
+        pinned: false,
+        is_archived: false,
     };
     debug!("Creating project to delete: {}", project_id);
     server
@@ -548,6 +1021,9 @@ async fn test_api_delete_project() {
         notes: None,
         pos_x: None,
         pos_y: None,
+        created_by: None,
+        created: None,
+        deleted_at: None,
     };
     let node_id2 = Uuid::new_v4();
     let node2 = node::Model {
@@ -560,6 +1036,9 @@ async fn test_api_delete_project() {
         notes: None,
         pos_x: None,
         pos_y: None,
+        created_by: None,
+        created: None,
+        deleted_at: None,
     };
 
     server
@@ -682,6 +1161,8 @@ async fn test_api_attachment_upload_download() {
         last_updated: None,
         description: None,
         tags: StringVec::default(),
+        pinned: false,
+        is_archived: false,
     };
     server
         .post("/api/v1/project")
@@ -700,6 +1181,9 @@ async fn test_api_attachment_upload_download() {
         notes: None,
         pos_x: None,
         pos_y: None,
+        created_by: None,
+        created: None,
+        deleted_at: None,
     };
     server
         .post("/api/v1/node")
@@ -771,6 +1255,8 @@ async fn test_api_attachment_view() {
         last_updated: None,
         description: None,
         tags: StringVec::default(),
+        pinned: false,
+        is_archived: false,
     };
     server
         .post("/api/v1/project")
@@ -789,6 +1275,9 @@ async fn test_api_attachment_view() {
         notes: None,
         pos_x: None,
         pos_y: None,
+        created_by: None,
+        created: None,
+        deleted_at: None,
     };
     server
         .post("/api/v1/node")
@@ -832,15 +1321,9 @@ async fn test_api_attachment_view() {
         .await;
     res.assert_status_ok();
 
-    let response_bytes = res.as_bytes();
-    let response_bytes = response_bytes.as_ref();
-    // decompress them because they'll be gzipped
-    let mut decoder = flate2::read::GzDecoder::new(response_bytes);
-    let mut response_bytes = Vec::new();
-    use std::io::Read;
-    decoder.read_to_end(&mut response_bytes).unwrap();
-
-    assert_eq!(response_bytes, png_content.as_slice());
+    // we didn't send Accept-Encoding, so it's decoded for us
+    assert!(res.maybe_header(CONTENT_ENCODING).is_none());
+    assert_eq!(res.as_bytes().as_ref(), png_content.as_slice());
 
     // Verify content type header
     assert_eq!(res.header(CONTENT_TYPE), "image/png");
@@ -872,6 +1355,8 @@ async fn test_api_attachment_list_and_metadata() {
         last_updated: None,
         description: None,
         tags: StringVec::default(),
+        pinned: false,
+        is_archived: false,
     };
     server
         .post("/api/v1/project")
@@ -890,6 +1375,9 @@ async fn test_api_attachment_list_and_metadata() {
         notes: None,
         pos_x: None,
         pos_y: None,
+        created_by: None,
+        created: None,
+        deleted_at: None,
     };
     server
         .post("/api/v1/node")
@@ -939,9 +1427,11 @@ async fn test_api_attachment_list_and_metadata() {
     let attachment_id2 = attachment2.id;
 
     // Get attachments list for the node
-    let res = server
-        .get(&format!("/api/v1/node/{}/attachments", node_id))
-        .await;
+    // node's project, project access, attachments
+    let res = with_query_budget(3, || {
+        server.get(&format!("/api/v1/node/{}/attachments", node_id))
+    })
+    .await;
     res.assert_status_ok();
     let attachments: Vec<crate::entity::attachment::Model> = res.json();
     dbg!(&attachments);
@@ -975,6 +1465,8 @@ async fn test_api_mermaid_export() {
         last_updated: None,
         description: Some("A project for testing Mermaid export".to_string()),
         tags: StringVec(vec!["test".to_string(), "mermaid".to_string()]),
+        pinned: false,
+        is_archived: false,
     };
     server
         .post("/api/v1/project")
@@ -994,6 +1486,9 @@ async fn test_api_mermaid_export() {
         notes: Some("Main person".to_string()),
         pos_x: Some(100),
         pos_y: Some(200),
+        created_by: None,
+        created: None,
+        deleted_at: None,
     };
 
     let node2_id = Uuid::new_v4();
@@ -1007,6 +1502,9 @@ async fn test_api_mermaid_export() {
         notes: Some("Website domain".to_string()),
         pos_x: Some(300),
         pos_y: Some(200),
+        created_by: None,
+        created: None,
+        deleted_at: None,
     };
 
     let node3_id = Uuid::new_v4();
@@ -1020,6 +1518,9 @@ async fn test_api_mermaid_export() {
         notes: None,
         pos_x: Some(200),
         pos_y: Some(400),
+        created_by: None,
+        created: None,
+        deleted_at: None,
     };
 
     server
@@ -1065,6 +1566,12 @@ async fn test_api_mermaid_export() {
         left: node1_id,
         right: node2_id,
         linktype: LinkType::Directional,
+        weight: None,
+        kind: None,
+        valid_from: None,
+        valid_to: None,
+        created_by: None,
+        created: None,
     };
 
     let link2 = nodelink::Model {
@@ -1073,6 +1580,12 @@ async fn test_api_mermaid_export() {
         left: node2_id,
         right: node3_id,
         linktype: LinkType::Omni,
+        weight: None,
+        kind: None,
+        valid_from: None,
+        valid_to: None,
+        created_by: None,
+        created: None,
     };
 
     server
@@ -1087,9 +1600,12 @@ async fn test_api_mermaid_export() {
         .assert_status_ok();
 
     // Export as Mermaid
-    let res = server
-        .get(&format!("/api/v1/project/{}/export/mermaid", project_id))
-        .await;
+    // a cache miss: project, fingerprint (nodes, links, attachments), cache lookup, rendering
+    // (nodes, links, attachments), cache insert and eviction
+    let res = with_query_budget(10, || {
+        server.get(&format!("/api/v1/project/{}/export/mermaid", project_id))
+    })
+    .await;
     res.assert_status_ok();
 
     // Verify content type
@@ -1146,6 +1662,8 @@ async fn test_api_mermaid_export_sanitization() {
         last_updated: None,
         description: Some("Description with \"quotes\" and 'apostrophes'".to_string()),
         tags: StringVec::default(),
+        pinned: false,
+        is_archived: false,
     };
     server
         .post("/api/v1/project")
@@ -1165,6 +1683,9 @@ async fn test_api_mermaid_export_sanitization() {
         notes: Some("Notes with {braces} and <brackets>".to_string()),
         pos_x: None,
         pos_y: None,
+        created_by: None,
+        created: None,
+        deleted_at: None,
     };
 
     let node2_id = Uuid::new_v4();
@@ -1178,6 +1699,9 @@ async fn test_api_mermaid_export_sanitization() {
         notes: None,
         pos_x: None,
         pos_y: None,
+        created_by: None,
+        created: None,
+        deleted_at: None,
     };
 
     let node3_id = Uuid::new_v4();
@@ -1191,6 +1715,9 @@ async fn test_api_mermaid_export_sanitization() {
         notes: None,
         pos_x: None,
         pos_y: None,
+        created_by: None,
+        created: None,
+        deleted_at: None,
     };
 
     server
@@ -1233,3 +1760,9968 @@ async fn test_api_mermaid_export_sanitization() {
     assert!(mermaid.contains("Notes with (braces) and (brackets)")); // Braces/brackets converted to parentheses
     assert!(mermaid.contains("Description with \"quotes\" and 'apostrophes'")); // Quotes converted to apostrophes
 }
+
+#[tokio::test]
+async fn test_expired_session_cleanup() {
+    use crate::sessions::{delete_expired_sessions, session_count, SESSION_TABLE};
+
+    let appstate = AppState::test().await;
+    let pool = appstate.conn.get_sqlite_connection_pool().clone();
+    let store = tower_sessions_sqlx_store::SqliteStore::new(pool.clone());
+    store
+        .migrate()
+        .await
+        .expect("Failed to migrate session store");
+
+    sqlx::query(&format!(
+        "INSERT INTO {SESSION_TABLE} (id, data, expiry_date) VALUES (?, ?, ?)"
+    ))
+    .bind("expired-session")
+    .bind(Vec::<u8>::new())
+    .bind("2000-01-01 00:00:00")
+    .execute(&pool)
+    .await
+    .expect("Failed to insert expired session");
+    sqlx::query(&format!(
+        "INSERT INTO {SESSION_TABLE} (id, data, expiry_date) VALUES (?, ?, ?)"
+    ))
+    .bind("live-session")
+    .bind(Vec::<u8>::new())
+    .bind("2999-01-01 00:00:00")
+    .execute(&pool)
+    .await
+    .expect("Failed to insert live session");
+    assert_eq!(session_count(&pool).await.unwrap(), 2);
+
+    delete_expired_sessions(&store)
+        .await
+        .expect("Failed to clean up sessions");
+    assert_eq!(session_count(&pool).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_api_status() {
+    let server = setup_test_server().await;
+
+    let res = server.get("/api/v1/status").await;
+    res.assert_status_ok();
+    let status: crate::status::StatusResponse = res.json();
+    assert_eq!(status.version, env!("CARGO_PKG_VERSION"));
+    assert!(status.sessions >= 0);
+}
+
+#[tokio::test]
+async fn test_api_capabilities() {
+    use crate::capabilities::{AuthMode, Capabilities};
+    use crate::quota::{Quota, QuotaLimits};
+    use crate::value_policy::{parse_policy, ValuePolicyEngine};
+
+    let server = setup_test_server().await;
+    let res = server.get("/api/v1/capabilities").await;
+    res.assert_status_ok();
+    assert!(res
+        .header(axum::http::header::CACHE_CONTROL)
+        .to_str()
+        .expect("ascii header")
+        .contains("max-age="));
+    let defaults: Capabilities = res.json();
+    assert_eq!(defaults.auth_mode, AuthMode::None);
+    assert!(!defaults.read_only);
+    assert_eq!(defaults.features.get("value_policy"), Some(&false));
+    assert_eq!(defaults.features.get("cross_origin_capture"), Some(&false));
+    assert_eq!(defaults.features.get("quotas"), Some(&false));
+    assert_eq!(defaults.limits.get("max_projects"), Some(&None));
+    assert_eq!(
+        defaults.limits.get("max_upload_bytes"),
+        Some(&Some(crate::attachment::MAX_UPLOAD_BYTES as u64))
+    );
+    assert!(defaults.export_formats.iter().any(|f| f == "report.pdf"));
+
+    let mut appstate = AppState::test().await;
+    appstate.value_policy = ValuePolicyEngine::new(
+        parse_policy("[[rule]]\nname = \"a\"\npattern = \"x\"\naction = \"warn\"")
+            .expect("valid policy"),
+    );
+    appstate.cors_allowed_origins = vec![axum::http::HeaderValue::from_static(
+        "chrome-extension://abcdef",
+    )];
+    appstate.quota = Quota::new(QuotaLimits {
+        max_projects: Some(3),
+        max_nodes_per_project: None,
+        max_nodelinks_per_project: None,
+        max_total_attachment_bytes: None,
+        warning_percent: 80,
+    });
+    appstate.max_layout_nodes = 0;
+    appstate.report_sync_max_nodes = 7;
+    let server = setup_test_server_with_state(appstate).await;
+
+    let toggled: Capabilities = server.get("/api/v1/capabilities").await.json();
+    assert_eq!(toggled.features.get("value_policy"), Some(&true));
+    assert_eq!(toggled.features.get("cross_origin_capture"), Some(&true));
+    assert_eq!(toggled.features.get("quotas"), Some(&true));
+    assert_eq!(toggled.features.get("force_layout"), Some(&false));
+    assert_eq!(toggled.limits.get("max_projects"), Some(&Some(3)));
+    assert_eq!(toggled.limits.get("report_sync_max_nodes"), Some(&Some(7)));
+
+    // nothing in there should point anywhere
+    let raw = server.get("/api/v1/capabilities").await.text();
+    assert!(!raw.contains("://"), "{raw}");
+
+    // diagnostics carry the same payload
+    let status: crate::status::StatusResponse = server.get("/api/v1/status").await.json();
+    assert_eq!(status.capabilities.features, toggled.features);
+}
+
+#[tokio::test]
+async fn test_readyz_timeout() {
+    use sea_orm::TransactionTrait;
+
+    let mut appstate = AppState::test().await;
+    appstate.readiness_timeout = std::time::Duration::from_millis(50);
+    let conn = appstate.conn.clone();
+    let server = setup_test_server_with_state(appstate).await;
+
+    assert_eq!(server.get("/readyz").await.text(), "ok");
+
+    // the in-memory database has a single connection, so holding it blocks the check
+    let txn = conn.begin().await.expect("Failed to start transaction");
+    let started = std::time::Instant::now();
+    let res = server.get("/readyz").expect_failure().await;
+    res.assert_status(axum::http::StatusCode::SERVICE_UNAVAILABLE);
+    assert!(res.text().contains("timed out after 50ms"));
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    txn.rollback().await.expect("Failed to roll back");
+
+    server.get("/readyz").await.assert_status_ok();
+}
+
+fn new_test_project(name: &str) -> project::Model {
+    project::Model {
+        id: Uuid::new_v4(),
+        name: name.to_string(),
+        user: Uuid::new_v4(),
+        creationdate: chrono::Utc::now(),
+        last_updated: None,
+        description: None,
+        tags: StringVec::default(),
+        pinned: false,
+        is_archived: false,
+    }
+}
+
+#[tokio::test]
+async fn test_api_quota_max_projects() {
+    use crate::quota::{Quota, QuotaLimits, QUOTA_WARNING_HEADER};
+
+    let mut appstate = AppState::test().await;
+    // the Inbox project already counts towards the limit
+    appstate.quota = Quota::new(QuotaLimits {
+        max_projects: Some(4),
+        warning_percent: 75,
+        ..Default::default()
+    });
+    let server = setup_test_server_with_state(appstate).await;
+
+    let res = server
+        .post("/api/v1/project")
+        .json(&new_test_project("quota 1"))
+        .await;
+    res.assert_status_ok();
+    assert!(res.maybe_header(QUOTA_WARNING_HEADER).is_none());
+
+    let res = server
+        .post("/api/v1/project")
+        .json(&new_test_project("quota 2"))
+        .await;
+    res.assert_status_ok();
+    let warning = res.header(QUOTA_WARNING_HEADER);
+    assert!(warning.to_str().unwrap().contains("max_projects: 3 of 4"));
+
+    let last_project = new_test_project("quota 3");
+    server
+        .post("/api/v1/project")
+        .json(&last_project)
+        .await
+        .assert_status_ok();
+
+    let res = server
+        .post("/api/v1/project")
+        .json(&new_test_project("quota 4"))
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 409);
+    let body = res.text();
+    assert!(body.contains("max_projects"));
+    assert!(body.contains("4 of 4"));
+
+    // updating an existing project isn't blocked by the limit
+    server
+        .post("/api/v1/project?upsert=true")
+        .json(&last_project)
+        .await
+        .assert_status_ok();
+
+    let res = server.get("/api/v1/status").await;
+    let status: crate::status::StatusResponse = res.json();
+    assert_eq!(status.quota_warnings.len(), 1);
+    assert!(status.quota_warnings[0].starts_with("max_projects"));
+}
+
+#[tokio::test]
+async fn test_api_quota_max_nodes_per_project() {
+    use crate::quota::{Quota, QuotaLimits, QUOTA_WARNING_HEADER};
+
+    let mut appstate = AppState::test().await;
+    appstate.quota = Quota::new(QuotaLimits {
+        max_nodes_per_project: Some(2),
+        ..Default::default()
+    });
+    let server = setup_test_server_with_state(appstate).await;
+
+    let project = new_test_project("node quota");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    let other_project = new_test_project("other node quota");
+    server
+        .post("/api/v1/project")
+        .json(&other_project)
+        .await
+        .assert_status_ok();
+
+    let res = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            ..Default::default()
+        })
+        .await;
+    res.assert_status_ok();
+    assert!(res.maybe_header(QUOTA_WARNING_HEADER).is_none());
+
+    let res = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            ..Default::default()
+        })
+        .await;
+    res.assert_status_ok();
+    assert!(res.maybe_header(QUOTA_WARNING_HEADER).is_some());
+
+    let res = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            ..Default::default()
+        })
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 409);
+    assert!(res.text().contains("max_nodes_per_project"));
+
+    // the limit is per-project
+    server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: other_project.id,
+            ..Default::default()
+        })
+        .await
+        .assert_status_ok();
+}
+
+#[tokio::test]
+async fn test_api_quota_admin_exempt() {
+    use crate::quota::{Quota, QuotaLimits};
+
+    let mut appstate = AppState::test().await;
+    appstate.quota = Quota::new(QuotaLimits {
+        max_nodes_per_project: Some(1),
+        ..Default::default()
+    });
+    let users = new_test_users(&appstate.conn, &[("alice", false), ("root", true)]).await;
+    let servers = setup_test_servers_as_users(appstate, &users).await;
+    let (alice, root) = (&servers[0], &servers[1]);
+
+    let mut projects = Vec::new();
+    for (server, name) in [(alice, "alice's quota"), (root, "root's quota")] {
+        let project = new_test_project(name);
+        server
+            .post("/api/v1/project")
+            .json(&project)
+            .await
+            .assert_status_ok();
+        projects.push(project);
+    }
+
+    for _ in 0..2 {
+        root.post("/api/v1/node")
+            .json(&node::Model {
+                project_id: projects[1].id,
+                ..Default::default()
+            })
+            .await
+            .assert_status_ok();
+    }
+
+    alice
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: projects[0].id,
+            ..Default::default()
+        })
+        .await
+        .assert_status_ok();
+    let res = alice
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: projects[0].id,
+            ..Default::default()
+        })
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 409);
+}
+
+#[tokio::test]
+async fn test_api_quota_freed_by_delete() {
+    use crate::quota::{Quota, QuotaLimits};
+
+    let mut appstate = AppState::test().await;
+    // the Inbox project already counts towards the limit
+    appstate.quota = Quota::new(QuotaLimits {
+        max_projects: Some(2),
+        max_nodes_per_project: Some(1),
+        ..Default::default()
+    });
+    let server = setup_test_server_with_state(appstate).await;
+
+    let project = new_test_project("freed quota");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    let node = node::Model {
+        project_id: project.id,
+        ..Default::default()
+    };
+    server
+        .post("/api/v1/node")
+        .json(&node)
+        .await
+        .assert_status_ok();
+
+    // a trashed node still counts
+    server
+        .delete(&format!("/api/v1/node/{}", node.id))
+        .await
+        .assert_status_ok();
+    let res = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            ..Default::default()
+        })
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 409);
+
+    // purging frees the slot straight away rather than after the cache expires
+    server
+        .delete(&format!("/api/v1/node/{}?purge=true", node.id))
+        .await
+        .assert_status_ok();
+    server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            ..Default::default()
+        })
+        .await
+        .assert_status_ok();
+
+    let res = server
+        .post("/api/v1/project")
+        .json(&new_test_project("over quota"))
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 409);
+    server
+        .delete(&format!("/api/v1/project/{}", project.id))
+        .await
+        .assert_status_ok();
+    server
+        .post("/api/v1/project")
+        .json(&new_test_project("freed quota again"))
+        .await
+        .assert_status_ok();
+}
+
+#[tokio::test]
+async fn test_api_quota_max_nodelinks_per_project() {
+    use crate::entity::nodelink;
+    use crate::project::{ProjectGraph, ProjectGraphCreated};
+    use crate::quota::{Quota, QuotaLimits};
+    use osint_graph_shared::nodelink::LinkType;
+
+    let mut appstate = AppState::test().await;
+    appstate.quota = Quota::new(QuotaLimits {
+        max_nodelinks_per_project: Some(2),
+        ..Default::default()
+    });
+    let server = setup_test_server_with_state(appstate).await;
+
+    let project = new_test_project("link quota");
+    let other_project = new_test_project("other link quota");
+    let mut nodes = std::collections::HashMap::new();
+    for project in [&project, &other_project] {
+        server
+            .post("/api/v1/project")
+            .json(project)
+            .await
+            .assert_status_ok();
+        let project_nodes: Vec<node::Model> = (0..2)
+            .map(|_| node::Model {
+                project_id: project.id,
+                ..Default::default()
+            })
+            .collect();
+        for node in &project_nodes {
+            server
+                .post("/api/v1/node")
+                .json(node)
+                .await
+                .assert_status_ok();
+        }
+        nodes.insert(project.id, project_nodes);
+    }
+    let new_link = |project_id: Uuid| nodelink::Model {
+        id: Uuid::new_v4(),
+        left: nodes[&project_id][0].id,
+        right: nodes[&project_id][1].id,
+        project_id,
+        linktype: LinkType::Omni,
+        weight: None,
+        kind: None,
+        valid_from: None,
+        valid_to: None,
+        created_by: None,
+        created: None,
+    };
+
+    for _ in 0..2 {
+        server
+            .post("/api/v1/nodelink")
+            .json(&new_link(project.id))
+            .await
+            .assert_status_ok();
+    }
+    let res = server
+        .post("/api/v1/nodelink")
+        .json(&new_link(project.id))
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 403);
+    assert!(res.text().contains("max_nodelinks_per_project"));
+
+    // the limit is per-project
+    server
+        .post("/api/v1/nodelink")
+        .json(&new_link(other_project.id))
+        .await
+        .assert_status_ok();
+
+    // and bulk creation counts every link it brings along
+    let bulk_project = new_test_project("bulk link quota");
+    let bulk_nodes: Vec<node::Model> = (0..2)
+        .map(|_| node::Model {
+            project_id: bulk_project.id,
+            ..Default::default()
+        })
+        .collect();
+    let bulk_links: Vec<nodelink::Model> = (0..3)
+        .map(|_| nodelink::Model {
+            id: Uuid::new_v4(),
+            left: bulk_nodes[0].id,
+            right: bulk_nodes[1].id,
+            project_id: bulk_project.id,
+            linktype: LinkType::Omni,
+            weight: None,
+            kind: None,
+            valid_from: None,
+            valid_to: None,
+            created_by: None,
+            created: None,
+        })
+        .collect();
+    let graph = ProjectGraph {
+        project: bulk_project.clone(),
+        nodes: bulk_nodes.clone(),
+        nodelinks: bulk_links.clone(),
+    };
+    let res = server
+        .post("/api/v1/project/full")
+        .json(&graph)
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 403);
+    let graph = ProjectGraph {
+        project: bulk_project,
+        nodes: bulk_nodes,
+        nodelinks: bulk_links[..2].to_vec(),
+    };
+    let created: ProjectGraphCreated = server
+        .post("/api/v1/project/full")
+        .json(&graph)
+        .await
+        .json();
+    assert_eq!(created.nodelink_ids.len(), 2);
+}
+
+#[tokio::test]
+async fn test_api_quota_max_total_attachment_bytes() {
+    use crate::quota::{Quota, QuotaLimits, QUOTA_WARNING_HEADER};
+
+    let mut appstate = AppState::test().await;
+    appstate.quota = Quota::new(QuotaLimits {
+        max_total_attachment_bytes: Some(100),
+        warning_percent: 90,
+        ..Default::default()
+    });
+    let server = setup_test_server_with_state(appstate).await;
+
+    let project = new_test_project("attachment quota");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    let node = node::Model {
+        project_id: project.id,
+        ..Default::default()
+    };
+    server
+        .post("/api/v1/node")
+        .json(&node)
+        .await
+        .assert_status_ok();
+
+    let upload = |size: usize| {
+        axum_test::multipart::MultipartForm::new().add_part(
+            "file",
+            axum_test::multipart::Part::bytes(vec![b'a'; size])
+                .file_name("quota.txt")
+                .mime_type("text/plain"),
+        )
+    };
+
+    let res = server
+        .post(&format!("/api/v1/node/{}/attachment", node.id))
+        .multipart(upload(60))
+        .await;
+    res.assert_status_ok();
+    assert!(res.maybe_header(QUOTA_WARNING_HEADER).is_none());
+
+    let res = server
+        .post(&format!("/api/v1/node/{}/attachment", node.id))
+        .multipart(upload(50))
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 507);
+    assert!(res.text().contains("max_total_attachment_bytes"));
+
+    // exactly filling the limit is allowed, and warns
+    let res = server
+        .post(&format!("/api/v1/node/{}/attachment", node.id))
+        .multipart(upload(40))
+        .await;
+    res.assert_status_ok();
+    assert!(res.maybe_header(QUOTA_WARNING_HEADER).is_some());
+}
+
+#[tokio::test]
+async fn test_api_project_pinning() {
+    let server = setup_test_server().await;
+
+    let mut older = new_test_project("Older pinned project");
+    older.creationdate = chrono::Utc::now() - chrono::Duration::days(7);
+    let newer = new_test_project("Newer project");
+    for project in [&older, &newer] {
+        server
+            .post("/api/v1/project")
+            .json(project)
+            .await
+            .assert_status_ok();
+    }
+
+    let project_ids = |projects: PaginatedResponse<project::Model>| -> Vec<Uuid> {
+        projects.items.into_iter().map(|p| p.id).collect()
+    };
+    let position = |ids: &[Uuid], id: Uuid| ids.iter().position(|p| *p == id).unwrap();
+
+    let ids = project_ids(server.get("/api/v1/projects").await.json());
+    assert!(position(&ids, newer.id) < position(&ids, older.id));
+
+    let res = server
+        .post(&format!("/api/v1/project/{}/pin", older.id))
+        .await;
+    res.assert_status_ok();
+    assert!(res.json::<project::Model>().pinned);
+
+    let ids = project_ids(server.get("/api/v1/projects").await.json());
+    assert_eq!(ids[0], older.id);
+    assert!(position(&ids, older.id) < position(&ids, newer.id));
+
+    let res = server
+        .post(&format!("/api/v1/project/{}/unpin", older.id))
+        .await;
+    res.assert_status_ok();
+    assert!(!res.json::<project::Model>().pinned);
+
+    let ids = project_ids(server.get("/api/v1/projects").await.json());
+    assert!(position(&ids, newer.id) < position(&ids, older.id));
+
+    let res = server
+        .post(&format!("/api/v1/project/{}/pin", Uuid::new_v4()))
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_project_archiving() {
+    let server = setup_test_server().await;
+
+    let archived = new_test_project("Closed investigation");
+    let active = new_test_project("Open investigation");
+    for project in [&archived, &active] {
+        server
+            .post("/api/v1/project")
+            .json(project)
+            .await
+            .assert_status_ok();
+    }
+    let project_ids = |projects: PaginatedResponse<project::Model>| -> Vec<Uuid> {
+        projects.items.into_iter().map(|p| p.id).collect()
+    };
+
+    let res = server
+        .patch(&format!("/api/v1/project/{}/archive", archived.id))
+        .await;
+    res.assert_status_ok();
+    assert!(res.json::<project::Model>().is_archived);
+
+    // left out of the list by default, but can still be fetched
+    let projects: PaginatedResponse<project::Model> = server.get("/api/v1/projects").await.json();
+    assert_eq!(projects.total_count, 2);
+    let ids = project_ids(projects);
+    assert!(ids.contains(&active.id));
+    assert!(!ids.contains(&archived.id));
+    server
+        .get(&format!("/api/v1/project/{}", archived.id))
+        .await
+        .assert_status_ok();
+
+    let projects: PaginatedResponse<project::Model> = server
+        .get("/api/v1/projects")
+        .add_query_param("include_archived", true)
+        .await
+        .json();
+    assert_eq!(projects.total_count, 3);
+    assert!(project_ids(projects).contains(&archived.id));
+
+    let res = server
+        .patch(&format!("/api/v1/project/{}/unarchive", archived.id))
+        .await;
+    res.assert_status_ok();
+    assert!(!res.json::<project::Model>().is_archived);
+    let ids = project_ids(server.get("/api/v1/projects").await.json());
+    assert!(ids.contains(&archived.id));
+
+    // the Inbox can't be archived, matching the delete guard
+    let res = server
+        .patch(&format!("/api/v1/project/{}/archive", Uuid::nil()))
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 400);
+    assert!(res.text().contains("Cannot archive project with nil UUID"));
+    let ids = project_ids(server.get("/api/v1/projects").await.json());
+    assert!(ids.contains(&Uuid::nil()));
+
+    let res = server
+        .patch(&format!("/api/v1/project/{}/archive", Uuid::new_v4()))
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_invalid_path_parameter() {
+    use crate::project::ErrorResponse;
+
+    let server = setup_test_server().await;
+
+    for url in [
+        "/api/v1/node/not-a-uuid",
+        "/api/v1/project/not-a-uuid",
+        "/api/v1/project/not-a-uuid/nodes",
+        "/api/v1/attachment/not-a-uuid/view",
+    ] {
+        let res = server.get(url).expect_failure().await;
+        assert_eq!(res.status_code(), 400, "{url}");
+        assert_eq!(res.header(CONTENT_TYPE), "application/json");
+        let body: ErrorResponse = res.json();
+        assert_eq!(body.code.as_deref(), Some("invalid_path_parameter"));
+        assert_eq!(body.value.as_deref(), Some("not-a-uuid"));
+        assert!(body.parameter.is_some(), "{url} should name the parameter");
+    }
+
+    let res = server
+        .delete("/api/v1/nodelink/12345")
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 400);
+    let body: ErrorResponse = res.json();
+    assert_eq!(body.parameter.as_deref(), Some("id"));
+    assert_eq!(body.value.as_deref(), Some("12345"));
+
+    // valid requests are unaffected
+    server
+        .get(&format!("/api/v1/project/{}", Uuid::nil()))
+        .await
+        .assert_status_ok();
+}
+
+#[tokio::test]
+async fn test_api_invalid_query_parameter() {
+    use crate::project::ErrorResponse;
+
+    let server = setup_test_server().await;
+
+    let res = server
+        .get(&format!(
+            "/api/v1/project/{}/export?include_attachments=maybe",
+            Uuid::nil()
+        ))
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 400);
+    let body: ErrorResponse = res.json();
+    assert_eq!(body.code.as_deref(), Some("invalid_query_parameter"));
+    assert_eq!(body.parameter.as_deref(), Some("include_attachments"));
+    assert_eq!(body.value.as_deref(), Some("maybe"));
+
+    let res = server.get("/api/v1/search").expect_failure().await;
+    assert_eq!(res.status_code(), 400);
+    let body: ErrorResponse = res.json();
+    assert_eq!(body.code.as_deref(), Some("invalid_query_parameter"));
+    assert_eq!(body.parameter.as_deref(), Some("q"));
+    assert_eq!(body.value, None);
+
+    // valid requests are unaffected
+    server
+        .get(&format!(
+            "/api/v1/project/{}/export?include_attachments=true",
+            Uuid::nil()
+        ))
+        .await
+        .assert_status_ok();
+    server.get("/api/v1/search?q=foo").await.assert_status_ok();
+}
+
+#[tokio::test]
+async fn test_api_node_vcard_export() {
+    use crate::entity::nodelink;
+    use crate::export::VCARD_CONTENT_TYPE;
+    use osint_graph_shared::nodelink::LinkType;
+
+    let server = setup_test_server().await;
+
+    let project = new_test_project("vCard export");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+
+    let person = node::Model {
+        project_id: project.id,
+        node_type: NodeType::Person,
+        display: "Jane Doe".to_string(),
+        value: "Jane Doe".to_string(),
+        ..Default::default()
+    };
+    let email = node::Model {
+        project_id: project.id,
+        node_type: NodeType::Email,
+        display: "jane".to_string(),
+        value: "jane@example.com".to_string(),
+        ..Default::default()
+    };
+    let phone = node::Model {
+        project_id: project.id,
+        node_type: NodeType::Phone,
+        display: "jane's phone".to_string(),
+        value: "+61 400 000 000".to_string(),
+        ..Default::default()
+    };
+    for node in [&person, &email, &phone] {
+        server
+            .post("/api/v1/node")
+            .json(node)
+            .await
+            .assert_status_ok();
+    }
+    for (left, right) in [(person.id, email.id), (phone.id, person.id)] {
+        server
+            .post("/api/v1/nodelink")
+            .json(&nodelink::Model {
+                id: Uuid::new_v4(),
+                left,
+                right,
+                project_id: project.id,
+                linktype: LinkType::Omni,
+                weight: None,
+                kind: None,
+                valid_from: None,
+                valid_to: None,
+                created_by: None,
+                created: None,
+            })
+            .await
+            .assert_status_ok();
+    }
+
+    let res = server
+        .get(&format!("/api/v1/node/{}/export/vcard", person.id))
+        .await;
+    res.assert_status_ok();
+    assert_eq!(res.header(CONTENT_TYPE), VCARD_CONTENT_TYPE);
+    let vcard = res.text();
+    assert!(vcard.starts_with("BEGIN:VCARD\r\n"));
+    assert!(vcard.contains("\r\nFN:Jane Doe\r\n"));
+    assert!(vcard.contains("\r\nEMAIL;TYPE=INTERNET:jane@example.com\r\n"));
+    assert!(vcard.contains("\r\nTEL:+61 400 000 000\r\n"));
+    assert!(vcard.ends_with("END:VCARD\r\n"));
+
+    let res = server
+        .get(&format!("/api/v1/node/{}/export/vcard", email.id))
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 400);
+
+    let res = server
+        .get(&format!("/api/v1/node/{}/export/vcard", Uuid::new_v4()))
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_node_split() {
+    use crate::entity::{attachment, nodelink};
+    use crate::split::{NodeSplitResponse, SPLIT_UNASSIGNED_CHILDREN};
+    use crate::trash::{TrashItem, TrashKind};
+    use osint_graph_shared::nodelink::LinkType;
+    use serde_json::json;
+
+    let server = setup_test_server().await;
+
+    let project = new_test_project("Node split");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+
+    async fn create_node(server: &TestServer, project_id: Uuid, display: &str) -> node::Model {
+        let node = node::Model {
+            project_id,
+            node_type: NodeType::Person,
+            display: display.to_string(),
+            value: display.to_string(),
+            ..Default::default()
+        };
+        server
+            .post("/api/v1/node")
+            .json(&node)
+            .await
+            .assert_status_ok();
+        node
+    }
+
+    async fn setup_original(
+        server: &TestServer,
+        project_id: Uuid,
+    ) -> (node::Model, Vec<Uuid>, Vec<Uuid>) {
+        let original = create_node(server, project_id, "Two people").await;
+
+        let mut attachment_ids = Vec::new();
+        for filename in ["first.txt", "second.txt"] {
+            let form = axum_test::multipart::MultipartForm::new()
+                .add_text("filename", filename)
+                .add_part(
+                    "file",
+                    axum_test::multipart::Part::bytes(filename.as_bytes().to_vec())
+                        .file_name(filename)
+                        .mime_type("text/plain"),
+                );
+            let res = server
+                .post(&format!("/api/v1/node/{}/attachment", original.id))
+                .multipart(form)
+                .await;
+            res.assert_status_ok();
+            attachment_ids.push(res.json::<attachment::Model>().id);
+        }
+
+        let mut link_ids = Vec::new();
+        for neighbour in ["one", "two", "three"] {
+            let neighbour = create_node(server, project_id, neighbour).await;
+            let link = nodelink::Model {
+                id: Uuid::new_v4(),
+                left: original.id,
+                right: neighbour.id,
+                project_id,
+                linktype: LinkType::Omni,
+                weight: None,
+                kind: None,
+                valid_from: None,
+                valid_to: None,
+                created_by: None,
+                created: None,
+            };
+            server
+                .post("/api/v1/nodelink")
+                .json(&link)
+                .await
+                .assert_status_ok();
+            link_ids.push(link.id);
+        }
+        (original, attachment_ids, link_ids)
+    }
+
+    let (original, attachment_ids, link_ids) = setup_original(&server, project.id).await;
+
+    // assignments pointing at a node which wasn't specified are rejected
+    let res = server
+        .post(&format!("/api/v1/node/{}/split", original.id))
+        .json(&json!({
+            "nodes": [{"node_type": "person", "display": "Alice", "value": "Alice"}],
+            "attachments": {attachment_ids[0].to_string(): {"node": 1}},
+        }))
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 400);
+
+    let res = server
+        .post(&format!("/api/v1/node/{}/split", original.id))
+        .json(&json!({
+            "nodes": [
+                {"node_type": "person", "display": "Alice", "value": "Alice"},
+                {"node_type": "person", "display": "Bob", "value": "Bob"},
+            ],
+            "attachments": {
+                attachment_ids[0].to_string(): {"node": 0},
+                attachment_ids[1].to_string(): {"node": 1},
+            },
+            "links": {
+                link_ids[0].to_string(): {"node": 0},
+                link_ids[1].to_string(): {"node": 1},
+                link_ids[2].to_string(): "keep",
+            },
+            "link_to_original": true,
+        }))
+        .await;
+    res.assert_status_ok();
+    let split: NodeSplitResponse = res.json();
+    assert_eq!(split.nodes.len(), 2);
+    assert_eq!(split.moved_attachments.len(), 2);
+    assert_eq!(split.moved_links.len(), 2);
+    assert_eq!(split.created_links.len(), 2);
+    assert!(!split.original_deleted);
+    let (alice, bob) = (&split.nodes[0], &split.nodes[1]);
+
+    for (node_id, expected) in [(alice.id, 1), (bob.id, 1), (original.id, 0)] {
+        let attachments: Vec<attachment::Model> = server
+            .get(&format!("/api/v1/node/{}/attachments", node_id))
+            .await
+            .json();
+        assert_eq!(attachments.len(), expected);
+    }
+
+    // the project, then its links
+    let links: Vec<nodelink::Model> = with_query_budget(2, || {
+        server.get(&format!("/api/v1/project/{}/nodelinks", project.id))
+    })
+    .await
+    .json();
+    let link = |id: Uuid| links.iter().find(|l| l.id == id).expect("link missing");
+    assert_eq!(link(link_ids[0]).left, alice.id);
+    assert_eq!(link(link_ids[1]).left, bob.id);
+    assert_eq!(link(link_ids[2]).left, original.id);
+    assert_eq!(
+        links
+            .iter()
+            .filter(|l| l.left == original.id && (l.right == alice.id || l.right == bob.id))
+            .count(),
+        2
+    );
+
+    // now split another one and throw away the original
+    let (original, attachment_ids, link_ids) = setup_original(&server, project.id).await;
+
+    // which can't happen while anything would be left behind on it
+    let res = server
+        .post(&format!("/api/v1/node/{}/split", original.id))
+        .json(&json!({
+            "nodes": [{"node_type": "person", "display": "Carol", "value": "Carol"}],
+            "attachments": {
+                attachment_ids[0].to_string(): {"node": 0},
+                attachment_ids[1].to_string(): "keep",
+            },
+            "links": {
+                link_ids[0].to_string(): {"node": 0},
+                link_ids[1].to_string(): {"node": 0},
+            },
+            "delete_original": true,
+        }))
+        .expect_failure()
+        .await;
+    res.assert_status_bad_request();
+    let error: serde_json::Value = res.json();
+    assert_eq!(error["code"], SPLIT_UNASSIGNED_CHILDREN);
+    assert_eq!(error["attachments"], json!([attachment_ids[1].to_string()]));
+    assert_eq!(error["links"], json!([link_ids[2].to_string()]));
+    let attachments: Vec<attachment::Model> = server
+        .get(&format!("/api/v1/node/{}/attachments", original.id))
+        .await
+        .json();
+    assert_eq!(attachments.len(), 2, "nothing moved");
+    let res = server
+        .post(&format!("/api/v1/node/{}/split", original.id))
+        .json(&json!({
+            "nodes": [
+                {"node_type": "person", "display": "Carol", "value": "Carol"},
+                {"node_type": "person", "display": "Dave", "value": "Dave"},
+            ],
+            "attachments": {
+                attachment_ids[0].to_string(): {"node": 0},
+                attachment_ids[1].to_string(): {"node": 1},
+            },
+            "links": {
+                link_ids[0].to_string(): {"node": 0},
+                link_ids[1].to_string(): {"node": 1},
+                link_ids[2].to_string(): {"node": 1},
+            },
+            "delete_original": true,
+        }))
+        .await;
+    res.assert_status_ok();
+    let split: NodeSplitResponse = res.json();
+    assert!(split.original_deleted);
+    assert!(split.created_links.is_empty());
+    let trash: Vec<TrashItem> = server
+        .get(&format!("/api/v1/project/{}/trash", project.id))
+        .await
+        .json();
+    assert_eq!(
+        trash
+            .iter()
+            .filter(|item| item.kind == TrashKind::Node)
+            .map(|item| item.id)
+            .collect::<Vec<_>>(),
+        vec![original.id],
+        "the original goes to the trash"
+    );
+
+    server
+        .get(&format!("/api/v1/node/{}", original.id))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+    let attachments: Vec<attachment::Model> = server
+        .get(&format!("/api/v1/node/{}/attachments", split.nodes[1].id))
+        .await
+        .json();
+    assert_eq!(attachments.len(), 1);
+    let links: Vec<nodelink::Model> = server
+        .get(&format!("/api/v1/project/{}/nodelinks", project.id))
+        .await
+        .json();
+    assert_eq!(
+        links.iter().filter(|l| l.left == split.nodes[1].id).count(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn test_api_node_type_styles() {
+    use crate::styles::{NodeShape, NodeTypeStyle, NodeTypeStyles};
+    use sea_orm::Iterable;
+
+    let server = setup_test_server().await;
+    let res = server.get("/api/v1/node-type-styles").await;
+    res.assert_status_ok();
+    let styles: NodeTypeStyles = res.json();
+    for node_type in NodeType::iter() {
+        assert_eq!(
+            styles.0.get(&node_type),
+            Some(&NodeTypeStyle::default_for(node_type)),
+            "missing style for {node_type}"
+        );
+    }
+
+    let styles_file =
+        std::env::temp_dir().join(format!("osint-graph-styles-{}.json", Uuid::new_v4()));
+    std::fs::write(
+        &styles_file,
+        r##"{"person": {"color": "#000000", "shape": "diamond"}}"##,
+    )
+    .expect("Failed to write styles file");
+    let loaded = NodeTypeStyles::load(Some(&styles_file));
+    std::fs::remove_file(&styles_file).expect("Failed to remove styles file");
+    let loaded = loaded.expect("Failed to load styles file");
+
+    let mut appstate = AppState::test().await;
+    appstate.node_type_styles = loaded;
+    let server = setup_test_server_with_state(appstate).await;
+
+    let styles: NodeTypeStyles = server.get("/api/v1/node-type-styles").await.json();
+    let person = &styles.0[&NodeType::Person];
+    assert_eq!(person.color, "#000000");
+    assert_eq!(person.shape, NodeShape::Diamond);
+    assert_eq!(
+        person.icon,
+        NodeTypeStyle::default_for(NodeType::Person).icon
+    );
+    assert_eq!(
+        styles.0[&NodeType::Domain],
+        NodeTypeStyle::default_for(NodeType::Domain)
+    );
+
+    // the exporters use the same styles
+    let project = new_test_project("Styled export");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            node_type: NodeType::Person,
+            display: "Styled".to_string(),
+            value: "Styled".to_string(),
+            ..Default::default()
+        })
+        .await
+        .assert_status_ok();
+    let mermaid = server
+        .get(&format!("/api/v1/project/{}/export/mermaid", project.id))
+        .await
+        .text();
+    assert!(mermaid.contains("style Styled fill:#000000"));
+
+    let bad_file = std::env::temp_dir().join(format!("osint-graph-styles-{}.json", Uuid::new_v4()));
+    std::fs::write(&bad_file, r#"{"notatype": {"color": "red"}}"#)
+        .expect("Failed to write styles file");
+    let loaded = NodeTypeStyles::load(Some(&bad_file));
+    std::fs::remove_file(&bad_file).expect("Failed to remove styles file");
+    assert!(loaded.is_err());
+}
+
+/// A start or empty element from an XML document
+#[derive(Debug)]
+struct XmlElement {
+    name: String,
+    attrs: std::collections::HashMap<String, String>,
+    /// Text up to the next tag
+    text: String,
+}
+
+/// Just enough XML parsing to check exports are well formed, panics on unbalanced tags
+fn parse_xml(doc: &str) -> Vec<XmlElement> {
+    fn unescape(s: &str) -> String {
+        s.replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&")
+    }
+
+    let mut elements = Vec::new();
+    let mut open: Vec<String> = Vec::new();
+    let mut rest = doc
+        .trim_start()
+        .strip_prefix("<?xml")
+        .and_then(|rest| rest.split_once("?>"))
+        .map(|(_, rest)| rest)
+        .expect("XML declaration");
+    while let Some(start) = rest.find('<') {
+        let end = rest[start..].find('>').expect("unterminated tag") + start;
+        let tag = &rest[start + 1..end];
+        let text = rest[end + 1..].split('<').next().unwrap_or_default();
+        if let Some(name) = tag.strip_prefix('/') {
+            assert_eq!(open.pop().as_deref(), Some(name), "mismatched close tag");
+        } else {
+            let empty = tag.ends_with('/');
+            let tag = tag.trim_end_matches('/');
+            let (name, mut attr_text) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+            let mut attrs = std::collections::HashMap::new();
+            while let Some((key, after)) = attr_text.split_once("=\"") {
+                let (value, after) = after.split_once('"').expect("unterminated attribute");
+                assert!(!value.contains('<'), "unescaped < in attribute");
+                attrs.insert(key.trim().to_string(), unescape(value));
+                attr_text = after;
+            }
+            if !empty {
+                open.push(name.to_string());
+            }
+            elements.push(XmlElement {
+                name: name.to_string(),
+                attrs,
+                text: unescape(text),
+            });
+        }
+        rest = &rest[end + 1..];
+    }
+    assert!(open.is_empty(), "unclosed tags {open:?}");
+    elements
+}
+
+#[tokio::test]
+async fn test_api_graphml_export() {
+    use crate::entity::nodelink;
+    use crate::project::GRAPHML_CONTENT_TYPE;
+    use osint_graph_shared::nodelink::LinkType;
+
+    let server = setup_test_server().await;
+    let project = new_test_project("GraphML <Test> & \"friends\"");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+
+    let nodes: Vec<node::Model> = [
+        (NodeType::Person, "Jane <Doe>", Some("likes & dislikes")),
+        (NodeType::Domain, "example.com", None),
+        (NodeType::Email, "jane@example.com", None),
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(index, (node_type, display, notes))| node::Model {
+        project_id: project.id,
+        id: Uuid::new_v4(),
+        node_type,
+        display: display.to_string(),
+        value: display.to_string(),
+        updated: chrono::Utc::now(),
+        notes: notes.map(str::to_string),
+        pos_x: Some(index as i32 * 100),
+        pos_y: None,
+        created_by: None,
+        created: None,
+        deleted_at: None,
+    })
+    .collect();
+    for node in &nodes {
+        server
+            .post("/api/v1/node")
+            .json(node)
+            .await
+            .assert_status_ok();
+    }
+    let links = [
+        (0, 1, LinkType::Directional, Some("owns")),
+        (1, 2, LinkType::Omni, None),
+    ];
+    for (left, right, linktype, kind) in links {
+        server
+            .post("/api/v1/nodelink")
+            .json(&nodelink::Model {
+                id: Uuid::new_v4(),
+                project_id: project.id,
+                left: nodes[left].id,
+                right: nodes[right].id,
+                linktype,
+                weight: None,
+                kind: kind.map(str::to_string),
+                valid_from: None,
+                valid_to: None,
+                created_by: None,
+                created: None,
+            })
+            .await
+            .assert_status_ok();
+    }
+
+    let res = server
+        .get(&format!("/api/v1/project/{}/export/graphml", project.id))
+        .await;
+    res.assert_status_ok();
+    assert_eq!(res.header(CONTENT_TYPE), GRAPHML_CONTENT_TYPE);
+    assert!(res
+        .header(CONTENT_DISPOSITION)
+        .to_str()
+        .expect("ascii header")
+        .ends_with(".graphml\""));
+
+    let elements = parse_xml(&res.text());
+    assert_eq!(elements[0].name, "graphml");
+    let exported_nodes: Vec<&XmlElement> = elements.iter().filter(|e| e.name == "node").collect();
+    let edges: Vec<&XmlElement> = elements.iter().filter(|e| e.name == "edge").collect();
+    assert_eq!(exported_nodes.len(), nodes.len());
+    assert_eq!(edges.len(), links.len());
+
+    for node in &nodes {
+        assert!(exported_nodes
+            .iter()
+            .any(|e| e.attrs.get("id") == Some(&node.id.to_string())));
+    }
+    let directed = edges
+        .iter()
+        .find(|e| e.attrs.get("source") == Some(&nodes[0].id.to_string()))
+        .expect("directional edge");
+    assert_eq!(
+        directed.attrs.get("directed").map(String::as_str),
+        Some("true")
+    );
+    assert_eq!(directed.attrs.get("target"), Some(&nodes[1].id.to_string()));
+    let undirected = edges
+        .iter()
+        .find(|e| e.attrs.get("source") == Some(&nodes[1].id.to_string()))
+        .expect("omni edge");
+    assert_eq!(
+        undirected.attrs.get("directed").map(String::as_str),
+        Some("false")
+    );
+
+    // markup in values survives the round trip
+    let data: Vec<&str> = elements
+        .iter()
+        .filter(|e| e.name == "data")
+        .map(|e| e.text.as_str())
+        .collect();
+    assert!(data.contains(&"Jane <Doe>"));
+    assert!(data.contains(&"likes & dislikes"));
+    assert!(data.contains(&"person"));
+    assert!(data.contains(&"owns"));
+    for key in ["node_type", "display", "value", "notes", "pos_x", "pos_y"] {
+        assert!(elements
+            .iter()
+            .any(|e| e.name == "key" && e.attrs.get("id").map(String::as_str) == Some(key)));
+    }
+}
+
+#[tokio::test]
+async fn test_api_graphml_export_not_found() {
+    let server = setup_test_server().await;
+
+    let res = server
+        .get(&format!(
+            "/api/v1/project/{}/export/graphml",
+            Uuid::new_v4()
+        ))
+        .expect_failure()
+        .await;
+    res.assert_status_not_found();
+}
+
+#[tokio::test]
+async fn test_api_dot_export() {
+    use crate::entity::nodelink;
+    use crate::project::DOT_CONTENT_TYPE;
+    use osint_graph_shared::nodelink::LinkType;
+
+    let server = setup_test_server().await;
+    let project = new_test_project("DOT \"quoted\" project");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+
+    let nodes: Vec<node::Model> = [
+        (NodeType::Person, "Jane \"JD\" Doe"),
+        (NodeType::Domain, "example.com"),
+        (NodeType::Image, "photo"),
+    ]
+    .into_iter()
+    .map(|(node_type, display)| node::Model {
+        project_id: project.id,
+        node_type,
+        display: display.to_string(),
+        value: display.to_string(),
+        ..Default::default()
+    })
+    .collect();
+    for node in &nodes {
+        server
+            .post("/api/v1/node")
+            .json(node)
+            .await
+            .assert_status_ok();
+    }
+    for (left, right, linktype, kind) in [
+        (0, 1, LinkType::Directional, Some("owns")),
+        (1, 2, LinkType::Omni, None),
+    ] {
+        server
+            .post("/api/v1/nodelink")
+            .json(&nodelink::Model {
+                id: Uuid::new_v4(),
+                project_id: project.id,
+                left: nodes[left].id,
+                right: nodes[right].id,
+                linktype,
+                weight: None,
+                kind: kind.map(str::to_string),
+                valid_from: None,
+                valid_to: None,
+                created_by: None,
+                created: None,
+            })
+            .await
+            .assert_status_ok();
+    }
+
+    let res = server
+        .get(&format!("/api/v1/project/{}/export/dot", project.id))
+        .await;
+    res.assert_status_ok();
+    assert_eq!(res.header(CONTENT_TYPE), DOT_CONTENT_TYPE);
+    assert!(res
+        .header(CONTENT_DISPOSITION)
+        .to_str()
+        .expect("ascii header")
+        .ends_with(".dot\""));
+    let dot = res.text();
+    assert!(
+        dot.starts_with("// Project: DOT \"quoted\" project\n"),
+        "{dot}"
+    );
+    assert!(
+        dot.contains(&format!("digraph \"{}\" {{", project.id)),
+        "{dot}"
+    );
+    assert!(
+        dot.contains("label=\"DOT \\\"quoted\\\" project\";"),
+        "{dot}"
+    );
+    assert!(dot.contains(&format!(
+        "\"{}\" [label=\"Jane \\\"JD\\\" Doe\", shape=ellipse, fillcolor=\"#3b82f6\"];",
+        nodes[0].id
+    )));
+    assert!(dot.contains(&format!("\"{}\" [label=\"photo\", shape=box,", nodes[2].id)));
+
+    let edge = |left: &node::Model, right: &node::Model| {
+        let prefix = format!("    \"{}\" -> \"{}\"", left.id, right.id);
+        dot.lines()
+            .find(|line| line.starts_with(&prefix))
+            .unwrap_or_else(|| panic!("missing edge in {dot}"))
+            .to_string()
+    };
+    let directed = edge(&nodes[0], &nodes[1]);
+    assert!(!directed.contains("dir=none"), "{directed}");
+    assert!(directed.contains("label=\"owns\""), "{directed}");
+    let undirected = edge(&nodes[1], &nodes[2]);
+    assert!(undirected.ends_with("[dir=none];"), "{undirected}");
+
+    server
+        .get(&format!("/api/v1/project/{}/export/dot", Uuid::new_v4()))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+}
+
+#[tokio::test]
+async fn test_api_mermaid_export_cache() {
+    use crate::export_cache::CACHE_HEADER;
+
+    let mut appstate = AppState::test().await;
+    // small enough that switching filters evicts the other entry
+    appstate.export_cache.max_entries_per_project = 1;
+    let server = setup_test_server_with_state(appstate).await;
+
+    let project = new_test_project("Cached export");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    let mut person = node::Model {
+        project_id: project.id,
+        node_type: NodeType::Person,
+        display: "Cached Person".to_string(),
+        value: "Cached Person".to_string(),
+        ..Default::default()
+    };
+    let domain = node::Model {
+        project_id: project.id,
+        node_type: NodeType::Domain,
+        display: "example.com".to_string(),
+        value: "example.com".to_string(),
+        ..Default::default()
+    };
+    for node in [&person, &domain] {
+        server
+            .post("/api/v1/node")
+            .json(node)
+            .await
+            .assert_status_ok();
+    }
+
+    let export = |query: &'static str| {
+        let url = format!("/api/v1/project/{}/export/mermaid{}", project.id, query);
+        let server = &server;
+        async move {
+            let res = server.get(&url).await;
+            res.assert_status_ok();
+            (res.header(CACHE_HEADER), res.text())
+        }
+    };
+
+    let (status, first) = export("").await;
+    assert_eq!(status, "miss");
+    let (status, second) = export("").await;
+    assert_eq!(status, "hit");
+    assert_eq!(first, second);
+
+    person.display = "Renamed Person".to_string();
+    server
+        .put(&format!("/api/v1/node/{}", person.id))
+        .json(&person)
+        .await
+        .assert_status_ok();
+    let (status, renamed) = export("").await;
+    assert_eq!(status, "miss");
+    assert!(renamed.contains("class RenamedPerson"));
+    assert!(!renamed.contains("class CachedPerson"));
+
+    let (status, filtered) = export("?node_types=person").await;
+    assert_eq!(status, "miss");
+    assert!(filtered.contains("class RenamedPerson"));
+    assert!(!filtered.contains("class examplecom"));
+    // the same filter in a different order is the same cache entry
+    let (status, filtered_again) = export("?node_types=person,person").await;
+    assert_eq!(status, "hit");
+    assert_eq!(filtered, filtered_again);
+
+    // caching the filtered export evicted the unfiltered one
+    let (status, unfiltered) = export("").await;
+    assert_eq!(status, "miss");
+    assert_eq!(unfiltered, renamed);
+
+    let res = server
+        .get(&format!(
+            "/api/v1/project/{}/export/mermaid?node_types=nope",
+            project.id
+        ))
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 400);
+}
+
+#[tokio::test]
+async fn test_api_admin_users() {
+    use crate::admin_users::UserSummary;
+    use crate::entity::user;
+    use crate::oauth::middleware::AuthUser;
+    use axum::http::Method;
+    use sea_orm::{ActiveModelTrait, Set};
+    use serde_json::json;
+
+    let appstate = AppState::test().await;
+    let mut users = Vec::new();
+    for (name, is_admin) in [("alice", true), ("bob", false), ("carol", false)] {
+        let user = user::ActiveModel {
+            subject: Set(name.to_string()),
+            email: Set(format!("{name}@example.com")),
+            uuid: Set(Uuid::new_v4()),
+            is_admin: Set(is_admin),
+            ..Default::default()
+        }
+        .insert(&appstate.conn)
+        .await
+        .expect("Failed to create user");
+        users.push(AuthUser::from(user));
+    }
+    let servers = setup_test_servers_as_users(appstate, &users).await;
+    let (alice, bob, carol) = (&servers[0], &servers[1], &servers[2]);
+
+    // bob has two projects with three nodes and an attachment, carol one project and node
+    let mut bob_nodes: Vec<node::Model> = Vec::new();
+    for (server, nodes_per_project) in [(bob, vec![2, 1]), (carol, vec![1])] {
+        for (index, node_count) in nodes_per_project.into_iter().enumerate() {
+            let project: project::Model = server
+                .post("/api/v1/project")
+                .json(&new_test_project(&format!("Project {index}")))
+                .await
+                .json();
+            for node_index in 0..node_count {
+                let node: node::Model = server
+                    .post("/api/v1/node")
+                    .json(&node::Model {
+                        project_id: project.id,
+                        display: format!("Node {node_index}"),
+                        ..Default::default()
+                    })
+                    .await
+                    .json();
+                if project.user == users[1].id {
+                    bob_nodes.push(node);
+                }
+            }
+        }
+    }
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(b"0123456789".to_vec())
+            .file_name("evidence.txt")
+            .mime_type("text/plain"),
+    );
+    bob.post(&format!("/api/v1/node/{}/attachment", bob_nodes[0].id))
+        .multipart(form)
+        .await
+        .assert_status_ok();
+
+    let page: PaginatedResponse<UserSummary> = alice.get("/api/v1/admin/users").await.json();
+    assert_eq!(page.total_count, 3);
+    let summaries: Vec<(&str, bool, u64, u64, u64)> = page
+        .items
+        .iter()
+        .map(|user| {
+            (
+                user.subject.as_str(),
+                user.is_admin,
+                user.project_count,
+                user.node_count,
+                user.attachment_bytes,
+            )
+        })
+        .collect();
+    assert_eq!(
+        summaries,
+        vec![
+            ("alice", true, 0, 0, 0),
+            ("bob", false, 2, 3, 10),
+            ("carol", false, 1, 1, 0),
+        ]
+    );
+    assert_eq!(page.items[1].id, users[1].id);
+    assert_eq!(page.items[1].email, "bob@example.com");
+
+    // searching by email, case-insensitively
+    let page: PaginatedResponse<UserSummary> = alice.get("/api/v1/admin/users?q=BOB@").await.json();
+    assert_eq!(page.total_count, 1);
+    assert_eq!(page.items[0].subject, "bob");
+    let page: PaginatedResponse<UserSummary> =
+        alice.get("/api/v1/admin/users?q=example.com").await.json();
+    assert_eq!(page.total_count, 3);
+    let page: PaginatedResponse<UserSummary> =
+        alice.get("/api/v1/admin/users?q=nobody").await.json();
+    assert_eq!(page.total_count, 0);
+    assert!(page.items.is_empty());
+
+    // pagination
+    let page: PaginatedResponse<UserSummary> = alice
+        .get("/api/v1/admin/users?page=2&page_size=2")
+        .await
+        .json();
+    assert_eq!(page.total_count, 3);
+    assert_eq!(page.items.len(), 1);
+    assert_eq!(page.items[0].subject, "carol");
+
+    // fixing carol's name and promoting bob
+    let carol_summary: UserSummary = alice
+        .put(&format!("/api/v1/admin/users/{}", users[2].id))
+        .json(&json!({"display_name": "  Carol C  "}))
+        .await
+        .json();
+    assert_eq!(carol_summary.display_name.as_deref(), Some("Carol C"));
+    assert!(!carol_summary.is_admin);
+    assert_eq!(carol_summary.project_count, 1);
+    let bob_summary: UserSummary = alice
+        .put(&format!("/api/v1/admin/users/{}", users[1].id))
+        .json(&json!({"is_admin": true}))
+        .await
+        .json();
+    assert!(bob_summary.is_admin);
+    assert_eq!(bob_summary.display_name, None);
+
+    // admins can't lock themselves out
+    alice
+        .put(&format!("/api/v1/admin/users/{}", users[0].id))
+        .json(&json!({"is_admin": false}))
+        .expect_failure()
+        .await
+        .assert_status_bad_request();
+    alice
+        .delete(&format!("/api/v1/admin/users/{}", users[0].id))
+        .expect_failure()
+        .await
+        .assert_status_bad_request();
+    alice
+        .put(&format!("/api/v1/admin/users/{}", Uuid::new_v4()))
+        .json(&json!({"is_admin": true}))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+
+    // carol isn't an admin, so every admin route is off limits
+    let user_url = format!("/api/v1/admin/users/{}", users[1].id);
+    let raw_url = format!("/api/v1/attachment/{}/raw", Uuid::new_v4());
+    let dedup_job_url = format!("/api/v1/admin/attachments/deduplicate/{}", Uuid::new_v4());
+    let admin_routes: [(Method, &str); 13] = [
+        (Method::GET, "/api/v1/nodes"),
+        (Method::GET, &raw_url),
+        (Method::GET, "/api/v1/admin/attachments/duplicates"),
+        (Method::POST, "/api/v1/admin/attachments/deduplicate"),
+        (Method::GET, &dedup_job_url),
+        (Method::DELETE, &dedup_job_url),
+        (Method::GET, "/api/v1/admin/db-health"),
+        (Method::GET, "/api/v1/admin/value-policy"),
+        (Method::GET, "/api/v1/admin/storage/orphans"),
+        (Method::POST, "/api/v1/admin/storage/gc"),
+        (Method::GET, "/api/v1/admin/users"),
+        (Method::PUT, &user_url),
+        (Method::DELETE, &user_url),
+    ];
+    for (method, url) in admin_routes {
+        let request = carol.method(method.clone(), url).expect_failure();
+        let request = match method {
+            Method::PUT => request.json(&json!({"is_admin": false})),
+            _ => request,
+        };
+        request.await.assert_status_forbidden();
+    }
+    // while creating nodes in bulk on the same path isn't
+    let carol_project: project::Model = carol
+        .post("/api/v1/project")
+        .json(&new_test_project("Carol's bulk"))
+        .await
+        .json();
+    carol
+        .post("/api/v1/nodes")
+        .json(&vec![node::Model {
+            project_id: carol_project.id,
+            ..Default::default()
+        }])
+        .await
+        .assert_status_ok();
+
+    // deleting carol takes her projects with her
+    alice
+        .delete(&format!("/api/v1/admin/users/{}", users[2].id))
+        .await
+        .assert_status_ok();
+    let page: PaginatedResponse<UserSummary> = alice.get("/api/v1/admin/users").await.json();
+    assert_eq!(page.total_count, 2);
+    alice
+        .get(&format!("/api/v1/project/{}", carol_project.id))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+    let projects: PaginatedResponse<project::Model> =
+        bob.get("/api/v1/projects?page_size=1000").await.json();
+    assert!(projects.items.iter().any(|p| p.user == users[1].id));
+}
+
+#[tokio::test]
+async fn test_api_attachment_project_scope() {
+    use crate::entity::{attachment, user};
+    use crate::oauth::middleware::AuthUser;
+    use sea_orm::{ActiveModelTrait, Set};
+
+    let appstate = AppState::test().await;
+    let mut users = Vec::new();
+    for name in ["alice", "bob"] {
+        let user = user::ActiveModel {
+            subject: Set(name.to_string()),
+            email: Set(format!("{name}@example.com")),
+            uuid: Set(Uuid::new_v4()),
+            ..Default::default()
+        }
+        .insert(&appstate.conn)
+        .await
+        .expect("Failed to create user");
+        users.push(AuthUser::from(user));
+    }
+    let servers = setup_test_servers_as_users(appstate, &users).await;
+    let (alice, bob) = (&servers[0], &servers[1]);
+
+    // bob's project is owned by bob, whatever the client says
+    let project: project::Model = bob
+        .post("/api/v1/project")
+        .json(&new_test_project("Bob's evidence"))
+        .await
+        .json();
+    assert_eq!(project.user, users[1].id);
+    let node = node::Model {
+        project_id: project.id,
+        node_type: NodeType::Document,
+        display: "secret".to_string(),
+        value: "secret".to_string(),
+        ..Default::default()
+    };
+    bob.post("/api/v1/node")
+        .json(&node)
+        .await
+        .assert_status_ok();
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(b"bob's secret".to_vec())
+            .file_name("secret.txt")
+            .mime_type("text/plain"),
+    );
+    let bobs_attachment: attachment::Model = bob
+        .post(&format!("/api/v1/node/{}/attachment", node.id))
+        .multipart(form)
+        .await
+        .json();
+
+    let res = bob
+        .get(&format!("/api/v1/attachment/{}", bobs_attachment.id))
+        .await;
+    assert_eq!(res.as_bytes().as_ref(), b"bob's secret");
+    let export: crate::project::ProjectExport = bob
+        .get(&format!(
+            "/api/v1/project/{}/export?include_attachments=true",
+            project.id
+        ))
+        .await
+        .json();
+    assert_eq!(export.attachments.len(), 1);
+
+    for path in [
+        format!("/api/v1/attachment/{}", bobs_attachment.id),
+        format!("/api/v1/attachment/{}/view", bobs_attachment.id),
+        format!("/api/v1/node/{}/attachments", node.id),
+        format!(
+            "/api/v1/project/{}/export?include_attachments=true",
+            project.id
+        ),
+    ] {
+        alice
+            .get(&path)
+            .expect_failure()
+            .await
+            .assert_status_forbidden();
+    }
+    alice
+        .delete(&format!("/api/v1/attachment/{}", bobs_attachment.id))
+        .expect_failure()
+        .await
+        .assert_status_forbidden();
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(b"alice was here".to_vec()).file_name("a.txt"),
+    );
+    alice
+        .post(&format!("/api/v1/node/{}/attachment", node.id))
+        .multipart(form)
+        .expect_failure()
+        .await
+        .assert_status_forbidden();
+
+    // unknown attachments are still a 404
+    alice
+        .get(&format!("/api/v1/attachment/{}", Uuid::new_v4()))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+
+    // alice can't move bob's attachment into her project either
+    let alices_project: project::Model = alice
+        .post("/api/v1/project")
+        .json(&new_test_project("Alice's project"))
+        .await
+        .json();
+    let alices_node = node::Model {
+        project_id: alices_project.id,
+        ..Default::default()
+    };
+    alice
+        .post("/api/v1/node")
+        .json(&alices_node)
+        .await
+        .assert_status_ok();
+    alice
+        .patch(&format!("/api/v1/attachment/{}", bobs_attachment.id))
+        .json(&serde_json::json!({"node_id": alices_node.id}))
+        .expect_failure()
+        .await
+        .assert_status_forbidden();
+    bob.patch(&format!("/api/v1/attachment/{}", bobs_attachment.id))
+        .json(&serde_json::json!({"node_id": alices_node.id}))
+        .expect_failure()
+        .await
+        .assert_status_forbidden();
+}
+
+#[tokio::test]
+async fn test_api_project_reads_scoped() {
+    use crate::entity::nodelink;
+    use crate::project::SearchResult;
+    use axum::http::Method;
+    use osint_graph_shared::nodelink::LinkType;
+
+    let appstate = AppState::test().await;
+    let users = new_test_users(&appstate.conn, &[("alice", false), ("bob", false)]).await;
+    let servers = setup_test_servers_as_users(appstate, &users).await;
+    let (alice, bob) = (&servers[0], &servers[1]);
+
+    let project: project::Model = bob
+        .post("/api/v1/project")
+        .json(&new_test_project("Bob's needle case"))
+        .await
+        .json();
+    let person = node::Model {
+        project_id: project.id,
+        node_type: NodeType::Person,
+        display: "Needle Person".to_string(),
+        value: "needle person".to_string(),
+        ..Default::default()
+    };
+    let email = node::Model {
+        project_id: project.id,
+        node_type: NodeType::Email,
+        display: "needle mail".to_string(),
+        value: "needle@example.com".to_string(),
+        ..Default::default()
+    };
+    for node in [&person, &email] {
+        bob.post("/api/v1/node").json(node).await.assert_status_ok();
+    }
+    let link = nodelink::Model {
+        id: Uuid::new_v4(),
+        left: person.id,
+        right: email.id,
+        project_id: project.id,
+        linktype: LinkType::Omni,
+        weight: None,
+        kind: None,
+        valid_from: None,
+        valid_to: None,
+        created_by: None,
+        created: None,
+    };
+    bob.post("/api/v1/nodelink")
+        .json(&link)
+        .await
+        .assert_status_ok();
+
+    // every read and export of bob's project works for him and is forbidden to alice
+    let project_url = format!("/api/v1/project/{}", project.id);
+    let person_url = format!("/api/v1/node/{}", person.id);
+    let mut reads = vec![project_url.clone(), person_url.clone()];
+    reads.extend(
+        [
+            "stats",
+            "nodes",
+            "nodelinks",
+            "review",
+            "score",
+            "export",
+            "export/mermaid",
+            "export/graphml",
+            "export/dot",
+            "export/jsonld",
+            "export/csv",
+            "export/timeline.json",
+            "export/archive",
+            "export/report.pdf",
+        ]
+        .map(|path| format!("{project_url}/{path}")),
+    );
+    reads.extend(
+        ["nodelinks", "history", "type-history", "export/vcard"]
+            .map(|path| format!("{person_url}/{path}")),
+    );
+    for url in reads.iter() {
+        bob.get(url).await.assert_status_ok();
+        alice
+            .get(url)
+            .expect_failure()
+            .await
+            .assert_status_forbidden();
+    }
+
+    // and so is changing it
+    let link_url = format!("/api/v1/nodelink/{}", link.id);
+    let writes = [
+        (Method::PUT, project_url.clone()),
+        (Method::POST, format!("{project_url}/pin")),
+        (Method::POST, format!("{project_url}/unpin")),
+        (Method::PATCH, format!("{project_url}/archive")),
+        (Method::PATCH, format!("{project_url}/unarchive")),
+        (Method::POST, "/api/v1/node".to_string()),
+        (Method::POST, "/api/v1/nodes".to_string()),
+        (Method::PUT, person_url.clone()),
+        (Method::POST, "/api/v1/nodelink".to_string()),
+        (Method::PUT, link_url.clone()),
+        (Method::DELETE, link_url),
+        (Method::DELETE, person_url),
+        (Method::DELETE, project_url),
+    ];
+    for (method, url) in writes {
+        let request = alice.method(method.clone(), &url).expect_failure();
+        let request = match (method, url.as_str()) {
+            (Method::PUT, url) if url.starts_with("/api/v1/project/") => request.json(&project),
+            (Method::POST, "/api/v1/node") => request.json(&node::Model {
+                project_id: project.id,
+                ..Default::default()
+            }),
+            (Method::POST, "/api/v1/nodes") => request.json(&vec![node::Model {
+                project_id: project.id,
+                ..Default::default()
+            }]),
+            (Method::PUT, url) if url.starts_with("/api/v1/node/") => request.json(&person),
+            (Method::POST, "/api/v1/nodelink") => request.json(&nodelink::Model {
+                id: Uuid::new_v4(),
+                ..link.clone()
+            }),
+            (Method::PUT, _) => request.json(&link),
+            _ => request,
+        };
+        request.await.assert_status_forbidden();
+    }
+    let nodes: PaginatedResponse<node::Model> = bob
+        .get(&format!("/api/v1/project/{}/nodes", project.id))
+        .await
+        .json();
+    assert_eq!(nodes.total_count, 2);
+
+    // searching and listing only turn up the caller's projects
+    for (server, found) in [(alice, false), (bob, true)] {
+        let results: Vec<SearchResult> = server.get("/api/v1/search?q=needle").await.json();
+        assert_eq!(!results.is_empty(), found, "{results:?}");
+        assert!(results.iter().all(|r| r.project_id == project.id));
+        let projects: PaginatedResponse<project::Model> =
+            server.get("/api/v1/projects?page_size=1000").await.json();
+        assert_eq!(projects.items.iter().any(|p| p.id == project.id), found);
+    }
+    alice
+        .get(&format!(
+            "/api/v1/search?q=needle&project_id={}",
+            project.id
+        ))
+        .expect_failure()
+        .await
+        .assert_status_forbidden();
+}
+
+#[tokio::test]
+async fn test_api_redacted_exports() {
+    use crate::entity::nodelink;
+    use crate::redact::REDACTED;
+    use osint_graph_shared::nodelink::LinkType;
+
+    let server = setup_test_server().await;
+
+    let mut project = new_test_project("Operation Secret Squirrel");
+    project.description = Some("confidential description".to_string());
+    project.tags = StringVec(vec!["secret-tag".to_string()]);
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+
+    let nodes = [
+        (NodeType::Person, "Jane Target", "jane.target.secret"),
+        (
+            NodeType::Person,
+            "John Accomplice",
+            "john.accomplice.secret",
+        ),
+        (
+            NodeType::Domain,
+            "secret-domain.example",
+            "secret-domain.example",
+        ),
+        (NodeType::Email, "jane mail", "jane@secret-domain.example"),
+    ]
+    .map(|(node_type, display, value)| node::Model {
+        project_id: project.id,
+        node_type,
+        display: display.to_string(),
+        value: value.to_string(),
+        notes: Some(format!("private notes about {value}")),
+        pos_x: Some(10),
+        pos_y: Some(20),
+        ..Default::default()
+    });
+    for node in nodes.iter() {
+        server
+            .post("/api/v1/node")
+            .json(node)
+            .await
+            .assert_status_ok();
+    }
+    let links = [
+        (0, 1, LinkType::Omni),
+        (0, 3, LinkType::Directional),
+        (3, 2, LinkType::Directional),
+    ];
+    for (index, (left, right, linktype)) in links.into_iter().enumerate() {
+        server
+            .post("/api/v1/nodelink")
+            .json(&nodelink::Model {
+                id: Uuid::new_v4(),
+                left: nodes[left].id,
+                right: nodes[right].id,
+                project_id: project.id,
+                linktype,
+                weight: None,
+                kind: (index > 0).then(|| format!("secret-label-{index}")),
+                valid_from: (index == 2).then(|| "2011-11-11T00:00:00Z".parse().expect("date")),
+                valid_to: (index == 2).then(|| "2012-12-12T00:00:00Z".parse().expect("date")),
+                created_by: None,
+                created: None,
+            })
+            .await
+            .assert_status_ok();
+    }
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(b"evidence".to_vec())
+            .file_name("secret-evidence.txt")
+            .mime_type("text/plain"),
+    );
+    server
+        .post(&format!("/api/v1/node/{}/attachment", nodes[0].id))
+        .multipart(form)
+        .await
+        .assert_status_ok();
+
+    let mut secrets = vec![
+        project.name.clone(),
+        project.id.to_string(),
+        "confidential".to_string(),
+        "secret-tag".to_string(),
+        "secret-evidence".to_string(),
+        "secret-label".to_string(),
+        "2011-11-11".to_string(),
+        "2012-12-12".to_string(),
+    ];
+    for node in nodes.iter() {
+        secrets.extend([
+            node.id.to_string(),
+            node.display.clone(),
+            node.value.clone(),
+            node.notes.clone().unwrap_or_default(),
+        ]);
+    }
+    let assert_redacted = |output: &str| {
+        for secret in secrets.iter() {
+            assert!(
+                !output.contains(secret.as_str()),
+                "{secret} leaked into {output}"
+            );
+        }
+        assert!(output.contains(REDACTED));
+    };
+
+    let res = server
+        .get(&format!(
+            "/api/v1/project/{}/export?redact=true&include_attachments=true",
+            project.id
+        ))
+        .await;
+    res.assert_status_ok();
+    assert_redacted(&res.text());
+    let export: ProjectExport = res.json();
+    assert!(export.redacted);
+    assert!(export.attachments.is_empty());
+    assert_eq!(export.project.description, None);
+    let mut node_types: Vec<_> = export.nodes.iter().map(|n| n.node_type).collect();
+    node_types.sort();
+    assert_eq!(
+        node_types,
+        vec![
+            NodeType::Person,
+            NodeType::Person,
+            NodeType::Domain,
+            NodeType::Email
+        ]
+    );
+    assert!(export
+        .nodes
+        .iter()
+        .all(|n| n.pos_x == Some(10) && n.pos_y == Some(20) && n.notes.is_none()));
+    let by_id: std::collections::HashMap<_, _> = export.nodes.iter().map(|n| (n.id, n)).collect();
+    assert_eq!(export.nodelinks.len(), links.len());
+    for (left, right, linktype) in links {
+        let expected = (nodes[left].node_type, nodes[right].node_type, linktype);
+        assert!(
+            export.nodelinks.iter().any(|l| (
+                by_id[&l.left].node_type,
+                by_id[&l.right].node_type,
+                l.linktype
+            ) == expected),
+            "missing link {expected:?}"
+        );
+    }
+    let mut labels: Vec<_> = export.nodelinks.iter().map(|l| l.kind.as_deref()).collect();
+    labels.sort();
+    assert_eq!(labels, vec![None, Some(REDACTED), Some(REDACTED)]);
+    assert!(export
+        .nodelinks
+        .iter()
+        .all(|l| l.valid_from.is_none() && l.valid_to.is_none()));
+    let mut placeholders: Vec<_> = export.nodes.iter().map(|n| n.value.as_str()).collect();
+    placeholders.sort();
+    assert_eq!(
+        placeholders,
+        vec!["domain-1", "email-1", "person-1", "person-2"]
+    );
+
+    let res = server
+        .get(&format!(
+            "/api/v1/project/{}/export/mermaid?redact=true",
+            project.id
+        ))
+        .await;
+    res.assert_status_ok();
+    let mermaid = res.text();
+    assert_redacted(&mermaid);
+    assert!(mermaid.contains("class person1"));
+    assert_eq!(mermaid.matches(" --> ").count(), 2);
+    assert_eq!(mermaid.matches(" -- ").count(), 1);
+
+    // the other formats take the same flag, filenames included
+    for format in [
+        "graphml",
+        "dot",
+        "jsonld",
+        "csv",
+        "timeline.json",
+        "archive",
+        "report.pdf",
+    ] {
+        let res = server
+            .get(&format!(
+                "/api/v1/project/{}/export/{format}?redact=true",
+                project.id
+            ))
+            .await;
+        res.assert_status_ok();
+        let body = match format {
+            "report.pdf" => crate::pdf::extract_text(res.as_bytes()),
+            _ => res.text(),
+        };
+        assert!(body.contains("person-2"), "{format} is missing nodes");
+        let disposition = res.header(CONTENT_DISPOSITION);
+        let disposition = disposition.to_str().expect("ascii header");
+        assert!(disposition.contains(REDACTED), "{disposition}");
+        assert_redacted(&format!("{disposition}\n{body}"));
+    }
+
+    // and the normal export is untouched
+    let export: ProjectExport = server
+        .get(&format!("/api/v1/project/{}/export", project.id))
+        .await
+        .json();
+    assert!(!export.redacted);
+    assert_eq!(export.project.name, project.name);
+}
+
+#[tokio::test]
+async fn test_api_attachment_rename() {
+    use crate::entity::attachment;
+    use serde_json::json;
+
+    let server = setup_test_server().await;
+    let project = new_test_project("Attachment rename");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    let node = node::Model {
+        project_id: project.id,
+        ..Default::default()
+    };
+    server
+        .post("/api/v1/node")
+        .json(&node)
+        .await
+        .assert_status_ok();
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(b"hello".to_vec())
+            .file_name("tpyo.txt")
+            .mime_type("text/plain"),
+    );
+    let uploaded: attachment::Model = server
+        .post(&format!("/api/v1/node/{}/attachment", node.id))
+        .multipart(form)
+        .await
+        .json();
+    let url = format!("/api/v1/attachment/{}", uploaded.id);
+
+    let renamed: attachment::Model = server
+        .patch(&url)
+        .json(&json!({"filename": " typo.md ", "content_type": "text/markdown; charset=utf-8"}))
+        .await
+        .json();
+    assert_eq!(renamed.filename, "typo.md");
+    assert_eq!(renamed.content_type, "text/markdown; charset=utf-8");
+
+    let res = server.get(&url).await;
+    assert_eq!(res.header(CONTENT_TYPE), "text/markdown; charset=utf-8");
+    assert!(res
+        .header(CONTENT_DISPOSITION)
+        .to_str()
+        .unwrap()
+        .contains("typo.md"));
+
+    for body in [
+        json!({"filename": "../etc/passwd"}),
+        json!({"filename": "dir\\file.txt"}),
+        json!({"filename": "bad\nname.txt"}),
+        json!({"filename": "  "}),
+        json!({"content_type": "not a mime type"}),
+        json!({"content_type": "text/"}),
+    ] {
+        server
+            .patch(&url)
+            .json(&body)
+            .expect_failure()
+            .await
+            .assert_status_bad_request();
+    }
+
+    let listed: Vec<attachment::Model> = server
+        .get(&format!("/api/v1/node/{}/attachments", node.id))
+        .await
+        .json();
+    assert_eq!(listed[0].filename, "typo.md");
+    assert_eq!(listed[0].content_type, "text/markdown; charset=utf-8");
+}
+
+#[tokio::test]
+async fn test_api_cors_headers() {
+    use axum::http::{
+        header::{
+            ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE,
+            ACCESS_CONTROL_REQUEST_METHOD, ETAG, ORIGIN,
+        },
+        HeaderValue, Method,
+    };
+
+    let mut appstate = AppState::test().await;
+    appstate.cors_max_age = std::time::Duration::from_secs(1234);
+    let server = setup_test_server_with_state(appstate).await;
+    let origin = HeaderValue::from_static("https://elsewhere.example.com");
+
+    let res = server
+        .method(Method::OPTIONS, "/api/v1/projects")
+        .add_header(ORIGIN, origin.clone())
+        .add_header(
+            ACCESS_CONTROL_REQUEST_METHOD,
+            HeaderValue::from_static("GET"),
+        )
+        .await;
+    res.assert_status_ok();
+    assert_eq!(res.header(ACCESS_CONTROL_MAX_AGE), "1234");
+    assert_eq!(res.header(ACCESS_CONTROL_ALLOW_ORIGIN), "*");
+
+    // a real cross-origin request can read the ETag
+    let node = node::Model {
+        project_id: Uuid::nil(),
+        display: "cors".to_string(),
+        ..Default::default()
+    };
+    server
+        .post("/api/v1/node")
+        .json(&node)
+        .await
+        .assert_status_ok();
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(b"cross origin".to_vec())
+            .file_name("cors.txt")
+            .mime_type("text/plain"),
+    );
+    let attachment: crate::entity::attachment::Model = server
+        .post(&format!("/api/v1/node/{}/attachment", node.id))
+        .multipart(form)
+        .await
+        .json();
+    let res = server
+        .get(&format!("/api/v1/attachment/{}", attachment.id))
+        .add_header(ORIGIN, origin)
+        .await;
+    res.assert_status_ok();
+    assert!(res.maybe_header(ETAG).is_some());
+    let exposed: Vec<String> = res
+        .header(ACCESS_CONTROL_EXPOSE_HEADERS)
+        .to_str()
+        .expect("ascii header")
+        .split(',')
+        .map(|name| name.trim().to_lowercase())
+        .collect();
+    for name in [
+        "etag",
+        "content-disposition",
+        "location",
+        "x-request-id",
+        "x-content-sha256",
+    ] {
+        assert!(exposed.iter().any(|exposed| exposed == name), "{name}");
+    }
+}
+
+#[tokio::test]
+async fn test_api_default_link_type() {
+    use crate::capabilities::Capabilities;
+    use crate::capture::CaptureResponse;
+    use osint_graph_shared::nodelink::LinkType;
+
+    let mut appstate = AppState::test().await;
+    appstate.default_link_type = LinkType::Directional;
+    let server = setup_test_server_with_state(appstate).await;
+
+    let capabilities: Capabilities = server.get("/api/v1/capabilities").await.json();
+    assert_eq!(capabilities.default_link_type, LinkType::Directional);
+
+    let res: CaptureResponse = server
+        .post("/api/v1/capture")
+        .json(&serde_json::json!({
+            "url": "https://www.example.com/article",
+            "expand": true,
+        }))
+        .await
+        .json();
+    assert_eq!(res.nodelinks.len(), 1);
+    assert_eq!(res.nodelinks[0].linktype, LinkType::Directional);
+
+    assert_eq!("Directional".parse(), Ok(LinkType::Directional));
+    assert_eq!(" omni".parse(), Ok(LinkType::Omni));
+    assert!("both".parse::<LinkType>().is_err());
+}
+
+#[tokio::test]
+async fn test_api_capture() {
+    use crate::capture::{CaptureRequest, CaptureResponse};
+
+    let server = setup_test_server().await;
+
+    // into the Inbox by default, with tracking junk removed
+    let res: CaptureResponse = server
+        .post("/api/v1/capture")
+        .json(&serde_json::json!({
+            "url": "https://www.example.com/article/?id=5&utm_source=feed#comments",
+            "selection_text": "  an interesting quote  ",
+        }))
+        .await
+        .json();
+    assert_eq!(res.nodes.len(), 1);
+    assert!(res.nodelinks.is_empty());
+    let captured = &res.nodes[0];
+    assert_eq!(captured.project_id, Uuid::nil());
+    assert_eq!(captured.node_type, NodeType::Url);
+    assert_eq!(captured.value, "https://www.example.com/article/?id=5");
+    assert_eq!(captured.display, "www.example.com/article");
+    assert_eq!(captured.notes.as_deref(), Some("an interesting quote"));
+    assert_eq!(
+        res.deep_link,
+        format!(
+            "https://localhost:9000/#project={}&node={}",
+            Uuid::nil(),
+            captured.id
+        )
+    );
+    let saved: node::Model = server
+        .get(&format!("/api/v1/node/{}", captured.id))
+        .await
+        .json();
+    assert_eq!(&saved, captured);
+
+    // into a given project, titled
+    let project: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("Captures"))
+        .await
+        .json();
+    let request = CaptureRequest {
+        url: "https://example.com/about".to_string(),
+        title: Some("About Example".to_string()),
+        selection_text: None,
+        project_id: Some(project.id),
+        expand: false,
+    };
+    let res: CaptureResponse = server.post("/api/v1/capture").json(&request).await.json();
+    assert_eq!(res.nodes.len(), 1);
+    assert_eq!(res.nodes[0].project_id, project.id);
+    assert_eq!(res.nodes[0].display, "About Example");
+    assert_eq!(res.nodes[0].notes, None);
+
+    // expansion adds the domain and links it, reusing it the second time
+    let res: CaptureResponse = server
+        .post("/api/v1/capture")
+        .json(&CaptureRequest {
+            expand: true,
+            ..request
+        })
+        .await
+        .json();
+    assert_eq!(res.nodes.len(), 2);
+    let domain = &res.nodes[1];
+    assert_eq!(domain.node_type, NodeType::Domain);
+    assert_eq!(domain.value, "example.com");
+    assert_eq!(res.nodelinks.len(), 1);
+    assert_eq!(res.nodelinks[0].left, res.nodes[0].id);
+    assert_eq!(res.nodelinks[0].right, domain.id);
+
+    let res: CaptureResponse = server
+        .post("/api/v1/capture")
+        .json(&serde_json::json!({
+            "url": "https://www.example.com/contact",
+            "project_id": project.id,
+            "expand": true,
+        }))
+        .await
+        .json();
+    assert_eq!(res.nodes[1].id, domain.id);
+    let nodes = server
+        .get(&format!("/api/v1/project/{}/nodes", project.id))
+        .await
+        .json::<PaginatedResponse<node::Model>>()
+        .items;
+    assert_eq!(
+        nodes
+            .iter()
+            .filter(|n| n.node_type == NodeType::Domain)
+            .count(),
+        1
+    );
+
+    for url in ["not a url", "ftp://example.com/file", "javascript:alert(1)"] {
+        server
+            .post("/api/v1/capture")
+            .json(&serde_json::json!({ "url": url }))
+            .expect_failure()
+            .await
+            .assert_status_bad_request();
+    }
+    server
+        .post("/api/v1/capture")
+        .json(&serde_json::json!({
+            "url": "https://example.com",
+            "project_id": Uuid::new_v4(),
+        }))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+}
+
+#[tokio::test]
+async fn test_api_capture_token_auth() {
+    use crate::capture::CaptureResponse;
+    use crate::entity::{api_token, user};
+    use axum::http::{header::AUTHORIZATION, StatusCode};
+    use sea_orm::{ActiveModelTrait, Set};
+
+    let appstate = AppState::test().await;
+    let user = user::ActiveModel {
+        subject: Set("extension-user".to_string()),
+        email: Set("extension-user@example.com".to_string()),
+        uuid: Set(Uuid::new_v4()),
+        ..Default::default()
+    }
+    .insert(&appstate.conn)
+    .await
+    .expect("Failed to create user");
+    let (_, token) = crate::tokens::create_token(&appstate.conn, user.uuid, "browser")
+        .await
+        .expect("Failed to create token");
+    assert!(token.starts_with(crate::tokens::TOKEN_PREFIX));
+
+    let dbpool: sqlx::Pool<sqlx::Sqlite> = appstate.conn.get_sqlite_connection_pool().clone();
+    let shared_state = Arc::new(RwLock::new(appstate));
+    let server = test_server(build_app(&shared_state, dbpool, true).await);
+
+    let body = serde_json::json!({ "url": "https://example.com/page" });
+    // no session and no token gets the login redirect
+    server
+        .post("/api/v1/capture")
+        .json(&body)
+        .expect_failure()
+        .await
+        .assert_status(StatusCode::SEE_OTHER);
+    server
+        .post("/api/v1/capture")
+        .authorization_bearer("ogt_notarealtoken")
+        .json(&body)
+        .expect_failure()
+        .await
+        .assert_status_unauthorized();
+
+    let res: CaptureResponse = server
+        .post("/api/v1/capture")
+        .add_header(AUTHORIZATION, format!("Bearer {token}"))
+        .json(&body)
+        .await
+        .json();
+    assert_eq!(res.nodes[0].project_id, Uuid::nil());
+
+    let tokens: Vec<api_token::Model> = server
+        .get("/api/v1/tokens")
+        .authorization_bearer(&token)
+        .await
+        .json();
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(tokens[0].name, "browser");
+    assert!(tokens[0].last_used.is_some());
+
+    // the token can make another, and revoking it stops it working
+    let created: serde_json::Value = server
+        .post("/api/v1/tokens")
+        .authorization_bearer(&token)
+        .json(&serde_json::json!({ "name": "cli" }))
+        .await
+        .json();
+    assert!(created.get("token_hash").is_none());
+    let other = created["token"]
+        .as_str()
+        .expect("token missing")
+        .to_string();
+    server
+        .delete(&format!("/api/v1/tokens/{}", tokens[0].id))
+        .authorization_bearer(&other)
+        .await
+        .assert_status_ok();
+    server
+        .get("/api/v1/tokens")
+        .authorization_bearer(&token)
+        .expect_failure()
+        .await
+        .assert_status_unauthorized();
+}
+
+#[tokio::test]
+async fn test_api_search() {
+    use crate::project::{SearchResult, SearchResultType};
+
+    let server = setup_test_server().await;
+    let mut nodes = Vec::new();
+    for index in 0..3 {
+        let project: project::Model = server
+            .post("/api/v1/project")
+            .json(&new_test_project(&format!("Needle project {index}")))
+            .await
+            .json();
+        let node: node::Model = server
+            .post("/api/v1/node")
+            .json(&node::Model {
+                project_id: project.id,
+                node_type: NodeType::Document,
+                display: format!("Document {index}"),
+                value: format!("document-{index}"),
+                ..Default::default()
+            })
+            .await
+            .json();
+        for attachment in 0..2 {
+            let form = axum_test::multipart::MultipartForm::new().add_part(
+                "file",
+                axum_test::multipart::Part::bytes(format!("hay {attachment}").into_bytes())
+                    .file_name(format!("needle-{index}-{attachment}.txt"))
+                    .mime_type("text/plain"),
+            );
+            server
+                .post(&format!("/api/v1/node/{}/attachment", node.id))
+                .multipart(form)
+                .await
+                .assert_status_ok();
+        }
+        nodes.push(node);
+    }
+    // an empty project has no node to link to, so it links to itself
+    let empty_project: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("Empty needle project"))
+        .await
+        .json();
+
+    // nodes, attachments joined to their nodes, projects, each project's first node
+    let results: Vec<SearchResult> = with_query_budget(4, || server.get("/api/v1/search?q=NEEDLE"))
+        .await
+        .json();
+    let attachment_results: Vec<_> = results
+        .iter()
+        .filter(|r| r.title.contains("(attachment: needle-"))
+        .collect();
+    assert_eq!(attachment_results.len(), 6);
+    for node in nodes.iter() {
+        assert_eq!(
+            attachment_results
+                .iter()
+                .filter(|r| r.id == node.id && r.project_id == node.project_id)
+                .count(),
+            2
+        );
+    }
+    let project_results: Vec<_> = results
+        .iter()
+        .filter(|r| matches!(r.result_type, SearchResultType::Project))
+        .collect();
+    assert_eq!(project_results.len(), 3);
+    for node in nodes.iter() {
+        assert!(project_results
+            .iter()
+            .any(|r| r.id == node.id && r.project_id == node.project_id));
+    }
+    let empty_results: Vec<_> = results
+        .iter()
+        .filter(|r| matches!(r.result_type, SearchResultType::EmptyProject))
+        .collect();
+    assert_eq!(empty_results.len(), 1);
+    assert_eq!(empty_results[0].id, empty_project.id);
+    assert_eq!(empty_results[0].project_id, empty_project.id);
+    assert_eq!(empty_results[0].title, "Project: Empty needle project");
+}
+
+#[tokio::test]
+async fn test_api_search_snippets() {
+    use crate::extract::INVALID_QUERY_PARAMETER;
+    use crate::project::{ErrorResponse, SearchResult, SearchResultType, SEARCH_SNIPPET_CHARS};
+
+    let server = setup_test_server().await;
+    let project: project::Model = server
+        .post("/api/v1/project")
+        .json(&project::Model {
+            description: Some("Tracking the haystack crew".to_string()),
+            ..new_test_project("Snippet project")
+        })
+        .await
+        .json();
+    let notes = format!(
+        "{} spotted a Haystack van near the docks {}",
+        "lorem ipsum ".repeat(20),
+        "dolor sit amet ".repeat(20)
+    );
+    let node: node::Model = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            display: "Docks".to_string(),
+            notes: Some(notes),
+            ..Default::default()
+        })
+        .await
+        .json();
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(b"hay".to_vec())
+            .file_name("haystack-photo.jpg")
+            .mime_type("image/jpeg"),
+    );
+    server
+        .post(&format!("/api/v1/node/{}/attachment", node.id))
+        .multipart(form)
+        .await
+        .assert_status_ok();
+
+    let results: Vec<SearchResult> = server.get("/api/v1/search?q=haystack").await.json();
+    assert_eq!(results.len(), 3, "{results:?}");
+
+    let node_result = results
+        .iter()
+        .find(|r| r.title == "Docks")
+        .expect("node result");
+    let snippet = node_result.snippet.as_deref().expect("node snippet");
+    assert!(snippet.contains("Haystack van"), "{snippet}");
+    assert_eq!(snippet.chars().count(), SEARCH_SNIPPET_CHARS);
+    // the match sits in the middle
+    assert_eq!(
+        snippet.find("Haystack"),
+        Some((SEARCH_SNIPPET_CHARS - 8) / 2)
+    );
+
+    let attachment_result = results
+        .iter()
+        .find(|r| r.title.contains("(attachment: haystack-photo.jpg)"))
+        .expect("attachment result");
+    assert_eq!(attachment_result.id, node.id);
+    assert_eq!(
+        attachment_result.snippet.as_deref(),
+        Some("haystack-photo.jpg")
+    );
+
+    let project_result = results
+        .iter()
+        .find(|r| matches!(r.result_type, SearchResultType::Project))
+        .expect("project result");
+    assert_eq!(project_result.project_id, project.id);
+    assert_eq!(
+        project_result.snippet.as_deref(),
+        Some("Tracking the haystack crew")
+    );
+
+    // too short or too long to search for
+    for query in ["", "%20%20", "h", "%20h%20", &"a".repeat(201)] {
+        let res = server
+            .get(&format!("/api/v1/search?q={query}"))
+            .expect_failure()
+            .await;
+        assert_eq!(res.status_code(), 400, "{query}");
+        let body: ErrorResponse = res.json();
+        assert_eq!(body.code.as_deref(), Some(INVALID_QUERY_PARAMETER));
+        assert_eq!(body.parameter.as_deref(), Some("q"));
+    }
+    server
+        .get(&format!("/api/v1/search?q={}", "a".repeat(200)))
+        .await
+        .assert_status_ok();
+}
+
+#[tokio::test]
+async fn test_api_search_value_history() {
+    use crate::project::SearchResult;
+
+    let server = setup_test_server().await;
+    let project: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("History project"))
+        .await
+        .json();
+    let node: node::Model = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            node_type: NodeType::Person,
+            display: "Suspect".to_string(),
+            value: "darkfox99".to_string(),
+            ..Default::default()
+        })
+        .await
+        .json();
+    for value in ["darkfox01", "morningdove"] {
+        server
+            .put(&format!("/api/v1/node/{}", node.id))
+            .json(&node::Model {
+                value: value.to_string(),
+                ..node.clone()
+            })
+            .await
+            .assert_status_ok();
+    }
+
+    let results: Vec<SearchResult> = server.get("/api/v1/search?q=darkfox").await.json();
+    assert!(results.is_empty(), "{results:?}");
+
+    let results: Vec<SearchResult> = server
+        .get("/api/v1/search?q=darkfox&include_history=true")
+        .await
+        .json();
+    // both old values match, but each node shows up once, for its latest change
+    assert_eq!(results.len(), 1, "{results:?}");
+    assert_eq!(results[0].id, node.id);
+    assert_eq!(results[0].title, "Suspect (previously: darkfox01)");
+    assert_eq!(results[0].snippet.as_deref(), Some("darkfox01"));
+    assert!(results[0].historical_at.is_some());
+
+    // the current value is a normal hit, not a historical one
+    let results: Vec<SearchResult> = server
+        .get("/api/v1/search?q=morningdove&include_history=true")
+        .await
+        .json();
+    assert_eq!(results.len(), 1, "{results:?}");
+    assert!(results[0].historical_at.is_none());
+}
+
+#[tokio::test]
+async fn test_api_search_ranking_and_scope() {
+    use crate::extract::INVALID_QUERY_PARAMETER;
+    use crate::project::{ErrorResponse, SearchResult, SearchResultType, SEARCH_DEFAULT_LIMIT};
+
+    let server = setup_test_server().await;
+    let project: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("Kestrel watch"))
+        .await
+        .json();
+    let other: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("Other project"))
+        .await
+        .json();
+    let create_node = |project_id: Uuid, display: &str, value: &str| {
+        let request = server.post("/api/v1/node").json(&node::Model {
+            project_id,
+            node_type: NodeType::Person,
+            display: display.to_string(),
+            value: value.to_string(),
+            ..Default::default()
+        });
+        async move { request.await.json::<node::Model>() }
+    };
+    let substring = create_node(project.id, "Kestrel sighting", "sighting-1").await;
+    let exact_value = create_node(project.id, "Bird", "KESTREL").await;
+    let exact_display = create_node(project.id, "kestrel", "kestrel-2").await;
+    let elsewhere = create_node(other.id, "Kestrel again", "kestrel-3").await;
+
+    // exact display first, then exact value, then substrings in insertion order
+    let results: Vec<SearchResult> = server.get("/api/v1/search?q=Kestrel").await.json();
+    let ids: Vec<Uuid> = results.iter().map(|r| r.id).collect();
+    assert_eq!(ids[0], exact_display.id);
+    assert_eq!(ids[1], exact_value.id);
+    assert!(ids.contains(&substring.id));
+    assert!(ids.contains(&elsewhere.id));
+    assert_eq!(
+        results
+            .iter()
+            .filter(|r| matches!(r.result_type, SearchResultType::Project))
+            .count(),
+        1
+    );
+
+    // an exact project name beats substring node matches
+    let results: Vec<SearchResult> = server.get("/api/v1/search?q=kestrel%20watch").await.json();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].project_id, project.id);
+
+    let results: Vec<SearchResult> = server
+        .get(&format!("/api/v1/search?q=kestrel&project_id={}", other.id))
+        .await
+        .json();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].id, elsewhere.id);
+
+    let results: Vec<SearchResult> = server.get("/api/v1/search?q=kestrel&limit=2").await.json();
+    assert_eq!(
+        results.iter().map(|r| r.id).collect::<Vec<_>>(),
+        vec![exact_display.id, exact_value.id]
+    );
+
+    for _ in 0..SEARCH_DEFAULT_LIMIT {
+        create_node(other.id, "Kestrel copy", "copy").await;
+    }
+    let results: Vec<SearchResult> = server.get("/api/v1/search?q=kestrel").await.json();
+    assert_eq!(results.len() as u64, SEARCH_DEFAULT_LIMIT);
+
+    for limit in ["0", "501"] {
+        let res = server
+            .get(&format!("/api/v1/search?q=kestrel&limit={limit}"))
+            .expect_failure()
+            .await;
+        assert_eq!(res.status_code(), 400, "{limit}");
+        let body: ErrorResponse = res.json();
+        assert_eq!(body.code.as_deref(), Some(INVALID_QUERY_PARAMETER));
+    }
+    server
+        .get(&format!(
+            "/api/v1/search?q=kestrel&project_id={}",
+            Uuid::new_v4()
+        ))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+}
+
+#[tokio::test]
+async fn test_api_attachment_encoding_negotiation() {
+    use crate::attachment_codec::{reencode_attachments, store_reencoded, AttachmentCodec};
+    use crate::entity::attachment;
+    use axum::http::header::ACCEPT_ENCODING;
+    use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, QueryFilter};
+
+    let mut appstate = AppState::test().await;
+    appstate.attachment_codec = AttachmentCodec::Zstd;
+    let conn = appstate.conn.clone();
+    let server = setup_test_server_with_state(appstate).await;
+
+    let project: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("Encodings"))
+        .await
+        .json();
+    let node: node::Model = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            node_type: NodeType::Document,
+            display: "notes".to_string(),
+            value: "notes".to_string(),
+            ..Default::default()
+        })
+        .await
+        .json();
+    let content = "the quick brown fox jumps over the lazy dog\n".repeat(4096);
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(content.as_bytes().to_vec())
+            .file_name("notes.txt")
+            .mime_type("text/plain"),
+    );
+    let uploaded: attachment::Model = server
+        .post(&format!("/api/v1/node/{}/attachment", node.id))
+        .multipart(form)
+        .await
+        .json();
+    assert_eq!(uploaded.codec, AttachmentCodec::Zstd);
+    assert_eq!(uploaded.size, content.len() as i64);
+    let url = format!("/api/v1/attachment/{}", uploaded.id);
+
+    // a zstd-capable client gets the stored bytes
+    let res = server
+        .get(&url)
+        .add_header(ACCEPT_ENCODING, "gzip, deflate, br, zstd")
+        .await;
+    assert_eq!(res.header(CONTENT_ENCODING), "zstd");
+    assert!(res.as_bytes().len() < content.len());
+    assert_eq!(
+        AttachmentCodec::Zstd
+            .decode(res.as_bytes())
+            .expect("Failed to decode zstd"),
+        content.as_bytes()
+    );
+
+    // legacy clients, and clients refusing zstd, get it decoded
+    for accept in [None, Some("identity"), Some("zstd;q=0"), Some("*;q=0")] {
+        for url in [url.clone(), format!("{url}/view")] {
+            let mut req = server.get(&url);
+            if let Some(accept) = accept {
+                req = req.add_header(ACCEPT_ENCODING, accept);
+            }
+            let res = req.await;
+            assert!(res.maybe_header(CONTENT_ENCODING).is_none(), "{accept:?}");
+            assert_eq!(res.as_bytes().as_ref(), content.as_bytes(), "{accept:?}");
+        }
+    }
+    // the wildcard counts too
+    let res = server.get(&url).add_header(ACCEPT_ENCODING, "*").await;
+    assert_eq!(res.header(CONTENT_ENCODING), "zstd");
+
+    // switching the canonical codec re-encodes what's stored
+    assert_eq!(
+        reencode_attachments(&conn, AttachmentCodec::Gzip)
+            .await
+            .expect("Failed to re-encode"),
+        1
+    );
+    assert_eq!(
+        reencode_attachments(&conn, AttachmentCodec::Gzip)
+            .await
+            .expect("Failed to re-encode"),
+        0
+    );
+    let listed: Vec<attachment::Model> = server
+        .get(&format!("/api/v1/node/{}/attachments", node.id))
+        .await
+        .json();
+    assert_eq!(listed[0].codec, AttachmentCodec::Gzip);
+    assert_eq!(listed[0].size, content.len() as i64);
+    let res = server.get(&url).add_header(ACCEPT_ENCODING, "gzip").await;
+    assert_eq!(res.header(CONTENT_ENCODING), "gzip");
+    assert_eq!(
+        AttachmentCodec::Gzip
+            .decode(res.as_bytes())
+            .expect("Failed to decode gzip"),
+        content.as_bytes()
+    );
+    let res = server.get(&url).await;
+    assert_eq!(res.as_bytes().as_ref(), content.as_bytes());
+
+    // a re-encode never writes over a row a dedup job has moved to a shared blob meanwhile
+    attachment::Entity::update_many()
+        .col_expr(attachment::Column::SharedBlob, Expr::value(true))
+        .filter(attachment::Column::Id.eq(uploaded.id))
+        .exec(&conn)
+        .await
+        .unwrap();
+    assert_eq!(
+        store_reencoded(
+            &conn,
+            uploaded.id,
+            AttachmentCodec::Gzip,
+            AttachmentCodec::Zstd,
+            b"stale".to_vec()
+        )
+        .await
+        .unwrap(),
+        0
+    );
+    let stored = attachment::Entity::find_by_id(uploaded.id)
+        .one(&conn)
+        .await
+        .unwrap()
+        .expect("attachment");
+    assert_eq!(stored.codec, AttachmentCodec::Gzip);
+    assert_ne!(stored.data, b"stale");
+}
+
+#[tokio::test]
+async fn test_api_nodelink_weight_kind() {
+    use crate::entity::nodelink;
+    use osint_graph_shared::nodelink::LinkType;
+
+    let server = setup_test_server().await;
+
+    let project = new_test_project("Weighted links");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+
+    let person = node::Model {
+        project_id: project.id,
+        node_type: NodeType::Person,
+        display: "Jane Doe".to_string(),
+        value: "Jane Doe".to_string(),
+        ..Default::default()
+    };
+    let domain = node::Model {
+        project_id: project.id,
+        node_type: NodeType::Domain,
+        display: "example.com".to_string(),
+        value: "example.com".to_string(),
+        ..Default::default()
+    };
+    for node in [&person, &domain] {
+        server
+            .post("/api/v1/node")
+            .json(node)
+            .await
+            .assert_status_ok();
+    }
+
+    let mut link = nodelink::Model {
+        id: Uuid::new_v4(),
+        project_id: project.id,
+        left: person.id,
+        right: domain.id,
+        linktype: LinkType::Directional,
+        weight: Some(2.5),
+        kind: Some(" owns ".to_string()),
+        valid_from: None,
+        valid_to: None,
+        created_by: None,
+        created: None,
+    };
+    let res = server.post("/api/v1/nodelink").json(&link).await;
+    res.assert_status_ok();
+    let created: nodelink::Model = res.json();
+    assert_eq!(created.weight, Some(2.5));
+    assert_eq!(created.kind.as_deref(), Some("owns"));
+
+    let res = server
+        .get(&format!("/api/v1/project/{}/nodelinks", project.id))
+        .await;
+    res.assert_status_ok();
+    let links: Vec<nodelink::Model> = res.json();
+    assert_eq!(links, vec![created.clone()]);
+
+    let res = server
+        .get(&format!("/api/v1/project/{}/export", project.id))
+        .await;
+    res.assert_status_ok();
+    let export: ProjectExport = res.json();
+    assert_eq!(export.nodelinks, vec![created.clone()]);
+
+    let res = server
+        .get(&format!("/api/v1/project/{}/export/mermaid", project.id))
+        .await;
+    res.assert_status_ok();
+    assert!(res.text().contains(" : owns (2.5)"));
+
+    link.id = Uuid::new_v4();
+    link.weight = Some(-1.0);
+    let res = server
+        .post("/api/v1/nodelink")
+        .json(&link)
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 400);
+
+    let res = server
+        .put(&format!("/api/v1/nodelink/{}", created.id))
+        .json(&nodelink::Model {
+            weight: Some(0.5),
+            kind: Some(String::new()),
+            ..created.clone()
+        })
+        .await;
+    res.assert_status_ok();
+    let updated: nodelink::Model = res.json();
+    assert_eq!(updated.weight, Some(0.5));
+    assert_eq!(updated.kind, None);
+
+    let res = server
+        .put(&format!("/api/v1/nodelink/{}", created.id))
+        .json(&nodelink::Model {
+            weight: Some(-0.5),
+            ..created.clone()
+        })
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 400);
+
+    let res = server
+        .put(&format!("/api/v1/nodelink/{}", Uuid::new_v4()))
+        .json(&created)
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_api_get_nodes() {
+    use crate::project::NodesPage;
+
+    let server = setup_test_server().await;
+
+    let projects = [new_test_project("Nodes one"), new_test_project("Nodes two")];
+    let mut created = Vec::new();
+    for project in &projects {
+        server
+            .post("/api/v1/project")
+            .json(project)
+            .await
+            .assert_status_ok();
+        for (node_type, value) in [
+            (NodeType::Person, "Jane Doe"),
+            (NodeType::Domain, "example.com"),
+        ] {
+            let node = node::Model {
+                project_id: project.id,
+                node_type,
+                display: format!("{value} in {}", project.name),
+                value: value.to_string(),
+                ..Default::default()
+            };
+            server
+                .post("/api/v1/node")
+                .json(&node)
+                .await
+                .assert_status_ok();
+            created.push(node);
+        }
+    }
+
+    let res = with_query_budget(1, || server.get("/api/v1/nodes")).await;
+    res.assert_status_ok();
+    let page: NodesPage = res.json();
+    assert_eq!(page.nodes.len(), created.len());
+    assert_eq!(page.next_cursor, None);
+    for project in &projects {
+        assert!(page.nodes.iter().any(|node| node.project_id == project.id));
+    }
+
+    let res = server
+        .get("/api/v1/nodes")
+        .add_query_param("project_id", projects[0].id)
+        .await;
+    res.assert_status_ok();
+    let page: NodesPage = res.json();
+    assert_eq!(page.nodes.len(), 2);
+    assert!(page
+        .nodes
+        .iter()
+        .all(|node| node.project_id == projects[0].id));
+
+    let res = server
+        .get("/api/v1/nodes")
+        .add_query_param("node_type", "domain")
+        .add_query_param("q", "NODES TWO")
+        .await;
+    res.assert_status_ok();
+    let page: NodesPage = res.json();
+    assert_eq!(page.nodes.len(), 1);
+    assert_eq!(page.nodes[0].node_type, NodeType::Domain);
+    assert_eq!(page.nodes[0].project_id, projects[1].id);
+
+    // walk every page
+    let mut seen = Vec::new();
+    let mut cursor: Option<Uuid> = None;
+    loop {
+        let mut req = server.get("/api/v1/nodes").add_query_param("limit", 3);
+        if let Some(cursor) = cursor {
+            req = req.add_query_param("cursor", cursor);
+        }
+        let page: NodesPage = req.await.json();
+        assert!(page.nodes.len() <= 3);
+        seen.extend(page.nodes.into_iter().map(|node| node.id));
+        match page.next_cursor {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    let mut expected: Vec<Uuid> = created.iter().map(|node| node.id).collect();
+    expected.sort();
+    assert_eq!(seen, expected);
+
+    let res = server
+        .get("/api/v1/nodes")
+        .add_query_param("node_type", "spaceship")
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 400);
+}
+
+#[tokio::test]
+async fn test_api_attachment_duplicates() {
+    use crate::attachment_dedup::{content_hash, DuplicateReport};
+    use crate::entity::attachment;
+    use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+    let appstate = AppState::test().await;
+    let conn = appstate.conn.clone();
+    let server = setup_test_server_with_state(appstate).await;
+
+    let screenshot = "not really a png\n".repeat(1024);
+    let mut uploaded: Vec<attachment::Model> = Vec::new();
+    for (project_name, files) in [
+        (
+            "Case one",
+            vec![
+                ("screenshot.png", &screenshot),
+                ("other.txt", &"unique".to_string()),
+            ],
+        ),
+        ("Case two", vec![("evidence.png", &screenshot)]),
+    ] {
+        let project: project::Model = server
+            .post("/api/v1/project")
+            .json(&new_test_project(project_name))
+            .await
+            .json();
+        let node: node::Model = server
+            .post("/api/v1/node")
+            .json(&node::Model {
+                project_id: project.id,
+                node_type: NodeType::Image,
+                display: "screenshot".to_string(),
+                value: "screenshot".to_string(),
+                ..Default::default()
+            })
+            .await
+            .json();
+        for (filename, content) in files {
+            let form = axum_test::multipart::MultipartForm::new().add_part(
+                "file",
+                axum_test::multipart::Part::bytes(content.as_bytes().to_vec())
+                    .file_name(filename)
+                    .mime_type("image/png"),
+            );
+            uploaded.push(
+                server
+                    .post(&format!("/api/v1/node/{}/attachment", node.id))
+                    .multipart(form)
+                    .await
+                    .json(),
+            );
+        }
+    }
+    let hash = content_hash(screenshot.as_bytes());
+    assert_eq!(uploaded[0].sha256.as_deref(), Some(hash.as_str()));
+
+    // pretend one copy was stored before hashes were recorded
+    attachment::Entity::update_many()
+        .col_expr(
+            attachment::Column::Sha256,
+            sea_orm::sea_query::Expr::value(Option::<String>::None),
+        )
+        .filter(attachment::Column::Id.eq(uploaded[2].id))
+        .exec(&conn)
+        .await
+        .expect("Failed to clear hash");
+
+    let res = server.get("/api/v1/admin/attachments/duplicates").await;
+    res.assert_status_ok();
+    let report: DuplicateReport = res.json();
+    assert_eq!(report.groups.len(), 1);
+    let group = &report.groups[0];
+    assert_eq!(group.sha256, hash);
+    assert_eq!(group.size, screenshot.len() as u64);
+    assert_eq!(group.attachments.len(), 2);
+    assert_ne!(
+        group.attachments[0].project_id,
+        group.attachments[1].project_id
+    );
+    assert_eq!(group.wasted_bytes, uploaded[0].data.len() as u64);
+    assert_eq!(report.reclaimable_bytes, group.wasted_bytes);
+
+    let backfilled = attachment::Entity::find_by_id(uploaded[2].id)
+        .one(&conn)
+        .await
+        .expect("Failed to query attachment")
+        .expect("Attachment missing");
+    assert_eq!(backfilled.sha256, Some(hash));
+}
+
+#[tokio::test]
+async fn test_api_attachment_deduplicate() {
+    use crate::attachment_dedup::{DedupJobState, DedupJobStatus, DuplicateReport};
+    use crate::entity::attachment;
+    use crate::storage_gc::{OrphanReport, PruneOrphansReport};
+    use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+    let appstate = AppState::test().await;
+    let conn = appstate.conn.clone();
+    let server = setup_test_server_with_state(appstate).await;
+
+    let screenshot = "still not really a png\n".repeat(4096);
+    let mut projects: Vec<project::Model> = Vec::new();
+    let mut uploaded: Vec<attachment::Model> = Vec::new();
+    for (project_name, filename) in [
+        ("Case one", "screenshot.png"),
+        ("Case two", "evidence.png"),
+        ("Case three", "copy.png"),
+    ] {
+        let project: project::Model = server
+            .post("/api/v1/project")
+            .json(&new_test_project(project_name))
+            .await
+            .json();
+        let node: node::Model = server
+            .post("/api/v1/node")
+            .json(&node::Model {
+                project_id: project.id,
+                node_type: NodeType::Image,
+                display: filename.to_string(),
+                value: filename.to_string(),
+                ..Default::default()
+            })
+            .await
+            .json();
+        let form = axum_test::multipart::MultipartForm::new().add_part(
+            "file",
+            axum_test::multipart::Part::bytes(screenshot.as_bytes().to_vec())
+                .file_name(filename)
+                .mime_type("image/png"),
+        );
+        uploaded.push(
+            server
+                .post(&format!("/api/v1/node/{}/attachment", node.id))
+                .multipart(form)
+                .await
+                .json(),
+        );
+        projects.push(project);
+    }
+    // one copy from before hashes were recorded, which the job hashes first
+    attachment::Entity::update_many()
+        .col_expr(
+            attachment::Column::Sha256,
+            sea_orm::sea_query::Expr::value(Option::<String>::None),
+        )
+        .filter(attachment::Column::Id.eq(uploaded[2].id))
+        .exec(&conn)
+        .await
+        .expect("Failed to clear hash");
+    let stored_size = uploaded[0].data.len() as u64;
+
+    let report: DuplicateReport = server
+        .get("/api/v1/admin/attachments/duplicates")
+        .await
+        .json();
+    assert_eq!(report.reclaimable_bytes, 2 * stored_size);
+
+    let run = || async {
+        let res = server.post("/api/v1/admin/attachments/deduplicate").await;
+        assert_eq!(res.status_code(), 202);
+        let job: DedupJobStatus = res.json();
+        assert_eq!(res.header("location"), job.status_url.as_str());
+        for _ in 0..100 {
+            let job: DedupJobStatus = server.get(&job.status_url).await.json();
+            if job.state != DedupJobState::Running {
+                return job;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        panic!("deduplication job didn't finish");
+    };
+    let job = run().await;
+    assert_eq!(job.state, DedupJobState::Done, "{job:?}");
+    assert_eq!(job.progress.groups, 1);
+    assert_eq!(job.progress.groups_done, 1);
+    assert_eq!(job.progress.attachments_shared, 3);
+    assert_eq!(job.progress.bytes_reclaimed, 2 * stored_size);
+
+    // every copy downloads the same as before, with its own metadata
+    for original in &uploaded {
+        let res = server
+            .get(&format!("/api/v1/attachment/{}", original.id))
+            .await;
+        res.assert_status_ok();
+        assert_eq!(res.as_bytes().as_ref(), screenshot.as_bytes());
+        let stored = attachment::Entity::find_by_id(original.id)
+            .one(&conn)
+            .await
+            .expect("Failed to query attachment")
+            .expect("Attachment missing");
+        assert!(stored.shared_blob);
+        assert!(stored.data.is_empty());
+        assert_eq!(stored.filename, original.filename);
+        assert_eq!(stored.node_id, original.node_id);
+        assert_eq!(stored.created, original.created);
+    }
+    let export: ProjectExport = server
+        .get(&format!(
+            "/api/v1/project/{}/export?include_attachments=true",
+            projects[1].id
+        ))
+        .await
+        .json();
+    assert_eq!(
+        export.attachments[0]
+            .codec
+            .decode(&export.attachments[0].data)
+            .expect("Failed to decode export"),
+        screenshot.as_bytes()
+    );
+
+    let report: DuplicateReport = server
+        .get("/api/v1/admin/attachments/duplicates")
+        .await
+        .json();
+    assert!(report.groups.is_empty(), "{report:?}");
+    assert_eq!(report.reclaimable_bytes, 0);
+
+    // nothing left to do the second time
+    let job = run().await;
+    assert_eq!(job.state, DedupJobState::Done);
+    assert_eq!(job.progress.groups, 0);
+    server.delete(&job.status_url).await.assert_status_ok();
+    server
+        .get(&format!(
+            "/api/v1/admin/attachments/deduplicate/{}",
+            Uuid::new_v4()
+        ))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+
+    // the blob goes with the last attachment using it
+    for original in &uploaded {
+        server
+            .delete(&format!("/api/v1/attachment/{}", original.id))
+            .await
+            .assert_status_ok();
+    }
+    let orphans: OrphanReport = server.get("/api/v1/admin/storage/orphans").await.json();
+    assert_eq!(
+        orphans.orphans.blobs,
+        vec![uploaded[0].sha256.clone().unwrap()]
+    );
+    let pruned: PruneOrphansReport = server.post("/api/v1/admin/prune-orphans").await.json();
+    assert_eq!(pruned.blobs, 1);
+    let orphans: OrphanReport = server.get("/api/v1/admin/storage/orphans").await.json();
+    assert!(orphans.orphans.is_empty());
+}
+
+#[tokio::test]
+async fn test_api_attachment_upload_dedup() {
+    use crate::attachment_dedup::content_hash;
+    use crate::entity::attachment;
+    use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter};
+
+    let appstate = AppState::test().await;
+    let conn = appstate.conn.clone();
+    let server = setup_test_server_with_state(appstate).await;
+    let project: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("Upload dedup"))
+        .await
+        .json();
+    let mut nodes: Vec<node::Model> = Vec::new();
+    for display in ["first", "second"] {
+        nodes.push(
+            server
+                .post("/api/v1/node")
+                .json(&node::Model {
+                    project_id: project.id,
+                    node_type: NodeType::Document,
+                    display: display.to_string(),
+                    value: display.to_string(),
+                    ..Default::default()
+                })
+                .await
+                .json(),
+        );
+    }
+
+    let upload = |node_id: Uuid, filename: &'static str| {
+        let form = axum_test::multipart::MultipartForm::new().add_part(
+            "file",
+            axum_test::multipart::Part::bytes(b"same bytes".to_vec())
+                .file_name(filename)
+                .mime_type("text/plain"),
+        );
+        server
+            .post(&format!("/api/v1/node/{}/attachment", node_id))
+            .multipart(form)
+    };
+    let original: attachment::Model = upload(nodes[0].id, "report.txt").await.json();
+    assert_eq!(
+        original.sha256.as_deref(),
+        Some(content_hash(b"same bytes").as_str())
+    );
+
+    // the same content again, even under another name, gets the existing attachment
+    let again: attachment::Model = upload(nodes[0].id, "report-copy.txt").await.json();
+    assert_eq!(again.id, original.id);
+    assert_eq!(again.filename, "report.txt");
+    let attachments: Vec<attachment::Model> = server
+        .get(&format!("/api/v1/node/{}/attachments", nodes[0].id))
+        .await
+        .json();
+    assert_eq!(attachments.len(), 1);
+    let stored = attachment::Entity::find()
+        .filter(attachment::Column::NodeId.eq(nodes[0].id))
+        .count(&conn)
+        .await
+        .unwrap();
+    assert_eq!(stored, 1);
+
+    // other nodes get their own copy
+    let elsewhere: attachment::Model = upload(nodes[1].id, "report.txt").await.json();
+    assert_ne!(elsewhere.id, original.id);
+    assert_eq!(elsewhere.sha256, original.sha256);
+}
+
+#[tokio::test]
+async fn test_api_merge_projects() {
+    use crate::entity::{attachment, nodelink};
+    use crate::merge::ProjectMergeResponse;
+    use osint_graph_shared::nodelink::LinkType;
+
+    let server = setup_test_server().await;
+
+    let keep = new_test_project("Merge keep");
+    let absorb = new_test_project("Merge absorb");
+    for project in [&keep, &absorb] {
+        server
+            .post("/api/v1/project")
+            .json(project)
+            .await
+            .assert_status_ok();
+    }
+
+    let new_node = |project_id, node_type, value: &str, notes: Option<&str>| node::Model {
+        project_id,
+        node_type,
+        display: value.to_string(),
+        value: value.to_string(),
+        notes: notes.map(str::to_string),
+        ..Default::default()
+    };
+    let jane = new_node(
+        keep.id,
+        NodeType::Person,
+        "Jane Doe",
+        Some("owns the domain"),
+    );
+    let domain = new_node(keep.id, NodeType::Domain, "example.com", None);
+    let other_jane = new_node(
+        absorb.id,
+        NodeType::Person,
+        "jane  doe",
+        Some("met at conf"),
+    );
+    let other_domain = new_node(absorb.id, NodeType::Domain, "Example.COM", None);
+    let phone = new_node(absorb.id, NodeType::Phone, "+61 400 000 000", None);
+    for node in [&jane, &domain, &other_jane, &other_domain, &phone] {
+        server
+            .post("/api/v1/node")
+            .json(node)
+            .await
+            .assert_status_ok();
+    }
+    for (project_id, left, right) in [
+        (keep.id, jane.id, domain.id),
+        (absorb.id, other_jane.id, other_domain.id),
+        (absorb.id, other_jane.id, phone.id),
+    ] {
+        server
+            .post("/api/v1/nodelink")
+            .json(&nodelink::Model {
+                id: Uuid::new_v4(),
+                project_id,
+                left,
+                right,
+                linktype: LinkType::Omni,
+                weight: None,
+                kind: None,
+                valid_from: None,
+                valid_to: None,
+                created_by: None,
+                created: None,
+            })
+            .await
+            .assert_status_ok();
+    }
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(b"photo".to_vec())
+            .file_name("jane.jpg")
+            .mime_type("image/jpeg"),
+    );
+    let photo: attachment::Model = server
+        .post(&format!("/api/v1/node/{}/attachment", other_jane.id))
+        .multipart(form)
+        .await
+        .json();
+
+    let res = server
+        .post(&format!("/api/v1/project/{}/merge/{}", keep.id, keep.id))
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 400);
+
+    let res = server
+        .post(&format!("/api/v1/project/{}/merge/{}", keep.id, absorb.id))
+        .add_query_param("dedupe", true)
+        .await;
+    res.assert_status_ok();
+    let merged: ProjectMergeResponse = res.json();
+    assert_eq!(merged.moved_nodes, 1);
+    assert_eq!(merged.deduped_nodes, 2);
+    assert_eq!(merged.moved_nodelinks, 1);
+    assert_eq!(merged.deduped_nodelinks, 1);
+    assert_eq!(merged.moved_attachments, 1);
+    assert!(merged.absorbed_project_deleted);
+
+    let nodes = server
+        .get(&format!("/api/v1/project/{}/nodes", keep.id))
+        .await
+        .json::<PaginatedResponse<node::Model>>()
+        .items;
+    let mut node_ids: Vec<Uuid> = nodes.iter().map(|node| node.id).collect();
+    node_ids.sort();
+    let mut expected = vec![jane.id, domain.id, phone.id];
+    expected.sort();
+    assert_eq!(node_ids, expected);
+    let merged_jane = nodes.iter().find(|node| node.id == jane.id).unwrap();
+    assert_eq!(
+        merged_jane.notes.as_deref(),
+        Some("owns the domain\n\nmet at conf")
+    );
+
+    let links: Vec<nodelink::Model> = server
+        .get(&format!("/api/v1/project/{}/nodelinks", keep.id))
+        .await
+        .json();
+    let mut pairs: Vec<(Uuid, Uuid)> = links.iter().map(|l| (l.left, l.right)).collect();
+    pairs.sort();
+    let mut expected = vec![(jane.id, domain.id), (jane.id, phone.id)];
+    expected.sort();
+    assert_eq!(pairs, expected);
+
+    let attachments: Vec<attachment::Model> = server
+        .get(&format!("/api/v1/node/{}/attachments", jane.id))
+        .await
+        .json();
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(attachments[0].id, photo.id);
+
+    server
+        .get(&format!("/api/v1/project/{}", absorb.id))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+
+    // the Inbox is emptied but kept
+    let inbox_node = new_node(Uuid::nil(), NodeType::Email, "jane@example.com", None);
+    server
+        .post("/api/v1/node")
+        .json(&inbox_node)
+        .await
+        .assert_status_ok();
+    let merged: ProjectMergeResponse = server
+        .post(&format!(
+            "/api/v1/project/{}/merge/{}",
+            keep.id,
+            Uuid::nil()
+        ))
+        .await
+        .json();
+    assert_eq!(merged.moved_nodes, 1);
+    assert!(!merged.absorbed_project_deleted);
+    server
+        .get(&format!("/api/v1/project/{}", Uuid::nil()))
+        .await
+        .assert_status_ok();
+}
+
+#[tokio::test]
+async fn test_api_merge_nodes() {
+    use crate::entity::{attachment, nodelink};
+    use osint_graph_shared::nodelink::LinkType;
+
+    let server = setup_test_server().await;
+
+    let project = new_test_project("Merge nodes");
+    let other_project = new_test_project("Merge nodes elsewhere");
+    for project in [&project, &other_project] {
+        server
+            .post("/api/v1/project")
+            .json(project)
+            .await
+            .assert_status_ok();
+    }
+
+    let new_node = |project_id, value: &str, notes: Option<&str>| node::Model {
+        project_id,
+        node_type: NodeType::Person,
+        display: value.to_string(),
+        value: value.to_string(),
+        notes: notes.map(str::to_string),
+        ..Default::default()
+    };
+    let jane = new_node(project.id, "Jane Doe", Some("owns the domain"));
+    let duplicate = new_node(project.id, "J. Doe", Some("met at conf"));
+    let bob = new_node(project.id, "Bob", None);
+    let alice = new_node(project.id, "Alice", None);
+    let elsewhere = new_node(other_project.id, "Jane Doe", None);
+    for node in [&jane, &duplicate, &bob, &alice, &elsewhere] {
+        server
+            .post("/api/v1/node")
+            .json(node)
+            .await
+            .assert_status_ok();
+    }
+    // a link between the two, one the target already has, and one only the duplicate has
+    for (left, right) in [
+        (duplicate.id, jane.id),
+        (jane.id, bob.id),
+        (bob.id, duplicate.id),
+        (duplicate.id, alice.id),
+    ] {
+        server
+            .post("/api/v1/nodelink")
+            .json(&nodelink::Model {
+                id: Uuid::new_v4(),
+                project_id: project.id,
+                left,
+                right,
+                linktype: LinkType::Omni,
+                weight: None,
+                kind: None,
+                valid_from: None,
+                valid_to: None,
+                created_by: None,
+                created: None,
+            })
+            .await
+            .assert_status_ok();
+    }
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(b"photo".to_vec())
+            .file_name("jane.jpg")
+            .mime_type("image/jpeg"),
+    );
+    let photo: attachment::Model = server
+        .post(&format!("/api/v1/node/{}/attachment", duplicate.id))
+        .multipart(form)
+        .await
+        .json();
+
+    for (id, target_id, status) in [
+        (jane.id, jane.id, 400),
+        (Uuid::nil(), jane.id, 400),
+        (jane.id, Uuid::nil(), 400),
+        (elsewhere.id, jane.id, 400),
+        (Uuid::new_v4(), jane.id, 404),
+        (duplicate.id, Uuid::new_v4(), 404),
+    ] {
+        let res = server
+            .post(&format!("/api/v1/node/{}/merge/{}", id, target_id))
+            .expect_failure()
+            .await;
+        assert_eq!(res.status_code(), status, "merging {id} into {target_id}");
+    }
+
+    let res = server
+        .post(&format!("/api/v1/node/{}/merge/{}", duplicate.id, jane.id))
+        .await;
+    res.assert_status_ok();
+    let merged: node::Model = res.json();
+    assert_eq!(merged.id, jane.id);
+    assert_eq!(
+        merged.notes.as_deref(),
+        Some("owns the domain\n\nmet at conf")
+    );
+
+    server
+        .get(&format!("/api/v1/node/{}", duplicate.id))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+
+    let links: Vec<nodelink::Model> = server
+        .get(&format!("/api/v1/project/{}/nodelinks", project.id))
+        .await
+        .json();
+    let mut pairs: Vec<(Uuid, Uuid)> = links.iter().map(|l| (l.left, l.right)).collect();
+    pairs.sort();
+    let mut expected = vec![(jane.id, bob.id), (jane.id, alice.id)];
+    expected.sort();
+    assert_eq!(pairs, expected);
+
+    let attachments: Vec<attachment::Model> = server
+        .get(&format!("/api/v1/node/{}/attachments", jane.id))
+        .await
+        .json();
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(attachments[0].id, photo.id);
+}
+
+#[tokio::test]
+async fn test_api_timeline_export() {
+    use crate::entity::nodelink;
+    use crate::export::{TimelineJsExport, VisTimelineExport};
+    use osint_graph_shared::nodelink::LinkType;
+
+    let server = setup_test_server().await;
+
+    let project = new_test_project("Timeline");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    let person = node::Model {
+        project_id: project.id,
+        node_type: NodeType::Person,
+        display: "<script>alert(\"x\")</script>".to_string(),
+        value: "Jane & co".to_string(),
+        notes: Some("first line\nsecond line".to_string()),
+        ..Default::default()
+    };
+    let domain = node::Model {
+        project_id: project.id,
+        node_type: NodeType::Domain,
+        display: "example.com".to_string(),
+        value: "example.com".to_string(),
+        ..Default::default()
+    };
+    for node in [&person, &domain] {
+        server
+            .post("/api/v1/node")
+            .json(node)
+            .await
+            .assert_status_ok();
+    }
+    server
+        .post("/api/v1/nodelink")
+        .json(&nodelink::Model {
+            id: Uuid::new_v4(),
+            project_id: project.id,
+            left: person.id,
+            right: domain.id,
+            linktype: LinkType::Omni,
+            weight: None,
+            kind: None,
+            valid_from: None,
+            valid_to: None,
+            created_by: None,
+            created: None,
+        })
+        .await
+        .assert_status_ok();
+    let url = format!("/api/v1/project/{}/export/timeline.json", project.id);
+
+    let res = server.get(&url).await;
+    res.assert_status_ok();
+    let timeline: TimelineJsExport = res.json();
+    assert_eq!(timeline.title.text.headline, "Timeline");
+    assert_eq!(timeline.events.len(), 2);
+    assert_eq!(timeline.meta.undated, 1);
+    let event = timeline
+        .events
+        .iter()
+        .find(|event| event.unique_id == person.id.to_string())
+        .expect("Person missing from the timeline");
+    assert_eq!(
+        event.text.headline,
+        "&lt;script&gt;alert(&quot;x&quot;)&lt;/script&gt;"
+    );
+    assert_eq!(
+        event.text.text,
+        "Jane &amp; co<br>first line<br>second line"
+    );
+    assert_eq!(event.group, "person");
+    assert!(event.start_date.year >= 2024);
+
+    let res = server.get(&url).add_query_param("flavor", "vis").await;
+    res.assert_status_ok();
+    let timeline: VisTimelineExport = res.json();
+    assert_eq!(timeline.items.len(), 2);
+    assert_eq!(timeline.meta.undated, 1);
+    let mut groups: Vec<&str> = timeline.groups.iter().map(|g| g.id.as_str()).collect();
+    groups.sort();
+    assert_eq!(groups, vec!["domain", "person"]);
+    let item = timeline
+        .items
+        .iter()
+        .find(|item| item.id == person.id)
+        .expect("Person missing from the timeline");
+    assert!(!item.content.contains('<'));
+
+    // links to filtered-out nodes aren't counted either
+    let timeline: VisTimelineExport = server
+        .get(&url)
+        .add_query_param("flavor", "vis")
+        .add_query_param("node_types", "person")
+        .await
+        .json();
+    assert_eq!(timeline.items.len(), 1);
+    assert_eq!(timeline.meta.undated, 0);
+
+    let res = server
+        .get(&url)
+        .add_query_param("flavor", "gantt")
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 400);
+}
+
+#[tokio::test]
+async fn test_api_attachment_etag() {
+    use crate::attachment::ATTACHMENT_CACHE_CONTROL;
+    use crate::entity::attachment;
+    use axum::http::header::{ACCEPT_ENCODING, CACHE_CONTROL, ETAG, IF_NONE_MATCH};
+
+    let server = setup_test_server().await;
+
+    let project: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("ETags"))
+        .await
+        .json();
+    let node: node::Model = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            node_type: NodeType::Document,
+            display: "notes".to_string(),
+            value: "notes".to_string(),
+            ..Default::default()
+        })
+        .await
+        .json();
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(b"some notes".to_vec())
+            .file_name("notes.txt")
+            .mime_type("text/plain"),
+    );
+    let uploaded: attachment::Model = server
+        .post(&format!("/api/v1/node/{}/attachment", node.id))
+        .multipart(form)
+        .await
+        .json();
+    let sha256 = uploaded.sha256.clone().expect("Upload wasn't hashed");
+
+    for url in [
+        format!("/api/v1/attachment/{}", uploaded.id),
+        format!("/api/v1/attachment/{}/view", uploaded.id),
+    ] {
+        let res = server
+            .get(&url)
+            .add_header(ACCEPT_ENCODING, "identity")
+            .await;
+        res.assert_status_ok();
+        assert_eq!(res.header(CACHE_CONTROL), ATTACHMENT_CACHE_CONTROL);
+        let etag = res.header(ETAG);
+        assert_eq!(etag, format!("\"{sha256}\""));
+
+        let res = server
+            .get(&url)
+            .add_header(ACCEPT_ENCODING, "identity")
+            .add_header(IF_NONE_MATCH, etag.clone())
+            .expect_failure()
+            .await;
+        assert_eq!(res.status_code(), 304);
+        assert!(res.as_bytes().is_empty());
+        assert_eq!(res.header(ETAG), etag);
+        assert_eq!(res.header(CACHE_CONTROL), ATTACHMENT_CACHE_CONTROL);
+
+        // the stored encoding is a different representation
+        let res = server
+            .get(&url)
+            .add_header(ACCEPT_ENCODING, "gzip")
+            .add_header(IF_NONE_MATCH, etag)
+            .await;
+        res.assert_status_ok();
+        assert_eq!(res.header(ETAG), format!("\"{sha256}.gzip\""));
+
+        let res = server
+            .get(&url)
+            .add_header(ACCEPT_ENCODING, "identity")
+            .add_header(IF_NONE_MATCH, "\"something-else\"")
+            .await;
+        res.assert_status_ok();
+    }
+
+    // everything else still isn't cached
+    let res = server.get(&format!("/api/v1/node/{}", node.id)).await;
+    assert_eq!(res.header(CACHE_CONTROL), "private, no-transform max-age=0");
+}
+
+#[tokio::test]
+async fn test_drop_project_nodes_migration() {
+    use crate::migration::Migrator;
+    use sea_orm::{ConnectionTrait, Database, EntityTrait, Statement};
+    use sea_orm_migration::MigratorTrait;
+
+    let conn = Database::connect("sqlite::memory:")
+        .await
+        .expect("Failed to open DB");
+    let earlier = Migrator::migrations()
+        .iter()
+        .position(|migration| migration.name() == "m20261015_000008_drop_project_nodes")
+        .expect("drop project nodes migration should be registered") as u32;
+    Migrator::up(&conn, Some(earlier))
+        .await
+        .expect("Failed to run earlier migrations");
+
+    let project_id = Uuid::new_v4();
+    conn.execute(Statement::from_sql_and_values(
+        conn.get_database_backend(),
+        "INSERT INTO project (id, name, user, creationdate, nodes, tags) VALUES (?, ?, ?, ?, ?, ?)",
+        [
+            project_id.into(),
+            "Legacy".into(),
+            Uuid::nil().into(),
+            chrono::Utc::now().to_rfc3339().into(),
+            format!("{{\"{}\": \"2024-01-01T00:00:00Z\"}}", Uuid::new_v4()).into(),
+            "[]".into(),
+        ],
+    ))
+    .await
+    .expect("Failed to seed legacy project");
+
+    Migrator::up(&conn, None)
+        .await
+        .expect("Failed to drop the nodes column");
+
+    let columns: Vec<String> = conn
+        .query_all(Statement::from_string(
+            conn.get_database_backend(),
+            "SELECT name FROM pragma_table_info('project')",
+        ))
+        .await
+        .expect("Failed to list columns")
+        .iter()
+        .map(|row| row.try_get("", "name").expect("Missing column name"))
+        .collect();
+    assert!(columns.contains(&"name".to_string()));
+    assert!(!columns.contains(&"nodes".to_string()));
+
+    let project = project::Entity::find_by_id(project_id)
+        .one(&conn)
+        .await
+        .expect("Failed to load project")
+        .expect("Legacy project missing");
+    assert_eq!(project.name, "Legacy");
+}
+
+#[tokio::test]
+async fn test_drop_node_change_history_migration() {
+    use crate::entity::node_history::{self, NodeChange};
+    use crate::migration::Migrator;
+    use osint_graph_shared::node::NodeType;
+    use sea_orm::{ConnectionTrait, Database, EntityTrait, QueryOrder, Statement};
+    use sea_orm_migration::MigratorTrait;
+
+    let conn = Database::connect("sqlite::memory:")
+        .await
+        .expect("Failed to open DB");
+    let earlier = Migrator::migrations()
+        .iter()
+        .position(|migration| migration.name() == "m20261015_000021_drop_node_change_history")
+        .expect("drop node change history migration should be registered") as u32;
+    Migrator::up(&conn, Some(earlier))
+        .await
+        .expect("Failed to run earlier migrations");
+
+    // a person at old.example, renamed to mid.example, retyped to a domain, renamed to new.example
+    let (project_id, node_id) = (Uuid::new_v4(), Uuid::new_v4());
+    let at = |minutes: i64| {
+        chrono::DateTime::parse_from_rfc3339("2026-01-01T00:00:00Z")
+            .unwrap()
+            .to_utc()
+            + chrono::Duration::minutes(minutes)
+    };
+    for (sql, values) in [
+        (
+            "INSERT INTO project (id, name, user, creationdate, tags) VALUES (?, ?, ?, ?, ?)",
+            vec![
+                project_id.into(),
+                "Legacy".into(),
+                Uuid::nil().into(),
+                at(0).into(),
+                "[]".into(),
+            ],
+        ),
+        (
+            "INSERT INTO node (id, project_id, type, display, value, updated) VALUES (?, ?, ?, ?, ?, ?)",
+            vec![
+                node_id.into(),
+                project_id.into(),
+                "domain".into(),
+                "Legacy node".into(),
+                "new.example".into(),
+                at(3).into(),
+            ],
+        ),
+        (
+            "INSERT INTO node_value_history (id, node_id, old_value, new_value, changed) VALUES (?, ?, ?, ?, ?)",
+            vec![
+                Uuid::new_v4().into(),
+                node_id.into(),
+                "old.example".into(),
+                "mid.example".into(),
+                at(1).into(),
+            ],
+        ),
+        (
+            "INSERT INTO node_type_history (id, node_id, from_type, to_type, changed) VALUES (?, ?, ?, ?, ?)",
+            vec![
+                Uuid::new_v4().into(),
+                node_id.into(),
+                "person".into(),
+                "domain".into(),
+                at(2).into(),
+            ],
+        ),
+        (
+            "INSERT INTO node_value_history (id, node_id, old_value, new_value, changed) VALUES (?, ?, ?, ?, ?)",
+            vec![
+                Uuid::new_v4().into(),
+                node_id.into(),
+                "mid.example".into(),
+                "new.example".into(),
+                at(3).into(),
+            ],
+        ),
+    ] {
+        conn.execute(Statement::from_sql_and_values(
+            conn.get_database_backend(),
+            sql,
+            values,
+        ))
+        .await
+        .expect("Failed to seed legacy history");
+    }
+
+    Migrator::up(&conn, None)
+        .await
+        .expect("Failed to drop the old history tables");
+
+    let history = node_history::Entity::find()
+        .order_by_asc(node_history::Column::ChangedAt)
+        .all(&conn)
+        .await
+        .expect("Failed to load history");
+    let versions: Vec<_> = history
+        .iter()
+        .map(|entry| {
+            let previous = entry.previous_node().expect("previous node");
+            assert_eq!(entry.node_id, node_id);
+            assert_eq!(entry.change, NodeChange::Update);
+            assert_eq!(previous.display, "Legacy node");
+            (entry.changed_at, previous.node_type, previous.value)
+        })
+        .collect();
+    assert_eq!(
+        versions,
+        vec![
+            (at(1), NodeType::Person, "old.example".to_string()),
+            (at(2), NodeType::Person, "mid.example".to_string()),
+            (at(3), NodeType::Domain, "mid.example".to_string()),
+        ]
+    );
+    for table in ["node_type_history", "node_value_history"] {
+        let tables = conn
+            .query_all(Statement::from_sql_and_values(
+                conn.get_database_backend(),
+                "SELECT name FROM sqlite_master WHERE type = 'table' AND name = ?",
+                [table.into()],
+            ))
+            .await
+            .expect("Failed to list tables");
+        assert!(tables.is_empty(), "{table} was dropped");
+    }
+}
+
+#[tokio::test]
+async fn test_legacy_project_nodes_key_ignored() {
+    let server = setup_test_server().await;
+
+    // older clients and exports still send the node update list with the project
+    let mut project = serde_json::to_value(new_test_project("Legacy export")).unwrap();
+    project["nodes"] = serde_json::json!({ Uuid::new_v4().to_string(): "2024-01-01T00:00:00Z" });
+    let created: project::Model = server.post("/api/v1/project").json(&project).await.json();
+    assert_eq!(created.name, "Legacy export");
+
+    let export = serde_json::json!({
+        "project": project,
+        "nodes": [],
+        "nodelinks": [],
+        "exported_at": "2024-01-01T00:00:00Z",
+        "version": "0.1.0",
+        "attachments": [],
+    });
+    let export: ProjectExport = serde_json::from_value(export).expect("Old export didn't parse");
+    assert_eq!(export.project.id, created.id);
+}
+
+#[tokio::test]
+async fn test_api_post_project_full() {
+    use crate::entity::nodelink;
+    use crate::project::{ErrorResponse, ProjectGraph, ProjectGraphCreated};
+    use osint_graph_shared::nodelink::LinkType;
+
+    let server = setup_test_server().await;
+
+    let project = new_test_project("Generated");
+    let nodes: Vec<node::Model> = (0..5)
+        .map(|idx| node::Model {
+            project_id: project.id,
+            node_type: NodeType::Domain,
+            display: format!("host{idx}.example.com"),
+            value: format!("host{idx}.example.com"),
+            ..Default::default()
+        })
+        .collect();
+    let nodelinks: Vec<nodelink::Model> = nodes
+        .windows(2)
+        .map(|pair| nodelink::Model {
+            id: Uuid::new_v4(),
+            project_id: project.id,
+            left: pair[0].id,
+            right: pair[1].id,
+            linktype: LinkType::Directional,
+            weight: None,
+            kind: None,
+            valid_from: None,
+            valid_to: None,
+            created_by: None,
+            created: None,
+        })
+        .collect();
+    let mut graph = ProjectGraph {
+        project: project.clone(),
+        nodes: nodes.clone(),
+        nodelinks: nodelinks.clone(),
+    };
+
+    // a link to a node that isn't in the request fails the lot
+    graph.nodelinks[3].right = Uuid::new_v4();
+    let res = server
+        .post("/api/v1/project/full")
+        .json(&graph)
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 400);
+    let err: serde_json::Value = res.json();
+    assert_eq!(err["field"], "nodelinks");
+    assert_eq!(err["index"], 3);
+    server
+        .get(&format!("/api/v1/project/{}", project.id))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+    graph.nodelinks = nodelinks.clone();
+
+    let res = server.post("/api/v1/project/full").json(&graph).await;
+    res.assert_status_ok();
+    let created: ProjectGraphCreated = res.json();
+    assert_eq!(created.project_id, project.id);
+    assert_eq!(
+        created.node_ids,
+        nodes.iter().map(|node| node.id).collect::<Vec<_>>()
+    );
+    assert_eq!(created.nodelink_ids.len(), 4);
+
+    let res = server
+        .get(&format!("/api/v1/project/{}/export", project.id))
+        .await;
+    res.assert_status_ok();
+    let export: ProjectExport = res.json();
+    assert_eq!(export.project.name, "Generated");
+    assert_eq!(export.nodes.len(), 5);
+    let mut link_ids: Vec<Uuid> = export.nodelinks.iter().map(|l| l.id).collect();
+    link_ids.sort();
+    let mut expected = created.nodelink_ids.clone();
+    expected.sort();
+    assert_eq!(link_ids, expected);
+
+    // doing it again conflicts
+    let res = server
+        .post("/api/v1/project/full")
+        .json(&graph)
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 409);
+
+    // as does reusing a node ID under a new project
+    let other = new_test_project("Reused");
+    let mut reused = nodes[2].clone();
+    reused.project_id = other.id;
+    let res = server
+        .post("/api/v1/project/full")
+        .json(&ProjectGraph {
+            project: other,
+            nodes: vec![reused],
+            nodelinks: vec![],
+        })
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 409);
+    let err: ErrorResponse = res.json();
+    assert_eq!(err.code.as_deref(), Some(crate::project::NODE_ID_CONFLICT));
+}
+
+#[test]
+fn test_openapi_operation_naming() {
+    use std::collections::HashSet;
+    use utoipa::openapi::path::ParameterIn;
+    use utoipa::OpenApi;
+
+    use crate::openapi::{ApiDoc, API_TAGS};
+
+    let doc = ApiDoc::openapi();
+    let declared: Vec<&str> = doc
+        .tags
+        .iter()
+        .flatten()
+        .map(|tag| tag.name.as_str())
+        .collect();
+    assert_eq!(declared, API_TAGS);
+
+    let mut operation_ids = HashSet::new();
+    for (path, item) in doc.paths.paths.iter() {
+        let operations = [
+            ("get", &item.get),
+            ("put", &item.put),
+            ("post", &item.post),
+            ("delete", &item.delete),
+            ("patch", &item.patch),
+        ];
+        for (method, operation) in operations {
+            let Some(operation) = operation else {
+                continue;
+            };
+            let context = format!("{method} {path}");
+
+            let operation_id = operation
+                .operation_id
+                .as_deref()
+                .unwrap_or_else(|| panic!("{context} has no operation_id"));
+            assert!(
+                !operation_id.is_empty()
+                    && !operation_id.starts_with('_')
+                    && operation_id
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_'),
+                "{context} operation_id {operation_id} isn't snake_case"
+            );
+            assert!(
+                operation_ids.insert(operation_id.to_string()),
+                "{context} reuses operation_id {operation_id}"
+            );
+
+            let tags = operation.tags.clone().unwrap_or_default();
+            assert_eq!(tags.len(), 1, "{context} should have one tag: {tags:?}");
+            assert!(
+                API_TAGS.contains(&tags[0].as_str()),
+                "{context} has unknown tag {}",
+                tags[0]
+            );
+
+            // every placeholder in the path is documented
+            let path_params: Vec<&str> = operation
+                .parameters
+                .iter()
+                .flatten()
+                .filter(|param| matches!(param.parameter_in, ParameterIn::Path))
+                .map(|param| param.name.as_str())
+                .collect();
+            for placeholder in path.split('/').filter_map(|segment| {
+                segment
+                    .strip_prefix('{')
+                    .and_then(|segment| segment.strip_suffix('}'))
+            }) {
+                assert!(
+                    path_params.contains(&placeholder),
+                    "{context} doesn't document {placeholder}"
+                );
+            }
+        }
+    }
+
+    let upload = doc.paths.paths["/api/v1/node/{id}/attachment"]
+        .post
+        .as_ref()
+        .and_then(|operation| operation.request_body.as_ref())
+        .expect("upload should have a request body");
+    assert!(upload.content.contains_key("multipart/form-data"));
+    let search = doc.paths.paths["/api/v1/search"].get.as_ref().unwrap();
+    assert!(search
+        .parameters
+        .iter()
+        .flatten()
+        .any(|param| param.name == "q"));
+}
+
+#[tokio::test]
+async fn test_api_layout_max_nodes() {
+    use crate::entity::nodelink;
+    use crate::layout::{force_iterations, LayoutResponse, LAYOUT_TOO_LARGE};
+    use crate::project::ErrorResponse;
+    use osint_graph_shared::nodelink::LinkType;
+
+    let mut appstate = AppState::test().await;
+    appstate.max_layout_nodes = 3;
+    let server = setup_test_server_with_state(appstate).await;
+
+    let project = new_test_project("layout");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    let mut node_ids = Vec::new();
+    for display in ["a", "b", "c"] {
+        let node = node::Model {
+            project_id: project.id,
+            display: display.to_string(),
+            ..Default::default()
+        };
+        server
+            .post("/api/v1/node")
+            .json(&node)
+            .await
+            .assert_status_ok();
+        node_ids.push(node.id);
+    }
+    server
+        .post("/api/v1/nodelink")
+        .json(&nodelink::Model {
+            id: Uuid::new_v4(),
+            left: node_ids[0],
+            right: node_ids[1],
+            project_id: project.id,
+            linktype: LinkType::Omni,
+            weight: None,
+            kind: None,
+            valid_from: None,
+            valid_to: None,
+            created_by: None,
+            created: None,
+        })
+        .await
+        .assert_status_ok();
+
+    // at the cap, force layout runs and saves the positions
+    let res = server
+        .post(&format!("/api/v1/project/{}/layout", project.id))
+        .await;
+    res.assert_status_ok();
+    let layout: LayoutResponse = res.json();
+    assert_eq!(layout.iterations, force_iterations(3));
+    assert_eq!(layout.positions.len(), 3);
+    let stored: node::Model = server
+        .get(&format!("/api/v1/node/{}", layout.positions[0].id))
+        .await
+        .json();
+    assert_eq!(stored.pos_x, Some(layout.positions[0].pos_x));
+    assert_eq!(stored.pos_y, Some(layout.positions[0].pos_y));
+
+    server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            ..Default::default()
+        })
+        .await
+        .assert_status_ok();
+
+    let res = server
+        .post(&format!("/api/v1/project/{}/layout", project.id))
+        .add_query_param("algorithm", "force")
+        .expect_failure()
+        .await;
+    res.assert_status(axum::http::StatusCode::PAYLOAD_TOO_LARGE);
+    let err: ErrorResponse = res.json();
+    assert_eq!(err.code.as_deref(), Some(LAYOUT_TOO_LARGE));
+    assert!(err.error.contains("algorithm=grid"));
+
+    let res = server
+        .post(&format!("/api/v1/project/{}/layout", project.id))
+        .add_query_param("algorithm", "grid")
+        .await;
+    res.assert_status_ok();
+    let layout: LayoutResponse = res.json();
+    assert_eq!(layout.iterations, 0);
+    assert_eq!(layout.positions.len(), 4);
+    let cells: std::collections::HashSet<(i32, i32)> = layout
+        .positions
+        .iter()
+        .map(|position| (position.pos_x, position.pos_y))
+        .collect();
+    assert_eq!(cells.len(), 4, "grid cells should be distinct");
+
+    // bigger projects get fewer iterations
+    assert!(force_iterations(2000) < force_iterations(100));
+}
+
+#[tokio::test]
+async fn test_api_project_review() {
+    use crate::review::{ProjectReview, ReviewCheck};
+
+    let server = setup_test_server().await;
+    let project = new_test_project("review");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+
+    let mut ids = std::collections::HashMap::new();
+    for (display, node_type, value, notes, attach) in [
+        (
+            "a complete",
+            NodeType::Ip,
+            "10.0.0.1",
+            Some("from the mail headers"),
+            true,
+        ),
+        ("b bad ip", NodeType::Ip, "10.0.0.999", Some("typo?"), true),
+        (
+            "c bare email",
+            NodeType::Email,
+            "user@example.com",
+            None,
+            false,
+        ),
+        (
+            "d person",
+            NodeType::Person,
+            "Jane Citizen",
+            Some("the CEO"),
+            false,
+        ),
+    ] {
+        let node = node::Model {
+            project_id: project.id,
+            node_type,
+            display: display.to_string(),
+            value: value.to_string(),
+            notes: notes.map(str::to_string),
+            ..Default::default()
+        };
+        // a single node with a bad value is refused, so it has to come in through the bulk API
+        if display == "b bad ip" {
+            server
+                .post("/api/v1/nodes")
+                .json(&vec![&node])
+                .await
+                .assert_status_ok();
+        } else {
+            server
+                .post("/api/v1/node")
+                .json(&node)
+                .await
+                .assert_status_ok();
+        }
+        if attach {
+            let form = axum_test::multipart::MultipartForm::new().add_part(
+                "file",
+                axum_test::multipart::Part::bytes(b"evidence".to_vec())
+                    .file_name("evidence.txt")
+                    .mime_type("text/plain"),
+            );
+            server
+                .post(&format!("/api/v1/node/{}/attachment", node.id))
+                .multipart(form)
+                .await
+                .assert_status_ok();
+        }
+        ids.insert(display, node.id);
+    }
+
+    let review: ProjectReview = server
+        .get(&format!("/api/v1/project/{}/review", project.id))
+        .await
+        .json();
+    assert_eq!(review.checks, ReviewCheck::ALL.to_vec());
+    assert_eq!(review.node_count, 4);
+    assert_eq!(review.complete_nodes, 1);
+    // 4 of the 12 node checks fail
+    assert!((review.completeness - 200.0 / 3.0).abs() < 0.001);
+    let failing: std::collections::BTreeMap<ReviewCheck, Vec<Uuid>> = review
+        .failing
+        .into_iter()
+        .map(|(check, nodes)| (check, nodes.into_iter().map(|node| node.id).collect()))
+        .collect();
+    assert_eq!(
+        failing,
+        [
+            (
+                ReviewCheck::HasAttachment,
+                vec![ids["c bare email"], ids["d person"]]
+            ),
+            (ReviewCheck::HasNotes, vec![ids["c bare email"]]),
+            (ReviewCheck::ValueValidates, vec![ids["b bad ip"]]),
+        ]
+        .into_iter()
+        .collect()
+    );
+
+    let review: ProjectReview = server
+        .get(&format!("/api/v1/project/{}/review", project.id))
+        .add_query_param("checks", "value_validates")
+        .await
+        .json();
+    assert_eq!(review.complete_nodes, 3);
+    assert_eq!(review.completeness, 75.0);
+
+    server
+        .get(&format!("/api/v1/project/{}/review", project.id))
+        .add_query_param("checks", "has_source")
+        .expect_failure()
+        .await
+        .assert_status_bad_request();
+
+    // the listing filter, for working through the review
+    let listed = |query: &'static [(&'static str, &'static str)]| {
+        let request = server.get(&format!("/api/v1/project/{}/nodes", project.id));
+        async move {
+            let mut nodes = request
+                .add_query_params(query)
+                .await
+                .json::<PaginatedResponse<node::Model>>()
+                .items;
+            nodes.sort_by(|a, b| a.display.cmp(&b.display));
+            nodes.into_iter().map(|node| node.id).collect::<Vec<_>>()
+        }
+    };
+    assert_eq!(listed(&[]).await.len(), 4);
+    assert_eq!(
+        listed(&[("incomplete_only", "true")]).await,
+        vec![ids["b bad ip"], ids["c bare email"], ids["d person"]]
+    );
+    assert_eq!(
+        listed(&[("incomplete_only", "true"), ("checks", "has_notes")]).await,
+        vec![ids["c bare email"]]
+    );
+}
+
+#[tokio::test]
+async fn test_api_project_score() {
+    use crate::entity::nodelink;
+    use crate::score::ProjectScore;
+    use osint_graph_shared::nodelink::LinkType;
+
+    let server = setup_test_server().await;
+
+    // a couple of bare nodes and nothing else
+    let sparse = new_test_project("sparse");
+    server
+        .post("/api/v1/project")
+        .json(&sparse)
+        .await
+        .assert_status_ok();
+    let sparse_nodes: Vec<node::Model> = (0..2)
+        .map(|index| node::Model {
+            project_id: sparse.id,
+            node_type: NodeType::Ip,
+            display: format!("host {index}"),
+            value: "not an address".to_string(),
+            ..Default::default()
+        })
+        .collect();
+    server
+        .post("/api/v1/nodes")
+        .json(&sparse_nodes)
+        .await
+        .assert_status_ok();
+
+    // linked, annotated nodes with evidence
+    let rich = new_test_project("rich");
+    server
+        .post("/api/v1/project")
+        .json(&rich)
+        .await
+        .assert_status_ok();
+    let rich_nodes: Vec<node::Model> = (0..6)
+        .map(|index| node::Model {
+            project_id: rich.id,
+            node_type: NodeType::Ip,
+            display: format!("host {index}"),
+            value: format!("10.0.0.{index}"),
+            notes: Some(format!("seen in log {index}")),
+            ..Default::default()
+        })
+        .collect();
+    server
+        .post("/api/v1/nodes")
+        .json(&rich_nodes)
+        .await
+        .assert_status_ok();
+    for (index, node) in rich_nodes.iter().enumerate() {
+        let next = &rich_nodes[(index + 1) % rich_nodes.len()];
+        server
+            .post("/api/v1/nodelink")
+            .json(&nodelink::Model {
+                id: Uuid::new_v4(),
+                left: node.id,
+                right: next.id,
+                project_id: rich.id,
+                linktype: LinkType::Omni,
+                weight: None,
+                kind: None,
+                valid_from: None,
+                valid_to: None,
+                created_by: None,
+                created: None,
+            })
+            .await
+            .assert_status_ok();
+        let form = axum_test::multipart::MultipartForm::new().add_part(
+            "file",
+            axum_test::multipart::Part::bytes(b"evidence".to_vec())
+                .file_name("evidence.txt")
+                .mime_type("text/plain"),
+        );
+        server
+            .post(&format!("/api/v1/node/{}/attachment", node.id))
+            .multipart(form)
+            .await
+            .assert_status_ok();
+    }
+
+    let score = |id: Uuid| {
+        let server = &server;
+        async move {
+            let res = server.get(&format!("/api/v1/project/{id}/score")).await;
+            res.assert_status_ok();
+            res.json::<ProjectScore>()
+        }
+    };
+    let sparse_score = score(sparse.id).await;
+    let rich_score = score(rich.id).await;
+    assert!(
+        rich_score.score > sparse_score.score,
+        "{} <= {}",
+        rich_score.score,
+        sparse_score.score
+    );
+    assert!(rich_score.score <= 100);
+    assert!(!rich_score.formula.is_empty());
+    for result in [&sparse_score, &rich_score] {
+        let names: Vec<&str> = result.components.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "node_count",
+                "link_density",
+                "notes",
+                "attachments",
+                "verified"
+            ]
+        );
+    }
+    assert!(sparse_score
+        .components
+        .iter()
+        .all(|c| c.name == "node_count" || c.points == 0.0));
+    assert!(rich_score
+        .components
+        .iter()
+        .filter(|c| c.name != "node_count" && c.name != "link_density")
+        .all(|c| c.value == 1.0));
+
+    server
+        .get(&format!("/api/v1/project/{}/score", Uuid::new_v4()))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+}
+
+#[tokio::test]
+async fn test_api_attachment_raw() {
+    use crate::attachment::{
+        ATTACHMENT_CODEC_HEADER, ATTACHMENT_CONTENT_TYPE_HEADER, ATTACHMENT_SHA256_HEADER,
+        ATTACHMENT_SIZE_HEADER,
+    };
+    use crate::attachment_codec::AttachmentCodec;
+    use crate::attachment_dedup::content_hash;
+    use crate::entity::attachment;
+
+    let mut appstate = AppState::test().await;
+    appstate.attachment_codec = AttachmentCodec::Zstd;
+    let server = setup_test_server_with_state(appstate).await;
+
+    let project = new_test_project("raw attachments");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    let node = node::Model {
+        project_id: project.id,
+        ..Default::default()
+    };
+    server
+        .post("/api/v1/node")
+        .json(&node)
+        .await
+        .assert_status_ok();
+
+    let content = "a line which compresses nicely\n".repeat(100).into_bytes();
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(content.clone())
+            .file_name("notes.txt")
+            .mime_type("text/plain"),
+    );
+    let uploaded: attachment::Model = server
+        .post(&format!("/api/v1/node/{}/attachment", node.id))
+        .multipart(form)
+        .await
+        .json();
+
+    // a client which accepts gzip still gets the stored bytes, not gzip on top of zstd
+    let res = server
+        .get(&format!("/api/v1/attachment/{}/raw", uploaded.id))
+        .add_header(axum::http::header::ACCEPT_ENCODING, "gzip")
+        .await;
+    res.assert_status_ok();
+    assert!(res.maybe_header(CONTENT_ENCODING).is_none());
+    assert_eq!(res.header(CONTENT_TYPE), "application/zstd");
+    assert_eq!(res.header(ATTACHMENT_CODEC_HEADER), "zstd");
+    assert_eq!(
+        res.header(ATTACHMENT_SIZE_HEADER),
+        content.len().to_string().as_str()
+    );
+    assert_eq!(res.header(ATTACHMENT_CONTENT_TYPE_HEADER), "text/plain");
+    assert_eq!(
+        res.header(ATTACHMENT_SHA256_HEADER),
+        content_hash(&content).as_str()
+    );
+
+    let raw = res.as_bytes().to_vec();
+    assert!(raw.len() < content.len());
+    let codec: AttachmentCodec = res
+        .header(ATTACHMENT_CODEC_HEADER)
+        .to_str()
+        .ok()
+        .and_then(|codec| serde_json::from_value(serde_json::json!(codec)).ok())
+        .expect("codec header should name a codec");
+    assert_eq!(codec.decode(&raw).ok(), Some(content));
+
+    server
+        .get(&format!("/api/v1/attachment/{}/raw", Uuid::new_v4()))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+}
+
+#[tokio::test]
+async fn test_dev_proxy() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::{
+        body::Body,
+        extract::{Request, State},
+        http::{
+            header::{CONNECTION, COOKIE, HOST, UPGRADE},
+            StatusCode,
+        },
+        response::Response,
+        routing::get,
+        Json, Router,
+    };
+    use http_body_util::BodyExt;
+    use hyper_util::{
+        client::legacy::Client,
+        rt::{TokioExecutor, TokioIo},
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::dev_proxy::{DevProxy, X_FORWARDED_HOST};
+
+    // a stand-in for the frontend dev server, reporting what it was sent
+    async fn echo(
+        State(hits): State<Arc<AtomicUsize>>,
+        request: Request,
+    ) -> Json<serde_json::Value> {
+        hits.fetch_add(1, Ordering::SeqCst);
+        let header = |name| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        Json(serde_json::json!({
+            "path": request.uri().path(),
+            "query": request.uri().query(),
+            "host": header(HOST),
+            "forwarded_host": header(X_FORWARDED_HOST),
+            "cookie": header(COOKIE),
+        }))
+    }
+    async fn live_reload(mut request: Request) -> Response {
+        let on_upgrade = hyper::upgrade::on(&mut request);
+        tokio::spawn(async move {
+            let mut io = TokioIo::new(on_upgrade.await.expect("upstream upgrade"));
+            let mut buf = [0u8; 4];
+            io.read_exact(&mut buf).await.expect("read from proxy");
+            buf.reverse();
+            io.write_all(&buf).await.expect("write to proxy");
+        });
+        Response::builder()
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .header(UPGRADE, "websocket")
+            .header(CONNECTION, "upgrade")
+            .body(Body::empty())
+            .expect("upgrade response")
+    }
+
+    let hits = Arc::new(AtomicUsize::new(0));
+    let upstream = Router::new()
+        .route("/dev-websocket", get(live_reload))
+        .fallback(echo)
+        .with_state(hits.clone());
+    let upstream_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream_listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(upstream_listener, upstream).await });
+
+    let mut appstate = AppState::test().await;
+    let upstream_url = url::Url::parse(&format!("http://{upstream_addr}")).unwrap();
+    appstate.dev_proxy = Some(DevProxy::new(&upstream_url).expect("valid dev proxy URL"));
+    assert!(DevProxy::new(&url::Url::parse("https://localhost:5173").unwrap()).is_err());
+    let dbpool = appstate.conn.get_sqlite_connection_pool().clone();
+    let shared_state = Arc::new(RwLock::new(appstate));
+    let app = build_app(&shared_state, dbpool, false).await;
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let client = Client::builder(TokioExecutor::new()).build_http::<Body>();
+    let get = |path: &str| {
+        Request::get(format!("http://{addr}{path}"))
+            .header(COOKIE, "id=session")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    // frontend requests go to the dev server, with their path, query and cookies
+    let res = client
+        .request(get("/assets/index-abc123.js?v=2"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let seen: serde_json::Value =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert_eq!(seen["path"], "/assets/index-abc123.js");
+    assert_eq!(seen["query"], "v=2");
+    assert_eq!(seen["cookie"], "id=session");
+    assert_eq!(seen["host"], upstream_addr.to_string());
+    assert_eq!(seen["forwarded_host"], addr.to_string());
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    // API routes stay local, including ones which don't exist
+    let res = client.request(get("/api/v1/projects")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let projects: PaginatedResponse<project::Model> =
+        serde_json::from_slice(&res.into_body().collect().await.unwrap().to_bytes()).unwrap();
+    assert!(projects
+        .items
+        .iter()
+        .any(|project| project.id == Uuid::nil()));
+    let res = client.request(get("/api/v1/nonexistent")).await.unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    // the live-reload websocket is piped straight through
+    let request = Request::get(format!("http://{addr}/dev-websocket"))
+        .header(CONNECTION, "upgrade")
+        .header(UPGRADE, "websocket")
+        .body(Body::empty())
+        .unwrap();
+    let res = client.request(request).await.unwrap();
+    assert_eq!(res.status(), StatusCode::SWITCHING_PROTOCOLS);
+    let mut io = TokioIo::new(hyper::upgrade::on(res).await.expect("client upgrade"));
+    io.write_all(b"ping").await.unwrap();
+    let mut buf = [0u8; 4];
+    io.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"gnip");
+}
+
+#[tokio::test]
+async fn test_api_export_jsonld() {
+    use crate::entity::nodelink;
+    use crate::export::JSONLD_CONTENT_TYPE;
+    use osint_graph_shared::nodelink::LinkType;
+
+    let server = setup_test_server().await;
+    let project = new_test_project("jsonld");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+
+    let mut nodes = Vec::new();
+    for (node_type, display, value) in [
+        (NodeType::Person, "Alice", "Alice Example"),
+        (NodeType::Person, "Bob", "Bob Example"),
+        (NodeType::Organisation, "Acme", "Acme Pty Ltd"),
+        (NodeType::Location, "HQ", "1 Example St"),
+        (NodeType::Image, "Logo", "https://example.com/logo.png"),
+        (NodeType::Email, "Alice's email", "alice@example.com"),
+    ] {
+        let node = node::Model {
+            project_id: project.id,
+            node_type,
+            display: display.to_string(),
+            value: value.to_string(),
+            ..Default::default()
+        };
+        server
+            .post("/api/v1/node")
+            .json(&node)
+            .await
+            .assert_status_ok();
+        nodes.push(node);
+    }
+    for (left, right, linktype) in [(0, 1, LinkType::Omni), (0, 2, LinkType::Directional)] {
+        server
+            .post("/api/v1/nodelink")
+            .json(&nodelink::Model {
+                id: Uuid::new_v4(),
+                left: nodes[left].id,
+                right: nodes[right].id,
+                project_id: project.id,
+                linktype,
+                weight: None,
+                kind: None,
+                valid_from: None,
+                valid_to: None,
+                created_by: None,
+                created: None,
+            })
+            .await
+            .assert_status_ok();
+    }
+
+    let res = server
+        .get(&format!("/api/v1/project/{}/export/jsonld", project.id))
+        .await;
+    res.assert_status_ok();
+    assert_eq!(res.header(CONTENT_TYPE), JSONLD_CONTENT_TYPE);
+    let doc: serde_json::Value = serde_json::from_slice(res.as_bytes()).expect("valid JSON");
+    assert_eq!(doc["@context"], "https://schema.org");
+    let graph = doc["@graph"].as_array().expect("@graph should be a list");
+    assert_eq!(graph.len(), nodes.len());
+
+    let entry = |node: &node::Model| {
+        let id = format!("urn:uuid:{}", node.id);
+        graph
+            .iter()
+            .find(|entry| entry["@id"] == id.as_str())
+            .unwrap_or_else(|| panic!("{} missing from the graph", node.display))
+    };
+    let reference =
+        |node: &node::Model| serde_json::json!([{ "@id": format!("urn:uuid:{}", node.id) }]);
+    for (node, expected) in nodes.iter().zip([
+        "Person",
+        "Person",
+        "Organization",
+        "Place",
+        "ImageObject",
+        "ContactPoint",
+    ]) {
+        assert_eq!(entry(node)["@type"], expected, "{}", node.display);
+        assert_eq!(entry(node)["name"], node.display.as_str());
+    }
+    assert_eq!(entry(&nodes[0])["knows"], reference(&nodes[1]));
+    assert_eq!(entry(&nodes[1])["knows"], reference(&nodes[0]));
+    assert_eq!(entry(&nodes[0])["relatedTo"], reference(&nodes[2]));
+    // directional links only go one way
+    assert!(entry(&nodes[2]).get("relatedTo").is_none());
+    assert_eq!(
+        entry(&nodes[4])["contentUrl"],
+        "https://example.com/logo.png"
+    );
+    assert_eq!(entry(&nodes[5])["email"], "alice@example.com");
+
+    server
+        .get(&format!("/api/v1/project/{}/export/jsonld", Uuid::new_v4()))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+}
+
+#[tokio::test]
+async fn test_api_export_csv() {
+    use crate::export::CSV_CONTENT_TYPE;
+    use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+
+    let server = setup_test_server().await;
+    let project: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("Case \"42\" / north\\south"))
+        .await
+        .json();
+    let plain: node::Model = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            node_type: NodeType::Person,
+            display: "Alice".to_string(),
+            value: "alice".to_string(),
+            pos_x: Some(10),
+            pos_y: Some(-20),
+            ..Default::default()
+        })
+        .await
+        .json();
+    let awkward: node::Model = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            node_type: NodeType::Organisation,
+            display: "Acme, \"the\" company".to_string(),
+            value: "acme".to_string(),
+            notes: Some("line one\nline two".to_string()),
+            ..Default::default()
+        })
+        .await
+        .json();
+
+    let res = server
+        .get(&format!("/api/v1/project/{}/export/csv", project.id))
+        .await;
+    res.assert_status_ok();
+    assert_eq!(res.header(CONTENT_TYPE), CSV_CONTENT_TYPE);
+    assert_eq!(
+        res.header(CONTENT_DISPOSITION),
+        "attachment; filename=\"Case _42_ _ north_south.csv\""
+    );
+    let body = res.text();
+    let rows: Vec<&str> = body.split("\r\n").collect();
+    assert_eq!(
+        rows[0],
+        "id,node_type,display,value,notes,pos_x,pos_y,updated"
+    );
+    assert_eq!(
+        rows[1],
+        format!(
+            "{},{},Alice,alice,,10,-20,{}",
+            plain.id,
+            NodeType::Person,
+            plain.updated.to_rfc3339()
+        )
+    );
+    // the notes' newline stays inside the quoted field, so the rest of the row follows it
+    assert_eq!(
+        rows[2],
+        format!(
+            "{},{},\"Acme, \"\"the\"\" company\",acme,\"line one\nline two\",,,{}",
+            awkward.id,
+            NodeType::Organisation,
+            awkward.updated.to_rfc3339()
+        )
+    );
+    assert_eq!(rows[3], "");
+    assert_eq!(rows.len(), 4);
+
+    server
+        .get(&format!("/api/v1/project/{}/export/csv", Uuid::new_v4()))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+}
+
+#[tokio::test]
+async fn test_api_attachment_media() {
+    use crate::entity::attachment;
+
+    let server = setup_test_server().await;
+
+    let project = new_test_project("media attachments");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    let node = node::Model {
+        project_id: project.id,
+        ..Default::default()
+    };
+    server
+        .post("/api/v1/node")
+        .json(&node)
+        .await
+        .assert_status_ok();
+
+    // the browser didn't know what a wav was
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(include_bytes!("fixtures/one_second.wav").to_vec())
+            .file_name("call.wav")
+            .mime_type("application/octet-stream"),
+    );
+    let wav: attachment::Model = server
+        .post(&format!("/api/v1/node/{}/attachment", node.id))
+        .multipart(form)
+        .await
+        .json();
+    assert_eq!(wav.content_type, "audio/wav");
+    let media = wav.media.expect("wav should have media metadata");
+    assert_eq!(media.container, "wav");
+    assert_eq!(media.duration_ms, Some(1000));
+    assert_eq!(media.sample_rate, Some(8000));
+    assert_eq!(media.channels, Some(1));
+    assert_eq!(media.codecs, vec!["pcm".to_string()]);
+
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(include_bytes!("fixtures/one_second.mp4").to_vec())
+            .file_name("screen.mp4")
+            .mime_type("text/plain"),
+    );
+    let mp4: attachment::Model = server
+        .post(&format!("/api/v1/node/{}/attachment", node.id))
+        .multipart(form)
+        .await
+        .json();
+    assert_eq!(mp4.content_type, "video/mp4");
+    let media = mp4.media.expect("mp4 should have media metadata");
+    assert_eq!(media.container, "mp4");
+    assert_eq!(media.duration_ms, Some(1000));
+    assert_eq!((media.width, media.height), (Some(320), Some(240)));
+    assert_eq!(media.codecs, vec!["avc1".to_string()]);
+
+    // a correct declared type is left alone, and files which aren't media get no metadata
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(b"just some notes".to_vec())
+            .file_name("notes.txt")
+            .mime_type("text/plain"),
+    );
+    let text: attachment::Model = server
+        .post(&format!("/api/v1/node/{}/attachment", node.id))
+        .multipart(form)
+        .await
+        .json();
+    assert_eq!(text.content_type, "text/plain");
+    assert_eq!(text.media, None);
+
+    // the list endpoint carries the metadata for the players
+    let listed: Vec<attachment::Model> = server
+        .get(&format!("/api/v1/node/{}/attachments", node.id))
+        .await
+        .json();
+    let listed_wav = listed
+        .iter()
+        .find(|attachment| attachment.id == wav.id)
+        .expect("wav should be listed");
+    assert_eq!(
+        listed_wav
+            .media
+            .as_ref()
+            .and_then(|media| media.duration_ms),
+        Some(1000)
+    );
+}
+
+#[tokio::test]
+async fn test_api_get_nodelinks_by_node() {
+    use crate::entity::nodelink;
+    use axum::http::StatusCode;
+    use osint_graph_shared::nodelink::LinkType;
+
+    let server = setup_test_server().await;
+
+    let project = new_test_project("node links");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    let nodes: Vec<node::Model> = (0..4)
+        .map(|index| node::Model {
+            project_id: project.id,
+            display: format!("node {index}"),
+            ..Default::default()
+        })
+        .collect();
+    for node in &nodes {
+        server
+            .post("/api/v1/node")
+            .json(node)
+            .await
+            .assert_status_ok();
+    }
+    let links: Vec<nodelink::Model> = [(0, 1), (2, 1), (2, 3)]
+        .into_iter()
+        .map(|(left, right)| nodelink::Model {
+            id: Uuid::new_v4(),
+            left: nodes[left].id,
+            right: nodes[right].id,
+            project_id: project.id,
+            linktype: LinkType::Directional,
+            weight: None,
+            kind: None,
+            valid_from: None,
+            valid_to: None,
+            created_by: None,
+            created: None,
+        })
+        .collect();
+    let mut stored = Vec::with_capacity(links.len());
+    for link in &links {
+        stored.push(
+            server
+                .post("/api/v1/nodelink")
+                .json(link)
+                .await
+                .json::<nodelink::Model>(),
+        );
+    }
+    let links = stored;
+
+    // node 1 is only ever on the right
+    let mut found: Vec<Uuid> = server
+        .get(&format!("/api/v1/node/{}/nodelinks", nodes[1].id))
+        .await
+        .json::<Vec<nodelink::Model>>()
+        .into_iter()
+        .map(|link| link.id)
+        .collect();
+    found.sort();
+    let mut expected = vec![links[0].id, links[1].id];
+    expected.sort();
+    assert_eq!(found, expected);
+
+    let found: Vec<nodelink::Model> = server
+        .get(&format!("/api/v1/node/{}/nodelinks", nodes[0].id))
+        .await
+        .json();
+    assert_eq!(found, vec![links[0].clone()]);
+
+    // a node without links is an empty list, a missing node is a 404
+    let lonely = node::Model {
+        project_id: project.id,
+        ..Default::default()
+    };
+    server
+        .post("/api/v1/node")
+        .json(&lonely)
+        .await
+        .assert_status_ok();
+    let found: Vec<nodelink::Model> = server
+        .get(&format!("/api/v1/node/{}/nodelinks", lonely.id))
+        .await
+        .json();
+    assert!(found.is_empty());
+
+    server
+        .get(&format!("/api/v1/node/{}/nodelinks", Uuid::new_v4()))
+        .expect_failure()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_api_pagination() {
+    use crate::project::ErrorResponse;
+
+    let server = setup_test_server().await;
+
+    let project = new_test_project("paginated");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    for index in 0..5 {
+        server
+            .post("/api/v1/node")
+            .json(&node::Model {
+                project_id: project.id,
+                display: format!("node {index}"),
+                ..Default::default()
+            })
+            .await
+            .assert_status_ok();
+    }
+    let url = format!("/api/v1/project/{}/nodes", project.id);
+
+    // defaults
+    let page: PaginatedResponse<node::Model> = server.get(&url).await.json();
+    assert_eq!((page.page, page.page_size), (1, 50));
+    assert_eq!(page.total_count, 5);
+    assert_eq!(page.items.len(), 5);
+
+    // pages don't overlap and cover everything
+    let mut seen = Vec::new();
+    for (page_number, expected) in [(1, 2), (2, 2), (3, 1)] {
+        let page: PaginatedResponse<node::Model> = server
+            .get(&url)
+            .add_query_param("page", page_number)
+            .add_query_param("page_size", 2)
+            .await
+            .json();
+        assert_eq!(page.total_count, 5);
+        assert_eq!(page.page, page_number);
+        assert_eq!(page.items.len(), expected);
+        seen.extend(page.items.into_iter().map(|node| node.id));
+    }
+    seen.sort();
+    seen.dedup();
+    assert_eq!(seen.len(), 5);
+
+    // past the last page is empty, not missing
+    let page: PaginatedResponse<node::Model> = server
+        .get(&url)
+        .add_query_param("page", 4)
+        .add_query_param("page_size", 2)
+        .await
+        .json();
+    assert!(page.items.is_empty());
+    assert_eq!(page.total_count, 5);
+
+    // the review filter pages the same way
+    let page: PaginatedResponse<node::Model> = server
+        .get(&url)
+        .add_query_param("incomplete_only", true)
+        .add_query_param("page", 2)
+        .add_query_param("page_size", 3)
+        .await
+        .json();
+    assert_eq!(page.total_count, 5);
+    assert_eq!(page.items.len(), 2);
+
+    for (parameter, value) in [("page_size", "0"), ("page_size", "1001"), ("page", "0")] {
+        for url in [url.as_str(), "/api/v1/projects"] {
+            let res = server
+                .get(url)
+                .add_query_param(parameter, value)
+                .expect_failure()
+                .await;
+            res.assert_status_bad_request();
+            let body: ErrorResponse = res.json();
+            assert_eq!(body.parameter.as_deref(), Some(parameter), "{url}");
+            assert_eq!(body.value.as_deref(), Some(value), "{url}");
+        }
+    }
+
+    let projects: PaginatedResponse<project::Model> = server
+        .get("/api/v1/projects")
+        .add_query_param("page_size", 1)
+        .await
+        .json();
+    assert_eq!(projects.items.len(), 1);
+    assert!(projects.total_count >= 2);
+    let projects: PaginatedResponse<project::Model> = server
+        .get("/api/v1/projects")
+        .add_query_param("page", 1000)
+        .await
+        .json();
+    assert!(projects.items.is_empty());
+}
+
+#[tokio::test]
+async fn test_api_value_policy() {
+    use crate::project::{PaginatedResponse, ProjectGraph};
+    use crate::value_policy::{parse_policy, ValuePolicyEngine, ValuePolicyReport};
+
+    let mut appstate = AppState::test().await;
+    appstate.value_policy = ValuePolicyEngine::new(
+        parse_policy(
+            r#"
+            [[rule]]
+            name = "credit_card"
+            pattern = '\b(?:\d[ -]?){12,18}\d\b'
+            action = "reject"
+            luhn = true
+
+            [[rule]]
+            name = "tfn"
+            pattern = '\b\d{3} \d{3} \d{3}\b'
+            action = "mask"
+            node_types = ["person"]
+
+            [[rule]]
+            name = "codename"
+            pattern = '(?i)\bbluebird\b'
+            action = "warn"
+            "#,
+        )
+        .expect("valid policy"),
+    );
+    let server = setup_test_server_with_state(appstate).await;
+
+    let project = new_test_project("value policy");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    let card = "4111 1111 1111 1111";
+
+    // reject, from the value or the notes, without echoing the card number back
+    for (node, field) in [
+        (
+            node::Model {
+                project_id: project.id,
+                value: card.to_string(),
+                ..Default::default()
+            },
+            "value",
+        ),
+        (
+            node::Model {
+                project_id: project.id,
+                value: "Jane".to_string(),
+                notes: Some(format!("paid with {card}")),
+                ..Default::default()
+            },
+            "notes",
+        ),
+    ] {
+        let res = server
+            .post("/api/v1/node")
+            .json(&node)
+            .expect_failure()
+            .await;
+        assert_eq!(res.status_code(), 422);
+        let text = res.text();
+        assert!(!text.contains(card), "{text}");
+        assert!(!text.contains("4111111111111111"), "{text}");
+        let body: serde_json::Value = res.json();
+        assert_eq!(body["code"], "value_policy_violation");
+        assert_eq!(body["rule"], "credit_card");
+        assert_eq!(body["field"], field);
+    }
+
+    // a number which fails the Luhn check, like an order number, is fine
+    server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            value: "4111 1111 1111 1112".to_string(),
+            ..Default::default()
+        })
+        .await
+        .assert_status_ok();
+
+    // mask only applies to the configured node types
+    let person = node::Model {
+        project_id: project.id,
+        node_type: NodeType::Person,
+        display: "Jane".to_string(),
+        value: "TFN 123 456 789".to_string(),
+        ..Default::default()
+    };
+    let stored: node::Model = server.post("/api/v1/node").json(&person).await.json();
+    assert_eq!(stored.value, "TFN 12*******89");
+    let fetched: node::Model = server
+        .get(&format!("/api/v1/node/{}", person.id))
+        .await
+        .json();
+    assert_eq!(fetched.value, "TFN 12*******89");
+    let document = node::Model {
+        project_id: project.id,
+        node_type: NodeType::Document,
+        value: "Reference 123 456 789".to_string(),
+        ..Default::default()
+    };
+    let stored: node::Model = server.post("/api/v1/node").json(&document).await.json();
+    assert_eq!(stored.value, "Reference 123 456 789");
+
+    // warn stores the node untouched
+    let stored: node::Model = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            display: "Operation Bluebird".to_string(),
+            ..Default::default()
+        })
+        .await
+        .json();
+    assert_eq!(stored.display, "Operation Bluebird");
+
+    // updates are checked too
+    let res = server
+        .put(&format!("/api/v1/node/{}", person.id))
+        .json(&node::Model {
+            value: card.to_string(),
+            ..fetched.clone()
+        })
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 422);
+
+    // as is bulk creation, which names the offending node
+    let bulk_project = new_test_project("bulk value policy");
+    let bulk_nodes = vec![
+        node::Model {
+            project_id: bulk_project.id,
+            ..Default::default()
+        },
+        node::Model {
+            project_id: bulk_project.id,
+            value: card.to_string(),
+            ..Default::default()
+        },
+    ];
+    let res = server
+        .post("/api/v1/project/full")
+        .json(&ProjectGraph {
+            project: bulk_project.clone(),
+            nodes: bulk_nodes.clone(),
+            nodelinks: Vec::new(),
+        })
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 422);
+    assert!(!res.text().contains(card));
+    let body: serde_json::Value = res.json();
+    assert_eq!(body["rule"], "credit_card");
+    assert_eq!(body["index"], 1);
+    assert_eq!(body["id"], bulk_nodes[1].id.to_string());
+    let projects: PaginatedResponse<project::Model> = server.get("/api/v1/projects").await.json();
+    assert!(!projects.items.iter().any(|p| p.id == bulk_project.id));
+
+    let report: ValuePolicyReport = server.get("/api/v1/admin/value-policy").await.json();
+    let hits: Vec<(&str, u64)> = report
+        .rules
+        .iter()
+        .map(|rule| (rule.name.as_str(), rule.hits))
+        .collect();
+    assert_eq!(hits, vec![("credit_card", 4), ("tfn", 1), ("codename", 1)]);
+    assert_eq!(report.rules[1].node_types, vec![NodeType::Person]);
+}
+
+#[tokio::test]
+async fn test_api_post_nodes() {
+    let server = setup_test_server().await;
+
+    let first = new_test_project("bulk nodes one");
+    let second = new_test_project("bulk nodes two");
+    for project in [&first, &second] {
+        server
+            .post("/api/v1/project")
+            .json(project)
+            .await
+            .assert_status_ok();
+    }
+
+    let stale = chrono::Utc::now() - chrono::Duration::days(30);
+    let nodes = vec![
+        node::Model {
+            project_id: first.id,
+            node_type: NodeType::Url,
+            value: "\u{200B}https://example.com\u{2069}".to_string(),
+            updated: stale,
+            ..Default::default()
+        },
+        node::Model {
+            project_id: second.id,
+            node_type: NodeType::Person,
+            display: "Jane".to_string(),
+            updated: stale,
+            ..Default::default()
+        },
+    ];
+    let created: Vec<node::Model> = server.post("/api/v1/nodes").json(&nodes).await.json();
+    assert_eq!(
+        created.iter().map(|n| n.id).collect::<Vec<_>>(),
+        nodes.iter().map(|n| n.id).collect::<Vec<_>>()
+    );
+    assert_eq!(created[0].value, "https://example.com");
+    assert!(created.iter().all(|n| n.updated > stale));
+    let stored: node::Model = server
+        .get(&format!("/api/v1/node/{}", nodes[1].id))
+        .await
+        .json();
+    assert_eq!(stored, created[1]);
+
+    // every failure rolls back the whole batch, naming the node which caused it
+    let count_nodes = || async {
+        let page: PaginatedResponse<node::Model> = server
+            .get(&format!("/api/v1/project/{}/nodes", first.id))
+            .await
+            .json();
+        page.total_count
+    };
+    let fresh = node::Model {
+        project_id: first.id,
+        ..Default::default()
+    };
+    let missing_project = Uuid::new_v4();
+    for (batch, status, index) in [
+        (
+            vec![
+                fresh.clone(),
+                node::Model {
+                    project_id: missing_project,
+                    ..Default::default()
+                },
+            ],
+            404,
+            1,
+        ),
+        (vec![fresh.clone(), fresh.clone()], 400, 1),
+        (vec![fresh.clone(), nodes[0].clone()], 409, 1),
+    ] {
+        let res = server
+            .post("/api/v1/nodes")
+            .json(&batch)
+            .expect_failure()
+            .await;
+        assert_eq!(res.status_code(), status);
+        let body: serde_json::Value = res.json();
+        assert_eq!(body["index"], index);
+        assert_eq!(body["id"], batch[index].id.to_string());
+        assert_eq!(count_nodes().await, 1);
+    }
+
+    let created: Vec<node::Model> = server
+        .post("/api/v1/nodes")
+        .json(&Vec::<node::Model>::new())
+        .await
+        .json();
+    assert!(created.is_empty());
+}
+
+#[tokio::test]
+async fn test_api_import_project() {
+    use crate::entity::nodelink;
+    use crate::project::ProjectImported;
+    use osint_graph_shared::nodelink::LinkType;
+
+    let server = setup_test_server().await;
+    let project = new_test_project("import me");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    let nodes: Vec<node::Model> = (0..2)
+        .map(|index| node::Model {
+            project_id: project.id,
+            display: format!("node {index}"),
+            ..Default::default()
+        })
+        .collect();
+    server
+        .post("/api/v1/nodes")
+        .json(&nodes)
+        .await
+        .assert_status_ok();
+    server
+        .post("/api/v1/nodelink")
+        .json(&nodelink::Model {
+            id: Uuid::new_v4(),
+            left: nodes[0].id,
+            right: nodes[1].id,
+            project_id: project.id,
+            linktype: LinkType::Directional,
+            weight: None,
+            kind: Some("owns".to_string()),
+            valid_from: None,
+            valid_to: None,
+            created_by: None,
+            created: None,
+        })
+        .await
+        .assert_status_ok();
+    let file_content = b"evidence to carry across";
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(file_content.to_vec())
+            .file_name("evidence.txt")
+            .mime_type("text/plain"),
+    );
+    server
+        .post(&format!("/api/v1/node/{}/attachment", nodes[1].id))
+        .multipart(form)
+        .await
+        .assert_status_ok();
+
+    let export: ProjectExport = server
+        .get(&format!(
+            "/api/v1/project/{}/export?include_attachments=true",
+            project.id
+        ))
+        .await
+        .json();
+
+    // the original is still there, so its IDs are taken
+    let res = server
+        .post("/api/v1/project/import")
+        .json(&export)
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 409);
+
+    let mut imported_ids = Vec::new();
+    for _ in 0..2 {
+        let imported: ProjectImported = server
+            .post("/api/v1/project/import?remap_ids=true")
+            .json(&export)
+            .await
+            .json();
+        assert_ne!(imported.project_id, project.id);
+        assert_eq!(imported.nodes, 2);
+        assert_eq!(imported.nodelinks, 1);
+        assert_eq!(imported.attachments, 1);
+        assert_eq!(imported.skipped_attachments, 0);
+        imported_ids.push(imported.project_id);
+
+        let copy: ProjectExport = server
+            .get(&format!(
+                "/api/v1/project/{}/export?include_attachments=true",
+                imported.project_id
+            ))
+            .await
+            .json();
+        assert_eq!(copy.project.name, project.name);
+        assert!(copy
+            .nodes
+            .iter()
+            .all(|node| !nodes.iter().any(|n| n.id == node.id)));
+        let copied = |display: &str| {
+            copy.nodes
+                .iter()
+                .find(|node| node.display == display)
+                .expect("node was imported")
+                .id
+        };
+        let link = &copy.nodelinks[0];
+        assert_eq!(
+            (link.left, link.right),
+            (copied("node 0"), copied("node 1"))
+        );
+        assert_eq!(link.kind.as_deref(), Some("owns"));
+        let attachment = &copy.attachments[0];
+        assert_eq!(attachment.node_id, copied("node 1"));
+        let res = server
+            .get(&format!("/api/v1/attachment/{}", attachment.id))
+            .await;
+        res.assert_status_ok();
+        assert_eq!(res.as_bytes().as_ref(), file_content);
+    }
+    assert_ne!(imported_ids[0], imported_ids[1]);
+
+    // without include_attachments there's no data to import
+    let export: ProjectExport = server
+        .get(&format!("/api/v1/project/{}/export", project.id))
+        .await
+        .json();
+    let imported: ProjectImported = server
+        .post("/api/v1/project/import?remap_ids=true")
+        .json(&export)
+        .await
+        .json();
+    assert_eq!(imported.attachments, 0);
+    assert_eq!(imported.skipped_attachments, 1);
+
+    // links must point at nodes in the export
+    let mut broken = export;
+    broken.nodelinks[0].right = Uuid::new_v4();
+    let res = server
+        .post("/api/v1/project/import?remap_ids=true")
+        .json(&broken)
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 400);
+    let body: serde_json::Value = res.json();
+    assert_eq!(body["field"], "nodelinks");
+    assert_eq!(body["index"], 0);
+}
+
+#[tokio::test]
+async fn test_api_import_project_round_trip() {
+    use crate::entity::nodelink;
+    use crate::project::ProjectImported;
+    use osint_graph_shared::nodelink::LinkType;
+
+    let source = setup_test_server().await;
+    let project = new_test_project("round trip");
+    source
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    let nodes: Vec<node::Model> = (0..3)
+        .map(|index| node::Model {
+            project_id: project.id,
+            display: format!("node {index}"),
+            notes: Some(format!("notes {index}")),
+            pos_x: Some(index * 10),
+            ..Default::default()
+        })
+        .collect();
+    source
+        .post("/api/v1/nodes")
+        .json(&nodes)
+        .await
+        .assert_status_ok();
+    for (left, right) in [(0, 1), (1, 2)] {
+        source
+            .post("/api/v1/nodelink")
+            .json(&nodelink::Model {
+                id: Uuid::new_v4(),
+                left: nodes[left].id,
+                right: nodes[right].id,
+                project_id: project.id,
+                linktype: LinkType::Directional,
+                weight: Some(0.5),
+                kind: Some("knows".to_string()),
+                valid_from: None,
+                valid_to: None,
+                created_by: None,
+                created: None,
+            })
+            .await
+            .assert_status_ok();
+    }
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(b"round trip evidence".to_vec())
+            .file_name("evidence.txt")
+            .mime_type("text/plain"),
+    );
+    source
+        .post(&format!("/api/v1/node/{}/attachment", nodes[2].id))
+        .multipart(form)
+        .await
+        .assert_status_ok();
+
+    let export_url = format!(
+        "/api/v1/project/{}/export?include_attachments=true",
+        project.id
+    );
+    let original: ProjectExport = source.get(&export_url).await.json();
+
+    // keeping IDs on a fresh instance
+    let target = setup_test_server().await;
+    let imported: ProjectImported = target
+        .post("/api/v1/project/import")
+        .json(&original)
+        .await
+        .json();
+    assert_eq!(imported.project_id, project.id);
+    assert_eq!(imported.attachments, 1);
+
+    let copy: ProjectExport = target.get(&export_url).await.json();
+    // the import is stamped with when it happened rather than when the originals were made
+    let sorted = |export: &ProjectExport| {
+        let mut nodes = export.nodes.clone();
+        nodes.sort_by_key(|node| node.id);
+        nodes.iter_mut().for_each(|node| node.created = None);
+        let mut nodelinks = export.nodelinks.clone();
+        nodelinks.sort_by_key(|link| link.id);
+        nodelinks.iter_mut().for_each(|link| link.created = None);
+        (nodes, nodelinks)
+    };
+    assert_eq!(copy.project.name, original.project.name);
+    assert_eq!(copy.project.tags, original.project.tags);
+    assert_eq!(sorted(&copy), sorted(&original));
+    // the response already describes what was stored
+    assert_eq!(sorted(&imported.export), sorted(&copy));
+    assert!(imported.export.attachments[0].data.is_empty());
+    assert_eq!(copy.attachments.len(), 1);
+    let (copied, attachment) = (&copy.attachments[0], &original.attachments[0]);
+    assert_eq!(copied.id, attachment.id);
+    // stored compressed exactly as exported, not compressed a second time
+    assert_eq!(copied.data, attachment.data);
+    assert_eq!(copied.size, attachment.size);
+    let res = target
+        .get(&format!("/api/v1/attachment/{}", attachment.id))
+        .await;
+    res.assert_status_ok();
+    assert_eq!(res.as_bytes().as_ref(), b"round trip evidence");
+
+    // the same IDs again clash
+    let res = target
+        .post("/api/v1/project/import")
+        .json(&original)
+        .expect_failure()
+        .await;
+    res.assert_status(axum::http::StatusCode::CONFLICT);
+
+    // unless they're regenerated, exports from other versions are still accepted
+    let mut older = original.clone();
+    older.version = "0.0.1".to_string();
+    let imported: ProjectImported = target
+        .post("/api/v1/project/import?regenerate_ids=true")
+        .json(&older)
+        .await
+        .json();
+    assert_ne!(imported.project_id, project.id);
+    assert_eq!(imported.export.project.id, imported.project_id);
+    let regenerated: ProjectExport = target
+        .get(&format!(
+            "/api/v1/project/{}/export?include_attachments=true",
+            imported.project_id
+        ))
+        .await
+        .json();
+    assert_eq!(regenerated.nodes.len(), nodes.len());
+    assert_eq!(sorted(&imported.export), sorted(&regenerated));
+    assert!(regenerated
+        .nodes
+        .iter()
+        .all(|node| node.project_id == imported.project_id
+            && !nodes.iter().any(|original| original.id == node.id)));
+    let node_ids: Vec<Uuid> = regenerated.nodes.iter().map(|node| node.id).collect();
+    assert!(regenerated
+        .nodelinks
+        .iter()
+        .all(|link| node_ids.contains(&link.left) && node_ids.contains(&link.right)));
+    assert!(node_ids.contains(&regenerated.attachments[0].node_id));
+}
+
+#[tokio::test]
+async fn test_api_export_report_pdf() {
+    use crate::entity::nodelink;
+    use crate::pdf::{extract_text, page_count, TINY_JPEG};
+    use crate::report::{ReportJobState, ReportJobStatus, PDF_CONTENT_TYPE};
+    use osint_graph_shared::nodelink::LinkType;
+    use sha2::{Digest, Sha256};
+
+    let mut appstate = AppState::test().await;
+    appstate.report_sync_max_nodes = 20;
+    let server = setup_test_server_with_state(appstate).await;
+
+    let project = project::Model {
+        description: Some("Who runs example.com (really)?".to_string()),
+        ..new_test_project("Report case 東京")
+    };
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    let person = node::Model {
+        project_id: project.id,
+        node_type: NodeType::Person,
+        display: "Jane Citizen".to_string(),
+        value: "Jane Q Citizen".to_string(),
+        notes: Some("Met at the conference".to_string()),
+        ..Default::default()
+    };
+    let source = node::Model {
+        project_id: project.id,
+        node_type: NodeType::Url,
+        display: "Profile".to_string(),
+        value: "https://example.com/profile".to_string(),
+        ..Default::default()
+    };
+    server
+        .post("/api/v1/nodes")
+        .json(&vec![person.clone(), source.clone()])
+        .await
+        .assert_status_ok();
+    server
+        .post("/api/v1/nodelink")
+        .json(&nodelink::Model {
+            id: Uuid::new_v4(),
+            left: person.id,
+            right: source.id,
+            project_id: project.id,
+            linktype: LinkType::Omni,
+            weight: None,
+            kind: None,
+            valid_from: None,
+            valid_to: None,
+            created_by: None,
+            created: None,
+        })
+        .await
+        .assert_status_ok();
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(TINY_JPEG.to_vec())
+            .file_name("photo.jpg")
+            .mime_type("image/jpeg"),
+    );
+    server
+        .post(&format!("/api/v1/node/{}/attachment", person.id))
+        .multipart(form)
+        .await
+        .assert_status_ok();
+
+    // small projects get their report straight away
+    let res = server
+        .get(&format!("/api/v1/project/{}/export/report.pdf", project.id))
+        .await;
+    res.assert_status_ok();
+    assert_eq!(res.header(CONTENT_TYPE), PDF_CONTENT_TYPE);
+    assert!(res
+        .header(CONTENT_DISPOSITION)
+        .to_str()
+        .expect("ascii header")
+        .ends_with(".report.pdf\""));
+    let pdf = res.as_bytes().to_vec();
+    assert!(pdf.starts_with(b"%PDF-"));
+    // cover, graph, nodes, chronology and appendix
+    let pages = page_count(&pdf);
+    assert!((5..=7).contains(&pages), "{pages} pages");
+    let text = extract_text(&pdf);
+    for expected in [
+        "Report case ??",
+        "Who runs example.com (really)?",
+        "2 nodes, 1 links, 1 attachments",
+        "Graph",
+        "Person (1)",
+        "Jane Citizen [1]",
+        "[1] Met at the conference Sources: https://example.com/profile",
+        "Chronology",
+        "Evidence appendix",
+        "photo.jpg",
+        &format!("SHA-256: {:x}", Sha256::digest(TINY_JPEG)),
+        &format!("page {pages} of {pages}"),
+    ] {
+        assert!(text.contains(expected), "{expected:?} missing from {text}");
+    }
+    assert!(String::from_utf8_lossy(&pdf).contains("/Subtype /Image /Width 40 /Height 30"));
+
+    // long tables carry on over more pages
+    let nodes: Vec<node::Model> = (0..40)
+        .map(|index| node::Model {
+            project_id: project.id,
+            node_type: NodeType::Document,
+            display: format!("Document {index:02}"),
+            value: "lorem ipsum dolor sit amet ".repeat(10),
+            ..Default::default()
+        })
+        .collect();
+    server
+        .post("/api/v1/nodes")
+        .json(&nodes)
+        .await
+        .assert_status_ok();
+
+    // which makes the project big enough for a background job
+    let res = server
+        .get(&format!("/api/v1/project/{}/export/report.pdf", project.id))
+        .await;
+    assert_eq!(res.status_code(), 202);
+    let job: ReportJobStatus = res.json();
+    assert_eq!(job.state, ReportJobState::Running);
+    assert_eq!(job.project_id, project.id);
+    assert_eq!(res.header("location"), job.status_url.as_str());
+
+    let mut pdf = None;
+    for _ in 0..100 {
+        let res = server.get(&job.status_url).await;
+        if res.status_code() == 200 {
+            assert_eq!(res.header(CONTENT_TYPE), PDF_CONTENT_TYPE);
+            pdf = Some(res.as_bytes().to_vec());
+            break;
+        }
+        assert_eq!(res.status_code(), 202);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    let pdf = pdf.expect("report job finished");
+    let pages = page_count(&pdf);
+    assert!(pages > 8, "{pages} pages");
+    let text = extract_text(&pdf);
+    assert!(text.contains("Document (40)"));
+    assert!(text.contains("Document 39"));
+    // the finished report can be fetched again
+    server.get(&job.status_url).await.assert_status_ok();
+
+    server
+        .get(&format!("/api/v1/report-jobs/{}", Uuid::new_v4()))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+    server
+        .get(&format!(
+            "/api/v1/project/{}/export/report.pdf",
+            Uuid::new_v4()
+        ))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+}
+
+#[tokio::test]
+async fn test_api_nodelink_validity() {
+    use crate::entity::nodelink;
+    use chrono::{TimeZone, Utc};
+    use osint_graph_shared::nodelink::LinkType;
+
+    let server = setup_test_server().await;
+    let project = new_test_project("link validity");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    let nodes: Vec<node::Model> = (0..3)
+        .map(|index| node::Model {
+            project_id: project.id,
+            display: format!("node{index}"),
+            ..Default::default()
+        })
+        .collect();
+    server
+        .post("/api/v1/nodes")
+        .json(&nodes)
+        .await
+        .assert_status_ok();
+
+    let year = |year: i32| Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap();
+    let link = |right: usize, valid_from, valid_to| nodelink::Model {
+        id: Uuid::new_v4(),
+        left: nodes[0].id,
+        right: nodes[right].id,
+        project_id: project.id,
+        linktype: LinkType::Directional,
+        weight: None,
+        kind: Some("employed".to_string()),
+        valid_from,
+        valid_to,
+        created_by: None,
+        created: None,
+    };
+    let employed = link(1, Some(year(2019)), Some(year(2022)));
+    let since = link(2, Some(year(2021)), None);
+    let timeless = link(2, None, None);
+    for nodelink in [&employed, &since, &timeless] {
+        let created: nodelink::Model = server.post("/api/v1/nodelink").json(nodelink).await.json();
+        assert!(created.created.is_some());
+        assert_eq!(
+            &nodelink::Model {
+                created: None,
+                ..created
+            },
+            nodelink
+        );
+    }
+
+    // an inverted range is rejected on create and update
+    let inverted = link(1, Some(year(2022)), Some(year(2019)));
+    let res = server
+        .post("/api/v1/nodelink")
+        .json(&inverted)
+        .expect_failure()
+        .await;
+    res.assert_status_bad_request();
+    assert!(res.text().contains("valid_from"));
+    server
+        .put(&format!("/api/v1/nodelink/{}", employed.id))
+        .json(&nodelink::Model {
+            id: employed.id,
+            ..inverted.clone()
+        })
+        .expect_failure()
+        .await
+        .assert_status_bad_request();
+    // a single instant is fine
+    let updated: nodelink::Model = server
+        .put(&format!("/api/v1/nodelink/{}", since.id))
+        .json(&nodelink::Model {
+            valid_to: Some(year(2021)),
+            ..since.clone()
+        })
+        .await
+        .json();
+    assert_eq!(updated.valid_to, Some(year(2021)));
+
+    let active_at = |instant: &str| {
+        let server = &server;
+        let url = format!(
+            "/api/v1/project/{}/nodelinks?active_at={}",
+            project.id,
+            instant.replace('+', "%2B")
+        );
+        async move {
+            let mut ids: Vec<Uuid> = server
+                .get(&url)
+                .await
+                .json::<Vec<nodelink::Model>>()
+                .into_iter()
+                .map(|link| link.id)
+                .collect();
+            ids.sort();
+            ids
+        }
+    };
+    let sorted = |mut ids: Vec<Uuid>| {
+        ids.sort();
+        ids
+    };
+    assert_eq!(
+        active_at("2020-06-01T00:00:00Z").await,
+        sorted(vec![employed.id, timeless.id])
+    );
+    // both ends are inclusive
+    assert_eq!(
+        active_at("2021-01-01T00:00:00+00:00").await,
+        sorted(vec![employed.id, since.id, timeless.id])
+    );
+    assert_eq!(active_at("2018-01-01T00:00:00Z").await, vec![timeless.id]);
+    let all: Vec<nodelink::Model> = server
+        .get(&format!("/api/v1/project/{}/nodelinks", project.id))
+        .await
+        .json();
+    assert_eq!(all.len(), 3);
+    server
+        .get(&format!(
+            "/api/v1/project/{}/nodelinks?active_at=yesterday",
+            project.id
+        ))
+        .expect_failure()
+        .await
+        .assert_status_bad_request();
+
+    // and the range shows up in exports
+    let export: ProjectExport = server
+        .get(&format!("/api/v1/project/{}/export", project.id))
+        .await
+        .json();
+    let exported = export
+        .nodelinks
+        .iter()
+        .find(|link| link.id == employed.id)
+        .expect("link exported");
+    assert_eq!(exported.valid_from, Some(year(2019)));
+    let mermaid = server
+        .get(&format!("/api/v1/project/{}/export/mermaid", project.id))
+        .await
+        .text();
+    assert!(
+        mermaid.contains(": employed 2019-01-01 to 2022-01-01"),
+        "{mermaid}"
+    );
+    assert!(
+        mermaid.contains(": employed 2021-01-01 to 2021-01-01"),
+        "{mermaid}"
+    );
+    assert!(mermaid.contains(": employed\n"), "{mermaid}");
+}
+
+#[tokio::test]
+async fn test_api_storage_gc() {
+    use crate::entity::nodelink;
+    use crate::storage_gc::{OrphanReport, StorageGcReport};
+    use osint_graph_shared::nodelink::LinkType;
+    use sea_orm::{ConnectionTrait, EntityTrait};
+
+    let appstate = AppState::test().await;
+    let conn = appstate.conn.clone();
+    let server = setup_test_server_with_state(appstate).await;
+
+    // random bytes so compression can't hide how much space they take
+    let mut seed: u32 = 0x1234_5678;
+    let noise: Vec<u8> = (0..256 * 1024)
+        .map(|_| {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (seed >> 24) as u8
+        })
+        .collect();
+    let add_project = |name: &'static str| {
+        let server = &server;
+        let noise = noise.clone();
+        async move {
+            let project = new_test_project(name);
+            server
+                .post("/api/v1/project")
+                .json(&project)
+                .await
+                .assert_status_ok();
+            let nodes: Vec<node::Model> = (0..2)
+                .map(|index| node::Model {
+                    project_id: project.id,
+                    display: format!("node {index}"),
+                    ..Default::default()
+                })
+                .collect();
+            server
+                .post("/api/v1/nodes")
+                .json(&nodes)
+                .await
+                .assert_status_ok();
+            server
+                .post("/api/v1/nodelink")
+                .json(&nodelink::Model {
+                    id: Uuid::new_v4(),
+                    left: nodes[0].id,
+                    right: nodes[1].id,
+                    project_id: project.id,
+                    linktype: LinkType::Omni,
+                    weight: None,
+                    kind: None,
+                    valid_from: None,
+                    valid_to: None,
+                    created_by: None,
+                    created: None,
+                })
+                .await
+                .assert_status_ok();
+            let form = axum_test::multipart::MultipartForm::new().add_part(
+                "file",
+                axum_test::multipart::Part::bytes(noise)
+                    .file_name("noise.bin")
+                    .mime_type("application/octet-stream"),
+            );
+            server
+                .post(&format!("/api/v1/node/{}/attachment", nodes[0].id))
+                .multipart(form)
+                .await
+                .assert_status_ok();
+            (project, nodes)
+        }
+    };
+    let orphans = || async {
+        let res = server.get("/api/v1/admin/storage/orphans").await;
+        res.assert_status_ok();
+        res.json::<OrphanReport>()
+    };
+
+    // deleting through the API cascades
+    let (deleted, _) = add_project("deleted").await;
+    assert!(orphans().await.orphans.is_empty());
+    server
+        .delete(&format!("/api/v1/project/{}", deleted.id))
+        .await
+        .assert_status_ok();
+    assert!(orphans().await.orphans.is_empty());
+
+    // deleting behind the database's back doesn't
+    let (_, nodes) = add_project("orphaned").await;
+    conn.execute_unprepared("PRAGMA foreign_keys = OFF")
+        .await
+        .expect("Failed to turn off foreign keys");
+    node::Entity::delete_by_id(nodes[0].id)
+        .exec(&conn)
+        .await
+        .expect("Failed to delete node");
+    conn.execute_unprepared("PRAGMA foreign_keys = ON")
+        .await
+        .expect("Failed to turn on foreign keys");
+
+    let report = orphans().await;
+    assert_eq!(report.orphans.attachments.len(), 1);
+    assert!(report.orphans.attachment_bytes >= noise.len() as u64 / 2);
+    assert_eq!(report.orphans.nodelinks.len(), 1);
+    assert!(report.orphans.nodes.is_empty());
+    assert!(report.database.total_bytes > report.orphans.attachment_bytes);
+
+    server
+        .post("/api/v1/admin/storage/gc?vacuum=sideways")
+        .expect_failure()
+        .await
+        .assert_status_bad_request();
+
+    let res = server.post("/api/v1/admin/storage/gc").await;
+    res.assert_status_ok();
+    let gc: StorageGcReport = res.json();
+    assert_eq!(gc.removed.attachments, report.orphans.attachments);
+    assert_eq!(gc.removed.nodelinks, report.orphans.nodelinks);
+    assert!(
+        gc.bytes_freed >= noise.len() as u64 / 2,
+        "only freed {} bytes",
+        gc.bytes_freed
+    );
+    assert_eq!(gc.database.free_bytes, 0);
+    assert!(orphans().await.orphans.is_empty());
+
+    // the other node survived
+    server
+        .get(&format!("/api/v1/node/{}", nodes[1].id))
+        .await
+        .assert_status_ok();
+}
+
+#[tokio::test]
+async fn test_api_idempotency_key() {
+    use crate::entity::{attachment, user};
+    use crate::idempotency::{
+        IDEMPOTENCY_KEY_HEADER, IDEMPOTENCY_KEY_REUSED, IDEMPOTENT_REPLAYED_HEADER,
+        INVALID_IDEMPOTENCY_KEY,
+    };
+    use crate::oauth::middleware::AuthUser;
+    use crate::project::ErrorResponse;
+    use axum::http::HeaderValue;
+    use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, Set};
+
+    let appstate = AppState::test().await;
+    let conn = appstate.conn.clone();
+    let mut users = Vec::new();
+    for name in ["alice", "bob"] {
+        let user = user::ActiveModel {
+            subject: Set(name.to_string()),
+            email: Set(format!("{name}@example.com")),
+            uuid: Set(Uuid::new_v4()),
+            ..Default::default()
+        }
+        .insert(&conn)
+        .await
+        .expect("Failed to create user");
+        users.push(AuthUser::from(user));
+    }
+    let servers = setup_test_servers_as_users(appstate, &users).await;
+    let (alice, bob) = (&servers[0], &servers[1]);
+
+    let project: project::Model = alice
+        .post("/api/v1/project")
+        .json(&new_test_project("Idempotency"))
+        .await
+        .json();
+    let node: node::Model = alice
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            node_type: NodeType::Document,
+            display: "report".to_string(),
+            value: "report".to_string(),
+            ..Default::default()
+        })
+        .await
+        .json();
+
+    // a retry resends exactly the same bytes, boundary and all
+    let upload_body = |content: &str| {
+        format!(
+            "--retry\r\nContent-Disposition: form-data; name=\"file\"; filename=\"report.txt\"\r\nContent-Type: text/plain\r\n\r\n{content}\r\n--retry--\r\n"
+        )
+    };
+    let upload = |key: &'static str, content: &str| {
+        alice
+            .post(&format!("/api/v1/node/{}/attachment", node.id))
+            .add_header(IDEMPOTENCY_KEY_HEADER, HeaderValue::from_static(key))
+            .content_type("multipart/form-data; boundary=retry")
+            .bytes(upload_body(content).into())
+    };
+
+    let first = upload("upload-1", "first draft").await;
+    first.assert_status_ok();
+    assert!(first.maybe_header(IDEMPOTENT_REPLAYED_HEADER).is_none());
+    let retry = upload("upload-1", "first draft").await;
+    retry.assert_status_ok();
+    assert_eq!(retry.header(IDEMPOTENT_REPLAYED_HEADER), "true");
+    assert_eq!(retry.as_bytes(), first.as_bytes());
+    assert_eq!(
+        retry.header(axum::http::header::CONTENT_TYPE),
+        first.header(axum::http::header::CONTENT_TYPE)
+    );
+    let stored = attachment::Entity::find()
+        .filter(attachment::Column::NodeId.eq(node.id))
+        .count(&conn)
+        .await
+        .expect("Failed to count attachments");
+    assert_eq!(stored, 1);
+
+    // the same key with a different body is a client bug, not a retry
+    let res = upload("upload-1", "second draft").expect_failure().await;
+    res.assert_status(axum::http::StatusCode::UNPROCESSABLE_ENTITY);
+    let error: ErrorResponse = res.json();
+    assert_eq!(error.code.as_deref(), Some(IDEMPOTENCY_KEY_REUSED));
+
+    // keys belong to the user who sent them
+    let res = bob
+        .post("/api/v1/project")
+        .add_header(IDEMPOTENCY_KEY_HEADER, HeaderValue::from_static("upload-1"))
+        .json(&new_test_project("Bob's"))
+        .await;
+    res.assert_status_ok();
+    assert!(res.maybe_header(IDEMPOTENT_REPLAYED_HEADER).is_none());
+
+    let res = alice
+        .post("/api/v1/project")
+        .add_header(IDEMPOTENCY_KEY_HEADER, HeaderValue::from_static(" "))
+        .json(&new_test_project("Blank key"))
+        .expect_failure()
+        .await;
+    res.assert_status_bad_request();
+    let error: ErrorResponse = res.json();
+    assert_eq!(error.code.as_deref(), Some(INVALID_IDEMPOTENCY_KEY));
+}
+
+#[tokio::test]
+async fn test_api_contributors() {
+    use crate::contributors::Contributor;
+    use crate::entity::{attachment, nodelink, user};
+    use crate::oauth::middleware::AuthUser;
+    use osint_graph_shared::nodelink::LinkType;
+    use sea_orm::{ActiveModelTrait, IntoActiveModel, Set};
+
+    let appstate = AppState::test().await;
+    let mut users = Vec::new();
+    for name in ["alice", "bob"] {
+        let user = user::ActiveModel {
+            subject: Set(name.to_string()),
+            email: Set(format!("{name}@example.com")),
+            uuid: Set(Uuid::new_v4()),
+            ..Default::default()
+        }
+        .insert(&appstate.conn)
+        .await
+        .expect("Failed to create user");
+        users.push(AuthUser::from(user));
+    }
+    // owned by someone who isn't a user, so both can work on it
+    let project = new_test_project("Shared")
+        .into_active_model()
+        .insert(&appstate.conn)
+        .await
+        .expect("Failed to create project");
+    let servers = setup_test_servers_as_users(appstate, &users).await;
+    let (alice, bob) = (&servers[0], &servers[1]);
+
+    // whatever the client claims is replaced with the caller
+    let alice_node: node::Model = alice
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            display: "Alice's node".to_string(),
+            created_by: Some("mallory".to_string()),
+            ..Default::default()
+        })
+        .await
+        .json();
+    assert_eq!(alice_node.created_by.as_deref(), Some("alice"));
+    assert!(alice_node.created.is_some());
+    let bob_node: node::Model = bob
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            display: "Bob's node".to_string(),
+            ..Default::default()
+        })
+        .await
+        .json();
+    assert_eq!(bob_node.created_by.as_deref(), Some("bob"));
+    let link: nodelink::Model = bob
+        .post("/api/v1/nodelink")
+        .json(&nodelink::Model {
+            id: Uuid::new_v4(),
+            left: alice_node.id,
+            right: bob_node.id,
+            project_id: project.id,
+            linktype: LinkType::Omni,
+            weight: None,
+            kind: None,
+            valid_from: None,
+            valid_to: None,
+            created_by: None,
+            created: None,
+        })
+        .await
+        .json();
+    assert_eq!(link.created_by.as_deref(), Some("bob"));
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(b"evidence".to_vec())
+            .file_name("evidence.txt")
+            .mime_type("text/plain"),
+    );
+    let attachment: attachment::Model = alice
+        .post(&format!("/api/v1/node/{}/attachment", bob_node.id))
+        .multipart(form)
+        .await
+        .json();
+    assert_eq!(attachment.created_by.as_deref(), Some("alice"));
+
+    // updates keep the original reporter
+    let updated: node::Model = bob
+        .put(&format!("/api/v1/node/{}", alice_node.id))
+        .json(&node::Model {
+            notes: Some("Checked by bob".to_string()),
+            created_by: Some("bob".to_string()),
+            ..alice_node.clone()
+        })
+        .await
+        .json();
+    assert_eq!(updated.created_by.as_deref(), Some("alice"));
+    assert_eq!(updated.created, alice_node.created);
+
+    let nodes_by = |server: &TestServer, created_by: &str| {
+        server.get(&format!(
+            "/api/v1/project/{}/nodes?created_by={created_by}",
+            project.id
+        ))
+    };
+    let mine: PaginatedResponse<node::Model> = nodes_by(bob, "me").await.json();
+    assert_eq!(mine.total_count, 1);
+    assert_eq!(mine.items[0].id, bob_node.id);
+    let alices: PaginatedResponse<node::Model> = nodes_by(bob, "alice").await.json();
+    assert_eq!(alices.total_count, 1);
+    assert_eq!(alices.items[0].id, alice_node.id);
+    let nobodys: PaginatedResponse<node::Model> = nodes_by(bob, "carol").await.json();
+    assert_eq!(nobodys.total_count, 0);
+
+    let contributors: Vec<Contributor> = alice
+        .get(&format!("/api/v1/project/{}/contributors", project.id))
+        .await
+        .json();
+    assert_eq!(contributors.len(), 2);
+    let (alice_summary, bob_summary) = (&contributors[0], &contributors[1]);
+    assert_eq!(alice_summary.subject.as_deref(), Some("alice"));
+    assert_eq!(
+        (
+            alice_summary.nodes,
+            alice_summary.nodelinks,
+            alice_summary.attachments
+        ),
+        (1, 0, 1)
+    );
+    assert_eq!(bob_summary.subject.as_deref(), Some("bob"));
+    assert_eq!(
+        (
+            bob_summary.nodes,
+            bob_summary.nodelinks,
+            bob_summary.attachments
+        ),
+        (1, 1, 0)
+    );
+    assert_eq!(alice_summary.first_contribution, alice_node.created);
+    assert!(alice_summary.last_contribution >= alice_summary.first_contribution);
+
+    alice
+        .get(&format!("/api/v1/project/{}/contributors", Uuid::new_v4()))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+}
+
+#[tokio::test]
+async fn test_api_project_stats() {
+    use crate::entity::{attachment, nodelink};
+    use crate::project::ProjectStats;
+    use osint_graph_shared::nodelink::LinkType;
+    use sea_orm::Iterable;
+
+    let server = setup_test_server().await;
+    let project: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("Stats"))
+        .await
+        .json();
+
+    let empty: ProjectStats = server
+        .get(&format!("/api/v1/project/{}/stats", project.id))
+        .await
+        .json();
+    assert_eq!(empty.nodes, 0);
+    assert_eq!(empty.nodes_by_type.get(&NodeType::Email), Some(&0));
+    assert_eq!(
+        empty.nodelinks_by_type.get(&LinkType::Directional),
+        Some(&0)
+    );
+    assert_eq!(empty.attachment_bytes, 0);
+    assert_eq!(empty.last_node_created, None);
+    assert_eq!(empty.last_attachment_created, None);
+
+    let mut nodes: Vec<node::Model> = Vec::new();
+    for node_type in [NodeType::Person, NodeType::Person, NodeType::Email] {
+        nodes.push(
+            server
+                .post("/api/v1/node")
+                .json(&node::Model {
+                    project_id: project.id,
+                    node_type,
+                    ..Default::default()
+                })
+                .await
+                .json(),
+        );
+    }
+    for (left, right, linktype) in [(0, 1, LinkType::Omni), (1, 2, LinkType::Directional)] {
+        server
+            .post("/api/v1/nodelink")
+            .json(&nodelink::Model {
+                id: Uuid::new_v4(),
+                left: nodes[left].id,
+                right: nodes[right].id,
+                project_id: project.id,
+                linktype,
+                weight: None,
+                kind: None,
+                valid_from: None,
+                valid_to: None,
+                created_by: None,
+                created: None,
+            })
+            .await
+            .assert_status_ok();
+    }
+    let mut uploaded: Vec<attachment::Model> = Vec::new();
+    for (index, content) in [b"0123456789".to_vec(), b"abc".to_vec()]
+        .into_iter()
+        .enumerate()
+    {
+        let form = axum_test::multipart::MultipartForm::new().add_part(
+            "file",
+            axum_test::multipart::Part::bytes(content)
+                .file_name(format!("file{index}.txt"))
+                .mime_type("text/plain"),
+        );
+        uploaded.push(
+            server
+                .post(&format!("/api/v1/node/{}/attachment", nodes[index].id))
+                .multipart(form)
+                .await
+                .json(),
+        );
+    }
+
+    let stats: ProjectStats = server
+        .get(&format!("/api/v1/project/{}/stats", project.id))
+        .await
+        .json();
+    assert_eq!(stats.project_id, project.id);
+    assert_eq!(stats.nodes, 3);
+    assert_eq!(stats.nodes_by_type[&NodeType::Person], 2);
+    assert_eq!(stats.nodes_by_type[&NodeType::Email], 1);
+    assert_eq!(stats.nodes_by_type[&NodeType::Domain], 0);
+    assert_eq!(stats.nodes_by_type.len(), NodeType::iter().count());
+    assert_eq!(stats.nodelinks, 2);
+    assert_eq!(stats.nodelinks_by_type[&LinkType::Omni], 1);
+    assert_eq!(stats.nodelinks_by_type[&LinkType::Directional], 1);
+    assert_eq!(stats.attachments, 2);
+    assert_eq!(stats.attachment_bytes, 13);
+    assert_eq!(
+        stats.last_node_created,
+        nodes.iter().filter_map(|node| node.created).max()
+    );
+    assert_eq!(
+        stats.last_attachment_created,
+        uploaded.iter().map(|attachment| attachment.created).max()
+    );
+
+    server
+        .get(&format!("/api/v1/project/{}/stats", Uuid::new_v4()))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+}
+
+#[tokio::test]
+async fn test_api_post_project_conflict() {
+    use crate::project::{ErrorResponse, PROJECT_ID_CONFLICT};
+
+    let server = setup_test_server().await;
+    let project = new_test_project("Original");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+
+    let reused = project::Model {
+        name: "Clobbered".to_string(),
+        ..project.clone()
+    };
+    let res = server
+        .post("/api/v1/project")
+        .json(&reused)
+        .expect_failure()
+        .await;
+    res.assert_status(axum::http::StatusCode::CONFLICT);
+    let error: ErrorResponse = res.json();
+    assert_eq!(error.code.as_deref(), Some(PROJECT_ID_CONFLICT));
+    assert!(error.error.contains("PUT"));
+    let stored: project::Model = server
+        .get(&format!("/api/v1/project/{}", project.id))
+        .await
+        .json();
+    assert_eq!(stored.name, "Original");
+
+    // the old behaviour, for tools which depend on it
+    let updated: project::Model = server
+        .post("/api/v1/project?upsert=true")
+        .json(&reused)
+        .await
+        .json();
+    assert_eq!(updated.id, project.id);
+    assert_eq!(updated.name, "Clobbered");
+    assert!(updated.last_updated.is_some());
+}
+
+#[tokio::test]
+async fn test_api_post_project_upsert_access() {
+    let appstate = AppState::test().await;
+    let users = new_test_users(&appstate.conn, &[("alice", false), ("bob", false)]).await;
+    let servers = setup_test_servers_as_users(appstate, &users).await;
+    let (alice, bob) = (&servers[0], &servers[1]);
+
+    let project: project::Model = bob
+        .post("/api/v1/project")
+        .json(&new_test_project("Bob's case"))
+        .await
+        .json();
+    let clobbered = project::Model {
+        name: "Clobbered".to_string(),
+        description: Some("Not bob's".to_string()),
+        ..project.clone()
+    };
+    alice
+        .post("/api/v1/project?upsert=true")
+        .json(&clobbered)
+        .expect_failure()
+        .await
+        .assert_status_forbidden();
+    let stored: project::Model = bob
+        .get(&format!("/api/v1/project/{}", project.id))
+        .await
+        .json();
+    assert_eq!(stored.name, "Bob's case");
+    assert_eq!(stored.description, None);
+
+    // bob can still upsert his own
+    let updated: project::Model = bob
+        .post("/api/v1/project?upsert=true")
+        .json(&clobbered)
+        .await
+        .json();
+    assert_eq!(updated.name, "Clobbered");
+}
+
+#[tokio::test]
+async fn test_api_build_info() {
+    use crate::status::BuildInfo;
+
+    let server = setup_test_server().await;
+    let res = server.get("/api/v1/build-info").await;
+    assert_eq!(res.header("cache-control"), "no-cache");
+    let raw: serde_json::Value = res.json();
+    assert!(!raw["build_timestamp"]
+        .as_str()
+        .unwrap_or_default()
+        .is_empty());
+
+    let info: BuildInfo = res.json();
+    assert_eq!(info.backend_version, env!("CARGO_PKG_VERSION"));
+    assert!(info.build_timestamp.timestamp() > 0);
+    assert!(info.build_timestamp <= chrono::Utc::now());
+    if let Some(git_sha) = info.git_sha {
+        assert!(git_sha.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+}
+
+#[tokio::test]
+async fn test_api_attachment_range() {
+    use crate::entity::attachment;
+    use axum::http::header::{
+        ACCEPT_ENCODING, ACCEPT_RANGES, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_RANGE,
+        CONTENT_TYPE, IF_RANGE, RANGE,
+    };
+    use axum::http::StatusCode;
+
+    let server = setup_test_server().await;
+    let project: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("Ranges"))
+        .await
+        .json();
+    let node: node::Model = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            node_type: NodeType::Document,
+            ..Default::default()
+        })
+        .await
+        .json();
+    // bigger than a few stream chunks
+    let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(content.clone())
+            .file_name("capture.bin")
+            .mime_type("application/octet-stream"),
+    );
+    let uploaded: attachment::Model = server
+        .post(&format!("/api/v1/node/{}/attachment", node.id))
+        .multipart(form)
+        .await
+        .json();
+    let url = format!("/api/v1/attachment/{}", uploaded.id);
+
+    let res = server.get(&url).await;
+    res.assert_status_ok();
+    assert_eq!(res.header(ACCEPT_RANGES), "bytes");
+    assert_eq!(res.as_bytes().as_ref(), content.as_slice());
+
+    // ranges are of the decoded file, even when the client would take gzip
+    let res = server
+        .get(&url)
+        .add_header(RANGE, "bytes=150000-150009")
+        .add_header(ACCEPT_ENCODING, "gzip")
+        .await;
+    res.assert_status(StatusCode::PARTIAL_CONTENT);
+    assert_eq!(res.header(CONTENT_RANGE), "bytes 150000-150009/200000");
+    assert_eq!(res.header(CONTENT_LENGTH), "10");
+    assert_eq!(res.header(CONTENT_TYPE), "application/octet-stream");
+    assert!(res
+        .header("content-disposition")
+        .to_str()
+        .unwrap()
+        .contains("capture.bin"));
+    assert!(res.maybe_header(CONTENT_ENCODING).is_none());
+    assert_eq!(res.as_bytes().as_ref(), &content[150000..150010]);
+
+    // resuming, and the last few bytes
+    let res = server.get(&url).add_header(RANGE, "bytes=199990-").await;
+    res.assert_status(StatusCode::PARTIAL_CONTENT);
+    assert_eq!(res.as_bytes().as_ref(), &content[199990..]);
+    let res = server.get(&url).add_header(RANGE, "bytes=-5").await;
+    assert_eq!(res.header(CONTENT_RANGE), "bytes 199995-199999/200000");
+    assert_eq!(res.as_bytes().as_ref(), &content[199995..]);
+
+    let res = server
+        .get(&url)
+        .add_header(RANGE, "bytes=200000-")
+        .expect_failure()
+        .await;
+    res.assert_status(StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(res.header(CONTENT_RANGE), "bytes */200000");
+
+    // multiple ranges and a stale If-Range get the whole file
+    let res = server.get(&url).add_header(RANGE, "bytes=0-1,5-6").await;
+    res.assert_status_ok();
+    assert_eq!(res.as_bytes().len(), content.len());
+    let res = server
+        .get(&url)
+        .add_header(RANGE, "bytes=0-9")
+        .add_header(IF_RANGE, "\"stale\"")
+        .await;
+    res.assert_status_ok();
+    let etag = format!("\"{}\"", uploaded.sha256.expect("Upload wasn't hashed"));
+    let res = server
+        .get(&url)
+        .add_header(RANGE, "bytes=0-9")
+        .add_header(IF_RANGE, etag.as_str())
+        .await;
+    res.assert_status(StatusCode::PARTIAL_CONTENT);
+    assert_eq!(res.as_bytes().as_ref(), &content[..10]);
+}
+
+#[tokio::test]
+async fn test_api_metrics() {
+    use axum::http::header::{AUTHORIZATION, CONTENT_TYPE};
+    use axum::http::StatusCode;
+
+    let server = setup_test_server().await;
+    let project = new_test_project("metrics");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    server
+        .get(&format!("/api/v1/project/{}", project.id))
+        .await
+        .assert_status_ok();
+    server
+        .get(&format!("/api/v1/project/{}", Uuid::new_v4()))
+        .expect_failure()
+        .await;
+
+    let res = server.get("/metrics").await;
+    res.assert_status_ok();
+    assert_eq!(
+        res.header(CONTENT_TYPE),
+        crate::metrics::METRICS_CONTENT_TYPE
+    );
+    let body = res.text();
+    // labelled by route, not by project ID
+    assert!(body.contains(
+        "osint_graph_requests_total{method=\"GET\",path=\"/api/v1/project/{id}\",status=\"200\"} 1\n"
+    ));
+    assert!(body.contains(
+        "osint_graph_requests_total{method=\"GET\",path=\"/api/v1/project/{id}\",status=\"404\"} 1\n"
+    ));
+    assert!(!body.contains(&project.id.to_string()));
+    assert!(body.contains(
+        "osint_graph_request_duration_seconds_count{method=\"GET\",path=\"/api/v1/project/{id}\"} 2\n"
+    ));
+    assert!(body.contains("osint_graph_db_query_duration_seconds_bucket{le=\"+Inf\"}"));
+    assert!(!body.contains("osint_graph_db_query_duration_seconds_count{} 0\n"));
+    // and the Inbox
+    assert!(body.contains("osint_graph_projects_total 2\n"));
+    assert!(body.contains("osint_graph_nodes_total 0\n"));
+    assert!(body.contains("osint_graph_attachments_total 0\n"));
+    assert!(body.contains("osint_graph_active_sessions_total "));
+
+    let mut appstate = AppState::test().await;
+    appstate.metrics_token = Some("scrape-me".to_string());
+    let server = setup_test_server_with_state(appstate).await;
+    server
+        .get("/metrics")
+        .expect_failure()
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+    server
+        .get("/metrics")
+        .add_header(AUTHORIZATION, "Bearer wrong")
+        .expect_failure()
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+    server
+        .get("/metrics")
+        .add_header(AUTHORIZATION, "Bearer scrape-me")
+        .await
+        .assert_status_ok();
+}
+
+#[tokio::test]
+async fn test_api_export_push() {
+    use crate::entity::export_record;
+    use crate::export_push::{ExportPushStatus, S3Config, S3Credentials, EXPORT_PUSH_UNAVAILABLE};
+    use crate::project::ErrorResponse;
+    use axum::body::Bytes;
+    use axum::http::{HeaderMap, Method, StatusCode, Uri};
+    use std::sync::Mutex;
+
+    type Received = Arc<Mutex<Vec<(Method, String, HeaderMap, Bytes)>>>;
+
+    // fails the first upload so the push has to retry
+    async fn fake_s3(
+        axum::extract::State(received): axum::extract::State<Received>,
+        method: Method,
+        uri: Uri,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> axum::response::Response {
+        use axum::response::IntoResponse;
+        let mut received = received.lock().unwrap();
+        received.push((method, uri.path().to_string(), headers, body));
+        if received.len() == 1 {
+            return (StatusCode::INTERNAL_SERVER_ERROR, "<Error>SlowDown</Error>").into_response();
+        }
+        (StatusCode::OK, [("etag", "\"0123abcd\"")]).into_response()
+    }
+
+    let received: Received = Arc::default();
+    let s3 = axum::Router::new()
+        .fallback(fake_s3)
+        .with_state(received.clone());
+    let s3_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let s3_addr = s3_listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(s3_listener, s3).await });
+
+    // no credentials, no pushing
+    let server = setup_test_server().await;
+    let project = new_test_project("pushed");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    let push = serde_json::json!({
+        "format": "graphml",
+        "destination": {"kind": "s3", "bucket": "evidence", "prefix": "cases/"},
+    });
+    let res = server
+        .post(&format!("/api/v1/project/{}/export/push", project.id))
+        .json(&push)
+        .expect_failure()
+        .await;
+    res.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(
+        res.json::<ErrorResponse>().code.as_deref(),
+        Some(EXPORT_PUSH_UNAVAILABLE)
+    );
+
+    let mut appstate = AppState::test().await;
+    appstate.s3 = S3Config {
+        endpoint: Some(url::Url::parse(&format!("http://{s3_addr}")).unwrap()),
+        region: "us-east-1".to_string(),
+        credentials: Some(S3Credentials {
+            access_key_id: "AKIDTEST".to_string(),
+            secret_access_key: "secret".to_string(),
+            session_token: None,
+        }),
+    };
+    let server = setup_test_server_with_state(appstate).await;
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    for display in ["left", "right"] {
+        server
+            .post("/api/v1/node")
+            .json(&node::Model {
+                project_id: project.id,
+                display: display.to_string(),
+                value: display.to_string(),
+                ..Default::default()
+            })
+            .await
+            .assert_status_ok();
+    }
+
+    server
+        .post(&format!("/api/v1/project/{}/export/push", project.id))
+        .json(&serde_json::json!({
+            "format": "graphml",
+            "destination": {"kind": "s3", "bucket": "Not A Bucket"},
+        }))
+        .expect_failure()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+
+    let res = server
+        .post(&format!("/api/v1/project/{}/export/push", project.id))
+        .json(&push)
+        .await;
+    res.assert_status(StatusCode::ACCEPTED);
+    let started: export_record::Model = res.json();
+    assert_eq!(started.status, ExportPushStatus::Pending);
+    assert!(started
+        .object_key
+        .starts_with(&format!("cases/{}/", project.id)));
+    assert!(started.object_key.ends_with(".graphml"));
+
+    let mut record = started.clone();
+    for _ in 0..50 {
+        let records: Vec<export_record::Model> = server
+            .get(&format!("/api/v1/project/{}/export-records", project.id))
+            .await
+            .json();
+        assert_eq!(records.len(), 1);
+        record = records.into_iter().next().unwrap();
+        if record.status != ExportPushStatus::Pending {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(record.status, ExportPushStatus::Uploaded, "{record:?}");
+    assert_eq!(record.attempts, 2);
+    assert_eq!(record.etag.as_deref(), Some("0123abcd"));
+    assert!(record.error.is_none());
+    assert!(record.finished.is_some());
+
+    let direct = server
+        .get(&format!("/api/v1/project/{}/export/graphml", project.id))
+        .await;
+    direct.assert_status_ok();
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 2);
+    let (method, path, headers, body) = &received[1];
+    assert_eq!(method, Method::PUT);
+    assert_eq!(path, &format!("/evidence/{}", record.object_key));
+    assert_eq!(body.as_ref(), direct.as_bytes().as_ref());
+    assert_eq!(record.size, Some(body.len() as i64));
+    let authorization = headers["authorization"].to_str().unwrap();
+    assert!(authorization.starts_with("AWS4-HMAC-SHA256 Credential=AKIDTEST/"));
+    assert!(authorization.contains("SignedHeaders=content-type;host;x-amz-checksum-sha256;"));
+    assert!(headers.contains_key("x-amz-checksum-sha256"));
+}
+
+#[tokio::test]
+async fn test_api_identify_urls() {
+    use crate::identifier::{IdentifiedUrl, MAX_IDENTIFY_URLS};
+    use axum::http::StatusCode;
+
+    let server = setup_test_server().await;
+    let res = server
+        .post("/api/v1/identify")
+        .json(&serde_json::json!({"urls": [
+            "https://www.instagram.com/yaleman13/",
+            "https://old.reddit.com/u/yaleman",
+            "https://example.com/about",
+            "not a url",
+            "https:///",
+        ]}))
+        .await;
+    res.assert_status_ok();
+    let results: Vec<IdentifiedUrl> = res.json();
+    assert_eq!(results.len(), 5);
+
+    assert_eq!(
+        results[0],
+        IdentifiedUrl {
+            url: "https://www.instagram.com/yaleman13/".to_string(),
+            platform: Some("instagram".to_string()),
+            username: Some("yaleman13".to_string()),
+            error: None,
+        }
+    );
+    assert_eq!(results[1].platform.as_deref(), Some("reddit"));
+    assert_eq!(results[1].username.as_deref(), Some("yaleman"));
+    assert_eq!(results[2].platform, None);
+    assert_eq!(results[2].error, None);
+    // bad entries are reported in place, not fatal
+    for result in &results[3..] {
+        assert_eq!(result.platform, None);
+        assert!(result.error.is_some(), "{result:?}");
+    }
+    assert_eq!(results[3].url, "not a url");
+
+    server
+        .post("/api/v1/identify")
+        .json(&serde_json::json!({
+            "urls": vec!["https://example.com"; MAX_IDENTIFY_URLS + 1]
+        }))
+        .expect_failure()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_api_health() {
+    use crate::status::{HealthState, HealthStatus};
+    use axum::http::StatusCode;
+    use sea_orm::TransactionTrait;
+
+    let mut appstate = AppState::test().await;
+    appstate.readiness_timeout = std::time::Duration::from_millis(50);
+    let conn = appstate.conn.clone();
+    let server = setup_test_server_with_state(appstate).await;
+
+    let res = server.get("/api/v1/health").await;
+    res.assert_status_ok();
+    let raw: serde_json::Value = res.json();
+    assert_eq!(raw["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(raw["status"], "ok");
+    let health: HealthStatus = res.json();
+    assert!(health.db_ok);
+    assert!(health.uptime_seconds < 60);
+
+    // holding the only connection makes the probe time out
+    let txn = conn.begin().await.expect("Failed to start transaction");
+    let res = server.get("/api/v1/health").expect_failure().await;
+    res.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+    let health: HealthStatus = res.json();
+    assert_eq!(health.status, HealthState::Degraded);
+    assert!(!health.db_ok);
+    txn.rollback().await.expect("Failed to roll back");
+}
+
+#[tokio::test]
+async fn test_migrate_command() {
+    use crate::migrate::{dry_run, migrate_with_backup, migration_status, table_row_counts};
+    use crate::migration::Migrator;
+    use sea_orm::ConnectionTrait;
+    use sea_orm_migration::MigratorTrait;
+
+    let dir = std::env::temp_dir().join(format!("osint-graph-migrate-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let db_path = dir.join("legacy.sqlite3");
+
+    // a database from before most of the migrations, with a node in the Inbox
+    let conn = crate::storage::connect(Some(&db_path)).await.unwrap();
+    Migrator::up(&conn, Some(4)).await.unwrap();
+    conn.execute_unprepared(
+        "INSERT INTO node (id, project_id, type, display, value, updated) \
+         SELECT '11111111-1111-1111-1111-111111111111', id, 'person', 'Legacy', 'legacy', \
+         '2025-01-01T00:00:00Z' FROM project",
+    )
+    .await
+    .unwrap();
+    let status = migration_status(&conn).await.unwrap();
+    assert_eq!(status.applied.len(), 4);
+    assert_eq!(
+        status.applied.len() + status.pending.len(),
+        Migrator::migrations().len()
+    );
+    let pending = status.pending.clone();
+    conn.close().await.unwrap();
+
+    // dry run migrates a copy and leaves the original alone
+    let report = dry_run(&db_path).await.unwrap();
+    assert!(report.error.is_none(), "{:?}", report.error);
+    assert_eq!(report.pending, pending);
+    assert_eq!(report.rows_before.get("node"), Some(&1));
+    assert_eq!(report.rows_after.get("node"), Some(&1));
+    assert_eq!(report.rows_after.get("project"), Some(&1));
+    assert!(!report.rows_before.contains_key("export_record"));
+    assert_eq!(report.rows_after.get("export_record"), Some(&0));
+    let conn = crate::storage::connect(Some(&db_path)).await.unwrap();
+    assert_eq!(migration_status(&conn).await.unwrap().pending, pending);
+    conn.close().await.unwrap();
+
+    // no backup, no migration
+    let err = migrate_with_backup(&db_path, Some(&dir.join("missing/dir")))
+        .await
+        .expect_err("backup into a missing directory should fail");
+    assert!(err.starts_with("Not migrating"), "{err}");
+    let conn = crate::storage::connect(Some(&db_path)).await.unwrap();
+    assert_eq!(migration_status(&conn).await.unwrap().pending, pending);
+    conn.close().await.unwrap();
+
+    let report = migrate_with_backup(&db_path, None).await.unwrap();
+    assert_eq!(report.applied, pending);
+    let backup = report.backup.expect("a backup was taken");
+    assert_eq!(backup.parent(), Some(dir.as_path()));
+    let conn = crate::storage::connect(Some(&db_path)).await.unwrap();
+    assert!(migration_status(&conn).await.unwrap().pending.is_empty());
+    assert_eq!(table_row_counts(&conn).await.unwrap().get("node"), Some(&1));
+    conn.close().await.unwrap();
+    // the backup is the database as it was
+    let conn = crate::storage::connect(Some(&backup)).await.unwrap();
+    assert_eq!(migration_status(&conn).await.unwrap().pending, pending);
+    conn.close().await.unwrap();
+
+    let report = migrate_with_backup(&db_path, None).await.unwrap();
+    assert!(report.backup.is_none());
+    assert!(report.applied.is_empty());
+
+    // the server refuses to start on an out of date database without auto-migrate
+    let stale = dir.join("stale.sqlite3");
+    let conn = crate::storage::connect(Some(&stale)).await.unwrap();
+    Migrator::up(&conn, Some(4)).await.unwrap();
+    conn.close().await.unwrap();
+    assert!(crate::storage::new(&stale, false, 1).await.is_err());
+    assert!(crate::storage::new(&stale, true, 1).await.is_ok());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_api_export_archive() {
+    use crate::archive::{HTML_CONTENT_TYPE, MAX_EMBEDDED_ATTACHMENT_BYTES};
+    use crate::entity::nodelink;
+    use crate::pdf::TINY_JPEG;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use osint_graph_shared::nodelink::LinkType;
+
+    let server = setup_test_server().await;
+    let project = project::Model {
+        description: Some("Closed <case>, see **notes**".to_string()),
+        ..new_test_project("Archive case 東京")
+    };
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    let person = node::Model {
+        project_id: project.id,
+        node_type: NodeType::Person,
+        display: "Jane <b>Citizen</b>".to_string(),
+        value: "Jane Q Citizen".to_string(),
+        notes: Some("Met at the conference".to_string()),
+        ..Default::default()
+    };
+    let domain = node::Model {
+        project_id: project.id,
+        node_type: NodeType::Domain,
+        display: "example.com".to_string(),
+        value: "example.com".to_string(),
+        ..Default::default()
+    };
+    server
+        .post("/api/v1/nodes")
+        .json(&vec![person.clone(), domain.clone()])
+        .await
+        .assert_status_ok();
+    server
+        .post("/api/v1/nodelink")
+        .json(&nodelink::Model {
+            id: Uuid::new_v4(),
+            left: person.id,
+            right: domain.id,
+            project_id: project.id,
+            linktype: LinkType::Directional,
+            weight: None,
+            kind: Some("owns".to_string()),
+            valid_from: None,
+            valid_to: None,
+            created_by: None,
+            created: None,
+        })
+        .await
+        .assert_status_ok();
+    for (filename, mime_type, data) in [
+        ("photo.jpg", "image/jpeg", TINY_JPEG.to_vec()),
+        (
+            "dump.bin",
+            "application/octet-stream",
+            vec![7u8; MAX_EMBEDDED_ATTACHMENT_BYTES as usize + 1],
+        ),
+    ] {
+        let form = axum_test::multipart::MultipartForm::new().add_part(
+            "file",
+            axum_test::multipart::Part::bytes(data)
+                .file_name(filename)
+                .mime_type(mime_type),
+        );
+        server
+            .post(&format!("/api/v1/node/{}/attachment", person.id))
+            .multipart(form)
+            .await
+            .assert_status_ok();
+    }
+
+    let res = server
+        .get(&format!("/api/v1/project/{}/export/archive", project.id))
+        .add_query_param("format", "html")
+        .await;
+    res.assert_status_ok();
+    assert_eq!(res.header(CONTENT_TYPE), HTML_CONTENT_TYPE);
+    assert_eq!(
+        res.header(CONTENT_DISPOSITION),
+        "attachment; filename=\"Archive case __.archive.html\""
+    );
+    let html = res.text();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.trim_end().ends_with("</html>"));
+    assert!(html.contains("<pre class=\"mermaid\">\nclassDiagram"));
+    assert!(html.contains("<table id=\"node-table\">"));
+    assert!(html.contains(&format!(
+        "<tr id=\"node-{}\"><td>person</td><td>Jane &lt;b&gt;Citizen&lt;/b&gt;</td>",
+        person.id
+    )));
+    assert!(html.contains("<p>Closed &lt;case&gt;, see <strong>notes</strong></p>"));
+    assert!(html.contains("<td>owns</td>"));
+    // opens standalone: nothing is loaded from anywhere else
+    assert!(!html.contains("<script"));
+    assert!(!html.contains("src=\"http"));
+    assert!(!html.contains("<link"));
+    // small attachments are embedded, big ones only listed
+    assert!(html.contains(&format!(
+        "<img src=\"data:image/jpeg;base64,{}\"",
+        STANDARD.encode(TINY_JPEG)
+    )));
+    assert!(html.contains("<td>dump.bin</td>"));
+    assert!(html.contains("Not embedded"));
+
+    server
+        .get(&format!("/api/v1/project/{}/export/archive", project.id))
+        .add_query_param("format", "warc")
+        .expect_failure()
+        .await
+        .assert_status_bad_request();
+    server
+        .get(&format!(
+            "/api/v1/project/{}/export/archive",
+            Uuid::new_v4()
+        ))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+}
+
+#[tokio::test]
+async fn test_api_render_markdown() {
+    use crate::markdown::{
+        markdown_hash, MarkdownContext, RenderedMarkdown, MARKDOWN_CACHE_CONTROL,
+        MARKDOWN_TOO_LARGE, MAX_MARKDOWN_BYTES,
+    };
+    use crate::project::ErrorResponse;
+    use axum::http::header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH};
+    use serde_json::json;
+
+    let server = setup_test_server().await;
+    let text = "# Findings\n\n![Logo](https://example.com/logo.png) <script>alert(1)</script>";
+
+    let res = server
+        .post("/api/v1/render-markdown")
+        .json(&json!({"text": text, "context": "case_note"}))
+        .await;
+    res.assert_status_ok();
+    assert_eq!(res.header(CACHE_CONTROL), MARKDOWN_CACHE_CONTROL);
+    let rendered: RenderedMarkdown = res.json();
+    assert_eq!(
+        rendered.html,
+        "<h1>Findings</h1>\n<p><img src=\"https://example.com/logo.png\" alt=\"Logo\"> \
+         &lt;script&gt;alert(1)&lt;/script&gt;</p>\n"
+    );
+    assert_eq!(
+        rendered.hash,
+        markdown_hash(text, MarkdownContext::CaseNote)
+    );
+    let etag = res.header(ETAG);
+    assert_eq!(etag, format!("\"{}\"", rendered.hash).as_str());
+
+    // the same text renders differently, and is cached separately, in a comment
+    let comment: RenderedMarkdown = server
+        .post("/api/v1/render-markdown")
+        .json(&json!({"text": text, "context": "comment"}))
+        .await
+        .json();
+    assert_ne!(comment.hash, rendered.hash);
+    assert!(comment.html.starts_with("<p><strong>Findings</strong></p>"));
+    assert!(!comment.html.contains("<img"));
+
+    let res = server
+        .post("/api/v1/render-markdown")
+        .add_header(IF_NONE_MATCH, etag)
+        .json(&json!({"text": text, "context": "case_note"}))
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 304);
+    assert!(res.as_bytes().is_empty());
+
+    let res = server
+        .post("/api/v1/render-markdown")
+        .json(&json!({"text": "a".repeat(MAX_MARKDOWN_BYTES + 1), "context": "node_note"}))
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 413);
+    let body: ErrorResponse = res.json();
+    assert_eq!(body.code.as_deref(), Some(MARKDOWN_TOO_LARGE));
+
+    server
+        .post("/api/v1/render-markdown")
+        .json(&json!({"text": "hi", "context": "tweet"}))
+        .expect_failure()
+        .await
+        .assert_status_unprocessable_entity();
+}
+
+#[tokio::test]
+async fn test_api_attachments_by_hash() {
+    use crate::attachment_dedup::content_hash;
+    use crate::entity::attachment;
+
+    let server = setup_test_server().await;
+    let mut nodes: Vec<node::Model> = Vec::new();
+    for name in ["Hash lookup one", "Hash lookup two"] {
+        let project: project::Model = server
+            .post("/api/v1/project")
+            .json(&new_test_project(name))
+            .await
+            .json();
+        nodes.push(
+            server
+                .post("/api/v1/node")
+                .json(&node::Model {
+                    project_id: project.id,
+                    node_type: NodeType::Image,
+                    display: "screenshot".to_string(),
+                    value: "screenshot".to_string(),
+                    ..Default::default()
+                })
+                .await
+                .json(),
+        );
+    }
+    let upload = |node_id: Uuid, filename: &'static str, data: &'static [u8]| {
+        let form = axum_test::multipart::MultipartForm::new().add_part(
+            "file",
+            axum_test::multipart::Part::bytes(data.to_vec())
+                .file_name(filename)
+                .mime_type("image/png"),
+        );
+        server
+            .post(&format!("/api/v1/node/{}/attachment", node_id))
+            .multipart(form)
+    };
+    let first: attachment::Model = upload(nodes[0].id, "shot.png", b"screenshot bytes")
+        .await
+        .json();
+    let renamed: attachment::Model = upload(nodes[0].id, "shot (1).png", b"screenshot bytes")
+        .await
+        .json();
+    assert_eq!(renamed.id, first.id);
+    let second: attachment::Model = upload(nodes[1].id, "other.png", b"screenshot bytes")
+        .await
+        .json();
+    upload(nodes[1].id, "different.png", b"different bytes")
+        .await
+        .assert_status_ok();
+
+    // the hash is of the content, so it's the same every time
+    let sha256 = content_hash(b"screenshot bytes");
+    assert_eq!(first.sha256.as_deref(), Some(sha256.as_str()));
+    assert_eq!(second.sha256.as_deref(), Some(sha256.as_str()));
+
+    for hash in [sha256.clone(), sha256.to_uppercase()] {
+        let found: Vec<attachment::Model> = server
+            .get(&format!("/api/v1/attachment/by-hash/{hash}"))
+            .await
+            .json();
+        assert_eq!(
+            found.iter().map(|a| a.id).collect::<Vec<_>>(),
+            vec![first.id, second.id]
+        );
+        assert!(found.iter().all(|a| a.data.is_empty()));
+    }
+
+    let none: Vec<attachment::Model> = server
+        .get(&format!(
+            "/api/v1/attachment/by-hash/{}",
+            content_hash(b"never uploaded")
+        ))
+        .await
+        .json();
+    assert!(none.is_empty());
+    server
+        .get("/api/v1/attachment/by-hash/not-a-hash")
+        .expect_failure()
+        .await
+        .assert_status_bad_request();
+}
+
+#[tokio::test]
+async fn test_api_project_tags() {
+    use crate::project::{SearchResult, INVALID_PROJECT_TAGS};
+    use osint_graph_shared::{MAX_TAGS, MAX_TAG_CHARS};
+    use serde_json::json;
+
+    let server = setup_test_server().await;
+    let mut tagged = new_test_project("Tag search");
+    tagged.tags = StringVec(vec![
+        " abc ".to_string(),
+        "ABC".to_string(),
+        "case-1".to_string(),
+    ]);
+    let tagged: project::Model = server.post("/api/v1/project").json(&tagged).await.json();
+    // trimmed and deduplicated on the way in
+    assert_eq!(
+        tagged.tags,
+        StringVec(vec!["abc".to_string(), "case-1".to_string()])
+    );
+
+    let search = |q: &'static str| {
+        let server = &server;
+        async move {
+            server
+                .get("/api/v1/search")
+                .add_query_param("q", q)
+                .await
+                .json::<Vec<SearchResult>>()
+                .into_iter()
+                .filter(|result| result.project_id == tagged.id)
+                .count()
+        }
+    };
+    assert_eq!(search("abc").await, 1);
+    assert_eq!(search("ABC").await, 1);
+    assert_eq!(search("ab").await, 0);
+    // the raw JSON isn't searched
+    assert_eq!(search("\"abc\"").await, 0);
+    assert_eq!(search("c\",\"ca").await, 0);
+    // names still match as substrings
+    assert_eq!(search("g sea").await, 1);
+
+    for tags in [
+        json!(["ok", ""]),
+        json!(["a".repeat(MAX_TAG_CHARS + 1)]),
+        json!((0..=MAX_TAGS).map(|i| i.to_string()).collect::<Vec<_>>()),
+    ] {
+        let mut body = serde_json::to_value(new_test_project("Bad tags")).unwrap();
+        body["tags"] = tags;
+        let err: crate::project::ErrorResponse = server
+            .post("/api/v1/project")
+            .json(&body)
+            .expect_failure()
+            .await
+            .json();
+        assert_eq!(err.code.as_deref(), Some(INVALID_PROJECT_TAGS));
+        server
+            .put(&format!("/api/v1/project/{}", tagged.id))
+            .json(&body)
+            .expect_failure()
+            .await
+            .assert_status_bad_request();
+    }
+
+    // nested arrays and non-strings aren't tags at all
+    for tags in [json!([["abc"]]), json!([1, 2]), json!({"tag": "abc"})] {
+        let mut body = serde_json::to_value(new_test_project("Bad tags")).unwrap();
+        body["tags"] = tags;
+        server
+            .post("/api/v1/project")
+            .json(&body)
+            .expect_failure()
+            .await
+            .assert_status_unprocessable_entity();
+    }
+    let unchanged: project::Model = server
+        .get(&format!("/api/v1/project/{}", tagged.id))
+        .await
+        .json();
+    assert_eq!(unchanged.tags, tagged.tags);
+}
+
+#[tokio::test]
+async fn test_api_attachment_update_fields() {
+    use crate::attachment_dedup::content_hash;
+    use crate::entity::attachment;
+    use serde_json::json;
+
+    let server = setup_test_server().await;
+    let project = new_test_project("Attachment update fields");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    let mut nodes: Vec<node::Model> = Vec::new();
+    for display in ["first", "second"] {
+        nodes.push(
+            server
+                .post("/api/v1/node")
+                .json(&node::Model {
+                    project_id: project.id,
+                    node_type: NodeType::Document,
+                    display: display.to_string(),
+                    value: display.to_string(),
+                    ..Default::default()
+                })
+                .await
+                .json(),
+        );
+    }
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(b"dragged and dropped".to_vec())
+            .file_name("upload.bin")
+            .mime_type("application/octet-stream"),
+    );
+    let uploaded: attachment::Model = server
+        .post(&format!("/api/v1/node/{}/attachment", nodes[0].id))
+        .multipart(form)
+        .await
+        .json();
+    let url = format!("/api/v1/attachment/{}", uploaded.id);
+
+    let renamed: attachment::Model = server
+        .patch(&url)
+        .json(&json!({"filename": "notes.txt"}))
+        .await
+        .json();
+    assert_eq!(renamed.filename, "notes.txt");
+    assert_eq!(renamed.content_type, "application/octet-stream");
+
+    let retyped: attachment::Model = server
+        .patch(&url)
+        .json(&json!({"content_type": "text/plain"}))
+        .await
+        .json();
+    assert_eq!(retyped.filename, "notes.txt");
+    assert_eq!(retyped.content_type, "text/plain");
+
+    // replacing the data keeps the name and type but not the old size or hash
+    let replacement = b"a much longer replacement for the original text".to_vec();
+    let replaced: attachment::Model = server
+        .patch(&url)
+        .json(&json!({"data": replacement}))
+        .await
+        .json();
+    assert_eq!(replaced.filename, "notes.txt");
+    assert_eq!(replaced.content_type, "text/plain");
+    assert_eq!(replaced.size, replacement.len() as i64);
+    assert_eq!(
+        replaced.sha256.as_deref(),
+        Some(content_hash(&replacement).as_str())
+    );
+    let listed: Vec<attachment::Model> = server
+        .get(&format!("/api/v1/node/{}/attachments", nodes[0].id))
+        .await
+        .json();
+    assert_eq!(listed[0].size, replacement.len() as i64);
+    assert_eq!(server.get(&url).await.as_bytes().as_ref(), replacement);
+
+    let moved: attachment::Model = server
+        .patch(&url)
+        .json(&json!({"node_id": nodes[1].id}))
+        .await
+        .json();
+    assert_eq!(moved.node_id, nodes[1].id);
+    assert_eq!(moved.filename, "notes.txt");
+
+    // moving to a node which doesn't exist leaves the attachment where it was
+    server
+        .patch(&url)
+        .json(&json!({"node_id": Uuid::new_v4(), "filename": "lost.txt"}))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+    let listed: Vec<attachment::Model> = server
+        .get(&format!("/api/v1/node/{}/attachments", nodes[1].id))
+        .await
+        .json();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].filename, "notes.txt");
+}
+
+#[tokio::test]
+async fn test_api_copy_node() {
+    use crate::entity::attachment;
+
+    let server = setup_test_server().await;
+    let source_project = new_test_project("Copy source");
+    let target_project = new_test_project("Copy target");
+    for project in [&source_project, &target_project] {
+        server
+            .post("/api/v1/project")
+            .json(project)
+            .await
+            .assert_status_ok();
+    }
+    let original: node::Model = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: source_project.id,
+            node_type: NodeType::Email,
+            display: "Suspect".to_string(),
+            value: "suspect@example.com".to_string(),
+            notes: Some("seen twice".to_string()),
+            pos_x: Some(10),
+            pos_y: Some(20),
+            ..Default::default()
+        })
+        .await
+        .json();
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(b"email headers".to_vec())
+            .file_name("headers.txt")
+            .mime_type("text/plain"),
+    );
+    let original_attachment: attachment::Model = server
+        .post(&format!("/api/v1/node/{}/attachment", original.id))
+        .multipart(form)
+        .await
+        .json();
+    let url = format!("/api/v1/node/{}/copy", original.id);
+
+    // same project by default, with a new ID and without attachments
+    let same: node::Model = server.post(&url).await.json();
+    assert_ne!(same.id, original.id);
+    assert_eq!(same.project_id, source_project.id);
+    assert_eq!(same.value, original.value);
+    assert_eq!(same.notes, original.notes);
+    assert_eq!((same.pos_x, same.pos_y), (original.pos_x, original.pos_y));
+    assert!(same.updated > original.updated);
+    let attachments: Vec<attachment::Model> = server
+        .get(&format!("/api/v1/node/{}/attachments", same.id))
+        .await
+        .json();
+    assert!(attachments.is_empty());
+
+    let other: node::Model = server
+        .post(&url)
+        .add_query_param("project_id", target_project.id)
+        .await
+        .json();
+    assert_eq!(other.project_id, target_project.id);
+    assert_eq!(other.display, original.display);
+    let target_nodes: PaginatedResponse<node::Model> = server
+        .get(&format!("/api/v1/project/{}/nodes", target_project.id))
+        .await
+        .json();
+    assert_eq!(target_nodes.items.len(), 1);
+
+    let with_attachments: node::Model = server
+        .post(&url)
+        .add_query_param("project_id", target_project.id)
+        .add_query_param("include_attachments", true)
+        .await
+        .json();
+    let copied: Vec<attachment::Model> = server
+        .get(&format!("/api/v1/node/{}/attachments", with_attachments.id))
+        .await
+        .json();
+    assert_eq!(copied.len(), 1);
+    assert_ne!(copied[0].id, original_attachment.id);
+    assert_eq!(copied[0].filename, "headers.txt");
+    assert_eq!(copied[0].sha256, original_attachment.sha256);
+    let res = server
+        .get(&format!("/api/v1/attachment/{}", copied[0].id))
+        .await;
+    assert_eq!(res.as_bytes().as_ref(), b"email headers");
+    // the original still has its attachment
+    let kept: Vec<attachment::Model> = server
+        .get(&format!("/api/v1/node/{}/attachments", original.id))
+        .await
+        .json();
+    assert_eq!(kept.len(), 1);
+
+    server
+        .post(&url)
+        .add_query_param("project_id", Uuid::new_v4())
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+    server
+        .post(&format!("/api/v1/node/{}/copy", Uuid::new_v4()))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+}
+
+#[tokio::test]
+async fn test_api_db_health() {
+    use crate::db_health::DbHealthReport;
+
+    let server = setup_test_server().await;
+    let project = new_test_project("Database health");
+    server
+        .post("/api/v1/project")
+        .json(&project)
+        .await
+        .assert_status_ok();
+    // creating a node runs in a transaction, so waits for a connection
+    server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            display: "health".to_string(),
+            value: "health".to_string(),
+            ..Default::default()
+        })
+        .await
+        .assert_status_ok();
+
+    let report: DbHealthReport = server.get("/api/v1/admin/db-health").await.json();
+    // the in-memory test database has a single connection
+    assert_eq!(report.pool.max_connections, 1);
+    assert!(report.acquires >= 1);
+    assert!(report.acquire_p95_ms.is_some());
+    assert_eq!(report.busy_errors, 0);
+    assert_eq!(report.wal_bytes, None);
+    assert!(report.database_bytes > 0);
+
+    let metrics = server.get("/metrics").await.text();
+    for series in [
+        "osint_graph_db_acquire_wait_seconds_count",
+        "osint_graph_db_busy_errors_total 0",
+        "osint_graph_db_pool_connections{state=\"idle\"}",
+        "osint_graph_db_pool_max_connections 1",
+    ] {
+        assert!(metrics.contains(series), "{series} missing from {metrics}");
+    }
+}
+
+#[tokio::test]
+async fn test_api_update_nodelink_type() {
+    use crate::entity::nodelink;
+    use osint_graph_shared::nodelink::LinkType;
+
+    let server = setup_test_server().await;
+    let project = new_test_project("Nodelink type");
+    let other_project = new_test_project("Nodelink other project");
+    for project in [&project, &other_project] {
+        server
+            .post("/api/v1/project")
+            .json(project)
+            .await
+            .assert_status_ok();
+    }
+    let mut nodes = Vec::new();
+    for (project_id, display) in [
+        (project.id, "a"),
+        (project.id, "b"),
+        (project.id, "c"),
+        (other_project.id, "elsewhere"),
+    ] {
+        let node: node::Model = server
+            .post("/api/v1/node")
+            .json(&node::Model {
+                project_id,
+                display: display.to_string(),
+                value: display.to_string(),
+                ..Default::default()
+            })
+            .await
+            .json();
+        nodes.push(node);
+    }
+    let created: nodelink::Model = server
+        .post("/api/v1/nodelink")
+        .json(&nodelink::Model {
+            id: Uuid::new_v4(),
+            project_id: project.id,
+            left: nodes[0].id,
+            right: nodes[1].id,
+            linktype: LinkType::Omni,
+            weight: None,
+            kind: None,
+            valid_from: None,
+            valid_to: None,
+            created_by: None,
+            created: None,
+        })
+        .await
+        .json();
+    let url = format!("/api/v1/nodelink/{}", created.id);
+
+    let directional: nodelink::Model = server
+        .put(&url)
+        .json(&nodelink::Model {
+            linktype: LinkType::Directional,
+            ..created.clone()
+        })
+        .await
+        .json();
+    assert_eq!(directional.id, created.id);
+    assert_eq!(directional.linktype, LinkType::Directional);
+
+    let moved: nodelink::Model = server
+        .put(&url)
+        .json(&nodelink::Model {
+            right: nodes[2].id,
+            ..directional.clone()
+        })
+        .await
+        .json();
+    assert_eq!((moved.left, moved.right), (nodes[0].id, nodes[2].id));
+
+    // ends have to be nodes in the link's project
+    for right in [nodes[3].id, Uuid::new_v4()] {
+        server
+            .put(&url)
+            .json(&nodelink::Model {
+                right,
+                linktype: LinkType::Omni,
+                ..moved.clone()
+            })
+            .expect_failure()
+            .await
+            .assert_status_bad_request();
+    }
+    let links: Vec<nodelink::Model> = server
+        .get(&format!("/api/v1/project/{}/nodelinks", project.id))
+        .await
+        .json();
+    assert_eq!(links, vec![moved.clone()]);
+
+    server
+        .put(&format!("/api/v1/nodelink/{}", Uuid::new_v4()))
+        .json(&moved)
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+}
+
+#[tokio::test]
+async fn test_api_project_attachment_summary() {
+    use crate::attachment::NodeAttachmentSummary;
+
+    let server = setup_test_server().await;
+    let mut projects = Vec::new();
+    let mut expected: Vec<Vec<NodeAttachmentSummary>> = Vec::new();
+    for project_index in 0..2 {
+        let project: project::Model = server
+            .post("/api/v1/project")
+            .json(&new_test_project(&format!(
+                "Summary project {project_index}"
+            )))
+            .await
+            .json();
+        let mut summaries = Vec::new();
+        // the last node has no attachments, so it's left out
+        for attachments in [1, 3, 0] {
+            let node: node::Model = server
+                .post("/api/v1/node")
+                .json(&node::Model {
+                    project_id: project.id,
+                    display: format!("Node with {attachments}"),
+                    ..Default::default()
+                })
+                .await
+                .json();
+            let mut total_size = 0;
+            for index in 0..attachments {
+                let data = format!("{project_index} {index} {}", "x".repeat(index * 10));
+                total_size += data.len() as u64;
+                let form = axum_test::multipart::MultipartForm::new().add_part(
+                    "file",
+                    axum_test::multipart::Part::bytes(data.into_bytes())
+                        .file_name(format!("file-{index}.txt"))
+                        .mime_type("text/plain"),
+                );
+                server
+                    .post(&format!("/api/v1/node/{}/attachment", node.id))
+                    .multipart(form)
+                    .await
+                    .assert_status_ok();
+            }
+            if attachments > 0 {
+                summaries.push(NodeAttachmentSummary {
+                    node_id: node.id,
+                    count: attachments as u64,
+                    total_size,
+                });
+            }
+        }
+        summaries.sort_by_key(|summary| summary.node_id);
+        projects.push(project);
+        expected.push(summaries);
+    }
+
+    for (project, expected) in projects.iter().zip(expected) {
+        let summary: Vec<NodeAttachmentSummary> = server
+            .get(&format!(
+                "/api/v1/project/{}/attachment-summary",
+                project.id
+            ))
+            .await
+            .json();
+        assert_eq!(summary, expected);
+    }
+
+    let empty: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("No attachments"))
+        .await
+        .json();
+    let summary: Vec<NodeAttachmentSummary> = server
+        .get(&format!("/api/v1/project/{}/attachment-summary", empty.id))
+        .await
+        .json();
+    assert!(summary.is_empty());
+    server
+        .get(&format!(
+            "/api/v1/project/{}/attachment-summary",
+            Uuid::new_v4()
+        ))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+}
+
+#[tokio::test]
+async fn test_api_project_snapshots() {
+    use crate::project::{ErrorResponse, ProjectExport};
+    use crate::snapshot::{
+        run_due_snapshots, SnapshotDiff, SnapshotInfo, SnapshotRun, SnapshotSettings,
+        INVALID_SNAPSHOT_SETTINGS, SNAPSHOT_PROJECT_MISMATCH,
+    };
+
+    let appstate = AppState::test().await;
+    let conn = appstate.conn.clone();
+    let server = setup_test_server_with_state(appstate).await;
+
+    let project: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("Long running case"))
+        .await
+        .json();
+    let node: node::Model = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            display: "Suspect".to_string(),
+            value: "suspect".to_string(),
+            ..Default::default()
+        })
+        .await
+        .json();
+    let snapshots_url = format!("/api/v1/project/{}/snapshots", project.id);
+    let settings_url = format!("/api/v1/project/{}/snapshot-settings", project.id);
+
+    // off until configured
+    let settings: SnapshotSettings = server.get(&settings_url).await.json();
+    assert_eq!(settings, SnapshotSettings::default());
+    let start = chrono::Utc::now();
+    assert_eq!(
+        run_due_snapshots(&conn, start).await.unwrap(),
+        SnapshotRun::default()
+    );
+
+    for invalid in [
+        serde_json::json!({"interval_secs": 59, "keep": 2}),
+        serde_json::json!({"interval_secs": 3600, "keep": 0}),
+        serde_json::json!({"interval_secs": 3600, "keep": 101}),
+    ] {
+        let res = server
+            .put(&settings_url)
+            .json(&invalid)
+            .expect_failure()
+            .await;
+        assert_eq!(res.status_code(), 400, "{invalid}");
+        let body: ErrorResponse = res.json();
+        assert_eq!(body.code.as_deref(), Some(INVALID_SNAPSHOT_SETTINGS));
+    }
+    let settings: SnapshotSettings = server
+        .put(&settings_url)
+        .json(&serde_json::json!({"interval_secs": 3600, "keep": 2}))
+        .await
+        .json();
+    assert_eq!(settings.interval_secs, Some(3600));
+    assert_eq!(settings.keep, 2);
+
+    // the first run snapshots straight away
+    let run = run_due_snapshots(&conn, start).await.unwrap();
+    assert_eq!(run.created, 1);
+    let first: Vec<SnapshotInfo> = server.get(&snapshots_url).await.json();
+    assert_eq!(first.len(), 1);
+    let first = first[0].clone();
+    let settings: SnapshotSettings = server.get(&settings_url).await.json();
+    assert!(settings.last_run.is_some());
+
+    // not due yet, then due but unchanged
+    let hour = chrono::Duration::hours(1);
+    let run = run_due_snapshots(&conn, start + chrono::Duration::minutes(30))
+        .await
+        .unwrap();
+    assert_eq!(run, SnapshotRun::default());
+    let run = run_due_snapshots(&conn, start + hour).await.unwrap();
+    assert_eq!(run.unchanged, 1);
+    assert_eq!(run.created, 0);
+
+    let snapshot: ProjectExport = server
+        .get(&format!("/api/v1/snapshot/{}", first.id))
+        .await
+        .json();
+    assert_eq!(snapshot.project.id, project.id);
+    assert_eq!(snapshot.nodes.len(), 1);
+
+    server
+        .put(&format!("/api/v1/node/{}", node.id))
+        .json(&node::Model {
+            display: "Suspect (confirmed)".to_string(),
+            ..node.clone()
+        })
+        .await
+        .assert_status_ok();
+    let added: node::Model = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            display: "Associate".to_string(),
+            value: "associate".to_string(),
+            ..Default::default()
+        })
+        .await
+        .json();
+    let run = run_due_snapshots(&conn, start + hour * 2).await.unwrap();
+    assert_eq!(run.created, 1);
+    let snapshots: Vec<SnapshotInfo> = server.get(&snapshots_url).await.json();
+    assert_eq!(snapshots.len(), 2);
+    let second = snapshots[0].clone();
+    assert_ne!(second.fingerprint, first.fingerprint);
+
+    let diff: SnapshotDiff = server
+        .get(&format!("/api/v1/snapshot/{}/diff/{}", first.id, second.id))
+        .await
+        .json();
+    assert_eq!(diff.from.id, first.id);
+    assert_eq!(diff.to.id, second.id);
+    assert_eq!(diff.nodes.added, vec![added.id]);
+    assert_eq!(diff.nodes.changed, vec![node.id]);
+    assert!(diff.nodes.removed.is_empty());
+    assert!(diff.nodelinks.added.is_empty());
+
+    // a third snapshot pushes the first one out
+    server
+        .delete(&format!("/api/v1/node/{}", added.id))
+        .await
+        .assert_status_ok();
+    let run = run_due_snapshots(&conn, start + hour * 3).await.unwrap();
+    assert_eq!(run.created, 1);
+    assert_eq!(run.pruned, 1);
+    let snapshots: Vec<SnapshotInfo> = server.get(&snapshots_url).await.json();
+    assert_eq!(
+        snapshots.iter().map(|s| s.id).collect::<Vec<_>>()[1..],
+        [second.id]
+    );
+    server
+        .get(&format!("/api/v1/snapshot/{}", first.id))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+    let diff: SnapshotDiff = server
+        .get(&format!(
+            "/api/v1/snapshot/{}/diff/{}",
+            second.id, snapshots[0].id
+        ))
+        .await
+        .json();
+    assert_eq!(diff.nodes.removed, vec![added.id]);
+
+    // snapshots of different projects can't be compared
+    let other: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("Other case"))
+        .await
+        .json();
+    server
+        .put(&format!("/api/v1/project/{}/snapshot-settings", other.id))
+        .json(&serde_json::json!({"interval_secs": 3600}))
+        .await
+        .assert_status_ok();
+    run_due_snapshots(&conn, start + hour * 3).await.unwrap();
+    let other_snapshots: Vec<SnapshotInfo> = server
+        .get(&format!("/api/v1/project/{}/snapshots", other.id))
+        .await
+        .json();
+    assert_eq!(other_snapshots.len(), 1);
+    let res = server
+        .get(&format!(
+            "/api/v1/snapshot/{}/diff/{}",
+            second.id, other_snapshots[0].id
+        ))
+        .expect_failure()
+        .await;
+    assert_eq!(res.status_code(), 400);
+    let body: ErrorResponse = res.json();
+    assert_eq!(body.code.as_deref(), Some(SNAPSHOT_PROJECT_MISMATCH));
+}
+
+#[tokio::test]
+async fn test_api_post_nodelink_project_mismatch() {
+    use crate::entity::nodelink;
+    use crate::project::{ErrorResponse, NODELINK_PROJECT_MISMATCH};
+    use osint_graph_shared::nodelink::LinkType;
+
+    let server = setup_test_server().await;
+    let mut projects = Vec::new();
+    let mut nodes = Vec::new();
+    for name in ["Link home", "Link elsewhere"] {
+        let project: project::Model = server
+            .post("/api/v1/project")
+            .json(&new_test_project(name))
+            .await
+            .json();
+        let node: node::Model = server
+            .post("/api/v1/node")
+            .json(&node::Model {
+                project_id: project.id,
+                display: format!("{name} node"),
+                ..Default::default()
+            })
+            .await
+            .json();
+        projects.push(project);
+        nodes.push(node);
+    }
+    let second: node::Model = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: projects[0].id,
+            display: "Second home node".to_string(),
+            ..Default::default()
+        })
+        .await
+        .json();
+    let link = |left: Uuid, right: Uuid| nodelink::Model {
+        id: Uuid::new_v4(),
+        left,
+        right,
+        project_id: projects[0].id,
+        linktype: LinkType::Omni,
+        weight: None,
+        kind: None,
+        valid_from: None,
+        valid_to: None,
+        created_by: None,
+        created: None,
+    };
+
+    let created: nodelink::Model = server
+        .post("/api/v1/nodelink")
+        .json(&link(nodes[0].id, second.id))
+        .await
+        .json();
+    assert_eq!(created.project_id, projects[0].id);
+
+    let missing = Uuid::new_v4();
+    for (left, right, end, node_id) in [
+        (nodes[0].id, nodes[1].id, "right", nodes[1].id),
+        (nodes[1].id, second.id, "left", nodes[1].id),
+        (missing, second.id, "left", missing),
+    ] {
+        let res = server
+            .post("/api/v1/nodelink")
+            .json(&link(left, right))
+            .expect_failure()
+            .await;
+        assert_eq!(res.status_code(), 400);
+        let body: ErrorResponse = res.json();
+        assert_eq!(body.code.as_deref(), Some(NODELINK_PROJECT_MISMATCH));
+        let body: serde_json::Value = res.json();
+        assert_eq!(body["end"], end);
+        assert_eq!(body["node_id"], node_id.to_string());
+    }
+    let links: Vec<nodelink::Model> = server
+        .get(&format!("/api/v1/project/{}/nodelinks", projects[0].id))
+        .await
+        .json();
+    assert_eq!(links.len(), 1);
+}
+
+#[tokio::test]
+async fn test_api_delete_node_without_foreign_keys() {
+    use crate::entity::{attachment, nodelink};
+    use crate::storage_gc::PruneOrphansReport;
+    use osint_graph_shared::nodelink::LinkType;
+    use sea_orm::{
+        ColumnTrait, ConnectionTrait, DbBackend, EntityTrait, PaginatorTrait, QueryFilter,
+        Statement,
+    };
+
+    let appstate = AppState::test().await;
+    let conn = appstate.conn.clone();
+    let server = setup_test_server_with_state(appstate).await;
+
+    let project: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("Foreign keys off"))
+        .await
+        .json();
+    let mut nodes = Vec::new();
+    for display in ["Deleted by the API", "Deleted behind its back"] {
+        let node: node::Model = server
+            .post("/api/v1/node")
+            .json(&node::Model {
+                project_id: project.id,
+                display: display.to_string(),
+                ..Default::default()
+            })
+            .await
+            .json();
+        let form = axum_test::multipart::MultipartForm::new().add_part(
+            "file",
+            axum_test::multipart::Part::bytes(display.as_bytes().to_vec())
+                .file_name("evidence.txt")
+                .mime_type("text/plain"),
+        );
+        server
+            .post(&format!("/api/v1/node/{}/attachment", node.id))
+            .multipart(form)
+            .await
+            .assert_status_ok();
+        nodes.push(node);
+    }
+    server
+        .post("/api/v1/nodelink")
+        .json(&nodelink::Model {
+            id: Uuid::new_v4(),
+            left: nodes[0].id,
+            right: nodes[1].id,
+            project_id: project.id,
+            linktype: LinkType::Omni,
+            weight: None,
+            kind: None,
+            valid_from: None,
+            valid_to: None,
+            created_by: None,
+            created: None,
+        })
+        .await
+        .assert_status_ok();
+
+    conn.execute_unprepared("PRAGMA foreign_keys = OFF")
+        .await
+        .expect("Failed to turn off foreign keys");
+    let attachments_of = |node_id: Uuid| {
+        attachment::Entity::find()
+            .filter(attachment::Column::NodeId.eq(node_id))
+            .count(&conn)
+    };
+
+    // purging deletes attachments itself
+    server
+        .delete(&format!("/api/v1/node/{}?purge=true", nodes[0].id))
+        .await
+        .assert_status_ok();
+    assert_eq!(attachments_of(nodes[0].id).await.unwrap(), 0);
+
+    // raw SQL doesn't, leaving an attachment and the link behind
+    conn.execute(Statement::from_sql_and_values(
+        DbBackend::Sqlite,
+        "DELETE FROM node WHERE id = ?",
+        [nodes[1].id.into()],
+    ))
+    .await
+    .expect("Failed to delete node");
+    conn.execute_unprepared("PRAGMA foreign_keys = ON")
+        .await
+        .expect("Failed to turn on foreign keys");
+    assert_eq!(attachments_of(nodes[1].id).await.unwrap(), 1);
+
+    let report: PruneOrphansReport = server.post("/api/v1/admin/prune-orphans").await.json();
+    assert_eq!(report.attachments, 1);
+    assert!(report.attachment_bytes > 0);
+    assert_eq!(report.nodelinks, 1);
+    assert_eq!(report.nodes, 0);
+    assert_eq!(attachments_of(nodes[1].id).await.unwrap(), 0);
+    assert_eq!(
+        nodelink::Entity::find()
+            .filter(nodelink::Column::ProjectId.eq(project.id))
+            .count(&conn)
+            .await
+            .unwrap(),
+        0
+    );
+
+    let report: PruneOrphansReport = server.post("/api/v1/admin/prune-orphans").await.json();
+    assert_eq!(report.attachments + report.nodes + report.nodelinks, 0);
+}
+
+#[tokio::test]
+async fn test_api_admin_reindex() {
+    use crate::entity::attachment;
+    use crate::reindex::ReindexReport;
+    use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, QueryFilter};
+
+    let appstate = AppState::test().await;
+    let conn = appstate.conn.clone();
+    let server = setup_test_server_with_state(appstate).await;
+
+    let project: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("Reindex"))
+        .await
+        .json();
+    let node: node::Model = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            ..Default::default()
+        })
+        .await
+        .json();
+    let mut uploaded = Vec::new();
+    for index in 0..3 {
+        let form = axum_test::multipart::MultipartForm::new().add_part(
+            "file",
+            axum_test::multipart::Part::bytes(format!("contents {index}").into_bytes())
+                .file_name(format!("file-{index}.txt"))
+                .mime_type("text/plain"),
+        );
+        let attachment: attachment::Model = server
+            .post(&format!("/api/v1/node/{}/attachment", node.id))
+            .multipart(form)
+            .await
+            .json();
+        assert!(attachment.sha256.is_some());
+        uploaded.push(attachment);
+    }
+
+    let report: ReindexReport = server.post("/api/v1/admin/reindex").await.json();
+    assert_eq!(report.attachment_hashes, 0);
+
+    // as if a migration had skipped them
+    attachment::Entity::update_many()
+        .col_expr(attachment::Column::Sha256, Expr::value(None::<String>))
+        .filter(attachment::Column::Id.is_in([uploaded[0].id, uploaded[2].id]))
+        .exec(&conn)
+        .await
+        .expect("Failed to clear hashes");
+
+    let report: ReindexReport = server.post("/api/v1/admin/reindex").await.json();
+    assert_eq!(report.attachment_hashes, 2);
+    for attachment in uploaded {
+        let stored = attachment::Entity::find_by_id(attachment.id)
+            .one(&conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.sha256, attachment.sha256);
+    }
+    let report: ReindexReport = server.post("/api/v1/admin/reindex").await.json();
+    assert_eq!(report.attachment_hashes, 0);
+}
+
+#[tokio::test]
+async fn test_api_list_attachments_sort() {
+    use crate::entity::attachment;
+    use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, QueryFilter};
+
+    let appstate = AppState::test().await;
+    let conn = appstate.conn.clone();
+    let server = setup_test_server_with_state(appstate).await;
+
+    let project: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("Sorted attachments"))
+        .await
+        .json();
+    let node: node::Model = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            ..Default::default()
+        })
+        .await
+        .json();
+    // uploaded oldest first, sizes out of step with both names and upload order
+    let start = chrono::Utc::now() - chrono::Duration::hours(1);
+    for (index, (filename, size)) in [("b.txt", 30), ("a.txt", 10), ("c.txt", 20)]
+        .into_iter()
+        .enumerate()
+    {
+        let form = axum_test::multipart::MultipartForm::new().add_part(
+            "file",
+            axum_test::multipart::Part::bytes(filename.repeat(size / 5).into_bytes())
+                .file_name(filename)
+                .mime_type("text/plain"),
+        );
+        let uploaded: attachment::Model = server
+            .post(&format!("/api/v1/node/{}/attachment", node.id))
+            .multipart(form)
+            .await
+            .json();
+        attachment::Entity::update_many()
+            .col_expr(
+                attachment::Column::Created,
+                Expr::value(start + chrono::Duration::minutes(index as i64)),
+            )
+            .filter(attachment::Column::Id.eq(uploaded.id))
+            .exec(&conn)
+            .await
+            .expect("Failed to set created");
+    }
+
+    for (query, expected) in [
+        ("", ["c.txt", "a.txt", "b.txt"]),
+        ("?sort=created&order=asc", ["b.txt", "a.txt", "c.txt"]),
+        ("?sort=size", ["b.txt", "c.txt", "a.txt"]),
+        ("?sort=size&order=asc", ["a.txt", "c.txt", "b.txt"]),
+        ("?sort=name", ["a.txt", "b.txt", "c.txt"]),
+        ("?sort=name&order=desc", ["c.txt", "b.txt", "a.txt"]),
+    ] {
+        let attachments: Vec<attachment::Model> = server
+            .get(&format!("/api/v1/node/{}/attachments{query}", node.id))
+            .await
+            .json();
+        assert_eq!(
+            attachments
+                .iter()
+                .map(|a| a.filename.as_str())
+                .collect::<Vec<_>>(),
+            expected,
+            "{query}"
+        );
+        assert!(attachments.iter().all(|a| a.data.is_empty()));
+    }
+    server
+        .get(&format!("/api/v1/node/{}/attachments?sort=colour", node.id))
+        .expect_failure()
+        .await
+        .assert_status_bad_request();
+}
+
+#[tokio::test]
+async fn test_api_download_large_attachment() {
+    use crate::entity::attachment;
+    use axum::http::header::{CONTENT_ENCODING, CONTENT_LENGTH};
+
+    let server = setup_test_server().await;
+    let project: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("Large attachment"))
+        .await
+        .json();
+    let node: node::Model = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            ..Default::default()
+        })
+        .await
+        .json();
+    // 10MiB, mostly incompressible so the stored copy is about as big
+    let mut state = 0x2545_f491_u32;
+    let content: Vec<u8> = (0..10 * 1024 * 1024)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(content.clone())
+            .file_name("capture.mp4")
+            .mime_type("video/mp4"),
+    );
+    let uploaded: attachment::Model = server
+        .post(&format!("/api/v1/node/{}/attachment", node.id))
+        .multipart(form)
+        .await
+        .json();
+    assert_eq!(uploaded.size, content.len() as i64);
+
+    for url in [
+        format!("/api/v1/attachment/{}", uploaded.id),
+        format!("/api/v1/attachment/{}/view", uploaded.id),
+    ] {
+        let res = server.get(&url).await;
+        res.assert_status_ok();
+        assert!(res.maybe_header(CONTENT_ENCODING).is_none(), "{url}");
+        assert_eq!(res.header(CONTENT_LENGTH), content.len().to_string());
+        let body = res.as_bytes();
+        assert_eq!(body.len(), content.len(), "{url}");
+        assert!(body.as_ref() == content.as_slice(), "{url}");
+    }
+}
+
+#[tokio::test]
+async fn test_api_node_trash_and_restore() {
+    use crate::entity::{attachment, nodelink};
+    use crate::project::{PaginatedResponse, ProjectExport, SearchResult};
+    use crate::trash::{TrashItem, TrashKind};
+    use axum::http::StatusCode;
+    use osint_graph_shared::nodelink::LinkType;
+    use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter};
+
+    let appstate = AppState::test().await;
+    let conn = appstate.conn.clone();
+    let server = setup_test_server_with_state(appstate).await;
+
+    let project: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("Trash"))
+        .await
+        .json();
+    let mut nodes = Vec::new();
+    for display in ["trashcandidate", "keeper"] {
+        let node: node::Model = server
+            .post("/api/v1/node")
+            .json(&node::Model {
+                project_id: project.id,
+                display: display.to_string(),
+                value: display.to_string(),
+                ..Default::default()
+            })
+            .await
+            .json();
+        nodes.push(node);
+    }
+    let (trashed, kept) = (&nodes[0], &nodes[1]);
+    let form = axum_test::multipart::MultipartForm::new().add_part(
+        "file",
+        axum_test::multipart::Part::bytes(b"notes".to_vec())
+            .file_name("notes.txt")
+            .mime_type("text/plain"),
+    );
+    server
+        .post(&format!("/api/v1/node/{}/attachment", trashed.id))
+        .multipart(form)
+        .await
+        .assert_status_ok();
+    let link: nodelink::Model = server
+        .post("/api/v1/nodelink")
+        .json(&nodelink::Model {
+            id: Uuid::new_v4(),
+            left: trashed.id,
+            right: kept.id,
+            project_id: project.id,
+            linktype: LinkType::Omni,
+            weight: None,
+            kind: None,
+            valid_from: None,
+            valid_to: None,
+            created_by: None,
+            created: None,
+        })
+        .await
+        .json();
+
+    server
+        .delete(&format!("/api/v1/node/{}", trashed.id))
+        .await
+        .assert_status_ok();
+
+    // hidden everywhere, along with its link
+    server
+        .get(&format!("/api/v1/node/{}", trashed.id))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+    let listed: PaginatedResponse<node::Model> = server
+        .get(&format!("/api/v1/project/{}/nodes", project.id))
+        .await
+        .json();
+    assert_eq!(
+        listed.items.iter().map(|n| n.id).collect::<Vec<_>>(),
+        vec![kept.id]
+    );
+    let links: Vec<nodelink::Model> = server
+        .get(&format!("/api/v1/project/{}/nodelinks", project.id))
+        .await
+        .json();
+    assert!(links.is_empty());
+    let links: Vec<nodelink::Model> = server
+        .get(&format!("/api/v1/node/{}/nodelinks", kept.id))
+        .await
+        .json();
+    assert!(links.is_empty());
+    let results: Vec<SearchResult> = server.get("/api/v1/search?q=trashcandidate").await.json();
+    assert!(results.is_empty());
+    let export: ProjectExport = server
+        .get(&format!("/api/v1/project/{}/export", project.id))
+        .await
+        .json();
+    assert_eq!(export.nodes.len(), 1);
+    assert!(export.nodelinks.is_empty());
+    assert!(export.attachments.is_empty());
+    server
+        .delete(&format!("/api/v1/node/{}", trashed.id))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+
+    // the link and attachment are in the trash with it
+    let trash: Vec<TrashItem> = server
+        .get(&format!("/api/v1/project/{}/trash", project.id))
+        .await
+        .json();
+    assert_eq!(
+        trash
+            .iter()
+            .map(|item| (item.kind, item.id))
+            .collect::<Vec<_>>(),
+        vec![
+            (TrashKind::Node, trashed.id),
+            (TrashKind::Nodelink, link.id),
+            (TrashKind::Attachment, trash[2].id),
+        ]
+    );
+    assert!(trash
+        .iter()
+        .all(|item| item.deleted_at == trash[0].deleted_at));
+    assert_eq!(trash[1].name, "trashcandidate → keeper");
+    assert_eq!(trash[2].name, "notes.txt");
+
+    // restoring brings the link back too
+    let restored: node::Model = server
+        .post(&format!("/api/v1/node/{}/restore", trashed.id))
+        .await
+        .json();
+    assert_eq!(restored.deleted_at, None);
+    assert_eq!(restored.display, trashed.display);
+    server
+        .get(&format!("/api/v1/node/{}", trashed.id))
+        .await
+        .assert_status_ok();
+    let links: Vec<nodelink::Model> = server
+        .get(&format!("/api/v1/project/{}/nodelinks", project.id))
+        .await
+        .json();
+    assert_eq!(
+        links.iter().map(|l| l.id).collect::<Vec<_>>(),
+        vec![link.id]
+    );
+    let trash: Vec<TrashItem> = server
+        .get(&format!("/api/v1/project/{}/trash", project.id))
+        .await
+        .json();
+    assert!(trash.is_empty());
+
+    // purging works on live and trashed nodes alike, and really removes them
+    server
+        .delete(&format!("/api/v1/node/{}", trashed.id))
+        .await
+        .assert_status_ok();
+    server
+        .delete(&format!("/api/v1/node/{}?purge=true", trashed.id))
+        .await
+        .assert_status_ok();
+    assert!(node::Entity::find_by_id(trashed.id)
+        .one(&conn)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        attachment::Entity::find()
+            .filter(attachment::Column::NodeId.eq(trashed.id))
+            .count(&conn)
+            .await
+            .unwrap(),
+        0
+    );
+    server
+        .post(&format!("/api/v1/node/{}/restore", trashed.id))
+        .expect_failure()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    server
+        .get(&format!("/api/v1/project/{}/trash", Uuid::new_v4()))
+        .expect_failure()
+        .await
+        .assert_status_not_found();
+}
+
+#[tokio::test]
+async fn test_api_project_trash_restore() {
+    use crate::entity::node_history::{NodeChange, NodeHistoryEntry};
+    use crate::entity::nodelink;
+    use crate::project::PaginatedResponse;
+    use crate::trash::{purge_expired, TrashItem, TrashKind, TrashRef, TrashRestoreResponse};
+    use axum::http::StatusCode;
+    use osint_graph_shared::nodelink::LinkType;
+
+    let mut appstate = AppState::test().await;
+    appstate.trash_retention = chrono::Duration::try_days(30);
+    let conn = appstate.conn.clone();
+    let users = new_test_users(&conn, &[("alice", false), ("bob", false)]).await;
+    let servers = setup_test_servers_as_users(appstate, &users).await;
+    let (alice, bob) = (&servers[0], &servers[1]);
+
+    let project: project::Model = alice
+        .post("/api/v1/project")
+        .json(&new_test_project("Trash"))
+        .await
+        .json();
+    let mut nodes = Vec::new();
+    for display in ["left", "right"] {
+        let node: node::Model = alice
+            .post("/api/v1/node")
+            .json(&node::Model {
+                project_id: project.id,
+                display: display.to_string(),
+                value: display.to_string(),
+                ..Default::default()
+            })
+            .await
+            .json();
+        nodes.push(node);
+    }
+    let link: nodelink::Model = alice
+        .post("/api/v1/nodelink")
+        .json(&nodelink::Model {
+            id: Uuid::new_v4(),
+            left: nodes[0].id,
+            right: nodes[1].id,
+            project_id: project.id,
+            linktype: LinkType::Omni,
+            weight: None,
+            kind: None,
+            valid_from: None,
+            valid_to: None,
+            created_by: None,
+            created: None,
+        })
+        .await
+        .json();
+    alice
+        .delete(&format!("/api/v1/node/{}", nodes[0].id))
+        .await
+        .assert_status_ok();
+
+    let trash: Vec<TrashItem> = alice
+        .get(&format!("/api/v1/project/{}/trash", project.id))
+        .await
+        .json();
+    assert_eq!(trash.len(), 2);
+    let (node_item, link_item) = (&trash[0], &trash[1]);
+    assert_eq!(
+        (node_item.kind, node_item.id),
+        (TrashKind::Node, nodes[0].id)
+    );
+    assert!(node_item.requires.is_empty());
+    assert_eq!(node_item.deleted_by.as_deref(), Some("alice"));
+    assert_eq!(
+        node_item.purge_at,
+        Some(node_item.deleted_at + chrono::Duration::days(30))
+    );
+    let node_ref = TrashRef {
+        kind: TrashKind::Node,
+        id: nodes[0].id,
+    };
+    let link_ref = TrashRef {
+        kind: TrashKind::Nodelink,
+        id: link.id,
+    };
+    assert_eq!(
+        (link_item.kind, link_item.id),
+        (TrashKind::Nodelink, link.id)
+    );
+    assert_eq!(link_item.requires, vec![node_ref]);
+    assert_eq!(link_item.deleted_by.as_deref(), Some("alice"));
+    assert_eq!(link_item.purge_at, node_item.purge_at);
+
+    // it's alice's project
+    bob.get(&format!("/api/v1/project/{}/trash", project.id))
+        .expect_failure()
+        .await
+        .assert_status_forbidden();
+    bob.post(&format!("/api/v1/project/{}/trash/restore", project.id))
+        .json(&serde_json::json!({ "items": [node_ref] }))
+        .expect_failure()
+        .await
+        .assert_status_forbidden();
+    bob.post(&format!("/api/v1/node/{}/restore", nodes[0].id))
+        .expect_failure()
+        .await
+        .assert_status_forbidden();
+
+    // the link can't come back without its end, nor can something that isn't in the trash
+    let res = alice
+        .post(&format!("/api/v1/project/{}/trash/restore", project.id))
+        .json(&serde_json::json!({
+            "items": [
+                link_ref,
+                { "kind": "node", "id": nodes[1].id },
+            ]
+        }))
+        .expect_failure()
+        .await;
+    res.assert_status(StatusCode::CONFLICT);
+    let body: serde_json::Value = res.json();
+    assert_eq!(body["code"], "trash_restore_blocked");
+    assert_eq!(
+        body["failures"],
+        serde_json::json!([
+            {
+                "kind": "node",
+                "id": nodes[1].id,
+                "error": format!("Not in the trash of project {}", project.id),
+                "missing": [],
+            },
+            {
+                "kind": "nodelink",
+                "id": link.id,
+                "error": "Needs other items in the trash restored with it",
+                "missing": [node_ref],
+            },
+        ])
+    );
+    let trash: Vec<TrashItem> = alice
+        .get(&format!("/api/v1/project/{}/trash", project.id))
+        .await
+        .json();
+    assert_eq!(trash.len(), 2, "nothing was restored");
+
+    // together they can, node first
+    let restored: TrashRestoreResponse = alice
+        .post(&format!("/api/v1/project/{}/trash/restore", project.id))
+        .json(&serde_json::json!({ "items": [link_ref, node_ref] }))
+        .await
+        .json();
+    assert_eq!(restored.restored, vec![node_ref, link_ref]);
+    let history: PaginatedResponse<NodeHistoryEntry> = alice
+        .get(&format!("/api/v1/node/{}/history", nodes[0].id))
+        .await
+        .json();
+    assert_eq!(
+        history
+            .items
+            .iter()
+            .map(|entry| (entry.change, entry.changed_by.as_deref()))
+            .collect::<Vec<_>>(),
+        vec![
+            (NodeChange::Restore, Some("alice")),
+            (NodeChange::Delete, Some("alice")),
+        ]
+    );
+    alice
+        .get(&format!("/api/v1/node/{}", nodes[0].id))
+        .await
+        .assert_status_ok();
+    let links: Vec<nodelink::Model> = alice
+        .get(&format!("/api/v1/project/{}/nodelinks", project.id))
+        .await
+        .json();
+    assert_eq!(links.len(), 1);
+    let trash: Vec<TrashItem> = alice
+        .get(&format!("/api/v1/project/{}/trash", project.id))
+        .await
+        .json();
+    assert!(trash.is_empty());
+
+    // malformed IDs get the usual JSON error
+    for res in [
+        alice
+            .get("/api/v1/project/not-a-uuid/trash")
+            .expect_failure()
+            .await,
+        alice
+            .post("/api/v1/project/not-a-uuid/trash/restore")
+            .json(&serde_json::json!({ "items": [] }))
+            .expect_failure()
+            .await,
+    ] {
+        res.assert_status_bad_request();
+        let body: serde_json::Value = res.json();
+        assert_eq!(body["code"], "invalid_path_parameter");
+    }
+
+    // nodes are purged once they've been in the trash longer than the retention period
+    alice
+        .delete(&format!("/api/v1/node/{}", nodes[1].id))
+        .await
+        .assert_status_ok();
+    assert!(purge_expired(&conn, chrono::Duration::days(1))
+        .await
+        .map_err(|err| err.message().to_string())
+        .unwrap()
+        .is_empty());
+    let purged = purge_expired(&conn, chrono::Duration::zero())
+        .await
+        .map_err(|err| err.message().to_string())
+        .unwrap();
+    assert_eq!(
+        purged.iter().map(|node| node.id).collect::<Vec<_>>(),
+        vec![nodes[1].id]
+    );
+    alice
+        .get(&format!("/api/v1/node/{}/history", nodes[1].id))
+        .await
+        .assert_status_ok();
+    let trash: Vec<TrashItem> = alice
+        .get(&format!("/api/v1/project/{}/trash", project.id))
+        .await
+        .json();
+    assert!(trash.is_empty());
+}
+
+#[tokio::test]
+async fn test_restore_command() {
+    use crate::migrate::migration_status;
+    use crate::migration::Migrator;
+    use crate::restore::{inspect_backup, list_backups, restore_backup, RestoreError};
+    use crate::storage::{backup_database, lock_database, lock_database_for_server};
+    use sea_orm::{ActiveModelTrait, EntityTrait, IntoActiveModel, PaginatorTrait};
+    use sea_orm_migration::MigratorTrait;
+
+    let dir = std::env::temp_dir().join(format!("osint-graph-restore-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let db_path = dir.join("osint.sqlite3");
+    let backup = dir.join("osint.backup.sqlite3");
+    let project_count = |path: std::path::PathBuf| async move {
+        let conn = crate::storage::connect(Some(&path)).await.unwrap();
+        let count = project::Entity::find().count(&conn).await.unwrap();
+        conn.close().await.unwrap();
+        count
+    };
+
+    // backup, then carry on changing the database
+    let conn = crate::storage::start_db(Some(&db_path)).await.unwrap();
+    new_test_project("Before the backup")
+        .into_active_model()
+        .insert(&conn)
+        .await
+        .unwrap();
+    backup_database(&conn, &backup).await.unwrap();
+    new_test_project("After the backup")
+        .into_active_model()
+        .insert(&conn)
+        .await
+        .unwrap();
+    conn.close().await.unwrap();
+    let backed_up = project_count(backup.clone()).await;
+    let newer = project_count(db_path.clone()).await;
+    assert_eq!(newer, backed_up + 1);
+
+    let info = inspect_backup(&backup).await.unwrap();
+    assert!(info.pending.is_empty());
+    assert_eq!(info.applied.len(), Migrator::migrations().len());
+
+    // an existing database needs --force
+    let err = restore_backup(&db_path, &backup, false).await.unwrap_err();
+    assert!(matches!(err, RestoreError::DatabaseExists(_)), "{err}");
+    assert_eq!(err.exit_code(), 4);
+
+    // never while a server holds the lock, even with --force
+    let lock = lock_database(&db_path).unwrap();
+    let err = lock_database_for_server(&db_path).unwrap_err();
+    assert_eq!(
+        err.kind(),
+        std::io::ErrorKind::WouldBlock,
+        "servers won't start either"
+    );
+    let err = restore_backup(&db_path, &backup, true).await.unwrap_err();
+    assert!(matches!(err, RestoreError::ServerRunning(_)), "{err}");
+    assert!(err.to_string().contains(&std::process::id().to_string()));
+    assert_eq!(err.exit_code(), 5);
+    assert_eq!(project_count(db_path.clone()).await, newer);
+    drop(lock);
+    // other failures to lock don't stop a server
+    assert!(
+        lock_database_for_server(&dir.join("missing").join("db.sqlite3"))
+            .unwrap()
+            .is_none()
+    );
+
+    let report = restore_backup(&db_path, &backup, true).await.unwrap();
+    assert!(report.applied.is_empty());
+    assert_eq!(project_count(db_path.clone()).await, backed_up);
+    let aside = report.moved_aside.expect("the database was moved aside");
+    assert_eq!(aside.parent(), Some(dir.as_path()));
+    assert_eq!(project_count(aside.clone()).await, newer);
+
+    // corrupt and foreign files are refused without touching anything
+    let garbage = dir.join("garbage.sqlite3");
+    std::fs::write(&garbage, vec![0x5a; 8192]).unwrap();
+    let err = restore_backup(&db_path, &garbage, true).await.unwrap_err();
+    assert!(matches!(err, RestoreError::InvalidBackup(_)), "{err}");
+    assert_eq!(err.exit_code(), 3);
+    let err = restore_backup(&db_path, &dir.join("missing.sqlite3"), true)
+        .await
+        .unwrap_err();
+    assert_eq!(err.exit_code(), 3);
+    assert_eq!(project_count(db_path.clone()).await, backed_up);
+
+    // backups from before the latest migrations are brought up to date
+    let old = dir.join("old.sqlite3");
+    let conn = crate::storage::connect(Some(&old)).await.unwrap();
+    Migrator::up(&conn, Some(4)).await.unwrap();
+    conn.close().await.unwrap();
+    let fresh = dir.join("fresh").join("osint.sqlite3");
+    std::fs::create_dir_all(fresh.parent().unwrap()).unwrap();
+    let report = restore_backup(&fresh, &old, false).await.unwrap();
+    assert!(report.moved_aside.is_none());
+    assert_eq!(report.applied.len(), Migrator::migrations().len() - 4);
+    let conn = crate::storage::connect(Some(&fresh)).await.unwrap();
+    assert!(migration_status(&conn).await.unwrap().pending.is_empty());
+    conn.close().await.unwrap();
+
+    let listed = list_backups(&dir, &db_path).await.unwrap();
+    let names: Vec<String> = listed
+        .iter()
+        .map(|(path, _)| path.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            "garbage.sqlite3".to_string(),
+            "old.sqlite3".to_string(),
+            aside.file_name().unwrap().to_string_lossy().to_string(),
+            "osint.backup.sqlite3".to_string(),
+        ]
+    );
+    assert!(listed[0].1.is_err());
+    let old_info = listed[1].1.as_ref().unwrap();
+    assert_eq!(old_info.applied.len(), 4);
+    assert_eq!(old_info.schema_version(), old_info.applied[3]);
+    assert!(old_info.size > 0);
+    assert!(listed[3].1.as_ref().unwrap().pending.is_empty());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_api_notes_length_limit() {
+    use crate::text_limit::{TextLimit, TEXT_TOO_LONG, TRUNCATED_HEADER};
+
+    let mut appstate = AppState::test().await;
+    appstate.notes_limit = TextLimit {
+        max_bytes: Some(16),
+        truncate: false,
+    };
+    let server = setup_test_server_with_state(appstate).await;
+
+    // normal sized text goes through untouched
+    let project = project::Model {
+        description: Some("sixteen bytes ok".to_string()),
+        ..new_test_project("Limited")
+    };
+    let res = server.post("/api/v1/project").json(&project).await;
+    res.assert_status_ok();
+    assert!(res.maybe_header(TRUNCATED_HEADER).is_none());
+    let node = node::Model {
+        project_id: project.id,
+        notes: Some("short".to_string()),
+        ..Default::default()
+    };
+    let res = server.post("/api/v1/node").json(&node).await;
+    res.assert_status_ok();
+    assert!(res.maybe_header(TRUNCATED_HEADER).is_none());
+    assert_eq!(res.json::<node::Model>().notes.as_deref(), Some("short"));
+
+    let too_long = Some("seventeen bytes!!".to_string());
+    let res = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            notes: too_long.clone(),
+            ..Default::default()
+        })
+        .expect_failure()
+        .await;
+    res.assert_status_bad_request();
+    let body: serde_json::Value = res.json();
+    assert_eq!(body["code"], TEXT_TOO_LONG);
+    assert_eq!(body["field"], "notes");
+    assert_eq!(body["bytes"], 17);
+    assert_eq!(body["max_bytes"], 16);
+    server
+        .put(&format!("/api/v1/node/{}", node.id))
+        .json(&node::Model {
+            notes: too_long.clone(),
+            ..node.clone()
+        })
+        .expect_failure()
+        .await
+        .assert_status_bad_request();
+    let res = server
+        .put(&format!("/api/v1/project/{}", project.id))
+        .json(&project::Model {
+            description: too_long.clone(),
+            ..project.clone()
+        })
+        .expect_failure()
+        .await;
+    res.assert_status_bad_request();
+    assert_eq!(res.json::<serde_json::Value>()["field"], "description");
+    let stored: node::Model = server
+        .get(&format!("/api/v1/node/{}", node.id))
+        .await
+        .json();
+    assert_eq!(stored.notes.as_deref(), Some("short"));
+
+    // truncating instead, never splitting a character
+    let mut appstate = AppState::test().await;
+    appstate.notes_limit = TextLimit {
+        max_bytes: Some(16),
+        truncate: true,
+    };
+    let server = setup_test_server_with_state(appstate).await;
+    let project = project::Model {
+        description: too_long.clone(),
+        ..new_test_project("Truncated")
+    };
+    let res = server.post("/api/v1/project").json(&project).await;
+    res.assert_status_ok();
+    assert_eq!(res.header(TRUNCATED_HEADER), "description");
+    assert_eq!(
+        res.json::<project::Model>().description.as_deref(),
+        Some("seventeen bytes!")
+    );
+    let node = node::Model {
+        project_id: project.id,
+        notes: Some("é".repeat(9)),
+        ..Default::default()
+    };
+    let res = server.post("/api/v1/node").json(&node).await;
+    res.assert_status_ok();
+    assert_eq!(res.header(TRUNCATED_HEADER), "notes");
+    assert_eq!(res.json::<node::Model>().notes, Some("é".repeat(8)));
+    let res = server
+        .put(&format!("/api/v1/node/{}", node.id))
+        .json(&node::Model {
+            notes: Some("x".repeat(40)),
+            ..node.clone()
+        })
+        .await;
+    res.assert_status_ok();
+    assert_eq!(res.header(TRUNCATED_HEADER), "notes");
+    assert_eq!(res.json::<node::Model>().notes, Some("x".repeat(16)));
+    let res = server
+        .put(&format!("/api/v1/node/{}", node.id))
+        .json(&node::Model {
+            notes: Some("fits".to_string()),
+            ..node.clone()
+        })
+        .await;
+    res.assert_status_ok();
+    assert!(res.maybe_header(TRUNCATED_HEADER).is_none());
+}
+
+#[tokio::test]
+async fn test_api_nodelink_label_alias() {
+    use crate::entity::nodelink;
+
+    let server = setup_test_server().await;
+    let project: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("Labels"))
+        .await
+        .json();
+    let mut ends = Vec::new();
+    for display in ["Alice", "example.com"] {
+        let node: node::Model = server
+            .post("/api/v1/node")
+            .json(&node::Model {
+                project_id: project.id,
+                display: display.to_string(),
+                ..Default::default()
+            })
+            .await
+            .json();
+        ends.push(node);
+    }
+
+    // `label` in and out, with the older `kind` still accepted
+    let id = Uuid::new_v4();
+    let mut link = serde_json::json!({
+        "id": id,
+        "left": ends[0].id,
+        "right": ends[1].id,
+        "project_id": project.id,
+        "linktype": "Directional",
+        "label": "owns",
+    });
+    let created: nodelink::Model = server.post("/api/v1/nodelink").json(&link).await.json();
+    assert_eq!(created.kind.as_deref(), Some("owns"));
+    let mermaid = server
+        .get(&format!("/api/v1/project/{}/export/mermaid", project.id))
+        .await
+        .text();
+    assert!(mermaid.contains(" : owns"), "{mermaid}");
+
+    link["label"] = "registered".into();
+    let updated: nodelink::Model = server
+        .put(&format!("/api/v1/nodelink/{id}"))
+        .json(&link)
+        .await
+        .json();
+    assert_eq!(updated.kind.as_deref(), Some("registered"));
+    let links: Vec<nodelink::Model> = server
+        .get(&format!("/api/v1/project/{}/nodelinks", project.id))
+        .await
+        .json();
+    assert_eq!(links[0].kind.as_deref(), Some("registered"));
+    let raw: serde_json::Value = server
+        .get(&format!("/api/v1/project/{}/nodelinks", project.id))
+        .await
+        .json();
+    assert_eq!(raw[0]["label"], "registered");
+    assert!(raw[0].get("kind").is_none(), "{raw}");
+
+    link.as_object_mut().expect("link object").remove("label");
+    link["kind"] = "hosted".into();
+    let updated: serde_json::Value = server
+        .put(&format!("/api/v1/nodelink/{id}"))
+        .json(&link)
+        .await
+        .json();
+    assert_eq!(updated["label"], "hosted");
+}
+
+#[tokio::test]
+async fn test_api_node_history() {
+    use crate::entity::node_history::{NodeChange, NodeHistoryEntry};
+    use crate::entity::user;
+    use crate::oauth::middleware::AuthUser;
+    use crate::project::PaginatedResponse;
+    use axum::http::StatusCode;
+    use sea_orm::{ActiveModelTrait, Set};
+
+    let appstate = AppState::test().await;
+    let user = user::ActiveModel {
+        subject: Set("alice".to_string()),
+        email: Set("alice@example.com".to_string()),
+        uuid: Set(Uuid::new_v4()),
+        ..Default::default()
+    }
+    .insert(&appstate.conn)
+    .await
+    .expect("Failed to create user");
+    let servers = setup_test_servers_as_users(appstate, &[AuthUser::from(user)]).await;
+    let server = &servers[0];
+
+    let project: project::Model = server
+        .post("/api/v1/project")
+        .json(&new_test_project("History"))
+        .await
+        .json();
+    let mut node: node::Model = server
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            display: "suspect".to_string(),
+            value: "value 0".to_string(),
+            notes: Some("notes 0".to_string()),
+            ..Default::default()
+        })
+        .await
+        .json();
+    let history_url = format!("/api/v1/node/{}/history", node.id);
+    let history: PaginatedResponse<NodeHistoryEntry> = server.get(&history_url).await.json();
+    assert_eq!(history.total_count, 0, "creating a node isn't a change");
+
+    for version in 1..=3 {
+        node.value = format!("value {version}");
+        node.notes = Some(format!("notes {version}"));
+        node = server
+            .put(&format!("/api/v1/node/{}", node.id))
+            .json(&node)
+            .await
+            .json();
+    }
+
+    let history: PaginatedResponse<NodeHistoryEntry> = server.get(&history_url).await.json();
+    assert_eq!(history.total_count, 3);
+    let previous: Vec<(String, Option<String>)> = history
+        .items
+        .iter()
+        .map(|entry| {
+            assert_eq!(entry.node_id, node.id);
+            assert_eq!(entry.change, NodeChange::Update);
+            assert_eq!(entry.changed_by.as_deref(), Some("alice"));
+            let previous: node::Model =
+                serde_json::from_value(entry.previous.clone()).expect("previous isn't a node");
+            (previous.value, previous.notes)
+        })
+        .collect();
+    assert_eq!(
+        previous,
+        (0..3)
+            .rev()
+            .map(|version| (format!("value {version}"), Some(format!("notes {version}"))))
+            .collect::<Vec<_>>(),
+        "newest first, each holding what the node was before the update"
+    );
+
+    let page: PaginatedResponse<NodeHistoryEntry> = server
+        .get(&format!("{history_url}?page=2&page_size=2"))
+        .await
+        .json();
+    assert_eq!(page.total_count, 3);
+    assert_eq!(page.items.len(), 1);
+    assert_eq!(page.items[0].previous["value"], "value 0");
+
+    server
+        .delete(&format!("/api/v1/node/{}", node.id))
+        .await
+        .assert_status_ok();
+    server
+        .delete(&format!("/api/v1/node/{}?purge=true", node.id))
+        .await
+        .assert_status_ok();
+    let history: PaginatedResponse<NodeHistoryEntry> = server.get(&history_url).await.json();
+    assert_eq!(history.total_count, 5, "the history outlives a purge");
+    assert_eq!(history.items[0].change, NodeChange::Purge);
+    assert!(!history.items[0].previous["deleted_at"].is_null());
+    assert_eq!(history.items[1].change, NodeChange::Delete);
+    assert_eq!(history.items[1].previous["value"], "value 3");
+    assert!(history.items[1].previous["deleted_at"].is_null());
+
+    server
+        .get(&format!("/api/v1/node/{}/history", Uuid::new_v4()))
+        .expect_failure()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    server
+        .get(&format!("{history_url}?page=0"))
+        .expect_failure()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_api_merge_projects_access() {
+    use crate::project::PaginatedResponse;
+
+    let appstate = AppState::test().await;
+    let users = new_test_users(&appstate.conn, &[("alice", false), ("bob", false)]).await;
+    let servers = setup_test_servers_as_users(appstate, &users).await;
+    let (alice, bob) = (&servers[0], &servers[1]);
+
+    let mut projects = Vec::new();
+    for (server, name) in [(alice, "Alice's case"), (bob, "Bob's case")] {
+        let project: project::Model = server
+            .post("/api/v1/project")
+            .json(&new_test_project(name))
+            .await
+            .json();
+        server
+            .post("/api/v1/node")
+            .json(&node::Model {
+                project_id: project.id,
+                display: name.to_string(),
+                ..Default::default()
+            })
+            .await
+            .assert_status_ok();
+        projects.push(project);
+    }
+    let (alices, bobs) = (&projects[0], &projects[1]);
+
+    // neither absorbing bob's project nor pushing alice's into it
+    for (keep, absorb) in [(alices, bobs), (bobs, alices)] {
+        alice
+            .post(&format!("/api/v1/project/{}/merge/{}", keep.id, absorb.id))
+            .expect_failure()
+            .await
+            .assert_status_forbidden();
+    }
+    for (server, project) in [(bob, bobs), (alice, alices)] {
+        let nodes: PaginatedResponse<node::Model> = server
+            .get(&format!("/api/v1/project/{}/nodes", project.id))
+            .await
+            .json();
+        assert_eq!(nodes.total_count, 1, "neither project was touched");
+    }
+}
+
+#[tokio::test]
+async fn test_api_merge_nodes_access() {
+    use crate::entity::node_history::{NodeChange, NodeHistoryEntry};
+    use crate::project::PaginatedResponse;
+
+    let appstate = AppState::test().await;
+    let users = new_test_users(&appstate.conn, &[("alice", false), ("bob", false)]).await;
+    let servers = setup_test_servers_as_users(appstate, &users).await;
+    let (alice, bob) = (&servers[0], &servers[1]);
+
+    let project: project::Model = bob
+        .post("/api/v1/project")
+        .json(&new_test_project("Bob's case"))
+        .await
+        .json();
+    let mut nodes = Vec::new();
+    for (display, notes) in [("jane", "from the leak"), ("jane doe", "from the registry")] {
+        let node: node::Model = bob
+            .post("/api/v1/node")
+            .json(&node::Model {
+                project_id: project.id,
+                display: display.to_string(),
+                notes: Some(notes.to_string()),
+                ..Default::default()
+            })
+            .await
+            .json();
+        nodes.push(node);
+    }
+    let (source, target) = (&nodes[0], &nodes[1]);
+    let merge_url = format!("/api/v1/node/{}/merge/{}", source.id, target.id);
+
+    alice
+        .post(&merge_url)
+        .expect_failure()
+        .await
+        .assert_status_forbidden();
+    for node in [source, target] {
+        let stored: node::Model = bob.get(&format!("/api/v1/node/{}", node.id)).await.json();
+        assert_eq!(stored.notes, node.notes, "alice changed nothing");
+    }
+
+    // bob can, and both nodes keep a record of what they were
+    let merged: node::Model = bob.post(&merge_url).await.json();
+    assert_eq!(
+        merged.notes.as_deref(),
+        Some("from the registry\n\nfrom the leak")
+    );
+    for (node, change) in [(source, NodeChange::Merge), (target, NodeChange::Update)] {
+        let history: PaginatedResponse<NodeHistoryEntry> = bob
+            .get(&format!("/api/v1/node/{}/history", node.id))
+            .await
+            .json();
+        assert_eq!(history.total_count, 1);
+        assert_eq!(history.items[0].change, change);
+        assert_eq!(history.items[0].changed_by.as_deref(), Some("bob"));
+        assert_eq!(
+            history.items[0].previous["notes"],
+            node.notes.clone().unwrap()
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_api_node_split_access() {
+    use serde_json::json;
+
+    let appstate = AppState::test().await;
+    let users = new_test_users(&appstate.conn, &[("alice", false), ("bob", false)]).await;
+    let servers = setup_test_servers_as_users(appstate, &users).await;
+    let (alice, bob) = (&servers[0], &servers[1]);
+
+    let project: project::Model = bob
+        .post("/api/v1/project")
+        .json(&new_test_project("Bob's case"))
+        .await
+        .json();
+    let original: node::Model = bob
+        .post("/api/v1/node")
+        .json(&node::Model {
+            project_id: project.id,
+            display: "Acme".to_string(),
+            ..Default::default()
+        })
+        .await
+        .json();
+
+    alice
+        .post(&format!("/api/v1/node/{}/split", original.id))
+        .json(&json!({
+            "nodes": [{"node_type": "person", "display": "Mallory", "value": "Mallory"}],
+            "delete_original": true,
+        }))
+        .expect_failure()
+        .await
+        .assert_status_forbidden();
+    let nodes: crate::project::PaginatedResponse<node::Model> = bob
+        .get(&format!("/api/v1/project/{}/nodes", project.id))
+        .await
+        .json();
+    assert_eq!(nodes.total_count, 1, "nothing was created or deleted");
+    assert_eq!(nodes.items[0].id, original.id);
+}
+
+#[tokio::test]
+async fn test_api_merge_projects_trash() {
+    use crate::merge::ProjectMergeResponse;
+    use crate::project::PaginatedResponse;
+    use crate::trash::TrashItem;
+
+    let server = setup_test_server().await;
+    let keep = new_test_project("Merge keep");
+    let absorb = new_test_project("Merge absorb");
+    for project in [&keep, &absorb] {
+        server
+            .post("/api/v1/project")
+            .json(project)
+            .await
+            .assert_status_ok();
+    }
+    let new_node = |project_id, value: &str| node::Model {
+        project_id,
+        node_type: NodeType::Domain,
+        display: value.to_string(),
+        value: value.to_string(),
+        ..Default::default()
+    };
+    // a trashed node in the kept project and a live duplicate of it in the absorbed one
+    let trashed_survivor = new_node(keep.id, "example.com");
+    let duplicate = new_node(absorb.id, "Example.com");
+    let trashed_absorbed = new_node(absorb.id, "example.org");
+    for node in [&trashed_survivor, &duplicate, &trashed_absorbed] {
+        server
+            .post("/api/v1/node")
+            .json(node)
+            .await
+            .assert_status_ok();
+    }
+    for node in [&trashed_survivor, &trashed_absorbed] {
+        server
+            .delete(&format!("/api/v1/node/{}", node.id))
+            .await
+            .assert_status_ok();
+    }
+
+    let merged: ProjectMergeResponse = server
+        .post(&format!("/api/v1/project/{}/merge/{}", keep.id, absorb.id))
+        .add_query_param("dedupe", true)
+        .await
+        .json();
+    assert_eq!(merged.moved_nodes, 1, "only live nodes are moved");
+    assert_eq!(merged.deduped_nodes, 0, "nor folded into trashed ones");
+    assert_eq!(merged.trashed_nodes, 1);
+
+    let nodes: PaginatedResponse<node::Model> = server
+        .get(&format!("/api/v1/project/{}/nodes", keep.id))
+        .await
+        .json();
+    assert_eq!(
+        nodes.items.iter().map(|node| node.id).collect::<Vec<_>>(),
+        vec![duplicate.id]
+    );
+    let trash: Vec<TrashItem> = server
+        .get(&format!("/api/v1/project/{}/trash", keep.id))
+        .await
+        .json();
+    let mut trashed: Vec<Uuid> = trash.iter().map(|item| item.id).collect();
+    trashed.sort();
+    let mut expected = vec![trashed_survivor.id, trashed_absorbed.id];
+    expected.sort();
+    assert_eq!(
+        trashed, expected,
+        "the absorbed trash can still be restored"
+    );
+    server
+        .post(&format!("/api/v1/node/{}/restore", trashed_absorbed.id))
+        .await
+        .assert_status_ok();
+}
@@ -0,0 +1,48 @@
+//! Counting the SQL statements a request runs, to catch N+1 query regressions
+//!
+//! [AppState::test](crate::AppState::test) hooks [record_statement] into the connection's metric
+//! callback, which logs into a task-local. Requests made through axum-test's mock transport run on
+//! the test's task, so everything a request executes lands in the enclosing
+//! [with_query_budget] scope. Session store queries go straight to sqlx and aren't counted.
+
+use std::{cell::RefCell, future::IntoFuture};
+
+tokio::task_local! {
+    static STATEMENTS: RefCell<Vec<String>>;
+}
+
+/// Metric callback which records the statement if we're inside [with_query_budget]
+pub fn record_statement(info: &sea_orm::metric::Info<'_>) {
+    let _ = STATEMENTS.try_with(|statements| {
+        statements.borrow_mut().push(info.statement.to_string());
+    });
+}
+
+/// Run `f`, failing the test if it executes more than `budget` statements
+///
+/// Returns whatever `f` returns, and panics if nothing was recorded at all since that means the
+/// instrumentation isn't wired up and the budget would pass vacuously.
+pub async fn with_query_budget<F, Fut, T>(budget: usize, f: F) -> T
+where
+    F: FnOnce() -> Fut,
+    Fut: IntoFuture<Output = T>,
+{
+    let (res, statements) = STATEMENTS
+        .scope(RefCell::new(Vec::new()), async {
+            let res = f().await;
+            (res, STATEMENTS.with(RefCell::take))
+        })
+        .await;
+    assert!(
+        !statements.is_empty(),
+        "No statements were recorded, is the query metric callback set?"
+    );
+    assert!(
+        statements.len() <= budget,
+        "Query budget exceeded: {} statements, budget {}\n{}",
+        statements.len(),
+        budget,
+        statements.join("\n")
+    );
+    res
+}
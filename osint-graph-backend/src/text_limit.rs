@@ -0,0 +1,69 @@
+//! Size limits on free text, node notes and project descriptions
+//!
+//! Text over the limit is refused, or with `--truncate-notes` cut down to size on a character
+//! boundary, in which case the response names what was cut in [TRUNCATED_HEADER].
+
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use tracing::warn;
+
+use crate::project::WebError;
+
+/// Default for `--max-notes-bytes`
+pub const DEFAULT_MAX_NOTES_BYTES: u64 = 1024 * 1024;
+
+/// Error code for text over the limit when it isn't being truncated
+pub const TEXT_TOO_LONG: &str = "text_too_long";
+
+/// Comma-separated fields which were truncated to fit the limit
+pub const TRUNCATED_HEADER: HeaderName = HeaderName::from_static("x-osintgraph-truncated");
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TextLimit {
+    /// Unset for no limit
+    pub max_bytes: Option<usize>,
+    /// Cut text down to size instead of refusing it
+    pub truncate: bool,
+}
+
+impl TextLimit {
+    /// Enforce the limit on `field`, returning whether it was truncated
+    pub fn apply(&self, field: &'static str, text: &mut Option<String>) -> Result<bool, WebError> {
+        let (Some(max_bytes), Some(text)) = (self.max_bytes, text.as_mut()) else {
+            return Ok(false);
+        };
+        let bytes = text.len();
+        if bytes <= max_bytes {
+            return Ok(false);
+        }
+        if !self.truncate {
+            return Err(WebError::new(
+                StatusCode::BAD_REQUEST,
+                format!("{field} is {bytes} bytes, the limit is {max_bytes}"),
+            )
+            .with_code(TEXT_TOO_LONG)
+            .with_detail("field", field)
+            .with_detail("bytes", bytes)
+            .with_detail("max_bytes", max_bytes));
+        }
+        text.truncate(text.floor_char_boundary(max_bytes));
+        warn!(
+            field,
+            original_bytes = bytes,
+            max_bytes,
+            "Truncated text over the size limit"
+        );
+        Ok(true)
+    }
+}
+
+/// The [TRUNCATED_HEADER] for `fields`, if any were truncated
+pub fn truncated_headers(fields: &[&str]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if fields.is_empty() {
+        return headers;
+    }
+    if let Ok(value) = HeaderValue::from_str(&fields.join(",")) {
+        headers.insert(TRUNCATED_HEADER, value);
+    }
+    headers
+}
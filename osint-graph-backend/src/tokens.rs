@@ -0,0 +1,207 @@
+//! Personal API tokens, for clients that can't hold a session cookie
+//!
+//! Tokens are sent as `Authorization: Bearer <token>`. Only a SHA-256 hash of each token is stored,
+//! the token itself is returned once when it's created.
+
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use chrono::Utc;
+use rand::RngCore;
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait,
+    IntoActiveModel, QueryFilter, QueryOrder,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::info;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    entity::{api_token, user},
+    extract::Path,
+    oauth::middleware::AuthUser,
+    project::{ErrorResponse, WebError},
+    SharedState,
+};
+
+/// Prefix on every token, so they're easy to spot in logs and secret scanners
+pub const TOKEN_PREFIX: &str = "ogt_";
+
+fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Create a token for `user_id`, returning the stored record and the token itself
+pub async fn create_token(
+    conn: &impl ConnectionTrait,
+    user_id: Uuid,
+    name: &str,
+) -> Result<(api_token::Model, String), DbErr> {
+    let mut secret = [0u8; 32];
+    rand::rng().fill_bytes(&mut secret);
+    let token = format!(
+        "{}{}",
+        TOKEN_PREFIX,
+        secret
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>()
+    );
+    let model = api_token::Model {
+        id: Uuid::new_v4(),
+        user_id,
+        name: name.to_string(),
+        token_hash: hash_token(&token),
+        created: Utc::now(),
+        last_used: None,
+    }
+    .into_active_model()
+    .insert(conn)
+    .await?;
+    Ok((model, token))
+}
+
+/// Find the user a token belongs to, recording that the token was used
+pub async fn authenticate(
+    conn: &impl ConnectionTrait,
+    token: &str,
+) -> Result<Option<user::Model>, DbErr> {
+    if !token.starts_with(TOKEN_PREFIX) {
+        return Ok(None);
+    }
+    let Some(api_token) = api_token::Entity::find()
+        .filter(api_token::Column::TokenHash.eq(hash_token(token)))
+        .one(conn)
+        .await?
+    else {
+        return Ok(None);
+    };
+    api_token::Entity::update_many()
+        .col_expr(api_token::Column::LastUsed, Expr::value(Utc::now()))
+        .filter(api_token::Column::Id.eq(api_token.id))
+        .exec(conn)
+        .await?;
+    user::Entity::find()
+        .filter(user::Column::Uuid.eq(api_token.user_id))
+        .one(conn)
+        .await
+}
+
+fn require_user(auth_user: Option<Extension<AuthUser>>) -> Result<AuthUser, WebError> {
+    auth_user.map(|Extension(user)| user).ok_or_else(|| {
+        WebError::new(
+            StatusCode::BAD_REQUEST,
+            "API tokens are only available when authentication is enabled",
+        )
+    })
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct NewApiToken {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CreatedApiToken {
+    #[serde(flatten)]
+    pub api_token: api_token::Model,
+    /// The token itself, this is the only time it's shown
+    pub token: String,
+}
+
+/// Create a personal API token
+#[utoipa::path(
+    post,
+    path = "/api/v1/tokens",
+    tag = "auth",
+    operation_id = "post_token",
+    request_body = NewApiToken,
+    responses(
+        (status = OK, description = "Token created", body = CreatedApiToken),
+        (status = BAD_REQUEST, description = "Invalid request", body = ErrorResponse)
+    )
+)]
+pub async fn post_token(
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+    Json(new_token): Json<NewApiToken>,
+) -> Result<Json<CreatedApiToken>, WebError> {
+    let auth_user = require_user(auth_user)?;
+    let name = new_token.name.trim();
+    if name.is_empty() {
+        return Err(WebError::new(
+            StatusCode::BAD_REQUEST,
+            "Token name cannot be empty",
+        ));
+    }
+    let (api_token, token) = create_token(&state.read().await.conn, auth_user.id, name).await?;
+    info!(
+        token_id = api_token.id.to_string(),
+        user = auth_user.subject,
+        "Created API token"
+    );
+    Ok(Json(CreatedApiToken { api_token, token }))
+}
+
+/// List the caller's API tokens
+#[utoipa::path(
+    get,
+    path = "/api/v1/tokens",
+    tag = "auth",
+    operation_id = "get_tokens",
+    responses(
+        (status = OK, description = "The caller's tokens", body = Vec<api_token::Model>),
+        (status = BAD_REQUEST, description = "Authentication is disabled", body = ErrorResponse)
+    )
+)]
+pub async fn get_tokens(
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<Vec<api_token::Model>>, WebError> {
+    let auth_user = require_user(auth_user)?;
+    let tokens = api_token::Entity::find()
+        .filter(api_token::Column::UserId.eq(auth_user.id))
+        .order_by_asc(api_token::Column::Created)
+        .all(&state.read().await.conn)
+        .await?;
+    Ok(Json(tokens))
+}
+
+/// Revoke one of the caller's API tokens
+#[utoipa::path(
+    delete,
+    path = "/api/v1/tokens/{id}",
+    tag = "auth",
+    operation_id = "delete_token",
+    params(
+        ("id" = Uuid, Path, description = "Token ID")
+    ),
+    responses(
+        (status = OK, description = "Token revoked"),
+        (status = NOT_FOUND, description = "Token not found", body = ErrorResponse)
+    )
+)]
+pub async fn delete_token(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<StatusCode, WebError> {
+    let auth_user = require_user(auth_user)?;
+    let res = api_token::Entity::delete_many()
+        .filter(api_token::Column::Id.eq(id))
+        .filter(api_token::Column::UserId.eq(auth_user.id))
+        .exec(&state.read().await.conn)
+        .await?;
+    if res.rows_affected == 0 {
+        return Err(WebError::not_found(format!("Token {} not found", id)));
+    }
+    info!(
+        token_id = id.to_string(),
+        user = auth_user.subject,
+        "Revoked API token"
+    );
+    Ok(StatusCode::OK)
+}
@@ -0,0 +1,439 @@
+//! A project's trash, and taking things back out of it
+//!
+//! Only nodes have `deleted_at`. Links touching a node in the trash, and the node's attachments,
+//! are hidden with it rather than deleted, so [get_project_trash] lists them too and they come
+//! back when the node does. Each item says which other items in the trash have to be restored
+//! with it, and [restore_trash] refuses a batch which leaves any of them behind.
+//!
+//! With `--trash-retention-days` set, [spawn_trash_purge] purges nodes which have been in the
+//! trash longer than that, along with their attachments.
+
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, EntityTrait,
+    QueryFilter, QueryOrder, QuerySelect, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    access::find_accessible_project,
+    entity::{
+        attachment, node,
+        node_history::{self, NodeChange},
+        nodelink,
+    },
+    extract::Path,
+    oauth::middleware::AuthUser,
+    project::{purge_node, ErrorResponse, WebError},
+    quota::QuotaKind,
+    SharedState,
+};
+
+/// What sort of thing is in the trash, in the order they're restored
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize, ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum TrashKind {
+    Node,
+    /// Hidden because one or both ends are in the trash
+    Nodelink,
+    /// Hidden because its node is in the trash
+    Attachment,
+}
+
+/// Something in the trash
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize, ToSchema,
+)]
+pub struct TrashRef {
+    pub kind: TrashKind,
+    pub id: Uuid,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct TrashItem {
+    pub kind: TrashKind,
+    pub id: Uuid,
+    /// The node's display, the link's ends, or the attachment's filename
+    pub name: String,
+    /// When it went in the trash. For links and attachments, when the node which hid them did
+    pub deleted_at: DateTime<Utc>,
+    /// Subject of the user who deleted it, unset when authentication is off
+    pub deleted_by: Option<String>,
+    /// When it'll be purged, unset when the server keeps the trash until it's purged by hand
+    pub purge_at: Option<DateTime<Utc>>,
+    /// Other items in the trash which have to be restored with this one, empty when it can be
+    /// restored on its own
+    pub requires: Vec<TrashRef>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct TrashRestoreRequest {
+    pub items: Vec<TrashRef>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct TrashRestoreResponse {
+    /// What was restored, in the order it was restored
+    pub restored: Vec<TrashRef>,
+}
+
+/// Why an item in a restore couldn't be restored
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct TrashRestoreFailure {
+    pub kind: TrashKind,
+    pub id: Uuid,
+    pub error: String,
+    /// Items which have to be restored with it but weren't asked for
+    pub missing: Vec<TrashRef>,
+}
+
+/// Everything in a project's trash, most recently deleted first
+pub async fn list_trash(
+    conn: &impl ConnectionTrait,
+    project_id: Uuid,
+    retention: Option<chrono::Duration>,
+) -> Result<Vec<TrashItem>, WebError> {
+    let nodes = node::Entity::find()
+        .filter(node::Column::ProjectId.eq(project_id))
+        .filter(node::Column::DeletedAt.is_not_null())
+        .all(conn)
+        .await?;
+    if nodes.is_empty() {
+        return Ok(Vec::new());
+    }
+    let node_ids: Vec<Uuid> = nodes.iter().map(|node| node.id).collect();
+
+    // the latest delete is the one which put it in the trash this time
+    let mut deleted_by: HashMap<Uuid, Option<String>> = HashMap::new();
+    for (node_id, changed_by) in node_history::Entity::find()
+        .select_only()
+        .columns([
+            node_history::Column::NodeId,
+            node_history::Column::ChangedBy,
+        ])
+        .filter(node_history::Column::NodeId.is_in(node_ids.clone()))
+        .filter(node_history::Column::Change.eq(NodeChange::Delete))
+        .order_by_desc(node_history::Column::ChangedAt)
+        .into_tuple::<(Uuid, Option<String>)>()
+        .all(conn)
+        .await?
+    {
+        deleted_by.entry(node_id).or_insert(changed_by);
+    }
+
+    let trashed: HashMap<Uuid, TrashItem> = nodes
+        .iter()
+        .filter_map(|node| {
+            let deleted_at = node.deleted_at?;
+            Some((
+                node.id,
+                TrashItem {
+                    kind: TrashKind::Node,
+                    id: node.id,
+                    name: node.display.clone(),
+                    deleted_at,
+                    deleted_by: deleted_by.get(&node.id).cloned().flatten(),
+                    purge_at: retention.map(|retention| deleted_at + retention),
+                    requires: Vec::new(),
+                },
+            ))
+        })
+        .collect();
+
+    // a link or attachment went when the first of the nodes it needs did, and goes when it's purged
+    let hidden_by = |kind: TrashKind, id: Uuid, name: String, requires: Vec<Uuid>| {
+        let first = requires
+            .iter()
+            .filter_map(|node_id| trashed.get(node_id))
+            .min_by_key(|node| node.deleted_at)?;
+        Some(TrashItem {
+            kind,
+            id,
+            name,
+            deleted_at: first.deleted_at,
+            deleted_by: first.deleted_by.clone(),
+            purge_at: first.purge_at,
+            requires: requires
+                .into_iter()
+                .filter(|node_id| trashed.contains_key(node_id))
+                .map(|id| TrashRef {
+                    kind: TrashKind::Node,
+                    id,
+                })
+                .collect(),
+        })
+    };
+
+    let mut items: Vec<TrashItem> = trashed.values().cloned().collect();
+
+    let links = nodelink::Entity::find()
+        .filter(nodelink::Column::ProjectId.eq(project_id))
+        .filter(
+            Condition::any()
+                .add(nodelink::Column::Left.is_in(node_ids.clone()))
+                .add(nodelink::Column::Right.is_in(node_ids.clone())),
+        )
+        .all(conn)
+        .await?;
+    let ends: BTreeSet<Uuid> = links
+        .iter()
+        .flat_map(|link| [link.left, link.right])
+        .filter(|end| !trashed.contains_key(end))
+        .collect();
+    let mut names: HashMap<Uuid, String> = node::Entity::find()
+        .select_only()
+        .columns([node::Column::Id, node::Column::Display])
+        .filter(node::Column::Id.is_in(ends))
+        .into_tuple::<(Uuid, String)>()
+        .all(conn)
+        .await?
+        .into_iter()
+        .collect();
+    names.extend(nodes.iter().map(|node| (node.id, node.display.clone())));
+    let name = |id: &Uuid| names.get(id).map(String::as_str).unwrap_or_default();
+    items.extend(links.iter().filter_map(|link| {
+        hidden_by(
+            TrashKind::Nodelink,
+            link.id,
+            format!("{} → {}", name(&link.left), name(&link.right)),
+            vec![link.left, link.right],
+        )
+    }));
+
+    let attachments: Vec<(Uuid, Uuid, String)> = attachment::Entity::find()
+        .select_only()
+        .columns([
+            attachment::Column::Id,
+            attachment::Column::NodeId,
+            attachment::Column::Filename,
+        ])
+        .filter(attachment::Column::NodeId.is_in(node_ids))
+        .into_tuple()
+        .all(conn)
+        .await?;
+    items.extend(
+        attachments
+            .into_iter()
+            .filter_map(|(id, node_id, filename)| {
+                hidden_by(TrashKind::Attachment, id, filename, vec![node_id])
+            }),
+    );
+
+    items.sort_by(|a, b| {
+        b.deleted_at
+            .cmp(&a.deleted_at)
+            .then(a.kind.cmp(&b.kind))
+            .then(a.id.cmp(&b.id))
+    });
+    Ok(items)
+}
+
+/// Nodes in a project's trash, and the links and attachments hidden with them
+///
+/// Most recently deleted first. Items with something in `requires` can only be restored along
+/// with those items.
+#[utoipa::path(
+    get,
+    path = "/api/v1/project/{id}/trash",
+    tag = "nodes",
+    operation_id = "get_project_trash",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    responses(
+        (status = BAD_REQUEST, description = "Invalid path parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "No access to the project", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse),
+        (status = OK, description = "Everything in the trash", body = Vec<TrashItem>)
+    )
+)]
+pub async fn get_project_trash(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+) -> Result<Json<Vec<TrashItem>>, WebError> {
+    let reader = state.read().await;
+    let project = find_accessible_project(&reader.conn, id, auth_user.as_deref()).await?;
+    Ok(Json(
+        list_trash(&reader.conn, project.id, reader.trash_retention).await?,
+    ))
+}
+
+/// Restore several items from a project's trash at once
+///
+/// Nodes are restored first, then the links and attachments which needed them. Every item has
+/// to be in the project's trash and come with everything in its `requires`, otherwise nothing
+/// is restored and the 409 lists each item which couldn't be, under `failures`.
+#[utoipa::path(
+    post,
+    path = "/api/v1/project/{id}/trash/restore",
+    tag = "nodes",
+    operation_id = "restore_trash",
+    params(
+        ("id" = Uuid, Path, description = "Project ID")
+    ),
+    request_body = TrashRestoreRequest,
+    responses(
+        (status = BAD_REQUEST, description = "Invalid path parameter", body = ErrorResponse),
+        (status = FORBIDDEN, description = "No access to the project", body = ErrorResponse),
+        (status = NOT_FOUND, description = "Project not found", body = ErrorResponse),
+        (status = CONFLICT, description = "Some items couldn't be restored, nothing was", body = ErrorResponse),
+        (status = OK, description = "Everything was restored", body = TrashRestoreResponse)
+    )
+)]
+pub async fn restore_trash(
+    Path(id): Path<Uuid>,
+    State(state): State<SharedState>,
+    auth_user: Option<Extension<AuthUser>>,
+    Json(request): Json<TrashRestoreRequest>,
+) -> Result<Json<TrashRestoreResponse>, WebError> {
+    let txn = state.read().await.begin().await?;
+    let project = find_accessible_project(&txn, id, auth_user.as_deref()).await?;
+    let trash: HashMap<TrashRef, TrashItem> = list_trash(&txn, project.id, None)
+        .await?
+        .into_iter()
+        .map(|item| {
+            (
+                TrashRef {
+                    kind: item.kind,
+                    id: item.id,
+                },
+                item,
+            )
+        })
+        .collect();
+
+    let requested: BTreeSet<TrashRef> = request.items.into_iter().collect();
+    let failures: Vec<TrashRestoreFailure> = requested
+        .iter()
+        .filter_map(|item| {
+            let Some(trashed) = trash.get(item) else {
+                return Some(TrashRestoreFailure {
+                    kind: item.kind,
+                    id: item.id,
+                    error: format!("Not in the trash of project {}", project.id),
+                    missing: Vec::new(),
+                });
+            };
+            let missing: Vec<TrashRef> = trashed
+                .requires
+                .iter()
+                .filter(|required| !requested.contains(required))
+                .copied()
+                .collect();
+            (!missing.is_empty()).then(|| TrashRestoreFailure {
+                kind: item.kind,
+                id: item.id,
+                error: "Needs other items in the trash restored with it".to_string(),
+                missing,
+            })
+        })
+        .collect();
+    if !failures.is_empty() {
+        return Err(WebError::new(
+            StatusCode::CONFLICT,
+            format!(
+                "{} of {} items can't be restored",
+                failures.len(),
+                requested.len()
+            ),
+        )
+        .with_code("trash_restore_blocked")
+        .with_detail("failures", serde_json::to_value(failures)?));
+    }
+
+    // links and attachments come back with their nodes, there's nothing else to do for them
+    let node_ids: Vec<Uuid> = requested
+        .iter()
+        .filter(|item| item.kind == TrashKind::Node)
+        .map(|item| item.id)
+        .collect();
+    if !node_ids.is_empty() {
+        let changed_by = AuthUser::created_by(auth_user.as_deref());
+        for node in node::Entity::find()
+            .filter(node::Column::Id.is_in(node_ids.clone()))
+            .all(&txn)
+            .await?
+        {
+            node_history::ActiveModel::record(&node, NodeChange::Restore, changed_by.clone())?
+                .insert(&txn)
+                .await?;
+        }
+        node::Entity::update_many()
+            .col_expr(
+                node::Column::DeletedAt,
+                Expr::value(Option::<DateTime<Utc>>::None),
+            )
+            .filter(node::Column::Id.is_in(node_ids))
+            .exec(&txn)
+            .await?;
+    }
+    txn.commit().await?;
+    info!(
+        project_id = project.id.to_string(),
+        items = requested.len(),
+        "Restored items from the trash"
+    );
+    Ok(Json(TrashRestoreResponse {
+        restored: requested.into_iter().collect(),
+    }))
+}
+
+/// Purge every node which went in the trash more than `retention` ago, returning the nodes purged
+pub async fn purge_expired(
+    conn: &impl TransactionTrait,
+    retention: chrono::Duration,
+) -> Result<Vec<node::Model>, WebError> {
+    let txn = conn.begin().await?;
+    let expired = node::Entity::find()
+        .filter(node::Column::DeletedAt.lt(Utc::now() - retention))
+        .all(&txn)
+        .await?;
+    for node in &expired {
+        node_history::ActiveModel::record(node, NodeChange::Purge, None)?
+            .insert(&txn)
+            .await?;
+        purge_node(&txn, node.id).await?;
+    }
+    txn.commit().await?;
+    Ok(expired)
+}
+
+/// Spawns a task which purges expired nodes from the trash every `interval`, if the server has a
+/// trash retention period
+pub fn spawn_trash_purge(state: SharedState, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let reader = state.read().await;
+            let Some(retention) = reader.trash_retention else {
+                return;
+            };
+            match purge_expired(&reader.conn, retention).await {
+                Ok(purged) if purged.is_empty() => debug!("No nodes in the trash have expired"),
+                Ok(purged) => {
+                    reader.quota.invalidate(purged.iter().flat_map(|node| {
+                        [
+                            QuotaKind::NodesPerProject(node.project_id),
+                            QuotaKind::NodelinksPerProject(node.project_id),
+                        ]
+                    }));
+                    reader.quota.invalidate([QuotaKind::AttachmentBytes]);
+                    info!(purged = purged.len(), "Purged expired nodes from the trash");
+                }
+                Err(err) => error!(error = err.message(), "Trash purge failed"),
+            }
+        }
+    })
+}
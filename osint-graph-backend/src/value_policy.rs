@@ -0,0 +1,347 @@
+//! Enforcing the value policy on node writes
+//!
+//! Rules come from a TOML file passed via `--value-policy-file`, one `[[rule]]` table per rule:
+//!
+//! ```toml
+//! [[rule]]
+//! name = "credit_card"
+//! pattern = '\b(?:\d[ -]?){12,18}\d\b'
+//! action = "reject"        # reject, mask or warn
+//! node_types = ["person"]  # optional, defaults to every type
+//! luhn = true              # optional, only match numbers passing the Luhn check
+//! ```
+//!
+//! Every rule is checked against a node's display name, value and notes whenever a node is
+//! created or changed. Error messages name the rule and field, never the offending text.
+
+use std::{
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use axum::{extract::State, http::StatusCode, Json};
+use osint_graph_shared::{
+    error::OsintError,
+    node::NodeType,
+    policy::{mask_matches, PolicyAction, PolicyField, ValuePolicy, ValueRule},
+};
+use serde::{Deserialize, Serialize};
+use toml_edit::{DocumentMut, Item, Value};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::{entity::node, project::WebError, SharedState};
+
+/// Error code when a node breaks a `reject` rule
+pub const VALUE_POLICY_VIOLATION: &str = "value_policy_violation";
+
+/// A node which breaks a `reject` rule
+#[derive(Debug)]
+pub struct PolicyViolation {
+    pub rule: String,
+    pub field: PolicyField,
+}
+
+impl PolicyViolation {
+    pub fn message(&self) -> String {
+        format!(
+            "Node {} is not allowed by value policy rule {}",
+            self.field.as_str(),
+            self.rule
+        )
+    }
+}
+
+impl From<PolicyViolation> for WebError {
+    fn from(violation: PolicyViolation) -> Self {
+        WebError::new(StatusCode::UNPROCESSABLE_ENTITY, violation.message())
+            .with_code(VALUE_POLICY_VIOLATION)
+            .with_detail("rule", violation.rule)
+            .with_detail("field", violation.field.as_str())
+    }
+}
+
+#[derive(Default)]
+pub struct ValuePolicyEngine {
+    pub policy: ValuePolicy,
+    hits: Vec<AtomicU64>,
+}
+
+impl ValuePolicyEngine {
+    pub fn new(policy: ValuePolicy) -> Self {
+        Self {
+            hits: policy.rules.iter().map(|_| AtomicU64::new(0)).collect(),
+            policy,
+        }
+    }
+
+    /// Load rules from `path`, or no rules at all
+    pub fn load(path: Option<&Path>) -> Result<Self, OsintError> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let contents = std::fs::read_to_string(path).map_err(|err| {
+            OsintError::Configuration(format!(
+                "Failed to read value policy file {}: {}",
+                path.display(),
+                err
+            ))
+        })?;
+        let policy = parse_policy(&contents).map_err(|err| {
+            OsintError::Configuration(format!(
+                "Failed to parse value policy file {}: {}",
+                path.display(),
+                err
+            ))
+        })?;
+        info!(
+            rules = policy.rules.len(),
+            "Loaded value policy from {}",
+            path.display()
+        );
+        Ok(Self::new(policy))
+    }
+
+    /// Check a node against every rule before it's stored, masking it in place where needed
+    ///
+    /// If a `reject` rule matches, only that rule is counted and the node is left alone.
+    pub fn apply(&self, node: &mut node::Model) -> Result<(), PolicyViolation> {
+        let node_type = node.node_type;
+        for field in [PolicyField::Display, PolicyField::Value, PolicyField::Notes] {
+            let Some(text) = field_text(node, field) else {
+                continue;
+            };
+            if let Some(index) = self
+                .policy
+                .matching_rules(node_type, text)
+                .into_iter()
+                .find(|index| self.policy.rules[*index].action == PolicyAction::Reject)
+            {
+                self.hit(index);
+                let rule = &self.policy.rules[index];
+                warn!(
+                    rule = rule.name,
+                    node_id = node.id.to_string(),
+                    field = field.as_str(),
+                    "Rejected node breaking value policy"
+                );
+                return Err(PolicyViolation {
+                    rule: rule.name.clone(),
+                    field,
+                });
+            }
+        }
+
+        for field in [PolicyField::Display, PolicyField::Value, PolicyField::Notes] {
+            let Some(text) = field_text(node, field) else {
+                continue;
+            };
+            let mut masked = None;
+            for index in self.policy.matching_rules(node_type, text) {
+                self.hit(index);
+                let rule = &self.policy.rules[index];
+                match rule.action {
+                    PolicyAction::Mask => {
+                        info!(
+                            rule = rule.name,
+                            node_id = node.id.to_string(),
+                            field = field.as_str(),
+                            "Masked node breaking value policy"
+                        );
+                        let current: &str = masked.as_deref().unwrap_or(text);
+                        masked = Some(mask_matches(rule, current));
+                    }
+                    PolicyAction::Warn => warn!(
+                        rule = rule.name,
+                        node_id = node.id.to_string(),
+                        field = field.as_str(),
+                        "Stored node breaking value policy"
+                    ),
+                    PolicyAction::Reject => {}
+                }
+            }
+            if let Some(masked) = masked {
+                match field {
+                    PolicyField::Display => node.display = masked,
+                    PolicyField::Value => node.value = masked,
+                    PolicyField::Notes => node.notes = Some(masked),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn hit(&self, index: usize) {
+        if let Some(hits) = self.hits.get(index) {
+            hits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn report(&self) -> ValuePolicyReport {
+        ValuePolicyReport {
+            rules: self
+                .policy
+                .rules
+                .iter()
+                .zip(&self.hits)
+                .map(|(rule, hits)| ValueRuleReport {
+                    name: rule.name.clone(),
+                    pattern: rule.pattern.as_str().to_string(),
+                    node_types: rule.node_types.clone(),
+                    action: rule.action,
+                    luhn: rule.luhn,
+                    hits: hits.load(Ordering::Relaxed),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn field_text(node: &node::Model, field: PolicyField) -> Option<&str> {
+    match field {
+        PolicyField::Display => Some(&node.display),
+        PolicyField::Value => Some(&node.value),
+        PolicyField::Notes => node.notes.as_deref(),
+    }
+}
+
+/// Parse the `[[rule]]` tables of a value policy file
+pub fn parse_policy(contents: &str) -> Result<ValuePolicy, String> {
+    let doc = contents
+        .parse::<DocumentMut>()
+        .map_err(|err| err.to_string())?;
+    let Some(rules) = doc.get("rule") else {
+        return Ok(ValuePolicy::default());
+    };
+    let rules = rules
+        .as_array_of_tables()
+        .ok_or("`rule` must be an array of tables, use [[rule]]")?;
+
+    let mut res = ValuePolicy::default();
+    for (index, table) in rules.iter().enumerate() {
+        if let Some((key, _)) = table
+            .iter()
+            .find(|(key, _)| !matches!(*key, "name" | "pattern" | "action" | "node_types" | "luhn"))
+        {
+            return Err(format!("rule {index}: unknown key {key:?}"));
+        }
+        let string = |key: &str| -> Result<&str, String> {
+            table
+                .get(key)
+                .and_then(Item::as_str)
+                .ok_or_else(|| format!("rule {index}: `{key}` must be a string"))
+        };
+        let name = string("name")?;
+        let action = string("action")?
+            .parse::<PolicyAction>()
+            .map_err(|err| format!("rule {name}: {err:?}"))?;
+        let node_types = match table.get("node_types") {
+            None => Vec::new(),
+            Some(item) => item
+                .as_array()
+                .ok_or_else(|| format!("rule {name}: `node_types` must be a list"))?
+                .iter()
+                .map(|value| {
+                    let node_type = value
+                        .as_str()
+                        .ok_or_else(|| format!("rule {name}: node types must be strings"))?;
+                    node_type
+                        .parse::<NodeType>()
+                        .map_err(|_| format!("rule {name}: unknown node type {node_type:?}"))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+        let luhn = match table.get("luhn") {
+            None => false,
+            Some(item) => item
+                .as_value()
+                .and_then(Value::as_bool)
+                .ok_or_else(|| format!("rule {name}: `luhn` must be true or false"))?,
+        };
+        if res.rules.iter().any(|rule| rule.name == name) {
+            return Err(format!("rule {name} is defined more than once"));
+        }
+        res.rules.push(
+            ValueRule::new(name, string("pattern")?, node_types, action, luhn)
+                .map_err(|err| format!("{err:?}"))?,
+        );
+    }
+    Ok(res)
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ValueRuleReport {
+    pub name: String,
+    pub pattern: String,
+    /// Node types the rule applies to, every type if empty
+    pub node_types: Vec<NodeType>,
+    pub action: PolicyAction,
+    pub luhn: bool,
+    /// Matches since the server started, including rejected writes
+    pub hits: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ValuePolicyReport {
+    pub rules: Vec<ValueRuleReport>,
+}
+
+/// The loaded value policy rules and how often each has matched
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/value-policy",
+    tag = "admin",
+    operation_id = "get_value_policy",
+    responses(
+        (status = OK, description = "Loaded rules with hit counters", body = ValuePolicyReport)
+    )
+)]
+pub async fn get_value_policy(State(state): State<SharedState>) -> Json<ValuePolicyReport> {
+    Json(state.read().await.value_policy.report())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_policy() {
+        let policy = parse_policy(
+            r#"
+            [[rule]]
+            name = "credit_card"
+            pattern = '\b(?:\d[ -]?){12,18}\d\b'
+            action = "reject"
+            luhn = true
+
+            [[rule]]
+            name = "tfn"
+            pattern = '\b\d{3} ?\d{3} ?\d{3}\b'
+            action = "mask"
+            node_types = ["person", "document"]
+            "#,
+        )
+        .expect("valid policy");
+        assert_eq!(policy.rules.len(), 2);
+        assert_eq!(policy.rules[0].action, PolicyAction::Reject);
+        assert!(policy.rules[0].luhn);
+        assert!(policy.rules[0].node_types.is_empty());
+        assert_eq!(
+            policy.rules[1].node_types,
+            vec![NodeType::Person, NodeType::Document]
+        );
+
+        assert_eq!(parse_policy("").map(|p| p.rules.len()), Ok(0));
+        for broken in [
+            "rule = 1",
+            "[[rule]]\nname = \"a\"\npattern = \"(\"\naction = \"warn\"",
+            "[[rule]]\nname = \"a\"\npattern = \"x\"\naction = \"delete\"",
+            "[[rule]]\nname = \"a\"\npattern = \"x\"\naction = \"warn\"\nnode_types = [\"cat\"]",
+            "[[rule]]\nname = \"a\"\npattern = \"x\"\naction = \"warn\"\nregex = \"y\"",
+            "[[rule]]\nname = \"a\"\npattern = \"x\"\naction = \"warn\"\n[[rule]]\nname = \"a\"\npattern = \"y\"\naction = \"warn\"",
+            "[[rule]]\npattern = \"x\"\naction = \"warn\"",
+        ] {
+            assert!(parse_policy(broken).is_err(), "{broken}");
+        }
+    }
+}
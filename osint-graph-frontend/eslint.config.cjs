@@ -27,6 +27,8 @@ module.exports = defineConfig([
 		"**/.eslintrc.cjs",
 		"**/vite.config.ts",
 		"**/eslint.config.cjs",
+		"src/**/*.test.ts",
+		"src/**/*.bench.ts",
 	]),
 	{
 		languageOptions: {
@@ -1,8 +1,16 @@
 import type React from "react";
-import { useCallback, useEffect, useId, useRef, useState } from "react";
+import {
+	useCallback,
+	useEffect,
+	useId,
+	useMemo,
+	useRef,
+	useState,
+} from "react";
 import ReactFlow, {
 	addEdge,
 	Background,
+	ControlButton,
 	Controls,
 	type Edge,
 	MiniMap,
@@ -14,6 +22,7 @@ import ReactFlow, {
 	useEdgesState,
 	useNodesState,
 	useReactFlow,
+	useStore,
 } from "reactflow";
 import "reactflow/dist/style.css";
 import toast, { Toaster } from "react-hot-toast";
@@ -28,6 +37,8 @@ import {
 	downloadAttachment,
 	exportProject,
 	exportProjectMermaid,
+	fetchCapabilities,
+	fetchNodeTypeStyles,
 	fetchProjects,
 	listAttachments,
 	setAuthFailureCallback,
@@ -42,9 +53,17 @@ import { NodeSearch } from "./components/NodeSearch";
 import { ProjectManagementDialog } from "./components/ProjectManagementDialog";
 import { ProjectMismatchDialog } from "./components/ProjectMismatchDialog";
 import { ProjectSelector } from "./components/ProjectSelector";
+import { RenderStats } from "./components/RenderStats";
 import { AuthProvider, useAuth } from "./contexts/AuthContext";
-import type { Attachment, OSINTNode, Project } from "./types";
-import { getNodeColor, hasSyncedValue, NodeTypeInfo } from "./types";
+import { isZoomedOut, withLevelOfDetail } from "./lod";
+import { nodeTypes as graphNodeTypes } from "./nodes";
+import type { Attachment, Capabilities, OSINTNode, Project } from "./types";
+import {
+	applyNodeTypeStyles,
+	getNodeColor,
+	hasSyncedValue,
+	NodeTypeInfo,
+} from "./types";
 import "./osint-graph.css";
 
 const initialNodes: Node[] = [];
@@ -57,6 +76,13 @@ function AppContent() {
 	const [nodes, setNodes, onNodesChange] = useNodesState(initialNodes);
 	const [edges, setEdges, onEdgesChange] = useEdgesState(initialEdges);
 	const { screenToFlowPosition, setCenter, getZoom } = useReactFlow();
+	// only changes when the zoom crosses the threshold, so zooming doesn't re-render the app
+	const zoomedOut = useStore((state) => isZoomedOut(state.transform[2]));
+	const renderedNodes = useMemo(
+		() => withLevelOfDetail(nodes, zoomedOut),
+		[nodes, zoomedOut],
+	);
+	const [showRenderStats, setShowRenderStats] = useState(false);
 	const { requireLogin } = useAuth();
 	const [isPanelCollapsed, setIsPanelCollapsed] = useState(false);
 	const [editingNode, setEditingNode] = useState<string | null>(null);
@@ -382,7 +408,33 @@ function AppContent() {
 		[setEdges, saveHistory],
 	);
 
-	const getNodeColorCallBack = useCallback(getNodeColor, []);
+	// Features and limits of this server, unknown until the request comes back
+	const [capabilities, setCapabilities] = useState<Capabilities | null>(null);
+	useEffect(() => {
+		fetchCapabilities()
+			.then(setCapabilities)
+			.catch((error) => {
+				console.error("Failed to load server capabilities:", error);
+			});
+	}, []);
+
+	// Bumped when the server's node type styles arrive, so colours are recalculated
+	const [nodeTypeStylesVersion, setNodeTypeStylesVersion] = useState(0);
+	useEffect(() => {
+		fetchNodeTypeStyles()
+			.then((styles) => {
+				applyNodeTypeStyles(styles);
+				setNodeTypeStylesVersion((version) => version + 1);
+			})
+			.catch((error) => {
+				console.error("Failed to load node type styles:", error);
+			});
+	}, []);
+
+	// biome-ignore lint/correctness/useExhaustiveDependencies: colours change when the styles load
+	const getNodeColorCallBack = useCallback(getNodeColor, [
+		nodeTypeStylesVersion,
+	]);
 
 	/** Helper function to load data for a project */
 	const loadProjectData = useCallback(
@@ -482,6 +534,9 @@ function AppContent() {
 		initializeProject();
 	}, [currentProject, showMismatchDialog, loadProjectData]);
 
+	// Node to select once its project's nodes have loaded, from a deep link
+	const [deepLinkNodeId, setDeepLinkNodeId] = useState<string | null>(null);
+
 	// Handle URL fragments for deep linking
 	useEffect(() => {
 		const handleFragmentChange = async () => {
@@ -491,6 +546,7 @@ function AppContent() {
 			const params = new URLSearchParams(hash);
 			const projectId = params.get("project");
 			const viewType = params.get("view");
+			const nodeId = params.get("node");
 
 			if (projectId) {
 				// Load the specified project
@@ -499,6 +555,9 @@ function AppContent() {
 					if (project) {
 						setCurrentProject(project);
 						localStorage.setItem(PROJECT_ID_KEY, projectId);
+						if (nodeId) {
+							setDeepLinkNodeId(nodeId);
+						}
 
 						// Handle view type
 						if (viewType === "mermaid") {
@@ -1060,6 +1119,14 @@ function AppContent() {
 				toast.error("No file selected for upload");
 				return;
 			}
+			const maxUploadBytes = capabilities?.limits.max_upload_bytes;
+			if (maxUploadBytes && file.size > maxUploadBytes) {
+				toast.error(
+					`${file.name} is too large, the limit is ${Math.floor(maxUploadBytes / (1024 * 1024))}MB`,
+				);
+				event.target.value = "";
+				return;
+			}
 			setUploadingAttachment(true);
 
 			try {
@@ -1077,7 +1144,7 @@ function AppContent() {
 				event.target.value = "";
 			}
 		},
-		[editingNode],
+		[editingNode, capabilities],
 	);
 
 	const handleDownloadAttachment = useCallback(
@@ -1176,11 +1243,21 @@ function AppContent() {
 		[nodes, setCenter, getZoom, setNodes],
 	);
 
+	useEffect(() => {
+		if (deepLinkNodeId && nodes.some((n) => n.id === deepLinkNodeId)) {
+			handleNodeSelect(deepLinkNodeId);
+			setDeepLinkNodeId(null);
+		}
+	}, [deepLinkNodeId, nodes, handleNodeSelect]);
+
 	const handleGlobalNodeSelect = useCallback(
-		async (nodeId: string, projectId: string) => {
+		async (nodeId: string | null, projectId: string) => {
 			try {
 				// Switch to the target project
 				await handleProjectChange(projectId);
+				if (!nodeId) {
+					return;
+				}
 
 				// Wait a bit for the project to load, then center on the node
 				setTimeout(() => {
@@ -1200,6 +1277,11 @@ function AppContent() {
 		return `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
 	};
 
+	const formatDuration = (ms: number): string => {
+		const seconds = Math.round(ms / 1000);
+		return `${Math.floor(seconds / 60)}:${String(seconds % 60).padStart(2, "0")}`;
+	};
+
 	const isViewableFile = (contentType: string, filename: string): boolean => {
 		// Images
 		if (contentType.startsWith("image/")) return true;
@@ -1207,6 +1289,9 @@ function AppContent() {
 		if (contentType === "application/pdf") return true;
 		// Text files
 		if (contentType.startsWith("text/")) return true;
+		// Audio and video, played inline by the browser
+		if (contentType.startsWith("audio/") || contentType.startsWith("video/"))
+			return true;
 		// JSON, XML
 		if (contentType === "application/json" || contentType === "application/xml")
 			return true;
@@ -1379,11 +1464,13 @@ function AppContent() {
 					currentProject={currentProject}
 					onProjectUpdate={handleProjectUpdate}
 					onProjectDelete={handleProjectDelete}
+					exportFormats={capabilities?.export_formats}
 				/>
 			)}
 
 			<ReactFlow
-				nodes={nodes}
+				nodes={renderedNodes}
+				nodeTypes={graphNodeTypes}
 				edges={edges}
 				onNodesChange={handleNodesChange}
 				onEdgesChange={handleEdgesChange}
@@ -1391,14 +1478,23 @@ function AppContent() {
 				onNodeDragStart={onNodeDragStart}
 				onNodeDoubleClick={handleNodeDoubleClick}
 				onNodeContextMenu={handleNodeContextMenu}
+				onlyRenderVisibleElements
 				fitView
 				className={
 					movingAttachment ? "react-flow-crosshair" : "react-flow-default"
 				}
 			>
-				<Controls />
+				<Controls>
+					<ControlButton
+						onClick={() => setShowRenderStats((show) => !show)}
+						title="Toggle rendered node counts"
+					>
+						#
+					</ControlButton>
+				</Controls>
 				<MiniMap />
 				<Background />
+				{showRenderStats && <RenderStats />}
 			</ReactFlow>
 
 			{/* Right-side collapsible panel for adding nodes */}
@@ -1520,6 +1616,10 @@ function AppContent() {
 													</div>
 													<div className="attachment-size">
 														{formatFileSize(attachment.size)}
+														{attachment.media?.duration_ms !== undefined &&
+															` · ${formatDuration(attachment.media.duration_ms)}`}
+														{attachment.media?.width !== undefined &&
+															` · ${attachment.media.width}×${attachment.media.height}`}
 													</div>
 												</div>
 												<div className="attachment-actions">
@@ -3,8 +3,12 @@ import axios, { type AxiosResponse } from "axios";
 import { v4 as uuidv4 } from "uuid";
 import type {
 	Attachment,
+	Capabilities,
+	NodeAttachmentSummary,
 	NodeLink,
+	NodeTypeStyle,
 	OSINTNode,
+	PaginatedResponse,
 	Project,
 	ProjectExport,
 	SearchResult,
@@ -16,6 +20,9 @@ const NODE_URL = "/api/v1/node";
 const ATTACHMENT_URL = "/api/v1/attachment";
 const NODELINK_URL = "/api/v1/nodelink";
 const SEARCH_URL = "/api/v1/search";
+const NODE_TYPE_STYLES_URL = "/api/v1/node-type-styles";
+const CAPABILITIES_URL = "/api/v1/capabilities";
+const RENDER_MARKDOWN_URL = "/api/v1/render-markdown";
 
 // Authentication callback that will be set by the AuthContext
 let authFailureCallback: (() => void) | null = null;
@@ -53,9 +60,28 @@ axios.interceptors.response.use(
 	},
 );
 
+// The biggest page the backend will return
+const MAX_PAGE_SIZE = 1000;
+
+// Walk a paginated list until every item has been fetched
+const fetchAllPages = async <T,>(url: string): Promise<T[]> => {
+	const items: T[] = [];
+	for (let page = 1; ; page++) {
+		const response = await axios.get<PaginatedResponse<T>>(url, {
+			params: { page, page_size: MAX_PAGE_SIZE },
+		});
+		items.push(...response.data.items);
+		if (
+			response.data.items.length === 0 ||
+			items.length >= response.data.total_count
+		) {
+			return items;
+		}
+	}
+};
+
 export const fetchProjects = async (): Promise<Project[]> => {
-	const response = await axios.get<Project[]>(PROJECTS_URL);
-	return response.data;
+	return fetchAllPages<Project>(PROJECTS_URL);
 };
 
 export const newProject = async (): Promise<AxiosResponse<Project, string>> => {
@@ -102,10 +128,7 @@ export const updateNode = async (
 export const fetchNodesByProject = async (
 	projectId: string,
 ): Promise<OSINTNode[]> => {
-	const response = await axios.get<OSINTNode[]>(
-		`${PROJECT_URL}/${projectId}/nodes`,
-	);
-	return response.data;
+	return fetchAllPages<OSINTNode>(`${PROJECT_URL}/${projectId}/nodes`);
 };
 
 export const createProject = async (
@@ -151,6 +174,35 @@ export const fetchNodeLinksByProject = async (
 	return response.data;
 };
 
+/** Attachment count and size for each node in a project which has any */
+export const fetchAttachmentSummary = async (
+	projectId: string,
+): Promise<NodeAttachmentSummary[]> => {
+	const response = await axios.get<NodeAttachmentSummary[]>(
+		`${PROJECT_URL}/${projectId}/attachment-summary`,
+	);
+	return response.data;
+};
+
+/** Copy a node into `projectId`, or its own project when that's left out */
+export const copyNode = async (
+	nodeId: string,
+	projectId?: string,
+	includeAttachments = false,
+): Promise<OSINTNode> => {
+	const response = await axios.post<OSINTNode>(
+		`${NODE_URL}/${nodeId}/copy`,
+		undefined,
+		{
+			params: {
+				...(projectId && { project_id: projectId }),
+				include_attachments: includeAttachments,
+			},
+		},
+	);
+	return response.data;
+};
+
 export const deleteNode = async (nodeId: string): Promise<void> => {
 	await axios.delete(`${NODE_URL}/${nodeId}`);
 };
@@ -170,6 +222,70 @@ export const updateProject = async (
 	return response.data;
 };
 
+/** Pin or unpin a project, pinned projects are listed first */
+export const setProjectPinned = async (
+	projectId: string,
+	pinned: boolean,
+): Promise<Project> => {
+	const action = pinned ? "pin" : "unpin";
+	const response = await axios.post<Project>(
+		`${PROJECT_URL}/${projectId}/${action}`,
+	);
+	return response.data;
+};
+
+/** Archive or unarchive a project, archived projects are left out of the project list */
+export const setProjectArchived = async (
+	projectId: string,
+	archived: boolean,
+): Promise<Project> => {
+	const action = archived ? "archive" : "unarchive";
+	const response = await axios.patch<Project>(
+		`${PROJECT_URL}/${projectId}/${action}`,
+	);
+	return response.data;
+};
+
+export type MarkdownContext = "node_note" | "comment" | "case_note";
+
+/** Sanitised HTML for markdown, rendered by the server so the rules match everywhere */
+export const renderMarkdown = async (
+	text: string,
+	context: MarkdownContext,
+): Promise<{ html: string; hash: string }> => {
+	const response = await axios.post<{ html: string; hash: string }>(
+		RENDER_MARKDOWN_URL,
+		{ text, context },
+	);
+	return response.data;
+};
+
+export const fetchNodeTypeStyles = async (): Promise<
+	Record<string, NodeTypeStyle>
+> => {
+	const response = await axios.get<Record<string, NodeTypeStyle>>(
+		NODE_TYPE_STYLES_URL,
+	);
+	return response.data;
+};
+
+// Capabilities only change when the server restarts, so they're fetched once per page load
+let capabilitiesRequest: Promise<Capabilities> | null = null;
+
+export const fetchCapabilities = (): Promise<Capabilities> => {
+	if (!capabilitiesRequest) {
+		capabilitiesRequest = axios
+			.get<Capabilities>(CAPABILITIES_URL)
+			.then((response) => response.data)
+			.catch((error) => {
+				// let the next caller try again
+				capabilitiesRequest = null;
+				throw error;
+			});
+	}
+	return capabilitiesRequest;
+};
+
 export const deleteProject = async (projectId: string): Promise<void> => {
 	await axios.delete(`${PROJECT_URL}/${projectId}`);
 };
@@ -240,6 +356,19 @@ export const updateAttachment = async (
 	return response.data;
 };
 
+/** Rename an attachment and/or change its content type */
+export const renameAttachment = async (
+	attachmentId: string,
+	filename: string,
+	contentType?: string,
+): Promise<Attachment> => {
+	const response = await axios.patch<Attachment>(
+		`${ATTACHMENT_URL}/${attachmentId}`,
+		{ filename, content_type: contentType },
+	);
+	return response.data;
+};
+
 /** List all attachments for a node */
 export const listAttachments = async (
 	nodeId: string,
@@ -7,7 +7,7 @@ import { getNodeColor } from "../types";
 interface NodeSearchProps {
 	nodes: Node[];
 	onNodeSelect: (nodeId: string) => void;
-	onGlobalResultSelect: (nodeId: string, projectId: string) => void;
+	onGlobalResultSelect: (nodeId: string | null, projectId: string) => void;
 	currentProjectId: string | null;
 	projects: Map<string, string>; // projectId -> projectName
 }
@@ -67,7 +67,8 @@ export function NodeSearch({
 	// Search globally across all projects
 	const performGlobalSearch = useCallback(
 		async (term: string) => {
-			if (!term.trim()) {
+			// the server refuses queries shorter than this
+			if (term.trim().length < 2) {
 				setGlobalResults([]);
 				return;
 			}
@@ -106,7 +107,10 @@ export function NodeSearch({
 	};
 
 	// Handle clicking on a global search result
-	const handleGlobalResultClick = (nodeId: string, projectId: string) => {
+	const handleGlobalResultClick = (
+		nodeId: string | null,
+		projectId: string,
+	) => {
 		onGlobalResultSelect(nodeId, projectId);
 		setSearchTerm("");
 		setLocalResults([]);
@@ -222,7 +226,10 @@ export function NodeSearch({
 									nodeType = result.result_type.Node;
 									nodeColor = getNodeColor(nodeType);
 									typeLabel = nodeType;
-								} else if (result.result_type === "Project") {
+								} else if (
+									result.result_type === "Project" ||
+									result.result_type === "EmptyProject"
+								) {
 									typeLabel = "project";
 									nodeColor = "#3b82f6"; // blue for projects
 								} else if (result.result_type === "Attachment") {
@@ -236,12 +243,20 @@ export function NodeSearch({
 										type="button"
 										className="node-search-result-item"
 										onClick={() =>
-											handleGlobalResultClick(result.id, result.project_id)
+											handleGlobalResultClick(
+												result.result_type === "EmptyProject" ? null : result.id,
+												result.project_id,
+											)
 										}
 									>
 										<div className="node-search-result-title">
 											{result.title}
 										</div>
+										{result.snippet && (
+											<div className="node-search-result-snippet">
+												{result.snippet}
+											</div>
+										)}
 										<div className="node-search-result-meta">
 											{typeLabel && (
 												<span
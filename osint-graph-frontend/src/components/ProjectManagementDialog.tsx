@@ -17,13 +17,24 @@ interface ProjectManagementDialogProps {
 	currentProject: Project | null;
 	onProjectUpdate: (project: Project) => void;
 	onProjectDelete: () => void;
+	/** Formats the server can export, everything is shown until they're known */
+	exportFormats?: string[];
 }
 
 type TabType = "general" | "export" | "import" | "delete";
 
 export const ProjectManagementDialog: React.FC<
 	ProjectManagementDialogProps
-> = ({ isOpen, onClose, currentProject, onProjectUpdate, onProjectDelete }) => {
+> = ({
+	isOpen,
+	onClose,
+	currentProject,
+	onProjectUpdate,
+	onProjectDelete,
+	exportFormats,
+}) => {
+	const canExport = (format: string) =>
+		exportFormats === undefined || exportFormats.includes(format);
 	const [activeTab, setActiveTab] = useState<TabType>("general");
 	const [loading, setLoading] = useState(false);
 
@@ -384,23 +395,27 @@ export const ProjectManagementDialog: React.FC<
 									{loading ? "Exporting..." : "Export as JSON"}
 								</button>
 
-								<button
-									type="button"
-									onClick={handleExportMermaid}
-									disabled={loading}
-									className="btn btn-primary"
-								>
-									{loading ? "Exporting..." : "Export as Mermaid"}
-								</button>
-
-								<button
-									type="button"
-									onClick={handleViewMermaid}
-									disabled={loading}
-									className="btn btn-primary"
-								>
-									{loading ? "Loading..." : "View as Mermaid"}
-								</button>
+								{canExport("mermaid") && (
+									<>
+										<button
+											type="button"
+											onClick={handleExportMermaid}
+											disabled={loading}
+											className="btn btn-primary"
+										>
+											{loading ? "Exporting..." : "Export as Mermaid"}
+										</button>
+
+										<button
+											type="button"
+											onClick={handleViewMermaid}
+											disabled={loading}
+											className="btn btn-primary"
+										>
+											{loading ? "Loading..." : "View as Mermaid"}
+										</button>
+									</>
+								)}
 							</div>
 						</div>
 					)}
@@ -0,0 +1,25 @@
+import type { ReactFlowState } from "reactflow";
+import { Panel, useStore } from "reactflow";
+import { countRendered, type RenderCounts, visibleRect } from "../lod";
+
+const selectCounts = (state: ReactFlowState): RenderCounts => {
+	const [x, y, zoom] = state.transform;
+	return countRendered(
+		state.getNodes(),
+		visibleRect({ x, y, zoom }, state.width, state.height),
+	);
+};
+
+const sameCounts = (a: RenderCounts, b: RenderCounts) =>
+	a.rendered === b.rendered && a.culled === b.culled;
+
+/** How many nodes are on screen and how many were skipped, recounted as the view moves */
+export function RenderStats() {
+	const { rendered, culled } = useStore(selectCounts, sameCounts);
+
+	return (
+		<Panel position="top-left" className="render-stats">
+			{rendered} rendered / {culled} culled
+		</Panel>
+	);
+}
@@ -0,0 +1,48 @@
+// Times the per-frame work for big graphs: `npm run bench`
+import type { Node } from "reactflow";
+import { countRendered, visibleRect, withLevelOfDetail } from "./lod.ts";
+
+const ITERATIONS = 200;
+
+/** Nodes on a square grid 250px apart, the screen covers a small corner of it */
+function grid(count: number): Node[] {
+	const side = Math.ceil(Math.sqrt(count));
+	return Array.from({ length: count }, (_, i) => ({
+		id: `${i}`,
+		type: "default",
+		position: { x: (i % side) * 250, y: Math.floor(i / side) * 250 },
+		data: { label: `node ${i}` },
+		width: 208,
+		height: 40,
+	}));
+}
+
+function bench(name: string, run: () => unknown) {
+	run();
+	const start = performance.now();
+	for (let i = 0; i < ITERATIONS; i++) {
+		run();
+	}
+	const perRun = (performance.now() - start) / ITERATIONS;
+	console.log(`${name.padEnd(44)} ${perRun.toFixed(3)} ms`);
+}
+
+const rect = visibleRect({ x: 0, y: 0, zoom: 1 }, 1920, 1080);
+for (const count of [1_000, 10_000, 50_000]) {
+	const nodes = grid(count);
+	const { rendered, culled } = countRendered(nodes, rect);
+	console.log(`${count} nodes, ${rendered} rendered, ${culled} culled`);
+	bench("  countRendered", () => countRendered(nodes, rect));
+	bench("  withLevelOfDetail (zoomed in)", () =>
+		withLevelOfDetail(nodes, false),
+	);
+	bench("  withLevelOfDetail (zoomed out)", () =>
+		withLevelOfDetail(nodes, true),
+	);
+	bench("  withLevelOfDetail (zoomed out, new nodes)", () =>
+		withLevelOfDetail(
+			nodes.map((node) => ({ ...node })),
+			true,
+		),
+	);
+}
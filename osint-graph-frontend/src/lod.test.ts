@@ -0,0 +1,112 @@
+import assert from "node:assert/strict";
+import { describe, it } from "node:test";
+import type { Node } from "reactflow";
+import {
+	countRendered,
+	DOT_ZOOM_THRESHOLD,
+	isNodeRendered,
+	isZoomedOut,
+	visibleRect,
+	withLevelOfDetail,
+} from "./lod.ts";
+
+const node = (x: number, y: number, extra: Partial<Node> = {}): Node => ({
+	id: `${x},${y}`,
+	type: "default",
+	position: { x, y },
+	data: {},
+	width: 100,
+	height: 40,
+	className: "react-node",
+	...extra,
+});
+
+// an 800x600 screen showing graph x 0..1600, y 0..1200
+const rect = visibleRect({ x: 0, y: 0, zoom: 0.5 }, 800, 600);
+
+describe("visibleRect", () => {
+	it("converts the screen to graph coordinates", () => {
+		assert.deepEqual(visibleRect({ x: -200, y: 100, zoom: 2 }, 800, 600), {
+			x: 100,
+			y: -50,
+			width: 400,
+			height: 300,
+		});
+	});
+});
+
+describe("isNodeRendered", () => {
+	it("renders nodes on screen", () => {
+		assert.ok(isNodeRendered(node(500, 500), rect));
+	});
+
+	it("renders nodes only partly on screen", () => {
+		assert.ok(isNodeRendered(node(-50, -20), rect));
+		assert.ok(isNodeRendered(node(1550, 1180), rect));
+	});
+
+	it("culls nodes off screen", () => {
+		assert.ok(!isNodeRendered(node(-100, 500), rect));
+		assert.ok(!isNodeRendered(node(1600, 500), rect));
+		assert.ok(!isNodeRendered(node(500, 1200), rect));
+	});
+
+	it("uses the absolute position for child nodes", () => {
+		const child = node(0, 0, { positionAbsolute: { x: 5000, y: 5000 } });
+		assert.ok(!isNodeRendered(child, rect));
+	});
+
+	it("always renders unmeasured and dragged nodes", () => {
+		assert.ok(isNodeRendered(node(5000, 5000, { width: null }), rect));
+		assert.ok(isNodeRendered(node(5000, 5000, { dragging: true }), rect));
+	});
+
+	it("never renders hidden nodes", () => {
+		assert.ok(!isNodeRendered(node(500, 500, { hidden: true }), rect));
+	});
+});
+
+describe("countRendered", () => {
+	it("splits the nodes into rendered and culled", () => {
+		const nodes = [node(0, 0), node(500, 500), node(5000, 0), node(0, -500)];
+		assert.deepEqual(countRendered(nodes, rect), { rendered: 2, culled: 2 });
+	});
+
+	it("counts nothing for an empty graph", () => {
+		assert.deepEqual(countRendered([], rect), { rendered: 0, culled: 0 });
+	});
+});
+
+describe("isZoomedOut", () => {
+	it("switches at the threshold", () => {
+		assert.ok(isZoomedOut(DOT_ZOOM_THRESHOLD - 0.01));
+		assert.ok(!isZoomedOut(DOT_ZOOM_THRESHOLD));
+		assert.ok(!isZoomedOut(1));
+	});
+});
+
+describe("withLevelOfDetail", () => {
+	it("leaves the nodes alone when zoomed in", () => {
+		const nodes = [node(0, 0)];
+		assert.equal(withLevelOfDetail(nodes, false), nodes);
+	});
+
+	it("draws default nodes as dots when zoomed out", () => {
+		const [dot] = withLevelOfDetail([node(0, 0)], true);
+		assert.equal(dot?.type, "dot");
+		assert.equal(dot?.className, "react-node-dot");
+		assert.deepEqual(dot?.position, { x: 0, y: 0 });
+	});
+
+	it("reuses the dot for an unchanged node", () => {
+		const nodes = [node(0, 0)];
+		const [first] = withLevelOfDetail(nodes, true);
+		assert.equal(withLevelOfDetail(nodes, true)[0], first);
+		assert.notEqual(withLevelOfDetail([node(0, 0)], true)[0], first);
+	});
+
+	it("leaves other node types alone", () => {
+		const logger = node(0, 0, { type: "position-logger" });
+		assert.equal(withLevelOfDetail([logger], true)[0], logger);
+	});
+});
@@ -0,0 +1,92 @@
+import type { Node, Viewport } from "reactflow";
+
+/** Below this zoom nodes are drawn as coloured dots, their labels are unreadable anyway */
+export const DOT_ZOOM_THRESHOLD = 0.4;
+
+export interface Rect {
+	x: number;
+	y: number;
+	width: number;
+	height: number;
+}
+
+export interface RenderCounts {
+	rendered: number;
+	culled: number;
+}
+
+/** The part of the graph on screen, in graph coordinates */
+export function visibleRect(
+	viewport: Viewport,
+	width: number,
+	height: number,
+): Rect {
+	return {
+		x: -viewport.x / viewport.zoom,
+		y: -viewport.y / viewport.zoom,
+		width: width / viewport.zoom,
+		height: height / viewport.zoom,
+	};
+}
+
+/**
+ * Whether ReactFlow draws the node with onlyRenderVisibleElements on
+ *
+ * Mirrors its getNodesInside: hidden nodes never are, nodes it hasn't measured yet and nodes
+ * being dragged always are, and everything else is if any of it overlaps the screen.
+ */
+export function isNodeRendered(node: Node, rect: Rect): boolean {
+	if (node.hidden) {
+		return false;
+	}
+	if (node.width == null || node.height == null || node.dragging) {
+		return true;
+	}
+	const { x, y } = node.positionAbsolute ?? node.position;
+	return (
+		x < rect.x + rect.width &&
+		x + node.width > rect.x &&
+		y < rect.y + rect.height &&
+		y + node.height > rect.y
+	);
+}
+
+export function countRendered(nodes: Node[], rect: Rect): RenderCounts {
+	let rendered = 0;
+	for (const node of nodes) {
+		if (isNodeRendered(node, rect)) {
+			rendered++;
+		}
+	}
+	return { rendered, culled: nodes.length - rendered };
+}
+
+export function isZoomedOut(zoom: number): boolean {
+	return zoom < DOT_ZOOM_THRESHOLD;
+}
+
+/** Dot copies of nodes, kept while the node object is, so an unchanged node stays the same object */
+const dots = new WeakMap<Node, Node>();
+
+/**
+ * Swap the labelled nodes for dots when zoomed out
+ *
+ * Returns the same array when zoomed in, so only crossing the threshold makes ReactFlow
+ * re-render every node.
+ */
+export function withLevelOfDetail(nodes: Node[], zoomedOut: boolean): Node[] {
+	if (!zoomedOut) {
+		return nodes;
+	}
+	return nodes.map((node) => {
+		if (node.type !== "default") {
+			return node;
+		}
+		let dot = dots.get(node);
+		if (!dot) {
+			dot = { ...node, type: "dot", className: "react-node-dot" };
+			dots.set(node, dot);
+		}
+		return dot;
+	});
+}
@@ -0,0 +1,16 @@
+import type { NodeProps } from "reactflow";
+import { Handle, Position } from "reactflow";
+
+export type DotNodeData = {
+	label?: string;
+};
+
+/** What a node is drawn as when zoomed out, the colour comes from the node's style */
+export function DotNode({ data }: NodeProps<DotNodeData>) {
+	return (
+		<div className="react-node-dot-body" title={data.label}>
+			<Handle type="target" position={Position.Top} />
+			<Handle type="source" position={Position.Bottom} />
+		</div>
+	);
+}
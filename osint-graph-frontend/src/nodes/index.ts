@@ -1,4 +1,5 @@
 import type { Node, NodeTypes } from "reactflow";
+import { DotNode } from "./DotNode";
 import { PositionLoggerNode } from "./PositionLoggerNode";
 
 export interface CustomNode extends Node {
@@ -9,4 +10,5 @@ export const initialNodes: CustomNode[] = [];
 
 export const nodeTypes = {
 	"position-logger": PositionLoggerNode,
+	dot: DotNode,
 } satisfies NodeTypes;
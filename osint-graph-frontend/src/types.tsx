@@ -7,6 +7,8 @@ export interface Project {
 	last_updated?: Date;
 	tags: string[];
 	description?: string;
+	pinned?: boolean;
+	is_archived?: boolean;
 	// Add other fields as necessary
 }
 
@@ -21,6 +23,11 @@ export interface OSINTNode {
 	pos_x: number;
 	pos_y: number;
 	attachments: string[];
+	/** Subject of the user who first reported it, set by the server */
+	created_by?: string;
+	created?: string;
+	/** When it was moved to the trash, set by the server */
+	deleted_at?: string | null;
 }
 
 export interface NodeLink {
@@ -29,6 +36,16 @@ export interface NodeLink {
 	right: string;
 	project_id: string;
 	linktype: "Omni" | "Directional";
+	weight?: number;
+	/** What the relationship means, eg "owns" */
+	label?: string;
+	/** RFC 3339, when the relationship started */
+	valid_from?: string;
+	/** RFC 3339, when the relationship ended */
+	valid_to?: string;
+	/** Subject of the user who added it, set by the server */
+	created_by?: string;
+	created?: string;
 }
 
 export interface Attachment {
@@ -38,6 +55,30 @@ export interface Attachment {
 	content_type: string;
 	size: number;
 	created: string;
+	codec?: "gzip" | "zstd";
+	sha256?: string;
+	media?: MediaInfo;
+	created_by?: string;
+	/** The content is shared with other attachments with the same hash */
+	shared_blob?: boolean;
+}
+
+/** One node's attachments, from the project attachment summary */
+export interface NodeAttachmentSummary {
+	node_id: string;
+	count: number;
+	/** Uncompressed bytes */
+	total_size: number;
+}
+
+export interface MediaInfo {
+	container: string;
+	duration_ms?: number;
+	width?: number;
+	height?: number;
+	codecs?: string[];
+	sample_rate?: number;
+	channels?: number;
 }
 
 export interface ProjectExport {
@@ -47,11 +88,13 @@ export interface ProjectExport {
 	exported_at: string;
 	version: string;
 	attachments: Attachment[];
+	redacted?: boolean;
 }
 
 export type SearchResultType =
 	| { Node: string } // NodeType as string
 	| "Project"
+	| "EmptyProject" // id is the project's, it has no nodes
 	| "Attachment";
 
 export interface SearchResult {
@@ -59,6 +102,34 @@ export interface SearchResult {
 	project_id: string;
 	title: string;
 	result_type: SearchResultType;
+	snippet?: string | null;
+	historical_at?: string | null; // set when an old value matched, when it was replaced
+}
+
+export type NodeShape =
+	| "rectangle"
+	| "rounded_rectangle"
+	| "ellipse"
+	| "diamond"
+	| "hexagon";
+
+/** What the server supports, from /api/v1/capabilities */
+export interface Capabilities {
+	version: string;
+	features: Record<string, boolean>;
+	/** null for unlimited */
+	limits: Record<string, number | null>;
+	export_formats: string[];
+	/** type of the links the server creates itself */
+	default_link_type: "Omni" | "Directional";
+	auth_mode: "oidc" | "none";
+	read_only: boolean;
+}
+
+export interface NodeTypeStyle {
+	color: string;
+	shape: NodeShape;
+	icon: string;
 }
 
 export const NodeTypeInfo: Record<
@@ -67,6 +138,8 @@ export const NodeTypeInfo: Record<
 		label: string;
 		defaultDisplay: string;
 		color: string;
+		shape?: NodeShape;
+		icon?: string;
 		syncedvalue?: boolean;
 	}
 > = {
@@ -122,3 +195,24 @@ export const getNodeColor = (nodeType: string): string => {
 	const typeInfo = NodeTypeInfo[nodeType] ?? { color: "#6b7280" };
 	return typeInfo.color;
 };
+
+/** Apply the server-configured styles on top of the built-in defaults */
+export const applyNodeTypeStyles = (
+	styles: Record<string, NodeTypeStyle>,
+): void => {
+	for (const [nodeType, style] of Object.entries(styles)) {
+		const info = NodeTypeInfo[nodeType];
+		if (info) {
+			info.color = style.color;
+			info.shape = style.shape;
+			info.icon = style.icon;
+		}
+	}
+};
+
+export interface PaginatedResponse<T> {
+	total_count: number;
+	page: number;
+	page_size: number;
+	items: T[];
+}
@@ -1,76 +1,83 @@
-// // TODO: do tests
-// pub struct TikTokUser {
-//     // pub id: String,
-//     pub username: String,
-//     // pub nickname: String,
-//     // pub avatar: String,
-//     // pub bio: String,
-//     // pub followers: i32,
-//     // pub following: i32,
-//     // pub likes: i32,
-//     // pub videos: i32,
-//     // pub verified: bool,
-//     // pub private: bool,
-//     // pub blocked: bool,
-//     // pub country: String,
-//     // pub region: String,
-//     // pub city: String,
-// }
+pub const USER_DETAIL_URL: &str = "https://www.tiktok.com/api/user/detail/";
 
-// impl TikTokUser {
-//     pub fn url(&self) -> String {
-//         let _url = "https://www.tiktok.com/api/user/detail/";
-//         let time = chrono::Utc::now().timestamp();
+pub struct TikTokUser {
+    // pub id: String,
+    pub username: String,
+    // pub nickname: String,
+    // pub avatar: String,
+    // pub bio: String,
+    // pub followers: i32,
+    // pub following: i32,
+    // pub likes: i32,
+    // pub videos: i32,
+    // pub verified: bool,
+    // pub private: bool,
+    // pub blocked: bool,
+    // pub country: String,
+    // pub region: String,
+    // pub city: String,
+}
 
-//         let _params = [
-//         ("WebIdLastTime", time.to_string().as_str()),
-//         ("aid", "1988"),
-//         ("app_language", "en"),
-//         ("app_name", "tiktok_web"),
-//         ("browser_language", "en-AU"),
-//         ("browser_name", "Mozilla"),
-//         ("browser_online", "true"),
-//         ("browser_platform", "MacIntel"),
-//         ("browser_version", "5.0%20%28Macintosh%3B%20Intel%20Mac%20OS%20X%2010_15_7%29%20AppleWebKit%2F605.1.1520%28KHTML%2C%20like%20Gecko%29%20Version%2F17.2.1%20Safari%2F605.1.15"),
-//         ("channel", "tiktok_web"),
-//         ("cookie_enabled", "true"),
-//         // ("device_id", "7333968473036801543"), // maybe make this a random number
-//         ("device_platform", "web_pc"),
-//         ("focus_state","true"),
-//         ("from_page", "user"),
-//         ("history_len", "2"),
-//         ("is_fullscreen", "false"),
-//         ("is_page_visible", "true"),
-//         ("language", "en"),
-//         ("os", "mac"),
-//         ("priority_region", "" ),
-//         ("referer", ""),
-//         ("region", "AU"),
-//         ("screen_height", "982"),
-//         ("screen_width", "1512"),
-//         // ("secUid", "MS4wLjABAAAAOGwQju9GJb4TERHHfJ3PBKI8IEzTqBEjHqJfxRg6oSwAaU3DcXKszCp3AaVZgeWs"),
-//         ("tz_name","Australia%2FBrisbane"),
-//         ("uniqueId",self.username.as_str()),
-//         ("webcast_language","en"),
-//         // ("msToken","bb6ArFE5IGo-0wiq7lU1_lcQz5VIu-W3CYxkKZrfpnzboORF4a3Q8oYcFWW_thMXMmJtLWgslROVFjaKlq-p7zGZTHgyBwshzWwOs-IAVNEk8L5v2vr9JkbRbKtN"),
-//         // ("X-Bogus","DFSzsIVO9HiANyWftq/GmU9WcBjT"),
-//         // ("_signature","_02B4Z6wo00001TTbkYgAAIDBNNuRizkvIPk02ZUAACj047"),
-//     ];
+impl TikTokUser {
+    /// The user detail API URL for this username, with the query string the web client sends
+    pub fn url(&self) -> String {
+        let time = chrono::Utc::now().timestamp().to_string();
 
-//         let _headers = [
-//             ("Accept", "*/*"),
-//             ("Accept-Language", "en-AU,en;q=0.9"),
-//             ("Cache-Control", "no-cache"),
-//             ("Pragma", "no-cache"),
-//             (
-//                 "referrer",
-//                 &format!("https://www.tiktok.com/@{}", self.username),
-//             ),
-//         ];
-//         let _method = "GET";
-//         todo!()
-//     }
-// }
+        let params = [
+            ("WebIdLastTime", time.as_str()),
+            ("aid", "1988"),
+            ("app_language", "en"),
+            ("app_name", "tiktok_web"),
+            ("browser_language", "en-AU"),
+            ("browser_name", "Mozilla"),
+            ("browser_online", "true"),
+            ("browser_platform", "MacIntel"),
+            ("browser_version", "5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.2.1 Safari/605.1.15"),
+            ("channel", "tiktok_web"),
+            ("cookie_enabled", "true"),
+            // ("device_id", "7333968473036801543"), // maybe make this a random number
+            ("device_platform", "web_pc"),
+            ("focus_state", "true"),
+            ("from_page", "user"),
+            ("history_len", "2"),
+            ("is_fullscreen", "false"),
+            ("is_page_visible", "true"),
+            ("language", "en"),
+            ("os", "mac"),
+            ("priority_region", ""),
+            ("referer", ""),
+            ("region", "AU"),
+            ("screen_height", "982"),
+            ("screen_width", "1512"),
+            // ("secUid", "MS4wLjABAAAAOGwQju9GJb4TERHHfJ3PBKI8IEzTqBEjHqJfxRg6oSwAaU3DcXKszCp3AaVZgeWs"),
+            ("tz_name", "Australia/Brisbane"),
+            ("uniqueId", self.username.as_str()),
+            ("webcast_language", "en"),
+            // ("msToken","bb6ArFE5IGo-0wiq7lU1_lcQz5VIu-W3CYxkKZrfpnzboORF4a3Q8oYcFWW_thMXMmJtLWgslROVFjaKlq-p7zGZTHgyBwshzWwOs-IAVNEk8L5v2vr9JkbRbKtN"),
+            // ("X-Bogus","DFSzsIVO9HiANyWftq/GmU9WcBjT"),
+            // ("_signature","_02B4Z6wo00001TTbkYgAAIDBNNuRizkvIPk02ZUAACj047"),
+        ];
+        let query = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(params)
+            .finish();
+
+        format!("{USER_DETAIL_URL}?{query}")
+    }
+
+    /// Headers to send with a GET of [Self::url]
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Accept", "*/*".to_string()),
+            ("Accept-Language", "en-AU,en;q=0.9".to_string()),
+            ("Cache-Control", "no-cache".to_string()),
+            ("Pragma", "no-cache".to_string()),
+            (
+                "referrer",
+                format!("https://www.tiktok.com/@{}", self.username),
+            ),
+        ]
+    }
+}
 
 // // {
 // //     "cache": "default",
@@ -82,3 +89,28 @@
 // //     "referrer": "https://www.tiktok.com/@username?_t=8jleV3SJSs6&_r=1",
 // //     "referrerPolicy": "strict-origin-when-cross-origin"
 // // })
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url() {
+        let user = TikTokUser {
+            username: "some.user_1".to_string(),
+        };
+        let url = user.url();
+        assert!(url.starts_with("https://www.tiktok.com/api/user/detail/?"));
+        assert!(url.contains("&uniqueId=some.user_1&"), "{url}");
+        assert!(url.contains("&tz_name=Australia%2FBrisbane&"), "{url}");
+        assert!(!url.contains(' '));
+
+        let user = TikTokUser {
+            username: "a&b=c".to_string(),
+        };
+        assert!(user.url().contains("&uniqueId=a%26b%3Dc&"));
+        assert!(user
+            .headers()
+            .contains(&("referrer", "https://www.tiktok.com/@a&b=c".to_string())));
+    }
+}
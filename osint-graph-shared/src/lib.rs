@@ -10,6 +10,7 @@ pub mod data;
 pub mod error;
 pub mod node;
 pub mod nodelink;
+pub mod policy;
 pub mod storage;
 
 pub struct AddrInfo {
@@ -96,16 +97,81 @@ mod tests {
         let _ = AddrInfo::from_env();
         let _ = AddrInfo::test();
     }
+
+    #[test]
+    fn test_tags() {
+        let tags = StringVec(vec![
+            " abc ".to_string(),
+            "ABC".to_string(),
+            "x y".to_string(),
+        ]);
+        assert!(tags.has_tag("Abc"));
+        assert!(!tags.has_tag("ab"));
+        assert!(!tags.has_tag("\"abc\""));
+        assert_eq!(
+            tags.normalized_tags().unwrap(),
+            StringVec(vec!["abc".to_string(), "x y".to_string()])
+        );
+
+        assert!(StringVec(vec![" ".to_string()]).normalized_tags().is_err());
+        assert!(StringVec(vec!["a".repeat(MAX_TAG_CHARS + 1)])
+            .normalized_tags()
+            .is_err());
+        let many = StringVec((0..=MAX_TAGS).map(|i| i.to_string()).collect());
+        assert!(many.normalized_tags().is_err());
+        // nested arrays aren't tags
+        assert!(serde_json::from_str::<StringVec>(r#"[["abc"]]"#).is_err());
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, FromJsonQueryResult, ToSchema)]
 #[schema(value_type = Vec<String>)]
 pub struct StringVec(pub Vec<String>);
 
+/// Most tags a project can have
+pub const MAX_TAGS: usize = 50;
+/// Longest tag, in characters
+pub const MAX_TAG_CHARS: usize = 64;
+
 impl StringVec {
     pub fn empty() -> Self {
         Self(Vec::new())
     }
+
+    /// How tags are compared, trimmed and case-insensitive
+    pub fn normalize_tag(tag: &str) -> String {
+        tag.trim().to_lowercase()
+    }
+
+    /// Whether any tag is `tag`, whole rather than as a substring
+    pub fn has_tag(&self, tag: &str) -> bool {
+        let tag = Self::normalize_tag(tag);
+        self.0.iter().any(|t| Self::normalize_tag(t) == tag)
+    }
+
+    /// Trim tags and drop repeats, refusing empty or overlong tags and too many of them
+    pub fn normalized_tags(&self) -> Result<Self, String> {
+        let mut seen = std::collections::HashSet::new();
+        let mut res = Vec::new();
+        for tag in &self.0 {
+            let trimmed = tag.trim();
+            if trimmed.is_empty() {
+                return Err("Tags can't be empty".to_string());
+            }
+            if trimmed.chars().count() > MAX_TAG_CHARS {
+                return Err(format!(
+                    "Tag {trimmed:?} is longer than {MAX_TAG_CHARS} characters"
+                ));
+            }
+            if seen.insert(Self::normalize_tag(trimmed)) {
+                res.push(trimmed.to_string());
+            }
+        }
+        if res.len() > MAX_TAGS {
+            return Err(format!("More than {MAX_TAGS} tags"));
+        }
+        Ok(Self(res))
+    }
 }
 
 impl Default for StringVec {
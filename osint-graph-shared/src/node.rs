@@ -1,7 +1,7 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{collections::HashMap, net::IpAddr, str::FromStr};
 
 use chrono::{DateTime, Utc};
-use sea_orm::{DeriveValueType, EnumIter};
+use sea_orm::{DeriveValueType, EnumIter, Iterable};
 use serde::{Deserialize, Serialize};
 use sqlx::{Decode, Encode, FromRow};
 
@@ -79,7 +79,19 @@ impl NodeUpdateList {
 }
 
 #[derive(
-    Debug, Copy, Clone, Eq, PartialEq, EnumIter, Serialize, Deserialize, ToSchema, DeriveValueType,
+    Debug,
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    Hash,
+    PartialOrd,
+    Ord,
+    EnumIter,
+    Serialize,
+    Deserialize,
+    ToSchema,
+    DeriveValueType,
 )]
 #[sea_orm(value_type = "String")]
 #[serde(rename_all = "lowercase")]
@@ -115,6 +127,48 @@ impl AsRef<str> for NodeType {
     }
 }
 
+impl NodeType {
+    /// Check a value looks like this type of node, only emails, IPs, URLs and domains are checked
+    ///
+    /// Values are trimmed first. The error says what the value should have been.
+    pub fn validate_value(&self, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        let (what, valid) = match self {
+            NodeType::Email => (
+                "email address",
+                value.split_once('@').is_some_and(|(local, domain)| {
+                    !local.is_empty() && !domain.contains('@') && hostname_validates(domain)
+                }),
+            ),
+            NodeType::Ip => ("IP address", value.parse::<IpAddr>().is_ok()),
+            NodeType::Url => (
+                "URL",
+                url::Url::parse(value).is_ok_and(|url| {
+                    matches!(url.scheme(), "http" | "https") && url.host().is_some()
+                }),
+            ),
+            NodeType::Domain => ("domain name", hostname_validates(value)),
+            _ => return Ok(()),
+        };
+        match valid {
+            true => Ok(()),
+            false => Err(format!("Invalid {what} for node type {self}")),
+        }
+    }
+}
+
+/// At least two dot-separated labels of letters, digits and inner hyphens, so IDNs pass as typed
+fn hostname_validates(hostname: &str) -> bool {
+    let hostname = hostname.trim_end_matches('.');
+    hostname.contains('.')
+        && hostname.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        })
+}
+
 impl std::fmt::Display for NodeType {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{}", self.as_ref())
@@ -137,7 +191,14 @@ impl TryFrom<&str> for NodeType {
             "organisation" => Ok(NodeType::Organisation),
             "document" => Ok(NodeType::Document),
             "currency" => Ok(NodeType::Currency),
-            _ => Err(format!("Unknown NodeType: {}", value)),
+            _ => Err(format!(
+                "Unknown NodeType: {}, expected one of {}",
+                value,
+                NodeType::iter()
+                    .map(|node_type| node_type.as_ref().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
         }
     }
 }
@@ -163,6 +224,33 @@ mod tests {
     use super::*;
     use chrono::{TimeZone, Utc};
 
+    #[test]
+    fn test_node_type_validate_value() {
+        for (node_type, value, valid) in [
+            (NodeType::Email, "user@example.com", true),
+            (NodeType::Email, "not an email", false),
+            (NodeType::Ip, " 192.0.2.1 ", true),
+            (NodeType::Ip, "garbage", false),
+            (NodeType::Url, "https://example.com/", true),
+            (NodeType::Url, "example.com", false),
+            (NodeType::Domain, "bücher.de", true),
+            (NodeType::Domain, "-bad-.example.com", false),
+            (NodeType::Person, "not an email", true),
+            (NodeType::Document, "", true),
+            (NodeType::Currency, "💰", true),
+        ] {
+            assert_eq!(
+                node_type.validate_value(value).is_ok(),
+                valid,
+                "{node_type} {value:?}"
+            );
+        }
+        assert_eq!(
+            NodeType::Ip.validate_value("garbage"),
+            Err("Invalid IP address for node type ip".to_string())
+        );
+    }
+
     #[test]
     fn test_node_position_creation() {
         let pos = NodePosition { x: 100, y: 200 };
@@ -12,6 +12,7 @@ use utoipa::ToSchema;
     Clone,
     Eq,
     PartialEq,
+    Hash,
     EnumIter,
     DeriveActiveEnum,
     ToSchema,
@@ -26,3 +27,33 @@ pub enum LinkType {
     Omni,
     Directional,
 }
+
+impl AsRef<str> for LinkType {
+    fn as_ref(&self) -> &str {
+        match self {
+            LinkType::Omni => "omni",
+            LinkType::Directional => "directional",
+        }
+    }
+}
+
+impl std::str::FromStr for LinkType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use sea_orm::Iterable;
+
+        LinkType::iter()
+            .find(|link_type| link_type.as_ref().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| {
+                format!(
+                    "Unknown LinkType: {}, expected one of {}",
+                    s,
+                    LinkType::iter()
+                        .map(|link_type| link_type.as_ref().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+    }
+}
@@ -0,0 +1,256 @@
+//! Value policy rules
+//!
+//! Some kinds of data must never be stored, eg full card numbers. A [ValuePolicy] is a list of
+//! named regex rules, each of which rejects, masks or just warns about matching text. The backend
+//! enforces them on every write, and they're kept here so the frontend can check values before
+//! sending them.
+
+use std::ops::Range;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{error::OsintError, node::NodeType};
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyAction {
+    /// Refuse to store the node
+    Reject,
+    /// Store the node with the middle of each match replaced by `*`
+    Mask,
+    /// Store the node untouched, but log and count the match
+    Warn,
+}
+
+impl std::str::FromStr for PolicyAction {
+    type Err = OsintError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(PolicyAction::Reject),
+            "mask" => Ok(PolicyAction::Mask),
+            "warn" => Ok(PolicyAction::Warn),
+            other => Err(OsintError::Configuration(format!(
+                "Unknown value policy action {other:?}, expected reject, mask or warn"
+            ))),
+        }
+    }
+}
+
+/// The node fields policy rules are checked against
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyField {
+    Display,
+    Value,
+    Notes,
+}
+
+impl PolicyField {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PolicyField::Display => "display",
+            PolicyField::Value => "value",
+            PolicyField::Notes => "notes",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ValueRule {
+    pub name: String,
+    pub pattern: Regex,
+    /// Node types the rule applies to, every type if empty
+    pub node_types: Vec<NodeType>,
+    pub action: PolicyAction,
+    /// Only count matches whose digits pass the Luhn check, for card numbers
+    pub luhn: bool,
+}
+
+impl ValueRule {
+    pub fn new(
+        name: &str,
+        pattern: &str,
+        node_types: Vec<NodeType>,
+        action: PolicyAction,
+        luhn: bool,
+    ) -> Result<Self, OsintError> {
+        let pattern = Regex::new(pattern).map_err(|err| {
+            OsintError::Configuration(format!(
+                "Invalid pattern for value policy rule {name}: {err}"
+            ))
+        })?;
+        Ok(Self {
+            name: name.to_string(),
+            pattern,
+            node_types,
+            action,
+            luhn,
+        })
+    }
+
+    pub fn applies_to(&self, node_type: NodeType) -> bool {
+        self.node_types.is_empty() || self.node_types.contains(&node_type)
+    }
+
+    /// Byte ranges of `text` which break this rule
+    pub fn find_matches(&self, text: &str) -> Vec<Range<usize>> {
+        self.pattern
+            .find_iter(text)
+            .filter(|found| !self.luhn || luhn_valid(found.as_str()))
+            .map(|found| found.range())
+            .collect()
+    }
+}
+
+/// Whether the digits in `value` pass the Luhn checksum, ignoring spaces and dashes
+pub fn luhn_valid(value: &str) -> bool {
+    let digits: Vec<u32> = value.chars().filter_map(|c| c.to_digit(10)).collect();
+    if digits.len() < 2 {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, digit)| match index % 2 {
+            1 if *digit * 2 > 9 => *digit * 2 - 9,
+            1 => *digit * 2,
+            _ => *digit,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// Replace the middle of `value` with `*`, keeping up to four characters at each end
+pub fn mask_middle(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    let keep = (chars.len() / 4).min(4);
+    chars
+        .iter()
+        .enumerate()
+        .map(
+            |(index, c)| match index < keep || index >= chars.len() - keep {
+                true => *c,
+                false => '*',
+            },
+        )
+        .collect()
+}
+
+/// Mask every match of `rule` in `text`
+pub fn mask_matches(rule: &ValueRule, text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut last = 0;
+    for range in rule.find_matches(text) {
+        res.push_str(&text[last..range.start]);
+        res.push_str(&mask_middle(&text[range.clone()]));
+        last = range.end;
+    }
+    res.push_str(&text[last..]);
+    res
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ValuePolicy {
+    pub rules: Vec<ValueRule>,
+}
+
+impl ValuePolicy {
+    /// Indexes of the rules which `text` in a node of `node_type` breaks, in rule order
+    pub fn matching_rules(&self, node_type: NodeType, text: &str) -> Vec<usize> {
+        self.rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| rule.applies_to(node_type) && !rule.find_matches(text).is_empty())
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card_rule(action: PolicyAction) -> ValueRule {
+        ValueRule::new(
+            "credit_card",
+            r"\b(?:\d[ -]?){12,18}\d\b",
+            Vec::new(),
+            action,
+            true,
+        )
+        .expect("valid rule")
+    }
+
+    #[test]
+    fn test_luhn_valid() {
+        assert!(luhn_valid("4111111111111111"));
+        assert!(luhn_valid("4111 1111 1111 1111"));
+        assert!(luhn_valid("5500-0000-0000-0004"));
+        assert!(!luhn_valid("4111111111111112"));
+        assert!(!luhn_valid("7"));
+        assert!(!luhn_valid(""));
+    }
+
+    #[test]
+    fn test_mask_middle() {
+        assert_eq!(mask_middle("4111111111111111"), "4111********1111");
+        assert_eq!(mask_middle("12345678"), "12****78");
+        assert_eq!(mask_middle("abc"), "***");
+        assert_eq!(mask_middle(""), "");
+    }
+
+    #[test]
+    fn test_find_matches() {
+        let rule = card_rule(PolicyAction::Reject);
+        assert_eq!(
+            rule.find_matches("card 4111 1111 1111 1111 on file").len(),
+            1
+        );
+        // the right shape but a bad checksum, like an order number
+        assert!(rule.find_matches("order 4111111111111112").is_empty());
+        assert!(rule.find_matches("no numbers here").is_empty());
+    }
+
+    #[test]
+    fn test_mask_matches() {
+        let rule = card_rule(PolicyAction::Mask);
+        assert_eq!(
+            mask_matches(&rule, "paid with 4111111111111111, twice"),
+            "paid with 4111********1111, twice"
+        );
+        assert_eq!(mask_matches(&rule, "nothing to hide"), "nothing to hide");
+    }
+
+    #[test]
+    fn test_matching_rules() {
+        let policy = ValuePolicy {
+            rules: vec![
+                card_rule(PolicyAction::Reject),
+                ValueRule::new(
+                    "tfn",
+                    r"\b\d{3} ?\d{3} ?\d{3}\b",
+                    vec![NodeType::Person],
+                    PolicyAction::Warn,
+                    false,
+                )
+                .expect("valid rule"),
+            ],
+        };
+        assert_eq!(
+            policy.matching_rules(NodeType::Person, "TFN 123 456 789"),
+            vec![1]
+        );
+        assert!(policy
+            .matching_rules(NodeType::Document, "TFN 123 456 789")
+            .is_empty());
+        assert_eq!(
+            policy.matching_rules(NodeType::Document, "4111111111111111"),
+            vec![0]
+        );
+        assert!(ValueRule::new("broken", "(", Vec::new(), PolicyAction::Warn, false).is_err());
+    }
+}